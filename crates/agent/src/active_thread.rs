@@ -1,4 +1,4 @@
-use crate::context::{AgentContextHandle, RULES_ICON};
+use crate::context::{AgentContextHandle, RULES_ICON, RetrievedChunk};
 use crate::context_picker::{ContextPicker, MentionLink};
 use crate::context_store::ContextStore;
 use crate::context_strip::{ContextStrip, ContextStripEvent, SuggestContextKind};
@@ -24,14 +24,15 @@ use editor::{Editor, EditorElement, EditorEvent, EditorStyle, MultiBuffer};
 use gpui::{
     AbsoluteLength, Animation, AnimationExt, AnyElement, App, ClickEvent, ClipboardEntry,
     ClipboardItem, DefiniteLength, EdgesRefinement, Empty, Entity, EventEmitter, Focusable, Hsla,
-    ListAlignment, ListState, MouseButton, PlatformDisplay, ScrollHandle, Stateful,
+    ListAlignment, ListOffset, ListState, MouseButton, PlatformDisplay, ScrollHandle, Stateful,
     StyleRefinement, Subscription, Task, TextStyle, TextStyleRefinement, Transformation,
     UnderlineStyle, WeakEntity, WindowHandle, linear_color_stop, linear_gradient, list, percentage,
     pulsating_between,
 };
 use language::{Buffer, Language, LanguageRegistry};
 use language_model::{
-    LanguageModelRequestMessage, LanguageModelToolUseId, MessageContent, Role, StopReason,
+    LanguageModelRequestMessage, LanguageModelToolUseId, MessageContent, RequestPriority, Role,
+    StopReason,
 };
 use markdown::parser::{CodeBlockKind, CodeBlockMetadata};
 use markdown::{
@@ -1026,6 +1027,14 @@ impl ActiveThread {
                     cx,
                 );
             }
+            ThreadEvent::TimeBudgetReached => {
+                self.show_notification(
+                    "Out of time, summarizing progress.",
+                    IconName::Warning,
+                    window,
+                    cx,
+                );
+            }
             ThreadEvent::StreamedAssistantText(message_id, text) => {
                 if let Some(rendered_message) = self.rendered_messages_by_id.get_mut(&message_id) {
                     rendered_message.append_text(text, cx);
@@ -1461,6 +1470,9 @@ impl ActiveThread {
                             &configured_model.model,
                             cx,
                         ),
+                        seed: None,
+                        priority: RequestPriority::Interactive,
+                        web_search: false,
                     };
 
                     Some(configured_model.model.count_tokens(request, cx))
@@ -1657,19 +1669,13 @@ impl ActiveThread {
         })
         .detach_and_log_err(cx);
 
-        match feedback {
-            ThreadFeedback::Positive => {
-                self.open_feedback_editors.remove(&message_id);
-            }
-            ThreadFeedback::Negative => {
-                self.handle_show_feedback_comments(message_id, window, cx);
-            }
-        }
+        self.handle_show_feedback_comments(message_id, feedback, window, cx);
     }
 
     fn handle_show_feedback_comments(
         &mut self,
         message_id: MessageId,
+        feedback: ThreadFeedback,
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
@@ -1687,7 +1693,12 @@ impl ActiveThread {
                 cx,
             );
             editor.set_placeholder_text(
-                "What went wrong? Share your feedback so we can improve.",
+                match feedback {
+                    ThreadFeedback::Positive => "What went well? (optional)",
+                    ThreadFeedback::Negative => {
+                        "What went wrong? Share your feedback so we can improve."
+                    }
+                },
                 cx,
             );
             editor
@@ -1703,14 +1714,13 @@ impl ActiveThread {
             return;
         };
 
-        let report_task = self.thread.update(cx, |thread, cx| {
-            thread.report_message_feedback(message_id, ThreadFeedback::Negative, cx)
-        });
-
         let comments = editor.read(cx).text(cx);
         if !comments.is_empty() {
             let thread_id = self.thread.read(cx).id().clone();
             let comments_value = String::from(comments.as_str());
+            let report_task = self.thread.update(cx, |thread, cx| {
+                thread.report_feedback_reason(message_id, comments, cx)
+            });
 
             let message_content = self
                 .thread
@@ -1797,6 +1807,7 @@ impl ActiveThread {
         }
 
         let message_creases = message.creases.clone();
+        let substitution = message.substitution.clone();
 
         let Some(rendered_message) = self.rendered_messages_by_id.get(&message_id) else {
             return Empty.into_any();
@@ -1813,6 +1824,7 @@ impl ActiveThread {
             .map(|context| AddedContext::new_attached(context, configured_model.as_ref(), cx))
             .collect::<Vec<_>>();
 
+        let retrieved_chunks = message.loaded_context.retrieved_chunks.clone();
         let tool_uses = thread.tool_uses_for_message(message_id, cx);
         let has_tool_uses = !tool_uses.is_empty();
         let is_generating = thread.is_generating();
@@ -1967,7 +1979,8 @@ impl ActiveThread {
         };
 
         let message_is_empty = message.should_display_content();
-        let has_content = !message_is_empty || !added_context.is_empty();
+        let has_content =
+            !message_is_empty || !added_context.is_empty() || !retrieved_chunks.is_empty();
 
         let message_content = has_content.then(|| {
             if let Some(state) = editing_message_state.as_ref() {
@@ -1977,24 +1990,39 @@ impl ActiveThread {
                 v_flex()
                     .w_full()
                     .gap_1()
-                    .when(!added_context.is_empty(), |parent| {
-                        parent.child(h_flex().flex_wrap().gap_1().children(
-                            added_context.into_iter().map(|added_context| {
-                                let context = added_context.handle.clone();
-                                ContextPill::added(added_context, false, false, None).on_click(
-                                    Rc::new(cx.listener({
-                                        let workspace = workspace.clone();
-                                        move |_, _, window, cx| {
-                                            if let Some(workspace) = workspace.upgrade() {
-                                                open_context(&context, workspace, window, cx);
-                                                cx.notify();
-                                            }
-                                        }
-                                    })),
-                                )
-                            }),
-                        ))
-                    })
+                    .when(
+                        !added_context.is_empty() || !retrieved_chunks.is_empty(),
+                        |parent| {
+                            parent.child(
+                                h_flex()
+                                    .flex_wrap()
+                                    .gap_1()
+                                    .children(added_context.into_iter().map(|added_context| {
+                                        let context = added_context.handle.clone();
+                                        ContextPill::added(added_context, false, false, None)
+                                            .on_click(Rc::new(cx.listener({
+                                                let workspace = workspace.clone();
+                                                move |_, _, window, cx| {
+                                                    if let Some(workspace) = workspace.upgrade() {
+                                                        open_context(
+                                                            &context, workspace, window, cx,
+                                                        );
+                                                        cx.notify();
+                                                    }
+                                                }
+                                            })))
+                                    }))
+                                    .when(!retrieved_chunks.is_empty(), |parent| {
+                                        parent.child(self.render_retrieved_chunks_chip(
+                                            ix,
+                                            retrieved_chunks,
+                                            workspace.clone(),
+                                            cx,
+                                        ))
+                                    }),
+                            )
+                        },
+                    )
                     .when(!message_is_empty, |parent| {
                         parent.child(div().pt_0p5().min_h_6().child(self.render_message_content(
                             message_id,
@@ -2128,6 +2156,33 @@ impl ActiveThread {
                 .id(("message-container", ix))
                 .px(RESPONSE_PADDING_X)
                 .gap_2()
+                .children(substitution.map(|substitution| {
+                    h_flex()
+                        .id(("substitution", ix))
+                        .gap_1()
+                        .child(
+                            Icon::new(IconName::ArrowRightLeft)
+                                .size(IconSize::XSmall)
+                                .color(Color::Hint),
+                        )
+                        .child(
+                            Label::new(substitution.substitute_model.clone())
+                                .size(LabelSize::XSmall)
+                                .color(Color::Hint),
+                        )
+                        .tooltip(move |window, cx| {
+                            Tooltip::with_meta(
+                                format!("Switched from {}", substitution.original_model),
+                                None,
+                                format!(
+                                    "Used a free model because the {}",
+                                    substitution.reason.label()
+                                ),
+                                window,
+                                cx,
+                            )
+                        })
+                }))
                 .children(message_content)
                 .when(has_tool_uses, |parent| {
                     parent.children(tool_uses.into_iter().map(|tool_use| {
@@ -2333,6 +2388,34 @@ impl ActiveThread {
             .into_any()
     }
 
+    fn render_retrieved_chunks_chip(
+        &self,
+        ix: usize,
+        retrieved_chunks: Vec<RetrievedChunk>,
+        workspace: WeakEntity<Workspace>,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let label = if retrieved_chunks.len() == 1 {
+            "1 chunk auto-attached".to_string()
+        } else {
+            format!("{} chunks auto-attached", retrieved_chunks.len())
+        };
+        Button::new(("retrieved-chunks", ix), label)
+            .icon(IconName::SearchCode)
+            .icon_size(IconSize::XSmall)
+            .icon_color(Color::Muted)
+            .icon_position(IconPosition::Start)
+            .label_size(LabelSize::Small)
+            .style(ButtonStyle::Filled)
+            .tooltip(Tooltip::text("Show the code auto-context attached"))
+            .on_click(cx.listener(move |_, _, window, cx| {
+                if let Some(workspace) = workspace.upgrade() {
+                    open_retrieved_chunks(retrieved_chunks.clone(), workspace, window, cx)
+                        .detach_and_log_err(cx);
+                }
+            }))
+    }
+
     fn render_message_content(
         &self,
         message_id: MessageId,
@@ -3465,6 +3548,14 @@ impl ActiveThread {
         self.list_state.reset(self.messages.len());
         cx.notify();
     }
+
+    pub fn scroll_position(&self) -> ListOffset {
+        self.list_state.logical_scroll_top()
+    }
+
+    pub fn set_scroll_position(&self, scroll_top: ListOffset) {
+        self.list_state.scroll_to(scroll_top);
+    }
 }
 
 pub enum ActiveThreadEvent {
@@ -3553,6 +3644,61 @@ pub(crate) fn open_active_thread_as_markdown(
     })
 }
 
+/// Opens the chunks auto-context attached to a message in a new buffer, so
+/// they can be inspected.
+fn open_retrieved_chunks(
+    retrieved_chunks: Vec<RetrievedChunk>,
+    workspace: Entity<Workspace>,
+    window: &mut Window,
+    cx: &mut App,
+) -> Task<anyhow::Result<()>> {
+    let markdown_language_task = workspace
+        .read(cx)
+        .app_state()
+        .languages
+        .language_for_name("Markdown");
+
+    window.spawn(cx, async move |cx| {
+        let markdown_language = markdown_language_task.await?;
+
+        workspace.update_in(cx, |workspace, window, cx| {
+            let title = "Auto-Context";
+            let mut markdown = String::new();
+            for chunk in &retrieved_chunks {
+                markdown.push_str(&chunk.to_string());
+                markdown.push('\n');
+            }
+
+            let project = workspace.project().clone();
+            if !project.read(cx).is_local() {
+                anyhow::bail!("failed to open auto-context chunks in remote project");
+            }
+
+            let buffer = project.update(cx, |project, cx| {
+                project.create_local_buffer(&markdown, Some(markdown_language), cx)
+            });
+            let buffer = cx.new(|cx| MultiBuffer::singleton(buffer, cx).with_title(title.into()));
+
+            workspace.add_item_to_active_pane(
+                Box::new(cx.new(|cx| {
+                    let mut editor =
+                        Editor::for_multibuffer(buffer, Some(project.clone()), window, cx);
+                    editor.set_read_only(true);
+                    editor.set_breadcrumb_header(title.into());
+                    editor
+                })),
+                None,
+                true,
+                window,
+                cx,
+            );
+
+            anyhow::Ok(())
+        })??;
+        anyhow::Ok(())
+    })
+}
+
 pub(crate) fn open_context(
     context: &AgentContextHandle,
     workspace: Entity<Workspace>,
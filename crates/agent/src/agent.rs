@@ -1,31 +1,46 @@
 mod active_thread;
 mod agent_configuration;
 mod agent_diff;
+mod agent_hooks;
 mod agent_model_selector;
 mod agent_panel;
 mod agent_profile;
+mod auto_context;
 mod buffer_codegen;
 mod context;
 mod context_picker;
+mod context_prefetch;
 mod context_server_configuration;
 mod context_server_tool;
 mod context_store;
 mod context_strip;
 mod debug;
+mod edit_verification;
+mod feedback_store;
+mod file_chunks;
 mod history_store;
+mod inline_assist_presets;
 mod inline_assistant;
 mod inline_prompt_editor;
+mod memory_recall;
 mod message_editor;
+mod model_recommendation;
+mod plan;
 mod profile_selector;
+mod quick_ask;
+mod repo_map;
+mod report_webhook;
 mod slash_command_settings;
 mod terminal_codegen;
 mod terminal_inline_assistant;
+mod terminal_remote;
 mod thread;
 mod thread_history;
 mod thread_store;
 mod tool_compatibility;
 mod tool_use;
 mod ui;
+mod workspace_trust;
 
 use std::sync::Arc;
 
@@ -49,9 +64,11 @@ pub use crate::active_thread::ActiveThread;
 use crate::agent_configuration::{AddContextServerModal, ManageProfilesModal};
 pub use crate::agent_panel::{AgentPanel, ConcreteAssistantPanelDelegate};
 pub use crate::context::{ContextLoadResult, LoadedContext};
+pub use crate::feedback_store::{FeedbackRecord, parse_feedback};
 pub use crate::inline_assistant::InlineAssistant;
+use crate::quick_ask::QuickAskModal;
 use crate::slash_command_settings::SlashCommandSettings;
-pub use crate::thread::{Message, MessageSegment, Thread, ThreadEvent};
+pub use crate::thread::{Message, MessageSegment, Thread, ThreadEvent, ThreadFeedback};
 pub use crate::thread_store::{SerializedThread, TextThreadStore, ThreadStore};
 pub use agent_diff::{AgentDiffPane, AgentDiffToolbar};
 pub use context_store::ContextStore;
@@ -75,6 +92,7 @@ actions!(
         ChatWithFollow,
         CycleNextInlineAssist,
         CyclePreviousInlineAssist,
+        ToggleInlineAssistPresets,
         FocusUp,
         FocusDown,
         FocusLeft,
@@ -82,6 +100,9 @@ actions!(
         RemoveFocusedContext,
         AcceptSuggestedContext,
         OpenActiveThreadAsMarkdown,
+        OpenTextThreadInSplit,
+        QuickAsk,
+        PromoteQuickAsk,
         OpenAgentDiff,
         Keep,
         Reject,
@@ -93,6 +114,7 @@ actions!(
         ContinueThread,
         ContinueWithBurnMode,
         ToggleBurnMode,
+        ExportFeedback,
     ]
 );
 
@@ -116,7 +138,14 @@ impl ManageProfiles {
     }
 }
 
-impl_actions!(agent, [NewThread, ManageProfiles]);
+/// Replaces the inline assistant's prompt with the preset that has the given
+/// title.
+#[derive(PartialEq, Clone, Default, Debug, Deserialize, JsonSchema)]
+pub struct InsertInlineAssistPreset {
+    pub title: String,
+}
+
+impl_actions!(agent, [NewThread, ManageProfiles, InsertInlineAssistPreset]);
 
 #[derive(Clone)]
 pub(crate) enum ModelUsageContext {
@@ -160,9 +189,13 @@ pub fn init(
         init_language_model_settings(cx);
     }
     assistant_slash_command::init(cx);
+    assistant_slash_commands::SnippetLibrary::init(cx);
     thread_store::init(cx);
     agent_panel::init(cx);
+    agent_hooks::init(cx);
+    workspace_trust::init(cx);
     context_server_configuration::init(language_registry, cx);
+    semantic_index::init(cx);
 
     register_slash_commands(cx);
     inline_assistant::init(
@@ -180,6 +213,7 @@ pub fn init(
     indexed_docs::init(cx);
     cx.observe_new(AddContextServerModal::register).detach();
     cx.observe_new(ManageProfilesModal::register).detach();
+    cx.observe_new(QuickAskModal::register).detach();
 }
 
 fn init_language_model_settings(cx: &mut App) {
@@ -249,12 +283,29 @@ fn register_slash_commands(cx: &mut App) {
     slash_command_registry
         .register_command(assistant_slash_commands::CargoWorkspaceSlashCommand, true);
     slash_command_registry.register_command(assistant_slash_commands::PromptSlashCommand, true);
+    slash_command_registry.register_command(assistant_slash_commands::SnippetSlashCommand, true);
     slash_command_registry.register_command(assistant_slash_commands::SelectionCommand, true);
     slash_command_registry.register_command(assistant_slash_commands::DefaultSlashCommand, false);
     slash_command_registry.register_command(assistant_slash_commands::NowSlashCommand, false);
     slash_command_registry
         .register_command(assistant_slash_commands::DiagnosticsSlashCommand, true);
     slash_command_registry.register_command(assistant_slash_commands::FetchSlashCommand, true);
+    slash_command_registry.register_command(assistant_slash_commands::PdfSlashCommand, true);
+    slash_command_registry.register_command(assistant_slash_commands::TodosSlashCommand, true);
+    slash_command_registry.register_command(assistant_slash_commands::DepsSlashCommand, true);
+    slash_command_registry.register_command(assistant_slash_commands::LogSlashCommand, true);
+    slash_command_registry.register_command(
+        assistant_slash_commands::IssueSlashCommand::new(
+            assistant_slash_commands::IssueKind::Issue,
+        ),
+        true,
+    );
+    slash_command_registry.register_command(
+        assistant_slash_commands::IssueSlashCommand::new(
+            assistant_slash_commands::IssueKind::PullRequest,
+        ),
+        true,
+    );
 
     cx.observe_flag::<assistant_slash_commands::StreamingExampleSlashCommandFeatureFlag, _>({
         let slash_command_registry = slash_command_registry.clone();
@@ -291,4 +342,17 @@ fn update_slash_commands_from_settings(cx: &mut App) {
         slash_command_registry
             .unregister_command(assistant_slash_commands::CargoWorkspaceSlashCommand);
     }
+
+    if semantic_index::SemanticIndexSettings::get_global(cx).enabled {
+        slash_command_registry.register_command(assistant_slash_commands::SearchSlashCommand, true);
+    } else {
+        slash_command_registry.unregister_command(assistant_slash_commands::SearchSlashCommand);
+    }
+
+    let issue_tracker_tokens = assistant_slash_commands::IssueTrackerTokens {
+        github: settings.issue_tracker.github_token.clone(),
+        gitlab: settings.issue_tracker.gitlab_token.clone(),
+        hosts: settings.issue_tracker.hosts.clone(),
+    };
+    cx.set_global(issue_tracker_tokens);
 }
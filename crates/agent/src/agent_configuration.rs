@@ -1,11 +1,15 @@
 mod add_context_server_modal;
 mod configure_context_server_modal;
+mod connection_test;
 mod manage_profiles_modal;
+mod onboarding_wizard;
 mod tool_picker;
 
 use std::{sync::Arc, time::Duration};
 
 use agent_settings::AgentSettings;
+use anyhow::anyhow;
+use assistant_context_editor::{TextThreadStorageUsage, TextThreadsDatabase};
 use assistant_tool::{ToolSource, ToolWorkingSet};
 use collections::HashMap;
 use context_server::ContextServerId;
@@ -14,7 +18,9 @@ use gpui::{
     Action, Animation, AnimationExt as _, AnyView, App, Entity, EventEmitter, FocusHandle,
     Focusable, ScrollHandle, Subscription, pulsating_between,
 };
-use language_model::{LanguageModelProvider, LanguageModelProviderId, LanguageModelRegistry};
+use language_model::{
+    LanguageModelProvider, LanguageModelProviderId, LanguageModelRegistry, SpendTracker,
+};
 use project::context_server_store::{ContextServerStatus, ContextServerStore};
 use settings::{Settings, update_settings_file};
 use ui::{
@@ -26,10 +32,18 @@ use zed_actions::ExtensionCategoryFilter;
 
 pub(crate) use add_context_server_modal::AddContextServerModal;
 pub(crate) use configure_context_server_modal::ConfigureContextServerModal;
+use connection_test::{ConnectionTestError, test_connection};
 pub(crate) use manage_profiles_modal::ManageProfilesModal;
+use onboarding_wizard::{OnboardingWizard, OnboardingWizardEvent};
 
 use crate::AddContextServer;
 
+enum ProviderConnectionTest {
+    Running,
+    Succeeded { model_name: SharedString },
+    Failed(ConnectionTestError),
+}
+
 pub struct AgentConfiguration {
     fs: Arc<dyn Fs>,
     focus_handle: FocusHandle,
@@ -37,7 +51,10 @@ pub struct AgentConfiguration {
     context_server_store: Entity<ContextServerStore>,
     expanded_context_server_tools: HashMap<ContextServerId, bool>,
     expanded_provider_configurations: HashMap<LanguageModelProviderId, bool>,
+    connection_tests: HashMap<LanguageModelProviderId, ProviderConnectionTest>,
     tools: Entity<ToolWorkingSet>,
+    onboarding_wizard: Option<(Entity<OnboardingWizard>, Subscription)>,
+    text_thread_storage: Option<TextThreadStorageUsage>,
     _registry_subscription: Subscription,
     scroll_handle: ScrollHandle,
     scrollbar_state: ScrollbarState,
@@ -73,6 +90,20 @@ impl AgentConfiguration {
         let scroll_handle = ScrollHandle::new();
         let scrollbar_state = ScrollbarState::new(scroll_handle.clone());
 
+        let onboarding_wizard = OnboardingWizard::should_show(cx).then(|| {
+            let wizard = cx.new(|cx| OnboardingWizard::new(fs.clone(), window, cx));
+            let subscription = cx.subscribe(&wizard, |this, _, event, cx| match event {
+                OnboardingWizardEvent::OpenSampleTextThread => {
+                    cx.emit(AssistantConfigurationEvent::OpenSampleTextThread)
+                }
+                OnboardingWizardEvent::Finished => {
+                    this.onboarding_wizard = None;
+                    cx.notify();
+                }
+            });
+            (wizard, subscription)
+        });
+
         let mut this = Self {
             fs,
             focus_handle,
@@ -80,12 +111,16 @@ impl AgentConfiguration {
             context_server_store,
             expanded_context_server_tools: HashMap::default(),
             expanded_provider_configurations: HashMap::default(),
+            connection_tests: HashMap::default(),
             tools,
+            onboarding_wizard,
+            text_thread_storage: None,
             _registry_subscription: registry_subscription,
             scroll_handle,
             scrollbar_state,
         };
         this.build_provider_configuration_views(window, cx);
+        this.load_text_thread_storage(cx);
         this
     }
 
@@ -99,6 +134,7 @@ impl AgentConfiguration {
     fn remove_provider_configuration_view(&mut self, provider_id: &LanguageModelProviderId) {
         self.configuration_views_by_provider.remove(provider_id);
         self.expanded_provider_configurations.remove(provider_id);
+        self.connection_tests.remove(provider_id);
     }
 
     fn add_provider_configuration_view(
@@ -111,6 +147,43 @@ impl AgentConfiguration {
         self.configuration_views_by_provider
             .insert(provider.id(), configuration_view);
     }
+
+    /// Sends a test request to the provider's model, preferring the default
+    /// model when it comes from this provider.
+    fn test_provider_connection(
+        &mut self,
+        provider: &Arc<dyn LanguageModelProvider>,
+        cx: &mut Context<Self>,
+    ) {
+        let model = LanguageModelRegistry::read_global(cx)
+            .default_model()
+            .filter(|default| default.provider.id() == provider.id())
+            .map(|default| default.model)
+            .or_else(|| provider.default_model(cx));
+        let Some(model) = model else {
+            return;
+        };
+
+        let provider_id = provider.id();
+        let model_name = model.name().0;
+        let test = test_connection(model, cx);
+        self.connection_tests
+            .insert(provider_id.clone(), ProviderConnectionTest::Running);
+        cx.spawn(async move |this, cx| {
+            let result = test.await;
+            this.update(cx, |this, cx| {
+                let test = match result {
+                    Ok(()) => ProviderConnectionTest::Succeeded { model_name },
+                    Err(error) => ProviderConnectionTest::Failed(error),
+                };
+                this.connection_tests.insert(provider_id, test);
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+        cx.notify();
+    }
 }
 
 impl Focusable for AgentConfiguration {
@@ -121,6 +194,7 @@ impl Focusable for AgentConfiguration {
 
 pub enum AssistantConfigurationEvent {
     NewThread(Arc<dyn LanguageModelProvider>),
+    OpenSampleTextThread,
 }
 
 impl EventEmitter<AssistantConfigurationEvent> for AgentConfiguration {}
@@ -144,6 +218,42 @@ impl AgentConfiguration {
             .copied()
             .unwrap_or(false);
 
+        let is_testing = matches!(
+            self.connection_tests.get(&provider.id()),
+            Some(ProviderConnectionTest::Running)
+        );
+        let connection_test_status = match self.connection_tests.get(&provider.id()) {
+            Some(ProviderConnectionTest::Running) => Some(
+                h_flex()
+                    .gap_1p5()
+                    .child(Indicator::dot().color(Color::Warning))
+                    .child(
+                        Label::new("Sending a test request…")
+                            .size(LabelSize::Small)
+                            .color(Color::Muted),
+                    )
+                    .into_any_element(),
+            ),
+            Some(ProviderConnectionTest::Succeeded { model_name }) => Some(
+                h_flex()
+                    .gap_1p5()
+                    .child(
+                        Icon::new(IconName::Check)
+                            .size(IconSize::Small)
+                            .color(Color::Success),
+                    )
+                    .child(
+                        Label::new(format!("{model_name} responded to a test request."))
+                            .size(LabelSize::Small),
+                    )
+                    .into_any_element(),
+            ),
+            Some(ProviderConnectionTest::Failed(error)) => {
+                Some(error.render(cx).into_any_element())
+            }
+            None => None,
+        };
+
         v_flex()
             .pt_3()
             .gap_1p5()
@@ -168,6 +278,28 @@ impl AgentConfiguration {
                     .child(
                         h_flex()
                             .gap_1()
+                            .when(provider.is_authenticated(cx), |parent| {
+                                parent.child(
+                                    Button::new(
+                                        SharedString::from(format!(
+                                            "test-connection-{provider_id}"
+                                        )),
+                                        "Test Connection",
+                                    )
+                                    .icon_position(IconPosition::Start)
+                                    .icon(IconName::Play)
+                                    .icon_size(IconSize::Small)
+                                    .layer(ElevationIndex::ModalSurface)
+                                    .label_size(LabelSize::Small)
+                                    .disabled(is_testing)
+                                    .on_click(cx.listener({
+                                        let provider = provider.clone();
+                                        move |this, _event, _window, cx| {
+                                            this.test_provider_connection(&provider, cx)
+                                        }
+                                    })),
+                                )
+                            })
                             .when(provider.is_authenticated(cx), |parent| {
                                 parent.child(
                                     Button::new(
@@ -212,6 +344,7 @@ impl AgentConfiguration {
                             ),
                     ),
             )
+            .children(connection_test_status)
             .when(is_expanded, |parent| match configuration_view {
                 Some(configuration_view) => parent.child(configuration_view),
                 None => parent.child(Label::new(format!(
@@ -365,6 +498,87 @@ impl AgentConfiguration {
             )
     }
 
+    fn load_text_thread_storage(&mut self, cx: &mut Context<Self>) {
+        let database = TextThreadsDatabase::global_future(cx);
+        cx.spawn(async move |this, cx| {
+            let database = database.await.map_err(|err| anyhow!(err))?;
+            let usage = database.storage_usage().await?;
+            this.update(cx, |this, cx| {
+                this.text_thread_storage = Some(usage);
+                cx.notify();
+            })
+        })
+        .detach_and_log_err(cx);
+    }
+
+    fn render_text_thread_storage(&mut self, _cx: &mut Context<Self>) -> impl IntoElement {
+        let usage = match self.text_thread_storage {
+            Some(usage) => format!(
+                "{} ({} text threads)",
+                util::size::format_file_size(usage.total_bytes, false),
+                usage.thread_count
+            ),
+            None => "Calculating…".to_string(),
+        };
+
+        h_flex()
+            .gap_4()
+            .justify_between()
+            .flex_wrap()
+            .child(
+                v_flex()
+                    .gap_0p5()
+                    .max_w_5_6()
+                    .child(Label::new("Text thread storage"))
+                    .child(
+                        Label::new(
+                            "Limit how many saved text threads are kept with the `text_thread_retention` setting.",
+                        )
+                        .color(Color::Muted),
+                    ),
+            )
+            .child(Label::new(usage).color(Color::Muted))
+    }
+
+    fn render_monthly_spend_limit(&mut self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let max_monthly_spend = AgentSettings::get_global(cx).max_monthly_spend?;
+        let current_spend = SpendTracker::current_spend(cx);
+        let unpriced_model = LanguageModelRegistry::read_global(cx)
+            .default_model()
+            .filter(|configured| configured.model.pricing().is_none())
+            .map(|configured| configured.model.name());
+
+        Some(
+            v_flex()
+                .gap_0p5()
+                .child(Label::new("Monthly spend limit"))
+                .child(
+                    Label::new(format!(
+                        "${current_spend:.2} of ${max_monthly_spend:.2} spent this month."
+                    ))
+                    .color(Color::Muted),
+                )
+                .when_some(unpriced_model, |this, model_name| {
+                    this.child(
+                        h_flex()
+                            .gap_1()
+                            .child(
+                                Icon::new(IconName::Warning)
+                                    .size(IconSize::Small)
+                                    .color(Color::Warning),
+                            )
+                            .child(
+                                Label::new(format!(
+                                    "{} has no known pricing, so its requests aren't counted or blocked by the limit.",
+                                    model_name.0
+                                ))
+                                .color(Color::Warning),
+                            ),
+                    )
+                }),
+        )
+    }
+
     fn render_general_settings_section(&mut self, cx: &mut Context<Self>) -> impl IntoElement {
         v_flex()
             .p(DynamicSpacing::Base16.rems(cx))
@@ -376,6 +590,8 @@ impl AgentConfiguration {
             .child(self.render_command_permission(cx))
             .child(self.render_single_file_review(cx))
             .child(self.render_sound_notification(cx))
+            .child(self.render_text_thread_storage(cx))
+            .children(self.render_monthly_spend_limit(cx))
     }
 
     fn render_context_servers_section(
@@ -669,9 +885,16 @@ impl Render for AgentConfiguration {
                     .track_scroll(&self.scroll_handle)
                     .size_full()
                     .overflow_y_scroll()
-                    .child(self.render_general_settings_section(cx))
-                    .child(self.render_context_servers_section(window, cx))
-                    .child(self.render_provider_configuration_section(cx)),
+                    .map(|this| match self.onboarding_wizard.as_ref() {
+                        Some((wizard, _)) => this
+                            .child(wizard.clone())
+                            .child(self.render_general_settings_section(cx))
+                            .child(self.render_context_servers_section(window, cx)),
+                        None => this
+                            .child(self.render_general_settings_section(cx))
+                            .child(self.render_context_servers_section(window, cx))
+                            .child(self.render_provider_configuration_section(cx)),
+                    }),
             )
             .child(
                 div()
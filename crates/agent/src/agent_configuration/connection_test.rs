@@ -0,0 +1,244 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use futures::StreamExt as _;
+use gpui::{App, AppContext as _, Task};
+use language_model::{
+    LanguageModel, LanguageModelError, LanguageModelRequest, LanguageModelRequestMessage, Role,
+};
+use ui::prelude::*;
+
+/// Sends the smallest possible request to the model, to check that the
+/// provider is configured correctly before it is used for real.
+pub(crate) fn test_connection(
+    model: Arc<dyn LanguageModel>,
+    cx: &App,
+) -> Task<Result<(), ConnectionTestError>> {
+    let request = LanguageModelRequest {
+        messages: vec![LanguageModelRequestMessage {
+            role: Role::User,
+            content: vec!["Reply with \"ok\".".into()],
+            cache: false,
+        }],
+        ..Default::default()
+    };
+    cx.spawn(async move |cx| {
+        let mut response = model
+            .stream_completion_text(request, cx)
+            .await
+            .map_err(ConnectionTestError::new)?;
+        // The first chunk of the response is enough to know that the request succeeded.
+        match response.stream.next().await {
+            Some(Ok(_)) => Ok(()),
+            Some(Err(error)) => Err(ConnectionTestError::new(error.into())),
+            None => Err(ConnectionTestError::new(anyhow!(
+                "The model returned an empty response."
+            ))),
+        }
+    })
+}
+
+/// The likely cause of a failed test request, guessed from the error that the
+/// provider returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ConnectionProblem {
+    InvalidApiKey,
+    WrongApiUrl,
+    ModelNotEnabled,
+    ClockSkew,
+    RateLimited,
+    QuotaExceeded,
+    Other,
+}
+
+impl ConnectionProblem {
+    fn diagnose(error: &anyhow::Error) -> Self {
+        match error.downcast_ref() {
+            Some(LanguageModelError::RateLimitExceeded { .. }) => return Self::RateLimited,
+            Some(LanguageModelError::QuotaExceeded { .. }) => return Self::QuotaExceeded,
+            Some(LanguageModelError::Network { .. }) => return Self::WrongApiUrl,
+            _ => {}
+        }
+
+        let message = format!("{error:#}").to_lowercase();
+        let mentions = |patterns: &[&str]| patterns.iter().any(|pattern| message.contains(pattern));
+        // Clock skew is reported as an authentication error by some providers,
+        // so it's checked for first.
+        if mentions(&[
+            "signature expired",
+            "request time too skewed",
+            "clock skew",
+            "invalidsignatureexception",
+            "signature not yet current",
+        ]) {
+            Self::ClockSkew
+        } else if mentions(&[
+            "insufficient_quota",
+            "credit balance",
+            "billing",
+            "payment required",
+            "402",
+        ]) {
+            Self::QuotaExceeded
+        } else if mentions(&["429", "rate limit", "too many requests"]) {
+            Self::RateLimited
+        } else if mentions(&[
+            "401",
+            "unauthorized",
+            "invalid api key",
+            "invalid x-api-key",
+            "incorrect api key",
+            "invalid_api_key",
+            "api key not valid",
+            "authentication_error",
+            "unrecognizedclientexception",
+        ]) {
+            Self::InvalidApiKey
+        } else if mentions(&[
+            "model_not_found",
+            "does not have access",
+            "do not have access",
+            "don't have access",
+            "not enabled",
+            "accessdeniedexception",
+            "403",
+        ]) || mentions(&["404"]) && mentions(&["model"])
+        {
+            Self::ModelNotEnabled
+        } else if mentions(&[
+            "error sending request",
+            "dns error",
+            "failed to lookup address",
+            "connection refused",
+            "tcp connect error",
+            "invalid uri",
+            "relative url",
+            "404",
+        ]) {
+            Self::WrongApiUrl
+        } else {
+            Self::Other
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::InvalidApiKey => "The API key was rejected.",
+            Self::WrongApiUrl => "The API couldn't be reached.",
+            Self::ModelNotEnabled => "Your account can't use this model.",
+            Self::ClockSkew => "The request was rejected because your clock is out of sync.",
+            Self::RateLimited => "The provider is rate limiting your requests.",
+            Self::QuotaExceeded => "Your account has run out of credits.",
+            Self::Other => "The test request failed.",
+        }
+    }
+
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            Self::InvalidApiKey => Some(
+                "Check that the key was copied completely and hasn't been revoked, then reset it and paste it again.",
+            ),
+            Self::WrongApiUrl => Some(
+                "Check the provider's `api_url` in your settings, and that you're connected to the network.",
+            ),
+            Self::ModelNotEnabled => Some(
+                "Enable access to the model for your organization or project in the provider's console, or choose another model.",
+            ),
+            Self::ClockSkew => Some("Sync your system clock with a time server and try again."),
+            Self::RateLimited => {
+                Some("Wait a moment and try again, or check the rate limits of your plan.")
+            }
+            Self::QuotaExceeded => {
+                Some("Add credits or a payment method to your account with the provider.")
+            }
+            Self::Other => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ConnectionTestError {
+    pub problem: ConnectionProblem,
+    pub message: SharedString,
+}
+
+impl ConnectionTestError {
+    fn new(error: anyhow::Error) -> Self {
+        Self {
+            problem: ConnectionProblem::diagnose(&error),
+            message: format!("{error:#}").into(),
+        }
+    }
+
+    pub fn render(&self, cx: &App) -> impl IntoElement + use<> {
+        h_flex()
+            .gap_1p5()
+            .items_start()
+            .child(
+                Icon::new(IconName::XCircle)
+                    .size(IconSize::Small)
+                    .color(Color::Error),
+            )
+            .child(
+                v_flex()
+                    .gap_0p5()
+                    .child(Label::new(self.problem.title()).size(LabelSize::Small))
+                    .children(self.problem.suggestion().map(|suggestion| {
+                        Label::new(suggestion)
+                            .size(LabelSize::Small)
+                            .color(Color::Muted)
+                    }))
+                    .child(
+                        Label::new(self.message.clone())
+                            .buffer_font(cx)
+                            .size(LabelSize::XSmall)
+                            .color(Color::Muted),
+                    ),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnose(message: &str) -> ConnectionProblem {
+        ConnectionProblem::diagnose(&anyhow!(message.to_string()))
+    }
+
+    #[test]
+    fn test_diagnose_connection_problems() {
+        assert_eq!(
+            diagnose("Failed to connect to OpenAI API: 401 Incorrect API key provided"),
+            ConnectionProblem::InvalidApiKey
+        );
+        assert_eq!(
+            diagnose("Failed to connect to API: 401 authentication_error invalid x-api-key"),
+            ConnectionProblem::InvalidApiKey
+        );
+        assert_eq!(
+            diagnose(
+                "Failed to connect to OpenAI API: 404 The model `gpt-4o` does not exist or you do not have access to it."
+            ),
+            ConnectionProblem::ModelNotEnabled
+        );
+        assert_eq!(
+            diagnose("error sending request for url (https://api.opnai.com/v1/chat/completions)"),
+            ConnectionProblem::WrongApiUrl
+        );
+        assert_eq!(
+            diagnose(
+                "InvalidSignatureException: Signature expired: 20250101T000000Z is now earlier"
+            ),
+            ConnectionProblem::ClockSkew
+        );
+        assert_eq!(
+            diagnose("Failed to connect to OpenAI API: 429 insufficient_quota"),
+            ConnectionProblem::QuotaExceeded
+        );
+        assert_eq!(
+            diagnose("something unexpected happened"),
+            ConnectionProblem::Other
+        );
+    }
+}
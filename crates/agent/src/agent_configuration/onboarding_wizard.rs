@@ -0,0 +1,598 @@
+use std::sync::Arc;
+
+use agent_settings::AgentSettings;
+use collections::HashMap;
+use db::kvp::Dismissable;
+use fs::Fs;
+use gpui::{AnyView, App, EventEmitter, Subscription, Task};
+use language_model::{
+    LanguageModel, LanguageModelProvider, LanguageModelProviderId, LanguageModelRegistry,
+};
+use settings::update_settings_file;
+use ui::{Disclosure, ElevationIndex, Indicator, prelude::*};
+
+use super::connection_test::{ConnectionTestError, test_connection};
+
+/// Providers that run models locally, and are detected rather than configured.
+const LOCAL_PROVIDER_IDS: [&str; 2] = ["ollama", "lmstudio"];
+
+const OPEN_ROUTER_PROVIDER_ID: &str = "openrouter";
+
+/// The order in which authenticated providers are considered when suggesting
+/// a default model. Providers that aren't listed come last.
+const PROVIDER_PREFERENCE: [&str; 11] = [
+    "zed.dev",
+    "anthropic",
+    "openai",
+    "google",
+    "copilot_chat",
+    "openrouter",
+    "mistral",
+    "deepseek",
+    "amazon-bedrock",
+    "ollama",
+    "lmstudio",
+];
+
+const MAX_FREE_MODELS: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum WizardStep {
+    Connect,
+    ChooseModel,
+    Done,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LocalRuntimeStatus {
+    Detecting,
+    Running,
+    NotRunning,
+}
+
+enum ConnectionTest {
+    Running,
+    Succeeded,
+    Failed(ConnectionTestError),
+}
+
+pub enum OnboardingWizardEvent {
+    OpenSampleTextThread,
+    Finished,
+}
+
+/// Walks a new user through connecting a language model: detecting local
+/// runtimes, configuring a provider, checking that it works with a real
+/// request and choosing the default model.
+pub struct OnboardingWizard {
+    fs: Arc<dyn Fs>,
+    step: WizardStep,
+    local_runtimes: HashMap<LanguageModelProviderId, LocalRuntimeStatus>,
+    configuration_views: HashMap<LanguageModelProviderId, AnyView>,
+    expanded_provider: Option<LanguageModelProviderId>,
+    selected_model: Option<Arc<dyn LanguageModel>>,
+    connection_test: Option<ConnectionTest>,
+    connection_test_task: Option<Task<()>>,
+    _registry_subscription: Subscription,
+}
+
+impl Dismissable for OnboardingWizard {
+    const KEY: &'static str = "dismissed-agent-onboarding-wizard";
+}
+
+impl OnboardingWizard {
+    /// Whether the wizard should replace the list of providers, because it
+    /// hasn't been completed and no provider has been configured yet.
+    pub fn should_show(cx: &App) -> bool {
+        !Self::dismissed()
+            && !LanguageModelRegistry::read_global(cx)
+                .providers()
+                .iter()
+                .any(|provider| provider.is_authenticated(cx))
+    }
+
+    pub fn new(fs: Arc<dyn Fs>, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let registry_subscription = cx.subscribe_in(
+            &LanguageModelRegistry::global(cx),
+            window,
+            |_, _, _: &language_model::Event, _, cx| cx.notify(),
+        );
+
+        let mut this = Self {
+            fs,
+            step: WizardStep::Connect,
+            local_runtimes: HashMap::default(),
+            configuration_views: HashMap::default(),
+            expanded_provider: None,
+            selected_model: None,
+            connection_test: None,
+            connection_test_task: None,
+            _registry_subscription: registry_subscription,
+        };
+        this.detect_local_runtimes(cx);
+        this
+    }
+
+    /// Local providers authenticate by listing the models of a running server.
+    fn detect_local_runtimes(&mut self, cx: &mut Context<Self>) {
+        for provider in LanguageModelRegistry::read_global(cx).providers() {
+            if !LOCAL_PROVIDER_IDS.contains(&provider.id().0.as_ref()) {
+                continue;
+            }
+            let provider_id = provider.id();
+            self.local_runtimes
+                .insert(provider_id.clone(), LocalRuntimeStatus::Detecting);
+            let authenticate = provider.authenticate(cx);
+            cx.spawn(async move |this, cx| {
+                let status = if authenticate.await.is_ok() {
+                    LocalRuntimeStatus::Running
+                } else {
+                    LocalRuntimeStatus::NotRunning
+                };
+                this.update(cx, |this, cx| {
+                    this.local_runtimes.insert(provider_id, status);
+                    cx.notify();
+                })
+                .ok();
+            })
+            .detach();
+        }
+    }
+
+    fn authenticated_providers(cx: &App) -> Vec<Arc<dyn LanguageModelProvider>> {
+        let mut providers = LanguageModelRegistry::read_global(cx)
+            .providers()
+            .into_iter()
+            .filter(|provider| provider.is_authenticated(cx))
+            .collect::<Vec<_>>();
+        providers.sort_by_key(|provider| provider_preference(&provider.id().0));
+        providers
+    }
+
+    fn free_open_router_models(cx: &App) -> Vec<Arc<dyn LanguageModel>> {
+        let Some(provider) = LanguageModelRegistry::read_global(cx)
+            .provider(&LanguageModelProviderId(OPEN_ROUTER_PROVIDER_ID.into()))
+        else {
+            return Vec::new();
+        };
+        provider
+            .provided_models(cx)
+            .into_iter()
+            .filter(|model| model.pricing().is_some_and(|pricing| pricing.is_free()))
+            .take(MAX_FREE_MODELS)
+            .collect()
+    }
+
+    /// The models offered in the second step: the default model of each
+    /// configured provider, followed by OpenRouter's free models.
+    fn model_options(cx: &App) -> Vec<Arc<dyn LanguageModel>> {
+        let mut models = Self::authenticated_providers(cx)
+            .iter()
+            .filter_map(|provider| provider.default_model(cx))
+            .collect::<Vec<_>>();
+        if LanguageModelRegistry::read_global(cx)
+            .provider(&LanguageModelProviderId(OPEN_ROUTER_PROVIDER_ID.into()))
+            .is_some_and(|provider| provider.is_authenticated(cx))
+        {
+            for model in Self::free_open_router_models(cx) {
+                if !models.iter().any(|option| option.id() == model.id()) {
+                    models.push(model);
+                }
+            }
+        }
+        models
+    }
+
+    fn toggle_provider(
+        &mut self,
+        provider: &Arc<dyn LanguageModelProvider>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let provider_id = provider.id();
+        if self.expanded_provider.as_ref() == Some(&provider_id) {
+            self.expanded_provider = None;
+        } else {
+            self.configuration_views
+                .entry(provider_id.clone())
+                .or_insert_with(|| provider.configuration_view(window, cx));
+            self.expanded_provider = Some(provider_id);
+        }
+        cx.notify();
+    }
+
+    fn continue_to_model(&mut self, cx: &mut Context<Self>) {
+        self.step = WizardStep::ChooseModel;
+        if let Some(model) = Self::model_options(cx).into_iter().next() {
+            self.select_model(model, cx);
+        }
+        cx.notify();
+    }
+
+    fn select_model(&mut self, model: Arc<dyn LanguageModel>, cx: &mut Context<Self>) {
+        self.selected_model = Some(model.clone());
+        self.connection_test = Some(ConnectionTest::Running);
+        let test = test_connection(model, cx);
+        self.connection_test_task = Some(cx.spawn(async move |this, cx| {
+            let result = test.await;
+            this.update(cx, |this, cx| {
+                this.connection_test = Some(match result {
+                    Ok(()) => ConnectionTest::Succeeded,
+                    Err(error) => ConnectionTest::Failed(error),
+                });
+                cx.notify();
+            })
+            .ok();
+        }));
+        cx.notify();
+    }
+
+    fn use_selected_model(&mut self, cx: &mut Context<Self>) {
+        let Some(model) = self.selected_model.clone() else {
+            return;
+        };
+        update_settings_file::<AgentSettings>(self.fs.clone(), cx, move |settings, _| {
+            settings.set_model(model)
+        });
+        self.step = WizardStep::Done;
+        cx.notify();
+    }
+
+    fn finish(&mut self, open_sample: bool, cx: &mut Context<Self>) {
+        Self::set_dismissed(true, cx);
+        if open_sample {
+            cx.emit(OnboardingWizardEvent::OpenSampleTextThread);
+        }
+        cx.emit(OnboardingWizardEvent::Finished);
+    }
+
+    fn render_step_header(
+        &self,
+        step: WizardStep,
+        title: &'static str,
+        description: &'static str,
+    ) -> impl IntoElement {
+        let (number, total) = match step {
+            WizardStep::Connect => (1, 3),
+            WizardStep::ChooseModel => (2, 3),
+            WizardStep::Done => (3, 3),
+        };
+        v_flex()
+            .gap_0p5()
+            .child(
+                Label::new(format!("Step {number} of {total}"))
+                    .size(LabelSize::Small)
+                    .color(Color::Muted),
+            )
+            .child(Headline::new(title))
+            .child(Label::new(description).color(Color::Muted))
+    }
+
+    fn render_provider_row(
+        &self,
+        provider: &Arc<dyn LanguageModelProvider>,
+        detail: Option<(Indicator, SharedString)>,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement + use<> {
+        let provider_id = provider.id();
+        let is_expanded = self.expanded_provider.as_ref() == Some(&provider_id);
+        let configuration_view = is_expanded
+            .then(|| self.configuration_views.get(&provider_id).cloned())
+            .flatten();
+
+        v_flex()
+            .gap_1p5()
+            .child(
+                h_flex()
+                    .justify_between()
+                    .child(
+                        h_flex()
+                            .gap_2()
+                            .child(
+                                Icon::new(provider.icon())
+                                    .size(IconSize::Small)
+                                    .color(Color::Muted),
+                            )
+                            .child(Label::new(provider.name().0.clone()))
+                            .when_some(detail, |this, (indicator, detail)| {
+                                this.child(indicator).child(
+                                    Label::new(detail)
+                                        .size(LabelSize::Small)
+                                        .color(Color::Muted),
+                                )
+                            })
+                            .when(provider.is_authenticated(cx), |this| {
+                                this.child(
+                                    Icon::new(IconName::Check)
+                                        .size(IconSize::Small)
+                                        .color(Color::Success),
+                                )
+                            }),
+                    )
+                    .child(
+                        Disclosure::new(
+                            SharedString::from(format!("wizard-provider-{}", provider_id.0)),
+                            is_expanded,
+                        )
+                        .opened_icon(IconName::ChevronUp)
+                        .closed_icon(IconName::ChevronDown)
+                        .on_click(cx.listener({
+                            let provider = provider.clone();
+                            move |this, _, window, cx| this.toggle_provider(&provider, window, cx)
+                        })),
+                    ),
+            )
+            .children(configuration_view)
+    }
+
+    fn render_connect_step(&mut self, cx: &mut Context<Self>) -> impl IntoElement {
+        let providers = LanguageModelRegistry::read_global(cx).providers();
+        let (local_providers, cloud_providers): (Vec<_>, Vec<_>) = providers
+            .into_iter()
+            .partition(|provider| LOCAL_PROVIDER_IDS.contains(&provider.id().0.as_ref()));
+        let open_router = cloud_providers
+            .iter()
+            .find(|provider| provider.id().0 == OPEN_ROUTER_PROVIDER_ID)
+            .cloned();
+        let free_model_count = Self::free_open_router_models(cx).len();
+        let has_provider = !Self::authenticated_providers(cx).is_empty();
+
+        v_flex()
+            .gap_4()
+            .child(self.render_step_header(
+                WizardStep::Connect,
+                "Connect a Language Model",
+                "Use a model running on this machine, or add an API key for a hosted provider.",
+            ))
+            .child(
+                v_flex()
+                    .gap_2()
+                    .child(
+                        Label::new("Local Models")
+                            .size(LabelSize::Small)
+                            .color(Color::Muted),
+                    )
+                    .children(local_providers.iter().map(|provider| {
+                        let detail = match self.local_runtimes.get(&provider.id()) {
+                            Some(LocalRuntimeStatus::Running) => {
+                                let count = provider.provided_models(cx).len();
+                                (
+                                    Indicator::dot().color(Color::Success),
+                                    format!("Running, {count} models installed").into(),
+                                )
+                            }
+                            Some(LocalRuntimeStatus::NotRunning) => {
+                                (Indicator::dot().color(Color::Muted), "Not running".into())
+                            }
+                            Some(LocalRuntimeStatus::Detecting) | None => {
+                                (Indicator::dot().color(Color::Warning), "Detecting…".into())
+                            }
+                        };
+                        self.render_provider_row(provider, Some(detail), cx)
+                    })),
+            )
+            .when_some(open_router, |this, provider| {
+                let detail = (free_model_count > 0).then(|| {
+                    (
+                        Indicator::dot().color(Color::Accent),
+                        format!("{free_model_count}+ free models").into(),
+                    )
+                });
+                this.child(
+                    v_flex()
+                        .gap_2()
+                        .child(
+                            Label::new("Free Models")
+                                .size(LabelSize::Small)
+                                .color(Color::Muted),
+                        )
+                        .child(self.render_provider_row(&provider, detail, cx)),
+                )
+            })
+            .child(
+                v_flex()
+                    .gap_2()
+                    .child(
+                        Label::new("Hosted Providers")
+                            .size(LabelSize::Small)
+                            .color(Color::Muted),
+                    )
+                    .children(
+                        cloud_providers
+                            .iter()
+                            .filter(|provider| provider.id().0 != OPEN_ROUTER_PROVIDER_ID)
+                            .map(|provider| self.render_provider_row(provider, None, cx)),
+                    ),
+            )
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(
+                        Button::new("skip-wizard", "Skip")
+                            .layer(ElevationIndex::ModalSurface)
+                            .on_click(cx.listener(|this, _, _, cx| this.finish(false, cx))),
+                    )
+                    .child(
+                        Button::new("continue-to-model", "Continue")
+                            .style(ButtonStyle::Filled)
+                            .layer(ElevationIndex::ModalSurface)
+                            .disabled(!has_provider)
+                            .on_click(cx.listener(|this, _, _, cx| this.continue_to_model(cx))),
+                    ),
+            )
+    }
+
+    fn render_choose_model_step(&mut self, cx: &mut Context<Self>) -> impl IntoElement {
+        let selected_id = self.selected_model.as_ref().map(|model| model.id());
+        let models = Self::model_options(cx);
+
+        let status = match &self.connection_test {
+            Some(ConnectionTest::Running) => Some(
+                h_flex()
+                    .gap_1p5()
+                    .child(Indicator::dot().color(Color::Warning))
+                    .child(Label::new("Sending a test request…").color(Color::Muted))
+                    .into_any_element(),
+            ),
+            Some(ConnectionTest::Succeeded) => Some(
+                h_flex()
+                    .gap_1p5()
+                    .child(
+                        Icon::new(IconName::Check)
+                            .size(IconSize::Small)
+                            .color(Color::Success),
+                    )
+                    .child(Label::new("The model responded to a test request."))
+                    .into_any_element(),
+            ),
+            Some(ConnectionTest::Failed(error)) => Some(error.render(cx).into_any_element()),
+            None => None,
+        };
+        let can_use = matches!(self.connection_test, Some(ConnectionTest::Succeeded));
+        let can_retry = matches!(self.connection_test, Some(ConnectionTest::Failed(_)));
+
+        v_flex()
+            .gap_4()
+            .child(self.render_step_header(
+                WizardStep::ChooseModel,
+                "Choose a Default Model",
+                "The agent uses this model unless you pick another one. It is checked with a short test request.",
+            ))
+            .child(
+                v_flex()
+                    .gap_1()
+                    .children(models.into_iter().enumerate().map(|(ix, model)| {
+                        let is_selected = selected_id.as_ref() == Some(&model.id());
+                        let is_free = model.pricing().is_some_and(|pricing| pricing.is_free());
+                        let label = if is_free {
+                            format!("{} (Free)", model.name().0)
+                        } else {
+                            model.name().0.to_string()
+                        };
+                        let icon = LanguageModelRegistry::read_global(cx)
+                            .provider(&model.provider_id())
+                            .map_or(IconName::ZedAssistant, |provider| provider.icon());
+                        Button::new(("wizard-model", ix), label)
+                            .full_width()
+                            .toggle_state(is_selected)
+                            .icon(icon)
+                            .icon_position(IconPosition::Start)
+                            .icon_size(IconSize::Small)
+                            .icon_color(Color::Muted)
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.select_model(model.clone(), cx)
+                            }))
+                    })),
+            )
+            .children(status)
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(
+                        Button::new("back-to-connect", "Back")
+                            .layer(ElevationIndex::ModalSurface)
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.step = WizardStep::Connect;
+                                cx.notify();
+                            })),
+                    )
+                    .when(can_retry, |this| {
+                        this.child(
+                            Button::new("retry-connection-test", "Try Again")
+                                .layer(ElevationIndex::ModalSurface)
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    if let Some(model) = this.selected_model.clone() {
+                                        this.select_model(model, cx);
+                                    }
+                                })),
+                        )
+                    })
+                    .child(
+                        Button::new("use-model", "Use This Model")
+                            .style(ButtonStyle::Filled)
+                            .layer(ElevationIndex::ModalSurface)
+                            .disabled(!can_use)
+                            .on_click(cx.listener(|this, _, _, cx| this.use_selected_model(cx))),
+                    ),
+            )
+    }
+
+    fn render_done_step(&mut self, cx: &mut Context<Self>) -> impl IntoElement {
+        let model_name = self
+            .selected_model
+            .as_ref()
+            .map(|model| model.name().0.clone())
+            .unwrap_or_default();
+
+        v_flex()
+            .gap_4()
+            .child(self.render_step_header(
+                WizardStep::Done,
+                "You're All Set",
+                "Try a text thread to see how slash commands add context to a conversation.",
+            ))
+            .child(Label::new(format!(
+                "{model_name} is now your default model."
+            )))
+            .child(
+                h_flex()
+                    .justify_end()
+                    .gap_2()
+                    .child(
+                        Button::new("close-wizard", "Close")
+                            .layer(ElevationIndex::ModalSurface)
+                            .on_click(cx.listener(|this, _, _, cx| this.finish(false, cx))),
+                    )
+                    .child(
+                        Button::new("open-sample-text-thread", "Open Sample Text Thread")
+                            .style(ButtonStyle::Filled)
+                            .layer(ElevationIndex::ModalSurface)
+                            .icon(IconName::Plus)
+                            .icon_size(IconSize::Small)
+                            .icon_position(IconPosition::Start)
+                            .on_click(cx.listener(|this, _, _, cx| this.finish(true, cx))),
+                    ),
+            )
+    }
+}
+
+impl EventEmitter<OnboardingWizardEvent> for OnboardingWizard {}
+
+impl Render for OnboardingWizard {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        v_flex()
+            .p(DynamicSpacing::Base16.rems(cx))
+            .pr(DynamicSpacing::Base20.rems(cx))
+            .border_b_1()
+            .border_color(cx.theme().colors().border)
+            .map(|this| match self.step {
+                WizardStep::Connect => this.child(self.render_connect_step(cx)),
+                WizardStep::ChooseModel => this.child(self.render_choose_model_step(cx)),
+                WizardStep::Done => this.child(self.render_done_step(cx)),
+            })
+    }
+}
+
+fn provider_preference(provider_id: &str) -> usize {
+    PROVIDER_PREFERENCE
+        .iter()
+        .position(|preferred| *preferred == provider_id)
+        .unwrap_or(PROVIDER_PREFERENCE.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_preference() {
+        let mut providers = vec!["ollama", "custom", "openai", "zed.dev", "openrouter"];
+        providers.sort_by_key(|provider| provider_preference(provider));
+        assert_eq!(
+            providers,
+            ["zed.dev", "openai", "openrouter", "ollama", "custom"]
+        );
+    }
+}
@@ -1378,6 +1378,8 @@ impl AgentDiff {
             | ThreadEvent::CheckpointChanged
             | ThreadEvent::ToolConfirmationNeeded
             | ThreadEvent::ToolUseLimitReached
+            | ThreadEvent::TimeBudgetReached
+            | ThreadEvent::PlanChanged
             | ThreadEvent::CancelEditing
             | ThreadEvent::ProfileChanged => {}
         }
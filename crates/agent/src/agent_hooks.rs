@@ -0,0 +1,363 @@
+use std::time::{Duration, Instant};
+
+use agent_settings::{AgentHookAction, AgentHookEvent, AgentSettings};
+use collections::HashSet;
+use gpui::{
+    App, AppContext as _, Context, DismissEvent, Entity, Subscription, Task, WeakEntity, Window,
+};
+use language_model::StopReason;
+use project::{
+    Project,
+    git_store::{GitStore, GitStoreEvent, RepositoryId},
+};
+use settings::Settings as _;
+use ui::prelude::*;
+use util::ResultExt as _;
+use workspace::{
+    Workspace,
+    notifications::{NotificationId, simple_message_notification::MessageNotification},
+};
+use zed_llm_client::CompletionIntent;
+
+use crate::AgentPanel;
+use crate::context::ContextLoadResult;
+use crate::thread::{Thread, ThreadEvent};
+
+/// The placeholder in a hook's prompt that is replaced with a description of the event.
+const DETAILS_PLACEHOLDER: &str = "$ZED_HOOK_DETAILS";
+
+/// How long after a save diagnostics changes are attributed to it.
+const DIAGNOSTICS_SAVE_WINDOW: Duration = Duration::from_secs(10);
+
+/// How long to wait for language servers to settle after diagnostics change.
+const DIAGNOSTICS_DEBOUNCE: Duration = Duration::from_secs(1);
+
+pub(crate) fn init(cx: &mut App) {
+    cx.observe_new(|_: &mut Workspace, window, cx| {
+        let Some(window) = window else {
+            return;
+        };
+        let workspace = cx.entity();
+        let hooks = cx.new(|cx| AgentHooks::new(workspace, window, cx));
+        cx.on_release(move |_, _| drop(hooks)).detach();
+    })
+    .detach();
+}
+
+struct PendingSave {
+    error_count: usize,
+    saved_at: Instant,
+}
+
+/// Runs the hooks from the `agent.hooks` setting when the matching events
+/// happen in a workspace.
+struct AgentHooks {
+    workspace: WeakEntity<Workspace>,
+    project: Entity<Project>,
+    pending_save: Option<PendingSave>,
+    check_diagnostics_task: Option<Task<()>>,
+    conflicted_repositories: HashSet<RepositoryId>,
+    background_threads: Vec<(Entity<Thread>, Subscription)>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl AgentHooks {
+    fn new(workspace: Entity<Workspace>, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let project = workspace.read(cx).project().clone();
+        let git_store = project.read(cx).git_store().clone();
+        let subscriptions = vec![
+            cx.subscribe_in(&workspace, window, Self::handle_workspace_event),
+            cx.subscribe_in(&project, window, Self::handle_project_event),
+            cx.subscribe_in(&git_store, window, Self::handle_git_store_event),
+        ];
+        Self {
+            workspace: workspace.downgrade(),
+            project,
+            pending_save: None,
+            check_diagnostics_task: None,
+            conflicted_repositories: HashSet::default(),
+            background_threads: Vec::new(),
+            _subscriptions: subscriptions,
+        }
+    }
+
+    fn handle_workspace_event(
+        &mut self,
+        _: &Entity<Workspace>,
+        event: &workspace::Event,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        match event {
+            workspace::Event::TaskFinished {
+                label,
+                command_label,
+                success: false,
+                exit_code,
+            } => {
+                let status = exit_code
+                    .map(|code| format!("exit code {code}"))
+                    .unwrap_or_else(|| "no exit code".to_string());
+                let details =
+                    format!("The task \"{label}\" (`{command_label}`) failed with {status}.");
+                self.trigger(AgentHookEvent::TaskFailed, &details, window, cx);
+            }
+            workspace::Event::UserSavedItem { .. } => {
+                if self.has_hooks(AgentHookEvent::DiagnosticsIncreased, cx) {
+                    self.pending_save = Some(PendingSave {
+                        error_count: self.error_count(cx),
+                        saved_at: Instant::now(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_project_event(
+        &mut self,
+        _: &Entity<Project>,
+        event: &project::Event,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if !matches!(event, project::Event::DiagnosticsUpdated { .. }) {
+            return;
+        }
+        let Some(pending_save) = self.pending_save.as_ref() else {
+            return;
+        };
+        if pending_save.saved_at.elapsed() > DIAGNOSTICS_SAVE_WINDOW {
+            self.pending_save = None;
+            return;
+        }
+
+        self.check_diagnostics_task = Some(cx.spawn_in(window, async move |this, cx| {
+            cx.background_executor().timer(DIAGNOSTICS_DEBOUNCE).await;
+            this.update_in(cx, |this, window, cx| {
+                let Some(pending_save) = this.pending_save.as_ref() else {
+                    return;
+                };
+                let error_count = this.error_count(cx);
+                if error_count > pending_save.error_count {
+                    let details = format!(
+                        "The number of errors in the project went from {} to {} after saving.",
+                        pending_save.error_count, error_count
+                    );
+                    this.pending_save = None;
+                    this.trigger(AgentHookEvent::DiagnosticsIncreased, &details, window, cx);
+                }
+            })
+            .log_err();
+        }));
+    }
+
+    fn handle_git_store_event(
+        &mut self,
+        git_store: &Entity<GitStore>,
+        event: &GitStoreEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let GitStoreEvent::RepositoryUpdated(repository_id, _, _) = event else {
+            return;
+        };
+        let Some(repository) = git_store.read(cx).repositories().get(repository_id) else {
+            return;
+        };
+        let repository = repository.read(cx);
+        if repository.merge.conflicted_paths.is_empty() {
+            self.conflicted_repositories.remove(repository_id);
+            return;
+        }
+        // Only trigger once per merge, rather than on every update while conflicts remain.
+        if !self.conflicted_repositories.insert(*repository_id) {
+            return;
+        }
+
+        let paths = repository
+            .merge
+            .conflicted_paths
+            .iter()
+            .map(|path| format!("- {}", path.display()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let details = format!(
+            "The repository at {} has merge conflicts in:\n{paths}",
+            repository.work_directory_abs_path.display()
+        );
+        self.trigger(AgentHookEvent::MergeConflict, &details, window, cx);
+    }
+
+    fn has_hooks(&self, event: AgentHookEvent, cx: &App) -> bool {
+        AgentSettings::get_global(cx)
+            .hooks
+            .iter()
+            .any(|hook| hook.on == event)
+    }
+
+    fn error_count(&self, cx: &App) -> usize {
+        self.project
+            .read(cx)
+            .diagnostic_summary(false, cx)
+            .error_count
+    }
+
+    fn trigger(
+        &mut self,
+        event: AgentHookEvent,
+        details: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let settings = AgentSettings::get_global(cx);
+        if !settings.enabled {
+            return;
+        }
+        let hooks = settings
+            .hooks
+            .iter()
+            .filter(|hook| hook.on == event)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for hook in hooks {
+            let prompt = hook.prompt.replace(DETAILS_PLACEHOLDER, details);
+            match hook.action {
+                AgentHookAction::OpenPanel => self.open_panel(&prompt, window, cx),
+                AgentHookAction::Background => {
+                    self.run_in_background(prompt, hook.report, window, cx)
+                }
+            }
+        }
+    }
+
+    fn open_panel(&self, prompt: &str, window: &mut Window, cx: &mut Context<Self>) {
+        self.workspace
+            .update(cx, |workspace, cx| {
+                let Some(panel) = workspace.panel::<AgentPanel>(cx) else {
+                    return;
+                };
+                workspace.focus_panel::<AgentPanel>(window, cx);
+                panel.update(cx, |panel, cx| {
+                    panel.new_thread_with_message(prompt, window, cx);
+                });
+            })
+            .log_err();
+    }
+
+    fn run_in_background(
+        &mut self,
+        prompt: String,
+        report: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(panel) = self
+            .workspace
+            .upgrade()
+            .and_then(|workspace| workspace.read(cx).panel::<AgentPanel>(cx))
+        else {
+            return;
+        };
+        let thread = panel
+            .read(cx)
+            .thread_store()
+            .update(cx, |thread_store, cx| thread_store.create_thread(cx));
+        let window_handle = window.window_handle();
+        let sent = thread.update(cx, |thread, cx| {
+            let Some(configured_model) = thread.get_or_init_configured_model(cx) else {
+                return false;
+            };
+            // Nobody is watching the thread to review its changes, so it
+            // can only read the project.
+            thread.set_read_only(true);
+            thread.set_report_when_done(report);
+            thread.insert_user_message(prompt, ContextLoadResult::default(), None, Vec::new(), cx);
+            thread.advance_prompt_id();
+            thread.send_to_model(
+                configured_model.model,
+                CompletionIntent::UserPrompt,
+                Some(window_handle),
+                cx,
+            );
+            true
+        });
+        if !sent {
+            return;
+        }
+
+        let subscription = cx.subscribe_in(&thread, window, Self::handle_background_thread_event);
+        self.background_threads.push((thread, subscription));
+    }
+
+    fn handle_background_thread_event(
+        &mut self,
+        thread: &Entity<Thread>,
+        event: &ThreadEvent,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let message = match event {
+            ThreadEvent::Stopped(Ok(StopReason::ToolUse)) => return,
+            ThreadEvent::Stopped(Ok(_)) => "The agent finished a background analysis.",
+            ThreadEvent::Stopped(Err(_)) | ThreadEvent::ShowError(_) => {
+                "A background analysis by the agent failed."
+            }
+            ThreadEvent::ToolConfirmationNeeded => {
+                "A background analysis by the agent is waiting for confirmation."
+            }
+            _ => return,
+        };
+
+        if !matches!(event, ThreadEvent::ToolConfirmationNeeded) {
+            self.background_threads
+                .retain(|(background_thread, _)| background_thread != thread);
+        }
+
+        let Some(workspace) = self.workspace.upgrade() else {
+            return;
+        };
+        if let Some(panel) = workspace.read(cx).panel::<AgentPanel>(cx) {
+            panel
+                .read(cx)
+                .thread_store()
+                .update(cx, |thread_store, cx| thread_store.save_thread(thread, cx))
+                .detach_and_log_err(cx);
+        }
+
+        struct AgentHookNotification;
+
+        let thread = thread.clone();
+        let notification_id =
+            NotificationId::composite::<AgentHookNotification>(thread.read(cx).id().to_string());
+        workspace.update(cx, |workspace, cx| {
+            let weak_workspace = workspace.weak_handle();
+            workspace.show_notification(notification_id, cx, |cx| {
+                cx.new(move |cx| {
+                    MessageNotification::new(message, cx)
+                        .primary_message("View Thread")
+                        .primary_icon(IconName::ZedAssistant)
+                        .primary_on_click(move |window, cx| {
+                            // Once the user views the thread, they can review
+                            // its edits like in any other thread.
+                            thread.update(cx, |thread, _| thread.set_read_only(false));
+                            let thread = thread.clone();
+                            weak_workspace
+                                .update(cx, |workspace, cx| {
+                                    let Some(panel) = workspace.panel::<AgentPanel>(cx) else {
+                                        return;
+                                    };
+                                    workspace.focus_panel::<AgentPanel>(window, cx);
+                                    panel.update(cx, |panel, cx| {
+                                        panel.open_thread(thread, window, cx);
+                                    });
+                                })
+                                .log_err();
+                            cx.emit(DismissEvent);
+                        })
+                })
+            });
+        });
+    }
+}
@@ -8,7 +8,7 @@ use assistant_context_editor::language_model_selector::{
     LanguageModelSelector, ToggleModelSelector, language_model_selector,
 };
 use language_model::{ConfiguredModel, LanguageModelRegistry};
-use settings::update_settings_file;
+use settings::{Settings as _, update_settings_file};
 use std::sync::Arc;
 use ui::{PopoverMenuHandle, Tooltip, prelude::*};
 
@@ -35,6 +35,7 @@ impl AgentModelSelector {
                         let model_context = model_usage_context.clone();
                         move |cx| model_context.configured_model(cx)
                     },
+                    |cx| AgentSettings::get_global(cx).answer_language.clone(),
                     move |model, cx| {
                         let provider = model.provider_id().0.to_string();
                         let model_id = model.id().0.to_string();
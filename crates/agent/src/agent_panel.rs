@@ -1,5 +1,5 @@
 use std::ops::Range;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::Arc;
 use std::time::Duration;
@@ -11,8 +11,9 @@ use agent_settings::{AgentDockPosition, AgentSettings, CompletionMode, DefaultVi
 use anyhow::{Result, anyhow};
 use assistant_context_editor::{
     AgentPanelDelegate, AssistantContext, ConfigurationError, ContextEditor, ContextEvent,
-    ContextSummary, SlashCommandCompletionProvider, humanize_token_count,
-    make_lsp_adapter_delegate, render_remaining_tokens,
+    ContextStoreEvent as TextThreadStoreEvent, ContextSummary, MessageStatus,
+    SlashCommandCompletionProvider, humanize_token_count, make_lsp_adapter_delegate,
+    render_remaining_tokens, tr,
 };
 use assistant_slash_command::SlashCommandWorkingSet;
 use assistant_tool::ToolWorkingSet;
@@ -24,12 +25,14 @@ use fs::Fs;
 use gpui::{
     Action, Animation, AnimationExt as _, AnyElement, App, AsyncWindowContext, ClipboardItem,
     Corner, DismissEvent, Entity, EventEmitter, ExternalPaths, FocusHandle, Focusable, FontWeight,
-    KeyContext, Pixels, Subscription, Task, UpdateGlobal, WeakEntity, linear_color_stop,
-    linear_gradient, prelude::*, pulsating_between,
+    KeyContext, ListOffset, Pixels, Subscription, Task, UpdateGlobal, WeakEntity,
+    linear_color_stop, linear_gradient, point, prelude::*, pulsating_between,
 };
 use language::LanguageRegistry;
 use language_model::{
-    LanguageModelProviderTosView, LanguageModelRegistry, RequestUsage, ZED_CLOUD_PROVIDER_ID,
+    ConfiguredModel, LanguageModel, LanguageModelId, LanguageModelProviderId,
+    LanguageModelProviderTosView, LanguageModelRegistry, MaxMonthlySpendReachedError, RequestUsage,
+    Role, SelectedModel, ZED_CLOUD_PROVIDER_ID,
 };
 use project::{Project, ProjectPath, Worktree};
 use prompt_store::{PromptBuilder, PromptStore, UserPromptId};
@@ -48,6 +51,7 @@ use util::{ResultExt as _, maybe};
 use workspace::dock::{DockPosition, Panel, PanelEvent};
 use workspace::{
     CollaboratorId, DraggedSelection, DraggedTab, ToggleZoom, ToolbarItemView, Workspace,
+    notifications::{NotificationId, simple_message_notification::MessageNotification},
 };
 use zed_actions::agent::{OpenConfiguration, OpenOnboardingModal, ResetOnboarding};
 use zed_actions::assistant::{OpenRulesLibrary, ToggleFocus};
@@ -57,25 +61,51 @@ use zed_llm_client::{CompletionIntent, UsageLimit};
 use crate::active_thread::{self, ActiveThread, ActiveThreadEvent};
 use crate::agent_configuration::{AgentConfiguration, AssistantConfigurationEvent};
 use crate::agent_diff::AgentDiff;
+use crate::feedback_store;
 use crate::history_store::{HistoryStore, RecentEntry};
 use crate::message_editor::{MessageEditor, MessageEditorEvent};
+use crate::plan::PlanStepStatus;
 use crate::thread::{Thread, ThreadError, ThreadId, ThreadSummary, TokenUsageRatio};
 use crate::thread_history::{HistoryEntryElement, ThreadHistory};
 use crate::thread_store::ThreadStore;
 use crate::ui::AgentOnboardingModal;
+use crate::workspace_trust::WorkspaceTrust;
 use crate::{
     AddContextServer, AgentDiffPane, ContextStore, ContinueThread, ContinueWithBurnMode,
-    DeleteRecentlyOpenThread, ExpandMessageEditor, Follow, InlineAssistant, NewTextThread,
-    NewThread, OpenActiveThreadAsMarkdown, OpenAgentDiff, OpenHistory, ResetTrialEndUpsell,
-    ResetTrialUpsell, TextThreadStore, ThreadEvent, ToggleBurnMode, ToggleContextPicker,
-    ToggleNavigationMenu, ToggleOptionsMenu,
+    DeleteRecentlyOpenThread, ExpandMessageEditor, ExportFeedback, Follow, InlineAssistant,
+    NewTextThread, NewThread, OpenActiveThreadAsMarkdown, OpenAgentDiff, OpenHistory,
+    OpenTextThreadInSplit, ResetTrialEndUpsell, ResetTrialUpsell, TextThreadStore, ThreadEvent,
+    ToggleBurnMode, ToggleContextPicker, ToggleNavigationMenu, ToggleOptionsMenu,
 };
 
 const AGENT_PANEL_KEY: &str = "agent_panel";
 
+/// How much more time a run that ran out of time is given when continued.
+const EXTRA_RUN_TIME: Duration = Duration::from_secs(5 * 60);
+
 #[derive(Serialize, Deserialize)]
 struct SerializedAgentPanel {
     width: Option<Pixels>,
+    #[serde(default)]
+    height: Option<Pixels>,
+    #[serde(default)]
+    active_view: Option<SerializedActiveView>,
+}
+
+/// The thread or text thread that was open in the panel, along with how far
+/// it was scrolled. The panel's zoom is restored along with its dock.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SerializedActiveView {
+    Thread {
+        id: String,
+        scroll_top_item: usize,
+        scroll_offset_in_item: Pixels,
+    },
+    TextThread {
+        path: PathBuf,
+        scroll_top: f32,
+    },
 }
 
 pub fn init(cx: &mut App) {
@@ -103,7 +133,9 @@ pub fn init(cx: &mut App) {
                 .register_action(|workspace, _: &NewTextThread, window, cx| {
                     if let Some(panel) = workspace.panel::<AgentPanel>(cx) {
                         workspace.focus_panel::<AgentPanel>(window, cx);
-                        panel.update(cx, |panel, cx| panel.new_prompt_editor(window, cx));
+                        panel.update(cx, |panel, cx| {
+                            panel.new_prompt_editor(window, cx);
+                        });
                     }
                 })
                 .register_action(|workspace, action: &OpenRulesLibrary, window, cx| {
@@ -157,6 +189,9 @@ pub fn init(cx: &mut App) {
                     window.dispatch_action(workspace::RestoreBanner.boxed_clone(), cx);
                     window.refresh();
                 })
+                .register_action(|workspace, _: &ExportFeedback, _window, cx| {
+                    feedback_store::export(workspace.app_state().fs.clone(), cx);
+                })
                 .register_action(|_workspace, _: &ResetTrialUpsell, _window, cx| {
                     Upsell::set_dismissed(false, cx);
                 })
@@ -372,21 +407,111 @@ pub struct AgentPanel {
     zoomed: bool,
     pending_serialization: Option<Task<Result<()>>>,
     hide_upsell: bool,
+    split_context_editor: Option<Entity<ContextEditor>>,
 }
 
 impl AgentPanel {
     fn serialize(&mut self, cx: &mut Context<Self>) {
-        let width = self.width;
+        let serialized_panel = SerializedAgentPanel {
+            width: self.width,
+            height: self.height,
+            active_view: self.serialize_active_view(cx),
+        };
         self.pending_serialization = Some(cx.background_spawn(async move {
             KEY_VALUE_STORE
                 .write_kvp(
                     AGENT_PANEL_KEY.into(),
-                    serde_json::to_string(&SerializedAgentPanel { width })?,
+                    serde_json::to_string(&serialized_panel)?,
                 )
                 .await?;
             anyhow::Ok(())
         }));
     }
+
+    fn serialize_active_view(&self, cx: &App) -> Option<SerializedActiveView> {
+        // History and configuration are transient, so restore what they cover.
+        let view = match &self.active_view {
+            ActiveView::History | ActiveView::Configuration => self.previous_view.as_ref()?,
+            view => view,
+        };
+        match view {
+            ActiveView::Thread { thread, .. } => {
+                let thread = thread.upgrade()?;
+                if thread.read(cx).is_empty() {
+                    return None;
+                }
+                let scroll_top = self.thread.read(cx).scroll_position();
+                Some(SerializedActiveView::Thread {
+                    id: thread.read(cx).id().to_string(),
+                    scroll_top_item: scroll_top.item_ix,
+                    scroll_offset_in_item: scroll_top.offset_in_item,
+                })
+            }
+            ActiveView::TextThread { context_editor, .. } => {
+                let context_editor = context_editor.read(cx);
+                let path = context_editor.context().read(cx).path()?;
+                Some(SerializedActiveView::TextThread {
+                    path: path.to_path_buf(),
+                    scroll_top: context_editor.editor().read(cx).scroll_position(cx).y,
+                })
+            }
+            ActiveView::History | ActiveView::Configuration => None,
+        }
+    }
+
+    fn restore_active_view(
+        &mut self,
+        serialized_view: SerializedActiveView,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        match serialized_view {
+            SerializedActiveView::Thread {
+                id,
+                scroll_top_item,
+                scroll_offset_in_item,
+            } => {
+                let open_thread = self.open_thread_by_id(&ThreadId::from(id.as_str()), window, cx);
+                cx.spawn(async move |this, cx| {
+                    open_thread.await?;
+                    this.update(cx, |this, cx| {
+                        this.thread.read(cx).set_scroll_position(ListOffset {
+                            item_ix: scroll_top_item,
+                            offset_in_item: scroll_offset_in_item,
+                        });
+                    })
+                })
+                .detach_and_log_err(cx);
+            }
+            SerializedActiveView::TextThread { path, scroll_top } => {
+                let open_text_thread = self.open_saved_prompt_editor(path.into(), window, cx);
+                cx.spawn_in(window, async move |this, cx| {
+                    open_text_thread.await?;
+                    this.update_in(cx, |this, window, cx| {
+                        if let ActiveView::TextThread { context_editor, .. } = &this.active_view {
+                            let editor = context_editor.read(cx).editor().clone();
+                            editor.update(cx, |editor, cx| {
+                                editor.set_scroll_position(point(0., scroll_top), window, cx);
+                            });
+                        }
+                    })
+                })
+                .detach_and_log_err(cx);
+            }
+        }
+    }
+
+    fn on_app_quit(&mut self, cx: &mut Context<Self>) -> Task<()> {
+        // Scrolling doesn't serialize the panel, so capture where the active
+        // view is scrolled to before quitting.
+        self.serialize(cx);
+        let pending_serialization = self.pending_serialization.take();
+        cx.background_spawn(async move {
+            if let Some(pending_serialization) = pending_serialization {
+                pending_serialization.await.log_err();
+            }
+        })
+    }
     pub fn load(
         workspace: WeakEntity<Workspace>,
         prompt_builder: Arc<PromptBuilder>,
@@ -450,6 +575,10 @@ impl AgentPanel {
                 if let Some(serialized_panel) = serialized_panel {
                     panel.update(cx, |panel, cx| {
                         panel.width = serialized_panel.width.map(|w| w.round());
+                        panel.height = serialized_panel.height.map(|h| h.round());
+                        if let Some(active_view) = serialized_panel.active_view {
+                            panel.restore_active_view(active_view, window, cx);
+                        }
                         cx.notify();
                     });
                 }
@@ -549,9 +678,11 @@ impl AgentPanel {
         };
 
         let thread_subscription = cx.subscribe(&thread, |_, _, event, cx| {
-            if let ThreadEvent::MessageAdded(_) = &event {
+            match &event {
                 // needed to leave empty state
-                cx.notify();
+                ThreadEvent::MessageAdded(_) => cx.notify(),
+                ThreadEvent::PlanChanged => cx.notify(),
+                _ => {}
             }
         });
         let active_thread = cx.new(|cx| {
@@ -685,6 +816,9 @@ impl AgentPanel {
                 .ok();
         });
 
+        cx.subscribe(&context_store, Self::handle_text_thread_store_event)
+            .detach();
+
         let _default_model_subscription = cx.subscribe(
             &LanguageModelRegistry::global(cx),
             |this, _, event: &language_model::Event, cx| match event {
@@ -699,6 +833,8 @@ impl AgentPanel {
             },
         );
 
+        cx.on_app_quit(Self::on_app_quit).detach();
+
         Self {
             active_view,
             workspace,
@@ -736,6 +872,7 @@ impl AgentPanel {
             zoomed: false,
             pending_serialization: None,
             hide_upsell: false,
+            split_context_editor: None,
         }
     }
 
@@ -761,6 +898,65 @@ impl AgentPanel {
         &self.prompt_store
     }
 
+    fn handle_text_thread_store_event(
+        &mut self,
+        context_store: Entity<TextThreadStore>,
+        event: &TextThreadStoreEvent,
+        cx: &mut Context<Self>,
+    ) {
+        let TextThreadStoreEvent::GuestModelRequested {
+            user_id,
+            github_login,
+        } = event
+        else {
+            return;
+        };
+
+        struct GuestModelRequestNotification;
+
+        let user_id = *user_id;
+        let message = format!(
+            "{github_login} wants to use your language model to get responses in a shared text thread."
+        );
+        let context_store = context_store.downgrade();
+        self.workspace
+            .update(cx, |workspace, cx| {
+                workspace.show_notification(
+                    NotificationId::composite::<GuestModelRequestNotification>(user_id as usize),
+                    cx,
+                    |cx| {
+                        let respond = move |approved: bool, cx: &mut App| {
+                            context_store
+                                .update(cx, |context_store, _| {
+                                    context_store.respond_to_guest_model_request(user_id, approved)
+                                })
+                                .ok();
+                        };
+                        let respond = Rc::new(respond);
+                        cx.new(move |cx| {
+                            MessageNotification::new(message, cx)
+                                .primary_message("Allow")
+                                .primary_icon(IconName::Check)
+                                .primary_on_click({
+                                    let respond = respond.clone();
+                                    move |_, cx| {
+                                        respond(true, cx);
+                                        cx.emit(DismissEvent);
+                                    }
+                                })
+                                .secondary_message("Deny")
+                                .secondary_icon(IconName::Close)
+                                .secondary_on_click(move |_, cx| {
+                                    respond(false, cx);
+                                    cx.emit(DismissEvent);
+                                })
+                        })
+                    },
+                );
+            })
+            .log_err();
+    }
+
     pub(crate) fn inline_assist_context_store(
         &self,
     ) -> &Entity<crate::context_store::ContextStore> {
@@ -816,9 +1012,11 @@ impl AgentPanel {
         }
 
         let thread_subscription = cx.subscribe(&thread, |_, _, event, cx| {
-            if let ThreadEvent::MessageAdded(_) = &event {
+            match &event {
                 // needed to leave empty state
-                cx.notify();
+                ThreadEvent::MessageAdded(_) => cx.notify(),
+                ThreadEvent::PlanChanged => cx.notify(),
+                _ => {}
             }
         });
 
@@ -873,7 +1071,24 @@ impl AgentPanel {
         ];
     }
 
-    fn new_prompt_editor(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    /// Starts a new thread with the given message ready to be sent in the message editor.
+    pub(crate) fn new_thread_with_message(
+        &mut self,
+        message: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.new_thread(&NewThread::default(), window, cx);
+        self.message_editor.update(cx, |message_editor, cx| {
+            message_editor.set_text(message, window, cx);
+        });
+    }
+
+    fn new_prompt_editor(
+        &mut self,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Entity<ContextEditor> {
         let context = self
             .context_store
             .update(cx, |context_store, cx| context_store.create(cx));
@@ -906,6 +1121,7 @@ impl AgentPanel {
             cx,
         );
         context_editor.focus_handle(cx).focus(window);
+        context_editor
     }
 
     fn deploy_rules_library(
@@ -990,6 +1206,40 @@ impl AgentPanel {
         );
     }
 
+    /// Opens a new text thread that starts with the given question and answer,
+    /// as asked from the Quick Ask modal.
+    pub(crate) fn new_text_thread_from_exchange(
+        &mut self,
+        question: &str,
+        answer: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let context = self
+            .context_store
+            .update(cx, |context_store, cx| context_store.create(cx));
+        context.update(cx, |context, cx| {
+            let Some(question_id) = context.messages(cx).next().map(|message| message.id) else {
+                return;
+            };
+            context.buffer().update(cx, |buffer, cx| {
+                buffer.edit([(0..0, question)], None, cx);
+            });
+            let Some(answer_message) =
+                context.insert_message_after(question_id, Role::Assistant, MessageStatus::Done, cx)
+            else {
+                return;
+            };
+            context.buffer().update(cx, |buffer, cx| {
+                let end = buffer.len();
+                buffer.edit([(end..end, answer)], None, cx);
+            });
+            context.insert_message_after(answer_message.id, Role::User, MessageStatus::Done, cx);
+            context.summarize(false, cx);
+        });
+        self.open_prompt_editor(context, window, cx);
+    }
+
     pub(crate) fn open_thread_by_id(
         &mut self,
         thread_id: &ThreadId,
@@ -1024,9 +1274,11 @@ impl AgentPanel {
             )
         });
         let thread_subscription = cx.subscribe(&thread, |_, _, event, cx| {
-            if let ThreadEvent::MessageAdded(_) = &event {
+            match &event {
                 // needed to leave empty state
-                cx.notify();
+                ThreadEvent::MessageAdded(_) => cx.notify(),
+                ThreadEvent::PlanChanged => cx.notify(),
+                _ => {}
             }
         });
 
@@ -1251,6 +1503,55 @@ impl AgentPanel {
             .detach_and_log_err(cx);
     }
 
+    /// Shows the active text thread next to the panel's text thread, so that
+    /// another one can be opened in the panel and viewed side by side with it.
+    fn open_text_thread_in_split(
+        &mut self,
+        _: &OpenTextThreadInSplit,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let ActiveView::TextThread { context_editor, .. } = &self.active_view else {
+            return;
+        };
+        let context = context_editor.read(cx).context().clone();
+        self.open_context_in_split(context, window, cx);
+    }
+
+    fn open_context_in_split(
+        &mut self,
+        context: Entity<AssistantContext>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let lsp_adapter_delegate = make_lsp_adapter_delegate(&self.project.clone(), cx)
+            .log_err()
+            .flatten();
+        let editor = cx.new(|cx| {
+            ContextEditor::for_context(
+                context,
+                self.fs.clone(),
+                self.workspace.clone(),
+                self.project.clone(),
+                lsp_adapter_delegate,
+                window,
+                cx,
+            )
+        });
+        editor.focus_handle(cx).focus(window);
+        self.split_context_editor = Some(editor);
+        cx.notify();
+    }
+
+    fn close_split(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.split_context_editor.take().is_some() {
+            if let ActiveView::TextThread { context_editor, .. } = &self.active_view {
+                context_editor.focus_handle(cx).focus(window);
+            }
+            cx.notify();
+        }
+    }
+
     fn handle_agent_configuration_event(
         &mut self,
         _entity: &Entity<AgentConfiguration>,
@@ -1275,6 +1576,12 @@ impl AgentPanel {
 
                 self.new_thread(&NewThread::default(), window, cx);
             }
+            AssistantConfigurationEvent::OpenSampleTextThread => {
+                let context_editor = self.new_prompt_editor(window, cx);
+                context_editor.update(cx, |context_editor, cx| {
+                    context_editor.insert_sample_prompt(window, cx)
+                });
+            }
         }
     }
 
@@ -1323,6 +1630,31 @@ impl AgentPanel {
         }
     }
 
+    fn grant_more_time(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let thread_state = self.thread.read(cx).thread().read(cx);
+        if !thread_state.time_budget_reached() {
+            return;
+        }
+
+        let Some(model) = thread_state.configured_model().map(|cm| cm.model.clone()) else {
+            log::warn!("No configured model available for continuation");
+            return;
+        };
+        self.thread.update(cx, |active_thread, cx| {
+            active_thread.thread().update(cx, |thread, cx| {
+                thread.grant_more_time(EXTRA_RUN_TIME);
+                thread.insert_invisible_continue_message(cx);
+                thread.advance_prompt_id();
+                thread.send_to_model(
+                    model,
+                    CompletionIntent::UserPrompt,
+                    Some(window.window_handle()),
+                    cx,
+                );
+            });
+        });
+    }
+
     fn toggle_burn_mode(
         &mut self,
         _: &ToggleBurnMode,
@@ -1423,6 +1755,7 @@ impl AgentPanel {
             self.active_view = new_view;
         }
 
+        self.serialize(cx);
         self.focus_handle(cx).focus(window);
     }
 }
@@ -1497,7 +1830,32 @@ impl Panel for AgentPanel {
         cx.notify();
     }
 
-    fn set_active(&mut self, _active: bool, _window: &mut Window, _cx: &mut Context<Self>) {}
+    fn set_active(&mut self, active: bool, _window: &mut Window, cx: &mut Context<Self>) {
+        if !active || !AgentSettings::get_global(cx).warm_up_model_on_open {
+            return;
+        }
+
+        let registry = LanguageModelRegistry::read_global(cx);
+        let mut models = Vec::new();
+        for configured_model in [registry.default_model(), registry.inline_assistant_model()]
+            .into_iter()
+            .flatten()
+        {
+            if !models.iter().any(|model: &Arc<dyn LanguageModel>| {
+                model.provider_id() == configured_model.provider.id()
+                    && model.id() == configured_model.model.id()
+            }) {
+                models.push(configured_model.model);
+            }
+        }
+
+        cx.spawn(async move |_, cx| {
+            for model in models {
+                model.warm_up(cx).await.log_err();
+            }
+        })
+        .detach();
+    }
 
     fn remote_id() -> Option<proto::PanelId> {
         Some(proto::PanelId::AssistantPanel)
@@ -1574,11 +1932,9 @@ impl AgentPanel {
                                     }
                                 })
                                 .tooltip(move |_window, cx| {
-                                    cx.new(|_| {
-                                        Tooltip::new("Failed to generate title")
-                                            .meta("Click to try again")
-                                    })
-                                    .into()
+                                    let title = tr("Failed to generate title", cx);
+                                    let meta = tr("Click to try again", cx);
+                                    cx.new(|_| Tooltip::new(title).meta(meta)).into()
                                 }),
                         )
                         .into_any_element(),
@@ -1621,18 +1977,18 @@ impl AgentPanel {
                                     }
                                 })
                                 .tooltip(move |_window, cx| {
-                                    cx.new(|_| {
-                                        Tooltip::new("Failed to generate title")
-                                            .meta("Click to try again")
-                                    })
-                                    .into()
+                                    let title = tr("Failed to generate title", cx);
+                                    let meta = tr("Click to try again", cx);
+                                    cx.new(|_| Tooltip::new(title).meta(meta)).into()
                                 }),
                         )
                         .into_any_element(),
                 }
             }
-            ActiveView::History => Label::new("History").truncate().into_any_element(),
-            ActiveView::Configuration => Label::new("Settings").truncate().into_any_element(),
+            ActiveView::History => Label::new(tr("History", cx)).truncate().into_any_element(),
+            ActiveView::Configuration => {
+                Label::new(tr("Settings", cx)).truncate().into_any_element()
+            }
         };
 
         h_flex()
@@ -1692,7 +2048,7 @@ impl AgentPanel {
                     let focus_handle = focus_handle.clone();
                     move |window, cx| {
                         Tooltip::for_action_in(
-                            "Go Back",
+                            tr("Go Back", cx),
                             &workspace::GoBack,
                             &focus_handle,
                             window,
@@ -1712,7 +2068,7 @@ impl AgentPanel {
                         let focus_handle = focus_handle.clone();
                         move |window, cx| {
                             Tooltip::for_action_in(
-                                "Toggle Panel Menu",
+                                tr("Toggle Panel Menu", cx),
                                 &ToggleNavigationMenu,
                                 &focus_handle,
                                 window,
@@ -1738,6 +2094,7 @@ impl AgentPanel {
                 }),
         );
 
+        let is_text_thread = matches!(self.active_view, ActiveView::TextThread { .. });
         let zoom_in_label = if self.is_zoomed(window, cx) {
             "Zoom Out"
         } else {
@@ -1752,7 +2109,7 @@ impl AgentPanel {
                     let focus_handle = focus_handle.clone();
                     move |window, cx| {
                         Tooltip::for_action_in(
-                            "Toggle Agent Menu",
+                            tr("Toggle Agent Menu", cx),
                             &ToggleOptionsMenu,
                             &focus_handle,
                             window,
@@ -1776,6 +2133,9 @@ impl AgentPanel {
                                 }),
                             )
                         })
+                        .when(is_text_thread, |menu| {
+                            menu.action("Open in Split Pane", Box::new(OpenTextThreadInSplit))
+                        })
                         .separator();
 
                     menu = menu
@@ -1877,7 +2237,7 @@ impl AgentPanel {
                                     .style(ButtonStyle::Subtle)
                                     .tooltip(move |window, cx| {
                                         Tooltip::for_action_in(
-                                            "New Thread",
+                                            tr("New Thread", cx),
                                             &NewThread::default(),
                                             &focus_handle,
                                             window,
@@ -1951,7 +2311,7 @@ impl AgentPanel {
                             )
                             .tooltip(move |window, cx| {
                                 Tooltip::with_meta(
-                                    "Estimated New Token Count",
+                                    tr("Estimated New Token Count", cx),
                                     None,
                                     format!(
                                         "Current Conversation Tokens: {}",
@@ -2066,7 +2426,7 @@ impl AgentPanel {
     fn render_young_account_upsell(&self, cx: &mut Context<Self>) -> impl IntoElement {
         let checkbox = CheckboxWithLabel::new(
             "dont-show-again",
-            Label::new("Don't show again").color(Color::Muted),
+            Label::new(tr("Don't show again", cx)).color(Color::Muted),
             ToggleState::Unselected,
             move |toggle_state, _window, cx| {
                 let toggle_state_bool = toggle_state.selected();
@@ -2080,14 +2440,14 @@ impl AgentPanel {
             .gap_2()
             .flex()
             .flex_col()
-            .child(Headline::new("Build better with Zed Pro").size(HeadlineSize::Small))
+            .child(Headline::new(tr("Build better with Zed Pro", cx)).size(HeadlineSize::Small))
             .child(
-                Label::new("Your GitHub account was created less than 30 days ago, so we can't offer you a free trial.")
+                Label::new(tr("Your GitHub account was created less than 30 days ago, so we can't offer you a free trial.", cx))
                     .size(LabelSize::Small),
             )
             .child(
                 Label::new(
-                    "Use your own API keys, upgrade to Zed Pro or send an email to billing-support@zed.dev.",
+                    tr("Use your own API keys, upgrade to Zed Pro or send an email to billing-support@zed.dev.", cx),
                 )
                 .color(Color::Muted),
             )
@@ -2102,7 +2462,7 @@ impl AgentPanel {
                         h_flex()
                             .gap_2()
                             .child(
-                                Button::new("dismiss-button", "Not Now")
+                                Button::new("dismiss-button", tr("Not Now", cx))
                                     .style(ButtonStyle::Transparent)
                                     .color(Color::Muted)
                                     .on_click({
@@ -2116,7 +2476,7 @@ impl AgentPanel {
                                     }),
                             )
                             .child(
-                                Button::new("cta-button", "Upgrade to Zed Pro")
+                                Button::new("cta-button", tr("Upgrade to Zed Pro", cx))
                                     .style(ButtonStyle::Transparent)
                                     .on_click(|_, _, cx| cx.open_url(&zed_urls::account_url(cx))),
                             ),
@@ -2129,7 +2489,7 @@ impl AgentPanel {
     fn render_trial_upsell(&self, cx: &mut Context<Self>) -> impl IntoElement {
         let checkbox = CheckboxWithLabel::new(
             "dont-show-again",
-            Label::new("Don't show again").color(Color::Muted),
+            Label::new(tr("Don't show again", cx)).color(Color::Muted),
             ToggleState::Unselected,
             move |toggle_state, _window, cx| {
                 let toggle_state_bool = toggle_state.selected();
@@ -2143,15 +2503,19 @@ impl AgentPanel {
             .gap_2()
             .flex()
             .flex_col()
-            .child(Headline::new("Build better with Zed Pro").size(HeadlineSize::Small))
+            .child(Headline::new(tr("Build better with Zed Pro", cx)).size(HeadlineSize::Small))
             .child(
-                Label::new("Try Zed Pro for free for 14 days - no credit card required.")
-                    .size(LabelSize::Small),
+                Label::new(tr(
+                    "Try Zed Pro for free for 14 days - no credit card required.",
+                    cx,
+                ))
+                .size(LabelSize::Small),
             )
             .child(
-                Label::new(
+                Label::new(tr(
                     "Use your own API keys or enable usage-based billing once you hit the cap.",
-                )
+                    cx,
+                ))
                 .color(Color::Muted),
             )
             .child(
@@ -2165,7 +2529,7 @@ impl AgentPanel {
                         h_flex()
                             .gap_2()
                             .child(
-                                Button::new("dismiss-button", "Not Now")
+                                Button::new("dismiss-button", tr("Not Now", cx))
                                     .style(ButtonStyle::Transparent)
                                     .color(Color::Muted)
                                     .on_click({
@@ -2179,7 +2543,7 @@ impl AgentPanel {
                                     }),
                             )
                             .child(
-                                Button::new("cta-button", "Start Trial")
+                                Button::new("cta-button", tr("Start Trial", cx))
                                     .style(ButtonStyle::Transparent)
                                     .on_click(|_, _, cx| cx.open_url(&zed_urls::account_url(cx))),
                             ),
@@ -2207,10 +2571,11 @@ impl AgentPanel {
                     .flex()
                     .flex_col()
                     .child(
-                        Headline::new("Your Zed Pro trial has expired.").size(HeadlineSize::Small),
+                        Headline::new(tr("Your Zed Pro trial has expired.", cx))
+                            .size(HeadlineSize::Small),
                     )
                     .child(
-                        Label::new("You've been automatically reset to the free plan.")
+                        Label::new(tr("You've been automatically reset to the free plan.", cx))
                             .size(LabelSize::Small),
                     )
                     .child(
@@ -2224,7 +2589,7 @@ impl AgentPanel {
                                 h_flex()
                                     .gap_2()
                                     .child(
-                                        Button::new("dismiss-button", "Stay on Free")
+                                        Button::new("dismiss-button", tr("Stay on Free", cx))
                                             .style(ButtonStyle::Transparent)
                                             .color(Color::Muted)
                                             .on_click({
@@ -2238,7 +2603,7 @@ impl AgentPanel {
                                             }),
                                     )
                                     .child(
-                                        Button::new("cta-button", "Upgrade to Zed Pro")
+                                        Button::new("cta-button", tr("Upgrade to Zed Pro", cx))
                                             .style(ButtonStyle::Transparent)
                                             .on_click(|_, _, cx| {
                                                 cx.open_url(&zed_urls::account_url(cx))
@@ -2395,20 +2760,20 @@ impl AgentPanel {
                         .gap_1()
                         .child(
                             h_flex().child(
-                                Headline::new("Welcome to the Agent Panel")
+                                Headline::new(tr("Welcome to the Agent Panel", cx))
                             ),
                         )
                         .when(no_error, |parent| {
                             parent
                                 .child(
                                     h_flex().child(
-                                        Label::new("Ask and build anything.")
+                                        Label::new(tr("Ask and build anything.", cx))
                                             .color(Color::Muted)
                                             .mb_2p5(),
                                     ),
                                 )
                                 .child(
-                                    Button::new("new-thread", "Start New Thread")
+                                    Button::new("new-thread", tr("Start New Thread", cx))
                                         .icon(IconName::Plus)
                                         .icon_position(IconPosition::Start)
                                         .icon_size(IconSize::Small)
@@ -2425,7 +2790,7 @@ impl AgentPanel {
                                         }),
                                 )
                                 .child(
-                                    Button::new("context", "Add Context")
+                                    Button::new("context", tr("Add Context", cx))
                                         .icon(IconName::FileCode)
                                         .icon_position(IconPosition::Start)
                                         .icon_size(IconSize::Small)
@@ -2442,7 +2807,7 @@ impl AgentPanel {
                                         }),
                                 )
                                 .child(
-                                    Button::new("mode", "Switch Model")
+                                    Button::new("mode", tr("Switch Model", cx))
                                         .icon(IconName::DatabaseZap)
                                         .icon_position(IconPosition::Start)
                                         .icon_size(IconSize::Small)
@@ -2459,7 +2824,7 @@ impl AgentPanel {
                                         }),
                                 )
                                 .child(
-                                    Button::new("settings", "View Settings")
+                                    Button::new("settings", tr("View Settings", cx))
                                         .icon(IconName::Settings)
                                         .icon_position(IconPosition::Start)
                                         .icon_size(IconSize::Small)
@@ -2483,13 +2848,13 @@ impl AgentPanel {
                                     parent
                                         .child(
                                             h_flex().child(
-                                                Label::new("To start using the agent, configure at least one LLM provider.")
+                                                Label::new(tr("To start using the agent, configure at least one LLM provider.", cx))
                                                     .color(Color::Muted)
                                                     .mb_2p5()
                                             )
                                         )
                                         .child(
-                                            Button::new("settings", "Configure a Provider")
+                                            Button::new("settings", tr("Configure a Provider", cx))
                                                 .icon(IconName::Settings)
                                                 .icon_position(IconPosition::Start)
                                                 .icon_size(IconSize::Small)
@@ -2537,12 +2902,12 @@ impl AgentPanel {
                             .border_b_1()
                             .border_color(cx.theme().colors().border_variant)
                             .child(
-                                Label::new("Recent")
+                                Label::new(tr("Recent", cx))
                                     .size(LabelSize::Small)
                                     .color(Color::Muted),
                             )
                             .child(
-                                Button::new("view-history", "View All")
+                                Button::new("view-history", tr("View All", cx))
                                     .style(ButtonStyle::Subtle)
                                     .label_size(LabelSize::Small)
                                     .key_binding(
@@ -2589,12 +2954,12 @@ impl AgentPanel {
                                             .severity(ui::Severity::Warning)
                                             .child(
                                                 Label::new(
-                                                    "Configure at least one LLM provider to start using the panel.",
+                                                    tr("Configure at least one LLM provider to start using the panel.", cx),
                                                 )
                                                 .size(LabelSize::Small),
                                             )
                                             .action_slot(
-                                                Button::new("settings", "Configure Provider")
+                                                Button::new("settings", tr("Configure Provider", cx))
                                                     .style(ButtonStyle::Tinted(ui::TintColor::Warning))
                                                     .label_size(LabelSize::Small)
                                                     .key_binding(
@@ -2665,12 +3030,12 @@ impl AgentPanel {
 
         let banner = Banner::new()
             .severity(ui::Severity::Info)
-            .child(Label::new("Consecutive tool use limit reached.").size(LabelSize::Small))
+            .child(Label::new(tr("Consecutive tool use limit reached.", cx)).size(LabelSize::Small))
             .action_slot(
                 h_flex()
                     .gap_1()
                     .child(
-                        Button::new("continue-conversation", "Continue")
+                        Button::new("continue-conversation", tr("Continue", cx))
                             .layer(ElevationIndex::ModalSurface)
                             .label_size(LabelSize::Small)
                             .key_binding(
@@ -2688,7 +3053,7 @@ impl AgentPanel {
                     )
                     .when(model.supports_max_mode(), |this| {
                         this.child(
-                            Button::new("continue-burn-mode", "Continue with Burn Mode")
+                            Button::new("continue-burn-mode", tr("Continue with Burn Mode", cx))
                                 .style(ButtonStyle::Filled)
                                 .style(ButtonStyle::Tinted(ui::TintColor::Accent))
                                 .layer(ElevationIndex::ModalSurface)
@@ -2702,7 +3067,10 @@ impl AgentPanel {
                                     )
                                     .map(|kb| kb.size(rems_from_px(10.))),
                                 )
-                                .tooltip(Tooltip::text("Enable Burn Mode for unlimited tool use."))
+                                .tooltip(Tooltip::text(tr(
+                                    "Enable Burn Mode for unlimited tool use.",
+                                    cx,
+                                )))
                                 .on_click(cx.listener(|this, _, window, cx| {
                                     this.thread.update(cx, |active_thread, cx| {
                                         active_thread.thread().update(cx, |thread, _cx| {
@@ -2718,6 +3086,249 @@ impl AgentPanel {
         Some(div().px_2().pb_2().child(banner).into_any_element())
     }
 
+    /// Shows the plan proposed in plan-first mode, which can be reordered and
+    /// trimmed until it's approved, and then shows the progress through it.
+    fn render_plan(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let thread = self.thread.read(cx).thread().clone();
+        let plan = thread.read(cx).plan()?.clone();
+        let step_count = plan.steps.len();
+        let done_count = plan
+            .steps
+            .iter()
+            .filter(|step| step.status == PlanStepStatus::Done)
+            .count();
+
+        let steps = plan
+            .steps
+            .into_iter()
+            .enumerate()
+            .map(|(ix, step)| {
+                let (icon, color) = match step.status {
+                    PlanStepStatus::Pending => (IconName::Circle, Color::Muted),
+                    PlanStepStatus::InProgress => (IconName::ArrowCircle, Color::Accent),
+                    PlanStepStatus::Done => (IconName::Check, Color::Success),
+                    PlanStepStatus::Failed => (IconName::XCircle, Color::Error),
+                };
+                h_flex()
+                    .gap_1p5()
+                    .child(Icon::new(icon).size(IconSize::XSmall).color(color))
+                    .child(
+                        div()
+                            .flex_1()
+                            .min_w_0()
+                            .child(Label::new(step.text).size(LabelSize::Small)),
+                    )
+                    .when(!plan.approved, |this| {
+                        this.child(
+                            IconButton::new(("move-plan-step-up", ix), IconName::ArrowUp)
+                                .icon_size(IconSize::XSmall)
+                                .disabled(ix == 0)
+                                .tooltip(Tooltip::text("Move Up"))
+                                .on_click({
+                                    let thread = thread.clone();
+                                    move |_, _, cx| {
+                                        thread.update(cx, |thread, cx| {
+                                            thread.move_plan_step(ix, ix.saturating_sub(1), cx)
+                                        });
+                                    }
+                                }),
+                        )
+                        .child(
+                            IconButton::new(("move-plan-step-down", ix), IconName::ArrowDown)
+                                .icon_size(IconSize::XSmall)
+                                .disabled(ix + 1 == step_count)
+                                .tooltip(Tooltip::text("Move Down"))
+                                .on_click({
+                                    let thread = thread.clone();
+                                    move |_, _, cx| {
+                                        thread.update(cx, |thread, cx| {
+                                            thread.move_plan_step(ix, ix + 1, cx)
+                                        });
+                                    }
+                                }),
+                        )
+                        .child(
+                            IconButton::new(("remove-plan-step", ix), IconName::Trash)
+                                .icon_size(IconSize::XSmall)
+                                .tooltip(Tooltip::text("Remove Step"))
+                                .on_click({
+                                    let thread = thread.clone();
+                                    move |_, _, cx| {
+                                        thread.update(cx, |thread, cx| {
+                                            thread.remove_plan_step(ix, cx)
+                                        });
+                                    }
+                                }),
+                        )
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        let footer = if plan.approved {
+            Label::new(format!("{done_count} of {step_count} steps done"))
+                .size(LabelSize::Small)
+                .color(Color::Muted)
+                .into_any_element()
+        } else {
+            Button::new("approve-plan", tr("Approve Plan", cx))
+                .style(ButtonStyle::Filled)
+                .label_size(LabelSize::Small)
+                .disabled(step_count == 0 || thread.read(cx).is_generating())
+                .on_click(cx.listener(move |_, _, window, cx| {
+                    thread.update(cx, |thread, cx| {
+                        thread.approve_plan(Some(window.window_handle()), cx)
+                    });
+                }))
+                .into_any_element()
+        };
+
+        Some(
+            v_flex()
+                .mx_2()
+                .mb_2()
+                .p_2()
+                .gap_1()
+                .rounded_md()
+                .border_1()
+                .border_color(cx.theme().colors().border)
+                .bg(cx.theme().colors().editor_background)
+                .child(
+                    Label::new(tr("Plan", cx))
+                        .size(LabelSize::Small)
+                        .color(Color::Muted),
+                )
+                .children(steps)
+                .child(h_flex().justify_end().child(footer))
+                .into_any_element(),
+        )
+    }
+
+    fn render_time_budget_reached(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let thread = self.thread.read(cx).thread().read(cx);
+        if !thread.time_budget_reached() || thread.is_generating() {
+            return None;
+        }
+
+        let banner = Banner::new()
+            .severity(ui::Severity::Info)
+            .child(
+                Label::new(tr(
+                    "The agent ran out of time and summarized its progress.",
+                    cx,
+                ))
+                .size(LabelSize::Small),
+            )
+            .action_slot(
+                Button::new("grant-more-time", tr("Grant 5 More Minutes", cx))
+                    .layer(ElevationIndex::ModalSurface)
+                    .label_size(LabelSize::Small)
+                    .on_click(cx.listener(|this, _, window, cx| {
+                        this.grant_more_time(window, cx);
+                    })),
+            );
+
+        Some(div().px_2().pb_2().child(banner).into_any_element())
+    }
+
+    /// Warns about a deprecated model that the active thread was saved with,
+    /// or that the settings refer to.
+    fn render_model_deprecation(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let deprecation = self
+            .thread
+            .read(cx)
+            .thread()
+            .read(cx)
+            .model_deprecation()
+            .or_else(|| {
+                AgentSettings::get_global(cx)
+                    .model_deprecations()
+                    .into_iter()
+                    .next()
+            })?;
+
+        let status = if deprecation.removed {
+            "has been removed"
+        } else {
+            "is deprecated"
+        };
+        let banner = Banner::new()
+            .severity(ui::Severity::Warning)
+            .child(
+                Label::new(format!(
+                    "{} {status} by its provider. {} is recommended instead.",
+                    deprecation.model, deprecation.replacement
+                ))
+                .size(LabelSize::Small),
+            )
+            .action_slot(
+                Button::new(
+                    "replace-deprecated-model",
+                    format!("Switch to {}", deprecation.replacement),
+                )
+                .layer(ElevationIndex::ModalSurface)
+                .label_size(LabelSize::Small)
+                .on_click(cx.listener(|this, _, _, cx| this.replace_deprecated_models(cx))),
+            );
+
+        Some(div().px_2().pb_2().child(banner).into_any_element())
+    }
+
+    fn render_workspace_trust(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        if WorkspaceTrust::is_trusted(&self.project, cx) {
+            return None;
+        }
+
+        let banner = Banner::new()
+            .severity(ui::Severity::Warning)
+            .child(
+                Label::new(
+                    tr("This workspace isn't trusted, so the agent can't edit files or run commands in it.", cx),
+                )
+                .size(LabelSize::Small),
+            )
+            .action_slot(
+                Button::new("trust-workspace", tr("Trust Workspace", cx))
+                    .layer(ElevationIndex::ModalSurface)
+                    .label_size(LabelSize::Small)
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        WorkspaceTrust::trust(&this.project, cx);
+                        cx.notify();
+                    })),
+            );
+
+        Some(div().px_2().pb_2().child(banner).into_any_element())
+    }
+
+    fn replace_deprecated_models(&mut self, cx: &mut Context<Self>) {
+        if !AgentSettings::get_global(cx)
+            .model_deprecations()
+            .is_empty()
+        {
+            update_settings_file::<AgentSettings>(self.fs.clone(), cx, |settings, _| {
+                settings.replace_deprecated_models()
+            });
+        }
+
+        let thread = self.thread.read(cx).thread().clone();
+        let Some(deprecation) = thread.read(cx).model_deprecation() else {
+            return;
+        };
+        let replacement = LanguageModelRegistry::global(cx).update(cx, |registry, cx| {
+            registry
+                .select_model(
+                    &SelectedModel {
+                        provider: LanguageModelProviderId(deprecation.provider.into()),
+                        model: LanguageModelId(deprecation.replacement.into()),
+                    },
+                    cx,
+                )
+                .or_else(|| registry.default_model())
+        });
+        thread.update(cx, |thread, cx| {
+            thread.set_configured_model(replacement, cx)
+        });
+    }
+
     fn render_last_error(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
         let last_error = self.thread.read(cx).last_error()?;
 
@@ -2736,6 +3347,9 @@ impl AgentPanel {
                     ThreadError::ModelRequestLimitReached { plan } => {
                         self.render_model_request_limit_reached_error(plan, cx)
                     }
+                    ThreadError::MaxMonthlySpendReached(error) => {
+                        self.render_max_monthly_spend_reached_error(error, cx)
+                    }
                     ThreadError::Message { header, message } => {
                         self.render_error_message(header, message, cx)
                     }
@@ -2754,7 +3368,7 @@ impl AgentPanel {
                     .gap_1p5()
                     .items_center()
                     .child(Icon::new(IconName::XCircle).color(Color::Error))
-                    .child(Label::new("Free Usage Exceeded").weight(FontWeight::MEDIUM)),
+                    .child(Label::new(tr("Free Usage Exceeded", cx)).weight(FontWeight::MEDIUM)),
             )
             .child(
                 div()
@@ -2769,25 +3383,29 @@ impl AgentPanel {
                     .mt_1()
                     .gap_1()
                     .child(self.create_copy_button(ERROR_MESSAGE))
-                    .child(Button::new("subscribe", "Subscribe").on_click(cx.listener(
-                        |this, _, _, cx| {
-                            this.thread.update(cx, |this, _cx| {
-                                this.clear_last_error();
-                            });
+                    .child(
+                        Button::new("subscribe", tr("Subscribe", cx)).on_click(cx.listener(
+                            |this, _, _, cx| {
+                                this.thread.update(cx, |this, _cx| {
+                                    this.clear_last_error();
+                                });
 
-                            cx.open_url(&zed_urls::account_url(cx));
-                            cx.notify();
-                        },
-                    )))
-                    .child(Button::new("dismiss", "Dismiss").on_click(cx.listener(
-                        |this, _, _, cx| {
-                            this.thread.update(cx, |this, _cx| {
-                                this.clear_last_error();
-                            });
+                                cx.open_url(&zed_urls::account_url(cx));
+                                cx.notify();
+                            },
+                        )),
+                    )
+                    .child(
+                        Button::new("dismiss", tr("Dismiss", cx)).on_click(cx.listener(
+                            |this, _, _, cx| {
+                                this.thread.update(cx, |this, _cx| {
+                                    this.clear_last_error();
+                                });
 
-                            cx.notify();
-                        },
-                    ))),
+                                cx.notify();
+                            },
+                        )),
+                    ),
             )
             .into_any()
     }
@@ -2819,7 +3437,10 @@ impl AgentPanel {
                     .gap_1p5()
                     .items_center()
                     .child(Icon::new(IconName::XCircle).color(Color::Error))
-                    .child(Label::new("Model Request Limit Reached").weight(FontWeight::MEDIUM)),
+                    .child(
+                        Label::new(tr("Model Request Limit Reached", cx))
+                            .weight(FontWeight::MEDIUM),
+                    ),
             )
             .child(
                 div()
@@ -2846,15 +3467,127 @@ impl AgentPanel {
                             },
                         )),
                     )
-                    .child(Button::new("dismiss", "Dismiss").on_click(cx.listener(
-                        |this, _, _, cx| {
-                            this.thread.update(cx, |this, _cx| {
-                                this.clear_last_error();
-                            });
+                    .child(
+                        Button::new("dismiss", tr("Dismiss", cx)).on_click(cx.listener(
+                            |this, _, _, cx| {
+                                this.thread.update(cx, |this, _cx| {
+                                    this.clear_last_error();
+                                });
 
-                            cx.notify();
-                        },
-                    ))),
+                                cx.notify();
+                            },
+                        )),
+                    ),
+            )
+            .into_any()
+    }
+
+    fn render_max_monthly_spend_reached_error(
+        &self,
+        error: MaxMonthlySpendReachedError,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let error_message = error.to_string();
+        let raised_limit = (error.max_monthly_spend * 2.).max(1.);
+        let registry = LanguageModelRegistry::read_global(cx);
+        let free_model = self
+            .thread
+            .read(cx)
+            .thread()
+            .read(cx)
+            .configured_model()
+            .or_else(|| registry.default_model())
+            .and_then(|configured_model| {
+                registry.free_model_substitute(&configured_model.model, cx)
+            });
+
+        v_flex()
+            .gap_0p5()
+            .child(
+                h_flex()
+                    .gap_1p5()
+                    .items_center()
+                    .child(Icon::new(IconName::XCircle).color(Color::Error))
+                    .child(
+                        Label::new(tr("Monthly Spending Limit Reached", cx))
+                            .weight(FontWeight::MEDIUM),
+                    ),
+            )
+            .child(
+                div()
+                    .id("error-message")
+                    .max_h_24()
+                    .overflow_y_scroll()
+                    .child(Label::new(error_message.clone())),
+            )
+            .child(
+                h_flex()
+                    .justify_end()
+                    .mt_1()
+                    .gap_1()
+                    .child(self.create_copy_button(error_message))
+                    .child(
+                        Button::new("raise-limit", format!("Raise to ${raised_limit:.2}"))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.thread.update(cx, |this, _cx| {
+                                    this.clear_last_error();
+                                });
+
+                                update_settings_file::<AgentSettings>(
+                                    this.fs.clone(),
+                                    cx,
+                                    move |settings, _| {
+                                        settings.set_max_monthly_spend(Some(raised_limit))
+                                    },
+                                );
+                                cx.notify();
+                            })),
+                    )
+                    .when_some(free_model, |this, model| {
+                        this.child(
+                            Button::new("use-free-model", tr("Use Free Model", cx))
+                                .tooltip(Tooltip::text(format!("Switch to {}", model.name().0)))
+                                .on_click(cx.listener(move |this, _, _, cx| {
+                                    let model = model.clone();
+                                    let thread = this.thread.update(cx, |this, _cx| {
+                                        this.clear_last_error();
+                                        this.thread().clone()
+                                    });
+                                    thread.update(cx, |thread, cx| {
+                                        let registry = LanguageModelRegistry::read_global(cx);
+                                        if let Some(provider) =
+                                            registry.provider(&model.provider_id())
+                                        {
+                                            thread.set_configured_model(
+                                                Some(ConfiguredModel {
+                                                    provider,
+                                                    model: model.clone(),
+                                                }),
+                                                cx,
+                                            );
+                                        }
+                                    });
+
+                                    update_settings_file::<AgentSettings>(
+                                        this.fs.clone(),
+                                        cx,
+                                        move |settings, _| settings.set_model(model),
+                                    );
+                                    cx.notify();
+                                })),
+                        )
+                    })
+                    .child(
+                        Button::new("dismiss", tr("Dismiss", cx)).on_click(cx.listener(
+                            |this, _, _, cx| {
+                                this.thread.update(cx, |this, _cx| {
+                                    this.clear_last_error();
+                                });
+
+                                cx.notify();
+                            },
+                        )),
+                    ),
             )
             .into_any()
     }
@@ -2888,15 +3621,17 @@ impl AgentPanel {
                     .mt_1()
                     .gap_1()
                     .child(self.create_copy_button(message_with_header))
-                    .child(Button::new("dismiss", "Dismiss").on_click(cx.listener(
-                        |this, _, _, cx| {
-                            this.thread.update(cx, |this, _cx| {
-                                this.clear_last_error();
-                            });
+                    .child(
+                        Button::new("dismiss", tr("Dismiss", cx)).on_click(cx.listener(
+                            |this, _, _, cx| {
+                                this.thread.update(cx, |this, _cx| {
+                                    this.clear_last_error();
+                                });
 
-                            cx.notify();
-                        },
-                    ))),
+                                cx.notify();
+                            },
+                        )),
+                    ),
             )
             .into_any()
     }
@@ -2941,6 +3676,38 @@ impl AgentPanel {
             .child(self.render_drag_target(cx))
     }
 
+    fn render_split_context_editor(&self, cx: &Context<Self>) -> Option<Div> {
+        let split_context_editor = self.split_context_editor.clone()?;
+        let title = split_context_editor.read(cx).title(cx);
+        Some(
+            v_flex()
+                .flex_1()
+                .min_w_0()
+                .h_full()
+                .border_l_1()
+                .border_color(cx.theme().colors().border)
+                .child(
+                    h_flex()
+                        .px_2()
+                        .py_1()
+                        .gap_1()
+                        .justify_between()
+                        .border_b_1()
+                        .border_color(cx.theme().colors().border_variant)
+                        .child(Label::new(title).size(LabelSize::Small).truncate())
+                        .child(
+                            IconButton::new("close-split", IconName::Close)
+                                .icon_size(IconSize::XSmall)
+                                .tooltip(Tooltip::text("Close Split"))
+                                .on_click(cx.listener(|this, _, window, cx| {
+                                    this.close_split(window, cx);
+                                })),
+                        ),
+                )
+                .child(div().flex_1().min_h_0().child(split_context_editor)),
+        )
+    }
+
     fn render_drag_target(&self, cx: &Context<Self>) -> Div {
         let is_local = self.project.read(cx).is_local();
         div()
@@ -2958,6 +3725,14 @@ impl AgentPanel {
             })
             .on_drop(cx.listener(move |this, tab: &DraggedTab, window, cx| {
                 let item = tab.pane.read(cx).item_for_index(tab.ix);
+                if let Some(context_editor) = item.and_then(|item| item.downcast::<ContextEditor>())
+                {
+                    if matches!(this.active_view, ActiveView::TextThread { .. }) {
+                        let context = context_editor.read(cx).context().clone();
+                        this.open_context_in_split(context, window, cx);
+                        return;
+                    }
+                }
                 let project_paths = item
                     .and_then(|item| item.project_path(cx))
                     .into_iter()
@@ -3088,6 +3863,7 @@ impl Render for AgentPanel {
                 this.open_configuration(window, cx);
             }))
             .on_action(cx.listener(Self::open_active_thread_as_markdown))
+            .on_action(cx.listener(Self::open_text_thread_in_split))
             .on_action(cx.listener(Self::deploy_rules_library))
             .on_action(cx.listener(Self::open_agent_diff))
             .on_action(cx.listener(Self::go_back))
@@ -3116,7 +3892,11 @@ impl Render for AgentPanel {
                 ActiveView::Thread { .. } => parent
                     .relative()
                     .child(self.render_active_thread_or_empty_state(window, cx))
+                    .children(self.render_model_deprecation(cx))
+                    .children(self.render_workspace_trust(cx))
                     .children(self.render_tool_use_limit_reached(window, cx))
+                    .children(self.render_plan(cx))
+                    .children(self.render_time_budget_reached(cx))
                     .child(h_flex().child(self.message_editor.clone()))
                     .children(self.render_last_error(cx))
                     .child(self.render_drag_target(cx)),
@@ -3125,12 +3905,19 @@ impl Render for AgentPanel {
                     context_editor,
                     buffer_search_bar,
                     ..
-                } => parent.child(self.render_prompt_editor(
-                    context_editor,
-                    buffer_search_bar,
-                    window,
-                    cx,
-                )),
+                } => {
+                    let prompt_editor =
+                        self.render_prompt_editor(context_editor, buffer_search_bar, window, cx);
+                    match self.render_split_context_editor(cx) {
+                        Some(split) => parent.child(
+                            h_flex()
+                                .size_full()
+                                .child(div().flex_1().min_w_0().h_full().child(prompt_editor))
+                                .child(split),
+                        ),
+                        None => parent.child(prompt_editor),
+                    }
+                }
                 ActiveView::Configuration => parent.children(self.configuration.clone()),
             });
 
@@ -3310,3 +4097,54 @@ struct TrialEndUpsell;
 impl Dismissable for TrialEndUpsell {
     const KEY: &'static str = "dismissed-trial-end-upsell";
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_deserializing_agent_panel() {
+        let panel: SerializedAgentPanel =
+            serde_json::from_value(json!({ "width": 400.0 })).unwrap();
+        assert_eq!(panel.width, Some(px(400.)));
+        assert_eq!(panel.height, None);
+        assert!(panel.active_view.is_none());
+
+        let panel = SerializedAgentPanel {
+            width: None,
+            height: Some(px(300.)),
+            active_view: Some(SerializedActiveView::Thread {
+                id: "thread-1".into(),
+                scroll_top_item: 3,
+                scroll_offset_in_item: px(12.),
+            }),
+        };
+        let panel: SerializedAgentPanel =
+            serde_json::from_str(&serde_json::to_string(&panel).unwrap()).unwrap();
+        assert_eq!(panel.height, Some(px(300.)));
+        let Some(SerializedActiveView::Thread {
+            id,
+            scroll_top_item,
+            scroll_offset_in_item,
+        }) = panel.active_view
+        else {
+            panic!("expected a thread view");
+        };
+        assert_eq!(id, "thread-1");
+        assert_eq!(scroll_top_item, 3);
+        assert_eq!(scroll_offset_in_item, px(12.));
+
+        let view: SerializedActiveView = serde_json::from_value(json!({
+            "kind": "text_thread",
+            "path": "/contexts/notes.zed.json",
+            "scroll_top": 7.5,
+        }))
+        .unwrap();
+        assert!(matches!(
+            view,
+            SerializedActiveView::TextThread { path, scroll_top }
+                if path == Path::new("/contexts/notes.zed.json") && scroll_top == 7.5
+        ));
+    }
+}
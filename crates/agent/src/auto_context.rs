@@ -0,0 +1,145 @@
+//! Finds the code in the project that's most relevant to a message, so that
+//! it can be attached to the message without the user adding it as context.
+
+use agent_settings::AgentSettings;
+use gpui::{App, Entity, Task};
+use project::Project;
+use semantic_index::{LoadedSearchResult, ProjectIndex, SemanticDb};
+use settings::Settings as _;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use util::ResultExt as _;
+
+use crate::context::RetrievedChunk;
+
+const BYTES_PER_TOKEN_ESTIMATE: usize = 4;
+
+/// The number of search results used to rank the chunks of the large files
+/// attached to a message.
+const FILE_CHUNK_SEARCH_LIMIT: usize = 64;
+
+/// Retrieves the chunks most relevant to the message, as many as fit within
+/// the auto-context settings' limits. Returns no chunks when the semantic
+/// index isn't available.
+pub(crate) fn retrieve_chunks(
+    project: &Entity<Project>,
+    message: String,
+    cx: &mut App,
+) -> Task<Vec<RetrievedChunk>> {
+    let Some(project_index) = project_index(project, &message, cx) else {
+        return Task::ready(Vec::new());
+    };
+
+    let settings = AgentSettings::get_global(cx).auto_context.clone();
+    let results = project_index
+        .read(cx)
+        .retrieve(message, settings.max_chunks, cx);
+    cx.background_spawn(async move {
+        let results = results.await.log_err().unwrap_or_default();
+        select_within_budget(results, settings.max_tokens)
+    })
+}
+
+/// A part of a file that the semantic index found relevant to a message.
+pub(crate) struct RelevantRange {
+    pub full_path: PathBuf,
+    /// The byte range of the part in the file.
+    pub range: Range<usize>,
+    pub score: f32,
+}
+
+/// Searches the semantic index for the parts of the project most relevant to
+/// the message, to rank the chunks of the large files attached to it by.
+/// Returns no ranges when the semantic index isn't available.
+pub(crate) fn search_relevant_ranges(
+    project: &Entity<Project>,
+    message: String,
+    cx: &mut App,
+) -> Task<Vec<RelevantRange>> {
+    let Some(project_index) = project_index(project, &message, cx) else {
+        return Task::ready(Vec::new());
+    };
+
+    let results = project_index
+        .read(cx)
+        .search(vec![message], FILE_CHUNK_SEARCH_LIMIT, cx);
+    cx.spawn(async move |cx| {
+        let results = results.await.log_err().unwrap_or_default();
+        results
+            .into_iter()
+            .filter_map(|result| {
+                let root_name = result
+                    .worktree
+                    .read_with(cx, |worktree, _| worktree.root_name().to_string())
+                    .ok()?;
+                Some(RelevantRange {
+                    full_path: Path::new(&root_name).join(&result.path),
+                    range: result.range,
+                    score: result.score,
+                })
+            })
+            .collect()
+    })
+}
+
+fn project_index(
+    project: &Entity<Project>,
+    message: &str,
+    cx: &mut App,
+) -> Option<Entity<ProjectIndex>> {
+    if message.trim().is_empty() || !cx.has_global::<SemanticDb>() {
+        return None;
+    }
+    Some(cx.update_global(|semantic_db: &mut SemanticDb, cx| {
+        semantic_db.project_index(project.clone(), cx)
+    }))
+}
+
+/// Keeps the best-ranked results whose text fits in the token budget
+/// together, skipping the ones that would exceed it.
+fn select_within_budget(
+    results: Vec<LoadedSearchResult>,
+    max_tokens: usize,
+) -> Vec<RetrievedChunk> {
+    let mut remaining_len = max_tokens * BYTES_PER_TOKEN_ESTIMATE;
+    results
+        .into_iter()
+        .filter(|result| {
+            let fits = result.excerpt_content.len() <= remaining_len;
+            if fits {
+                remaining_len -= result.excerpt_content.len();
+            }
+            fits
+        })
+        .map(|result| RetrievedChunk {
+            full_path: result.full_path,
+            row_range: result.row_range,
+            text: result.excerpt_content,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(path: &str, len: usize) -> LoadedSearchResult {
+        LoadedSearchResult {
+            path: Path::new(path).into(),
+            full_path: PathBuf::from(path),
+            excerpt_content: "x".repeat(len),
+            row_range: 0..=1,
+            query_index: 0,
+        }
+    }
+
+    #[test]
+    fn test_select_within_budget() {
+        let results = vec![result("a.rs", 24), result("b.rs", 40), result("c.rs", 12)];
+        let paths = select_within_budget(results, 10)
+            .into_iter()
+            .map(|chunk| chunk.full_path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths, [PathBuf::from("a.rs"), PathBuf::from("c.rs")]);
+    }
+}
@@ -13,7 +13,7 @@ use gpui::{App, AppContext as _, Context, Entity, EventEmitter, Subscription, Ta
 use language::{Buffer, IndentKind, Point, TransactionId, line_diff};
 use language_model::{
     LanguageModel, LanguageModelRegistry, LanguageModelRequest, LanguageModelRequestMessage,
-    LanguageModelTextStream, Role, report_assistant_event,
+    LanguageModelTextStream, RequestPriority, Role, report_assistant_event,
 };
 use multi_buffer::MultiBufferRow;
 use parking_lot::Mutex;
@@ -426,9 +426,20 @@ impl CodegenAlternative {
             anyhow::bail!("invalid transformation range");
         };
 
+        let language_preamble = language_name.and_then(|language_name| {
+            AgentSettings::get_global(cx)
+                .language_preamble(language_name.as_ref())
+                .map(|preamble| preamble.to_string())
+        });
         let prompt = self
             .builder
-            .generate_inline_transformation_prompt(user_prompt, language_name, buffer, range)
+            .generate_inline_transformation_prompt(
+                user_prompt,
+                language_name,
+                language_preamble.as_deref(),
+                buffer,
+                range,
+            )
             .context("generating content prompt")?;
 
         let context_task = self.context_store.as_ref().map(|context_store| {
@@ -471,7 +482,10 @@ impl CodegenAlternative {
                 tool_choice: None,
                 stop: Vec::new(),
                 temperature,
+                seed: None,
+                priority: RequestPriority::Interactive,
                 messages: vec![request_message],
+                web_search: false,
             }
         }))
     }
@@ -1,7 +1,11 @@
 use std::fmt::{self, Display, Formatter, Write as _};
 use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
-use std::{ops::Range, path::Path, sync::Arc};
+use std::{
+    ops::{Range, RangeInclusive},
+    path::Path,
+    sync::Arc,
+};
 
 use assistant_context_editor::AssistantContext;
 use assistant_tool::outline;
@@ -17,12 +21,16 @@ use project::{Project, ProjectEntryId, ProjectPath, Worktree};
 use prompt_store::{PromptStore, UserPromptId};
 use ref_cast::RefCast;
 use rope::Point;
+use serde::{Deserialize, Serialize};
 use text::{Anchor, OffsetRangeExt as _};
 use ui::{Context, ElementId, IconName};
 use util::markdown::MarkdownCodeBlock;
 use util::{ResultExt as _, post_inc};
 
+use crate::auto_context::RelevantRange;
 use crate::context_store::{ContextStore, ContextStoreEvent};
+use crate::file_chunks::{self, FileChunk};
+use crate::memory_recall::Memory;
 use crate::thread::Thread;
 
 pub const RULES_ICON: IconName = IconName::Context;
@@ -166,6 +174,8 @@ pub struct FileContext {
     pub full_path: Arc<Path>,
     pub text: SharedString,
     pub is_outline: bool,
+    /// For large files, the chunks of the file that are included in the context.
+    pub chunks: Vec<FileChunk>,
 }
 
 impl FileContextHandle {
@@ -211,7 +221,22 @@ impl FileContextHandle {
                             let items = outline
                                 .items
                                 .into_iter()
-                                .map(|item| item.to_point(&snapshot));
+                                .map(|item| item.to_point(&snapshot))
+                                .collect::<Vec<_>>();
+
+                            // Split the file along its syntax tree, so that the least relevant
+                            // chunks can be dropped. Without any syntax, use the outline instead.
+                            if !items.is_empty() {
+                                let chunks = file_chunks::chunk_file(&snapshot, &items);
+                                let context = AgentContext::File(FileContext {
+                                    handle: self,
+                                    full_path,
+                                    text: rope.to_string().into(),
+                                    is_outline: false,
+                                    chunks,
+                                });
+                                return Some((context, vec![buffer]));
+                            }
 
                             if let Ok(outline_text) =
                                 outline::render_outline(items, None, 0, usize::MAX).await
@@ -221,6 +246,7 @@ impl FileContextHandle {
                                     full_path,
                                     text: outline_text.into(),
                                     is_outline: true,
+                                    chunks: Vec::new(),
                                 });
                                 return Some((context, vec![buffer]));
                             }
@@ -236,6 +262,7 @@ impl FileContextHandle {
                 full_path,
                 text: rope.to_string().into(),
                 is_outline: false,
+                chunks: Vec::new(),
             });
             Some((context, vec![buffer]))
         })
@@ -244,14 +271,52 @@ impl FileContextHandle {
 
 impl Display for FileContext {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            MarkdownCodeBlock {
-                tag: &codeblock_tag(&self.full_path, None),
-                text: &self.text,
+        if self.chunks.is_empty() {
+            return write!(
+                f,
+                "{}",
+                MarkdownCodeBlock {
+                    tag: &codeblock_tag(&self.full_path, None),
+                    text: &self.text,
+                }
+            );
+        }
+
+        for chunk in self.chunks.iter().filter(|chunk| chunk.included) {
+            writeln!(f, "{}:", chunk.label)?;
+            write!(
+                f,
+                "{}",
+                MarkdownCodeBlock {
+                    tag: &codeblock_tag(&self.full_path, Some(chunk.line_range())),
+                    text: &chunk.text,
+                }
+            )?;
+        }
+
+        let mut omitted = self
+            .chunks
+            .iter()
+            .filter(|chunk| !chunk.included)
+            .peekable();
+        if omitted.peek().is_some() {
+            writeln!(
+                f,
+                "The following parts of {} were omitted as less relevant, and can be read if needed:",
+                self.full_path.display()
+            )?;
+            for chunk in omitted {
+                let lines = chunk.line_range();
+                writeln!(
+                    f,
+                    "- {} (lines {}-{})",
+                    chunk.label,
+                    lines.start.row + 1,
+                    lines.end.row + 1
+                )?;
             }
-        )
+        }
+        Ok(())
     }
 }
 
@@ -793,15 +858,110 @@ pub struct ContextLoadResult {
 #[derive(Debug, Clone, Default)]
 pub struct LoadedContext {
     pub contexts: Vec<AgentContext>,
+    /// Code that was found relevant to the message by searching the project,
+    /// rather than added by the user.
+    pub retrieved_chunks: Vec<RetrievedChunk>,
+    /// Exchanges from past threads the user attached as memories.
+    pub memories: Vec<Memory>,
     pub text: String,
     pub images: Vec<LanguageModelImage>,
 }
 
+/// An excerpt of a project file that auto-context attached to a message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetrievedChunk {
+    pub full_path: PathBuf,
+    /// The zero-based rows the excerpt spans.
+    pub row_range: RangeInclusive<u32>,
+    pub text: String,
+}
+
+impl Display for RetrievedChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line_range =
+            Point::new(*self.row_range.start(), 0)..Point::new(*self.row_range.end(), 0);
+        write!(
+            f,
+            "{}",
+            MarkdownCodeBlock {
+                tag: &codeblock_tag(&self.full_path, Some(line_range)),
+                text: &self.text,
+            }
+        )
+    }
+}
+
 impl LoadedContext {
     pub fn is_empty(&self) -> bool {
         self.text.is_empty() && self.images.is_empty()
     }
 
+    /// Whether any of the files were split into chunks because they're too
+    /// large to include in full.
+    pub fn has_chunked_files(&self) -> bool {
+        self.contexts.iter().any(
+            |context| matches!(context, AgentContext::File(context) if !context.chunks.is_empty()),
+        )
+    }
+
+    /// Keeps the chunks of large files that overlap the parts of the project
+    /// most relevant to the message, dropping the rest once the budget for
+    /// each file is reached.
+    pub(crate) fn select_relevant_chunks(&mut self, relevant_ranges: &[RelevantRange]) {
+        let mut changed = false;
+        for context in &mut self.contexts {
+            if let AgentContext::File(context) = context {
+                let relevant_rows = relevant_ranges
+                    .iter()
+                    .filter(|relevant| relevant.full_path.as_path() == context.full_path.as_ref())
+                    .map(|relevant| {
+                        let rows =
+                            file_chunks::rows_for_range(&context.text, relevant.range.clone());
+                        (rows, relevant.score)
+                    })
+                    .collect::<Vec<_>>();
+                if !context.chunks.is_empty() && !relevant_rows.is_empty() {
+                    file_chunks::select_chunks(&mut context.chunks, &relevant_rows);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.render_text();
+        }
+    }
+
+    /// Attaches the retrieved chunks, skipping the ones from files the user
+    /// already attached in full.
+    pub fn add_retrieved_chunks(&mut self, chunks: Vec<RetrievedChunk>) {
+        let attached_paths = self
+            .contexts
+            .iter()
+            .filter_map(|context| match context {
+                AgentContext::File(context) => Some(context.full_path.clone()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        self.retrieved_chunks.extend(
+            chunks
+                .into_iter()
+                .filter(|chunk| !attached_paths.contains(chunk.full_path.as_path())),
+        );
+        self.render_text();
+    }
+
+    pub fn add_memories(&mut self, memories: Vec<Memory>) {
+        self.memories.extend(memories);
+        self.render_text();
+    }
+
+    fn render_text(&mut self) {
+        self.text = render_context_text(&self.contexts);
+        self.text.push_str(&render_memories(&self.memories));
+        self.text
+            .push_str(&render_retrieved_chunks(&self.retrieved_chunks));
+    }
+
     pub fn add_to_request_message(&self, request_message: &mut LanguageModelRequestMessage) {
         if !self.text.is_empty() {
             request_message
@@ -852,7 +1012,6 @@ pub fn load_context(
         let load_results = future::join_all(load_tasks).await;
 
         let mut contexts = Vec::new();
-        let mut text = String::new();
         let mut referenced_buffers = HashSet::default();
         for context in load_results {
             let Some((context, buffers)) = context else {
@@ -862,142 +1021,193 @@ pub fn load_context(
             referenced_buffers.extend(buffers);
         }
 
-        let mut file_context = Vec::new();
-        let mut directory_context = Vec::new();
-        let mut symbol_context = Vec::new();
-        let mut selection_context = Vec::new();
-        let mut fetched_url_context = Vec::new();
-        let mut thread_context = Vec::new();
-        let mut text_thread_context = Vec::new();
-        let mut rules_context = Vec::new();
-        let mut images = Vec::new();
-        for context in &contexts {
-            match context {
-                AgentContext::File(context) => file_context.push(context),
-                AgentContext::Directory(context) => directory_context.push(context),
-                AgentContext::Symbol(context) => symbol_context.push(context),
-                AgentContext::Selection(context) => selection_context.push(context),
-                AgentContext::FetchedUrl(context) => fetched_url_context.push(context),
-                AgentContext::Thread(context) => thread_context.push(context),
-                AgentContext::TextThread(context) => text_thread_context.push(context),
-                AgentContext::Rules(context) => rules_context.push(context),
-                AgentContext::Image(context) => images.extend(context.image()),
-            }
-        }
+        let images = contexts
+            .iter()
+            .filter_map(|context| match context {
+                AgentContext::Image(context) => context.image(),
+                _ => None,
+            })
+            .collect();
+        let text = render_context_text(&contexts);
 
-        // Use empty text if there are no contexts that contribute to text (everything but image
-        // context).
-        if file_context.is_empty()
-            && directory_context.is_empty()
-            && symbol_context.is_empty()
-            && selection_context.is_empty()
-            && fetched_url_context.is_empty()
-            && thread_context.is_empty()
-            && text_thread_context.is_empty()
-            && rules_context.is_empty()
-        {
-            return ContextLoadResult {
-                loaded_context: LoadedContext {
-                    contexts,
-                    text,
-                    images,
-                },
-                referenced_buffers,
-            };
+        ContextLoadResult {
+            loaded_context: LoadedContext {
+                contexts,
+                retrieved_chunks: Vec::new(),
+                memories: Vec::new(),
+                text,
+                images,
+            },
+            referenced_buffers,
         }
+    })
+}
 
-        text.push_str(
-            "\n<context>\n\
-            The following items were attached by the user. \
-            They are up-to-date and don't need to be re-read.\n\n",
-        );
+/// Formats the text of the loaded contexts, to be included in a user message.
+fn render_context_text(contexts: &[AgentContext]) -> String {
+    let mut text = String::new();
+    let mut file_context = Vec::new();
+    let mut directory_context = Vec::new();
+    let mut symbol_context = Vec::new();
+    let mut selection_context = Vec::new();
+    let mut fetched_url_context = Vec::new();
+    let mut thread_context = Vec::new();
+    let mut text_thread_context = Vec::new();
+    let mut rules_context = Vec::new();
+    for context in contexts {
+        match context {
+            AgentContext::File(context) => file_context.push(context),
+            AgentContext::Directory(context) => directory_context.push(context),
+            AgentContext::Symbol(context) => symbol_context.push(context),
+            AgentContext::Selection(context) => selection_context.push(context),
+            AgentContext::FetchedUrl(context) => fetched_url_context.push(context),
+            AgentContext::Thread(context) => thread_context.push(context),
+            AgentContext::TextThread(context) => text_thread_context.push(context),
+            AgentContext::Rules(context) => rules_context.push(context),
+            AgentContext::Image(_) => {}
+        }
+    }
 
-        if !file_context.is_empty() {
-            text.push_str("<files>");
-            for context in file_context {
-                text.push('\n');
-                let _ = write!(text, "{context}");
-            }
-            text.push_str("</files>\n");
+    // Use empty text if there are no contexts that contribute to text (everything but image
+    // context).
+    if file_context.is_empty()
+        && directory_context.is_empty()
+        && symbol_context.is_empty()
+        && selection_context.is_empty()
+        && fetched_url_context.is_empty()
+        && thread_context.is_empty()
+        && text_thread_context.is_empty()
+        && rules_context.is_empty()
+    {
+        return text;
+    }
+
+    text.push_str(
+        "\n<context>\n\
+        The following items were attached by the user. \
+        They are up-to-date and don't need to be re-read.\n\n",
+    );
+
+    if !file_context.is_empty() {
+        text.push_str("<files>");
+        for context in file_context {
+            text.push('\n');
+            let _ = write!(text, "{context}");
         }
+        text.push_str("</files>\n");
+    }
 
-        if !directory_context.is_empty() {
-            text.push_str("<directories>");
-            for context in directory_context {
-                text.push('\n');
-                let _ = write!(text, "{context}");
-            }
-            text.push_str("</directories>\n");
+    if !directory_context.is_empty() {
+        text.push_str("<directories>");
+        for context in directory_context {
+            text.push('\n');
+            let _ = write!(text, "{context}");
         }
+        text.push_str("</directories>\n");
+    }
 
-        if !symbol_context.is_empty() {
-            text.push_str("<symbols>");
-            for context in symbol_context {
-                text.push('\n');
-                let _ = write!(text, "{context}");
-            }
-            text.push_str("</symbols>\n");
+    if !symbol_context.is_empty() {
+        text.push_str("<symbols>");
+        for context in symbol_context {
+            text.push('\n');
+            let _ = write!(text, "{context}");
         }
+        text.push_str("</symbols>\n");
+    }
 
-        if !selection_context.is_empty() {
-            text.push_str("<selections>");
-            for context in selection_context {
-                text.push('\n');
-                let _ = write!(text, "{context}");
-            }
-            text.push_str("</selections>\n");
+    if !selection_context.is_empty() {
+        text.push_str("<selections>");
+        for context in selection_context {
+            text.push('\n');
+            let _ = write!(text, "{context}");
         }
+        text.push_str("</selections>\n");
+    }
 
-        if !fetched_url_context.is_empty() {
-            text.push_str("<fetched_urls>");
-            for context in fetched_url_context {
-                text.push('\n');
-                let _ = write!(text, "{context}");
-            }
-            text.push_str("</fetched_urls>\n");
+    if !fetched_url_context.is_empty() {
+        text.push_str("<fetched_urls>");
+        for context in fetched_url_context {
+            text.push('\n');
+            let _ = write!(text, "{context}");
         }
+        text.push_str("</fetched_urls>\n");
+    }
 
-        if !thread_context.is_empty() {
-            text.push_str("<conversation_threads>");
-            for context in thread_context {
-                text.push('\n');
-                let _ = write!(text, "{context}");
-            }
-            text.push_str("</conversation_threads>\n");
+    if !thread_context.is_empty() {
+        text.push_str("<conversation_threads>");
+        for context in thread_context {
+            text.push('\n');
+            let _ = write!(text, "{context}");
         }
+        text.push_str("</conversation_threads>\n");
+    }
 
-        if !text_thread_context.is_empty() {
-            text.push_str("<text_threads>");
-            for context in text_thread_context {
-                text.push('\n');
-                let _ = writeln!(text, "{context}");
-            }
-            text.push_str("<text_threads>");
+    if !text_thread_context.is_empty() {
+        text.push_str("<text_threads>");
+        for context in text_thread_context {
+            text.push('\n');
+            let _ = writeln!(text, "{context}");
         }
+        text.push_str("<text_threads>");
+    }
 
-        if !rules_context.is_empty() {
-            text.push_str(
-                "<user_rules>\n\
-                The user has specified the following rules that should be applied:\n",
-            );
-            for context in rules_context {
-                text.push('\n');
-                let _ = write!(text, "{context}");
-            }
-            text.push_str("</user_rules>\n");
+    if !rules_context.is_empty() {
+        text.push_str(
+            "<user_rules>\n\
+            The user has specified the following rules that should be applied:\n",
+        );
+        for context in rules_context {
+            text.push('\n');
+            let _ = write!(text, "{context}");
         }
+        text.push_str("</user_rules>\n");
+    }
 
-        text.push_str("</context>\n");
+    text.push_str("</context>\n");
 
-        ContextLoadResult {
-            loaded_context: LoadedContext {
-                contexts,
-                text,
-                images,
-            },
-            referenced_buffers,
-        }
-    })
+    text
+}
+
+/// Formats the memories the user attached, to be included after the other
+/// contexts they attached.
+fn render_memories(memories: &[Memory]) -> String {
+    let mut text = String::new();
+    if memories.is_empty() {
+        return text;
+    }
+
+    text.push_str(
+        "\n<memories>\n\
+        The user attached these exchanges from earlier conversations in this project, \
+        so the answer can build on them.\n",
+    );
+    for memory in memories {
+        text.push('\n');
+        let _ = write!(text, "{memory}");
+    }
+    text.push_str("</memories>\n");
+    text
+}
+
+/// Formats the chunks auto-context attached, to be included after the
+/// contexts the user attached.
+fn render_retrieved_chunks(chunks: &[RetrievedChunk]) -> String {
+    let mut text = String::new();
+    if chunks.is_empty() {
+        return text;
+    }
+
+    text.push_str(
+        "\n<retrieved_context>\n\
+        The following code was found by searching the project for the user's message. \
+        Parts of it may not be relevant.\n",
+    );
+    for chunk in chunks {
+        text.push('\n');
+        let _ = write!(text, "{chunk}");
+    }
+    text.push_str("</retrieved_context>\n");
+    text
 }
 
 fn collect_files_in_path(worktree: &Worktree, path: &Path) -> Vec<Arc<Path>> {
@@ -0,0 +1,325 @@
+//! Opens and tokenizes the files a message mentions while it's being typed,
+//! so that attaching them when the message is sent doesn't wait on disk or on
+//! the model's tokenizer.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use collections::HashMap;
+use gpui::{AsyncApp, Context, Entity, Subscription, Task, WeakEntity};
+use language::{Buffer, BufferEvent};
+use language_model::{LanguageModel, LanguageModelRequest, LanguageModelRequestMessage, Role};
+use project::{Project, ProjectPath};
+use util::ResultExt as _;
+
+use crate::context::{AgentContextHandle, ContextId, FileContextHandle, load_context};
+
+/// How long typing has to pause before the mentions in the message are
+/// prefetched.
+const PREFETCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// The maximum number of files kept open for a single message.
+const MAX_PREFETCHED_FILES: usize = 8;
+
+/// Something in a message that may refer to a file in the project.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Mention {
+    /// A path such as `src/main.rs`.
+    Path(String),
+    /// An identifier in backticks, such as `MessageEditor`.
+    Symbol(String),
+}
+
+struct PrefetchedFile {
+    buffer: Entity<Buffer>,
+    /// The number of tokens the file takes up when attached, or `None` if it
+    /// hasn't been counted since the file was last edited.
+    token_count: Option<usize>,
+    _subscription: Subscription,
+}
+
+pub(crate) struct ContextPrefetcher {
+    project: WeakEntity<Project>,
+    files: HashMap<ProjectPath, PrefetchedFile>,
+    /// The file each symbol mention resolved to, so the language server is
+    /// only asked once per symbol.
+    symbol_paths: HashMap<String, Option<ProjectPath>>,
+    prefetch_task: Option<Task<()>>,
+}
+
+impl ContextPrefetcher {
+    pub fn new(project: WeakEntity<Project>) -> Self {
+        Self {
+            project,
+            files: HashMap::default(),
+            symbol_paths: HashMap::default(),
+            prefetch_task: None,
+        }
+    }
+
+    /// The number of tokens the given buffer takes up when attached, if it was
+    /// prefetched and hasn't been edited since.
+    pub fn token_count(&self, buffer: &Entity<Buffer>) -> Option<usize> {
+        self.files
+            .values()
+            .find(|file| &file.buffer == buffer)
+            .and_then(|file| file.token_count)
+    }
+
+    /// Prefetches the files mentioned in the given message text, and releases
+    /// the ones it no longer mentions.
+    pub fn prefetch(
+        &mut self,
+        text: &str,
+        model: Option<Arc<dyn LanguageModel>>,
+        cx: &mut Context<Self>,
+    ) {
+        let mentions = parse_mentions(text);
+        let project = self.project.clone();
+        self.prefetch_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(PREFETCH_DEBOUNCE).await;
+
+            let mut paths = Vec::new();
+            for mention in mentions {
+                if paths.len() == MAX_PREFETCHED_FILES {
+                    break;
+                }
+                let path = match mention {
+                    Mention::Path(path) => project
+                        .read_with(cx, |project, cx| project.find_project_path(&path, cx))
+                        .ok()
+                        .flatten(),
+                    Mention::Symbol(name) => resolve_symbol(&this, &project, name, cx).await,
+                };
+                if let Some(path) = path {
+                    if !paths.contains(&path) {
+                        paths.push(path);
+                    }
+                }
+            }
+
+            let Ok(stale_paths) = this.update(cx, |this, _| {
+                this.files.retain(|path, _| paths.contains(path));
+                paths
+                    .into_iter()
+                    .filter(|path| {
+                        this.files
+                            .get(path)
+                            .is_none_or(|file| file.token_count.is_none())
+                    })
+                    .collect::<Vec<_>>()
+            }) else {
+                return;
+            };
+
+            for path in stale_paths {
+                let Some(buffer) = open_buffer(&this, &project, &path, cx).await else {
+                    continue;
+                };
+                let Some(model) = model.clone() else {
+                    continue;
+                };
+                let Ok(version) = buffer.read_with(cx, |buffer, _| buffer.version()) else {
+                    return;
+                };
+                let Some(token_count) = count_tokens(&project, buffer.clone(), model, cx)
+                    .await
+                    .log_err()
+                else {
+                    continue;
+                };
+                this.update(cx, |this, cx| {
+                    if let Some(file) = this.files.get_mut(&path) {
+                        if file.buffer.read(cx).version() == version {
+                            file.token_count = Some(token_count);
+                        }
+                    }
+                })
+                .ok();
+            }
+        }));
+    }
+}
+
+async fn resolve_symbol(
+    this: &WeakEntity<ContextPrefetcher>,
+    project: &WeakEntity<Project>,
+    name: String,
+    cx: &mut AsyncApp,
+) -> Option<ProjectPath> {
+    if let Some(path) = this
+        .read_with(cx, |this, _| this.symbol_paths.get(&name).cloned())
+        .ok()?
+    {
+        return path;
+    }
+
+    let symbols = project
+        .update(cx, |project, cx| project.symbols(&name, cx))
+        .ok()?
+        .await
+        .log_err()?;
+    let path = symbols
+        .into_iter()
+        .find(|symbol| symbol.name == name)
+        .map(|symbol| symbol.path);
+    this.update(cx, |this, _| {
+        this.symbol_paths.insert(name, path.clone());
+    })
+    .ok();
+    path
+}
+
+async fn open_buffer(
+    this: &WeakEntity<ContextPrefetcher>,
+    project: &WeakEntity<Project>,
+    path: &ProjectPath,
+    cx: &mut AsyncApp,
+) -> Option<Entity<Buffer>> {
+    if let Some(buffer) = this
+        .read_with(cx, |this, _| {
+            this.files.get(path).map(|file| file.buffer.clone())
+        })
+        .ok()?
+    {
+        return Some(buffer);
+    }
+
+    let buffer = project
+        .update(cx, |project, cx| project.open_buffer(path.clone(), cx))
+        .ok()?
+        .await
+        .log_err()?;
+    this.update(cx, |this, cx| {
+        let subscription = cx.subscribe(&buffer, {
+            let path = path.clone();
+            move |this, _, event, _| {
+                if let BufferEvent::Edited = event {
+                    if let Some(file) = this.files.get_mut(&path) {
+                        file.token_count = None;
+                    }
+                }
+            }
+        });
+        this.files.insert(
+            path.clone(),
+            PrefetchedFile {
+                buffer: buffer.clone(),
+                token_count: None,
+                _subscription: subscription,
+            },
+        );
+    })
+    .ok()?;
+    Some(buffer)
+}
+
+/// Counts the tokens the buffer takes up when attached as file context.
+async fn count_tokens(
+    project: &WeakEntity<Project>,
+    buffer: Entity<Buffer>,
+    model: Arc<dyn LanguageModel>,
+    cx: &mut AsyncApp,
+) -> Result<usize> {
+    let project = project
+        .upgrade()
+        .ok_or_else(|| anyhow!("project dropped"))?;
+    let context = AgentContextHandle::File(FileContextHandle {
+        buffer,
+        context_id: ContextId::zero(),
+    });
+    let loaded = cx
+        .update(|cx| load_context(vec![context], &project, &None, cx))?
+        .await;
+
+    let mut message = LanguageModelRequestMessage {
+        role: Role::User,
+        content: Vec::new(),
+        cache: false,
+    };
+    loaded.loaded_context.add_to_request_message(&mut message);
+    let request = LanguageModelRequest {
+        messages: vec![message],
+        ..Default::default()
+    };
+    cx.update(|cx| model.count_tokens(request, cx))?.await
+}
+
+/// Finds the paths and backticked identifiers in a message, in the order they
+/// appear.
+fn parse_mentions(text: &str) -> Vec<Mention> {
+    let mut mentions = Vec::new();
+
+    for (ix, quoted) in text.split('`').enumerate() {
+        if ix % 2 == 1 {
+            // For paths such as `MessageEditor::new`, the type is a better
+            // guess at the file than the method.
+            let name = quoted
+                .rsplit("::")
+                .find(|segment| segment.starts_with(char::is_uppercase))
+                .or_else(|| quoted.rsplit("::").next())
+                .unwrap_or(quoted);
+            if name.len() >= 3
+                && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+            {
+                mentions.push(Mention::Symbol(name.to_string()));
+                continue;
+            }
+        }
+
+        for word in quoted.split_whitespace() {
+            let word = word
+                .trim_start_matches(['(', '[', '{', '"', '\'', '@'])
+                .trim_end_matches([')', ']', '}', '"', '\'', ',', ';', ':', '.', '!', '?']);
+            if is_path_like(word) {
+                mentions.push(Mention::Path(word.to_string()));
+            }
+        }
+    }
+
+    mentions.dedup();
+    mentions
+}
+
+fn is_path_like(word: &str) -> bool {
+    if word.contains("://") {
+        return false;
+    }
+    let file_name = word.rsplit('/').next().unwrap_or(word);
+    let has_extension = file_name.rsplit_once('.').is_some_and(|(stem, extension)| {
+        !stem.is_empty()
+            && extension.starts_with(char::is_alphabetic)
+            && extension.chars().all(char::is_alphanumeric)
+    });
+    has_extension || (word.contains('/') && !file_name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mentions() {
+        assert_eq!(
+            parse_mentions(
+                "Why does `MessageEditor::new` panic in crates/agent/src/message_editor.rs? \
+                 See (Cargo.toml), https://example.com/a.html and `let x = 1`."
+            ),
+            [
+                Mention::Symbol("MessageEditor".into()),
+                Mention::Path("crates/agent/src/message_editor.rs".into()),
+                Mention::Path("Cargo.toml".into()),
+            ]
+        );
+        assert_eq!(
+            parse_mentions("Rename `parse_mentions` in src/. Use @docs/readme"),
+            [
+                Mention::Symbol("parse_mentions".into()),
+                Mention::Path("docs/readme".into()),
+            ]
+        );
+        assert_eq!(parse_mentions("Fix it, then ship v1.0 today."), []);
+    }
+}
@@ -1,10 +1,14 @@
 use std::sync::Arc;
 
 use anyhow::{Result, anyhow, bail};
-use assistant_tool::{ActionLog, Tool, ToolResult, ToolSource};
+use assistant_tool::{
+    ActionLog, Tool, ToolResult, ToolResultContent, ToolResultOutput, ToolSource,
+};
 use context_server::{ContextServerId, types};
 use gpui::{AnyWindowHandle, App, Entity, Task};
-use language_model::{LanguageModel, LanguageModelRequest, LanguageModelToolSchemaFormat};
+use language_model::{
+    LanguageModel, LanguageModelImage, LanguageModelRequest, LanguageModelToolSchemaFormat,
+};
 use project::{Project, context_server_store::ContextServerStore};
 use ui::IconName;
 
@@ -88,7 +92,7 @@ impl Tool for ContextServerTool {
             let server_clone = server.clone();
             let input_clone = input.clone();
 
-            cx.spawn(async move |_cx| {
+            cx.spawn(async move |cx| {
                 let Some(protocol) = server_clone.client() else {
                     bail!("Context server not initialized");
                 };
@@ -115,19 +119,48 @@ impl Tool for ContextServerTool {
                     .await?;
 
                 let mut result = String::new();
+                let mut image = None;
                 for content in response.content {
                     match content {
                         types::ToolResponseContent::Text { text } => {
                             result.push_str(&text);
                         }
-                        types::ToolResponseContent::Image { .. } => {
-                            log::warn!("Ignoring image content from tool response");
+                        types::ToolResponseContent::Image { data, mime_type } => {
+                            if image.is_some() {
+                                log::warn!("Ignoring additional image from tool response");
+                            } else {
+                                image = Some((data, mime_type));
+                            }
                         }
                         types::ToolResponseContent::Resource { .. } => {
                             log::warn!("Ignoring resource content from tool response");
                         }
                     }
                 }
+
+                // A tool result has a single kind of content, and an image
+                // is usually what the tool was called for.
+                if let Some((data, mime_type)) = image {
+                    let image = cx
+                        .update(|cx| LanguageModelImage::from_base64(&data, &mime_type, cx))?
+                        .await;
+                    match image {
+                        Some(image) => {
+                            if !result.is_empty() {
+                                log::warn!(
+                                    "Ignoring text content from tool response with an image"
+                                );
+                            }
+                            return Ok(ToolResultOutput {
+                                content: ToolResultContent::Image(image),
+                                output: None,
+                            });
+                        }
+                        None => {
+                            log::warn!("Ignoring unsupported {mime_type} image from tool response")
+                        }
+                    }
+                }
                 Ok(result.into())
             })
             .into()
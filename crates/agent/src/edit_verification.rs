@@ -0,0 +1,203 @@
+//! Runs the `verify_edits` check after the agent edits files, to find the
+//! errors its edits introduced.
+
+use agent_settings::EditVerificationSettings;
+use anyhow::{Context as _, Result};
+use gpui::{App, AppContext as _, Task};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use util::command::new_smol_command;
+
+/// The maximum number of errors included in the message asking the model to
+/// fix them.
+const MAX_REPORTED_ERRORS: usize = 20;
+
+/// An error reported by the check command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CheckError {
+    pub path: PathBuf,
+    pub row: u32,
+    /// The error without its location, such as `error[E0425]: cannot find
+    /// value`.
+    pub message: String,
+}
+
+/// Runs the check command in the given directory and returns its output.
+pub(crate) fn run_check(
+    settings: &EditVerificationSettings,
+    cwd: &Path,
+    cx: &App,
+) -> Task<Result<String>> {
+    let mut command = new_smol_command(&settings.command);
+    command
+        .args(&settings.args)
+        .current_dir(cwd)
+        .kill_on_drop(true);
+    let program = settings.command.clone();
+    cx.background_spawn(async move {
+        let output = command
+            .output()
+            .await
+            .with_context(|| format!("failed to run {program}"))?;
+        // Compilers report errors on either stream.
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(text)
+    })
+}
+
+/// Parses the errors in the check's output, from lines such as
+/// `src/main.rs:3:5: error[E0425]: ...` or `src/main.ts(3,5): error TS2304: ...`.
+pub(crate) fn parse_check_errors(output: &str) -> Vec<CheckError> {
+    let mut errors = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        let Some(ix) = line.find(": error") else {
+            continue;
+        };
+        let (location, message) = (&line[..ix], &line[ix + 2..]);
+        let (path, row) = if let Some((path, position)) = location
+            .strip_suffix(')')
+            .and_then(|location| location.rsplit_once('('))
+        {
+            let row = position.split(',').next().unwrap_or_default();
+            (path, row)
+        } else {
+            let mut parts = location.rsplitn(3, ':');
+            let (Some(last), Some(second)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            match parts.next() {
+                Some(path) => (path, second),
+                None => (second, last),
+            }
+        };
+        let Ok(row) = row.parse::<u32>() else {
+            continue;
+        };
+        if path.is_empty() || path.contains(char::is_whitespace) {
+            continue;
+        }
+        errors.push(CheckError {
+            path: PathBuf::from(path.trim_start_matches("./")),
+            row,
+            message: message.to_string(),
+        });
+    }
+    errors.dedup();
+    errors
+}
+
+/// Returns the errors in the edited files that weren't reported before the
+/// edits. Errors are compared without their row, which the edits can shift.
+pub(crate) fn new_errors(
+    errors: Vec<CheckError>,
+    baseline: &[CheckError],
+    edited_paths: &[PathBuf],
+) -> Vec<CheckError> {
+    errors
+        .into_iter()
+        .filter(|error| {
+            edited_paths
+                .iter()
+                .any(|path| error.path.ends_with(path) || path.ends_with(&error.path))
+                && !baseline.iter().any(|existing| {
+                    existing.path == error.path && existing.message == error.message
+                })
+        })
+        .collect()
+}
+
+/// Returns the message asking the model to fix the errors.
+pub(crate) fn fix_prompt(command: &str, errors: &[CheckError]) -> String {
+    let mut prompt = format!("Running `{command}` after your edits reported new errors:\n\n");
+    for error in errors.iter().take(MAX_REPORTED_ERRORS) {
+        writeln!(
+            prompt,
+            "- {}:{}: {}",
+            error.path.display(),
+            error.row,
+            error.message
+        )
+        .unwrap();
+    }
+    if errors.len() > MAX_REPORTED_ERRORS {
+        writeln!(
+            prompt,
+            "- ...and {} more",
+            errors.len() - MAX_REPORTED_ERRORS
+        )
+        .unwrap();
+    }
+    prompt.push_str("\nPlease fix these errors.");
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_parse_check_errors() {
+        let output = indoc! {"
+                Checking app v0.1.0
+            src/main.rs:3:5: error[E0425]: cannot find value `x` in this scope
+            src/main.rs:8:1: warning: unused import
+            ./src/lib.c:10: error: expected ';'
+            web/app.ts(4,12): error TS2304: Cannot find name 'foo'.
+            error: could not compile `app` (bin \"app\") due to 1 previous error
+        "};
+        assert_eq!(
+            parse_check_errors(output),
+            [
+                CheckError {
+                    path: "src/main.rs".into(),
+                    row: 3,
+                    message: "error[E0425]: cannot find value `x` in this scope".into(),
+                },
+                CheckError {
+                    path: "src/lib.c".into(),
+                    row: 10,
+                    message: "error: expected ';'".into(),
+                },
+                CheckError {
+                    path: "web/app.ts".into(),
+                    row: 4,
+                    message: "error TS2304: Cannot find name 'foo'.".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_new_errors() {
+        let error = |path: &str, row, message: &str| CheckError {
+            path: path.into(),
+            row,
+            message: message.into(),
+        };
+        let baseline = [error("crates/a/src/lib.rs", 3, "error: old")];
+        let errors = vec![
+            error("crates/a/src/lib.rs", 7, "error: old"),
+            error("crates/a/src/lib.rs", 9, "error: new"),
+            error("crates/b/src/lib.rs", 1, "error: untouched"),
+        ];
+        assert_eq!(
+            new_errors(errors, &baseline, &["crates/a/src/lib.rs".into()]),
+            [error("crates/a/src/lib.rs", 9, "error: new")]
+        );
+        assert_eq!(
+            fix_prompt(
+                "cargo check",
+                &[error("crates/a/src/lib.rs", 9, "error: new")]
+            ),
+            indoc! {"
+                Running `cargo check` after your edits reported new errors:
+
+                - crates/a/src/lib.rs:9: error: new
+
+                Please fix these errors."}
+        );
+    }
+}
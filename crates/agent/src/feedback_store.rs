@@ -0,0 +1,162 @@
+//! Keeps the ratings given to the agent's answers on disk, so that models can
+//! be compared on them and the eval harness can use them as labeled examples.
+
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use collections::HashMap;
+use fs::Fs;
+use gpui::{App, Task};
+use language_model::LanguageModel;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use util::ResultExt as _;
+
+use crate::thread::{ThreadFeedback, ThreadId};
+
+/// A rating of one of the agent's answers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeedbackRecord {
+    pub thread_id: ThreadId,
+    pub message_id: usize,
+    pub rating: ThreadFeedback,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The model that gave the answer, as `provider/model`.
+    pub model: Option<String>,
+    /// Identifies answers to the same prompt, across threads and models.
+    pub prompt_hash: String,
+    pub prompt: String,
+    pub answer: String,
+    pub created_at: DateTime<Utc>,
+}
+
+fn feedback_path() -> PathBuf {
+    paths::data_dir()
+        .join("feedback")
+        .join("agent_feedback.jsonl")
+}
+
+/// Identifies the model as `provider/model`.
+pub(crate) fn model_key(model: &dyn LanguageModel) -> String {
+    format!("{}/{}", model.provider_id().0, model.id().0)
+}
+
+pub(crate) fn prompt_hash(prompt: &str) -> String {
+    format!("{:x}", Sha256::digest(prompt.trim().as_bytes()))
+}
+
+/// Appends the record to the feedback store.
+pub(crate) fn record(record: FeedbackRecord, cx: &App) -> Task<Result<()>> {
+    cx.background_spawn(async move {
+        let path = feedback_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?
+            .write_all(line.as_bytes())?;
+        Ok(())
+    })
+}
+
+/// Loads the records of the feedback store, keeping each message's latest.
+pub(crate) fn load(cx: &App) -> Task<Vec<FeedbackRecord>> {
+    cx.background_spawn(async move {
+        std::fs::read_to_string(feedback_path())
+            .map(|text| parse_feedback(&text))
+            .unwrap_or_default()
+    })
+}
+
+/// Parses the records of a feedback store or export. A message rated more
+/// than once keeps its latest rating, and unreadable lines are skipped.
+pub fn parse_feedback(text: &str) -> Vec<FeedbackRecord> {
+    let mut indices = HashMap::default();
+    let mut records = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let Some(record) = serde_json::from_str::<FeedbackRecord>(line).log_err() else {
+            continue;
+        };
+        let key = (record.thread_id.clone(), record.message_id);
+        if let Some(&ix) = indices.get(&key) {
+            records[ix] = record;
+        } else {
+            indices.insert(key, records.len());
+            records.push(record);
+        }
+    }
+    records
+}
+
+/// Asks where to save the feedback and writes it there as JSON lines.
+pub(crate) fn export(fs: Arc<dyn Fs>, cx: &mut App) {
+    let path = cx.prompt_for_new_path(paths::home_dir());
+    cx.spawn(async move |_| {
+        let Some(mut path) = path.await?? else {
+            return Ok(());
+        };
+        if path.extension().is_none() {
+            path.set_extension("jsonl");
+        }
+        let text = fs.load(&feedback_path()).await.unwrap_or_default();
+        let mut export = String::new();
+        for record in parse_feedback(&text) {
+            export.push_str(&serde_json::to_string(&record)?);
+            export.push('\n');
+        }
+        fs.atomic_write(path, export).await
+    })
+    .detach_and_log_err(cx);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(message_id: usize, rating: ThreadFeedback, reason: Option<&str>) -> FeedbackRecord {
+        FeedbackRecord {
+            thread_id: ThreadId::from("thread"),
+            message_id,
+            rating,
+            reason: reason.map(str::to_string),
+            model: Some("anthropic/claude-3-7-sonnet-latest".to_string()),
+            prompt_hash: prompt_hash("Fix the failing test"),
+            prompt: "Fix the failing test".to_string(),
+            answer: "Done.".to_string(),
+            created_at: DateTime::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_feedback() {
+        let lines = [
+            record(1, ThreadFeedback::Positive, None),
+            record(3, ThreadFeedback::Positive, None),
+            record(3, ThreadFeedback::Negative, Some("Broke another test")),
+        ]
+        .iter()
+        .map(|record| serde_json::to_string(record).unwrap())
+        .collect::<Vec<_>>();
+        let text = format!("{}\nnot a record\n{}\n{}\n", lines[0], lines[1], lines[2]);
+
+        assert_eq!(
+            parse_feedback(&text),
+            [
+                record(1, ThreadFeedback::Positive, None),
+                record(3, ThreadFeedback::Negative, Some("Broke another test")),
+            ]
+        );
+        assert_eq!(
+            prompt_hash("  Fix the failing test\n"),
+            prompt_hash("Fix the failing test")
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::ops::Range;
+
+use assistant_tool::outline;
+use gpui::SharedString;
+use language::{BufferSnapshot, OutlineItem};
+use text::Point;
+
+/// The number of bytes of a large file to include in the context. Once it is
+/// reached, the least relevant chunks of the file are dropped.
+pub const FILE_CHUNK_BUDGET: usize = outline::AUTO_OUTLINE_SIZE;
+
+/// Outline items spanning more rows than this are split along the items
+/// nested within them, such as the methods of an impl block.
+const MAX_CHUNK_ROWS: u32 = 150;
+
+/// A piece of a large file, delimited by the syntax tree.
+#[derive(Debug, Clone)]
+pub struct FileChunk {
+    /// Describes the chunk's contents, such as the signature of a function.
+    pub label: SharedString,
+    /// The rows of the file in the chunk, with an exclusive end.
+    pub row_range: Range<u32>,
+    pub text: SharedString,
+    /// Whether the chunk fits within the [`FILE_CHUNK_BUDGET`].
+    pub included: bool,
+}
+
+impl FileChunk {
+    pub fn line_range(&self) -> Range<Point> {
+        Point::new(self.row_range.start, 0)..Point::new(self.row_range.end.saturating_sub(1), 0)
+    }
+}
+
+/// Splits a file into chunks along the boundaries of its outline items, and
+/// selects which of them fit within the budget in file order.
+pub fn chunk_file(snapshot: &BufferSnapshot, items: &[OutlineItem<Point>]) -> Vec<FileChunk> {
+    let max_point = snapshot.max_point();
+    let mut chunks = chunk_rows(items, max_point.row + 1)
+        .into_iter()
+        .map(|(label, row_range)| {
+            let start = Point::new(row_range.start, 0);
+            let end = if row_range.end > max_point.row {
+                max_point
+            } else {
+                Point::new(row_range.end, 0)
+            };
+            FileChunk {
+                label: label.into(),
+                text: snapshot
+                    .text_for_range(start..end)
+                    .collect::<String>()
+                    .into(),
+                row_range,
+                included: false,
+            }
+        })
+        .collect::<Vec<_>>();
+    select_chunks(&mut chunks, &[]);
+    chunks
+}
+
+/// Marks the chunks that fit within the [`FILE_CHUNK_BUDGET`]. The chunks
+/// overlapping the most relevant rows are kept first, and the rest are kept in
+/// file order.
+pub fn select_chunks(chunks: &mut [FileChunk], relevant_rows: &[(Range<u32>, f32)]) {
+    let mut order = (0..chunks.len()).collect::<Vec<_>>();
+    if !relevant_rows.is_empty() {
+        let scores = chunks
+            .iter()
+            .map(|chunk| {
+                relevant_rows
+                    .iter()
+                    .filter(|(rows, _)| {
+                        rows.start < chunk.row_range.end && chunk.row_range.start < rows.end
+                    })
+                    .map(|(_, score)| *score)
+                    .fold(0., f32::max)
+            })
+            .collect::<Vec<_>>();
+        // The sort is stable, so chunks that are equally relevant stay in file order.
+        order.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+    }
+
+    let mut size = 0;
+    for ix in order {
+        let chunk = &mut chunks[ix];
+        chunk.included = size + chunk.text.len() <= FILE_CHUNK_BUDGET;
+        if chunk.included {
+            size += chunk.text.len();
+        }
+    }
+}
+
+/// The rows of the text spanned by a byte range, with an exclusive end. A
+/// range ending with a newline doesn't span the row after it.
+pub fn rows_for_range(text: &str, range: Range<usize>) -> Range<u32> {
+    let row_at = |offset: usize| {
+        text.as_bytes()[..offset]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count() as u32
+    };
+    let start = range.start.min(text.len());
+    let last = range.end.min(text.len()).saturating_sub(1).max(start);
+    row_at(start)..row_at(last) + 1
+}
+
+/// Splits the rows of a file into labeled chunks that each end with an outline
+/// item, so that the comments and attributes preceding an item stay with it.
+fn chunk_rows(items: &[OutlineItem<Point>], row_count: u32) -> Vec<(String, Range<u32>)> {
+    struct Ancestor {
+        depth: usize,
+        label: String,
+        end_row: u32,
+        is_split: bool,
+    }
+
+    let mut chunks = Vec::new();
+    let mut start_row = 0;
+    let mut ancestors: Vec<Ancestor> = Vec::new();
+    let mut push_chunk = |label: String, end_row: u32, start_row: &mut u32| {
+        let end_row = end_row.min(row_count);
+        if end_row > *start_row {
+            chunks.push((label, *start_row..end_row));
+            *start_row = end_row;
+        }
+    };
+
+    for (ix, item) in items.iter().enumerate() {
+        // Close the ancestors this item isn't nested in, keeping the rest of
+        // a split item (like its closing brace) in a chunk of its own.
+        while let Some(ancestor) = ancestors.pop_if(|ancestor| ancestor.depth >= item.depth) {
+            if ancestor.is_split {
+                push_chunk(ancestor.label, ancestor.end_row + 1, &mut start_row);
+            }
+        }
+
+        let label = match ancestors.last() {
+            Some(parent) => format!("{} > {}", parent.label, item.text),
+            None => item.text.clone(),
+        };
+        let has_children = items
+            .get(ix + 1)
+            .is_some_and(|next| next.depth > item.depth);
+        let is_split = has_children && item.range.end.row - item.range.start.row > MAX_CHUNK_ROWS;
+        let is_boundary = !is_split && ancestors.iter().all(|ancestor| ancestor.is_split);
+        if is_boundary && item.range.start.row >= start_row {
+            push_chunk(label.clone(), item.range.end.row + 1, &mut start_row);
+        }
+
+        ancestors.push(Ancestor {
+            depth: item.depth,
+            label,
+            end_row: item.range.end.row,
+            is_split,
+        });
+    }
+    while let Some(ancestor) = ancestors.pop() {
+        if ancestor.is_split {
+            push_chunk(ancestor.label, ancestor.end_row + 1, &mut start_row);
+        }
+    }
+
+    if start_row < row_count {
+        let label = format!("lines {}-{}", start_row + 1, row_count);
+        push_chunk(label, row_count, &mut start_row);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(depth: usize, text: &str, rows: Range<u32>) -> OutlineItem<Point> {
+        OutlineItem {
+            depth,
+            range: Point::new(rows.start, 0)..Point::new(rows.end, 1),
+            text: text.to_string(),
+            highlight_ranges: Vec::new(),
+            name_ranges: Vec::new(),
+            body_range: None,
+            annotation_range: None,
+        }
+    }
+
+    fn chunk(label: &str, text: &str, row_range: Range<u32>) -> FileChunk {
+        FileChunk {
+            label: label.to_string().into(),
+            row_range,
+            text: text.to_string().into(),
+            included: false,
+        }
+    }
+
+    #[test]
+    fn test_chunk_rows() {
+        let items = [
+            item(0, "struct Foo", 2..4),
+            item(0, "impl Foo", 6..400),
+            item(1, "fn new", 7..20),
+            item(1, "fn run", 22..398),
+            item(0, "fn main", 402..410),
+        ];
+        assert_eq!(
+            chunk_rows(&items, 420),
+            vec![
+                ("struct Foo".to_string(), 0..5),
+                ("impl Foo > fn new".to_string(), 5..21),
+                ("impl Foo > fn run".to_string(), 21..399),
+                ("impl Foo".to_string(), 399..401),
+                ("fn main".to_string(), 401..411),
+                ("lines 412-420".to_string(), 411..420),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select_chunks() {
+        let filler = "x".repeat(FILE_CHUNK_BUDGET / 2);
+        let mut chunks = vec![
+            chunk(
+                "fn parse_config",
+                &format!("fn parse_config() {{ {filler} }}"),
+                0..10,
+            ),
+            chunk(
+                "fn render_html",
+                &format!("fn render_html() {{ {filler} }}"),
+                10..20,
+            ),
+            chunk(
+                "fn send_request",
+                &format!("fn send_request() {{ {filler} }}"),
+                20..30,
+            ),
+        ];
+
+        select_chunks(&mut chunks, &[]);
+        let included = |chunks: &[FileChunk]| {
+            chunks
+                .iter()
+                .map(|chunk| chunk.included)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(included(&chunks), [true, false, false]);
+
+        select_chunks(&mut chunks, &[(12..14, 0.8), (25..32, 0.4)]);
+        assert_eq!(included(&chunks), [false, true, false]);
+
+        select_chunks(&mut chunks, &[(12..14, 0.3), (25..32, 0.6)]);
+        assert_eq!(included(&chunks), [false, false, true]);
+    }
+
+    #[test]
+    fn test_rows_for_range() {
+        let text = "fn a() {}\n\nfn b() {\n    c();\n}\n";
+        assert_eq!(rows_for_range(text, 0..9), 0..1);
+        assert_eq!(rows_for_range(text, 11..text.len() - 1), 2..5);
+        assert_eq!(rows_for_range(text, 11..usize::MAX), 2..5);
+        assert_eq!(rows_for_range(text, 10..10), 1..2);
+    }
+}
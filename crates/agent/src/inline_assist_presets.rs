@@ -0,0 +1,143 @@
+use gpui::{App, Entity, SharedString};
+use prompt_store::{PromptId, PromptStore};
+use std::ops::Range;
+
+/// Rules in the prompt library whose title starts with this prefix are offered
+/// as presets in the inline assistant, next to the built-in ones.
+pub const PRESET_TITLE_PREFIX: &str = "Inline: ";
+
+const BUILT_IN_PRESETS: &[(&str, &str)] = &[
+    (
+        "Refactor",
+        "Refactor this code to make it easier to read, without changing its behavior.",
+    ),
+    (
+        "Add Error Handling",
+        "Add error handling to this code, following how errors are handled in the surrounding code.",
+    ),
+    (
+        "Add Docs",
+        "Add documentation comments to this code, following the conventions of the language.",
+    ),
+    (
+        "Optimize",
+        "Optimize this code for performance, without changing its behavior.",
+    ),
+    ("Translate", "Translate this code to {{language}}."),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InlineAssistPresetSource {
+    BuiltIn(&'static str),
+    PromptLibrary(PromptId),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InlineAssistPreset {
+    pub title: SharedString,
+    pub source: InlineAssistPresetSource,
+}
+
+/// Returns the built-in presets, followed by the presets defined in the prompt
+/// library.
+pub fn presets(prompt_store: Option<&Entity<PromptStore>>, cx: &App) -> Vec<InlineAssistPreset> {
+    let mut presets = BUILT_IN_PRESETS
+        .iter()
+        .map(|(title, template)| InlineAssistPreset {
+            title: SharedString::new_static(title),
+            source: InlineAssistPresetSource::BuiltIn(template),
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(prompt_store) = prompt_store {
+        let mut library_presets = prompt_store
+            .read(cx)
+            .all_prompt_metadata()
+            .into_iter()
+            .filter_map(|metadata| {
+                let title = metadata
+                    .title?
+                    .strip_prefix(PRESET_TITLE_PREFIX)?
+                    .trim()
+                    .to_string();
+                (!title.is_empty()).then(|| InlineAssistPreset {
+                    title: title.into(),
+                    source: InlineAssistPresetSource::PromptLibrary(metadata.id),
+                })
+            })
+            .collect::<Vec<_>>();
+        library_presets.sort_by(|a, b| a.title.cmp(&b.title));
+        presets.extend(library_presets);
+    }
+
+    presets
+}
+
+/// Finds the preset with the given title, ignoring case.
+pub fn find_preset(
+    title: &str,
+    prompt_store: Option<&Entity<PromptStore>>,
+    cx: &App,
+) -> Option<InlineAssistPreset> {
+    presets(prompt_store, cx)
+        .into_iter()
+        .find(|preset| preset.title.eq_ignore_ascii_case(title))
+}
+
+/// A preset's prompt, with each of its parameter slots replaced by the slot's
+/// name so that it can be selected and typed over.
+#[derive(Debug, PartialEq)]
+pub struct ExpandedPreset {
+    pub text: String,
+    pub slots: Vec<Range<usize>>,
+}
+
+/// Expands the parameter slots in a preset's prompt, written as `{{name}}`.
+pub fn expand_slots(template: &str) -> ExpandedPreset {
+    let mut text = String::with_capacity(template.len());
+    let mut slots = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim();
+        text.push_str(&rest[..start]);
+        let slot_start = text.len();
+        text.push_str(name);
+        slots.push(slot_start..text.len());
+        rest = &rest[start + 2 + len + 2..];
+    }
+    text.push_str(rest);
+    ExpandedPreset { text, slots }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_slots() {
+        assert_eq!(
+            expand_slots("Translate this code to {{language}}."),
+            ExpandedPreset {
+                text: "Translate this code to language.".into(),
+                slots: vec![23..31],
+            }
+        );
+        assert_eq!(
+            expand_slots("Rename {{ old }} to {{new}}"),
+            ExpandedPreset {
+                text: "Rename old to new".into(),
+                slots: vec![7..10, 14..17],
+            }
+        );
+        assert_eq!(
+            expand_slots("Unterminated {{slot"),
+            ExpandedPreset {
+                text: "Unterminated {{slot".into(),
+                slots: Vec::new(),
+            }
+        );
+    }
+}
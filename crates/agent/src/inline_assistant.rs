@@ -485,6 +485,7 @@ impl InlineAssistant {
                     workspace.clone(),
                     thread_store.clone(),
                     text_thread_store.clone(),
+                    prompt_store.clone(),
                     window,
                     cx,
                 )
@@ -584,7 +585,7 @@ impl InlineAssistant {
                 initial_transaction_id,
                 context_store.clone(),
                 project,
-                prompt_store,
+                prompt_store.clone(),
                 self.telemetry.clone(),
                 self.prompt_builder.clone(),
                 cx,
@@ -604,6 +605,7 @@ impl InlineAssistant {
                 workspace.downgrade(),
                 thread_store,
                 text_thread_store,
+                prompt_store,
                 window,
                 cx,
             )
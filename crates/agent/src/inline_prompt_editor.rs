@@ -4,10 +4,14 @@ use crate::context::ContextCreasesAddon;
 use crate::context_picker::{ContextPicker, ContextPickerCompletionProvider};
 use crate::context_store::ContextStore;
 use crate::context_strip::{ContextStrip, ContextStripEvent, SuggestContextKind};
+use crate::inline_assist_presets::{self, InlineAssistPreset, InlineAssistPresetSource};
 use crate::message_editor::{extract_message_creases, insert_message_creases};
 use crate::terminal_codegen::TerminalCodegen;
 use crate::thread_store::{TextThreadStore, ThreadStore};
-use crate::{CycleNextInlineAssist, CyclePreviousInlineAssist, ModelUsageContext};
+use crate::{
+    CycleNextInlineAssist, CyclePreviousInlineAssist, InsertInlineAssistPreset, ModelUsageContext,
+    ToggleInlineAssistPresets,
+};
 use crate::{RemoveAllContext, ToggleContextPicker};
 use assistant_context_editor::language_model_selector::ToggleModelSelector;
 use client::ErrorExt;
@@ -27,6 +31,7 @@ use gpui::{
 };
 use language_model::{LanguageModel, LanguageModelRegistry};
 use parking_lot::Mutex;
+use prompt_store::PromptStore;
 use settings::Settings;
 use std::cmp;
 use std::rc::Rc;
@@ -34,7 +39,8 @@ use std::sync::Arc;
 use theme::ThemeSettings;
 use ui::utils::WithRemSize;
 use ui::{
-    CheckboxWithLabel, IconButtonShape, KeyBinding, Popover, PopoverMenuHandle, Tooltip, prelude::*,
+    CheckboxWithLabel, ContextMenu, IconButtonShape, KeyBinding, Popover, PopoverMenu,
+    PopoverMenuHandle, Tooltip, prelude::*,
 };
 use workspace::Workspace;
 
@@ -45,6 +51,8 @@ pub struct PromptEditor<T> {
     context_strip: Entity<ContextStrip>,
     context_picker_menu_handle: PopoverMenuHandle<ContextPicker>,
     model_selector: Entity<AgentModelSelector>,
+    prompt_store: Option<Entity<PromptStore>>,
+    presets_menu_handle: PopoverMenuHandle<ContextMenu>,
     edited_since_done: bool,
     prompt_history: VecDeque<String>,
     prompt_history_ix: Option<usize>,
@@ -124,6 +132,8 @@ impl<T: 'static> Render for PromptEditor<T> {
                     .on_action(cx.listener(Self::move_up))
                     .on_action(cx.listener(Self::move_down))
                     .on_action(cx.listener(Self::remove_all_context))
+                    .on_action(cx.listener(Self::toggle_presets_menu))
+                    .on_action(cx.listener(Self::insert_preset_by_title))
                     .capture_action(cx.listener(Self::cycle_prev))
                     .capture_action(cx.listener(Self::cycle_next))
                     .child(
@@ -214,7 +224,13 @@ impl<T: 'static> Render for PromptEditor<T> {
                             .items_start()
                             .justify_between()
                             .child(self.context_strip.clone())
-                            .child(self.model_selector.clone()),
+                            .child(
+                                h_flex()
+                                    .gap_1()
+                                    .children(self.render_remote_host_indicator())
+                                    .child(self.render_presets_menu(cx))
+                                    .child(self.model_selector.clone()),
+                            ),
                     ),
             )
     }
@@ -229,6 +245,36 @@ impl<T: 'static> Focusable for PromptEditor<T> {
 impl<T: 'static> PromptEditor<T> {
     const MAX_LINES: u8 = 8;
 
+    fn render_remote_host_indicator(&self) -> Option<AnyElement> {
+        let PromptEditorMode::Terminal {
+            remote_host: Some(remote_host),
+            ..
+        } = &self.mode
+        else {
+            return None;
+        };
+
+        let tooltip = SharedString::from(format!("Commands are generated for {remote_host}"));
+        Some(
+            h_flex()
+                .id("remote-host")
+                .flex_shrink_0()
+                .gap_1()
+                .child(
+                    Icon::new(IconName::Server)
+                        .size(IconSize::XSmall)
+                        .color(Color::Muted),
+                )
+                .child(
+                    Label::new(remote_host.clone())
+                        .size(LabelSize::Small)
+                        .color(Color::Muted),
+                )
+                .tooltip(Tooltip::text(tooltip))
+                .into_any_element(),
+        )
+    }
+
     fn codegen_status<'a>(&'a self, cx: &'a App) -> &'a CodegenStatus {
         match &self.mode {
             PromptEditorMode::Buffer { codegen, .. } => codegen.read(cx).status(cx),
@@ -763,6 +809,134 @@ impl<T: 'static> PromptEditor<T> {
         )
     }
 
+    fn toggle_presets_menu(
+        &mut self,
+        _: &ToggleInlineAssistPresets,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.presets_menu_handle.toggle(window, cx);
+    }
+
+    fn insert_preset_by_title(
+        &mut self,
+        action: &InsertInlineAssistPreset,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        match inline_assist_presets::find_preset(&action.title, self.prompt_store.as_ref(), cx) {
+            Some(preset) => self.insert_preset(preset, window, cx),
+            None => log::warn!("no inline assist preset named {:?}", action.title),
+        }
+    }
+
+    fn insert_preset(
+        &mut self,
+        preset: InlineAssistPreset,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        match preset.source {
+            InlineAssistPresetSource::BuiltIn(template) => {
+                self.insert_preset_prompt(template, window, cx);
+            }
+            InlineAssistPresetSource::PromptLibrary(prompt_id) => {
+                let Some(prompt_store) = self.prompt_store.as_ref() else {
+                    return;
+                };
+                let load = prompt_store.read(cx).load(prompt_id, cx);
+                cx.spawn_in(window, async move |this, cx| {
+                    let template = load.await?;
+                    this.update_in(cx, |this, window, cx| {
+                        this.insert_preset_prompt(&template, window, cx);
+                    })
+                })
+                .detach_and_log_err(cx);
+            }
+        }
+    }
+
+    /// Replaces the prompt with the preset's, selecting its first parameter
+    /// slot so that it can be filled in right away.
+    fn insert_preset_prompt(
+        &mut self,
+        template: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let expanded = inline_assist_presets::expand_slots(template.trim());
+        let end = expanded.text.len();
+        let selection = expanded.slots.first().cloned().unwrap_or(end..end);
+        self.editor.update(cx, |editor, cx| {
+            editor.set_text(expanded.text, window, cx);
+            editor.change_selections(None, window, cx, |selections| {
+                selections.select_ranges([selection]);
+            });
+        });
+        window.focus(&self.editor.focus_handle(cx));
+    }
+
+    fn render_presets_menu(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let this = cx.entity().downgrade();
+        let prompt_store = self.prompt_store.clone();
+        let focus_handle = self.editor.focus_handle(cx);
+
+        PopoverMenu::new("inline-assist-presets")
+            .trigger_with_tooltip(
+                IconButton::new("inline-assist-presets-trigger", IconName::Book)
+                    .icon_size(IconSize::XSmall)
+                    .icon_color(Color::Muted),
+                move |window, cx| {
+                    Tooltip::for_action_in(
+                        "Insert Preset",
+                        &ToggleInlineAssistPresets,
+                        &focus_handle,
+                        window,
+                        cx,
+                    )
+                },
+            )
+            .anchor(gpui::Corner::BottomRight)
+            .with_handle(self.presets_menu_handle.clone())
+            .menu(move |window, cx| {
+                let presets = inline_assist_presets::presets(prompt_store.as_ref(), cx);
+                let this = this.clone();
+                Some(ContextMenu::build(
+                    window,
+                    cx,
+                    move |mut menu, _window, _cx| {
+                        let mut in_library = false;
+                        for preset in presets {
+                            if !in_library
+                                && matches!(
+                                    preset.source,
+                                    InlineAssistPresetSource::PromptLibrary(_)
+                                )
+                            {
+                                in_library = true;
+                                menu = menu.separator().header("From Rules Library");
+                            }
+                            let this = this.clone();
+                            let action = InsertInlineAssistPreset {
+                                title: preset.title.to_string(),
+                            };
+                            menu = menu.entry(
+                                preset.title.clone(),
+                                Some(Box::new(action)),
+                                move |window, cx| {
+                                    this.update(cx, |this, cx| {
+                                        this.insert_preset(preset.clone(), window, cx);
+                                    })
+                                    .ok();
+                                },
+                            );
+                        }
+                        menu
+                    },
+                ))
+            })
+    }
+
     fn render_editor(&mut self, window: &mut Window, cx: &mut Context<Self>) -> AnyElement {
         let font_size = TextSize::Default.rems(cx);
         let line_height = font_size.to_pixels(window.rem_size()) * 1.3;
@@ -823,6 +997,8 @@ pub enum PromptEditorMode {
         id: TerminalInlineAssistId,
         codegen: Entity<TerminalCodegen>,
         height_in_lines: u8,
+        /// The remote host or container that the terminal is attached to.
+        remote_host: Option<SharedString>,
     },
 }
 
@@ -858,6 +1034,7 @@ impl PromptEditor<BufferCodegen> {
         workspace: WeakEntity<Workspace>,
         thread_store: Option<WeakEntity<ThreadStore>>,
         text_thread_store: Option<WeakEntity<TextThreadStore>>,
+        prompt_store: Option<Entity<PromptStore>>,
         window: &mut Window,
         cx: &mut Context<PromptEditor<BufferCodegen>>,
     ) -> PromptEditor<BufferCodegen> {
@@ -942,6 +1119,8 @@ impl PromptEditor<BufferCodegen> {
                     cx,
                 )
             }),
+            prompt_store,
+            presets_menu_handle: PopoverMenuHandle::default(),
             edited_since_done: false,
             prompt_history,
             prompt_history_ix: None,
@@ -1032,11 +1211,13 @@ impl PromptEditor<TerminalCodegen> {
         prompt_history: VecDeque<String>,
         prompt_buffer: Entity<MultiBuffer>,
         codegen: Entity<TerminalCodegen>,
+        remote_host: Option<SharedString>,
         fs: Arc<dyn Fs>,
         context_store: Entity<ContextStore>,
         workspace: WeakEntity<Workspace>,
         thread_store: Option<WeakEntity<ThreadStore>>,
         text_thread_store: Option<WeakEntity<TextThreadStore>>,
+        prompt_store: Option<Entity<PromptStore>>,
         window: &mut Window,
         cx: &mut Context<Self>,
     ) -> Self {
@@ -1045,6 +1226,7 @@ impl PromptEditor<TerminalCodegen> {
             id,
             codegen,
             height_in_lines: 1,
+            remote_host,
         };
 
         let prompt_editor = cx.new(|cx| {
@@ -1114,6 +1296,8 @@ impl PromptEditor<TerminalCodegen> {
                     cx,
                 )
             }),
+            prompt_store,
+            presets_menu_handle: PopoverMenuHandle::default(),
             edited_since_done: false,
             prompt_history,
             prompt_history_ix: None,
@@ -0,0 +1,288 @@
+//! Recalls the exchanges from the project's past threads that are relevant to
+//! the message being composed, so they can be attached to it as memories and
+//! repeated questions build on earlier answers.
+
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+use agent_settings::AgentSettings;
+use futures::FutureExt as _;
+use futures::future::Shared;
+use gpui::{Context, SharedString, Task, WeakEntity};
+use language_model::Role;
+use settings::Settings as _;
+use util::ResultExt as _;
+
+use crate::thread::ThreadId;
+use crate::thread_store::{SerializedMessage, SerializedMessageSegment, ThreadStore};
+
+/// How long typing has to pause before memories are recalled.
+const RECALL_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Messages shorter than this don't say enough to recall anything by.
+const MIN_QUERY_LEN: usize = 12;
+
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The number of characters of an answer kept in a memory.
+const MAX_ANSWER_LEN: usize = 2000;
+
+/// A question asked in an earlier thread, and the answer it got.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Memory {
+    pub thread_summary: SharedString,
+    pub question: String,
+    pub answer: String,
+}
+
+impl Display for Memory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "From the thread \"{}\":", self.thread_summary)?;
+        writeln!(f, "<question>\n{}\n</question>", self.question.trim())?;
+        writeln!(f, "<answer>\n{}\n</answer>", self.answer.trim())
+    }
+}
+
+pub(crate) struct MemoryRecall {
+    thread_store: WeakEntity<ThreadStore>,
+    thread_id: ThreadId,
+    /// The exchanges of the project's past threads, loaded the first time
+    /// memories are recalled.
+    memories: Option<Shared<Task<Arc<Vec<Memory>>>>>,
+    suggestions: Vec<Memory>,
+    attached: Vec<Memory>,
+    recall_task: Option<Task<()>>,
+}
+
+impl MemoryRecall {
+    pub fn new(thread_store: WeakEntity<ThreadStore>, thread_id: ThreadId) -> Self {
+        Self {
+            thread_store,
+            thread_id,
+            memories: None,
+            suggestions: Vec::new(),
+            attached: Vec::new(),
+            recall_task: None,
+        }
+    }
+
+    pub fn suggestions(&self) -> &[Memory] {
+        &self.suggestions
+    }
+
+    pub fn attached(&self) -> &[Memory] {
+        &self.attached
+    }
+
+    /// Suggests the memories most relevant to the message being composed,
+    /// once typing pauses.
+    pub fn recall(&mut self, message: &str, cx: &mut Context<Self>) {
+        let settings = AgentSettings::get_global(cx).memory_recall.clone();
+        if !settings.enabled || message.trim().len() < MIN_QUERY_LEN {
+            self.recall_task = None;
+            if !self.suggestions.is_empty() {
+                self.suggestions.clear();
+                cx.notify();
+            }
+            return;
+        }
+
+        let memories = self.memories(settings.max_threads, cx);
+        let query = message.to_string();
+        self.recall_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(RECALL_DEBOUNCE).await;
+            let memories = memories.await;
+            let suggestions = cx
+                .background_spawn(async move { most_relevant(&memories, &query) })
+                .await;
+            this.update(cx, |this, cx| {
+                this.suggestions = suggestions
+                    .into_iter()
+                    .filter(|memory| !this.attached.contains(memory))
+                    .collect();
+                cx.notify();
+            })
+            .ok();
+        }));
+    }
+
+    pub fn attach(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if ix < self.suggestions.len() {
+            self.attached.push(self.suggestions.remove(ix));
+            cx.notify();
+        }
+    }
+
+    pub fn detach(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if ix < self.attached.len() {
+            self.attached.remove(ix);
+            cx.notify();
+        }
+    }
+
+    /// Takes the attached memories, to be sent with the message.
+    pub fn take_attached(&mut self, cx: &mut Context<Self>) -> Vec<Memory> {
+        self.suggestions.clear();
+        self.recall_task = None;
+        cx.notify();
+        std::mem::take(&mut self.attached)
+    }
+
+    fn memories(
+        &mut self,
+        max_threads: usize,
+        cx: &mut Context<Self>,
+    ) -> Shared<Task<Arc<Vec<Memory>>>> {
+        if let Some(memories) = &self.memories {
+            return memories.clone();
+        }
+
+        let threads = self.thread_store.update(cx, |thread_store, cx| {
+            thread_store.load_project_threads(&self.thread_id, max_threads, cx)
+        });
+        let memories = cx
+            .background_spawn(async move {
+                let threads = match threads {
+                    Ok(threads) => threads.await.log_err().unwrap_or_default(),
+                    Err(_) => Vec::new(),
+                };
+                Arc::new(
+                    threads
+                        .iter()
+                        .flat_map(|(metadata, thread)| {
+                            exchanges(metadata.summary.clone(), &thread.messages)
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .shared();
+        self.memories = Some(memories.clone());
+        memories
+    }
+}
+
+/// Splits a thread's messages into its questions and the answers they got,
+/// skipping the messages the user didn't see.
+fn exchanges(thread_summary: SharedString, messages: &[SerializedMessage]) -> Vec<Memory> {
+    let mut memories = Vec::new();
+    let mut messages = messages
+        .iter()
+        .filter(|message| !message.is_hidden)
+        .peekable();
+    while let Some(message) = messages.next() {
+        if message.role != Role::User {
+            continue;
+        }
+
+        let question = message_text(message);
+        let mut answer = String::new();
+        while let Some(reply) = messages.next_if(|message| message.role == Role::Assistant) {
+            let text = message_text(reply);
+            if !text.trim().is_empty() {
+                if !answer.is_empty() {
+                    answer.push('\n');
+                }
+                answer.push_str(&text);
+            }
+        }
+
+        if question.trim().is_empty() || answer.trim().is_empty() {
+            continue;
+        }
+        memories.push(Memory {
+            thread_summary: thread_summary.clone(),
+            question,
+            answer: util::truncate_and_trailoff(&answer, MAX_ANSWER_LEN),
+        });
+    }
+    memories
+}
+
+fn message_text(message: &SerializedMessage) -> String {
+    message
+        .segments
+        .iter()
+        .filter_map(|segment| match segment {
+            SerializedMessageSegment::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Ranks the memories by the keywords their question and thread summary
+/// share with the message.
+fn most_relevant(memories: &[Memory], message: &str) -> Vec<Memory> {
+    let texts = memories
+        .iter()
+        .map(|memory| format!("{}\n{}", memory.thread_summary, memory.question))
+        .collect::<Vec<_>>();
+    semantic_index::rank_by_keywords(
+        message,
+        texts.iter().map(String::as_str).enumerate(),
+        MAX_SUGGESTIONS,
+    )
+    .into_iter()
+    .filter_map(|(ix, _)| memories.get(ix).cloned())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::thread::MessageId;
+
+    fn message(role: Role, text: &str, is_hidden: bool) -> SerializedMessage {
+        SerializedMessage {
+            id: MessageId(0),
+            role,
+            segments: vec![SerializedMessageSegment::Text {
+                text: text.to_string(),
+            }],
+            tool_uses: Vec::new(),
+            tool_results: Vec::new(),
+            context: String::new(),
+            retrieved_chunks: Vec::new(),
+            creases: Vec::new(),
+            is_hidden,
+            substitution: None,
+        }
+    }
+
+    #[test]
+    fn test_exchanges_and_recall() {
+        let messages = [
+            message(Role::User, "How are embeddings stored on disk?", false),
+            message(Role::Assistant, "In an LMDB database per worktree.", false),
+            message(Role::User, "Continue where you left off", true),
+            message(
+                Role::Assistant,
+                "Each file's chunks are keyed by path.",
+                false,
+            ),
+            message(Role::User, "Rename the settings struct", false),
+            message(Role::Assistant, "Done.", false),
+            message(Role::User, "Thanks", false),
+        ];
+
+        let memories = exchanges("Semantic index".into(), &messages);
+        assert_eq!(
+            memories
+                .iter()
+                .map(|memory| (memory.question.as_str(), memory.answer.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    "How are embeddings stored on disk?",
+                    "In an LMDB database per worktree.\nEach file's chunks are keyed by path."
+                ),
+                ("Rename the settings struct", "Done."),
+            ]
+        );
+
+        let recalled = most_relevant(&memories, "where are embeddings stored?");
+        assert_eq!(recalled.len(), 1);
+        assert_eq!(recalled[0].question, "How are embeddings stored on disk?");
+    }
+}
@@ -3,7 +3,13 @@ use std::rc::Rc;
 use std::sync::Arc;
 
 use crate::agent_model_selector::AgentModelSelector;
-use crate::context::{AgentContextKey, ContextCreasesAddon, ContextLoadResult, load_context};
+use crate::auto_context;
+use crate::context::{
+    AgentContextHandle, AgentContextKey, ContextCreasesAddon, ContextLoadResult, load_context,
+};
+use crate::context_prefetch::ContextPrefetcher;
+use crate::memory_recall::{Memory, MemoryRecall};
+use crate::model_recommendation::ModelRecommender;
 use crate::tool_compatibility::{IncompatibleToolsState, IncompatibleToolsTooltip};
 use crate::ui::{
     MaxModeTooltip,
@@ -14,7 +20,7 @@ use assistant_context_editor::language_model_selector::ToggleModelSelector;
 use buffer_diff::BufferDiff;
 use client::UserStore;
 use collections::{HashMap, HashSet};
-use editor::actions::{MoveUp, Paste};
+use editor::actions::{MoveToEnd, MoveUp, Paste};
 use editor::{
     AnchorRangeExt, ContextMenuOptions, ContextMenuPlacement, Editor, EditorElement, EditorEvent,
     EditorMode, EditorStyle, MultiBuffer,
@@ -29,13 +35,14 @@ use gpui::{
 };
 use language::{Buffer, Language, Point};
 use language_model::{
-    ConfiguredModel, LanguageModelRequestMessage, MessageContent, RequestUsage,
+    ConfiguredModel, LanguageModelRequestMessage, MessageContent, RequestPriority, RequestUsage,
     ZED_CLOUD_PROVIDER_ID,
 };
 use multi_buffer;
 use project::Project;
 use prompt_store::PromptStore;
 use proto::Plan;
+use semantic_index::SemanticIndexSettings;
 use settings::Settings;
 use std::time::Duration;
 use theme::ThemeSettings;
@@ -65,6 +72,9 @@ pub struct MessageEditor {
     project: Entity<Project>,
     user_store: Entity<UserStore>,
     context_store: Entity<ContextStore>,
+    context_prefetcher: Entity<ContextPrefetcher>,
+    memory_recall: Entity<MemoryRecall>,
+    model_recommender: Entity<ModelRecommender>,
     prompt_store: Option<Entity<PromptStore>>,
     context_strip: Entity<ContextStrip>,
     context_picker_menu_handle: PopoverMenuHandle<ContextPicker>,
@@ -177,13 +187,14 @@ impl MessageEditor {
 
         let incompatible_tools = cx.new(|cx| IncompatibleToolsState::new(thread.clone(), cx));
 
-        let subscriptions = vec![
+        let mut subscriptions = vec![
             cx.subscribe_in(&context_strip, window, Self::handle_context_strip_event),
             cx.subscribe(&editor, |this, _, event, cx| match event {
                 EditorEvent::BufferEdited => this.handle_message_changed(cx),
                 _ => {}
             }),
             cx.observe(&context_store, |this, _, cx| {
+                this.add_prefetched_token_counts(cx);
                 // When context changes, reload it for token counting.
                 let _ = this.reload_context(cx);
             }),
@@ -206,14 +217,25 @@ impl MessageEditor {
         let profile_selector =
             cx.new(|cx| ProfileSelector::new(fs, thread.clone(), editor.focus_handle(cx), cx));
 
+        let project = thread.read(cx).project().clone();
+        let context_prefetcher = cx.new(|_| ContextPrefetcher::new(project.downgrade()));
+        let memory_recall =
+            cx.new(|_| MemoryRecall::new(thread_store.clone(), thread.read(cx).id().clone()));
+        subscriptions.push(cx.observe(&memory_recall, |_, _, cx| cx.notify()));
+        let model_recommender = cx.new(|_| ModelRecommender::new());
+        subscriptions.push(cx.observe(&model_recommender, |_, _, cx| cx.notify()));
+
         Self {
             editor: editor.clone(),
-            project: thread.read(cx).project().clone(),
+            project,
             user_store,
             thread,
             incompatible_tools_state: incompatible_tools.clone(),
             workspace,
             context_store,
+            context_prefetcher,
+            memory_recall,
+            model_recommender,
             prompt_store,
             context_strip,
             context_picker_menu_handle,
@@ -322,6 +344,13 @@ impl MessageEditor {
         self.editor.read(cx).is_empty(cx)
     }
 
+    pub fn set_text(&mut self, text: &str, window: &mut Window, cx: &mut Context<Self>) {
+        self.editor.update(cx, |editor, cx| {
+            editor.set_text(text, window, cx);
+            editor.move_to_end(&MoveToEnd, window, cx);
+        });
+    }
+
     fn send_to_model(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         let Some(ConfiguredModel { model, provider }) = self
             .thread
@@ -346,14 +375,44 @@ impl MessageEditor {
         cx.emit(MessageEditorEvent::EstimatedTokenCount);
 
         let thread = self.thread.clone();
+        let project = self.project.clone();
         let git_store = self.project.read(cx).git_store().clone();
         let checkpoint = git_store.update(cx, |git_store, cx| git_store.checkpoint(cx));
         let context_task = self.reload_context(cx);
+        let retrieval_task = if self.thread.read(cx).auto_context() {
+            auto_context::retrieve_chunks(&self.project, user_message.clone(), cx)
+        } else {
+            Task::ready(Vec::new())
+        };
+        let memories = self
+            .memory_recall
+            .update(cx, |memory_recall, cx| memory_recall.take_attached(cx));
+        self.model_recommender
+            .update(cx, |model_recommender, cx| model_recommender.clear(cx));
         let window_handle = window.window_handle();
 
         cx.spawn(async move |_this, cx| {
-            let (checkpoint, loaded_context) = future::join(checkpoint, context_task).await;
-            let loaded_context = loaded_context.unwrap_or_default();
+            let (checkpoint, loaded_context, retrieved_chunks) =
+                future::join3(checkpoint, context_task, retrieval_task).await;
+            let mut loaded_context = loaded_context.unwrap_or_default();
+            if loaded_context.loaded_context.has_chunked_files() {
+                if let Ok(search) = cx.update(|cx| {
+                    auto_context::search_relevant_ranges(&project, user_message.clone(), cx)
+                }) {
+                    let relevant_ranges = search.await;
+                    loaded_context
+                        .loaded_context
+                        .select_relevant_chunks(&relevant_ranges);
+                }
+            }
+            if !memories.is_empty() {
+                loaded_context.loaded_context.add_memories(memories);
+            }
+            if !retrieved_chunks.is_empty() {
+                loaded_context
+                    .loaded_context
+                    .add_retrieved_chunks(retrieved_chunks);
+            }
 
             thread
                 .update(cx, |thread, cx| {
@@ -538,6 +597,215 @@ impl MessageEditor {
         )
     }
 
+    fn render_plan_first_toggle(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let plan_first = self.thread.read(cx).plan_first();
+        IconButton::new("plan-first", IconName::ListTodo)
+            .icon_size(IconSize::Small)
+            .icon_color(Color::Muted)
+            .toggle_state(plan_first)
+            .selected_icon_color(Some(Color::Accent))
+            .tooltip(move |window, cx| {
+                Tooltip::with_meta(
+                    if plan_first {
+                        "Don't Plan First"
+                    } else {
+                        "Plan First"
+                    },
+                    None,
+                    "Have the agent propose a plan to review before it starts working.",
+                    window,
+                    cx,
+                )
+            })
+            .on_click(cx.listener(move |this, _, _, cx| {
+                this.thread
+                    .update(cx, |thread, _| thread.set_plan_first(!plan_first));
+                cx.notify();
+            }))
+    }
+
+    fn render_memories(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let memory_recall = self.memory_recall.read(cx);
+        if memory_recall.attached().is_empty() && memory_recall.suggestions().is_empty() {
+            return None;
+        }
+
+        let memory_button = |id: (&'static str, usize), memory: &Memory| {
+            let question = memory.question.trim().to_string();
+            let thread_summary = format!("From \"{}\"", memory.thread_summary);
+            Button::new(id, util::truncate_and_trailoff(&question, 40))
+                .label_size(LabelSize::Small)
+                .icon_size(IconSize::XSmall)
+                .icon_color(Color::Muted)
+                .tooltip(move |window, cx| {
+                    Tooltip::with_meta(question.clone(), None, thread_summary.clone(), window, cx)
+                })
+        };
+
+        Some(
+            h_flex()
+                .flex_wrap()
+                .gap_1()
+                .child(
+                    Label::new("Memories")
+                        .size(LabelSize::Small)
+                        .color(Color::Muted),
+                )
+                .children(
+                    memory_recall
+                        .attached()
+                        .iter()
+                        .enumerate()
+                        .map(|(ix, memory)| {
+                            memory_button(("attached-memory", ix), memory)
+                                .style(ButtonStyle::Filled)
+                                .icon(IconName::Close)
+                                .icon_position(IconPosition::End)
+                                .on_click(cx.listener(move |this, _, _, cx| {
+                                    this.memory_recall.update(cx, |memory_recall, cx| {
+                                        memory_recall.detach(ix, cx)
+                                    });
+                                }))
+                        }),
+                )
+                .children(
+                    memory_recall
+                        .suggestions()
+                        .iter()
+                        .enumerate()
+                        .map(|(ix, memory)| {
+                            memory_button(("suggested-memory", ix), memory)
+                                .style(ButtonStyle::Subtle)
+                                .icon(IconName::Plus)
+                                .icon_position(IconPosition::Start)
+                                .on_click(cx.listener(move |this, _, _, cx| {
+                                    this.memory_recall.update(cx, |memory_recall, cx| {
+                                        memory_recall.attach(ix, cx)
+                                    });
+                                }))
+                        }),
+                )
+                .into_any_element(),
+        )
+    }
+
+    fn render_model_recommendation(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let recommendation = self.model_recommender.read(cx).recommendation()?;
+        let model = recommendation.model.clone();
+        let message = format!(
+            "This looks like {} — switch to {}?",
+            recommendation.task.label(),
+            model.model.name().0
+        );
+
+        Some(
+            h_flex()
+                .gap_1()
+                .child(
+                    Icon::new(IconName::ZedAssistant)
+                        .size(IconSize::XSmall)
+                        .color(Color::Muted),
+                )
+                .child(
+                    Label::new(message)
+                        .size(LabelSize::Small)
+                        .color(Color::Muted),
+                )
+                .child(
+                    Button::new("switch-to-recommended-model", "Switch")
+                        .label_size(LabelSize::Small)
+                        .style(ButtonStyle::Filled)
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            this.thread.update(cx, |thread, cx| {
+                                thread.set_configured_model(Some(model.clone()), cx)
+                            });
+                            this.model_recommender
+                                .update(cx, |model_recommender, cx| model_recommender.clear(cx));
+                        })),
+                )
+                .child(
+                    IconButton::new("dismiss-model-recommendation", IconName::Close)
+                        .icon_size(IconSize::XSmall)
+                        .icon_color(Color::Muted)
+                        .tooltip(Tooltip::text("Dismiss"))
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.model_recommender
+                                .update(cx, |model_recommender, cx| model_recommender.dismiss(cx));
+                        })),
+                )
+                .into_any_element(),
+        )
+    }
+
+    fn render_auto_context_toggle(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        if !SemanticIndexSettings::get_global(cx).enabled {
+            return None;
+        }
+
+        let auto_context = self.thread.read(cx).auto_context();
+        Some(
+            IconButton::new("auto-context", IconName::SearchCode)
+                .icon_size(IconSize::Small)
+                .icon_color(Color::Muted)
+                .toggle_state(auto_context)
+                .selected_icon_color(Some(Color::Accent))
+                .tooltip(move |window, cx| {
+                    Tooltip::with_meta(
+                        if auto_context {
+                            "Disable Auto-Context"
+                        } else {
+                            "Enable Auto-Context"
+                        },
+                        None,
+                        "Attach the project code most relevant to each message.",
+                        window,
+                        cx,
+                    )
+                })
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.thread
+                        .update(cx, |thread, _| thread.set_auto_context(!auto_context));
+                    cx.notify();
+                }))
+                .into_any_element(),
+        )
+    }
+
+    fn render_report_toggle(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        if AgentSettings::get_global(cx).report_webhooks.is_empty() {
+            return None;
+        }
+
+        let report_when_done = self.thread.read(cx).report_when_done();
+        Some(
+            IconButton::new("report-when-done", IconName::BellRing)
+                .icon_size(IconSize::Small)
+                .icon_color(Color::Muted)
+                .toggle_state(report_when_done)
+                .selected_icon_color(Some(Color::Accent))
+                .tooltip(move |window, cx| {
+                    Tooltip::with_meta(
+                        if report_when_done {
+                            "Don't Post Report When Done"
+                        } else {
+                            "Post Report When Done"
+                        },
+                        None,
+                        "Post the agent's final message to the configured report webhooks.",
+                        window,
+                        cx,
+                    )
+                })
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.thread.update(cx, |thread, _| {
+                        thread.set_report_when_done(!report_when_done)
+                    });
+                    cx.notify();
+                }))
+                .into_any_element(),
+        )
+    }
+
     fn render_follow_toggle(&self, cx: &mut Context<Self>) -> impl IntoElement {
         let following = self
             .workspace
@@ -678,6 +946,8 @@ impl MessageEditor {
                             }),
                     ),
             )
+            .children(self.render_memories(cx))
+            .children(self.render_model_recommendation(cx))
             .child(
                 v_flex()
                     .size_full()
@@ -726,7 +996,10 @@ impl MessageEditor {
                             .child(
                                 h_flex()
                                     .child(self.render_follow_toggle(cx))
-                                    .children(self.render_max_mode_toggle(cx)),
+                                    .children(self.render_max_mode_toggle(cx))
+                                    .child(self.render_plan_first_toggle(cx))
+                                    .children(self.render_auto_context_toggle(cx))
+                                    .children(self.render_report_toggle(cx)),
                             )
                             .child(
                                 h_flex()
@@ -1310,9 +1583,60 @@ impl MessageEditor {
     }
 
     fn handle_message_changed(&mut self, cx: &mut Context<Self>) {
+        let text = self.editor.read(cx).text(cx);
+        let model = self
+            .thread
+            .read(cx)
+            .configured_model()
+            .map(|configured_model| configured_model.model);
+        self.context_prefetcher
+            .update(cx, |prefetcher, cx| prefetcher.prefetch(&text, model, cx));
+        self.memory_recall
+            .update(cx, |memory_recall, cx| memory_recall.recall(&text, cx));
         self.message_or_context_changed(true, cx);
     }
 
+    fn update_model_recommendation(&mut self, cx: &mut Context<Self>) {
+        let message = self.editor.read(cx).text(cx);
+        let thread = self.thread.read(cx);
+        let tokens = thread.total_token_usage().map_or(0, |usage| usage.total)
+            + self.last_estimated_token_count.unwrap_or(0);
+        let current_model = thread.configured_model();
+        self.model_recommender.update(cx, |model_recommender, cx| {
+            model_recommender.recommend(&message, tokens, current_model, cx)
+        });
+    }
+
+    /// Adds the token counts of newly attached files that were prefetched to
+    /// the estimate, so it updates before the new context is counted.
+    fn add_prefetched_token_counts(&mut self, cx: &mut Context<Self>) {
+        let Some(estimate) = self.last_estimated_token_count else {
+            return;
+        };
+        let added_tokens: usize = {
+            let prefetcher = self.context_prefetcher.read(cx);
+            self.context_store
+                .read(cx)
+                .new_context_for_thread(self.thread.read(cx), None)
+                .iter()
+                .filter_map(|context| match context {
+                    AgentContextHandle::File(context)
+                        if !self.last_loaded_context.as_ref().is_some_and(|loaded| {
+                            loaded.referenced_buffers.contains(&context.buffer)
+                        }) =>
+                    {
+                        prefetcher.token_count(&context.buffer)
+                    }
+                    _ => None,
+                })
+                .sum()
+        };
+        if added_tokens > 0 {
+            self.last_estimated_token_count = Some(estimate + added_tokens);
+            cx.emit(MessageEditorEvent::EstimatedTokenCount);
+        }
+    }
+
     fn message_or_context_changed(&mut self, debounce: bool, cx: &mut Context<Self>) {
         cx.emit(MessageEditorEvent::Changed);
         self.update_token_count_task.take();
@@ -1371,6 +1695,9 @@ impl MessageEditor {
                         tool_choice: None,
                         stop: vec![],
                         temperature: AgentSettings::temperature_for_model(&model.model, cx),
+                        seed: None,
+                        priority: RequestPriority::Interactive,
+                        web_search: false,
                     };
 
                     Some(model.model.count_tokens(request, cx))
@@ -1389,6 +1716,7 @@ impl MessageEditor {
                     cx.emit(MessageEditorEvent::EstimatedTokenCount);
                 }
                 this.update_token_count_task.take();
+                this.update_model_recommendation(cx);
             })
             .ok();
         }));
@@ -0,0 +1,401 @@
+//! Suggests a model better suited to the message being composed, based on the
+//! kind of task it asks for: the model set for such tasks, or else the one
+//! rated best on them, preferring the faster of similarly rated models.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use agent_settings::{AgentSettings, ModelRecommendationSettings};
+use collections::{HashMap, HashSet};
+use futures::FutureExt as _;
+use futures::future::Shared;
+use gpui::{App, Context, Global, Task};
+use language_model::{ConfiguredModel, LanguageModel, LanguageModelRegistry, SelectedModel};
+use settings::Settings as _;
+
+use crate::feedback_store::{self, FeedbackRecord};
+use crate::thread::ThreadFeedback;
+
+/// Words that open a request for code to be written or changed.
+const CODE_VERBS: &[&str] = &[
+    "add",
+    "change",
+    "convert",
+    "create",
+    "debug",
+    "delete",
+    "extract",
+    "fix",
+    "implement",
+    "migrate",
+    "move",
+    "optimize",
+    "port",
+    "refactor",
+    "remove",
+    "rename",
+    "rewrite",
+    "update",
+    "write",
+];
+
+const QUESTION_WORDS: &[&str] = &[
+    "are", "can", "could", "do", "does", "how", "is", "should", "what", "when", "where", "which",
+    "who", "why",
+];
+
+/// Messages longer than this aren't quick questions.
+const MAX_QUESTION_LEN: usize = 300;
+
+/// Models whose ratings are this close are rated equally well.
+const RATING_TOLERANCE: f64 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum TaskKind {
+    LongContext,
+    Code,
+    Question,
+}
+
+impl TaskKind {
+    /// Classifies the task a message asks for, given how many tokens it takes
+    /// up along with the thread and its context.
+    pub fn classify(message: &str, tokens: usize, long_context_tokens: usize) -> Option<Self> {
+        let message = message.trim();
+        if message.is_empty() {
+            return None;
+        }
+        if tokens >= long_context_tokens {
+            return Some(Self::LongContext);
+        }
+
+        let first_word = message
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        if contains_code(message) || CODE_VERBS.contains(&first_word.as_str()) {
+            Some(Self::Code)
+        } else if message.len() <= MAX_QUESTION_LEN
+            && (message.ends_with('?') || QUESTION_WORDS.contains(&first_word.as_str()))
+        {
+            Some(Self::Question)
+        } else {
+            None
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::LongContext => "a long-context task",
+            Self::Code => "a coding task",
+            Self::Question => "a quick question",
+        }
+    }
+
+    fn configured_model(&self, settings: &ModelRecommendationSettings) -> Option<String> {
+        let selection = match self {
+            Self::LongContext => settings.long_context_model.as_ref(),
+            Self::Code => settings.code_model.as_ref(),
+            Self::Question => settings.question_model.as_ref(),
+        }?;
+        Some(format!("{}/{}", selection.provider.0, selection.model))
+    }
+}
+
+fn contains_code(message: &str) -> bool {
+    message.contains("```")
+        || message
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| line.ends_with(';') || line.ends_with('{') || line.ends_with('}'))
+            .count()
+            >= 2
+}
+
+/// How long each model took to start responding during this session.
+#[derive(Default)]
+pub(crate) struct ModelLatencies(HashMap<String, (u32, Duration)>);
+
+impl Global for ModelLatencies {}
+
+impl ModelLatencies {
+    pub fn record(model: &dyn LanguageModel, latency: Duration, cx: &mut App) {
+        let (count, total) = cx
+            .default_global::<Self>()
+            .0
+            .entry(feedback_store::model_key(model))
+            .or_default();
+        *count += 1;
+        *total += latency;
+    }
+
+    fn averages(cx: &App) -> HashMap<String, Duration> {
+        cx.try_global::<Self>()
+            .map(|latencies| {
+                latencies
+                    .0
+                    .iter()
+                    .map(|(model, (count, total))| (model.clone(), *total / (*count).max(1)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+pub(crate) struct Recommendation {
+    pub task: TaskKind,
+    pub model: ConfiguredModel,
+}
+
+pub(crate) struct ModelRecommender {
+    /// The rated answers, loaded the first time a model is recommended
+    /// based on them.
+    feedback: Option<Shared<Task<Arc<Vec<FeedbackRecord>>>>>,
+    recommendation: Option<Recommendation>,
+    /// The kinds of tasks whose recommendations were dismissed.
+    dismissed: HashSet<TaskKind>,
+    recommend_task: Option<Task<()>>,
+}
+
+impl ModelRecommender {
+    pub fn new() -> Self {
+        Self {
+            feedback: None,
+            recommendation: None,
+            dismissed: HashSet::default(),
+            recommend_task: None,
+        }
+    }
+
+    pub fn recommendation(&self) -> Option<&Recommendation> {
+        self.recommendation.as_ref()
+    }
+
+    /// Recommends a model for the message, if one is better suited to it
+    /// than the current model.
+    pub fn recommend(
+        &mut self,
+        message: &str,
+        tokens: usize,
+        current_model: Option<ConfiguredModel>,
+        cx: &mut Context<Self>,
+    ) {
+        self.recommend_task = None;
+        let settings = AgentSettings::get_global(cx).model_recommendation.clone();
+        let task = TaskKind::classify(message, tokens, settings.long_context_tokens)
+            .filter(|task| settings.enabled && !self.dismissed.contains(task));
+        let (Some(task), Some(current_model)) = (task, current_model) else {
+            self.set_recommendation(None, cx);
+            return;
+        };
+        let current_model = feedback_store::model_key(current_model.model.as_ref());
+
+        if let Some(model) = task.configured_model(&settings) {
+            let recommendation = (model != current_model)
+                .then(|| resolve_model(&model, cx))
+                .flatten()
+                .map(|model| Recommendation { task, model });
+            self.set_recommendation(recommendation, cx);
+            return;
+        }
+
+        let feedback = self.feedback(cx);
+        self.recommend_task = Some(cx.spawn(async move |this, cx| {
+            let records = feedback.await;
+            this.update(cx, |this, cx| {
+                let recommendation = best_rated_model(
+                    task,
+                    &current_model,
+                    &records,
+                    settings.min_ratings,
+                    &ModelLatencies::averages(cx),
+                )
+                .and_then(|model| resolve_model(&model, cx))
+                .map(|model| Recommendation { task, model });
+                this.set_recommendation(recommendation, cx);
+            })
+            .ok();
+        }));
+    }
+
+    /// Stops recommending models for the kind of task in the current
+    /// recommendation.
+    pub fn dismiss(&mut self, cx: &mut Context<Self>) {
+        if let Some(recommendation) = self.recommendation.take() {
+            self.dismissed.insert(recommendation.task);
+            cx.notify();
+        }
+    }
+
+    pub fn clear(&mut self, cx: &mut Context<Self>) {
+        self.recommend_task = None;
+        self.set_recommendation(None, cx);
+    }
+
+    fn set_recommendation(
+        &mut self,
+        recommendation: Option<Recommendation>,
+        cx: &mut Context<Self>,
+    ) {
+        if self.recommendation.is_some() || recommendation.is_some() {
+            self.recommendation = recommendation;
+            cx.notify();
+        }
+    }
+
+    fn feedback(&mut self, cx: &mut Context<Self>) -> Shared<Task<Arc<Vec<FeedbackRecord>>>> {
+        if let Some(feedback) = &self.feedback {
+            return feedback.clone();
+        }
+
+        let records = feedback_store::load(cx);
+        let feedback = cx
+            .background_spawn(async move { Arc::new(records.await) })
+            .shared();
+        self.feedback = Some(feedback.clone());
+        feedback
+    }
+}
+
+/// Returns the model of an authenticated provider with the given
+/// `provider/model` key.
+fn resolve_model(key: &str, cx: &mut App) -> Option<ConfiguredModel> {
+    let selected_model = key.parse::<SelectedModel>().ok()?;
+    let model = LanguageModelRegistry::global(cx).update(cx, |registry, cx| {
+        registry.select_model(&selected_model, cx)
+    })?;
+    model.provider.is_authenticated(cx).then_some(model)
+}
+
+/// Picks the model rated best on the kind of task, among those rated at least
+/// `min_ratings` times on it, if it's not the current model. Of the models
+/// rated equally well, the one that started responding fastest is picked.
+fn best_rated_model(
+    task: TaskKind,
+    current_model: &str,
+    records: &[FeedbackRecord],
+    min_ratings: usize,
+    latencies: &HashMap<String, Duration>,
+) -> Option<String> {
+    let mut ratings = HashMap::<&str, (usize, usize)>::default();
+    for record in records {
+        let Some(model) = record.model.as_deref() else {
+            continue;
+        };
+        if TaskKind::classify(&record.prompt, 0, usize::MAX) != Some(task) {
+            continue;
+        }
+        let (positive, total) = ratings.entry(model).or_default();
+        if record.rating == ThreadFeedback::Positive {
+            *positive += 1;
+        }
+        *total += 1;
+    }
+
+    let latency = |model: &str| latencies.get(model).copied().unwrap_or(Duration::MAX);
+    let (best_model, _) = ratings
+        .into_iter()
+        .filter(|(_, (_, total))| *total >= min_ratings.max(1))
+        .map(|(model, (positive, total))| (model, positive as f64 / total as f64))
+        .max_by(|(a, a_rating), (b, b_rating)| {
+            if (a_rating - b_rating).abs() <= RATING_TOLERANCE {
+                latency(b).cmp(&latency(a)).then_with(|| b.cmp(a))
+            } else {
+                a_rating.total_cmp(b_rating)
+            }
+        })?;
+    (best_model != current_model).then(|| best_model.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::thread::ThreadId;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            TaskKind::classify("Rename the settings struct", 40_000, 30_000),
+            Some(TaskKind::LongContext)
+        );
+        assert_eq!(
+            TaskKind::classify("Rename the settings struct", 100, 30_000),
+            Some(TaskKind::Code)
+        );
+        assert_eq!(
+            TaskKind::classify("This panics:\nlet a = b.unwrap();\nfoo(a);", 100, 30_000),
+            Some(TaskKind::Code)
+        );
+        assert_eq!(
+            TaskKind::classify("Where are embeddings stored?", 100, 30_000),
+            Some(TaskKind::Question)
+        );
+        assert_eq!(TaskKind::classify("Thanks!", 100, 30_000), None);
+    }
+
+    #[test]
+    fn test_best_rated_model() {
+        let records = [
+            (
+                "openai/gpt-4o",
+                "Fix the failing test",
+                ThreadFeedback::Negative,
+            ),
+            ("openai/gpt-4o", "Add a retry", ThreadFeedback::Positive),
+            (
+                "anthropic/claude",
+                "Fix the build",
+                ThreadFeedback::Positive,
+            ),
+            ("anthropic/claude", "Add logging", ThreadFeedback::Positive),
+            (
+                "ollama/llama",
+                "Rename the module",
+                ThreadFeedback::Positive,
+            ),
+            (
+                "ollama/llama",
+                "Why is this slow?",
+                ThreadFeedback::Negative,
+            ),
+            ("google/gemini", "Fix the parser", ThreadFeedback::Positive),
+            (
+                "google/gemini",
+                "Implement caching",
+                ThreadFeedback::Positive,
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(ix, (model, prompt, rating))| FeedbackRecord {
+            thread_id: ThreadId::from("thread"),
+            message_id: ix,
+            rating,
+            reason: None,
+            model: Some(model.to_string()),
+            prompt_hash: feedback_store::prompt_hash(prompt),
+            prompt: prompt.to_string(),
+            answer: String::new(),
+            created_at: Default::default(),
+        })
+        .collect::<Vec<_>>();
+
+        let latencies = HashMap::from_iter([
+            ("anthropic/claude".to_string(), Duration::from_secs(2)),
+            ("google/gemini".to_string(), Duration::from_secs(1)),
+        ]);
+        assert_eq!(
+            best_rated_model(TaskKind::Code, "openai/gpt-4o", &records, 2, &latencies),
+            Some("google/gemini".to_string())
+        );
+        assert_eq!(
+            best_rated_model(TaskKind::Code, "google/gemini", &records, 2, &latencies),
+            None
+        );
+        assert_eq!(
+            best_rated_model(TaskKind::Code, "openai/gpt-4o", &records, 3, &latencies),
+            None
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! The plan the agent proposes before working on a request in plan-first
+//! mode, which the user can reorder and trim before approving it, and which
+//! the agent then checks off step by step.
+
+use std::fmt::Write as _;
+
+use anyhow::{Result, anyhow};
+use language_model::LanguageModelRequestTool;
+use serde::{Deserialize, Serialize};
+
+/// The tool the agent calls to report its progress through an approved plan.
+pub(crate) const UPDATE_PLAN_TOOL_NAME: &str = "update_plan_step";
+
+/// Sent along with each request while the agent is working on a plan.
+pub(crate) const PLANNING_PROMPT: &str = "Before changing anything, make a plan. \
+    You may read files and search the project to inform it, but don't edit any files \
+    or run commands that change anything yet. Reply with the plan as a Markdown \
+    checklist, with one `- [ ] step` per line and nothing else, so it can be \
+    reviewed before you start.";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanStepStatus {
+    #[default]
+    Pending,
+    InProgress,
+    Done,
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub text: String,
+    #[serde(default)]
+    pub status: PlanStepStatus,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    pub steps: Vec<PlanStep>,
+    /// Whether the user approved the plan, after which the agent carries it
+    /// out and the steps can no longer be rearranged.
+    #[serde(default)]
+    pub approved: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdatePlanStepInput {
+    step: usize,
+    status: PlanStepStatus,
+}
+
+impl Plan {
+    /// Parses the checklist, or the bulleted or numbered list, in the model's
+    /// reply. Returns `None` if the reply has no list.
+    pub fn parse(text: &str) -> Option<Self> {
+        let steps = text
+            .lines()
+            .filter_map(parse_step)
+            .map(|text| PlanStep {
+                text,
+                status: PlanStepStatus::Pending,
+            })
+            .collect::<Vec<_>>();
+        if steps.is_empty() {
+            return None;
+        }
+        Some(Self {
+            steps,
+            approved: false,
+        })
+    }
+
+    pub fn move_step(&mut self, from: usize, to: usize) {
+        if from < self.steps.len() && to < self.steps.len() {
+            let step = self.steps.remove(from);
+            self.steps.insert(to, step);
+        }
+    }
+
+    pub fn remove_step(&mut self, ix: usize) {
+        if ix < self.steps.len() {
+            self.steps.remove(ix);
+        }
+    }
+
+    /// Applies a call to the `update_plan_step` tool, returning the text sent
+    /// back to the model.
+    pub fn update(&mut self, input: serde_json::Value) -> Result<String> {
+        let input = serde_json::from_value::<UpdatePlanStepInput>(input)?;
+        let step = input
+            .step
+            .checked_sub(1)
+            .and_then(|ix| self.steps.get_mut(ix))
+            .ok_or_else(|| anyhow!("the plan has no step {}", input.step))?;
+        step.status = input.status;
+        Ok(format!("Marked step {} as {:?}.", input.step, input.status))
+    }
+
+    /// The message asking the model to carry out the approved plan.
+    pub fn execution_prompt(&self) -> String {
+        let mut prompt = String::from(
+            "The plan below was approved, possibly after being edited. Carry out its steps \
+            in order. Call the `update_plan_step` tool when you start a step and when you \
+            finish or fail it.\n\n",
+        );
+        for (ix, step) in self.steps.iter().enumerate() {
+            writeln!(prompt, "{}. {}", ix + 1, step.text).ok();
+        }
+        prompt
+    }
+}
+
+/// The tool offered to the model while it carries out an approved plan.
+pub(crate) fn update_plan_tool() -> LanguageModelRequestTool {
+    LanguageModelRequestTool {
+        name: UPDATE_PLAN_TOOL_NAME.into(),
+        description: "Updates the status of a step in the approved plan.".into(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "step": {
+                    "type": "integer",
+                    "description": "The number of the step, starting at 1."
+                },
+                "status": {
+                    "type": "string",
+                    "enum": ["in_progress", "done", "failed"]
+                }
+            },
+            "required": ["step", "status"]
+        }),
+    }
+}
+
+fn parse_step(line: &str) -> Option<String> {
+    let line = line.trim();
+    let item = if let Some(item) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+        item
+    } else {
+        let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 {
+            return None;
+        }
+        line[digits..]
+            .strip_prefix(". ")
+            .or_else(|| line[digits..].strip_prefix(") "))?
+    };
+    let item = item
+        .strip_prefix("[ ] ")
+        .or_else(|| item.strip_prefix("[x] "))
+        .or_else(|| item.strip_prefix("[X] "))
+        .unwrap_or(item)
+        .trim();
+    (!item.is_empty()).then(|| item.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_plan() {
+        let plan = Plan::parse(
+            "Here's the plan:\n\n\
+            - [ ] Read `thread.rs`\n\
+            - [x] Add the field\n\
+            * Update the docs\n\
+            \n\
+            Let me know!",
+        )
+        .unwrap();
+        let steps = plan
+            .steps
+            .iter()
+            .map(|step| step.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            ["Read `thread.rs`", "Add the field", "Update the docs"]
+        );
+
+        let plan = Plan::parse("1. First\n2) Second\n10. Tenth").unwrap();
+        assert_eq!(plan.steps.len(), 3);
+        assert_eq!(plan.steps[2].text, "Tenth");
+
+        assert_eq!(Plan::parse("No list here.\n2024 was a year."), None);
+    }
+
+    #[test]
+    fn test_edit_and_update_plan() {
+        let mut plan = Plan::parse("- [ ] a\n- [ ] b\n- [ ] c").unwrap();
+        plan.move_step(2, 0);
+        plan.remove_step(1);
+        assert_eq!(
+            plan.steps.iter().map(|step| &step.text).collect::<Vec<_>>(),
+            ["c", "b"]
+        );
+
+        plan.update(json!({"step": 2, "status": "done"})).unwrap();
+        assert_eq!(plan.steps[1].status, PlanStepStatus::Done);
+        assert!(plan.update(json!({"step": 3, "status": "done"})).is_err());
+        assert!(plan.update(json!({"step": 0, "status": "done"})).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use std::sync::Arc;
+
+use agent_settings::AgentSettings;
+use editor::Editor;
+use futures::StreamExt as _;
+use gpui::{
+    DismissEvent, Entity, EventEmitter, FocusHandle, Focusable, Task, WeakEntity, prelude::*,
+};
+use language::LanguageRegistry;
+use language_model::{
+    ConfiguredModel, LanguageModelRegistry, LanguageModelRequest, LanguageModelRequestMessage, Role,
+};
+use markdown::{Markdown, MarkdownElement};
+use settings::Settings as _;
+use ui::{KeyBinding, prelude::*};
+use workspace::{ModalView, Workspace};
+use zed_llm_client::CompletionIntent;
+
+use crate::active_thread::default_markdown_style;
+use crate::{AgentPanel, PromoteQuickAsk, QuickAsk};
+
+/// A modal for one-off questions to the default model. Nothing is saved unless
+/// the exchange is promoted to a text thread.
+pub struct QuickAskModal {
+    workspace: WeakEntity<Workspace>,
+    language_registry: Arc<LanguageRegistry>,
+    question_editor: Entity<Editor>,
+    exchange: Option<Exchange>,
+    status: QuickAskStatus,
+    _pending_answer: Task<()>,
+}
+
+struct Exchange {
+    question: String,
+    answer: Entity<Markdown>,
+}
+
+enum QuickAskStatus {
+    Idle,
+    Streaming,
+    Done,
+    Error(SharedString),
+}
+
+impl QuickAskModal {
+    pub fn register(
+        workspace: &mut Workspace,
+        _window: Option<&mut Window>,
+        _cx: &mut Context<Workspace>,
+    ) {
+        workspace.register_action(|workspace, _: &QuickAsk, window, cx| {
+            let workspace_handle = cx.entity().downgrade();
+            let language_registry = workspace.project().read(cx).languages().clone();
+            workspace.toggle_modal(window, cx, |window, cx| {
+                Self::new(workspace_handle, language_registry, window, cx)
+            })
+        });
+    }
+
+    fn new(
+        workspace: WeakEntity<Workspace>,
+        language_registry: Arc<LanguageRegistry>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let question_editor = cx.new(|cx| {
+            let mut editor = Editor::single_line(window, cx);
+            editor.set_placeholder_text("Ask a quick question…", cx);
+            editor
+        });
+
+        Self {
+            workspace,
+            language_registry,
+            question_editor,
+            exchange: None,
+            status: QuickAskStatus::Idle,
+            _pending_answer: Task::ready(()),
+        }
+    }
+
+    fn ask(&mut self, _: &menu::Confirm, _window: &mut Window, cx: &mut Context<Self>) {
+        let question = self.question_editor.read(cx).text(cx).trim().to_string();
+        if question.is_empty() {
+            return;
+        }
+        let Some(ConfiguredModel { model, .. }) =
+            LanguageModelRegistry::read_global(cx).default_model()
+        else {
+            self.status = QuickAskStatus::Error("No default model configured.".into());
+            cx.notify();
+            return;
+        };
+
+        let request = LanguageModelRequest {
+            intent: Some(CompletionIntent::UserPrompt),
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![question.clone().into()],
+                cache: false,
+            }],
+            temperature: AgentSettings::temperature_for_model(&model, cx),
+            ..Default::default()
+        };
+        let answer =
+            cx.new(|cx| Markdown::new("".into(), Some(self.language_registry.clone()), None, cx));
+        self.exchange = Some(Exchange {
+            question,
+            answer: answer.clone(),
+        });
+        self.status = QuickAskStatus::Streaming;
+        self._pending_answer = cx.spawn(async move |this, cx| {
+            let result = async {
+                let mut response = model.stream_completion_text(request, cx).await?;
+                while let Some(chunk) = response.stream.next().await {
+                    let chunk = chunk?;
+                    answer.update(cx, |answer, cx| answer.append(&chunk, cx))?;
+                }
+                anyhow::Ok(())
+            }
+            .await;
+
+            this.update(cx, |this, cx| {
+                this.status = match result {
+                    Ok(()) => QuickAskStatus::Done,
+                    Err(error) => QuickAskStatus::Error(error.to_string().into()),
+                };
+                cx.notify();
+            })
+            .ok();
+        });
+        cx.notify();
+    }
+
+    /// Opens the question and its answer as a new text thread in the agent
+    /// panel, which is saved like any other text thread.
+    fn promote(&mut self, _: &PromoteQuickAsk, window: &mut Window, cx: &mut Context<Self>) {
+        if !matches!(self.status, QuickAskStatus::Done) {
+            return;
+        }
+        let Some(exchange) = self.exchange.as_ref() else {
+            return;
+        };
+        let Some(workspace) = self.workspace.upgrade() else {
+            return;
+        };
+
+        let question = exchange.question.clone();
+        let answer = exchange.answer.read(cx).source().to_string();
+        workspace.update(cx, |workspace, cx| {
+            if let Some(panel) = workspace.focus_panel::<AgentPanel>(window, cx) {
+                panel.update(cx, |panel, cx| {
+                    panel.new_text_thread_from_exchange(&question, &answer, window, cx)
+                });
+            }
+        });
+        cx.emit(DismissEvent);
+    }
+
+    fn cancel(&mut self, _: &menu::Cancel, _window: &mut Window, cx: &mut Context<Self>) {
+        cx.emit(DismissEvent);
+    }
+
+    fn render_footer(&self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let focus_handle = self.focus_handle(cx);
+        let status = match &self.status {
+            QuickAskStatus::Idle => Label::new("Answers aren't saved to your history")
+                .size(LabelSize::Small)
+                .color(Color::Muted),
+            QuickAskStatus::Streaming => Label::new("Answering…")
+                .size(LabelSize::Small)
+                .color(Color::Muted),
+            QuickAskStatus::Done => Label::new("Press enter to ask another question")
+                .size(LabelSize::Small)
+                .color(Color::Muted),
+            QuickAskStatus::Error(error) => Label::new(error.clone())
+                .size(LabelSize::Small)
+                .color(Color::Error),
+        };
+
+        h_flex()
+            .px_2()
+            .py_1()
+            .gap_2()
+            .justify_between()
+            .border_t_1()
+            .border_color(cx.theme().colors().border_variant)
+            .child(status)
+            .child(
+                Button::new("promote-quick-ask", "Open as Text Thread")
+                    .label_size(LabelSize::Small)
+                    .disabled(!matches!(self.status, QuickAskStatus::Done))
+                    .key_binding(
+                        KeyBinding::for_action_in(&PromoteQuickAsk, &focus_handle, window, cx)
+                            .map(|kb| kb.size(rems_from_px(12.))),
+                    )
+                    .on_click(cx.listener(|this, _, window, cx| {
+                        this.promote(&PromoteQuickAsk, window, cx)
+                    })),
+            )
+    }
+}
+
+impl ModalView for QuickAskModal {}
+
+impl Focusable for QuickAskModal {
+    fn focus_handle(&self, cx: &App) -> FocusHandle {
+        self.question_editor.focus_handle(cx)
+    }
+}
+
+impl EventEmitter<DismissEvent> for QuickAskModal {}
+
+impl Render for QuickAskModal {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        v_flex()
+            .key_context("QuickAsk")
+            .w(rems(34.))
+            .elevation_3(cx)
+            .on_action(cx.listener(Self::ask))
+            .on_action(cx.listener(Self::cancel))
+            .on_action(cx.listener(Self::promote))
+            .child(
+                h_flex()
+                    .p_2()
+                    .gap_2()
+                    .child(
+                        Icon::new(IconName::ZedAssistant)
+                            .size(IconSize::Small)
+                            .color(Color::Muted),
+                    )
+                    .child(self.question_editor.clone()),
+            )
+            .when_some(self.exchange.as_ref(), |this, exchange| {
+                this.child(
+                    div()
+                        .id("quick-ask-answer")
+                        .max_h(rems(24.))
+                        .overflow_y_scroll()
+                        .px_2()
+                        .py_1()
+                        .border_t_1()
+                        .border_color(cx.theme().colors().border_variant)
+                        .child(MarkdownElement::new(
+                            exchange.answer.clone(),
+                            default_markdown_style(window, cx),
+                        )),
+                )
+            })
+            .child(self.render_footer(window, cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor::EditorSettings;
+    use gpui::{TestAppContext, VisualTestContext};
+    use language_model::fake_provider::FakeLanguageModelProvider;
+    use settings::SettingsStore;
+
+    #[gpui::test]
+    async fn test_asking_a_quick_question(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+            language::init(cx);
+            AgentSettings::register(cx);
+            language_model::init_settings(cx);
+            theme::init(theme::LoadThemes::JustBase, cx);
+            EditorSettings::register(cx);
+        });
+        let language_registry = Arc::new(LanguageRegistry::test(cx.executor()));
+        let (modal, cx) = cx.add_window_view(|window, cx| {
+            QuickAskModal::new(WeakEntity::new_invalid(), language_registry, window, cx)
+        });
+        let ask = |question: &str, cx: &mut VisualTestContext| {
+            modal.update_in(cx, |modal, window, cx| {
+                modal.question_editor.update(cx, |editor, cx| {
+                    editor.set_text(question, window, cx);
+                });
+                modal.ask(&menu::Confirm, window, cx);
+            });
+            cx.run_until_parked();
+        };
+
+        ask("What is a monad?", cx);
+        modal.read_with(cx, |modal, _| {
+            assert!(modal.exchange.is_none());
+            assert!(matches!(modal.status, QuickAskStatus::Error(_)));
+        });
+
+        let provider = Arc::new(FakeLanguageModelProvider);
+        let model = Arc::new(provider.test_model());
+        cx.update(|_, cx| {
+            LanguageModelRegistry::global(cx).update(cx, |registry, cx| {
+                registry.set_default_model(
+                    Some(ConfiguredModel {
+                        provider,
+                        model: model.clone(),
+                    }),
+                    cx,
+                );
+            });
+        });
+
+        ask("  What is a monad? ", cx);
+        let requests = model.pending_completions();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].messages[0].string_contents(),
+            "What is a monad?"
+        );
+        modal.read_with(cx, |modal, _| {
+            assert!(matches!(modal.status, QuickAskStatus::Streaming));
+        });
+
+        model.stream_last_completion_response("A monoid in the category ");
+        model.stream_last_completion_response("of endofunctors.");
+        model.end_last_completion_stream();
+        cx.run_until_parked();
+        modal.read_with(cx, |modal, cx| {
+            assert!(matches!(modal.status, QuickAskStatus::Done));
+            let exchange = modal.exchange.as_ref().unwrap();
+            assert_eq!(exchange.question, "What is a monad?");
+            assert_eq!(
+                exchange.answer.read(cx).source(),
+                "A monoid in the category of endofunctors."
+            );
+        });
+    }
+}
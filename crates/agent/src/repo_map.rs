@@ -0,0 +1,489 @@
+//! Builds a compact outline of the project's files, with their sizes and the
+//! public types and functions they define, which is included in the agent's
+//! system prompt so it can find its way around large projects without
+//! searching first.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use agent_settings::{AgentSettings, RepoMapSettings};
+use collections::HashSet;
+use fs::Fs;
+use gpui::{Context, Entity, EventEmitter, Subscription, Task, WeakEntity};
+use project::{Project, ProjectPath};
+use regex::Regex;
+use settings::{Settings, SettingsStore};
+use util::ResultExt as _;
+
+/// How long file changes have to settle before the map is refreshed.
+const REFRESH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// The maximum number of files in the map. Files beyond it are left out.
+const MAX_FILES: usize = 10_000;
+
+/// Files larger than this are listed, but not read for their symbols.
+const MAX_SCANNED_FILE_SIZE: u64 = 512 * 1024;
+
+/// The number of symbols listed per file when the whole map doesn't fit.
+const ABBREVIATED_SYMBOL_COUNT: usize = 5;
+
+const BYTES_PER_TOKEN_ESTIMATE: usize = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct FileSummary {
+    /// The file's size in kilobytes, rounded up, so that small edits don't
+    /// change the map.
+    size_kb: u64,
+    symbols: Vec<String>,
+}
+
+/// A changed file's path in the map, along with its absolute path, if it can
+/// be read, and its size, or `None` if it was removed.
+type ChangedFile = (PathBuf, Option<(Option<PathBuf>, u64)>);
+
+/// How much of the map is rendered, from the most detailed to the least.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Detail {
+    AllSymbols,
+    SomeSymbols,
+    FileNames,
+    Directories,
+}
+
+pub struct RepoMapUpdated;
+
+pub struct RepoMap {
+    project: WeakEntity<Project>,
+    /// The summary of each file, keyed by its path starting with the name of
+    /// its worktree.
+    files: BTreeMap<PathBuf, FileSummary>,
+    dirty_paths: HashSet<ProjectPath>,
+    settings: RepoMapSettings,
+    refresh_task: Option<Task<()>>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl EventEmitter<RepoMapUpdated> for RepoMap {}
+
+impl RepoMap {
+    pub fn new(project: &Entity<Project>, cx: &mut Context<Self>) -> Self {
+        let subscriptions = vec![
+            cx.subscribe(project, Self::handle_project_event),
+            cx.observe_global::<SettingsStore>(|this, cx| {
+                let settings = AgentSettings::get_global(cx).repo_map.clone();
+                if settings == this.settings {
+                    return;
+                }
+                let was_enabled = this.settings.enabled;
+                this.settings = settings;
+                if this.settings.enabled && !was_enabled {
+                    this.rescan(cx);
+                }
+                cx.emit(RepoMapUpdated);
+            }),
+        ];
+        let mut this = Self {
+            project: project.downgrade(),
+            files: BTreeMap::new(),
+            dirty_paths: HashSet::default(),
+            settings: AgentSettings::get_global(cx).repo_map.clone(),
+            refresh_task: None,
+            _subscriptions: subscriptions,
+        };
+        if this.settings.enabled {
+            this.rescan(cx);
+        }
+        this
+    }
+
+    /// Renders the map within the token limit in the settings, or returns
+    /// `None` if it's disabled or empty.
+    pub fn render(&self) -> Option<String> {
+        if !self.settings.enabled || self.files.is_empty() {
+            return None;
+        }
+        Some(render_map(&self.files, self.settings.max_tokens))
+    }
+
+    fn handle_project_event(
+        &mut self,
+        _project: Entity<Project>,
+        event: &project::Event,
+        cx: &mut Context<Self>,
+    ) {
+        if !self.settings.enabled {
+            return;
+        }
+        match event {
+            project::Event::WorktreeAdded(_) | project::Event::WorktreeRemoved(_) => {
+                self.rescan(cx);
+            }
+            project::Event::WorktreeUpdatedEntries(worktree_id, changes) => {
+                self.dirty_paths
+                    .extend(changes.iter().map(|(path, _, _)| ProjectPath {
+                        worktree_id: *worktree_id,
+                        path: path.clone(),
+                    }));
+                self.schedule_refresh(cx);
+            }
+            _ => {}
+        }
+    }
+
+    /// Rebuilds the map from all the files in the project.
+    fn rescan(&mut self, cx: &mut Context<Self>) {
+        let Some(project) = self.project.upgrade() else {
+            return;
+        };
+        self.files.clear();
+        self.dirty_paths.clear();
+        for worktree in project.read(cx).visible_worktrees(cx) {
+            let worktree = worktree.read(cx);
+            let worktree_id = worktree.id();
+            self.dirty_paths
+                .extend(
+                    worktree
+                        .files(false, 0)
+                        .take(MAX_FILES)
+                        .map(|entry| ProjectPath {
+                            worktree_id,
+                            path: entry.path.clone(),
+                        }),
+                );
+        }
+        cx.emit(RepoMapUpdated);
+        self.schedule_refresh(cx);
+    }
+
+    fn schedule_refresh(&mut self, cx: &mut Context<Self>) {
+        if self.refresh_task.is_some() {
+            return;
+        }
+        self.refresh_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(REFRESH_DEBOUNCE).await;
+            let Ok(Some((fs, files))) = this.update(cx, |this, cx| this.take_dirty_files(cx))
+            else {
+                return;
+            };
+            let summaries = cx
+                .background_spawn(async move { summarize_files(fs, files).await })
+                .await;
+            this.update(cx, |this, cx| {
+                this.refresh_task = None;
+                this.apply_summaries(summaries, cx);
+                if !this.dirty_paths.is_empty() {
+                    this.schedule_refresh(cx);
+                }
+            })
+            .ok();
+        }));
+    }
+
+    /// Takes the paths that changed since the last refresh, along with where
+    /// to read them from, or `None` where they were removed or are no longer
+    /// files.
+    fn take_dirty_files(
+        &mut self,
+        cx: &mut Context<Self>,
+    ) -> Option<(Arc<dyn Fs>, Vec<ChangedFile>)> {
+        let project = self.project.upgrade()?;
+        let project = project.read(cx);
+        let is_local = project.is_local();
+        let fs = project.fs().clone();
+        let mut file_count = self.files.len();
+        let files = self
+            .dirty_paths
+            .drain()
+            .filter_map(|project_path| {
+                let worktree = project
+                    .worktree_for_id(project_path.worktree_id, cx)?
+                    .read(cx);
+                let key = Path::new(worktree.root_name()).join(&project_path.path);
+                let entry = worktree
+                    .entry_for_path(&project_path.path)
+                    .filter(|entry| entry.is_file() && !entry.is_ignored);
+                let Some(entry) = entry else {
+                    return Some((key, None));
+                };
+                if !self.files.contains_key(&key) {
+                    if file_count >= MAX_FILES {
+                        return None;
+                    }
+                    file_count += 1;
+                }
+                let abs_path = is_local.then(|| worktree.abs_path().join(&project_path.path));
+                Some((key, Some((abs_path, entry.size))))
+            })
+            .collect();
+        Some((fs, files))
+    }
+
+    fn apply_summaries(
+        &mut self,
+        summaries: Vec<(PathBuf, Option<FileSummary>)>,
+        cx: &mut Context<Self>,
+    ) {
+        let mut changed = false;
+        for (key, summary) in summaries {
+            match summary {
+                Some(summary) => {
+                    if self.files.get(&key) != Some(&summary) {
+                        self.files.insert(key, summary);
+                        changed = true;
+                    }
+                }
+                None => {
+                    // A removed directory takes the files in it with it.
+                    let file_count = self.files.len();
+                    self.files.retain(|path, _| !path.starts_with(&key));
+                    changed |= self.files.len() != file_count;
+                }
+            }
+        }
+        if changed {
+            cx.emit(RepoMapUpdated);
+        }
+    }
+}
+
+async fn summarize_files(
+    fs: Arc<dyn Fs>,
+    files: Vec<ChangedFile>,
+) -> Vec<(PathBuf, Option<FileSummary>)> {
+    let mut summaries = Vec::with_capacity(files.len());
+    for (key, file) in files {
+        let Some((abs_path, size)) = file else {
+            summaries.push((key, None));
+            continue;
+        };
+        let extension = key
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        let mut symbols = Vec::new();
+        if let Some(abs_path) = abs_path.filter(|_| size <= MAX_SCANNED_FILE_SIZE) {
+            if let Some(pattern) = symbol_pattern(extension) {
+                if let Some(text) = fs.load(&abs_path).await.log_err() {
+                    symbols = extract_symbols(pattern, &text);
+                }
+            }
+        }
+        summaries.push((
+            key,
+            Some(FileSummary {
+                size_kb: size.div_ceil(1024),
+                symbols,
+            }),
+        ));
+    }
+    summaries
+}
+
+static SYMBOL_PATTERNS: LazyLock<Vec<(&'static [&'static str], Regex)>> = LazyLock::new(|| {
+    [
+        (
+            &["rs"][..],
+            r#"(?m)^\s*pub(?:\([^)]*\))?\s+(?:(?:async|const|unsafe|extern\s+"[^"]*")\s+)*(?P<kind>fn|struct|enum|trait|type|union|mod|const|static)\s+(?P<name>[A-Za-z_]\w*)"#,
+        ),
+        (
+            &["py"][..],
+            r"(?m)^(?P<kind>class|def|async def)\s+(?P<name>[A-Za-z]\w*)",
+        ),
+        (
+            &["ts", "tsx", "js", "jsx", "mjs", "cjs"][..],
+            r"(?m)^export\s+(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(?P<kind>function|class|interface|type|enum|const)\s+(?P<name>[A-Za-z_$][\w$]*)",
+        ),
+        (
+            &["go"][..],
+            r"(?m)^(?P<kind>func|type)\s+(?:\([^)]*\)\s*)?(?P<name>[A-Z]\w*)",
+        ),
+        (
+            &["java", "cs"][..],
+            r"(?m)^\s*public\s+(?:(?:static|final|abstract|sealed|partial)\s+)*(?P<kind>class|interface|enum|record|struct)\s+(?P<name>[A-Za-z_]\w*)",
+        ),
+        (
+            &["rb"][..],
+            r"(?m)^\s*(?P<kind>class|module)\s+(?P<name>[A-Z][\w:]*)",
+        ),
+    ]
+    .into_iter()
+    .map(|(extensions, pattern)| (extensions, Regex::new(pattern).unwrap()))
+    .collect()
+});
+
+fn symbol_pattern(extension: &str) -> Option<&'static Regex> {
+    SYMBOL_PATTERNS
+        .iter()
+        .find(|(extensions, _)| extensions.contains(&extension))
+        .map(|(_, pattern)| pattern)
+}
+
+/// Finds the public definitions in a file, such as `struct Thread`.
+fn extract_symbols(pattern: &Regex, text: &str) -> Vec<String> {
+    pattern
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let kind = captures.name("kind")?.as_str();
+            let name = captures.name("name")?.as_str();
+            Some(format!("{} {name}", kind.split_whitespace().last()?))
+        })
+        .collect()
+}
+
+/// Renders the map with as much detail as fits within the token limit.
+fn render_map(files: &BTreeMap<PathBuf, FileSummary>, max_tokens: usize) -> String {
+    let max_len = max_tokens * BYTES_PER_TOKEN_ESTIMATE;
+    let mut map = String::new();
+    for detail in [
+        Detail::AllSymbols,
+        Detail::SomeSymbols,
+        Detail::FileNames,
+        Detail::Directories,
+    ] {
+        map = render_with_detail(files, detail);
+        if map.len() <= max_len {
+            return map;
+        }
+    }
+
+    // Even the directories don't fit, so the list is cut short.
+    let mut end = 0;
+    for line in map.split_inclusive('\n') {
+        if end + line.len() > max_len {
+            break;
+        }
+        end += line.len();
+    }
+    map.truncate(end);
+    map.push_str("...\n");
+    map
+}
+
+fn render_with_detail(files: &BTreeMap<PathBuf, FileSummary>, detail: Detail) -> String {
+    let mut directories = BTreeMap::<&Path, Vec<(&str, &FileSummary)>>::new();
+    for (path, summary) in files {
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        directories
+            .entry(directory)
+            .or_default()
+            .push((name, summary));
+    }
+
+    let mut map = String::new();
+    for (directory, files) in directories {
+        if detail == Detail::Directories {
+            let size_kb = files
+                .iter()
+                .map(|(_, summary)| summary.size_kb)
+                .sum::<u64>();
+            let file_count = match files.len() {
+                1 => "1 file".to_string(),
+                count => format!("{count} files"),
+            };
+            writeln!(map, "{}/ ({file_count}, {size_kb} KB)", directory.display()).ok();
+            continue;
+        }
+
+        writeln!(map, "{}/", directory.display()).ok();
+        for (name, summary) in files {
+            write!(map, "  {name} ({} KB)", summary.size_kb).ok();
+            let symbols = match detail {
+                Detail::AllSymbols => &summary.symbols[..],
+                Detail::SomeSymbols => {
+                    &summary.symbols[..summary.symbols.len().min(ABBREVIATED_SYMBOL_COUNT)]
+                }
+                Detail::FileNames | Detail::Directories => &[],
+            };
+            if !symbols.is_empty() {
+                write!(map, ": {}", symbols.join(", ")).ok();
+                if symbols.len() < summary.symbols.len() {
+                    write!(map, ", ...").ok();
+                }
+            }
+            map.push('\n');
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_symbols() {
+        let rust = "pub struct Thread {\n}\n\nimpl Thread {\n    pub fn new() -> Self {}\n    fn private() {}\n    pub(crate) async fn send() {}\n}\npub const fn limit() {}\npub const MAX: usize = 1;\n";
+        assert_eq!(
+            extract_symbols(symbol_pattern("rs").unwrap(), rust),
+            [
+                "struct Thread",
+                "fn new",
+                "fn send",
+                "fn limit",
+                "const MAX"
+            ]
+        );
+
+        let python = "class Model:\n    def method(self):\n        pass\n\ndef _private():\n    pass\n\nasync def fetch():\n    pass\n";
+        assert_eq!(
+            extract_symbols(symbol_pattern("py").unwrap(), python),
+            ["class Model", "def fetch"]
+        );
+
+        let typescript = "export default class App {}\nexport async function load() {}\nfunction local() {}\nexport type Id = string;\n";
+        assert_eq!(
+            extract_symbols(symbol_pattern("tsx").unwrap(), typescript),
+            ["class App", "function load", "type Id"]
+        );
+
+        assert!(symbol_pattern("md").is_none());
+    }
+
+    #[test]
+    fn test_render_map() {
+        let files = BTreeMap::from_iter([
+            (
+                PathBuf::from("project/src/lib.rs"),
+                FileSummary {
+                    size_kb: 3,
+                    symbols: (0..8).map(|ix| format!("fn f{ix}")).collect(),
+                },
+            ),
+            (
+                PathBuf::from("project/src/ui/mod.rs"),
+                FileSummary {
+                    size_kb: 1,
+                    symbols: vec!["struct View".into()],
+                },
+            ),
+            (
+                PathBuf::from("project/src/main.rs"),
+                FileSummary {
+                    size_kb: 2,
+                    symbols: Vec::new(),
+                },
+            ),
+        ]);
+
+        assert_eq!(
+            render_map(&files, 1000),
+            "project/src/\n  lib.rs (3 KB): fn f0, fn f1, fn f2, fn f3, fn f4, fn f5, fn f6, fn f7\n  main.rs (2 KB)\nproject/src/ui/\n  mod.rs (1 KB): struct View\n"
+        );
+        assert_eq!(
+            render_map(&files, 33),
+            "project/src/\n  lib.rs (3 KB): fn f0, fn f1, fn f2, fn f3, fn f4, ...\n  main.rs (2 KB)\nproject/src/ui/\n  mod.rs (1 KB): struct View\n"
+        );
+        assert_eq!(
+            render_map(&files, 16),
+            "project/src/ (2 files, 5 KB)\nproject/src/ui/ (1 file, 1 KB)\n"
+        );
+        assert_eq!(render_map(&files, 8), "project/src/ (2 files, 5 KB)\n...\n");
+    }
+}
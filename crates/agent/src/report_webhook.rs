@@ -0,0 +1,215 @@
+use agent_settings::{AgentSettings, ReportWebhook, ReportWebhookFormat};
+use anyhow::{Context as _, Result, anyhow, bail};
+use futures::future::join_all;
+use gpui::{App, AppContext as _, Task};
+use http_client::{AsyncBody, HttpClient, Method, Request, Url};
+use serde_json::json;
+use settings::Settings as _;
+use std::sync::Arc;
+use util::ResultExt as _;
+
+const DEFAULT_TEMPLATE: &str =
+    "$ZED_REPORT_TITLE ($ZED_REPORT_PROJECT, $ZED_REPORT_STATUS)\n\n$ZED_REPORT_SUMMARY";
+
+/// The maximum length of a Discord message.
+const DISCORD_MAX_CHARS: usize = 2000;
+
+/// The final summary of an agent thread, posted to the `report_webhooks`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct AgentReport {
+    pub title: String,
+    pub project: String,
+    /// How the thread ended, such as "finished" or "failed".
+    pub status: String,
+    /// The agent's final message, or the error the thread failed with.
+    pub summary: String,
+}
+
+impl AgentReport {
+    /// Substitutes the report's fields for the placeholders in the template,
+    /// in a single pass, so that placeholders in the fields are left as is.
+    fn render(&self, template: &str) -> String {
+        const PLACEHOLDER_PREFIX: &str = "$ZED_REPORT_";
+        let fields = [
+            ("TITLE", self.title.as_str()),
+            ("PROJECT", self.project.as_str()),
+            ("STATUS", self.status.as_str()),
+            ("SUMMARY", self.summary.trim()),
+        ];
+
+        let mut rendered = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find(PLACEHOLDER_PREFIX) {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start + PLACEHOLDER_PREFIX.len()..];
+            match fields.iter().find(|(name, _)| rest.starts_with(name)) {
+                Some((name, value)) => {
+                    rendered.push_str(value);
+                    rest = &rest[name.len()..];
+                }
+                None => rendered.push_str(PLACEHOLDER_PREFIX),
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+
+    fn payload(&self, webhook: &ReportWebhook) -> serde_json::Value {
+        let text = self.render(webhook.template.as_deref().unwrap_or(DEFAULT_TEMPLATE));
+        match webhook.format {
+            ReportWebhookFormat::Slack => json!({ "text": text }),
+            ReportWebhookFormat::Discord => {
+                let text = if text.chars().count() > DISCORD_MAX_CHARS {
+                    let mut text = text.chars().take(DISCORD_MAX_CHARS - 1).collect::<String>();
+                    text.push('…');
+                    text
+                } else {
+                    text
+                };
+                json!({ "content": text })
+            }
+            ReportWebhookFormat::Generic => json!({
+                "text": text,
+                "title": self.title,
+                "project": self.project,
+                "status": self.status,
+                "summary": self.summary,
+            }),
+        }
+    }
+}
+
+/// Posts the report to every configured webhook, logging the ones that fail.
+pub(crate) fn post_report(report: AgentReport, cx: &App) -> Task<()> {
+    let webhooks = AgentSettings::get_global(cx).report_webhooks.clone();
+    if webhooks.is_empty() {
+        return Task::ready(());
+    }
+    let http_client = cx.http_client();
+    cx.background_spawn(async move {
+        join_all(webhooks.iter().enumerate().map(|(ix, webhook)| {
+            let payload = report.payload(webhook);
+            let http_client = http_client.clone();
+            async move {
+                post_json(http_client, &webhook.url, &payload)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "posting agent report to webhook {} on {}",
+                            ix + 1,
+                            webhook_host(&webhook.url)
+                        )
+                    })
+                    .log_err();
+            }
+        }))
+        .await;
+    })
+}
+
+/// The host of a webhook's URL, which unlike the rest of the URL doesn't
+/// contain its secret token, so it can be logged.
+fn webhook_host(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(ToString::to_string))
+        .unwrap_or_else(|| "an invalid URL".to_string())
+}
+
+/// Posts the payload, leaving the URL out of the errors, which get logged.
+async fn post_json(
+    http_client: Arc<dyn HttpClient>,
+    url: &str,
+    payload: &serde_json::Value,
+) -> Result<()> {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header("Content-Type", "application/json")
+        .body(AsyncBody::from(serde_json::to_vec(payload)?))
+        .map_err(|_| anyhow!("invalid webhook URL"))?;
+    let response = http_client
+        .send(request)
+        .await
+        .map_err(|error| anyhow!(format!("{error:#}").replace(url, "<webhook URL>")))?;
+    if !response.status().is_success() {
+        bail!("status error {}", response.status().as_u16());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> AgentReport {
+        AgentReport {
+            title: "Fix flaky test".into(),
+            project: "zed".into(),
+            status: "finished".into(),
+            summary: "The test was racing the file watcher.\n".into(),
+        }
+    }
+
+    fn webhook(format: ReportWebhookFormat, template: Option<&str>) -> ReportWebhook {
+        ReportWebhook {
+            url: "https://example.com/hook".into(),
+            format,
+            template: template.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn test_report_payload() {
+        assert_eq!(
+            report().payload(&webhook(ReportWebhookFormat::Slack, None)),
+            json!({
+                "text": "Fix flaky test (zed, finished)\n\nThe test was racing the file watcher."
+            })
+        );
+        assert_eq!(
+            report().payload(&webhook(
+                ReportWebhookFormat::Discord,
+                Some("**$ZED_REPORT_TITLE**: $ZED_REPORT_STATUS")
+            )),
+            json!({ "content": "**Fix flaky test**: finished" })
+        );
+
+        let payload = report().payload(&webhook(ReportWebhookFormat::Generic, Some("done")));
+        assert_eq!(payload["text"], "done");
+        assert_eq!(payload["project"], "zed");
+        assert_eq!(payload["status"], "finished");
+    }
+
+    #[test]
+    fn test_placeholders_in_fields_are_not_substituted() {
+        let report = AgentReport {
+            title: "Print $ZED_REPORT_SUMMARY".into(),
+            status: "$ZED_REPORT_PROJECT".into(),
+            ..report()
+        };
+        assert_eq!(
+            report.render("$ZED_REPORT_TITLE: $ZED_REPORT_STATUS $ZED_REPORT_UNKNOWN"),
+            "Print $ZED_REPORT_SUMMARY: $ZED_REPORT_PROJECT $ZED_REPORT_UNKNOWN"
+        );
+    }
+
+    #[test]
+    fn test_webhook_host() {
+        assert_eq!(
+            webhook_host("https://hooks.slack.com/services/T000/B000/secret"),
+            "hooks.slack.com"
+        );
+        assert_eq!(webhook_host("not a url"), "an invalid URL");
+    }
+
+    #[test]
+    fn test_discord_payload_is_truncated() {
+        let mut report = report();
+        report.summary = "a".repeat(3000);
+        let payload = report.payload(&webhook(ReportWebhookFormat::Discord, None));
+        let content = payload["content"].as_str().unwrap();
+        assert_eq!(content.chars().count(), DISCORD_MAX_CHARS);
+        assert!(content.ends_with('…'));
+    }
+}
@@ -13,6 +13,9 @@ pub struct SlashCommandSettings {
     /// Settings for the `/cargo-workspace` slash command.
     #[serde(default)]
     pub cargo_workspace: CargoWorkspaceCommandSettings,
+    /// Settings for the `/issue` and `/pr` slash commands.
+    #[serde(default)]
+    pub issue_tracker: IssueTrackerCommandSettings,
 }
 
 /// Settings for the `/docs` slash command.
@@ -31,6 +34,24 @@ pub struct CargoWorkspaceCommandSettings {
     pub enabled: bool,
 }
 
+/// Settings for the `/issue` and `/pr` slash commands.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, JsonSchema)]
+pub struct IssueTrackerCommandSettings {
+    /// The token used to fetch GitHub issues and pull requests. Falls back to
+    /// the `GITHUB_TOKEN` environment variable.
+    #[serde(default)]
+    pub github_token: Option<String>,
+    /// The token used to fetch GitLab issues and merge requests. Falls back to
+    /// the `GITLAB_TOKEN` environment variable.
+    #[serde(default)]
+    pub gitlab_token: Option<String>,
+    /// The self-hosted GitHub Enterprise and GitLab hosts the tokens may be
+    /// sent to. Tokens are always sent to github.com, gitlab.com and the host
+    /// of the project's `origin` remote, and never to other hosts.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+}
+
 impl Settings for SlashCommandSettings {
     const KEY: Option<&'static str> = Some("slash_commands");
 
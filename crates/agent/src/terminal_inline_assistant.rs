@@ -4,6 +4,7 @@ use crate::inline_prompt_editor::{
     CodegenStatus, PromptEditor, PromptEditorEvent, TerminalInlineAssistId,
 };
 use crate::terminal_codegen::{CLEAR_INPUT, CodegenEvent, TerminalCodegen};
+use crate::terminal_remote::{RemotePlatform, RemoteTerminalTarget};
 use crate::thread_store::{TextThreadStore, ThreadStore};
 use agent_settings::AgentSettings;
 use anyhow::{Context as _, Result};
@@ -11,11 +12,12 @@ use client::telemetry::Telemetry;
 use collections::{HashMap, VecDeque};
 use editor::{MultiBuffer, actions::SelectAll};
 use fs::Fs;
+use futures::{FutureExt as _, future::Shared};
 use gpui::{App, Entity, Focusable, Global, Subscription, Task, UpdateGlobal, WeakEntity};
 use language::Buffer;
 use language_model::{
     ConfiguredModel, LanguageModelRegistry, LanguageModelRequest, LanguageModelRequestMessage,
-    Role, report_assistant_event,
+    RequestPriority, Role, report_assistant_event,
 };
 use project::Project;
 use prompt_store::{PromptBuilder, PromptStore};
@@ -79,6 +81,17 @@ impl TerminalInlineAssistant {
         cx: &mut App,
     ) {
         let terminal = terminal_view.read(cx).terminal().clone();
+        let remote = RemoteTerminalTarget::detect(
+            terminal.read(cx),
+            project.upgrade().as_ref().map(|project| project.read(cx)),
+            cx,
+        )
+        .map(|target| {
+            // Start probing right away, so the platform is usually known by the
+            // time the prompt has been written.
+            let platform = target.probe_platform(cx).shared();
+            (target, platform)
+        });
         let assist_id = self.next_assist_id.post_inc();
         let prompt_buffer = cx.new(|cx| {
             MultiBuffer::singleton(
@@ -95,11 +108,15 @@ impl TerminalInlineAssistant {
                 self.prompt_history.clone(),
                 prompt_buffer.clone(),
                 codegen,
+                remote
+                    .as_ref()
+                    .map(|(target, _)| SharedString::from(target.host.clone())),
                 self.fs.clone(),
                 context_store.clone(),
                 workspace.clone(),
                 thread_store.clone(),
                 text_thread_store.clone(),
+                prompt_store.clone(),
                 window,
                 cx,
             )
@@ -120,6 +137,7 @@ impl TerminalInlineAssistant {
             workspace.clone(),
             context_store,
             prompt_store,
+            remote,
             window,
             cx,
         );
@@ -231,7 +249,12 @@ impl TerminalInlineAssistant {
     ) -> Result<Task<LanguageModelRequest>> {
         let assist = self.assists.get(&assist_id).context("invalid assist")?;
 
-        let shell = std::env::var("SHELL").ok();
+        // The local shell and working directory don't apply to remote sessions.
+        let remote = assist.remote.as_ref().map(|(target, platform)| {
+            let platform = platform.clone().now_or_never().unwrap_or_default();
+            target.prompt_context(platform)
+        });
+        let shell = std::env::var("SHELL").ok().filter(|_| remote.is_none());
         let (latest_output, working_directory) = assist
             .terminal
             .update(cx, |terminal, cx| {
@@ -244,6 +267,7 @@ impl TerminalInlineAssistant {
             })
             .ok()
             .unwrap_or_default();
+        let working_directory = working_directory.filter(|_| remote.is_none());
 
         let prompt = self.prompt_builder.generate_terminal_assistant_prompt(
             &assist
@@ -255,6 +279,7 @@ impl TerminalInlineAssistant {
             shell.as_deref(),
             working_directory.as_deref(),
             &latest_output,
+            remote,
         )?;
 
         let contexts = assist
@@ -298,6 +323,9 @@ impl TerminalInlineAssistant {
                 tool_choice: None,
                 stop: Vec::new(),
                 temperature,
+                seed: None,
+                priority: RequestPriority::Interactive,
+                web_search: false,
             }
         }))
     }
@@ -413,6 +441,7 @@ struct TerminalInlineAssist {
     workspace: WeakEntity<Workspace>,
     context_store: Entity<ContextStore>,
     prompt_store: Option<Entity<PromptStore>>,
+    remote: Option<(RemoteTerminalTarget, Shared<Task<RemotePlatform>>)>,
     _subscriptions: Vec<Subscription>,
 }
 
@@ -424,6 +453,7 @@ impl TerminalInlineAssist {
         workspace: WeakEntity<Workspace>,
         context_store: Entity<ContextStore>,
         prompt_store: Option<Entity<PromptStore>>,
+        remote: Option<(RemoteTerminalTarget, Shared<Task<RemotePlatform>>)>,
         window: &mut Window,
         cx: &mut App,
     ) -> Self {
@@ -435,6 +465,7 @@ impl TerminalInlineAssist {
             workspace: workspace.clone(),
             context_store,
             prompt_store,
+            remote,
             _subscriptions: vec![
                 window.subscribe(&prompt_editor, cx, |prompt_editor, event, window, cx| {
                     TerminalInlineAssistant::update_global(cx, |this, cx| {
@@ -0,0 +1,289 @@
+use std::{path::Path, time::Duration};
+
+use futures::FutureExt as _;
+use gpui::{App, AppContext as _, Task};
+use project::Project;
+use prompt_store::TerminalAssistantRemoteContext;
+use terminal::Terminal;
+use util::command::new_smol_command;
+
+const PROBE_SCRIPT: &str = "uname -sm; echo \"$SHELL\"";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemoteTerminalKind {
+    Ssh,
+    Container,
+}
+
+impl RemoteTerminalKind {
+    fn label(&self) -> &'static str {
+        match self {
+            RemoteTerminalKind::Ssh => "SSH host",
+            RemoteTerminalKind::Container => "container",
+        }
+    }
+}
+
+/// A remote host or container that the commands typed into a terminal run on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteTerminalTarget {
+    pub kind: RemoteTerminalKind,
+    pub host: String,
+    /// The program and arguments that run a shell command on the target
+    /// without prompting, if there is one.
+    probe_command: Option<Vec<String>>,
+}
+
+/// The platform of a remote terminal target, as reported by the target itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RemotePlatform {
+    pub os: Option<String>,
+    pub arch: Option<String>,
+    pub shell: Option<String>,
+}
+
+impl RemoteTerminalTarget {
+    /// Detects whether the terminal is attached to a remote host or container,
+    /// either because the project is remote or because the terminal's
+    /// foreground process is a remote session, like `ssh` or `docker exec`.
+    pub fn detect(terminal: &Terminal, project: Option<&Project>, cx: &App) -> Option<Self> {
+        if terminal.is_ssh_terminal() {
+            let (host, ssh_command) = project?.ssh_details(cx)?;
+            let mut probe_command = vec!["ssh".to_string()];
+            probe_command.extend(ssh_command.arguments);
+            return Some(Self {
+                kind: RemoteTerminalKind::Ssh,
+                host,
+                probe_command: Some(probe_command),
+            });
+        }
+
+        let process = terminal.pty_info.current.as_ref()?;
+        Self::from_foreground_process(&process.argv)
+    }
+
+    fn from_foreground_process(argv: &[String]) -> Option<Self> {
+        let (program, args) = argv.split_first()?;
+        let program = Path::new(program).file_name()?.to_str()?;
+        match program {
+            "ssh" | "mosh" | "autossh" => {
+                let host = first_positional(args, SSH_FLAGS_WITH_VALUES)?;
+                Some(Self {
+                    kind: RemoteTerminalKind::Ssh,
+                    host: host.to_string(),
+                    // The user's own session may require interactive authentication.
+                    probe_command: None,
+                })
+            }
+            "docker" | "podman" | "nerdctl" => {
+                let (subcommand, args) = args.split_first()?;
+                if subcommand != "exec" && subcommand != "run" && subcommand != "attach" {
+                    return None;
+                }
+                let container = first_positional(args, CONTAINER_FLAGS_WITH_VALUES)?;
+                // Only `exec` names a running container that we can run commands in.
+                let probe_command = (subcommand == "exec").then(|| {
+                    vec![
+                        program.to_string(),
+                        "exec".to_string(),
+                        container.to_string(),
+                    ]
+                });
+                Some(Self {
+                    kind: RemoteTerminalKind::Container,
+                    host: container.to_string(),
+                    probe_command,
+                })
+            }
+            "kubectl" | "oc" => {
+                let (subcommand, args) = args.split_first()?;
+                if subcommand != "exec" && subcommand != "attach" {
+                    return None;
+                }
+                let pod = first_positional(args, KUBECTL_FLAGS_WITH_VALUES)?;
+                Some(Self {
+                    kind: RemoteTerminalKind::Container,
+                    host: pod.to_string(),
+                    probe_command: None,
+                })
+            }
+            "devcontainer" => {
+                let (subcommand, _) = args.split_first()?;
+                if subcommand != "exec" {
+                    return None;
+                }
+                Some(Self {
+                    kind: RemoteTerminalKind::Container,
+                    host: "dev container".to_string(),
+                    probe_command: None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Asks the target for its OS, architecture and shell, when it can be
+    /// reached without user interaction.
+    pub fn probe_platform(&self, cx: &App) -> Task<RemotePlatform> {
+        let Some((program, args)) = self
+            .probe_command
+            .as_ref()
+            .and_then(|command| command.split_first())
+        else {
+            return Task::ready(RemotePlatform::default());
+        };
+
+        let mut command = new_smol_command(program);
+        // Stop the probe when it times out, rather than leaving it hanging on
+        // an unreachable host.
+        command.args(args).kill_on_drop(true);
+        match self.kind {
+            RemoteTerminalKind::Ssh => command.arg(PROBE_SCRIPT),
+            RemoteTerminalKind::Container => command.args(["sh", "-c", PROBE_SCRIPT]),
+        };
+
+        let timer = cx.background_executor().timer(PROBE_TIMEOUT);
+        cx.background_spawn(async move {
+            let output = futures::select_biased! {
+                output = command.output().fuse() => output.ok(),
+                _ = timer.fuse() => None,
+            };
+            output
+                .filter(|output| output.status.success())
+                .map(|output| RemotePlatform::parse(&String::from_utf8_lossy(&output.stdout)))
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn prompt_context(&self, platform: RemotePlatform) -> TerminalAssistantRemoteContext {
+        TerminalAssistantRemoteContext {
+            kind: self.kind.label().to_string(),
+            host: self.host.clone(),
+            os: platform.os,
+            arch: platform.arch,
+            shell: platform.shell,
+        }
+    }
+}
+
+impl RemotePlatform {
+    /// Parses the output of [`PROBE_SCRIPT`].
+    fn parse(output: &str) -> Self {
+        let mut lines = output.lines().map(str::trim);
+        let (os, arch) = lines
+            .next()
+            .and_then(|uname| uname.split_once(' '))
+            .map(|(os, arch)| {
+                let os = match os {
+                    "Darwin" => "macos".to_string(),
+                    os => os.to_lowercase(),
+                };
+                (Some(os), Some(arch.to_string()))
+            })
+            .unwrap_or_default();
+        let shell = lines
+            .next()
+            .filter(|shell| !shell.is_empty())
+            .map(str::to_string);
+        Self { os, arch, shell }
+    }
+}
+
+const SSH_FLAGS_WITH_VALUES: &[&str] = &[
+    "-B", "-b", "-c", "-D", "-E", "-e", "-F", "-I", "-i", "-J", "-L", "-l", "-m", "-O", "-o", "-p",
+    "-Q", "-R", "-S", "-W", "-w", "--ssh", "--port",
+];
+
+const CONTAINER_FLAGS_WITH_VALUES: &[&str] = &[
+    "-e",
+    "--env",
+    "--env-file",
+    "-u",
+    "--user",
+    "-w",
+    "--workdir",
+    "--name",
+    "-v",
+    "--volume",
+    "-p",
+    "--publish",
+    "--network",
+    "--entrypoint",
+    "--platform",
+];
+
+const KUBECTL_FLAGS_WITH_VALUES: &[&str] = &[
+    "-n",
+    "--namespace",
+    "-c",
+    "--container",
+    "--context",
+    "--kubeconfig",
+    "-f",
+    "--filename",
+];
+
+/// Returns the first argument that is neither a flag nor a flag's value.
+fn first_positional<'a>(args: &'a [String], flags_with_values: &[&str]) -> Option<&'a str> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            return args.next().map(String::as_str);
+        } else if flags_with_values.contains(&arg.as_str()) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            return Some(arg);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(argv: &str) -> Option<(RemoteTerminalKind, String)> {
+        let argv = argv.split(' ').map(str::to_string).collect::<Vec<_>>();
+        RemoteTerminalTarget::from_foreground_process(&argv)
+            .map(|target| (target.kind, target.host))
+    }
+
+    #[test]
+    fn test_detect_remote_sessions() {
+        assert_eq!(
+            target("/usr/bin/ssh -p 2222 -i key.pem deploy@example.com"),
+            Some((RemoteTerminalKind::Ssh, "deploy@example.com".into()))
+        );
+        assert_eq!(
+            target("docker exec -it -u root web bash"),
+            Some((RemoteTerminalKind::Container, "web".into()))
+        );
+        assert_eq!(
+            target("kubectl exec -n staging -it api-7d9f -- sh"),
+            Some((RemoteTerminalKind::Container, "api-7d9f".into()))
+        );
+        assert_eq!(target("docker ps"), None);
+        assert_eq!(target("/bin/zsh -l"), None);
+    }
+
+    #[test]
+    fn test_parse_remote_platform() {
+        assert_eq!(
+            RemotePlatform::parse("Linux x86_64\n/bin/bash\n"),
+            RemotePlatform {
+                os: Some("linux".into()),
+                arch: Some("x86_64".into()),
+                shell: Some("/bin/bash".into()),
+            }
+        );
+        assert_eq!(
+            RemotePlatform::parse("Darwin arm64\n\n"),
+            RemotePlatform {
+                os: Some("macos".into()),
+                arch: Some("arm64".into()),
+                shell: None,
+            }
+        );
+    }
+}
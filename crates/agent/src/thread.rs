@@ -2,11 +2,14 @@ use std::fmt::Write as _;
 use std::io::Write;
 use std::ops::Range;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use agent_settings::{AgentProfileId, AgentSettings, CompletionMode};
+use agent_settings::{AgentProfileId, AgentSettings, CompletionMode, EditVerificationSettings};
 use anyhow::{Result, anyhow};
-use assistant_tool::{ActionLog, AnyToolCard, Tool, ToolWorkingSet};
+use assistant_context_editor::{ModelSubstitution, ModelSubstitutionReason, free_model_substitute};
+use assistant_tool::{
+    ActionLog, AnyToolCard, Tool, ToolResultContent, ToolResultOutput, ToolWorkingSet,
+};
 use chrono::{DateTime, Utc};
 use collections::HashMap;
 use editor::display_map::CreaseMetadata;
@@ -20,11 +23,12 @@ use gpui::{
 };
 use language_model::{
     ConfiguredModel, LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent,
-    LanguageModelId, LanguageModelKnownError, LanguageModelRegistry, LanguageModelRequest,
-    LanguageModelRequestMessage, LanguageModelRequestTool, LanguageModelToolResult,
-    LanguageModelToolResultContent, LanguageModelToolUseId, MessageContent,
-    ModelRequestLimitReachedError, PaymentRequiredError, RequestUsage, Role, SelectedModel,
-    StopReason, TokenUsage,
+    LanguageModelDeprecation, LanguageModelError, LanguageModelId, LanguageModelRegistry,
+    LanguageModelRequest, LanguageModelRequestMessage, LanguageModelRequestTool,
+    LanguageModelToolChoice, LanguageModelToolResult, LanguageModelToolResultContent,
+    LanguageModelToolSchemaFormat, LanguageModelToolUseId, MaxMonthlySpendReachedError,
+    MessageContent, ModelRequestLimitReachedError, PaymentRequiredError, RequestPriority,
+    RequestUsage, Role, SelectedModel, SpendTracker, StopReason, TokenUsage,
 };
 use postage::stream::Stream as _;
 use project::Project;
@@ -43,11 +47,26 @@ use zed_llm_client::{CompletionIntent, CompletionRequestStatus};
 use crate::ThreadStore;
 use crate::agent_profile::AgentProfile;
 use crate::context::{AgentContext, AgentContextHandle, ContextLoadResult, LoadedContext};
+use crate::edit_verification::{CheckError, fix_prompt, new_errors, parse_check_errors, run_check};
+use crate::feedback_store::{self, FeedbackRecord};
+use crate::model_recommendation::ModelLatencies;
+use crate::plan::{PLANNING_PROMPT, Plan, UPDATE_PLAN_TOOL_NAME, update_plan_tool};
+use crate::report_webhook::{AgentReport, post_report};
 use crate::thread_store::{
     SerializedCrease, SerializedLanguageModel, SerializedMessage, SerializedMessageSegment,
     SerializedThread, SerializedToolResult, SerializedToolUse, SharedProjectContext,
 };
 use crate::tool_use::{PendingToolUse, ToolUse, ToolUseMetadata, ToolUseState};
+use crate::workspace_trust::{self, WorkspaceTrust};
+
+/// How many times in a row the model may call a tool with input that doesn't
+/// match its schema, and be asked to correct it, before the turn is stopped.
+const MAX_TOOL_INPUT_REPAIRS: u32 = 3;
+
+/// Sent along with the tool results once a run has used up its time budget.
+const TIME_BUDGET_REACHED_PROMPT: &str = "You've run out of time for this request. \
+    Don't call any more tools. Summarize what you've done so far and list the \
+    steps that remain, so the work can be picked up later.";
 
 #[derive(
     Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize, JsonSchema,
@@ -117,6 +136,9 @@ pub struct Message {
     pub loaded_context: LoadedContext,
     pub creases: Vec<MessageCrease>,
     pub is_hidden: bool,
+    /// The free model that generated this message in place of the selected
+    /// one.
+    pub substitution: Option<ModelSubstitution>,
 }
 
 impl Message {
@@ -222,7 +244,8 @@ pub struct ThreadCheckpoint {
     git_checkpoint: GitStoreCheckpoint,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ThreadFeedback {
     Positive,
     Negative,
@@ -318,8 +341,15 @@ pub enum TokenUsageRatio {
 #[derive(Debug, Clone, Copy)]
 pub enum QueueState {
     Sending,
-    Queued { position: usize },
+    Queued {
+        position: usize,
+    },
     Started,
+    /// The request failed transiently and is about to be sent again.
+    Retrying {
+        attempt: u32,
+        max_attempts: u32,
+    },
 }
 
 /// A thread of conversation with the LLM.
@@ -360,7 +390,37 @@ pub struct Thread {
         Box<dyn FnMut(&LanguageModelRequest, &[Result<LanguageModelCompletionEvent, String>])>,
     >,
     remaining_turns: u32,
+    /// When the current run has to wrap up, if it has a time budget.
+    run_deadline: Option<Instant>,
+    /// Extra time granted for the next run, after the last one ran out.
+    granted_time: Option<Duration>,
+    time_budget_reached: bool,
+    /// Whether the agent proposes a plan for each request, to be approved
+    /// before it starts working.
+    plan_first: bool,
+    /// Whether code relevant to each message is found and attached to it.
+    auto_context: bool,
+    plan: Option<Plan>,
+    /// Whether the agent is working on a plan, rather than carrying one out.
+    planning: bool,
+    /// How many times in a row each tool was called with input that doesn't
+    /// match its schema.
+    invalid_tool_inputs: HashMap<Arc<str>, u32>,
+    /// Whether to post the thread's final summary to the `report_webhooks`
+    /// when it's done.
+    report_when_done: bool,
+    /// Whether the model can only use tools that neither edit files nor run
+    /// commands, as in threads started by background hooks.
+    read_only: bool,
+    edit_verification: Option<EditVerification>,
+    pending_verification: Option<Task<()>>,
+    /// The free model substitution of the request being streamed, recorded
+    /// on the assistant messages it generates.
+    pending_substitution: Option<ModelSubstitution>,
     configured_model: Option<ConfiguredModel>,
+    /// The deprecation of the model that the thread was saved with, until a
+    /// different model is chosen.
+    model_deprecation: Option<LanguageModelDeprecation>,
     profile: AgentProfile,
 }
 
@@ -391,6 +451,15 @@ impl ThreadSummary {
     }
 }
 
+/// The state of the `verify_edits` check for the current turn.
+struct EditVerification {
+    /// The errors reported before the turn's edits, checked once the first
+    /// edit tool is about to run.
+    baseline: Option<Shared<Task<Vec<CheckError>>>>,
+    /// The number of times the model has been asked to fix errors.
+    iterations: u32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExceededWindowError {
     /// Model used when last message exceeded context window
@@ -451,7 +520,21 @@ impl Thread {
             last_received_chunk_at: None,
             request_callback: None,
             remaining_turns: u32::MAX,
+            run_deadline: None,
+            granted_time: None,
+            time_budget_reached: false,
+            plan_first: false,
+            auto_context: AgentSettings::get_global(cx).auto_context.enabled,
+            plan: None,
+            planning: false,
+            invalid_tool_inputs: HashMap::default(),
+            report_when_done: false,
+            read_only: false,
+            edit_verification: None,
+            pending_verification: None,
+            pending_substitution: None,
             configured_model,
+            model_deprecation: None,
             profile: AgentProfile::new(profile_id, tools),
         }
     }
@@ -483,6 +566,10 @@ impl Thread {
         let (detailed_summary_tx, detailed_summary_rx) =
             postage::watch::channel_with(serialized.detailed_summary_state);
 
+        let model_deprecation = serialized
+            .model
+            .as_ref()
+            .and_then(|model| LanguageModelDeprecation::for_model(&model.provider, &model.model));
         let configured_model = LanguageModelRegistry::global(cx).update(cx, |registry, cx| {
             serialized
                 .model
@@ -533,6 +620,8 @@ impl Thread {
                         .collect(),
                     loaded_context: LoadedContext {
                         contexts: Vec::new(),
+                        retrieved_chunks: message.retrieved_chunks,
+                        memories: Vec::new(),
                         text: message.context,
                         images: Vec::new(),
                     },
@@ -549,6 +638,7 @@ impl Thread {
                         })
                         .collect(),
                     is_hidden: message.is_hidden,
+                    substitution: message.substitution,
                 })
                 .collect(),
             next_message_id,
@@ -576,11 +666,76 @@ impl Thread {
             last_received_chunk_at: None,
             request_callback: None,
             remaining_turns: u32::MAX,
+            run_deadline: None,
+            granted_time: None,
+            time_budget_reached: false,
+            plan_first: false,
+            auto_context: AgentSettings::get_global(cx).auto_context.enabled,
+            plan: serialized.plan,
+            planning: false,
+            invalid_tool_inputs: HashMap::default(),
+            report_when_done: false,
+            read_only: false,
+            edit_verification: None,
+            pending_verification: None,
+            pending_substitution: None,
             configured_model,
+            model_deprecation,
             profile: AgentProfile::new(profile_id, tools),
         }
     }
 
+    /// Posts the thread's final summary to the `report_webhooks`, unless the
+    /// model is still using tools or the completion was cancelled.
+    fn post_report(&self, result: Result<&StopReason, &anyhow::Error>, cx: &App) {
+        let last_response = || {
+            self.messages
+                .iter()
+                .rfind(|message| message.role == Role::Assistant)
+                .map(|message| {
+                    message
+                        .segments
+                        .iter()
+                        .filter_map(|segment| match segment {
+                            MessageSegment::Text(text) => Some(text.as_str()),
+                            _ => None,
+                        })
+                        .collect::<String>()
+                })
+                .unwrap_or_default()
+        };
+        let (status, summary) = match result {
+            Ok(StopReason::ToolUse) => return,
+            Ok(StopReason::EndTurn) => ("finished", last_response()),
+            Ok(StopReason::MaxTokens) => ("stopped at the output token limit", last_response()),
+            Ok(StopReason::Refusal) => ("refused", String::new()),
+            Err(error) => {
+                if matches!(
+                    error.downcast_ref::<LanguageModelError>(),
+                    Some(LanguageModelError::Cancelled)
+                ) {
+                    return;
+                }
+                ("failed", format!("{error:#}"))
+            }
+        };
+
+        let project = self
+            .project
+            .read(cx)
+            .visible_worktrees(cx)
+            .next()
+            .map(|worktree| worktree.read(cx).root_name().to_string())
+            .unwrap_or_default();
+        let report = AgentReport {
+            title: self.summary.unwrap_or(ThreadSummary::DEFAULT).to_string(),
+            project,
+            status: status.to_string(),
+            summary,
+        };
+        post_report(report, cx).detach();
+    }
+
     pub fn set_request_callback(
         &mut self,
         callback: impl 'static
@@ -637,9 +792,14 @@ impl Thread {
 
     pub fn set_configured_model(&mut self, model: Option<ConfiguredModel>, cx: &mut Context<Self>) {
         self.configured_model = model;
+        self.model_deprecation = None;
         cx.notify();
     }
 
+    pub fn model_deprecation(&self) -> Option<LanguageModelDeprecation> {
+        self.model_deprecation
+    }
+
     pub fn summary(&self) -> &ThreadSummary {
         &self.summary
     }
@@ -671,6 +831,87 @@ impl Thread {
         self.completion_mode = mode;
     }
 
+    pub fn report_when_done(&self) -> bool {
+        self.report_when_done
+    }
+
+    pub fn set_report_when_done(&mut self, report_when_done: bool) {
+        self.report_when_done = report_when_done;
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn plan_first(&self) -> bool {
+        self.plan_first
+    }
+
+    pub fn set_plan_first(&mut self, plan_first: bool) {
+        self.plan_first = plan_first;
+    }
+
+    pub fn auto_context(&self) -> bool {
+        self.auto_context
+    }
+
+    pub fn set_auto_context(&mut self, auto_context: bool) {
+        self.auto_context = auto_context;
+    }
+
+    pub fn plan(&self) -> Option<&Plan> {
+        self.plan.as_ref()
+    }
+
+    pub fn move_plan_step(&mut self, from: usize, to: usize, cx: &mut Context<Self>) {
+        if let Some(plan) = self.plan.as_mut().filter(|plan| !plan.approved) {
+            plan.move_step(from, to);
+            cx.emit(ThreadEvent::PlanChanged);
+        }
+    }
+
+    pub fn remove_plan_step(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if let Some(plan) = self.plan.as_mut().filter(|plan| !plan.approved) {
+            plan.remove_step(ix);
+            cx.emit(ThreadEvent::PlanChanged);
+        }
+    }
+
+    /// Approves the proposed plan, as edited, and has the agent carry it out.
+    pub fn approve_plan(&mut self, window: Option<AnyWindowHandle>, cx: &mut Context<Self>) {
+        let Some(plan) = self
+            .plan
+            .as_mut()
+            .filter(|plan| !plan.approved && !plan.steps.is_empty())
+        else {
+            return;
+        };
+        let Some(model) = self
+            .configured_model
+            .as_ref()
+            .map(|model| model.model.clone())
+        else {
+            return;
+        };
+        plan.approved = true;
+        let prompt = plan.execution_prompt();
+        cx.emit(ThreadEvent::PlanChanged);
+        self.insert_message(
+            Role::User,
+            vec![MessageSegment::Text(prompt)],
+            LoadedContext::default(),
+            Vec::new(),
+            true,
+            cx,
+        );
+        self.advance_prompt_id();
+        self.send_to_model(model, CompletionIntent::UserPrompt, window, cx);
+    }
+
     pub fn message(&self, id: MessageId) -> Option<&Message> {
         let index = self
             .messages
@@ -685,7 +926,9 @@ impl Thread {
     }
 
     pub fn is_generating(&self) -> bool {
-        !self.pending_completions.is_empty() || !self.all_tools_finished()
+        !self.pending_completions.is_empty()
+            || !self.all_tools_finished()
+            || self.pending_verification.is_some()
     }
 
     /// Indicates whether streaming of language model events is stale.
@@ -883,6 +1126,17 @@ impl Thread {
         self.tool_use_limit_reached
     }
 
+    /// Whether the last run was stopped for going over `max_run_minutes`.
+    pub fn time_budget_reached(&self) -> bool {
+        self.time_budget_reached
+    }
+
+    /// Gives the next run the given amount of time, instead of
+    /// `max_run_minutes`, so a run that ran out of time can be continued.
+    pub fn grant_more_time(&mut self, time: Duration) {
+        self.granted_time = Some(time);
+    }
+
     /// Returns whether all of the tool uses have finished running.
     pub fn all_tools_finished(&self) -> bool {
         // If the only pending tool uses left are the ones with errors, then
@@ -938,9 +1192,14 @@ impl Thread {
         model: Arc<dyn LanguageModel>,
     ) -> Vec<LanguageModelRequestTool> {
         if model.supports_tools() {
-            self.profile
+            let trusted = WorkspaceTrust::is_trusted(&self.project, cx);
+            let mut tools = self
+                .profile
                 .enabled_tools(cx)
                 .into_iter()
+                .filter(|tool| trusted || !workspace_trust::requires_trust(tool.as_ref()))
+                .filter(|tool| !self.planning || !tool.may_perform_edits())
+                .filter(|tool| !self.read_only || is_read_only(tool.as_ref()))
                 .filter_map(|tool| {
                     // Skip tools that cannot be supported
                     let input_schema = tool.input_schema(model.tool_input_format()).ok()?;
@@ -950,7 +1209,11 @@ impl Thread {
                         input_schema,
                     })
                 })
-                .collect()
+                .collect::<Vec<_>>();
+            if self.plan.as_ref().is_some_and(|plan| plan.approved) {
+                tools.push(update_plan_tool());
+            }
+            tools
         } else {
             Vec::default()
         }
@@ -972,6 +1235,9 @@ impl Thread {
             });
         }
 
+        if self.plan.take().is_some() {
+            cx.emit(ThreadEvent::PlanChanged);
+        }
         let message_id = self.insert_message(
             Role::User,
             vec![MessageSegment::Text(text.into())],
@@ -1012,14 +1278,18 @@ impl Thread {
         segments: Vec<MessageSegment>,
         cx: &mut Context<Self>,
     ) -> MessageId {
-        self.insert_message(
+        let id = self.insert_message(
             Role::Assistant,
             segments,
             LoadedContext::default(),
             Vec::new(),
             false,
             cx,
-        )
+        );
+        if let Some(message) = self.messages.last_mut() {
+            message.substitution = self.pending_substitution.clone();
+        }
+        id
     }
 
     pub fn insert_message(
@@ -1039,6 +1309,7 @@ impl Thread {
             loaded_context,
             creases,
             is_hidden,
+            substitution: None,
         });
         self.touch_updated_at();
         cx.emit(ThreadEvent::MessageAdded(id));
@@ -1168,9 +1439,14 @@ impl Thread {
                                 is_error: tool_result.is_error,
                                 content: tool_result.content.clone(),
                                 output: tool_result.output.clone(),
+                                images: this
+                                    .tool_use
+                                    .tool_result_images(&tool_result.tool_use_id)
+                                    .to_vec(),
                             })
                             .collect(),
                         context: message.loaded_context.text.clone(),
+                        retrieved_chunks: message.loaded_context.retrieved_chunks.clone(),
                         creases: message
                             .creases
                             .iter()
@@ -1182,6 +1458,7 @@ impl Thread {
                             })
                             .collect(),
                         is_hidden: message.is_hidden,
+                        substitution: message.substitution.clone(),
                     })
                     .collect(),
                 initial_project_snapshot,
@@ -1199,6 +1476,7 @@ impl Thread {
                 completion_mode: Some(this.completion_mode),
                 tool_use_limit_reached: this.tool_use_limit_reached,
                 profile: Some(this.profile.id().clone()),
+                plan: this.plan.clone(),
             })
         })
     }
@@ -1224,11 +1502,189 @@ impl Thread {
 
         self.remaining_turns -= 1;
 
-        let request = self.to_completion_request(model.clone(), intent, cx);
+        if matches!(intent, CompletionIntent::UserPrompt) {
+            self.edit_verification = self.can_verify_edits(cx).then_some(EditVerification {
+                baseline: None,
+                iterations: 0,
+            });
+            let time_budget = self.granted_time.take().or_else(|| {
+                AgentSettings::get_global(cx)
+                    .max_run_minutes
+                    .map(|minutes| Duration::from_secs(minutes * 60))
+            });
+            self.run_deadline = time_budget.map(|time_budget| Instant::now() + time_budget);
+            self.time_budget_reached = false;
+            self.planning = self.plan_first && self.plan.is_none();
+        }
+
+        let out_of_time = matches!(intent, CompletionIntent::ToolResults)
+            && self
+                .run_deadline
+                .is_some_and(|deadline| Instant::now() >= deadline);
+
+        let mut request = self.to_completion_request(model.clone(), intent, cx);
+        if out_of_time {
+            self.time_budget_reached = true;
+            if let Some(message) = request.messages.last_mut() {
+                message
+                    .content
+                    .push(MessageContent::Text(TIME_BUDGET_REACHED_PROMPT.into()));
+            }
+            if model.supports_tool_choice(LanguageModelToolChoice::None) {
+                request.tool_choice = Some(LanguageModelToolChoice::None);
+            }
+            cx.emit(ThreadEvent::TimeBudgetReached);
+        }
 
         self.stream_completion(request, model, window, cx);
     }
 
+    /// Whether the turn's edits can be checked with the `verify_edits`
+    /// command. The check runs a command in the project, so it's skipped in
+    /// untrusted workspaces, and in remote projects, where it would run on
+    /// this machine rather than the one the project is on.
+    fn can_verify_edits(&self, cx: &App) -> bool {
+        AgentSettings::get_global(cx).verify_edits.enabled
+            && self.project.read(cx).is_local()
+            && WorkspaceTrust::is_trusted(&self.project, cx)
+            && self.verification_dir(cx).is_some()
+    }
+
+    /// Starts checking the project for the errors it has before the turn's
+    /// first edit, so that turns without edits don't run the check. Returns
+    /// the check for the edit tools to wait on.
+    fn check_baseline(&mut self, cx: &App) -> Option<Shared<Task<Vec<CheckError>>>> {
+        if let Some(baseline) = &self.edit_verification.as_ref()?.baseline {
+            return Some(baseline.clone());
+        }
+        let cwd = self.verification_dir(cx)?;
+        let check = run_check(&AgentSettings::get_global(cx).verify_edits, &cwd, cx);
+        let baseline = cx
+            .background_spawn(async move {
+                check
+                    .await
+                    .map(|output| parse_check_errors(&output))
+                    .log_err()
+                    .unwrap_or_default()
+            })
+            .shared();
+        self.edit_verification.as_mut()?.baseline = Some(baseline.clone());
+        Some(baseline)
+    }
+
+    fn verification_dir(&self, cx: &App) -> Option<std::path::PathBuf> {
+        let worktree = self.project.read(cx).visible_worktrees(cx).next()?;
+        Some(worktree.read(cx).abs_path().to_path_buf())
+    }
+
+    /// Runs the `verify_edits` check after a turn that edited files, and asks
+    /// the model to fix the new errors in the edited files. Returns whether
+    /// the check started, in which case the turn isn't over yet.
+    fn verify_edits(
+        &mut self,
+        model: Arc<dyn LanguageModel>,
+        window: Option<AnyWindowHandle>,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let settings = AgentSettings::get_global(cx).verify_edits.clone();
+        let Some(baseline) = self
+            .edit_verification
+            .as_ref()
+            .and_then(|verification| verification.baseline.clone())
+        else {
+            return false;
+        };
+        let edited_paths = self
+            .action_log
+            .read(cx)
+            .changed_buffers(cx)
+            .into_keys()
+            .filter_map(|buffer| Some(buffer.read(cx).file()?.path().to_path_buf()))
+            .collect::<Vec<_>>();
+        if !settings.enabled
+            || edited_paths.is_empty()
+            || !WorkspaceTrust::is_trusted(&self.project, cx)
+        {
+            return false;
+        }
+        let Some(cwd) = self.verification_dir(cx) else {
+            return false;
+        };
+
+        let check = run_check(&settings, &cwd, cx);
+        self.pending_verification = Some(cx.spawn(async move |thread, cx| {
+            let output = check.await;
+            let baseline = baseline.await;
+            thread
+                .update(cx, |thread, cx| {
+                    thread.pending_verification = None;
+                    let errors = match output {
+                        Ok(output) => {
+                            new_errors(parse_check_errors(&output), &baseline, &edited_paths)
+                        }
+                        Err(error) => {
+                            log::error!("failed to verify agent edits: {error:#}");
+                            Vec::new()
+                        }
+                    };
+                    thread.finish_verification(errors, &settings, model, window, cx);
+                })
+                .ok();
+        }));
+        cx.notify();
+        true
+    }
+
+    fn finish_verification(
+        &mut self,
+        errors: Vec<CheckError>,
+        settings: &EditVerificationSettings,
+        model: Arc<dyn LanguageModel>,
+        window: Option<AnyWindowHandle>,
+        cx: &mut Context<Self>,
+    ) {
+        let command = std::iter::once(settings.command.as_str())
+            .chain(settings.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !errors.is_empty() {
+            if let Some(verification) = self
+                .edit_verification
+                .as_mut()
+                .filter(|verification| verification.iterations < settings.max_iterations)
+            {
+                verification.iterations += 1;
+                self.insert_user_message(
+                    fix_prompt(&command, &errors),
+                    ContextLoadResult::default(),
+                    None,
+                    Vec::new(),
+                    cx,
+                );
+                // Continue the turn without restarting the baseline check.
+                let request =
+                    self.to_completion_request(model.clone(), CompletionIntent::UserPrompt, cx);
+                self.stream_completion(request, model, window, cx);
+                return;
+            }
+
+            cx.emit(ThreadEvent::ShowError(ThreadError::Message {
+                header: "Edits still have errors".into(),
+                message: format!(
+                    "`{command}` reported {} new error(s) in the edited files.",
+                    errors.len()
+                )
+                .into(),
+            }));
+        }
+
+        self.edit_verification = None;
+        if self.report_when_done {
+            self.post_report(Ok(&StopReason::EndTurn), cx);
+        }
+        cx.emit(ThreadEvent::Stopped(Ok(StopReason::EndTurn)));
+    }
+
     pub fn used_tools_since_last_user_message(&self) -> bool {
         for message in self.messages.iter().rev() {
             if self.tool_use.message_has_tool_results(message.id) {
@@ -1257,6 +1713,9 @@ impl Thread {
             tool_choice: None,
             stop: Vec::new(),
             temperature: AgentSettings::temperature_for_model(&model, cx),
+            seed: AgentSettings::seed_for_model(&model, cx),
+            priority: RequestPriority::Interactive,
+            web_search: false,
         };
 
         let available_tools = self.available_tools(cx, model.clone());
@@ -1299,6 +1758,24 @@ impl Thread {
             }));
         }
 
+        if let Some(language) = AgentSettings::get_global(cx)
+            .answer_language
+            .as_deref()
+            .filter(|language| !language.trim().is_empty())
+        {
+            if let Some(prompt) = self
+                .prompt_builder
+                .generate_answer_language_prompt(language)
+                .log_err()
+            {
+                request.messages.push(LanguageModelRequestMessage {
+                    role: Role::System,
+                    content: vec![MessageContent::Text(prompt)],
+                    cache: false,
+                });
+            }
+        }
+
         let mut message_ix_to_cache = None;
         for message in &self.messages {
             let mut request_message = LanguageModelRequestMessage {
@@ -1342,6 +1819,7 @@ impl Thread {
                 content: Vec::new(),
                 cache: false,
             };
+            let mut tool_result_images = Vec::new();
             for (tool_use, tool_result) in self.tool_use.tool_results(message.id) {
                 if let Some(tool_result) = tool_result {
                     request_message
@@ -1362,6 +1840,15 @@ impl Thread {
                             },
                             output: None,
                         }));
+                    let images = self.tool_use.tool_result_images(&tool_use.id);
+                    if !images.is_empty() {
+                        tool_result_images.push(MessageContent::Text(format!(
+                            "Images returned by tool call {}:",
+                            tool_use.id
+                        )));
+                        tool_result_images
+                            .extend(images.iter().cloned().map(MessageContent::Image));
+                    }
                 } else {
                     cache_message = false;
                     log::debug!(
@@ -1370,6 +1857,7 @@ impl Thread {
                     );
                 }
             }
+            tool_results_message.content.extend(tool_result_images);
 
             if cache_message {
                 message_ix_to_cache = Some(request.messages.len());
@@ -1391,6 +1879,14 @@ impl Thread {
 
         self.attached_tracked_files_state(&mut request.messages, cx);
 
+        if self.planning {
+            if let Some(message) = request.messages.last_mut() {
+                message
+                    .content
+                    .push(MessageContent::Text(PLANNING_PROMPT.into()));
+            }
+        }
+
         request.tools = available_tools;
         request.mode = if model.supports_max_mode() {
             Some(self.completion_mode.into())
@@ -1418,6 +1914,9 @@ impl Thread {
             tool_choice: None,
             stop: Vec::new(),
             temperature: AgentSettings::temperature_for_model(model, cx),
+            seed: None,
+            priority: RequestPriority::Background,
+            web_search: false,
         };
 
         for message in &self.messages {
@@ -1502,6 +2001,25 @@ impl Thread {
     ) {
         self.tool_use_limit_reached = false;
 
+        // The request is sent to a free model in place of the selected one,
+        // which stays selected for the requests that follow.
+        let mut completion_model = model.clone();
+        let mut spend_check = SpendTracker::check(
+            model.as_ref(),
+            AgentSettings::get_global(cx).max_monthly_spend,
+            cx,
+        );
+        self.pending_substitution = None;
+        if spend_check.is_err() {
+            if let Some((substitute, substitution)) =
+                free_model_substitute(&model, ModelSubstitutionReason::MaxMonthlySpendReached, cx)
+            {
+                completion_model = substitute;
+                self.pending_substitution = Some(substitution);
+                spend_check = Ok(());
+            }
+        }
+
         let pending_completion_id = post_inc(&mut self.completion_count);
         let mut request_callback_parameters = if self.request_callback.is_some() {
             Some((request.clone(), Vec::new()))
@@ -1509,26 +2027,60 @@ impl Thread {
             None
         };
         let prompt_id = self.last_prompt_id.clone();
-        let tool_use_metadata = ToolUseMetadata {
-            model: model.clone(),
+        let mut tool_use_metadata = ToolUseMetadata {
+            model: completion_model.clone(),
             thread_id: self.id.clone(),
             prompt_id: prompt_id.clone(),
         };
 
         self.last_received_chunk_at = Some(Instant::now());
+        let request_started_at = Instant::now();
 
         let task = cx.spawn(async move |thread, cx| {
-            let stream_completion_future = model.stream_completion(request, &cx);
+            let stream_completion_future = match spend_check {
+                Ok(()) => completion_model.stream_completion(request.clone(), &cx),
+                Err(error) => async move { Err(anyhow::Error::from(error)) }.boxed(),
+            };
             let initial_token_usage =
                 thread.read_with(cx, |thread, _cx| thread.cumulative_token_usage);
             let stream_completion = async {
-                let mut events = stream_completion_future.await?;
+                let mut events = match stream_completion_future.await {
+                    Ok(events) => events,
+                    Err(error) => {
+                        let substitute = match error.downcast_ref::<LanguageModelError>() {
+                            Some(LanguageModelError::RateLimitExceeded { .. }) => {
+                                cx.update(|cx| {
+                                    free_model_substitute(
+                                        &completion_model,
+                                        ModelSubstitutionReason::RateLimitExceeded,
+                                        cx,
+                                    )
+                                })?
+                            }
+                            _ => None,
+                        };
+                        let Some((substitute, substitution)) = substitute else {
+                            return Err(error);
+                        };
+                        thread.update(cx, |thread, _| {
+                            thread.pending_substitution = Some(substitution);
+                        })?;
+                        completion_model = substitute;
+                        tool_use_metadata.model = completion_model.clone();
+                        completion_model.stream_completion(request, &cx).await?
+                    }
+                };
 
                 let mut stop_reason = StopReason::EndTurn;
                 let mut current_token_usage = TokenUsage::default();
 
                 thread
                     .update(cx, |_thread, cx| {
+                        ModelLatencies::record(
+                            completion_model.as_ref(),
+                            request_started_at.elapsed(),
+                            cx,
+                        );
                         cx.emit(ThreadEvent::NewRequest);
                     })
                     .ok();
@@ -1708,6 +2260,27 @@ impl Thread {
                                     }
                                 }
                             }
+                            LanguageModelCompletionEvent::WebSources(_) => {}
+                            LanguageModelCompletionEvent::Retrying {
+                                attempt,
+                                max_attempts,
+                                delay,
+                                message,
+                            } => {
+                                log::info!(
+                                    "retrying completion (attempt {attempt} of {max_attempts}) in {delay:?}: {message}"
+                                );
+                                if let Some(completion) = thread
+                                    .pending_completions
+                                    .iter_mut()
+                                    .find(|completion| completion.id == pending_completion_id)
+                                {
+                                    completion.queue_state = QueueState::Retrying {
+                                        attempt,
+                                        max_attempts,
+                                    };
+                                }
+                            }
                         }
 
                         thread.touch_updated_at();
@@ -1722,6 +2295,9 @@ impl Thread {
                 }
 
                 thread.update(cx, |thread, cx| {
+                    if let Some(pricing) = completion_model.pricing() {
+                        SpendTracker::record(pricing.cost(&current_token_usage), cx);
+                    }
                     thread.last_received_chunk_at = None;
                     thread
                         .pending_completions
@@ -1745,6 +2321,7 @@ impl Thread {
             thread
                 .update(cx, |thread, cx| {
                     thread.finalize_pending_checkpoint(cx);
+                    thread.pending_substitution = None;
                     match result.as_ref() {
                         Ok(stop_reason) => match stop_reason {
                             StopReason::ToolUse => {
@@ -1755,6 +2332,15 @@ impl Thread {
                                 thread.project.update(cx, |project, cx| {
                                     project.set_agent_location(None, cx);
                                 });
+                                if thread.planning {
+                                    thread.planning = false;
+                                    thread.plan = thread
+                                        .messages
+                                        .last()
+                                        .filter(|message| message.role == Role::Assistant)
+                                        .and_then(|message| Plan::parse(&message.to_string()));
+                                    cx.emit(ThreadEvent::PlanChanged);
+                                }
                             }
                             StopReason::Refusal => {
                                 thread.project.update(cx, |project, cx| {
@@ -1801,25 +2387,38 @@ impl Thread {
 
                             if error.is::<PaymentRequiredError>() {
                                 cx.emit(ThreadEvent::ShowError(ThreadError::PaymentRequired));
+                            } else if let Some(error) =
+                                error.downcast_ref::<MaxMonthlySpendReachedError>()
+                            {
+                                cx.emit(ThreadEvent::ShowError(
+                                    ThreadError::MaxMonthlySpendReached(*error),
+                                ));
                             } else if let Some(error) =
                                 error.downcast_ref::<ModelRequestLimitReachedError>()
                             {
                                 cx.emit(ThreadEvent::ShowError(
                                     ThreadError::ModelRequestLimitReached { plan: error.plan },
                                 ));
-                            } else if let Some(known_error) =
-                                error.downcast_ref::<LanguageModelKnownError>()
+                            } else if let Some(model_error) =
+                                error.downcast_ref::<LanguageModelError>()
                             {
-                                match known_error {
-                                    LanguageModelKnownError::ContextWindowLimitExceeded {
-                                        tokens,
+                                match model_error {
+                                    LanguageModelError::ContextWindowLimitExceeded {
+                                        tokens: Some(tokens),
                                     } => {
                                         thread.exceeded_window_error = Some(ExceededWindowError {
-                                            model_id: model.id(),
+                                            model_id: completion_model.id(),
                                             token_count: *tokens,
                                         });
                                         cx.notify();
                                     }
+                                    LanguageModelError::Cancelled => {}
+                                    model_error => {
+                                        cx.emit(ThreadEvent::ShowError(ThreadError::Message {
+                                            header: model_error.title().into(),
+                                            message: model_error.to_string().into(),
+                                        }));
+                                    }
                                 }
                             } else {
                                 let error_message = error
@@ -1837,7 +2436,16 @@ impl Thread {
                         }
                     }
 
-                    cx.emit(ThreadEvent::Stopped(result.map_err(Arc::new)));
+                    // The turn continues while the edits are verified.
+                    let verifying = matches!(result, Ok(StopReason::EndTurn))
+                        && thread.verify_edits(model.clone(), window, cx);
+                    if !verifying {
+                        if thread.report_when_done {
+                            thread.post_report(result.as_ref(), cx);
+                        }
+
+                        cx.emit(ThreadEvent::Stopped(result.map_err(Arc::new)));
+                    }
 
                     if let Some((request_callback, (request, response_events))) = thread
                         .request_callback
@@ -2095,9 +2703,48 @@ impl Thread {
             .cloned()
             .collect::<Vec<_>>();
 
+        let trusted = WorkspaceTrust::is_trusted(&self.project, cx);
         for tool_use in pending_tool_uses.iter() {
+            if &*tool_use.name == UPDATE_PLAN_TOOL_NAME && self.plan.is_some() {
+                self.handle_plan_update(
+                    tool_use.id.clone(),
+                    tool_use.name.clone(),
+                    tool_use.input.clone(),
+                    window,
+                    cx,
+                );
+                continue;
+            }
+
             if let Some(tool) = self.tools.read(cx).tool(&tool_use.name, cx) {
-                if tool.needs_confirmation(&tool_use.input, cx)
+                let input_errors = validate_tool_input(tool.as_ref(), &tool_use.input);
+                if !input_errors.is_empty() {
+                    self.handle_invalid_tool_input(
+                        tool_use.id.clone(),
+                        tool_use.name.clone(),
+                        input_errors,
+                        window,
+                        cx,
+                    );
+                    continue;
+                }
+                self.invalid_tool_inputs.remove(&tool_use.name);
+
+                if self.read_only && !is_read_only(tool.as_ref()) {
+                    self.handle_read_only_tool_use(
+                        tool_use.id.clone(),
+                        tool_use.name.clone(),
+                        window,
+                        cx,
+                    );
+                } else if !trusted && workspace_trust::requires_trust(tool.as_ref()) {
+                    self.handle_untrusted_tool_use(
+                        tool_use.id.clone(),
+                        tool_use.name.clone(),
+                        window,
+                        cx,
+                    );
+                } else if tool.needs_confirmation(&tool_use.input, cx)
                     && !AgentSettings::get_global(cx).always_allow_tool_actions
                 {
                     self.tool_use.confirm_tool_use(
@@ -2168,6 +2815,117 @@ impl Thread {
         self.tool_finished(tool_use_id, pending_tool_use, false, window, cx);
     }
 
+    /// Applies the model's update to the status of a step in the approved plan.
+    fn handle_plan_update(
+        &mut self,
+        tool_use_id: LanguageModelToolUseId,
+        tool_name: Arc<str>,
+        input: serde_json::Value,
+        window: Option<AnyWindowHandle>,
+        cx: &mut Context<Thread>,
+    ) {
+        let output = self
+            .plan
+            .as_mut()
+            .ok_or_else(|| anyhow!("there's no approved plan"))
+            .and_then(|plan| plan.update(input))
+            .map(|text| ToolResultOutput {
+                content: ToolResultContent::Text(text),
+                output: None,
+            });
+        let pending_tool_use = self.tool_use.insert_tool_output(
+            tool_use_id.clone(),
+            tool_name,
+            output,
+            self.configured_model.as_ref(),
+        );
+        cx.emit(ThreadEvent::PlanChanged);
+        self.tool_finished(tool_use_id, pending_tool_use, false, window, cx);
+    }
+
+    /// Fails a tool use whose input doesn't match the tool's schema, telling
+    /// the model what to correct. After `MAX_TOOL_INPUT_REPAIRS` failed
+    /// corrections in a row, the turn is stopped instead.
+    fn handle_invalid_tool_input(
+        &mut self,
+        tool_use_id: LanguageModelToolUseId,
+        tool_name: Arc<str>,
+        input_errors: Vec<String>,
+        window: Option<AnyWindowHandle>,
+        cx: &mut Context<Thread>,
+    ) {
+        let attempts = self
+            .invalid_tool_inputs
+            .entry(tool_name.clone())
+            .or_default();
+        *attempts += 1;
+        let give_up = *attempts > MAX_TOOL_INPUT_REPAIRS;
+        if give_up {
+            self.invalid_tool_inputs.remove(&tool_name);
+        }
+
+        let mut message =
+            format!("The input for the `{tool_name}` tool doesn't match its schema:\n");
+        for error in &input_errors {
+            writeln!(message, "- {error}").ok();
+        }
+        if give_up {
+            message.push_str(
+                "\nThe tool was called with invalid input too many times in a row, so the turn was stopped.",
+            );
+        } else {
+            message.push_str("\nCall the tool again with input that matches its schema.");
+        }
+
+        let pending_tool_use = self.tool_use.insert_tool_output(
+            tool_use_id.clone(),
+            tool_name,
+            Err(anyhow!(message)),
+            self.configured_model.as_ref(),
+        );
+        self.tool_finished(tool_use_id, pending_tool_use, give_up, window, cx);
+    }
+
+    /// Fails a tool use that would edit files or run commands in a
+    /// workspace that hasn't been trusted.
+    fn handle_untrusted_tool_use(
+        &mut self,
+        tool_use_id: LanguageModelToolUseId,
+        tool_name: Arc<str>,
+        window: Option<AnyWindowHandle>,
+        cx: &mut Context<Thread>,
+    ) {
+        let pending_tool_use = self.tool_use.insert_tool_output(
+            tool_use_id.clone(),
+            tool_name,
+            Err(anyhow!(
+                "This workspace isn't trusted, so tools that edit files or run commands are disabled. \
+                The user can trust it from the agent panel."
+            )),
+            self.configured_model.as_ref(),
+        );
+        self.tool_finished(tool_use_id, pending_tool_use, false, window, cx);
+    }
+
+    fn handle_read_only_tool_use(
+        &mut self,
+        tool_use_id: LanguageModelToolUseId,
+        tool_name: Arc<str>,
+        window: Option<AnyWindowHandle>,
+        cx: &mut Context<Thread>,
+    ) {
+        let pending_tool_use = self.tool_use.insert_tool_output(
+            tool_use_id.clone(),
+            tool_name,
+            Err(anyhow!(
+                "This thread runs in the background, so tools that edit files or run commands are disabled. \
+                Describe the changes you would make instead."
+            )),
+            self.configured_model.as_ref(),
+        );
+        self.tool_finished(tool_use_id, pending_tool_use, false, window, cx);
+    }
+
     pub fn receive_invalid_tool_json(
         &mut self,
         tool_use_id: LanguageModelToolUseId,
@@ -2214,8 +2972,29 @@ impl Thread {
         window: Option<AnyWindowHandle>,
         cx: &mut Context<Thread>,
     ) {
-        let task =
-            self.spawn_tool_use(tool_use_id.clone(), request, input, tool, model, window, cx);
+        let baseline = if tool.may_perform_edits() {
+            self.check_baseline(cx)
+        } else {
+            None
+        };
+        let task = match baseline {
+            // Edits wait for the baseline check, so that their errors aren't
+            // mistaken for ones the project already had.
+            Some(baseline) => cx.spawn({
+                let tool_use_id = tool_use_id.clone();
+                async move |thread, cx| {
+                    baseline.await;
+                    if let Ok(task) = thread.update(cx, |thread, cx| {
+                        thread.spawn_tool_use(tool_use_id, request, input, tool, model, window, cx)
+                    }) {
+                        task.await;
+                    }
+                }
+            }),
+            None => {
+                self.spawn_tool_use(tool_use_id.clone(), request, input, tool, model, window, cx)
+            }
+        };
         self.tool_use
             .run_pending_tool(tool_use_id, ui_text.into(), task);
     }
@@ -2277,7 +3056,7 @@ impl Thread {
     ) {
         if self.all_tools_finished() {
             if let Some(ConfiguredModel { model, .. }) = self.configured_model.as_ref() {
-                if !canceled {
+                if !canceled && !self.time_budget_reached {
                     self.send_to_model(model.clone(), CompletionIntent::ToolResults, window, cx);
                 }
                 self.auto_capture_telemetry(cx);
@@ -2299,6 +3078,7 @@ impl Thread {
         cx: &mut Context<Self>,
     ) -> bool {
         let mut canceled = self.pending_completions.pop().is_some();
+        canceled |= self.pending_verification.take().is_some();
 
         for pending_tool_use in self.tool_use.cancel_pending() {
             canceled = true;
@@ -2373,8 +3153,14 @@ impl Thread {
             .message(message_id)
             .map(|msg| msg.to_string())
             .unwrap_or_default();
+        let record = self
+            .feedback_record(message_id, feedback, None)
+            .map(|record| feedback_store::record(record, cx));
 
         cx.background_spawn(async move {
+            if let Some(record) = record {
+                record.await.log_err();
+            }
             let final_project_snapshot = final_project_snapshot.await;
             let serialized_thread = serialized_thread.await?;
             let thread_data =
@@ -2400,6 +3186,54 @@ impl Thread {
         })
     }
 
+    /// Stores the reason given for the message's rating.
+    pub fn report_feedback_reason(
+        &mut self,
+        message_id: MessageId,
+        reason: String,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        let Some(feedback) = self.message_feedback(message_id) else {
+            return Task::ready(Ok(()));
+        };
+        match self.feedback_record(message_id, feedback, Some(reason)) {
+            Some(record) => feedback_store::record(record, cx),
+            None => Task::ready(Ok(())),
+        }
+    }
+
+    fn feedback_record(
+        &self,
+        message_id: MessageId,
+        feedback: ThreadFeedback,
+        reason: Option<String>,
+    ) -> Option<FeedbackRecord> {
+        let answer = self.message(message_id)?.to_string();
+        let prompt = self
+            .messages
+            .iter()
+            .rev()
+            .filter(|message| message.id < message_id && !message.is_hidden)
+            .find(|message| message.role == Role::User)
+            .map(|message| message.to_string())
+            .unwrap_or_default();
+        let model = self
+            .configured_model
+            .as_ref()
+            .map(|model| feedback_store::model_key(model.model.as_ref()));
+        Some(FeedbackRecord {
+            thread_id: self.id.clone(),
+            message_id: message_id.0,
+            rating: feedback,
+            reason,
+            model,
+            prompt_hash: feedback_store::prompt_hash(&prompt),
+            prompt,
+            answer,
+            created_at: Utc::now(),
+        })
+    }
+
     pub fn report_feedback(
         &mut self,
         feedback: ThreadFeedback,
@@ -2823,6 +3657,8 @@ pub enum ThreadError {
     PaymentRequired,
     #[error("Model request limit reached")]
     ModelRequestLimitReached { plan: Plan },
+    #[error("Maximum monthly spend reached")]
+    MaxMonthlySpendReached(MaxMonthlySpendReachedError),
     #[error("Message {header}: {message}")]
     Message {
         header: SharedString,
@@ -2870,6 +3706,8 @@ pub enum ThreadEvent {
     CheckpointChanged,
     ToolConfirmationNeeded,
     ToolUseLimitReached,
+    TimeBudgetReached,
+    PlanChanged,
     CancelEditing,
     CompletionCanceled,
     ProfileChanged,
@@ -2883,6 +3721,46 @@ struct PendingCompletion {
     _task: Task<()>,
 }
 
+/// Whether the tool can be used in read-only threads.
+fn is_read_only(tool: &dyn Tool) -> bool {
+    !tool.may_perform_edits() && !tool.may_run_commands()
+}
+
+/// Checks a tool's input against its JSON schema, returning a description of
+/// each mismatch.
+fn validate_tool_input(tool: &dyn Tool, input: &serde_json::Value) -> Vec<String> {
+    match tool.input_schema(LanguageModelToolSchemaFormat::JsonSchema) {
+        Ok(schema) => schema_errors(&schema, input),
+        Err(error) => {
+            log::error!("failed to get the input schema of {}: {error}", tool.name());
+            Vec::new()
+        }
+    }
+}
+
+/// Describes each way the value doesn't match the schema, such as
+/// `/line: "ten" is not of type "integer"`.
+fn schema_errors(schema: &serde_json::Value, value: &serde_json::Value) -> Vec<String> {
+    let validator = match jsonschema::validator_for(schema) {
+        Ok(validator) => validator,
+        Err(error) => {
+            log::error!("invalid tool input schema: {error}");
+            return Vec::new();
+        }
+    };
+    validator
+        .iter_errors(value)
+        .map(|error| {
+            let path = error.instance_path.to_string();
+            if path.is_empty() {
+                error.to_string()
+            } else {
+                format!("{path}: {error}")
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2891,7 +3769,10 @@ mod tests {
     use assistant_tool::ToolRegistry;
     use editor::EditorSettings;
     use gpui::TestAppContext;
-    use language_model::fake_provider::{FakeLanguageModel, FakeLanguageModelProvider};
+    use language_model::LanguageModelImage;
+    use language_model::fake_provider::{
+        FakeFreeLanguageModelProvider, FakeLanguageModel, FakeLanguageModelProvider,
+    };
     use project::{FakeFs, Project};
     use prompt_store::PromptBuilder;
     use serde_json::json;
@@ -2901,6 +3782,28 @@ mod tests {
     use util::path;
     use workspace::Workspace;
 
+    #[test]
+    fn test_schema_errors() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "line": { "type": "integer" }
+            },
+            "required": ["path"]
+        });
+        assert!(schema_errors(&schema, &json!({ "path": "a.rs", "line": 3 })).is_empty());
+        let mut errors = schema_errors(&schema, &json!({ "line": "ten" }));
+        errors.sort();
+        assert_eq!(
+            errors,
+            [
+                r#""path" is a required property"#.to_string(),
+                r#"/line: "ten" is not of type "integer""#.to_string(),
+            ]
+        );
+    }
+
     #[gpui::test]
     async fn test_message_with_context(cx: &mut TestAppContext) {
         init_test_settings(cx);
@@ -3386,6 +4289,7 @@ fn main() {{
                         provider: Some(model.provider_id().0.to_string().into()),
                         model: Some(model.id().0.clone()),
                         temperature: Some(0.66),
+                        seed: None,
                     }],
                     ..AgentSettings::get_global(cx).clone()
                 },
@@ -3406,6 +4310,7 @@ fn main() {{
                         provider: None,
                         model: Some(model.id().0.clone()),
                         temperature: Some(0.66),
+                        seed: None,
                     }],
                     ..AgentSettings::get_global(cx).clone()
                 },
@@ -3426,6 +4331,7 @@ fn main() {{
                         provider: Some(model.provider_id().0.to_string().into()),
                         model: None,
                         temperature: Some(0.66),
+                        seed: None,
                     }],
                     ..AgentSettings::get_global(cx).clone()
                 },
@@ -3446,6 +4352,7 @@ fn main() {{
                         provider: Some("anthropic".into()),
                         model: Some(model.id().0.clone()),
                         temperature: Some(0.66),
+                        seed: None,
                     }],
                     ..AgentSettings::get_global(cx).clone()
                 },
@@ -3459,6 +4366,316 @@ fn main() {{
         assert_eq!(request.temperature, None);
     }
 
+    #[gpui::test]
+    async fn test_edit_verification_requires_workspace_trust(cx: &mut TestAppContext) {
+        init_test_settings(cx);
+
+        let project = create_test_project(cx, json!({"code.rs": "fn main() {}"})).await;
+        let (_workspace, _thread_store, thread, _context_store, model) =
+            setup_test_environment(cx, project.clone()).await;
+
+        let set_require_workspace_trust = |require_workspace_trust: bool, cx: &mut App| {
+            let settings = AgentSettings::get_global(cx).clone();
+            AgentSettings::override_global(
+                AgentSettings {
+                    require_workspace_trust,
+                    verify_edits: EditVerificationSettings {
+                        enabled: true,
+                        command: "true".into(),
+                        args: Vec::new(),
+                        max_iterations: 1,
+                    },
+                    ..settings
+                },
+                cx,
+            );
+        };
+
+        cx.update(|cx| set_require_workspace_trust(true, cx));
+        thread.update(cx, |thread, cx| {
+            assert!(!WorkspaceTrust::is_trusted(&project, cx));
+            thread.insert_user_message("Hi!", ContextLoadResult::default(), None, vec![], cx);
+            thread.send_to_model(model.clone(), CompletionIntent::UserPrompt, None, cx);
+            assert!(thread.edit_verification.is_none());
+            assert!(!thread.verify_edits(model.clone(), None, cx));
+        });
+        simulate_successful_response(&model.as_fake(), cx);
+
+        cx.update(|cx| set_require_workspace_trust(false, cx));
+        thread.update(cx, |thread, cx| {
+            thread.insert_user_message("Hi!", ContextLoadResult::default(), None, vec![], cx);
+            thread.send_to_model(model.clone(), CompletionIntent::UserPrompt, None, cx);
+            // The baseline is only checked once the model is about to edit.
+            let verification = thread.edit_verification.as_ref().unwrap();
+            assert!(verification.baseline.is_none());
+            assert!(!thread.verify_edits(model.clone(), None, cx));
+            assert!(thread.check_baseline(cx).is_some());
+            assert!(
+                thread
+                    .edit_verification
+                    .as_ref()
+                    .unwrap()
+                    .baseline
+                    .is_some()
+            );
+        });
+    }
+
+    #[gpui::test]
+    async fn test_free_model_substitution_when_spend_limit_is_reached(cx: &mut TestAppContext) {
+        init_test_settings(cx);
+
+        let project = create_test_project(cx, json!({})).await;
+        let (_workspace, _thread_store, thread, _context_store, model) =
+            setup_test_environment(cx, project.clone()).await;
+        let free_model = register_free_model(cx);
+        cx.update(|cx| {
+            AgentSettings::override_global(
+                AgentSettings {
+                    max_monthly_spend: Some(10.),
+                    auto_switch_to_free_model: true,
+                    ..AgentSettings::get_global(cx).clone()
+                },
+                cx,
+            );
+            SpendTracker::set_current_spend(10., cx);
+        });
+
+        thread.update(cx, |thread, cx| {
+            thread.insert_user_message("Hi!", ContextLoadResult::default(), None, vec![], cx);
+            thread.send_to_model(model.clone(), CompletionIntent::UserPrompt, None, cx);
+        });
+        cx.run_until_parked();
+        assert_eq!(model.as_fake().completion_count(), 0);
+        simulate_successful_response(&free_model, cx);
+
+        thread.read_with(cx, |thread, _| {
+            let message = thread.messages().last().unwrap();
+            assert_eq!(message.role, Role::Assistant);
+            assert_eq!(
+                message.substitution,
+                Some(ModelSubstitution {
+                    original_model: "Fake".into(),
+                    substitute_model: "Fake Free".into(),
+                    reason: ModelSubstitutionReason::MaxMonthlySpendReached,
+                })
+            );
+            assert_eq!(thread.configured_model().unwrap().model.id(), model.id());
+        });
+    }
+
+    #[gpui::test]
+    async fn test_free_model_substitution_when_rate_limited(cx: &mut TestAppContext) {
+        init_test_settings(cx);
+
+        let project = create_test_project(cx, json!({})).await;
+        let (_workspace, _thread_store, thread, _context_store, model) =
+            setup_test_environment(cx, project.clone()).await;
+        let free_model = register_free_model(cx);
+        cx.update(|cx| {
+            AgentSettings::override_global(
+                AgentSettings {
+                    auto_switch_to_free_model: true,
+                    ..AgentSettings::get_global(cx).clone()
+                },
+                cx,
+            );
+        });
+
+        model
+            .as_fake()
+            .fail_next_completion(LanguageModelError::RateLimitExceeded {
+                message: "Too many requests".into(),
+            });
+        thread.update(cx, |thread, cx| {
+            thread.insert_user_message("Hi!", ContextLoadResult::default(), None, vec![], cx);
+            thread.send_to_model(model.clone(), CompletionIntent::UserPrompt, None, cx);
+        });
+        simulate_successful_response(&free_model, cx);
+
+        thread.read_with(cx, |thread, _| {
+            let message = thread.messages().last().unwrap();
+            assert_eq!(message.role, Role::Assistant);
+            assert_eq!(
+                message
+                    .substitution
+                    .as_ref()
+                    .map(|substitution| substitution.reason),
+                Some(ModelSubstitutionReason::RateLimitExceeded)
+            );
+            assert_eq!(thread.configured_model().unwrap().model.id(), model.id());
+        });
+
+        // Once the rate limit has passed, the selected model is used again.
+        thread.update(cx, |thread, cx| {
+            thread.insert_user_message("Hi again!", ContextLoadResult::default(), None, vec![], cx);
+            thread.send_to_model(model.clone(), CompletionIntent::UserPrompt, None, cx);
+        });
+        simulate_successful_response(&model.as_fake(), cx);
+        thread.read_with(cx, |thread, _| {
+            let message = thread.messages().last().unwrap();
+            assert_eq!(message.role, Role::Assistant);
+            assert_eq!(message.substitution, None);
+        });
+    }
+
+    fn register_free_model(cx: &mut TestAppContext) -> Arc<FakeLanguageModel> {
+        let provider = FakeFreeLanguageModelProvider::new();
+        let model = provider.model();
+        cx.update(|cx| {
+            LanguageModelRegistry::global(cx).update(cx, |registry, cx| {
+                registry.register_provider(provider, cx);
+            });
+        });
+        model
+    }
+
+    #[gpui::test]
+    async fn test_tool_result_images_follow_the_tool_results(cx: &mut TestAppContext) {
+        init_test_settings(cx);
+
+        let project = create_test_project(cx, json!({})).await;
+        let (_, _thread_store, thread, _context_store, model) =
+            setup_test_environment(cx, project.clone()).await;
+
+        let tool_use_id = LanguageModelToolUseId::from("tool_1");
+        let image = LanguageModelImage {
+            source: "iVBORw0KGgo=".into(),
+            size: gpui::size(gpui::DevicePixels(1), gpui::DevicePixels(1)),
+        };
+        thread.update(cx, |thread, cx| {
+            let message_id =
+                thread.insert_assistant_message(vec![MessageSegment::Text("Plotting.".into())], cx);
+            thread.tool_use.request_tool_use(
+                message_id,
+                language_model::LanguageModelToolUse {
+                    id: tool_use_id.clone(),
+                    name: "run_python".into(),
+                    raw_input: "{}".into(),
+                    input: json!({}),
+                    is_input_complete: true,
+                },
+                ToolUseMetadata {
+                    model: model.clone(),
+                    thread_id: thread.id.clone(),
+                    prompt_id: PromptId::new(),
+                },
+                cx,
+            );
+            thread.tool_use.insert_tool_output(
+                tool_use_id.clone(),
+                "run_python".into(),
+                Ok(ToolResultOutput {
+                    content: ToolResultContent::TextWithImages {
+                        text: "42".into(),
+                        images: vec![image.clone()],
+                    },
+                    output: None,
+                }),
+                None,
+            );
+        });
+
+        let request = thread.update(cx, |thread, cx| {
+            thread.to_completion_request(model.clone(), CompletionIntent::ToolResults, cx)
+        });
+        let tool_results_message = request.messages.last().unwrap();
+        assert_eq!(tool_results_message.role, Role::User);
+        assert_eq!(
+            tool_results_message.content,
+            vec![
+                MessageContent::ToolResult(LanguageModelToolResult {
+                    tool_use_id: tool_use_id.clone(),
+                    tool_name: "run_python".into(),
+                    is_error: false,
+                    content: LanguageModelToolResultContent::Text("42".into()),
+                    output: None,
+                }),
+                MessageContent::Text("Images returned by tool call tool_1:".into()),
+                MessageContent::Image(image),
+            ]
+        );
+    }
+
+    #[gpui::test]
+    async fn test_read_only_threads_cannot_use_edit_tools(cx: &mut TestAppContext) {
+        init_test_settings(cx);
+
+        let project = create_test_project(cx, json!({})).await;
+        let (_, _thread_store, thread, _context_store, model) =
+            setup_test_environment(cx, project.clone()).await;
+        cx.update(|cx| ToolRegistry::global(cx).register_tool(FakeEditTool));
+
+        let tool_use_id = LanguageModelToolUseId::from("edit_1");
+        thread.update(cx, |thread, cx| {
+            thread.set_read_only(true);
+            let message_id =
+                thread.insert_assistant_message(vec![MessageSegment::Text("Editing.".into())], cx);
+            thread.tool_use.request_tool_use(
+                message_id,
+                language_model::LanguageModelToolUse {
+                    id: tool_use_id.clone(),
+                    name: "fake_edit".into(),
+                    raw_input: "{}".into(),
+                    input: json!({}),
+                    is_input_complete: true,
+                },
+                ToolUseMetadata {
+                    model: model.clone(),
+                    thread_id: thread.id.clone(),
+                    prompt_id: PromptId::new(),
+                },
+                cx,
+            );
+            thread.use_pending_tools(None, cx, model.clone());
+
+            assert!(thread.tool_result(&tool_use_id).unwrap().is_error);
+            let output = thread.output_for_tool(&tool_use_id).unwrap();
+            assert!(output.contains("tools that edit files or run commands are disabled"));
+        });
+    }
+
+    struct FakeEditTool;
+
+    impl Tool for FakeEditTool {
+        fn name(&self) -> String {
+            "fake_edit".into()
+        }
+
+        fn description(&self) -> String {
+            "Edits a file.".into()
+        }
+
+        fn icon(&self) -> ui::IconName {
+            ui::IconName::Pencil
+        }
+
+        fn needs_confirmation(&self, _: &serde_json::Value, _: &App) -> bool {
+            false
+        }
+
+        fn may_perform_edits(&self) -> bool {
+            true
+        }
+
+        fn ui_text(&self, _: &serde_json::Value) -> String {
+            "Edit a file".into()
+        }
+
+        fn run(
+            self: Arc<Self>,
+            _input: serde_json::Value,
+            _request: Arc<LanguageModelRequest>,
+            _project: Entity<Project>,
+            _action_log: Entity<ActionLog>,
+            _model: Arc<dyn LanguageModel>,
+            _window: Option<AnyWindowHandle>,
+            _cx: &mut App,
+        ) -> assistant_tool::ToolResult {
+            Task::ready(Ok("Edited.".to_string().into())).into()
+        }
+    }
+
     #[gpui::test]
     async fn test_thread_summary(cx: &mut TestAppContext) {
         init_test_settings(cx);
@@ -3617,6 +4834,58 @@ fn main() {{
         });
     }
 
+    #[gpui::test]
+    async fn test_time_budget(cx: &mut TestAppContext) {
+        init_test_settings(cx);
+        let project = create_test_project(cx, json!({})).await;
+        let (_workspace, _thread_store, thread, _context_store, model) =
+            setup_test_environment(cx, project.clone()).await;
+        cx.update(|cx| {
+            AgentSettings::override_global(
+                AgentSettings {
+                    max_run_minutes: Some(0),
+                    ..AgentSettings::get_global(cx).clone()
+                },
+                cx,
+            );
+        });
+        let fake_model = model.as_fake();
+        let send = |intent: CompletionIntent, cx: &mut TestAppContext| {
+            thread.update(cx, |thread, cx| {
+                thread.send_to_model(model.clone(), intent, None, cx)
+            });
+            cx.run_until_parked();
+            let request = fake_model.pending_completions().pop().unwrap();
+            simulate_successful_response(&fake_model, cx);
+            request
+                .messages
+                .last()
+                .unwrap()
+                .content
+                .contains(&MessageContent::Text(TIME_BUDGET_REACHED_PROMPT.into()))
+        };
+
+        thread.update(cx, |thread, cx| {
+            thread.insert_user_message("Refactor", ContextLoadResult::default(), None, vec![], cx);
+        });
+        assert!(!send(CompletionIntent::UserPrompt, cx));
+        assert!(!thread.read_with(cx, |thread, _| thread.time_budget_reached()));
+
+        // Once the run is out of time, the model is asked to wrap up.
+        assert!(send(CompletionIntent::ToolResults, cx));
+        assert!(thread.read_with(cx, |thread, _| thread.time_budget_reached()));
+
+        // Granting more time lets the next run continue with tool results.
+        thread.update(cx, |thread, cx| {
+            thread.grant_more_time(Duration::from_secs(60 * 60));
+            thread.insert_user_message("Continue", ContextLoadResult::default(), None, vec![], cx);
+        });
+        assert!(!send(CompletionIntent::UserPrompt, cx));
+        assert!(!thread.read_with(cx, |thread, _| thread.time_budget_reached()));
+        assert!(!send(CompletionIntent::ToolResults, cx));
+        assert!(!thread.read_with(cx, |thread, _| thread.time_budget_reached()));
+    }
+
     fn test_summarize_error(
         model: &Arc<dyn LanguageModel>,
         thread: &Entity<Thread>,
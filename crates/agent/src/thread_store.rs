@@ -5,9 +5,10 @@ use std::sync::{Arc, Mutex};
 
 use agent_settings::{AgentProfileId, CompletionMode};
 use anyhow::{Context as _, Result, anyhow};
+use assistant_context_editor::ModelSubstitution;
 use assistant_tool::{ToolId, ToolWorkingSet};
 use chrono::{DateTime, Utc};
-use collections::HashMap;
+use collections::{HashMap, HashSet};
 use context_server::ContextServerId;
 use futures::channel::{mpsc, oneshot};
 use futures::future::{self, BoxFuture, Shared};
@@ -17,7 +18,9 @@ use gpui::{
     Subscription, Task, prelude::*,
 };
 
-use language_model::{LanguageModelToolResultContent, LanguageModelToolUseId, Role, TokenUsage};
+use language_model::{
+    LanguageModelImage, LanguageModelToolResultContent, LanguageModelToolUseId, Role, TokenUsage,
+};
 use project::context_server_store::{ContextServerStatus, ContextServerStore};
 use project::{Project, ProjectItem, ProjectPath, Worktree};
 use prompt_store::{
@@ -28,7 +31,10 @@ use serde::{Deserialize, Serialize};
 use ui::Window;
 use util::ResultExt as _;
 
+use crate::context::RetrievedChunk;
 use crate::context_server_tool::ContextServerTool;
+use crate::plan::Plan;
+use crate::repo_map::{RepoMap, RepoMapUpdated};
 use crate::thread::{
     DetailedSummaryState, ExceededWindowError, MessageId, ProjectSnapshot, Thread, ThreadId,
 };
@@ -104,6 +110,7 @@ pub struct ThreadStore {
     context_server_tool_ids: HashMap<ContextServerId, Vec<ToolId>>,
     threads: Vec<SerializedThreadMetadata>,
     project_context: SharedProjectContext,
+    repo_map: Entity<RepoMap>,
     reload_system_prompt_tx: mpsc::Sender<()>,
     _reload_system_prompt_task: Task<()>,
     _subscriptions: Vec<Subscription>,
@@ -146,7 +153,13 @@ impl ThreadStore {
         prompt_store: Option<Entity<PromptStore>>,
         cx: &mut Context<Self>,
     ) -> (Self, oneshot::Receiver<()>) {
-        let mut subscriptions = vec![cx.subscribe(&project, Self::handle_project_event)];
+        let repo_map = cx.new(|cx| RepoMap::new(&project, cx));
+        let mut subscriptions = vec![
+            cx.subscribe(&project, Self::handle_project_event),
+            cx.subscribe(&repo_map, |this, _, _: &RepoMapUpdated, _| {
+                this.enqueue_system_prompt_reload();
+            }),
+        ];
 
         if let Some(prompt_store) = prompt_store.as_ref() {
             subscriptions.push(cx.subscribe(
@@ -190,6 +203,7 @@ impl ThreadStore {
             context_server_tool_ids: HashMap::default(),
             threads: Vec::new(),
             project_context: SharedProjectContext::default(),
+            repo_map,
             reload_system_prompt_tx,
             _reload_system_prompt_task: reload_system_prompt_task,
             _subscriptions: subscriptions,
@@ -254,6 +268,7 @@ impl ThreadStore {
                 cx.background_spawn(future::join_all(load_tasks))
             }),
         };
+        let repo_map = self.repo_map.read(cx).render();
 
         cx.spawn(async move |this, cx| {
             let (worktrees, default_user_rules) =
@@ -292,9 +307,10 @@ impl ThreadStore {
                 })
                 .collect::<Vec<_>>();
 
+            let mut project_context = ProjectContext::new(worktrees, default_user_rules);
+            project_context.repo_map = repo_map;
             this.update(cx, |this, _cx| {
-                *this.project_context.0.borrow_mut() =
-                    Some(ProjectContext::new(worktrees, default_user_rules));
+                *this.project_context.0.borrow_mut() = Some(project_context);
             })
             .ok();
         })
@@ -403,6 +419,59 @@ impl ThreadStore {
         threads
     }
 
+    /// Loads the most recently updated threads that were started in one of
+    /// this project's worktrees, other than the excluded one.
+    pub fn load_project_threads(
+        &self,
+        excluded_id: &ThreadId,
+        limit: usize,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Vec<(SerializedThreadMetadata, SerializedThread)>>> {
+        let worktree_paths = self
+            .project
+            .read(cx)
+            .visible_worktrees(cx)
+            .map(|worktree| worktree.read(cx).abs_path().to_string_lossy().to_string())
+            .collect::<HashSet<_>>();
+        let candidates = self
+            .reverse_chronological_threads()
+            .into_iter()
+            .filter(|metadata| &metadata.id != excluded_id)
+            .collect::<Vec<_>>();
+        let database_future = ThreadsDatabase::global_future(cx);
+        cx.background_spawn(async move {
+            let database = database_future.await.map_err(|err| anyhow!(err))?;
+            let mut threads = Vec::new();
+            for metadata in candidates {
+                if threads.len() >= limit {
+                    break;
+                }
+                let Some(thread) = database
+                    .try_find_thread(metadata.id.clone())
+                    .await
+                    .log_err()
+                    .flatten()
+                else {
+                    continue;
+                };
+                let is_in_project =
+                    thread
+                        .initial_project_snapshot
+                        .as_ref()
+                        .is_some_and(|snapshot| {
+                            snapshot
+                                .worktree_snapshots
+                                .iter()
+                                .any(|worktree| worktree_paths.contains(&worktree.worktree_path))
+                        });
+                if is_in_project {
+                    threads.push((metadata, thread));
+                }
+            }
+            Ok(threads)
+        })
+    }
+
     pub fn create_thread(&mut self, cx: &mut Context<Self>) -> Entity<Thread> {
         cx.new(|cx| {
             Thread::new(
@@ -632,6 +701,8 @@ pub struct SerializedThread {
     pub tool_use_limit_reached: bool,
     #[serde(default)]
     pub profile: Option<AgentProfileId>,
+    #[serde(default)]
+    pub plan: Option<Plan>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -712,9 +783,13 @@ pub struct SerializedMessage {
     #[serde(default)]
     pub context: String,
     #[serde(default)]
+    pub retrieved_chunks: Vec<RetrievedChunk>,
+    #[serde(default)]
     pub creases: Vec<SerializedCrease>,
     #[serde(default)]
     pub is_hidden: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub substitution: Option<ModelSubstitution>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -748,6 +823,8 @@ pub struct SerializedToolResult {
     pub is_error: bool,
     pub content: LanguageModelToolResultContent,
     pub output: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<LanguageModelImage>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -775,6 +852,7 @@ impl LegacySerializedThread {
             completion_mode: None,
             tool_use_limit_reached: false,
             profile: None,
+            plan: None,
         }
     }
 }
@@ -799,8 +877,10 @@ impl LegacySerializedMessage {
             tool_uses: self.tool_uses,
             tool_results: self.tool_results,
             context: String::new(),
+            retrieved_chunks: Vec::new(),
             creases: Vec::new(),
             is_hidden: false,
+            substitution: None,
         }
     }
 }
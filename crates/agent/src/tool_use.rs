@@ -9,8 +9,9 @@ use futures::FutureExt as _;
 use futures::future::Shared;
 use gpui::{App, Entity, SharedString, Task};
 use language_model::{
-    ConfiguredModel, LanguageModel, LanguageModelRequest, LanguageModelToolResult,
-    LanguageModelToolResultContent, LanguageModelToolUse, LanguageModelToolUseId, Role,
+    ConfiguredModel, LanguageModel, LanguageModelImage, LanguageModelRequest,
+    LanguageModelToolResult, LanguageModelToolResultContent, LanguageModelToolUse,
+    LanguageModelToolUseId, Role,
 };
 use project::Project;
 use ui::{IconName, Window};
@@ -36,6 +37,7 @@ pub struct ToolUseState {
     tool_results: HashMap<LanguageModelToolUseId, LanguageModelToolResult>,
     pending_tool_uses_by_id: HashMap<LanguageModelToolUseId, PendingToolUse>,
     tool_result_cards: HashMap<LanguageModelToolUseId, AnyToolCard>,
+    tool_result_images: HashMap<LanguageModelToolUseId, Vec<LanguageModelImage>>,
     tool_use_metadata_by_id: HashMap<LanguageModelToolUseId, ToolUseMetadata>,
 }
 
@@ -47,6 +49,7 @@ impl ToolUseState {
             tool_results: HashMap::default(),
             pending_tool_uses_by_id: HashMap::default(),
             tool_result_cards: HashMap::default(),
+            tool_result_images: HashMap::default(),
             tool_use_metadata_by_id: HashMap::default(),
         }
     }
@@ -110,6 +113,10 @@ impl ToolUseState {
                                     output: tool_result.output.clone(),
                                 },
                             );
+                            if !tool_result.images.is_empty() {
+                                this.tool_result_images
+                                    .insert(tool_use_id.clone(), tool_result.images.clone());
+                            }
 
                             if let Some(window) = &mut window {
                                 if let Some(tool) = this.tools.read(cx).tool(tool_use, cx) {
@@ -269,6 +276,17 @@ impl ToolUseState {
             .collect()
     }
 
+    /// The images a tool attached after its result, which are sent to the
+    /// model along with it.
+    pub fn tool_result_images(
+        &self,
+        tool_use_id: &LanguageModelToolUseId,
+    ) -> &[LanguageModelImage] {
+        self.tool_result_images
+            .get(tool_use_id)
+            .map_or(&[], Vec::as_slice)
+    }
+
     pub fn message_has_tool_results(&self, assistant_message_id: MessageId) -> bool {
         self.tool_uses_by_assistant_message
             .get(&assistant_message_id)
@@ -430,18 +448,47 @@ impl ToolUseState {
                     .map(|model| model.model.max_token_count() * BYTES_PER_TOKEN_ESTIMATE)
                     .unwrap_or(usize::MAX);
 
+                let truncate = |text: String| {
+                    if text.len() < tool_output_limit {
+                        text
+                    } else {
+                        let truncated = truncate_lines_to_byte_limit(&text, tool_output_limit);
+                        format!(
+                            "Tool result too long. The first {} bytes:\n\n{}",
+                            truncated.len(),
+                            truncated
+                        )
+                    }
+                };
                 let content = match tool_result {
                     ToolResultContent::Text(text) => {
-                        let text = if text.len() < tool_output_limit {
-                            text
-                        } else {
-                            let truncated = truncate_lines_to_byte_limit(&text, tool_output_limit);
-                            format!(
-                                "Tool result too long. The first {} bytes:\n\n{}",
-                                truncated.len(),
-                                truncated
-                            )
-                        };
+                        LanguageModelToolResultContent::Text(truncate(text).into())
+                    }
+                    ToolResultContent::TextWithImages { text, images } => {
+                        let mut remaining_limit = tool_output_limit.saturating_sub(text.len());
+                        let mut omitted_images = 0;
+                        let images = images
+                            .into_iter()
+                            .filter(|image| {
+                                let tokens = image.estimate_tokens();
+                                if tokens < remaining_limit {
+                                    remaining_limit -= tokens;
+                                    true
+                                } else {
+                                    omitted_images += 1;
+                                    false
+                                }
+                            })
+                            .collect::<Vec<_>>();
+                        let mut text = truncate(text);
+                        if omitted_images > 0 {
+                            text.push_str(&format!(
+                                "\n\n{omitted_images} image(s) were omitted because they would exceed the remaining tokens."
+                            ));
+                        }
+                        if !images.is_empty() {
+                            self.tool_result_images.insert(tool_use_id.clone(), images);
+                        }
                         LanguageModelToolResultContent::Text(text.into())
                     }
                     ToolResultContent::Image(language_model_image) => {
@@ -647,12 +647,29 @@ impl AddedContext {
             status,
             render_hover: Some(Rc::new({
                 let image = context.original_image.clone();
+                let prepared_size: Option<SharedString> = context.image().map(|prepared| {
+                    format!(
+                        "Sent as {}×{} (~{} tokens)",
+                        prepared.size.width.0,
+                        prepared.size.height.0,
+                        prepared.estimate_tokens()
+                    )
+                    .into()
+                });
                 move |_, cx| {
                     let image = image.clone();
+                    let prepared_size = prepared_size.clone();
                     ContextPillHover::new(cx, move |_, _| {
-                        gpui::img(image.clone())
-                            .max_w_96()
-                            .max_h_96()
+                        v_flex()
+                            .gap_1()
+                            .child(gpui::img(image.clone()).max_w_96().max_h_96())
+                            .when_some(prepared_size.clone(), |this, prepared_size| {
+                                this.child(
+                                    Label::new(prepared_size)
+                                        .size(LabelSize::Small)
+                                        .color(Color::Muted),
+                                )
+                            })
                             .into_any_element()
                     })
                     .into()
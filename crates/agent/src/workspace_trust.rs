@@ -0,0 +1,159 @@
+//! Tracks the workspaces that the agent is allowed to edit files and run
+//! commands in, when the `require_workspace_trust` setting is enabled.
+
+use std::path::{Path, PathBuf};
+
+use agent_settings::AgentSettings;
+use assistant_tool::{Tool, ToolSource};
+use collections::BTreeSet;
+use db::kvp::KEY_VALUE_STORE;
+use gpui::{App, AppContext as _, Entity, Global};
+use project::Project;
+use settings::Settings as _;
+use util::ResultExt as _;
+
+const TRUSTED_WORKSPACES_KEY: &str = "agent_trusted_workspaces";
+
+/// The root directories of the workspaces that have been trusted, persisted
+/// across restarts.
+#[derive(Default)]
+pub(crate) struct WorkspaceTrust {
+    trusted_roots: BTreeSet<PathBuf>,
+}
+
+impl Global for WorkspaceTrust {}
+
+pub(crate) fn init(cx: &mut App) {
+    cx.set_global(WorkspaceTrust::default());
+    cx.spawn(async move |cx| {
+        let roots = cx
+            .background_spawn(async move {
+                let Some(roots) = KEY_VALUE_STORE.read_kvp(TRUSTED_WORKSPACES_KEY)? else {
+                    return anyhow::Ok(Vec::new());
+                };
+                Ok(serde_json::from_str::<Vec<PathBuf>>(&roots)?)
+            })
+            .await
+            .log_err();
+        if let Some(roots) = roots {
+            cx.update_global::<WorkspaceTrust, _>(|trust, _| trust.trusted_roots.extend(roots))
+                .log_err();
+        }
+    })
+    .detach();
+}
+
+impl WorkspaceTrust {
+    /// Returns whether the agent may edit files and run commands in the
+    /// project, which is always the case unless trust is required.
+    pub fn is_trusted(project: &Entity<Project>, cx: &App) -> bool {
+        if !AgentSettings::get_global(cx).require_workspace_trust {
+            return true;
+        }
+        let Some(trust) = cx.try_global::<Self>() else {
+            return false;
+        };
+        worktree_roots(project, cx)
+            .iter()
+            .all(|root| is_covered(&trust.trusted_roots, root))
+    }
+
+    /// Trusts every folder in the project.
+    pub fn trust(project: &Entity<Project>, cx: &mut App) {
+        let roots = worktree_roots(project, cx);
+        let trusted_roots = cx.update_default_global::<Self, _>(|trust, _| {
+            trust.trusted_roots.extend(roots);
+            trust.trusted_roots.iter().cloned().collect::<Vec<_>>()
+        });
+        cx.background_spawn(async move {
+            KEY_VALUE_STORE
+                .write_kvp(
+                    TRUSTED_WORKSPACES_KEY.into(),
+                    serde_json::to_string(&trusted_roots)?,
+                )
+                .await
+        })
+        .detach_and_log_err(cx);
+    }
+}
+
+/// Returns whether the tool is disabled in untrusted workspaces. Tools
+/// provided by context servers run outside Zed, so they may do anything.
+pub(crate) fn requires_trust(tool: &dyn Tool) -> bool {
+    tool.may_perform_edits()
+        || tool.may_run_commands()
+        || matches!(tool.source(), ToolSource::ContextServer { .. })
+}
+
+fn worktree_roots(project: &Entity<Project>, cx: &App) -> Vec<PathBuf> {
+    project
+        .read(cx)
+        .visible_worktrees(cx)
+        .map(|worktree| worktree.read(cx).abs_path().to_path_buf())
+        .collect()
+}
+
+/// Returns whether the path is inside one of the trusted roots.
+fn is_covered(trusted_roots: &BTreeSet<PathBuf>, path: &Path) -> bool {
+    trusted_roots.iter().any(|root| path.starts_with(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assistant_tool::ToolRegistry;
+    use gpui::TestAppContext;
+    use http_client::FakeHttpClient;
+    use settings::SettingsStore;
+
+    #[gpui::test]
+    fn test_tools_that_edit_or_start_processes_require_trust(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+            Project::init_settings(cx);
+            AgentSettings::register(cx);
+            language_model::init_settings(cx);
+            ToolRegistry::default_global(cx);
+            assistant_tools::init(FakeHttpClient::with_404_response(), cx);
+        });
+        assert!(cx.update(|cx| AgentSettings::get_global(cx).require_workspace_trust));
+
+        let mut gated_tools = cx.update(|cx| {
+            ToolRegistry::global(cx)
+                .tools()
+                .into_iter()
+                .filter(|tool| requires_trust(tool.as_ref()))
+                .map(|tool| tool.name())
+                .collect::<Vec<_>>()
+        });
+        gated_tools.sort();
+        assert_eq!(
+            gated_tools,
+            [
+                "copy_path",
+                "create_directory",
+                "delete_path",
+                "edit_file",
+                "move_path",
+                "open",
+                "propose_tasks",
+                "run_python",
+                "sql_query",
+                "terminal",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_covered() {
+        let trusted_roots = BTreeSet::from_iter([PathBuf::from("/home/user/code")]);
+        assert!(is_covered(&trusted_roots, Path::new("/home/user/code")));
+        assert!(is_covered(&trusted_roots, Path::new("/home/user/code/app")));
+        assert!(!is_covered(
+            &trusted_roots,
+            Path::new("/home/user/code-old")
+        ));
+        assert!(!is_covered(&trusted_roots, Path::new("/home/user")));
+    }
+}
@@ -1,6 +1,6 @@
 mod agent_profile;
 
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use ::open_ai::Model as OpenAiModel;
 use anthropic::Model as AnthropicModel;
@@ -8,7 +8,7 @@ use anyhow::{Result, bail};
 use collections::IndexMap;
 use deepseek::Model as DeepseekModel;
 use gpui::{App, Pixels, SharedString};
-use language_model::LanguageModel;
+use language_model::{LanguageModel, LanguageModelDeprecation};
 use lmstudio::Model as LmStudioModel;
 use mistral::Model as MistralModel;
 use ollama::Model as OllamaModel;
@@ -104,6 +104,9 @@ pub struct AgentSettings {
     pub default_view: DefaultView,
     pub profiles: IndexMap<AgentProfileId, AgentProfileSettings>,
     pub always_allow_tool_actions: bool,
+    pub require_workspace_trust: bool,
+    pub interface_language: Option<String>,
+    pub answer_language: Option<String>,
     pub notify_when_agent_waiting: NotifyWhenAgentWaiting,
     pub play_sound_when_agent_done: bool,
     pub stream_edits: bool,
@@ -111,6 +114,26 @@ pub struct AgentSettings {
     pub model_parameters: Vec<LanguageModelParameters>,
     pub preferred_completion_mode: CompletionMode,
     pub enable_feedback: bool,
+    pub max_monthly_spend: Option<f64>,
+    pub max_run_minutes: Option<u64>,
+    pub auto_switch_to_free_model: bool,
+    pub hooks: Vec<AgentHook>,
+    pub allow_guest_model_requests: bool,
+    pub guest_token_budget: u64,
+    pub max_continuations: u32,
+    pub compress_large_attachments: bool,
+    pub warm_up_model_on_open: bool,
+    pub language_preambles: IndexMap<String, String>,
+    pub text_thread_retention: TextThreadRetention,
+    pub python_sandbox: PythonSandbox,
+    pub sql_query: SqlQuerySettings,
+    pub report_webhooks: Vec<ReportWebhook>,
+    pub verify_edits: EditVerificationSettings,
+    pub repo_map: RepoMapSettings,
+    pub auto_context: AutoContextSettings,
+    pub memory_recall: MemoryRecallSettings,
+    pub model_recommendation: ModelRecommendationSettings,
+    pub message_headers: MessageHeaderSettings,
 }
 
 impl AgentSettings {
@@ -123,6 +146,28 @@ impl AgentSettings {
             .and_then(|m| m.temperature)
     }
 
+    pub fn seed_for_model(model: &Arc<dyn LanguageModel>, cx: &App) -> Option<u64> {
+        if !model.supports_seed() {
+            return None;
+        }
+        let settings = Self::get_global(cx);
+        settings
+            .model_parameters
+            .iter()
+            .rfind(|setting| setting.matches(model))
+            .and_then(|m| m.seed)
+    }
+
+    /// Returns the guidelines configured for the language, if any. Language
+    /// names are matched case-insensitively.
+    pub fn language_preamble(&self, language_name: &str) -> Option<&str> {
+        self.language_preambles
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(language_name))
+            .map(|(_, preamble)| preamble.trim())
+            .filter(|preamble| !preamble.is_empty())
+    }
+
     pub fn set_inline_assistant_model(&mut self, provider: String, model: String) {
         self.inline_assistant_model = Some(LanguageModelSelection {
             provider: provider.into(),
@@ -143,6 +188,29 @@ impl AgentSettings {
             model,
         });
     }
+
+    /// Returns the deprecations of the models that the settings refer to.
+    pub fn model_deprecations(&self) -> Vec<LanguageModelDeprecation> {
+        let mut deprecations = Vec::new();
+        for selection in [
+            Some(&self.default_model),
+            self.inline_assistant_model.as_ref(),
+            self.commit_message_model.as_ref(),
+            self.thread_summary_model.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if let Some(deprecation) =
+                LanguageModelDeprecation::for_model(&selection.provider.0, &selection.model)
+            {
+                if !deprecations.contains(&deprecation) {
+                    deprecations.push(deprecation);
+                }
+            }
+        }
+        deprecations
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
@@ -150,6 +218,8 @@ pub struct LanguageModelParameters {
     pub provider: Option<LanguageModelProviderSetting>,
     pub model: Option<SharedString>,
     pub temperature: Option<f32>,
+    /// Sent to the providers that support deterministic sampling.
+    pub seed: Option<u64>,
 }
 
 impl LanguageModelParameters {
@@ -168,6 +238,47 @@ impl LanguageModelParameters {
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct AgentHook {
+    /// The workspace event that triggers the hook.
+    pub on: AgentHookEvent,
+    /// What the agent does when the hook is triggered.
+    ///
+    /// Default: open_panel
+    #[serde(default)]
+    pub action: AgentHookAction,
+    /// The prompt to send to the agent. `$ZED_HOOK_DETAILS` is replaced with
+    /// a description of the event, such as the failed task's command.
+    pub prompt: String,
+    /// Whether to post the final summary of the thread to the
+    /// `report_webhooks` when it's done. Only applies to background hooks.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub report: bool,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentHookEvent {
+    /// A task exited with a non-zero status.
+    TaskFailed,
+    /// The number of errors in the project went up after saving a file.
+    DiagnosticsIncreased,
+    /// A repository in the project has merge conflicts.
+    MergeConflict,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentHookAction {
+    /// Start a new thread with the prompt in the agent panel.
+    #[default]
+    OpenPanel,
+    /// Run a new thread with the prompt in the background, and notify when it's done.
+    Background,
+}
+
 /// Agent panel settings
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct AgentSettingsContent {
@@ -272,6 +383,9 @@ impl AgentSettingsContent {
                     default_view: None,
                     profiles: None,
                     always_allow_tool_actions: None,
+                    require_workspace_trust: None,
+                    interface_language: None,
+                    answer_language: None,
                     notify_when_agent_waiting: None,
                     stream_edits: None,
                     single_file_review: None,
@@ -279,6 +393,26 @@ impl AgentSettingsContent {
                     preferred_completion_mode: None,
                     enable_feedback: None,
                     play_sound_when_agent_done: None,
+                    max_monthly_spend: None,
+                    max_run_minutes: None,
+                    auto_switch_to_free_model: None,
+                    hooks: Vec::new(),
+                    allow_guest_model_requests: None,
+                    guest_token_budget: None,
+                    max_continuations: None,
+                    compress_large_attachments: None,
+                    warm_up_model_on_open: None,
+                    language_preambles: None,
+                    text_thread_retention: None,
+                    python_sandbox: None,
+                    sql_query: None,
+                    report_webhooks: Vec::new(),
+                    verify_edits: None,
+                    repo_map: None,
+                    auto_context: None,
+                    memory_recall: None,
+                    model_recommendation: None,
+                    message_headers: None,
                 },
                 VersionedAgentSettingsContent::V2(ref settings) => settings.clone(),
             },
@@ -305,6 +439,9 @@ impl AgentSettingsContent {
                 default_view: None,
                 profiles: None,
                 always_allow_tool_actions: None,
+                require_workspace_trust: None,
+                interface_language: None,
+                answer_language: None,
                 notify_when_agent_waiting: None,
                 stream_edits: None,
                 single_file_review: None,
@@ -312,6 +449,26 @@ impl AgentSettingsContent {
                 preferred_completion_mode: None,
                 enable_feedback: None,
                 play_sound_when_agent_done: None,
+                max_monthly_spend: None,
+                max_run_minutes: None,
+                auto_switch_to_free_model: None,
+                hooks: Vec::new(),
+                allow_guest_model_requests: None,
+                guest_token_budget: None,
+                max_continuations: None,
+                compress_large_attachments: None,
+                warm_up_model_on_open: None,
+                language_preambles: None,
+                text_thread_retention: None,
+                python_sandbox: None,
+                sql_query: None,
+                report_webhooks: Vec::new(),
+                verify_edits: None,
+                repo_map: None,
+                auto_context: None,
+                memory_recall: None,
+                model_recommendation: None,
+                message_headers: None,
             },
             None => AgentSettingsContentV2::default(),
         }
@@ -374,6 +531,7 @@ impl AgentSettingsContent {
                                 Some(language_model.supports_tools()),
                                 Some(language_model.supports_images()),
                                 None,
+                                Some(language_model.supports_fill_in_the_middle()),
                             )),
                             api_url,
                         });
@@ -465,6 +623,30 @@ impl AgentSettingsContent {
         .ok();
     }
 
+    /// Replaces the models that have been deprecated by their providers with
+    /// the recommended replacements.
+    pub fn replace_deprecated_models(&mut self) {
+        self.v2_setting(|setting| {
+            for selection in [
+                setting.default_model.as_mut(),
+                setting.inline_assistant_model.as_mut(),
+                setting.commit_message_model.as_mut(),
+                setting.thread_summary_model.as_mut(),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                if let Some(deprecation) =
+                    LanguageModelDeprecation::for_model(&selection.provider.0, &selection.model)
+                {
+                    selection.model = deprecation.replacement.to_string();
+                }
+            }
+            Ok(())
+        })
+        .ok();
+    }
+
     pub fn v2_setting(
         &mut self,
         f: impl FnOnce(&mut AgentSettingsContentV2) -> anyhow::Result<()>,
@@ -520,6 +702,14 @@ impl AgentSettingsContent {
         .ok();
     }
 
+    pub fn set_max_monthly_spend(&mut self, max_monthly_spend: Option<f64>) {
+        self.v2_setting(|setting| {
+            setting.max_monthly_spend = max_monthly_spend;
+            Ok(())
+        })
+        .ok();
+    }
+
     pub fn set_profile(&mut self, profile_id: AgentProfileId) {
         self.v2_setting(|setting| {
             setting.default_profile = Some(profile_id);
@@ -592,6 +782,9 @@ impl Default for VersionedAgentSettingsContent {
             default_view: None,
             profiles: None,
             always_allow_tool_actions: None,
+            require_workspace_trust: None,
+            interface_language: None,
+            answer_language: None,
             notify_when_agent_waiting: None,
             stream_edits: None,
             single_file_review: None,
@@ -599,6 +792,26 @@ impl Default for VersionedAgentSettingsContent {
             preferred_completion_mode: None,
             enable_feedback: None,
             play_sound_when_agent_done: None,
+            max_monthly_spend: None,
+            max_run_minutes: None,
+            auto_switch_to_free_model: None,
+            hooks: Vec::new(),
+            allow_guest_model_requests: None,
+            guest_token_budget: None,
+            max_continuations: None,
+            compress_large_attachments: None,
+            warm_up_model_on_open: None,
+            language_preambles: None,
+            text_thread_retention: None,
+            python_sandbox: None,
+            sql_query: None,
+            report_webhooks: Vec::new(),
+            verify_edits: None,
+            repo_map: None,
+            auto_context: None,
+            memory_recall: None,
+            model_recommendation: None,
+            message_headers: None,
         })
     }
 }
@@ -651,6 +864,21 @@ pub struct AgentSettingsContentV2 {
     ///
     /// Default: false
     always_allow_tool_actions: Option<bool>,
+    /// Whether file-writing and shell tools are disabled in workspaces that
+    /// you haven't trusted from the agent panel.
+    ///
+    /// Default: true
+    require_workspace_trust: Option<bool>,
+    /// The language to show the agent panel in, such as `es`, or null for
+    /// English.
+    ///
+    /// Default: null
+    interface_language: Option<String>,
+    /// The language the model is asked to answer in, such as `Spanish`, or
+    /// null to let it choose. Text threads can override it.
+    ///
+    /// Default: null
+    answer_language: Option<String>,
     /// Where to show a popup notification when the agent is waiting for user input.
     ///
     /// Default: "primary_screen"
@@ -684,6 +912,458 @@ pub struct AgentSettingsContentV2 {
     ///
     /// Default: true
     enable_feedback: Option<bool>,
+    /// The maximum amount, in US dollars, to spend on language model requests
+    /// each month, based on the pricing reported by providers. Requests are
+    /// blocked once it has been reached.
+    ///
+    /// Default: null
+    max_monthly_spend: Option<f64>,
+    /// How long, in minutes, the agent may work on a request before it's
+    /// asked to stop and summarize its progress and the remaining steps.
+    ///
+    /// Default: null
+    max_run_minutes: Option<u64>,
+    /// Whether to automatically switch to a free model with a comparable
+    /// context window when a request is blocked by the maximum monthly spend
+    /// or by the provider's rate limits, instead of offering to switch.
+    ///
+    /// Default: false
+    auto_switch_to_free_model: Option<bool>,
+    /// Assistant actions to run when workspace events happen, such as a task
+    /// failing or a merge conflict being detected.
+    ///
+    /// Default: []
+    #[serde(default)]
+    hooks: Vec<AgentHook>,
+    /// Whether guests in a shared project can ask for text thread responses
+    /// from this user's language model, once approved.
+    ///
+    /// Default: true
+    allow_guest_model_requests: Option<bool>,
+    /// The number of tokens each guest can use through this user's language
+    /// model in a collaboration session.
+    ///
+    /// Default: 100000
+    guest_token_budget: Option<u64>,
+    /// The number of times a response that was cut off by the model's output
+    /// token limit is automatically continued. Set to 0 to disable.
+    ///
+    /// Default: 3
+    max_continuations: Option<u32>,
+    /// Whether to compress large attachments in text threads before sending
+    /// them, by dropping lines that carry little information, such as blank
+    /// lines and repeated log lines.
+    ///
+    /// Default: false
+    compress_large_attachments: Option<bool>,
+    /// Whether to load the default and inline assistant models when the agent
+    /// panel opens, so the first request doesn't wait for a local model to
+    /// load. Only affects local providers, such as Ollama.
+    ///
+    /// Default: false
+    warm_up_model_on_open: Option<bool>,
+    /// Guidelines to give the model when working with a particular language,
+    /// keyed by language name. They're included when the inline assist target
+    /// or most of a text thread's attached files are in that language.
+    ///
+    /// Default: {}
+    language_preambles: Option<IndexMap<String, String>>,
+    /// Limits on how many saved text threads are kept on disk, and how large
+    /// saved text threads can get before their slash command outputs are
+    /// moved into separate files.
+    ///
+    /// Default: {"max_count": null, "max_age_days": null, "max_disk_size_mb": null, "compact_outputs_larger_than_kb": 64}
+    text_thread_retention: Option<TextThreadRetention>,
+    /// Where the `run_python` tool runs the snippets the model writes.
+    ///
+    /// Default: {"backend": "venv", "venv_path": null, "docker_image": "python:3.12-slim", "timeout_secs": 30}
+    python_sandbox: Option<PythonSandbox>,
+    /// The databases the `sql_query` tool can query, and how much of a
+    /// result it returns.
+    ///
+    /// Default: {"connections": {}, "max_rows": 100, "max_result_kb": 32}
+    sql_query: Option<SqlQuerySettings>,
+    /// Webhooks that receive the final summary of agent threads that opted
+    /// in to being reported, such as background threads run by hooks.
+    ///
+    /// Default: []
+    #[serde(default)]
+    report_webhooks: Vec<ReportWebhook>,
+    /// A check to run after the agent edits files, whose new errors in the
+    /// edited files are sent back to the model to fix before the turn ends.
+    ///
+    /// Default: {"enabled": false, "command": "cargo", "args": ["check", "--message-format=short"], "max_iterations": 2}
+    verify_edits: Option<EditVerificationSettings>,
+    /// A summary of the project's files and the types and functions they
+    /// define, included in the agent's system prompt.
+    ///
+    /// Default: {"enabled": true, "max_tokens": 2000}
+    repo_map: Option<RepoMapSettings>,
+    /// Code found by searching the project for each message, attached to it
+    /// when auto-context is on. Requires the semantic index.
+    ///
+    /// Default: {"enabled": false, "max_chunks": 5, "max_tokens": 2000}
+    auto_context: Option<AutoContextSettings>,
+    /// Exchanges from the project's past threads that are relevant to the
+    /// message being composed, offered as memories to attach to it.
+    ///
+    /// Default: {"enabled": false, "max_threads": 20}
+    memory_recall: Option<MemoryRecallSettings>,
+    /// Suggestions of a model better suited to the message being composed,
+    /// based on the kind of task it asks for and how models were rated on
+    /// such tasks.
+    ///
+    /// Default: {"enabled": true, "long_context_tokens": 30000, "min_ratings": 5}
+    model_recommendation: Option<ModelRecommendationSettings>,
+    /// How the headers of messages in text threads look. Their colors are
+    /// set by the theme, under `message_header`.
+    ///
+    /// Default: {"divider": "none", "tint_background": true}
+    message_headers: Option<MessageHeaderSettings>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default)]
+pub struct TextThreadRetention {
+    /// The maximum number of saved text threads to keep. The least recently
+    /// saved ones are deleted first.
+    ///
+    /// Default: null
+    pub max_count: Option<usize>,
+    /// The number of days after which a text thread that hasn't been saved
+    /// since is deleted.
+    ///
+    /// Default: null
+    pub max_age_days: Option<u64>,
+    /// The maximum disk space, in megabytes, that saved text threads can use.
+    /// The least recently saved ones are deleted until they fit.
+    ///
+    /// Default: null
+    pub max_disk_size_mb: Option<u64>,
+    /// Slash command outputs larger than this many kilobytes are moved into a
+    /// file next to the text thread when it's saved, and loaded back when it's
+    /// opened. Set to null to always save text threads as a single file.
+    ///
+    /// Default: 64
+    pub compact_outputs_larger_than_kb: Option<u64>,
+    /// The number of days deleted text threads are kept in the trash, from
+    /// which they can be restored, before they're deleted for good.
+    ///
+    /// Default: 30
+    pub trash_retention_days: u64,
+    /// Whether text threads that were never written in are discarded instead
+    /// of being kept in the history.
+    ///
+    /// Default: true
+    pub delete_empty: bool,
+}
+
+impl Default for TextThreadRetention {
+    fn default() -> Self {
+        Self {
+            max_count: None,
+            max_age_days: None,
+            max_disk_size_mb: None,
+            compact_outputs_larger_than_kb: Some(64),
+            trash_retention_days: 30,
+            delete_empty: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default)]
+pub struct PythonSandbox {
+    /// Whether snippets run with a local virtual environment's interpreter,
+    /// or in a throwaway Docker container without network access.
+    ///
+    /// The venv backend isn't a sandbox: snippets run as your user, with
+    /// access to the network and to every file you can read or write.
+    ///
+    /// Default: venv
+    pub backend: PythonSandboxBackend,
+    /// The virtual environment to run snippets with. When unset, the `.venv`
+    /// directory at the root of the project is used if there is one, or
+    /// `python3` otherwise.
+    ///
+    /// Default: null
+    pub venv_path: Option<PathBuf>,
+    /// The image to run snippets in when using the Docker backend.
+    ///
+    /// Default: "python:3.12-slim"
+    pub docker_image: String,
+    /// How long a snippet can run before it's stopped.
+    ///
+    /// Default: 30
+    pub timeout_secs: u64,
+}
+
+impl Default for PythonSandbox {
+    fn default() -> Self {
+        Self {
+            backend: PythonSandboxBackend::default(),
+            venv_path: None,
+            docker_image: "python:3.12-slim".into(),
+            timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PythonSandboxBackend {
+    #[default]
+    Venv,
+    Docker,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default)]
+pub struct SqlQuerySettings {
+    /// Connection strings for the databases the model can query, keyed by
+    /// the name it refers to them by, such as
+    /// `postgres://reader@localhost/app`, `mysql://reader@localhost/app` or
+    /// `sqlite:///path/to/app.db`. Queries run in read-only transactions, but
+    /// it's best to use credentials that can only read, too.
+    ///
+    /// Default: {}
+    pub connections: IndexMap<String, String>,
+    /// The maximum number of rows returned for a query.
+    ///
+    /// Default: 100
+    pub max_rows: usize,
+    /// The maximum size, in kilobytes, of the table returned for a query.
+    ///
+    /// Default: 32
+    pub max_result_kb: usize,
+}
+
+impl Default for SqlQuerySettings {
+    fn default() -> Self {
+        Self {
+            connections: IndexMap::default(),
+            max_rows: 100,
+            max_result_kb: 32,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct ReportWebhook {
+    /// The URL to post reports to.
+    pub url: String,
+    /// The shape of the posted JSON payload.
+    ///
+    /// Default: generic
+    #[serde(default)]
+    pub format: ReportWebhookFormat,
+    /// The text of the report. `$ZED_REPORT_TITLE`, `$ZED_REPORT_PROJECT`,
+    /// `$ZED_REPORT_STATUS` and `$ZED_REPORT_SUMMARY` are replaced with the
+    /// thread's title, the project's name, how the thread ended and the
+    /// agent's final message.
+    ///
+    /// Default: "$ZED_REPORT_TITLE ($ZED_REPORT_PROJECT, $ZED_REPORT_STATUS)\n\n$ZED_REPORT_SUMMARY"
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportWebhookFormat {
+    /// A Slack incoming webhook, posted as `{"text": ...}`.
+    Slack,
+    /// A Discord webhook, posted as `{"content": ...}`.
+    Discord,
+    /// Any other endpoint, posted as an object with the report's `text`,
+    /// `title`, `project`, `status` and `summary`.
+    #[default]
+    Generic,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default)]
+pub struct EditVerificationSettings {
+    /// Whether to run the check after a turn in which the agent edited files.
+    ///
+    /// Default: false
+    pub enabled: bool,
+    /// The program that checks the project, run from the root of its first
+    /// worktree. Errors are read from lines such as
+    /// `src/main.rs:3:5: error: ...` or `src/main.ts(3,5): error TS2304: ...`.
+    ///
+    /// Default: "cargo"
+    pub command: String,
+    /// The arguments to pass to the command.
+    ///
+    /// Default: ["check", "--message-format=short"]
+    pub args: Vec<String>,
+    /// The maximum number of times the model is asked to fix the errors in
+    /// a turn, after which the turn ends with the errors reported.
+    ///
+    /// Default: 2
+    pub max_iterations: u32,
+}
+
+impl Default for EditVerificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: "cargo".into(),
+            args: vec!["check".into(), "--message-format=short".into()],
+            max_iterations: 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default)]
+pub struct RepoMapSettings {
+    /// Whether to include the repository map in the agent's system prompt.
+    ///
+    /// Default: true
+    pub enabled: bool,
+    /// The maximum number of tokens the repository map takes up. Larger
+    /// projects are summarized with fewer details, such as only their
+    /// directories and file names.
+    ///
+    /// Default: 2000
+    pub max_tokens: usize,
+}
+
+impl Default for RepoMapSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_tokens: 2000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default)]
+pub struct AutoContextSettings {
+    /// Whether new threads start with auto-context on.
+    ///
+    /// Default: false
+    pub enabled: bool,
+    /// The maximum number of chunks attached to a message.
+    ///
+    /// Default: 5
+    pub max_chunks: usize,
+    /// The maximum number of tokens the attached chunks take up.
+    ///
+    /// Default: 2000
+    pub max_tokens: usize,
+}
+
+impl Default for AutoContextSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_chunks: 5,
+            max_tokens: 2000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default)]
+pub struct MemoryRecallSettings {
+    /// Whether to offer memories from past threads while composing a message.
+    ///
+    /// Default: false
+    pub enabled: bool,
+    /// How many of the project's most recent threads are recalled from.
+    ///
+    /// Default: 20
+    pub max_threads: usize,
+}
+
+impl Default for MemoryRecallSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_threads: 20,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default)]
+pub struct ModelRecommendationSettings {
+    /// Whether to suggest a better-suited model before a message is sent.
+    ///
+    /// Default: true
+    pub enabled: bool,
+    /// The model to suggest for messages that, with their context, take up
+    /// many tokens.
+    ///
+    /// Default: null
+    pub long_context_model: Option<LanguageModelSelection>,
+    /// The model to suggest for messages asking for code to be written or
+    /// changed.
+    ///
+    /// Default: null
+    pub code_model: Option<LanguageModelSelection>,
+    /// The model to suggest for short questions.
+    ///
+    /// Default: null
+    pub question_model: Option<LanguageModelSelection>,
+    /// How many tokens a message and its context take up before it's
+    /// considered a long-context task.
+    ///
+    /// Default: 30000
+    pub long_context_tokens: usize,
+    /// How many ratings a model needs on a kind of task before it's
+    /// suggested for it based on them, when no model is set for the task.
+    ///
+    /// Default: 5
+    pub min_ratings: usize,
+}
+
+impl Default for ModelRecommendationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            long_context_model: None,
+            code_model: None,
+            question_model: None,
+            long_context_tokens: 30000,
+            min_ratings: 5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default)]
+pub struct MessageHeaderSettings {
+    /// The line drawn above each message header, to separate messages.
+    ///
+    /// Default: none
+    pub divider: MessageHeaderDivider,
+    /// Whether message headers are tinted with the background color the
+    /// theme sets for their role.
+    ///
+    /// Default: true
+    pub tint_background: bool,
+}
+
+impl Default for MessageHeaderSettings {
+    fn default() -> Self {
+        Self {
+            divider: MessageHeaderDivider::None,
+            tint_background: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageHeaderDivider {
+    #[default]
+    None,
+    Solid,
+    Dashed,
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Default)]
@@ -882,6 +1562,14 @@ impl Settings for AgentSettings {
                 &mut settings.always_allow_tool_actions,
                 value.always_allow_tool_actions,
             );
+            merge(
+                &mut settings.require_workspace_trust,
+                value.require_workspace_trust,
+            );
+            settings.interface_language = value
+                .interface_language
+                .or(settings.interface_language.take());
+            settings.answer_language = value.answer_language.or(settings.answer_language.take());
             merge(
                 &mut settings.notify_when_agent_waiting,
                 value.notify_when_agent_waiting,
@@ -900,9 +1588,57 @@ impl Settings for AgentSettings {
             );
             merge(&mut settings.enable_feedback, value.enable_feedback);
 
+            merge(
+                &mut settings.auto_switch_to_free_model,
+                value.auto_switch_to_free_model,
+            );
+
+            if let Some(max_monthly_spend) = value.max_monthly_spend {
+                settings.max_monthly_spend = Some(max_monthly_spend);
+            }
+            if let Some(max_run_minutes) = value.max_run_minutes {
+                settings.max_run_minutes = Some(max_run_minutes);
+            }
+
             settings
                 .model_parameters
                 .extend_from_slice(&value.model_parameters);
+            settings.hooks.extend_from_slice(&value.hooks);
+            merge(
+                &mut settings.allow_guest_model_requests,
+                value.allow_guest_model_requests,
+            );
+            merge(&mut settings.guest_token_budget, value.guest_token_budget);
+            merge(&mut settings.max_continuations, value.max_continuations);
+            merge(
+                &mut settings.compress_large_attachments,
+                value.compress_large_attachments,
+            );
+            merge(
+                &mut settings.warm_up_model_on_open,
+                value.warm_up_model_on_open,
+            );
+            if let Some(language_preambles) = value.language_preambles {
+                settings.language_preambles.extend(language_preambles);
+            }
+            merge(
+                &mut settings.text_thread_retention,
+                value.text_thread_retention,
+            );
+            merge(&mut settings.python_sandbox, value.python_sandbox);
+            merge(&mut settings.sql_query, value.sql_query);
+            settings
+                .report_webhooks
+                .extend_from_slice(&value.report_webhooks);
+            merge(&mut settings.verify_edits, value.verify_edits);
+            merge(&mut settings.repo_map, value.repo_map);
+            merge(&mut settings.auto_context, value.auto_context);
+            merge(&mut settings.memory_recall, value.memory_recall);
+            merge(
+                &mut settings.model_recommendation,
+                value.model_recommendation,
+            );
+            merge(&mut settings.message_headers, value.message_headers);
 
             if let Some(profiles) = value.profiles {
                 settings
@@ -1029,6 +1765,9 @@ mod tests {
                             default_view: None,
                             profiles: None,
                             always_allow_tool_actions: None,
+                            require_workspace_trust: None,
+                            interface_language: None,
+                            answer_language: None,
                             play_sound_when_agent_done: None,
                             notify_when_agent_waiting: None,
                             stream_edits: None,
@@ -1036,6 +1775,26 @@ mod tests {
                             enable_feedback: None,
                             model_parameters: Vec::new(),
                             preferred_completion_mode: None,
+                            max_monthly_spend: None,
+                            max_run_minutes: None,
+                            auto_switch_to_free_model: None,
+                            hooks: Vec::new(),
+                            allow_guest_model_requests: None,
+                            guest_token_budget: None,
+                            max_continuations: None,
+                            compress_large_attachments: None,
+                            warm_up_model_on_open: None,
+                            language_preambles: None,
+                            text_thread_retention: None,
+                            python_sandbox: None,
+                            sql_query: None,
+                            report_webhooks: Vec::new(),
+                            verify_edits: None,
+                            repo_map: None,
+                            auto_context: None,
+                            memory_recall: None,
+                            model_recommendation: None,
+                            message_headers: None,
                         })),
                     }
                 },
@@ -263,6 +263,38 @@ impl Model {
         }
     }
 
+    /// Whether the model accepts PDF documents.
+    pub fn supports_documents(&self) -> bool {
+        match self {
+            Self::ClaudeOpus4
+            | Self::ClaudeOpus4Thinking
+            | Self::ClaudeSonnet4
+            | Self::ClaudeSonnet4Thinking
+            | Self::Claude3_5Sonnet
+            | Self::Claude3_7Sonnet
+            | Self::Claude3_7SonnetThinking
+            | Self::Claude3_5Haiku
+            | Self::Custom { .. } => true,
+            Self::Claude3Opus | Self::Claude3Sonnet | Self::Claude3Haiku => false,
+        }
+    }
+
+    /// Whether the model can search the web with the API's web search tool.
+    pub fn supports_web_search(&self) -> bool {
+        match self {
+            Self::ClaudeOpus4
+            | Self::ClaudeOpus4Thinking
+            | Self::ClaudeSonnet4
+            | Self::ClaudeSonnet4Thinking
+            | Self::Claude3_5Sonnet
+            | Self::Claude3_7Sonnet
+            | Self::Claude3_7SonnetThinking
+            | Self::Claude3_5Haiku
+            | Self::Custom { .. } => true,
+            Self::Claude3Opus | Self::Claude3Sonnet | Self::Claude3Haiku => false,
+        }
+    }
+
     pub const DEFAULT_BETA_HEADERS: &[&str] = &["prompt-caching-2024-07-31"];
 
     pub fn beta_headers(&self) -> String {
@@ -574,6 +606,14 @@ pub enum RequestContent {
         #[serde(skip_serializing_if = "Option::is_none")]
         cache_control: Option<CacheControl>,
     },
+    #[serde(rename = "document")]
+    Document {
+        source: DocumentSource,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
     #[serde(rename = "tool_use")]
     ToolUse {
         id: String,
@@ -621,6 +661,18 @@ pub enum ResponseContent {
         name: String,
         input: serde_json::Value,
     },
+    /// A call to a tool the API runs itself, such as web search.
+    #[serde(rename = "server_tool_use")]
+    ServerToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    #[serde(rename = "web_search_tool_result")]
+    WebSearchToolResult {
+        tool_use_id: String,
+        content: serde_json::Value,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -631,6 +683,14 @@ pub struct ImageSource {
     pub data: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocumentSource {
+    #[serde(rename = "type")]
+    pub source_type: String,
+    pub media_type: String,
+    pub data: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Tool {
     pub name: String,
@@ -638,6 +698,40 @@ pub struct Tool {
     pub input_schema: serde_json::Value,
 }
 
+/// A tool the model can call: one the client runs, or one the API runs
+/// itself.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ToolDefinition {
+    Custom(Tool),
+    WebSearch(WebSearchTool),
+}
+
+impl From<Tool> for ToolDefinition {
+    fn from(tool: Tool) -> Self {
+        Self::Custom(tool)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebSearchTool {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+}
+
+impl Default for WebSearchTool {
+    fn default() -> Self {
+        Self {
+            tool_type: "web_search_20250305".into(),
+            name: "web_search".into(),
+            max_uses: Some(5),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum ToolChoice {
@@ -666,7 +760,7 @@ pub struct Request {
     pub max_tokens: u32,
     pub messages: Vec<Message>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub tools: Vec<Tool>,
+    pub tools: Vec<ToolDefinition>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub thinking: Option<Thinking>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -759,6 +853,23 @@ pub enum ContentDelta {
     SignatureDelta { signature: String },
     #[serde(rename = "input_json_delta")]
     InputJsonDelta { partial_json: String },
+    #[serde(rename = "citations_delta")]
+    CitationsDelta { citation: Citation },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Citation {
+    #[serde(rename = "web_search_result_location")]
+    WebSearchResultLocation {
+        url: String,
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        cited_text: String,
+    },
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1,11 +1,35 @@
+mod citations;
 mod context;
+mod context_assembly;
 mod context_editor;
+mod context_environment;
+mod context_export;
 mod context_history;
+mod context_storage;
 mod context_store;
+mod context_sync;
+mod cost_tracker;
+mod environment_variables_modal;
+mod event_log;
+mod keyed_blocks;
 pub mod language_model_selector;
+mod localization;
 mod max_mode_tooltip;
+mod message_diffs;
+mod post_processing;
+mod prompt_compression;
+mod redaction;
+mod redaction_review_modal;
+mod reference_check;
+mod save_snippet_modal;
 mod slash_command;
 mod slash_command_picker;
+mod stack_trace;
+mod stop_condition;
+mod stop_conditions_modal;
+mod streaming_markdown;
+mod tag_contexts_modal;
+mod text_threads_database;
 
 use std::sync::Arc;
 
@@ -14,13 +38,24 @@ use gpui::{App, Context};
 use workspace::Workspace;
 
 pub use crate::context::*;
+pub use crate::context_assembly::{AssemblyEntry, AssemblyReport, AssemblyStrategy, Inclusion};
 pub use crate::context_editor::*;
 pub use crate::context_history::*;
+pub use crate::context_storage::TextThreadStorageUsage;
 pub use crate::context_store::*;
+pub use crate::cost_tracker::CostTracker;
+pub use crate::event_log::EventLog;
+pub use crate::localization::tr;
+pub use crate::post_processing::{PostProcessingChange, PostProcessor};
 pub use crate::slash_command::*;
+pub use crate::stack_trace::{StackTraceKind, StackTraceTag};
+pub use crate::stop_condition::StopCondition;
+pub use crate::text_threads_database::TextThreadsDatabase;
 
 pub fn init(client: Arc<Client>, cx: &mut App) {
     context_store::init(&client.into());
+    TextThreadsDatabase::init(cx);
+    localization::init(cx);
     workspace::FollowableViewRegistry::register::<ContextEditor>(cx);
 
     cx.observe_new(
@@ -29,6 +64,7 @@ pub fn init(client: Arc<Client>, cx: &mut App) {
                 .register_action(ContextEditor::quote_selection)
                 .register_action(ContextEditor::insert_selection)
                 .register_action(ContextEditor::copy_code)
+                .register_action(ContextEditor::save_snippet)
                 .register_action(ContextEditor::handle_insert_dragged_files);
         },
     )
@@ -0,0 +1,218 @@
+//! Matches the claims in an assistant message against the sources attached
+//! to the context, so that the message can cite where each claim came from.
+
+use collections::HashMap;
+use std::ops::Range;
+
+/// The number of consecutive words a claim and a source must share for the
+/// claim to be attributed to the source.
+const SHINGLE_LEN: usize = 5;
+/// The fraction of a claim's shingles that must appear in a source.
+const MIN_OVERLAP: f32 = 0.5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Citation {
+    /// The range of the claim in the message.
+    pub claim: Range<usize>,
+    /// The index of the cited source.
+    pub source_ix: usize,
+    /// The range of the text in the source that the claim matches.
+    pub source_range: Range<usize>,
+}
+
+/// Returns the citations of the given sources in the message, in the order
+/// the claims appear in it.
+pub fn find_citations(message: &str, sources: &[&str]) -> Vec<Citation> {
+    let sources = sources
+        .iter()
+        .map(|source| shingle_index(source))
+        .collect::<Vec<_>>();
+
+    let mut citations = Vec::new();
+    for claim in claims(message) {
+        let words = words(&message[claim.clone()]);
+        if words.len() < SHINGLE_LEN {
+            continue;
+        }
+        let shingles = words
+            .windows(SHINGLE_LEN)
+            .map(shingle_key)
+            .collect::<Vec<_>>();
+
+        let mut best: Option<(usize, usize, Range<usize>)> = None;
+        for (source_ix, index) in sources.iter().enumerate() {
+            let mut matched = 0;
+            let mut source_range: Option<Range<usize>> = None;
+            for shingle in &shingles {
+                let Some(range) = index.get(shingle) else {
+                    continue;
+                };
+                matched += 1;
+                // Extend the cited range over consecutive matches, which
+                // overlap because the shingles share words.
+                match &mut source_range {
+                    Some(source_range)
+                        if range.start <= source_range.end && range.end > source_range.end =>
+                    {
+                        source_range.end = range.end;
+                    }
+                    Some(_) => {}
+                    None => source_range = Some(range.clone()),
+                }
+            }
+            let Some(source_range) = source_range else {
+                continue;
+            };
+            if best
+                .as_ref()
+                .is_none_or(|(_, best_matched, _)| matched > *best_matched)
+            {
+                best = Some((source_ix, matched, source_range));
+            }
+        }
+
+        if let Some((source_ix, matched, source_range)) = best {
+            if matched as f32 / shingles.len() as f32 >= MIN_OVERLAP {
+                citations.push(Citation {
+                    claim,
+                    source_ix,
+                    source_range,
+                });
+            }
+        }
+    }
+    citations
+}
+
+/// Splits the message into sentences and lines of code, skipping code fences.
+fn claims(message: &str) -> Vec<Range<usize>> {
+    let mut claims = Vec::new();
+    let mut line_start = 0;
+    for line in message.split_inclusive('\n') {
+        let line_end = line_start + line.trim_end().len();
+        if !line.trim_start().starts_with("```") {
+            let mut claim_start = line_start;
+            for (ix, _) in line.match_indices(['.', '!', '?']) {
+                let end = line_start + ix + 1;
+                if end < line_end && message[end..].starts_with(' ') {
+                    claims.push(claim_start..end);
+                    claim_start = end + 1;
+                }
+            }
+            if claim_start < line_end {
+                claims.push(claim_start..line_end);
+            }
+        }
+        line_start += line.len();
+    }
+    claims.retain(|claim| !message[claim.clone()].trim().is_empty());
+    claims
+}
+
+/// Returns the lowercased words of the text, along with their ranges.
+fn words(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (ix, ch) in text.char_indices().chain([(text.len(), ' ')]) {
+        let is_word_char = ch.is_alphanumeric() || ch == '_';
+        match (start, is_word_char) {
+            (None, true) => start = Some(ix),
+            (Some(word_start), false) => {
+                words.push((word_start..ix, text[word_start..ix].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+fn shingle_key(window: &[(Range<usize>, String)]) -> String {
+    window
+        .iter()
+        .map(|(_, word)| word.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Maps each shingle of the source to the range of its first occurrence.
+fn shingle_index(source: &str) -> HashMap<String, Range<usize>> {
+    let mut index = HashMap::default();
+    for window in words(source).windows(SHINGLE_LEN) {
+        let range = window[0].0.start..window[SHINGLE_LEN - 1].0.end;
+        index.entry(shingle_key(window)).or_insert(range);
+    }
+    index
+}
+
+/// Returns the zero-based row in the original file of the given offset into
+/// the output of a `/file` command, which starts with a fence such as
+/// "```rs src/main.rs:10-20".
+pub fn file_row(output: &str, offset: usize) -> Option<u32> {
+    let (fence, _) = output.split_once('\n')?;
+    if !fence.starts_with("```") {
+        return None;
+    }
+    let first_row = fence
+        .rsplit_once(':')
+        .and_then(|(_, rows)| rows.split_once('-'))
+        .and_then(|(start, _)| start.parse::<u32>().ok())
+        .map_or(0, |start| start.saturating_sub(1));
+    let rows_into_content = output[..offset.min(output.len())]
+        .matches('\n')
+        .count()
+        .checked_sub(1)?;
+    Some(first_row + rows_into_content as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_find_citations() {
+        let readme = indoc! {"
+            # Retries
+            Requests are retried up to three times with exponential backoff between attempts.
+            The backoff starts at 100 milliseconds.
+        "};
+        let code = "fn parse_config(path: &Path) -> Result<Config> { todo!() }\n";
+        let message = indoc! {"
+            Failed requests are retried up to three times with exponential backoff. That keeps things simple.
+            ```rust
+            fn parse_config(path: &Path) -> Result<Config> {
+            ```
+        "};
+
+        let citations = find_citations(message, &[readme, code]);
+        assert_eq!(citations.len(), 2);
+
+        assert_eq!(
+            &message[citations[0].claim.clone()],
+            "Failed requests are retried up to three times with exponential backoff."
+        );
+        assert_eq!(citations[0].source_ix, 0);
+        assert_eq!(
+            &readme[citations[0].source_range.clone()],
+            "Requests are retried up to three times with exponential backoff"
+        );
+
+        assert_eq!(
+            &message[citations[1].claim.clone()],
+            "fn parse_config(path: &Path) -> Result<Config> {"
+        );
+        assert_eq!(citations[1].source_ix, 1);
+
+        assert!(find_citations("Nothing here is from the sources at all.", &[readme]).is_empty());
+    }
+
+    #[test]
+    fn test_file_row() {
+        let output = "```rs src/main.rs:10-12\nfn a() {}\nfn b() {}\n```";
+        assert_eq!(file_row(output, output.find("fn b").unwrap()), Some(10));
+        let output = "```rs src/main.rs\nfn a() {}\n";
+        assert_eq!(file_row(output, output.find("fn a").unwrap()), Some(0));
+        assert_eq!(file_row("no fence\ntext", 10), None);
+    }
+}
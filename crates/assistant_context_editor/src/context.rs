@@ -1,42 +1,48 @@
 #[cfg(test)]
 mod context_tests;
 
-use agent_settings::AgentSettings;
-use anyhow::{Context as _, Result, bail};
+use agent_settings::{AgentSettings, LanguageModelSelection};
+use anyhow::{Context as _, Result, anyhow, bail};
 use assistant_slash_command::{
-    SlashCommandContent, SlashCommandEvent, SlashCommandLine, SlashCommandOutputSection,
-    SlashCommandResult, SlashCommandWorkingSet,
+    SlashCommandContent, SlashCommandEvent, SlashCommandLine, SlashCommandOutput,
+    SlashCommandOutputSection, SlashCommandResult, SlashCommandWorkingSet,
 };
-use assistant_slash_commands::FileCommandMetadata;
+use assistant_slash_commands::{FileCommandMetadata, PdfCommandMetadata};
 use client::{self, proto, telemetry::Telemetry};
 use clock::ReplicaId;
-use collections::{HashMap, HashSet};
-use fs::{Fs, RemoveOptions};
+use collections::{BTreeMap, HashMap, HashSet};
 use futures::{FutureExt, StreamExt, future::Shared};
 use gpui::{
     App, AppContext as _, Context, Entity, EventEmitter, RenderImage, SharedString, Subscription,
     Task,
 };
-use language::{AnchorRangeExt, Bias, Buffer, LanguageRegistry, OffsetRangeExt, Point, ToOffset};
+use language::{
+    AnchorRangeExt, Bias, Buffer, LanguageName, LanguageRegistry, OffsetRangeExt, Point, ToOffset,
+    TransactionId,
+};
 use language_model::{
-    LanguageModel, LanguageModelCacheConfiguration, LanguageModelCompletionEvent,
-    LanguageModelImage, LanguageModelRegistry, LanguageModelRequest, LanguageModelRequestMessage,
-    LanguageModelToolUseId, MessageContent, PaymentRequiredError, Role, StopReason,
-    report_assistant_event,
+    ConfiguredModel, LanguageModel, LanguageModelCacheConfiguration, LanguageModelCompletionError,
+    LanguageModelCompletionEvent, LanguageModelDocument, LanguageModelError, LanguageModelImage,
+    LanguageModelProviderId, LanguageModelRegistry, LanguageModelRequest,
+    LanguageModelRequestMessage, LanguageModelToolUseId, MaxMonthlySpendReachedError,
+    MessageContent, PaymentRequiredError, RequestPriority, Role, SpendTracker, StopReason,
+    TokenUsage, WebSource, report_assistant_event,
 };
 use open_ai::Model as OpenAiModel;
-use paths::contexts_dir;
 use project::Project;
 use prompt_store::PromptBuilder;
+use semantic_index::{Embedding, SemanticDb, TextToEmbed};
 use serde::{Deserialize, Serialize};
 use settings::Settings;
 use smallvec::SmallVec;
 use std::{
     cmp::{Ordering, max},
+    collections::VecDeque,
     fmt::{Debug, Write as _},
     iter, mem,
-    ops::Range,
-    path::Path,
+    ops::{Add, Range},
+    path::{Path, PathBuf},
+    slice,
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -47,6 +53,19 @@ use util::{ResultExt, TryFutureExt, post_inc};
 use uuid::Uuid;
 use zed_llm_client::CompletionIntent;
 
+use crate::context_assembly::{
+    self, AssemblyCandidate, AssemblyEntry, AssemblyReport, AssemblyStrategy, Inclusion,
+};
+use crate::context_sync;
+use crate::cost_tracker::CostTracker;
+use crate::event_log::{EventLog, EventLogs};
+use crate::post_processing::{self, PostProcessingChange, PostProcessor};
+use crate::prompt_compression::{self, COMPRESSION_THRESHOLD};
+use crate::stack_trace::StackTraceTag;
+use crate::stop_condition::{StopCondition, StopMatcher};
+use crate::streaming_markdown::StreamingMarkdown;
+use crate::text_threads_database::TextThreadsDatabase;
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct ContextId(String);
 
@@ -160,6 +179,18 @@ pub enum ContextOperation {
         section: ThoughtProcessOutputSection<language::Anchor>,
         version: clock::Global,
     },
+    UpdateReaction {
+        message_id: MessageId,
+        reaction: SharedString,
+        author: SharedString,
+        added: bool,
+        timestamp: clock::Lamport,
+        version: clock::Global,
+    },
+    AddComment {
+        comment: MessageComment,
+        version: clock::Global,
+    },
     BufferOperation(language::Operation),
 }
 
@@ -186,6 +217,13 @@ impl ContextOperation {
                         ),
                         timestamp: id.0,
                         cache: None,
+                        usage: None,
+                        substitution: None,
+                        seed: None,
+                        post_processing: Vec::new(),
+                        web_sources: Vec::new(),
+                        stack_trace: None,
+                        pinned: message.pinned,
                     },
                     version: language::proto::deserialize_version(&insert.version),
                 })
@@ -201,6 +239,13 @@ impl ContextOperation {
                         update.timestamp.context("invalid timestamp")?,
                     ),
                     cache: None,
+                    usage: None,
+                    substitution: None,
+                    seed: None,
+                    post_processing: Vec::new(),
+                    web_sources: Vec::new(),
+                    stack_trace: None,
+                    pinned: update.pinned,
                 },
                 version: language::proto::deserialize_version(&update.version),
             }),
@@ -271,6 +316,39 @@ impl ContextOperation {
                     version: language::proto::deserialize_version(&message.version),
                 })
             }
+            proto::context_operation::Variant::UpdateReaction(message) => {
+                Ok(Self::UpdateReaction {
+                    message_id: MessageId(language::proto::deserialize_timestamp(
+                        message.message_id.context("invalid message id")?,
+                    )),
+                    reaction: message.reaction.into(),
+                    author: message.author.into(),
+                    added: message.added,
+                    timestamp: language::proto::deserialize_timestamp(
+                        message.timestamp.context("missing timestamp")?,
+                    ),
+                    version: language::proto::deserialize_version(&message.version),
+                })
+            }
+            proto::context_operation::Variant::AddComment(message) => Ok(Self::AddComment {
+                comment: MessageComment {
+                    id: CommentId(language::proto::deserialize_timestamp(
+                        message.id.context("invalid id")?,
+                    )),
+                    message_id: MessageId(language::proto::deserialize_timestamp(
+                        message.message_id.context("invalid message id")?,
+                    )),
+                    range: language::proto::deserialize_anchor_range(
+                        message.range.context("invalid range")?,
+                    )?,
+                    parent_id: message
+                        .parent_id
+                        .map(|id| CommentId(language::proto::deserialize_timestamp(id))),
+                    author: message.author.into(),
+                    text: message.text.into(),
+                },
+                version: language::proto::deserialize_version(&message.version),
+            }),
             proto::context_operation::Variant::BufferOperation(op) => Ok(Self::BufferOperation(
                 language::proto::deserialize_operation(
                     op.operation.context("invalid buffer operation")?,
@@ -293,6 +371,7 @@ impl ContextOperation {
                             start: Some(language::proto::serialize_anchor(&anchor.start)),
                             role: metadata.role.to_proto() as i32,
                             status: Some(metadata.status.to_proto()),
+                            pinned: metadata.pinned,
                         }),
                         version: language::proto::serialize_version(version),
                     },
@@ -310,6 +389,7 @@ impl ContextOperation {
                         status: Some(metadata.status.to_proto()),
                         timestamp: Some(language::proto::serialize_timestamp(metadata.timestamp)),
                         version: language::proto::serialize_version(version),
+                        pinned: metadata.pinned,
                     },
                 )),
             },
@@ -403,6 +483,44 @@ impl ContextOperation {
                     ),
                 ),
             },
+            Self::UpdateReaction {
+                message_id,
+                reaction,
+                author,
+                added,
+                timestamp,
+                version,
+            } => proto::ContextOperation {
+                variant: Some(proto::context_operation::Variant::UpdateReaction(
+                    proto::context_operation::UpdateReaction {
+                        message_id: Some(language::proto::serialize_timestamp(message_id.0)),
+                        reaction: reaction.to_string(),
+                        author: author.to_string(),
+                        added: *added,
+                        timestamp: Some(language::proto::serialize_timestamp(*timestamp)),
+                        version: language::proto::serialize_version(version),
+                    },
+                )),
+            },
+            Self::AddComment { comment, version } => proto::ContextOperation {
+                variant: Some(proto::context_operation::Variant::AddComment(
+                    proto::context_operation::AddComment {
+                        id: Some(language::proto::serialize_timestamp(comment.id.0)),
+                        message_id: Some(language::proto::serialize_timestamp(
+                            comment.message_id.0,
+                        )),
+                        range: Some(language::proto::serialize_anchor_range(
+                            comment.range.clone(),
+                        )),
+                        parent_id: comment
+                            .parent_id
+                            .map(|id| language::proto::serialize_timestamp(id.0)),
+                        author: comment.author.to_string(),
+                        text: comment.text.to_string(),
+                        version: language::proto::serialize_version(version),
+                    },
+                )),
+            },
             Self::BufferOperation(operation) => proto::ContextOperation {
                 variant: Some(proto::context_operation::Variant::BufferOperation(
                     proto::context_operation::BufferOperation {
@@ -421,7 +539,9 @@ impl ContextOperation {
             Self::SlashCommandStarted { id, .. } => id.0,
             Self::SlashCommandOutputSectionAdded { timestamp, .. }
             | Self::SlashCommandFinished { timestamp, .. }
-            | Self::ThoughtProcessOutputSectionAdded { timestamp, .. } => *timestamp,
+            | Self::ThoughtProcessOutputSectionAdded { timestamp, .. }
+            | Self::UpdateReaction { timestamp, .. } => *timestamp,
+            Self::AddComment { comment, .. } => comment.id.0,
             Self::BufferOperation(_) => {
                 panic!("reading the timestamp of a buffer operation is not supported")
             }
@@ -437,7 +557,9 @@ impl ContextOperation {
             | Self::SlashCommandStarted { version, .. }
             | Self::SlashCommandOutputSectionAdded { version, .. }
             | Self::SlashCommandFinished { version, .. }
-            | Self::ThoughtProcessOutputSectionAdded { version, .. } => version,
+            | Self::ThoughtProcessOutputSectionAdded { version, .. }
+            | Self::UpdateReaction { version, .. }
+            | Self::AddComment { version, .. } => version,
             Self::BufferOperation(_) => {
                 panic!("reading the version of a buffer operation is not supported")
             }
@@ -449,6 +571,8 @@ impl ContextOperation {
 pub enum ContextEvent {
     ShowAssistError(SharedString),
     ShowPaymentRequiredError,
+    ShowMaxMonthlySpendReachedError(MaxMonthlySpendReachedError),
+    ShowModelError(LanguageModelError),
     MessagesEdited,
     SummaryChanged,
     SummaryGenerated,
@@ -465,6 +589,24 @@ pub enum ContextEvent {
     SlashCommandOutputSectionAdded {
         section: SlashCommandOutputSection<language::Anchor>,
     },
+    OperationHistoryChanged,
+    MessageAnnotationsChanged,
+    ContinuationSeamsChanged,
+    PendingOutputPagesChanged,
+    /// A response finished streaming, failed or was cancelled, with the
+    /// tokens it used when they're known.
+    CompletionFinished {
+        assistant_message_id: MessageId,
+        usage: Option<MessageUsage>,
+    },
+    /// The model's provider failed transiently and is sending the request
+    /// again after a delay.
+    RetryingCompletion {
+        attempt: u32,
+        max_attempts: u32,
+        delay: Duration,
+        message: String,
+    },
     Operation(ContextOperation),
 }
 
@@ -557,13 +699,129 @@ pub struct MessageCacheMetadata {
     pub cached_at: clock::Global,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+/// Token usage and cost of generating an assistant message.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageUsage {
+    pub token_usage: TokenUsage,
+    /// Whether the token counts were estimated locally because the provider
+    /// didn't report them.
+    #[serde(default)]
+    pub estimated: bool,
+    /// Cost in US dollars, when the model's pricing is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    /// The input tokens counted locally before the request was sent, kept to
+    /// compare with the count the provider reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_input_tokens: Option<u32>,
+}
+
+/// How far, as a fraction of the reported count, the local count of input
+/// tokens can be from it before it's flagged as drift.
+const TOKEN_DRIFT_THRESHOLD: f64 = 0.25;
+
+impl MessageUsage {
+    /// How far the local count of input tokens was from the count the
+    /// provider reported, as a fraction of the reported count.
+    pub fn input_token_drift(&self) -> Option<f64> {
+        if self.estimated {
+            return None;
+        }
+        let estimated = self.estimated_input_tokens? as f64;
+        let reported = self.input_tokens() as f64;
+        (reported > 0.).then(|| (estimated - reported) / reported)
+    }
+
+    /// All input tokens, whether they were read from or written to the cache.
+    pub fn input_tokens(&self) -> u32 {
+        self.token_usage.input_tokens
+            + self.token_usage.cache_creation_input_tokens
+            + self.token_usage.cache_read_input_tokens
+    }
+
+    /// Whether the local count of input tokens was far enough off to suggest
+    /// that the local tokenizer doesn't match the model's.
+    pub fn has_token_drift(&self) -> bool {
+        self.input_token_drift()
+            .is_some_and(|drift| drift.abs() > TOKEN_DRIFT_THRESHOLD)
+    }
+}
+
+impl Add for MessageUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            token_usage: self.token_usage + other.token_usage,
+            estimated: self.estimated || other.estimated,
+            cost: match (self.cost, other.cost) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            },
+            estimated_input_tokens: match (
+                self.estimated_input_tokens,
+                other.estimated_input_tokens,
+            ) {
+                (Some(a), Some(b)) => Some(a + b),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Why a free model generated an assistant message in place of the selected model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelSubstitutionReason {
+    MaxMonthlySpendReached,
+    RateLimitExceeded,
+}
+
+impl ModelSubstitutionReason {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::MaxMonthlySpendReached => "monthly spending limit reached",
+            Self::RateLimitExceeded => "rate limit exceeded",
+        }
+    }
+}
+
+/// A free model that generated an assistant message in place of the selected model.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelSubstitution {
+    /// The name of the model that was selected.
+    pub original_model: String,
+    /// The name of the model that generated the message.
+    pub substitute_model: String,
+    pub reason: ModelSubstitutionReason,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MessageMetadata {
     pub role: Role,
     pub status: MessageStatus,
     pub timestamp: clock::Lamport,
     #[serde(skip)]
     pub cache: Option<MessageCacheMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<MessageUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub substitution: Option<ModelSubstitution>,
+    /// The seed the response was requested with, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// What the text thread's post-processors changed in the response.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_processing: Vec<PostProcessingChange>,
+    /// The web pages the response cites, found by the provider's web search.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub web_sources: Vec<WebSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack_trace: Option<StackTraceTag>,
+    /// Whether the message is always sent in full, even when attachments
+    /// would otherwise be compressed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
 }
 
 impl From<&Message> for MessageMetadata {
@@ -573,6 +831,13 @@ impl From<&Message> for MessageMetadata {
             status: message.status.clone(),
             timestamp: message.id.0,
             cache: message.cache.clone(),
+            usage: message.usage,
+            substitution: message.substitution.clone(),
+            seed: message.seed,
+            post_processing: message.post_processing.clone(),
+            web_sources: message.web_sources.clone(),
+            stack_trace: message.stack_trace.clone(),
+            pinned: message.pinned,
         }
     }
 }
@@ -599,7 +864,7 @@ pub struct ThoughtProcessOutputSection<T> {
 }
 
 impl ThoughtProcessOutputSection<language::Anchor> {
-    pub fn is_valid(&self, buffer: &language::TextBuffer) -> bool {
+    pub fn is_valid(&self, buffer: &language::TextBufferSnapshot) -> bool {
         self.range.start.is_valid(buffer) && !self.range.to_offset(buffer).is_empty()
     }
 }
@@ -613,6 +878,47 @@ pub struct Message {
     pub role: Role,
     pub status: MessageStatus,
     pub cache: Option<MessageCacheMetadata>,
+    pub usage: Option<MessageUsage>,
+    pub substitution: Option<ModelSubstitution>,
+    pub seed: Option<u64>,
+    pub post_processing: Vec<PostProcessingChange>,
+    pub web_sources: Vec<WebSource>,
+    pub stack_trace: Option<StackTraceTag>,
+    pub pinned: bool,
+}
+
+/// Content attached to a context, such as the output of a slash command or an image.
+#[derive(Debug, Clone)]
+pub struct ContextAttachment {
+    pub range: Range<language::Anchor>,
+    pub icon: IconName,
+    pub label: SharedString,
+    pub kind: ContextAttachmentKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum ContextAttachmentKind {
+    SlashCommandOutput {
+        metadata: Option<serde_json::Value>,
+    },
+    Image {
+        image_id: u64,
+        image: Shared<Task<Option<LanguageModelImage>>>,
+    },
+}
+
+impl ContextAttachment {
+    /// Returns the path of the file this attachment was read from, if any.
+    pub fn file_path(&self) -> Option<String> {
+        match &self.kind {
+            ContextAttachmentKind::SlashCommandOutput {
+                metadata: Some(metadata),
+            } => serde_json::from_value::<FileCommandMetadata>(metadata.clone())
+                .ok()
+                .map(|metadata| metadata.path),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -648,12 +954,134 @@ impl Content {
 struct PendingCompletion {
     id: usize,
     assistant_message_id: MessageId,
+    /// The transaction that the response's edits are merged into.
+    transaction_id: Option<TransactionId>,
+    markdown: StreamingMarkdown,
     _task: Task<()>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryOperationKind {
+    InsertMessage,
+    AssistantResponse,
+    SlashCommand,
+}
+
+/// A high-level operation on a context, like an assistant response, whose
+/// edits are grouped in a single buffer transaction so they can be undone
+/// as a whole.
+#[derive(Clone, Debug)]
+pub struct OperationHistoryEntry {
+    pub kind: HistoryOperationKind,
+    pub label: SharedString,
+    pub transaction_id: TransactionId,
+}
+
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 pub struct InvokedSlashCommandId(clock::Lamport);
 
+/// The number of lines of a paginated slash command's output that are
+/// inserted at a time.
+const SLASH_COMMAND_OUTPUT_PAGE_LINES: usize = 200;
+
+/// How many times a response whose connection drops is resumed by another
+/// request before the error is shown.
+const MAX_STREAM_RESUMES: usize = 2;
+
+/// How long to wait before resuming a response whose connection dropped.
+const STREAM_RESUME_DELAY: Duration = Duration::from_secs(1);
+
+/// Attachments shorter than this cost too little to be worth flagging when
+/// they're attached twice.
+const MIN_DUPLICATE_ATTACHMENT_LEN: usize = 256;
+
+/// The pages of a slash command's output that haven't been inserted yet.
+#[derive(Clone, Debug)]
+pub struct PendingOutputPages {
+    /// Where the next page will be inserted.
+    pub end: language::Anchor,
+    pages: VecDeque<SlashCommandOutput>,
+}
+
+impl PendingOutputPages {
+    pub fn remaining_lines(&self) -> usize {
+        self.pages
+            .iter()
+            .map(|page| page.text.lines().count())
+            .sum()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct CommentId(clock::Lamport);
+
+impl CommentId {
+    pub fn as_u64(self) -> u64 {
+        self.0.as_u64()
+    }
+}
+
+/// A comment left by a collaborator on a range of a message.
+#[derive(Clone, Debug)]
+pub struct MessageComment {
+    pub id: CommentId,
+    pub message_id: MessageId,
+    pub range: Range<language::Anchor>,
+    /// The first comment of the thread this comment replies to, if any.
+    pub parent_id: Option<CommentId>,
+    pub author: SharedString,
+    pub text: SharedString,
+}
+
+/// The collaborators who reacted to a message with the same reaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageReaction {
+    pub reaction: SharedString,
+    pub authors: Vec<SharedString>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ReactionState {
+    added: bool,
+    timestamp: clock::Lamport,
+}
+
+/// The kind of change a text thread is saved after. Each kind is debounced
+/// separately, so that typing keeps pushing back the saves of its edits
+/// without holding up the saves of other changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SaveReason {
+    /// Its text or messages were edited.
+    Edit,
+    /// Its title or one of its settings, such as its model, changed.
+    Metadata,
+    /// The user asked for it to be saved.
+    Explicit,
+}
+
+impl SaveReason {
+    fn debounce(self) -> Duration {
+        match self {
+            SaveReason::Edit => Duration::from_secs(2),
+            SaveReason::Metadata => Duration::from_millis(500),
+            SaveReason::Explicit => Duration::ZERO,
+        }
+    }
+}
+
+/// How much of a saved text thread's text is loaded at a time when it's
+/// opened progressively.
+const LOAD_CHUNK_LEN: usize = 64 * 1024;
+
+/// How long to wait after an edit before embedding the messages for
+/// [`AssemblyStrategy::RelevanceRanked`], so that typing doesn't embed every
+/// keystroke.
+const MESSAGE_EMBEDDING_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// How much of each message is embedded, which keeps long messages within
+/// the embedding model's input limit.
+const MAX_EMBEDDED_MESSAGE_LEN: usize = 8 * 1024;
+
 pub struct AssistantContext {
     id: ContextId,
     timestamp: clock::Lamport,
@@ -663,12 +1091,25 @@ pub struct AssistantContext {
     buffer: Entity<Buffer>,
     parsed_slash_commands: Vec<ParsedSlashCommand>,
     invoked_slash_commands: HashMap<InvokedSlashCommandId, InvokedSlashCommand>,
+    pending_output_pages: HashMap<InvokedSlashCommandId, PendingOutputPages>,
     edits_since_last_parse: language::Subscription,
     slash_commands: Arc<SlashCommandWorkingSet>,
     slash_command_output_sections: Vec<SlashCommandOutputSection<language::Anchor>>,
     thought_process_output_sections: Vec<ThoughtProcessOutputSection<language::Anchor>>,
+    continuation_seams: Vec<language::Anchor>,
+    /// The attachments that are sent as they are, even though they are large
+    /// enough to be compressed.
+    uncompressed_attachments: HashSet<Range<language::Anchor>>,
+    /// The attachments that are always sent in full, in addition to the ones
+    /// in pinned messages.
+    pinned_attachments: HashSet<Range<language::Anchor>>,
+    reactions: BTreeMap<(MessageId, SharedString, SharedString), ReactionState>,
+    comments: Vec<MessageComment>,
     message_anchors: Vec<MessageAnchor>,
     contents: Vec<Content>,
+    /// The PDFs attached with `/pdf`, by path, loaded in the background so
+    /// that they're ready when a request is assembled.
+    documents: HashMap<PathBuf, Shared<Task<Option<LanguageModelDocument>>>>,
     messages_metadata: HashMap<MessageId, MessageMetadata>,
     summary: ContextSummary,
     summary_task: Task<Option<()>>,
@@ -677,6 +1118,10 @@ pub struct AssistantContext {
     token_count: Option<usize>,
     pending_token_count: Task<Option<()>>,
     pending_save: Task<Result<()>>,
+    /// When the pending save should happen for each kind of change that's
+    /// waiting to be saved.
+    save_deadlines: HashMap<SaveReason, Instant>,
+    pending_load: Option<Task<Result<()>>>,
     pending_cache_warming_task: Task<Option<()>>,
     path: Option<Arc<Path>>,
     _subscriptions: Vec<Subscription>,
@@ -685,6 +1130,35 @@ pub struct AssistantContext {
     project: Option<Entity<Project>>,
     prompt_builder: Arc<PromptBuilder>,
     completion_mode: agent_settings::CompletionMode,
+    assembly_strategy: AssemblyStrategy,
+    /// The model the text thread uses instead of the default one.
+    model: Option<LanguageModelSelection>,
+    /// The environment variables passed to the slash commands it runs.
+    environment: BTreeMap<String, String>,
+    /// The language the model is asked to answer in, instead of the one in
+    /// the settings.
+    answer_language: Option<String>,
+    /// The seed sent with requests instead of the one in the settings.
+    seed: Option<u64>,
+    /// The patterns that end a response once its text matches one of them.
+    stop_conditions: Vec<StopCondition>,
+    /// The post-processors run on completed responses.
+    post_processors: Vec<PostProcessor>,
+    /// Whether the model may search the web with its provider's search tool.
+    web_search: bool,
+    /// The tokens used by every response and their cost, including the
+    /// responses that were since regenerated or deleted.
+    cost_tracker: CostTracker,
+    /// The events streamed from the model for the most recent responses.
+    event_logs: EventLogs,
+    /// Which messages were sent in the last response's request.
+    last_assembly: Option<AssemblyReport>,
+    /// The embeddings of the messages' text, by its digest, which rank them
+    /// when they're assembled with [`AssemblyStrategy::RelevanceRanked`].
+    message_embeddings: HashMap<[u8; 32], Embedding>,
+    pending_message_embeddings: Task<Option<()>>,
+    operation_history: Vec<OperationHistoryEntry>,
+    undone_operations: Vec<OperationHistoryEntry>,
 }
 
 trait ContextAnnotation {
@@ -729,6 +1203,269 @@ impl AssistantContext {
         self.completion_mode = completion_mode;
     }
 
+    pub fn assembly_strategy(&self) -> AssemblyStrategy {
+        self.assembly_strategy
+    }
+
+    pub fn set_assembly_strategy(&mut self, strategy: AssemblyStrategy, cx: &mut Context<Self>) {
+        self.assembly_strategy = strategy;
+        self.count_remaining_tokens(cx);
+        self.save(SaveReason::Metadata, cx);
+        cx.notify();
+    }
+
+    pub fn last_assembly(&self) -> Option<&AssemblyReport> {
+        self.last_assembly.as_ref()
+    }
+
+    /// Returns the model the text thread is sent to: its own model when it
+    /// has one that's still available, and the default model otherwise.
+    pub fn model(&self, cx: &App) -> Option<ConfiguredModel> {
+        let registry = LanguageModelRegistry::read_global(cx);
+        self.model
+            .as_ref()
+            .and_then(|selection| {
+                let provider = registry.provider(&LanguageModelProviderId(
+                    selection.provider.0.clone().into(),
+                ))?;
+                let model = provider
+                    .provided_models(cx)
+                    .into_iter()
+                    .find(|model| model.id().0 == selection.model)?;
+                Some(ConfiguredModel { provider, model })
+            })
+            .or_else(|| registry.default_model())
+    }
+
+    /// Whether the text thread uses its own model instead of the default one.
+    pub fn has_own_model(&self) -> bool {
+        self.model.is_some()
+    }
+
+    pub fn set_model(&mut self, model: &Arc<dyn LanguageModel>, cx: &mut Context<Self>) {
+        self.model = Some(model_selection(model));
+        self.count_remaining_tokens(cx);
+        self.save(SaveReason::Metadata, cx);
+        cx.notify();
+    }
+
+    pub fn environment(&self) -> &BTreeMap<String, String> {
+        &self.environment
+    }
+
+    /// Sets an environment variable, replacing its value if it's already set.
+    pub fn set_environment_variable(
+        &mut self,
+        name: String,
+        value: String,
+        cx: &mut Context<Self>,
+    ) {
+        self.environment.insert(name, value);
+        self.save(SaveReason::Metadata, cx);
+        cx.notify();
+    }
+
+    pub fn remove_environment_variable(&mut self, name: &str, cx: &mut Context<Self>) {
+        if self.environment.remove(name).is_some() {
+            self.save(SaveReason::Metadata, cx);
+            cx.notify();
+        }
+    }
+
+    pub fn stop_conditions(&self) -> &[StopCondition] {
+        &self.stop_conditions
+    }
+
+    pub fn add_stop_condition(&mut self, condition: StopCondition, cx: &mut Context<Self>) {
+        if self.stop_conditions.contains(&condition) {
+            return;
+        }
+        self.stop_conditions.push(condition);
+        self.save(SaveReason::Metadata, cx);
+        cx.notify();
+    }
+
+    pub fn remove_stop_condition(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if ix < self.stop_conditions.len() {
+            self.stop_conditions.remove(ix);
+            self.save(SaveReason::Metadata, cx);
+            cx.notify();
+        }
+    }
+
+    pub fn post_processors(&self) -> &[PostProcessor] {
+        &self.post_processors
+    }
+
+    pub fn toggle_post_processor(&mut self, processor: PostProcessor, cx: &mut Context<Self>) {
+        if let Some(ix) = self.post_processors.iter().position(|p| *p == processor) {
+            self.post_processors.remove(ix);
+        } else {
+            self.post_processors.push(processor);
+        }
+        self.save(SaveReason::Metadata, cx);
+        cx.notify();
+    }
+
+    pub fn web_search(&self) -> bool {
+        self.web_search
+    }
+
+    pub fn toggle_web_search(&mut self, cx: &mut Context<Self>) {
+        self.web_search = !self.web_search;
+        self.save(SaveReason::Metadata, cx);
+        cx.notify();
+    }
+
+    pub fn cost_tracker(&self) -> &CostTracker {
+        &self.cost_tracker
+    }
+
+    /// Runs the post-processors on a completed response, as part of the
+    /// response's transaction so that undoing it undoes them too. The
+    /// response is left alone if the text thread is edited in the meantime.
+    fn post_process_message(
+        &mut self,
+        message_id: MessageId,
+        transaction_id: Option<TransactionId>,
+        cx: &mut Context<Self>,
+    ) {
+        if self.post_processors.is_empty() {
+            return;
+        }
+        let Some(message) = self.messages(cx).find(|message| message.id == message_id) else {
+            return;
+        };
+        let buffer = self.buffer.read(cx);
+        let mut range = message.offset_range;
+        let mut text = buffer.text_for_range(range.clone()).collect::<String>();
+        // Leave the newline that separates the message from the next one.
+        if text.ends_with('\n') {
+            text.pop();
+            range.end -= 1;
+        }
+        let version = buffer.version();
+        let processors = self.post_processors.clone();
+        cx.spawn(async move |this, cx| {
+            let (new_text, changes) = cx
+                .background_spawn({
+                    let text = text.clone();
+                    async move { post_processing::run(&processors, text).await }
+                })
+                .await;
+            if changes.is_empty() {
+                return anyhow::Ok(());
+            }
+            let edits = language::text_diff(&text, &new_text);
+            this.update(cx, |this, cx| {
+                if this.buffer.read(cx).has_edits_since(&version) {
+                    return;
+                }
+                this.buffer.update(cx, |buffer, cx| {
+                    extend_transaction(buffer, transaction_id, cx, |buffer, cx| {
+                        buffer.edit(
+                            edits.into_iter().map(|(edit_range, text)| {
+                                (
+                                    range.start + edit_range.start..range.start + edit_range.end,
+                                    text,
+                                )
+                            }),
+                            None,
+                            cx,
+                        );
+                    });
+                });
+                this.update_metadata(message_id, cx, |metadata| {
+                    metadata.post_processing = changes;
+                });
+            })
+        })
+        .detach_and_log_err(cx);
+    }
+
+    /// Returns the language the model is asked to answer in: the text
+    /// thread's own language if it has one, and the one in the settings
+    /// otherwise.
+    pub fn answer_language(&self, cx: &App) -> Option<String> {
+        self.answer_language
+            .clone()
+            .or_else(|| AgentSettings::get_global(cx).answer_language.clone())
+            .filter(|language| !language.trim().is_empty())
+    }
+
+    /// Whether the text thread has its own answer language instead of the
+    /// one in the settings.
+    pub fn has_own_answer_language(&self) -> bool {
+        self.answer_language.is_some()
+    }
+
+    pub fn set_answer_language(&mut self, language: Option<String>, cx: &mut Context<Self>) {
+        self.answer_language = language;
+        self.count_remaining_tokens(cx);
+        self.save(SaveReason::Metadata, cx);
+        cx.notify();
+    }
+
+    /// Returns the seed sent with requests to the model: the text thread's
+    /// own seed if it has one, and the one in the settings otherwise. Models
+    /// that don't support seeds aren't sent one.
+    pub fn seed(&self, model: &Arc<dyn LanguageModel>, cx: &App) -> Option<u64> {
+        if !model.supports_seed() {
+            return None;
+        }
+        self.seed
+            .or_else(|| AgentSettings::seed_for_model(model, cx))
+    }
+
+    /// The text thread's own seed, if it has one.
+    pub fn own_seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: Option<u64>, cx: &mut Context<Self>) {
+        self.seed = seed;
+        self.save(SaveReason::Metadata, cx);
+        cx.notify();
+    }
+
+    /// Returns the events streamed from the model for the response in the
+    /// message, if it's one of the most recent responses.
+    pub fn event_log(&self, message_id: MessageId) -> Option<&EventLog> {
+        self.event_logs.get(message_id)
+    }
+
+    /// Saves a copy of the text thread, up to and including the given
+    /// message, that uses the given model. Returns the path of the copy.
+    pub fn duplicate(
+        &self,
+        up_to: MessageId,
+        model: &Arc<dyn LanguageModel>,
+        cx: &App,
+    ) -> Task<Result<Arc<Path>>> {
+        let mut saved_context = self.serialize(cx);
+        saved_context.id = None;
+        saved_context.truncate_after(up_to);
+        saved_context.model = Some(model_selection(model));
+        let title = format!("{} ({})", self.summary.or_default(), model.name().0);
+        saved_context.summary = title.clone();
+
+        let compaction_threshold = AgentSettings::get_global(cx)
+            .text_thread_retention
+            .compact_outputs_larger_than_kb;
+        let database = TextThreadsDatabase::global_future(cx);
+        cx.background_spawn(async move {
+            let database = database.await.map_err(|err| anyhow!(err))?;
+            database
+                .save(
+                    None,
+                    title,
+                    saved_context,
+                    compaction_threshold.map(|kb| kb as usize * 1024),
+                )
+                .await
+        })
+    }
+
     pub fn new(
         id: ContextId,
         replica_id: ReplicaId,
@@ -760,11 +1497,18 @@ impl AssistantContext {
             operations: Vec::new(),
             message_anchors: Default::default(),
             contents: Default::default(),
+            documents: HashMap::default(),
             messages_metadata: Default::default(),
             parsed_slash_commands: Vec::new(),
             invoked_slash_commands: HashMap::default(),
+            pending_output_pages: HashMap::default(),
             slash_command_output_sections: Vec::new(),
             thought_process_output_sections: Vec::new(),
+            continuation_seams: Vec::new(),
+            uncompressed_attachments: HashSet::default(),
+            pinned_attachments: HashSet::default(),
+            reactions: BTreeMap::default(),
+            comments: Vec::new(),
             edits_since_last_parse: edits_since_last_slash_command_parse,
             summary: ContextSummary::Pending,
             summary_task: Task::ready(None),
@@ -775,7 +1519,24 @@ impl AssistantContext {
             pending_cache_warming_task: Task::ready(None),
             _subscriptions: vec![cx.subscribe(&buffer, Self::handle_buffer_event)],
             pending_save: Task::ready(Ok(())),
+            save_deadlines: HashMap::default(),
+            pending_load: None,
             completion_mode: AgentSettings::get_global(cx).preferred_completion_mode,
+            assembly_strategy: AssemblyStrategy::default(),
+            model: None,
+            environment: BTreeMap::default(),
+            answer_language: None,
+            seed: None,
+            stop_conditions: Vec::new(),
+            post_processors: Vec::new(),
+            web_search: false,
+            cost_tracker: CostTracker::default(),
+            event_logs: EventLogs::default(),
+            last_assembly: None,
+            message_embeddings: HashMap::default(),
+            pending_message_embeddings: Task::ready(None),
+            operation_history: Vec::new(),
+            undone_operations: Vec::new(),
             path: None,
             buffer,
             telemetry,
@@ -800,6 +1561,13 @@ impl AssistantContext {
                 status: MessageStatus::Done,
                 timestamp: first_message_id.0,
                 cache: None,
+                usage: None,
+                substitution: None,
+                seed: None,
+                post_processing: Vec::new(),
+                web_sources: Vec::new(),
+                stack_trace: None,
+                pinned: false,
             },
         );
         this.message_anchors.push(message);
@@ -810,55 +1578,106 @@ impl AssistantContext {
     }
 
     pub(crate) fn serialize(&self, cx: &App) -> SavedContext {
-        let buffer = self.buffer.read(cx);
-        SavedContext {
-            id: Some(self.id.clone()),
+        self.serializer(cx)()
+    }
+
+    /// Serializes the text thread on the background executor, since
+    /// building the text of a large one takes long enough to be noticed.
+    fn serialize_in_background(&self, cx: &App) -> Task<SavedContext> {
+        let serialize = self.serializer(cx);
+        cx.background_spawn(async move { serialize() })
+    }
+
+    /// Captures what's needed to serialize the text thread, returning a
+    /// function that serializes it without access to the app.
+    fn serializer(&self, cx: &App) -> impl FnOnce() -> SavedContext + Send + 'static {
+        let buffer = self.buffer.read(cx).text_snapshot();
+        let id = self.id.clone();
+        let messages = self
+            .messages(cx)
+            .map(|message| SavedMessage {
+                id: message.id,
+                start: message.offset_range.start,
+                metadata: self.messages_metadata[&message.id].clone(),
+            })
+            .collect();
+        let summary = self
+            .summary
+            .content()
+            .map(|summary| summary.text.clone())
+            .unwrap_or_default();
+        let slash_command_output_sections = self.slash_command_output_sections.clone();
+        let thought_process_output_sections = self.thought_process_output_sections.clone();
+        let continuation_seams = self.continuation_seams.clone();
+        let assembly_strategy = self.assembly_strategy;
+        let project = self.project.as_ref().and_then(|project| {
+            let names = project
+                .read(cx)
+                .visible_worktrees(cx)
+                .map(|worktree| worktree.read(cx).root_name().to_string())
+                .collect::<Vec<_>>();
+            (!names.is_empty()).then(|| names.join(", "))
+        });
+        let model = self.model.clone();
+        let environment = self.environment.clone();
+        let answer_language = self.answer_language.clone();
+        let seed = self.seed;
+        let stop_conditions = self.stop_conditions.clone();
+        let post_processors = self.post_processors.clone();
+        let web_search = self.web_search;
+        let cost_tracker = self.cost_tracker;
+
+        move || SavedContext {
+            id: Some(id),
             zed: "context".into(),
             version: SavedContext::VERSION.into(),
             text: buffer.text(),
-            messages: self
-                .messages(cx)
-                .map(|message| SavedMessage {
-                    id: message.id,
-                    start: message.offset_range.start,
-                    metadata: self.messages_metadata[&message.id].clone(),
-                })
-                .collect(),
-            summary: self
-                .summary
-                .content()
-                .map(|summary| summary.text.clone())
-                .unwrap_or_default(),
-            slash_command_output_sections: self
-                .slash_command_output_sections
-                .iter()
+            messages,
+            summary,
+            slash_command_output_sections: slash_command_output_sections
+                .into_iter()
                 .filter_map(|section| {
-                    if section.is_valid(buffer) {
-                        let range = section.range.to_offset(buffer);
+                    if section.is_valid(&buffer) {
+                        let range = section.range.to_offset(&buffer);
                         Some(assistant_slash_command::SlashCommandOutputSection {
                             range,
                             icon: section.icon,
-                            label: section.label.clone(),
-                            metadata: section.metadata.clone(),
+                            label: section.label,
+                            metadata: section.metadata,
                         })
                     } else {
                         None
                     }
                 })
                 .collect(),
-            thought_process_output_sections: self
-                .thought_process_output_sections
-                .iter()
+            thought_process_output_sections: thought_process_output_sections
+                .into_iter()
                 .filter_map(|section| {
-                    if section.is_valid(buffer) {
-                        let range = section.range.to_offset(buffer);
+                    if section.is_valid(&buffer) {
+                        let range = section.range.to_offset(&buffer);
                         Some(ThoughtProcessOutputSection { range })
                     } else {
                         None
                     }
                 })
                 .collect(),
-        }
+            continuation_seams: continuation_seams
+                .into_iter()
+                .filter(|seam| seam.is_valid(&buffer))
+                .map(|seam| seam.to_offset(&buffer))
+                .collect(),
+            compacted_sections: Vec::new(),
+            assembly_strategy,
+            project,
+            model,
+            environment,
+            answer_language,
+            seed,
+            stop_conditions,
+            post_processors,
+            web_search,
+            cost_tracker,
+        }
     }
 
     pub fn deserialize(
@@ -870,6 +1689,88 @@ impl AssistantContext {
         project: Option<Entity<Project>>,
         telemetry: Option<Arc<Telemetry>>,
         cx: &mut Context<Self>,
+    ) -> Self {
+        let mut this = Self::for_saved_context(
+            &saved_context,
+            path,
+            language_registry,
+            prompt_builder,
+            slash_commands,
+            project,
+            telemetry,
+            cx,
+        );
+        let mut loader = SavedContextLoader::new(saved_context);
+        let operations = loader.load_messages(usize::MAX, &this.buffer, cx);
+        this.apply_ops(operations, cx);
+        this.finish_loading(loader, cx);
+        this
+    }
+
+    /// Deserializes a saved text thread a part of its text at a time,
+    /// showing its first messages right away instead of blocking until all
+    /// of it is loaded. It can't be edited or saved until it's fully loaded.
+    pub fn deserialize_progressively(
+        saved_context: SavedContext,
+        path: Arc<Path>,
+        language_registry: Arc<LanguageRegistry>,
+        prompt_builder: Arc<PromptBuilder>,
+        slash_commands: Arc<SlashCommandWorkingSet>,
+        project: Option<Entity<Project>>,
+        telemetry: Option<Arc<Telemetry>>,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let mut this = Self::for_saved_context(
+            &saved_context,
+            path,
+            language_registry,
+            prompt_builder,
+            slash_commands,
+            project,
+            telemetry,
+            cx,
+        );
+        let mut loader = SavedContextLoader::new(saved_context);
+        let operations = loader.load_messages(LOAD_CHUNK_LEN, &this.buffer, cx);
+        this.apply_ops(operations, cx);
+        if loader.is_done() {
+            this.finish_loading(loader, cx);
+            return this;
+        }
+
+        this.set_capability(language::Capability::ReadOnly, cx);
+        this.pending_load = Some(cx.spawn(async move |this, cx| {
+            loop {
+                smol::future::yield_now().await;
+                let done = this.update(cx, |this, cx| {
+                    let operations = loader.load_messages(LOAD_CHUNK_LEN, &this.buffer, cx);
+                    this.apply_ops(operations, cx);
+                    loader.is_done()
+                })?;
+                if done {
+                    break;
+                }
+            }
+
+            this.update(cx, |this, cx| {
+                this.finish_loading(loader, cx);
+                this.set_capability(language::Capability::ReadWrite, cx);
+                this.pending_load = None;
+                cx.emit(ContextEvent::ContinuationSeamsChanged);
+            })
+        }));
+        this
+    }
+
+    fn for_saved_context(
+        saved_context: &SavedContext,
+        path: Arc<Path>,
+        language_registry: Arc<LanguageRegistry>,
+        prompt_builder: Arc<PromptBuilder>,
+        slash_commands: Arc<SlashCommandWorkingSet>,
+        project: Option<Entity<Project>>,
+        telemetry: Option<Arc<Telemetry>>,
+        cx: &mut Context<Self>,
     ) -> Self {
         let id = saved_context.id.clone().unwrap_or_else(ContextId::new);
         let mut this = Self::new(
@@ -884,14 +1785,34 @@ impl AssistantContext {
             cx,
         );
         this.path = Some(path);
-        this.buffer.update(cx, |buffer, cx| {
-            buffer.set_text(saved_context.text.as_str(), cx)
-        });
-        let operations = saved_context.into_ops(&this.buffer, cx);
-        this.apply_ops(operations, cx);
+        this.assembly_strategy = saved_context.assembly_strategy;
+        this.model = saved_context.model.clone();
+        this.environment = saved_context.environment.clone();
+        this.answer_language = saved_context.answer_language.clone();
+        this.seed = saved_context.seed;
+        this.stop_conditions = saved_context.stop_conditions.clone();
+        this.post_processors = saved_context.post_processors.clone();
+        this.web_search = saved_context.web_search;
+        this.cost_tracker = saved_context.cost_tracker();
         this
     }
 
+    fn finish_loading(&mut self, loader: SavedContextLoader, cx: &mut Context<Self>) {
+        let (operations, continuation_seams) = loader.finish(&self.buffer, cx);
+        self.apply_ops(operations, cx);
+        let buffer = self.buffer.read(cx);
+        self.continuation_seams = continuation_seams
+            .into_iter()
+            .map(|offset| buffer.anchor_before(offset.min(buffer.len())))
+            .collect();
+    }
+
+    /// Whether the text thread is still being loaded, in which case it can't
+    /// be edited yet.
+    pub fn is_loading(&self) -> bool {
+        self.pending_load.is_some()
+    }
+
     pub fn id(&self) -> &ContextId {
         &self.id
     }
@@ -977,6 +1898,7 @@ impl AssistantContext {
     fn flush_ops(&mut self, cx: &mut Context<AssistantContext>) {
         let mut changed_messages = HashSet::default();
         let mut summary_generated = false;
+        let mut annotations_changed = false;
 
         self.pending_ops.sort_unstable_by_key(|op| op.timestamp());
         for op in mem::take(&mut self.pending_ops) {
@@ -1046,6 +1968,7 @@ impl AssistantContext {
                     {
                         self.slash_command_output_sections
                             .insert(ix, section.clone());
+                        self.load_document(&section, cx);
                         cx.emit(ContextEvent::SlashCommandOutputSectionAdded { section });
                     }
                 }
@@ -1081,6 +2004,20 @@ impl AssistantContext {
                         }
                     }
                 }
+                ContextOperation::UpdateReaction {
+                    message_id,
+                    reaction,
+                    author,
+                    added,
+                    timestamp,
+                    ..
+                } => {
+                    annotations_changed |=
+                        self.apply_reaction(message_id, reaction, author, added, timestamp);
+                }
+                ContextOperation::AddComment { comment, .. } => {
+                    annotations_changed |= self.apply_comment(comment);
+                }
                 ContextOperation::BufferOperation(_) => unreachable!(),
             }
 
@@ -1100,6 +2037,11 @@ impl AssistantContext {
             cx.emit(ContextEvent::SummaryGenerated);
             cx.notify();
         }
+
+        if annotations_changed {
+            cx.emit(ContextEvent::MessageAnnotationsChanged);
+            cx.notify();
+        }
     }
 
     fn can_apply_op(&self, op: &ContextOperation, cx: &App) -> bool {
@@ -1127,6 +2069,16 @@ impl AssistantContext {
                 self.has_received_operations_for_anchor_range(section.range.clone(), cx)
             }
             ContextOperation::SlashCommandFinished { .. } => true,
+            ContextOperation::UpdateReaction { message_id, .. } => {
+                self.messages_metadata.contains_key(message_id)
+            }
+            ContextOperation::AddComment { comment, .. } => {
+                self.messages_metadata.contains_key(&comment.message_id)
+                    && comment.parent_id.map_or(true, |parent_id| {
+                        self.comments.iter().any(|comment| comment.id == parent_id)
+                    })
+                    && self.has_received_operations_for_anchor_range(comment.range.clone(), cx)
+            }
             ContextOperation::BufferOperation(_) => {
                 panic!("buffer operations should always be applied")
             }
@@ -1173,6 +2125,18 @@ impl AssistantContext {
         self.path.as_ref()
     }
 
+    /// Whether nothing but whitespace was ever written in the context.
+    pub fn is_empty(&self, cx: &App) -> bool {
+        self.buffer
+            .read(cx)
+            .chars()
+            .all(|character| character.is_whitespace())
+    }
+
+    pub(crate) fn set_path(&mut self, path: Option<Arc<Path>>) {
+        self.path = path;
+    }
+
     pub fn summary(&self) -> &ContextSummary {
         &self.summary
     }
@@ -1192,6 +2156,14 @@ impl AssistantContext {
         &self.slash_command_output_sections
     }
 
+    pub fn pending_output_pages(&self) -> &HashMap<InvokedSlashCommandId, PendingOutputPages> {
+        &self.pending_output_pages
+    }
+
+    pub fn continuation_seams(&self) -> &[language::Anchor] {
+        &self.continuation_seams
+    }
+
     pub fn thought_process_output_sections(
         &self,
     ) -> &[ThoughtProcessOutputSection<language::Anchor>] {
@@ -1246,14 +2218,20 @@ impl AssistantContext {
         }
     }
 
+    /// Returns whether a completion is currently being streamed into the context.
+    pub fn is_streaming(&self) -> bool {
+        !self.pending_completions.is_empty()
+    }
+
     pub fn token_count(&self) -> Option<usize> {
         self.token_count
     }
 
     pub(crate) fn count_remaining_tokens(&mut self, cx: &mut Context<Self>) {
+        self.embed_messages(cx);
         // Assume it will be a Chat request, even though that takes fewer tokens (and risks going over the limit),
         // because otherwise you see in the UI that your empty message has a bunch of tokens already used.
-        let Some(model) = LanguageModelRegistry::read_global(cx).default_model() else {
+        let Some(model) = self.model(cx) else {
             return;
         };
         let request = self.to_completion_request(Some(&model.model), cx);
@@ -1280,6 +2258,84 @@ impl AssistantContext {
         });
     }
 
+    /// Embeds the messages that weren't embedded yet with the semantic index's
+    /// embedding provider, so that they can be ranked by relevance when the
+    /// request is assembled.
+    fn embed_messages(&mut self, cx: &mut Context<Self>) {
+        if self.assembly_strategy != AssemblyStrategy::RelevanceRanked {
+            return;
+        }
+        let Some(embedding_provider) = cx
+            .try_global::<SemanticDb>()
+            .map(|semantic_db| semantic_db.embedding_provider())
+        else {
+            return;
+        };
+
+        let mut texts = self
+            .to_completion_request(None, cx)
+            .messages
+            .iter()
+            .map(|message| message.string_contents())
+            .filter(|text| !text.trim().is_empty())
+            .map(|text| (TextToEmbed::new(&text).digest, text))
+            .collect::<Vec<_>>();
+        self.message_embeddings
+            .retain(|digest, _| texts.iter().any(|(text_digest, _)| text_digest == digest));
+        texts.retain(|(digest, _)| !self.message_embeddings.contains_key(digest));
+        if texts.is_empty() {
+            return;
+        }
+
+        self.pending_message_embeddings = cx.spawn(async move |this, cx| {
+            async move {
+                cx.background_executor()
+                    .timer(MESSAGE_EMBEDDING_DEBOUNCE)
+                    .await;
+
+                let mut embeddings = Vec::new();
+                for batch in texts.chunks(embedding_provider.batch_size().max(1)) {
+                    let batch = batch
+                        .iter()
+                        .map(|(digest, text)| TextToEmbed {
+                            text: util::truncate_to_byte_limit(text, MAX_EMBEDDED_MESSAGE_LEN),
+                            digest: *digest,
+                        })
+                        .collect::<Vec<_>>();
+                    let batch_embeddings = embedding_provider.embed(&batch).await?;
+                    embeddings.extend(batch.iter().map(|text| text.digest).zip(batch_embeddings));
+                }
+                this.update(cx, |this, cx| {
+                    this.message_embeddings.extend(embeddings);
+                    cx.notify();
+                })
+            }
+            .log_err()
+            .await
+        });
+    }
+
+    /// How close the embedding of each text is to the last one's, for the
+    /// texts that were embedded.
+    fn relevance_to_last(&self, texts: &[String]) -> Vec<Option<f32>> {
+        if self.assembly_strategy != AssemblyStrategy::RelevanceRanked {
+            return vec![None; texts.len()];
+        }
+        let embeddings = texts
+            .iter()
+            .map(|text| self.message_embeddings.get(&TextToEmbed::new(text).digest))
+            .collect::<Vec<_>>();
+        let Some(Some(query)) = embeddings.last() else {
+            return vec![None; texts.len()];
+        };
+        embeddings
+            .iter()
+            .map(|embedding| {
+                embedding.map(|embedding| query.similarity(slice::from_ref(embedding)).0)
+            })
+            .collect()
+    }
+
     pub fn mark_cache_anchors(
         &mut self,
         cache_configuration: &Option<LanguageModelCacheConfiguration>,
@@ -1304,7 +2360,13 @@ impl AssistantContext {
             sorted_messages.pop();
         }
         sorted_messages.retain(|m| m.role == Role::User);
-        sorted_messages.sort_by(|a, b| b.offset_range.len().cmp(&a.offset_range.len()));
+        // Messages with pinned attachments are sent with every request, so
+        // they're cached ahead of larger messages that may be left out.
+        sorted_messages.sort_by(|a, b| {
+            self.has_pinned_content(b, cx)
+                .cmp(&self.has_pinned_content(a, cx))
+                .then_with(|| b.offset_range.len().cmp(&a.offset_range.len()))
+        });
 
         let cache_anchors = if self.token_count.unwrap_or(0) < cache_configuration.min_total_token {
             // If we have't hit the minimum threshold to enable caching, don't cache anything.
@@ -1673,6 +2735,10 @@ impl AssistantContext {
     ) {
         let version = self.version.clone();
         let command_id = InvokedSlashCommandId(self.next_timestamp());
+        let paginate = self
+            .slash_commands
+            .command(name, cx)
+            .map_or(false, |command| command.paginate_output());
 
         const PENDING_OUTPUT_END_MARKER: &str = "…";
 
@@ -1711,11 +2777,26 @@ impl AssistantContext {
                     first_transaction,
                 )
             });
+        self.push_operation_history_entry(
+            HistoryOperationKind::SlashCommand,
+            format!("/{name}").into(),
+            first_transaction,
+            cx,
+        );
         self.reparse(cx);
 
         let insert_output_task = cx.spawn(async move |this, cx| {
             let run_command = async {
                 let mut stream = output.await?;
+                let mut remaining_pages = VecDeque::new();
+                if paginate {
+                    let output = SlashCommandOutput::from_event_stream(stream).await?;
+                    let mut pages = output
+                        .into_pages(SLASH_COMMAND_OUTPUT_PAGE_LINES)
+                        .into_iter();
+                    stream = pages.next().unwrap_or_default().to_event_stream();
+                    remaining_pages.extend(pages);
+                }
 
                 struct PendingSection {
                     start: language::Anchor,
@@ -1867,6 +2948,19 @@ impl AssistantContext {
                             buffer.merge_transactions(deletion_transaction, first_transaction);
                         }
                     });
+
+                    if !remaining_pages.is_empty() {
+                        let buffer = this.buffer.read(cx);
+                        let end = buffer.anchor_before(insert_position.to_offset(buffer));
+                        this.pending_output_pages.insert(
+                            command_id,
+                            PendingOutputPages {
+                                end,
+                                pages: remaining_pages,
+                            },
+                        );
+                        cx.emit(ContextEvent::PendingOutputPagesChanged);
+                    }
                 })?;
 
                 debug_assert!(pending_section_stack.is_empty());
@@ -1933,6 +3027,47 @@ impl AssistantContext {
         );
     }
 
+    /// Inserts the next page of a paginated slash command's output after the
+    /// pages that were already inserted.
+    pub fn load_more_command_output(
+        &mut self,
+        command_id: InvokedSlashCommandId,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(mut pending_pages) = self.pending_output_pages.remove(&command_id) else {
+            return;
+        };
+        let Some(page) = pending_pages.pages.pop_front() else {
+            return;
+        };
+
+        let page_start = self.buffer.update(cx, |buffer, cx| {
+            let page_start = pending_pages.end.to_offset(buffer);
+            buffer.edit([(page_start..page_start, page.text.as_str())], None, cx);
+            pending_pages.end = buffer.anchor_before(page_start + page.text.len());
+            page_start
+        });
+        for section in page.sections {
+            let buffer = self.buffer.read(cx);
+            let range = buffer.anchor_after(page_start + section.range.start)
+                ..buffer.anchor_before(page_start + section.range.end);
+            self.insert_slash_command_output_section(
+                SlashCommandOutputSection {
+                    range,
+                    icon: section.icon,
+                    label: section.label,
+                    metadata: section.metadata,
+                },
+                cx,
+            );
+        }
+
+        if !pending_pages.pages.is_empty() {
+            self.pending_output_pages.insert(command_id, pending_pages);
+        }
+        cx.emit(ContextEvent::PendingOutputPagesChanged);
+    }
+
     fn insert_slash_command_output_section(
         &mut self,
         section: SlashCommandOutputSection<language::Anchor>,
@@ -1962,6 +3097,47 @@ impl AssistantContext {
         );
     }
 
+    /// Starts loading the PDF attached by the given section, if it attaches
+    /// one that isn't loaded yet.
+    fn load_document(
+        &mut self,
+        section: &SlashCommandOutputSection<language::Anchor>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(metadata) = section
+            .metadata
+            .clone()
+            .and_then(|metadata| serde_json::from_value::<PdfCommandMetadata>(metadata).ok())
+        else {
+            return;
+        };
+        if self.documents.contains_key(&metadata.pdf_path) {
+            return;
+        }
+        let Some(project) = self.project.as_ref() else {
+            return;
+        };
+
+        let fs = project.read(cx).fs().clone();
+        let path = metadata.pdf_path.clone();
+        let name = path.file_name().map_or_else(
+            || section.label.clone(),
+            |name| name.to_string_lossy().into_owned().into(),
+        );
+        let document = cx
+            .background_spawn(async move {
+                let bytes = fs.load_bytes(&path).await.log_err()?;
+                Some(LanguageModelDocument::new(
+                    name,
+                    "application/pdf",
+                    &bytes,
+                    metadata.estimated_tokens,
+                ))
+            })
+            .shared();
+        self.documents.insert(metadata.pdf_path, document);
+    }
+
     fn insert_thought_process_output_section(
         &mut self,
         section: ThoughtProcessOutputSection<language::Anchor>,
@@ -2004,9 +3180,40 @@ impl AssistantContext {
         })
     }
 
+    /// Whether this is a guest's replica of a context shared by the project's
+    /// host, whose language model can complete it on the guest's behalf.
+    pub fn is_shared_by_host(&self, cx: &App) -> bool {
+        self.replica_id() != ReplicaId::default()
+            && self
+                .project
+                .as_ref()
+                .is_some_and(|project| project.read(cx).is_via_collab())
+    }
+
+    /// Asks the project's host to complete the context with their language
+    /// model. The response streams in as the host edits the shared context.
+    pub fn request_host_completion(&self, cx: &App) -> Task<Result<()>> {
+        let Some(project) = self.project.as_ref().map(|project| project.read(cx)) else {
+            return Task::ready(Err(anyhow!("context has no project")));
+        };
+        let Some(project_id) = project.remote_id() else {
+            return Task::ready(Err(anyhow!("project is not shared")));
+        };
+        let request = project.client().request(proto::RequestHostCompletion {
+            project_id,
+            context_id: self.id.to_proto(),
+        });
+        cx.background_spawn(async move {
+            request.await?;
+            Ok(())
+        })
+    }
+
     pub fn assist(&mut self, cx: &mut Context<Self>) -> Option<MessageAnchor> {
-        let model_registry = LanguageModelRegistry::read_global(cx);
-        let model = model_registry.default_model()?;
+        if self.is_loading() {
+            return None;
+        }
+        let model = self.model(cx)?;
         let last_message_id = self.get_last_valid_message_id(cx)?;
 
         if !model.provider.is_authenticated(cx) {
@@ -2014,138 +3221,418 @@ impl AssistantContext {
             return None;
         }
 
-        let model = model.model;
+        let mut model = model.model;
+        let mut substitution = None;
+
+        if let Err(error) = SpendTracker::check(
+            model.as_ref(),
+            AgentSettings::get_global(cx).max_monthly_spend,
+            cx,
+        ) {
+            let Some((substitute, model_substitution)) =
+                free_model_substitute(&model, ModelSubstitutionReason::MaxMonthlySpendReached, cx)
+            else {
+                cx.emit(ContextEvent::ShowMaxMonthlySpendReachedError(error));
+                return None;
+            };
+            model = substitute;
+            substitution = Some(model_substitution);
+        }
 
         // Compute which messages to cache, including the last one.
         self.mark_cache_anchors(&model.cache_configuration(), false, cx);
 
-        let request = self.to_completion_request(Some(&model), cx);
+        let (request, assembly) = self.assemble_completion_request(Some(&model), cx);
+        self.last_assembly = Some(assembly);
+        let estimated_input_tokens = self.token_count;
 
-        let assistant_message = self
-            .insert_message_after(last_message_id, Role::Assistant, MessageStatus::Pending, cx)
-            .unwrap();
+        let ((assistant_message, user_message), response_transaction) = self.record_operation(
+            HistoryOperationKind::AssistantResponse,
+            "Assistant response".into(),
+            cx,
+            |this, cx| {
+                let assistant_message = this
+                    .insert_message_after(
+                        last_message_id,
+                        Role::Assistant,
+                        MessageStatus::Pending,
+                        cx,
+                    )
+                    .unwrap();
+
+                // Queue up the user's next reply.
+                let user_message = this
+                    .insert_message_after(assistant_message.id, Role::User, MessageStatus::Done, cx)
+                    .unwrap();
+                (assistant_message, user_message)
+            },
+        );
 
-        // Queue up the user's next reply.
-        let user_message = self
-            .insert_message_after(assistant_message.id, Role::User, MessageStatus::Done, cx)
-            .unwrap();
+        if let Some(substitution) = substitution {
+            self.update_metadata(assistant_message.id, cx, |metadata| {
+                metadata.substitution = Some(substitution);
+            });
+        }
+        if let Some(seed) = request.seed {
+            self.update_metadata(assistant_message.id, cx, |metadata| {
+                metadata.seed = Some(seed);
+            });
+        }
 
+        self.event_logs.start(
+            assistant_message.id,
+            model.provider_id().0.to_string(),
+            model.id().0.to_string(),
+        );
         let pending_completion_id = post_inc(&mut self.completion_count);
+        let mut stop_matcher = StopMatcher::new(&self.stop_conditions);
 
         let task = cx.spawn({
             async move |this, cx| {
-                let stream = model.stream_completion(request, &cx);
+                let mut model = model;
+                let stream = model.stream_completion(request.clone(), &cx);
                 let assistant_message_id = assistant_message.id;
                 let mut response_latency = None;
                 let stream_completion = async {
                     let request_start = Instant::now();
-                    let mut events = stream.await?;
+                    let mut events = match stream.await {
+                        Ok(events) => events,
+                        Err(error) => {
+                            let substitute = match error.downcast_ref::<LanguageModelError>() {
+                                Some(LanguageModelError::RateLimitExceeded { .. }) => {
+                                    cx.update(|cx| {
+                                        free_model_substitute(
+                                            &model,
+                                            ModelSubstitutionReason::RateLimitExceeded,
+                                            cx,
+                                        )
+                                    })?
+                                }
+                                _ => None,
+                            };
+                            let Some((substitute, substitution)) = substitute else {
+                                return Err(error);
+                            };
+                            this.update(cx, |this, cx| {
+                                this.update_metadata(assistant_message_id, cx, |metadata| {
+                                    metadata.substitution = Some(substitution);
+                                });
+                            })?;
+                            model = substitute;
+                            model.stream_completion(request.clone(), &cx).await?
+                        }
+                    };
                     let mut stop_reason = StopReason::EndTurn;
+                    let mut reported_usage = None;
+                    let mut continued_usage = None;
+                    let mut continuations = 0;
+                    let max_continuations =
+                        cx.update(|cx| AgentSettings::get_global(cx).max_continuations)?;
+                    let mut resumes = 0;
                     let mut thought_process_stack = Vec::new();
 
                     const THOUGHT_PROCESS_START_MARKER: &str = "<think>\n";
                     const THOUGHT_PROCESS_END_MARKER: &str = "\n</think>";
 
-                    while let Some(event) = events.next().await {
-                        if response_latency.is_none() {
-                            response_latency = Some(request_start.elapsed());
-                        }
-                        let event = event?;
+                    // Responses that are cut off by the output token limit, or
+                    // by a dropped connection, are continued by further requests,
+                    // streaming into the same message.
+                    loop {
+                        let mut streamed = false;
+                        let mut disconnected = false;
+                        while let Some(event) = events.next().await {
+                            if response_latency.is_none()
+                                && !matches!(event, Ok(LanguageModelCompletionEvent::Retrying { .. }))
+                            {
+                                response_latency = Some(request_start.elapsed());
+                            }
+                            this.update(cx, |this, _| {
+                                this.event_logs.record(
+                                    assistant_message_id,
+                                    request_start.elapsed(),
+                                    event.as_ref().map_err(|error| error.to_string()),
+                                )
+                            })?;
+                            let event = match event {
+                                Ok(event) => event,
+                                Err(error) => {
+                                    // Unwrap the provider's error, so that it can be
+                                    // shown as a classified error.
+                                    let error = match error {
+                                        LanguageModelCompletionError::Other(error) => error,
+                                        error => anyhow::Error::from(error),
+                                    };
+                                    if streamed && resumes < MAX_STREAM_RESUMES && is_disconnect(&error) {
+                                        log::info!("resuming response after disconnect: {error:#}");
+                                        disconnected = true;
+                                        break;
+                                    }
+                                    return Err(error);
+                                }
+                            };
+                            if let LanguageModelCompletionEvent::Retrying {
+                                attempt,
+                                max_attempts,
+                                delay,
+                                message,
+                            } = event
+                            {
+                                log::info!(
+                                    "retrying completion (attempt {attempt} of {max_attempts}) in {delay:?}: {message}"
+                                );
+                                this.update(cx, |_, cx| {
+                                    cx.emit(ContextEvent::RetryingCompletion {
+                                        attempt,
+                                        max_attempts,
+                                        delay,
+                                        message,
+                                    })
+                                })?;
+                                continue;
+                            }
+                            streamed = true;
 
-                        let mut context_event = None;
-                        let mut thought_process_output_section = None;
+                            let mut context_event = None;
+                            let mut thought_process_output_section = None;
+                            let mut stop_condition_matched = false;
+                            let mut web_sources = None;
 
-                        this.update(cx, |this, cx| {
-                            let message_ix = this
-                                .message_anchors
-                                .iter()
-                                .position(|message| message.id == assistant_message_id)?;
-                            this.buffer.update(cx, |buffer, cx| {
-                                let message_old_end_offset = this.message_anchors[message_ix + 1..]
+                            this.update(cx, |this, cx| {
+                                // Hold back incomplete Markdown until it completes to avoid
+                                // flickering highlights and folds.
+                                let event = match event {
+                                    LanguageModelCompletionEvent::Text(mut chunk) => {
+                                        if let Some(len) = stop_matcher.push(&chunk) {
+                                            chunk.truncate(len);
+                                            stop_condition_matched = true;
+                                        }
+                                        let pending_completion = this
+                                            .pending_completions
+                                            .iter_mut()
+                                            .find(|completion| completion.id == pending_completion_id);
+                                        LanguageModelCompletionEvent::Text(match pending_completion {
+                                            Some(completion) => completion.markdown.push(&chunk),
+                                            None => chunk,
+                                        })
+                                    }
+                                    event => event,
+                                };
+                                let message_ix = this
+                                    .message_anchors
                                     .iter()
-                                    .find(|message| message.start.is_valid(buffer))
-                                    .map_or(buffer.len(), |message| {
-                                        message.start.to_offset(buffer).saturating_sub(1)
+                                    .position(|message| message.id == assistant_message_id)?;
+                                this.buffer.update(cx, |buffer, cx| {
+                                    extend_transaction(buffer, response_transaction, cx, |buffer, cx| {
+                                        let message_old_end_offset = this.message_anchors[message_ix + 1..]
+                                            .iter()
+                                            .find(|message| message.start.is_valid(buffer))
+                                            .map_or(buffer.len(), |message| {
+                                                message.start.to_offset(buffer).saturating_sub(1)
+                                            });
+
+                                        match event {
+                                            LanguageModelCompletionEvent::StatusUpdate { .. } => {}
+                                            LanguageModelCompletionEvent::StartMessage { .. } => {}
+                                            LanguageModelCompletionEvent::Stop(reason) => {
+                                                stop_reason = reason;
+                                            }
+                                            LanguageModelCompletionEvent::Thinking { text: chunk, .. } => {
+                                                if thought_process_stack.is_empty() {
+                                                    let start =
+                                                        buffer.anchor_before(message_old_end_offset);
+                                                    thought_process_stack.push(start);
+                                                    let chunk =
+                                                        format!("{THOUGHT_PROCESS_START_MARKER}{chunk}{THOUGHT_PROCESS_END_MARKER}");
+                                                    let chunk_len = chunk.len();
+                                                    buffer.edit(
+                                                        [(
+                                                            message_old_end_offset..message_old_end_offset,
+                                                            chunk,
+                                                        )],
+                                                        None,
+                                                        cx,
+                                                    );
+                                                    let end = buffer
+                                                        .anchor_before(message_old_end_offset + chunk_len);
+                                                    context_event = Some(
+                                                        ContextEvent::StartedThoughtProcess(start..end),
+                                                    );
+                                                } else {
+                                                    // This ensures that all the thinking chunks are inserted inside the thinking tag
+                                                    let insertion_position =
+                                                        message_old_end_offset - THOUGHT_PROCESS_END_MARKER.len();
+                                                    buffer.edit(
+                                                        [(insertion_position..insertion_position, chunk)],
+                                                        None,
+                                                        cx,
+                                                    );
+                                                }
+                                            }
+                                            LanguageModelCompletionEvent::Text(mut chunk) => {
+                                                if let Some(start) = thought_process_stack.pop() {
+                                                    let end = buffer.anchor_before(message_old_end_offset);
+                                                    context_event =
+                                                        Some(ContextEvent::EndedThoughtProcess(end));
+                                                    thought_process_output_section =
+                                                        Some(ThoughtProcessOutputSection {
+                                                            range: start..end,
+                                                        });
+                                                    chunk.insert_str(0, "\n\n");
+                                                }
+
+                                                buffer.edit(
+                                                    [(
+                                                        message_old_end_offset..message_old_end_offset,
+                                                        chunk,
+                                                    )],
+                                                    None,
+                                                    cx,
+                                                );
+                                            }
+                                            LanguageModelCompletionEvent::UsageUpdate(usage) => {
+                                                reported_usage = Some(usage);
+                                            }
+                                            LanguageModelCompletionEvent::ToolUse(_) => {}
+                                            LanguageModelCompletionEvent::WebSources(sources) => {
+                                                web_sources = Some(sources);
+                                            }
+                                            LanguageModelCompletionEvent::Retrying { .. } => {}
+                                        }
                                     });
+                                });
 
-                                match event {
-                                    LanguageModelCompletionEvent::StatusUpdate { .. } => {}
-                                    LanguageModelCompletionEvent::StartMessage { .. } => {}
-                                    LanguageModelCompletionEvent::Stop(reason) => {
-                                        stop_reason = reason;
-                                    }
-                                    LanguageModelCompletionEvent::Thinking { text: chunk, .. } => {
-                                        if thought_process_stack.is_empty() {
-                                            let start =
-                                                buffer.anchor_before(message_old_end_offset);
-                                            thought_process_stack.push(start);
-                                            let chunk =
-                                                format!("{THOUGHT_PROCESS_START_MARKER}{chunk}{THOUGHT_PROCESS_END_MARKER}");
-                                            let chunk_len = chunk.len();
-                                            buffer.edit(
-                                                [(
-                                                    message_old_end_offset..message_old_end_offset,
-                                                    chunk,
-                                                )],
-                                                None,
-                                                cx,
-                                            );
-                                            let end = buffer
-                                                .anchor_before(message_old_end_offset + chunk_len);
-                                            context_event = Some(
-                                                ContextEvent::StartedThoughtProcess(start..end),
-                                            );
-                                        } else {
-                                            // This ensures that all the thinking chunks are inserted inside the thinking tag
-                                            let insertion_position =
-                                                message_old_end_offset - THOUGHT_PROCESS_END_MARKER.len();
-                                            buffer.edit(
-                                                [(insertion_position..insertion_position, chunk)],
-                                                None,
-                                                cx,
-                                            );
-                                        }
-                                    }
-                                    LanguageModelCompletionEvent::Text(mut chunk) => {
-                                        if let Some(start) = thought_process_stack.pop() {
-                                            let end = buffer.anchor_before(message_old_end_offset);
-                                            context_event =
-                                                Some(ContextEvent::EndedThoughtProcess(end));
-                                            thought_process_output_section =
-                                                Some(ThoughtProcessOutputSection {
-                                                    range: start..end,
-                                                });
-                                            chunk.insert_str(0, "\n\n");
+                                if let Some(sources) = web_sources.take() {
+                                    this.update_metadata(assistant_message_id, cx, |metadata| {
+                                        for source in sources {
+                                            if !metadata
+                                                .web_sources
+                                                .iter()
+                                                .any(|existing| existing.url == source.url)
+                                            {
+                                                metadata.web_sources.push(source);
+                                            }
                                         }
+                                    });
+                                }
 
-                                        buffer.edit(
-                                            [(
-                                                message_old_end_offset..message_old_end_offset,
-                                                chunk,
-                                            )],
-                                            None,
-                                            cx,
-                                        );
-                                    }
-                                    LanguageModelCompletionEvent::ToolUse(_) |
-                                    LanguageModelCompletionEvent::UsageUpdate(_)  => {}
+                                if let Some(section) = thought_process_output_section.take() {
+                                    this.insert_thought_process_output_section(section, cx);
+                                }
+                                if let Some(context_event) = context_event.take() {
+                                    cx.emit(context_event);
                                 }
-                            });
 
-                            if let Some(section) = thought_process_output_section.take() {
-                                this.insert_thought_process_output_section(section, cx);
-                            }
-                            if let Some(context_event) = context_event.take() {
-                                cx.emit(context_event);
-                            }
+                                cx.emit(ContextEvent::StreamedCompletion);
 
-                            cx.emit(ContextEvent::StreamedCompletion);
+                                Some(())
+                            })?;
+                            if stop_condition_matched {
+                                // Dropping the stream cancels the request.
+                                stop_reason = StopReason::EndTurn;
+                                break;
+                            }
+                            smol::future::yield_now().await;
+                        }
 
-                            Some(())
+                        if disconnected {
+                            resumes += 1;
+                            cx.background_executor().timer(STREAM_RESUME_DELAY).await;
+                            let resume_request = this.update(cx, |this, cx| {
+                                this.flush_streamed_markdown(pending_completion_id, cx);
+                                this.insert_continuation_seam(assistant_message_id, cx);
+                                this.continuation_request(request.clone(), assistant_message_id, cx)
+                            })?;
+                            if let Some(usage) = reported_usage.take() {
+                                continued_usage =
+                                    Some(continued_usage.unwrap_or_default() + usage);
+                            }
+                            events = model.stream_completion(resume_request, &cx).await?;
+                            continue;
+                        }
+                        if stop_reason != StopReason::MaxTokens
+                            || continuations >= max_continuations
+                        {
+                            break;
+                        }
+                        continuations += 1;
+                        let continuation_request = this.update(cx, |this, cx| {
+                            this.flush_streamed_markdown(pending_completion_id, cx);
+                            this.insert_continuation_seam(assistant_message_id, cx);
+                            this.continuation_request(request.clone(), assistant_message_id, cx)
                         })?;
-                        smol::future::yield_now().await;
+                        if let Some(usage) = reported_usage.take() {
+                            continued_usage =
+                                Some(continued_usage.unwrap_or_default() + usage);
+                        }
+                        stop_reason = StopReason::EndTurn;
+                        events = model.stream_completion(continuation_request, &cx).await?;
+                    }
+                    this.update(cx, |this, cx| {
+                        this.flush_streamed_markdown(pending_completion_id, cx)
+                    })?;
+
+                    // The local count only covers the first request, so it's
+                    // only compared with the usage of responses that weren't
+                    // continued.
+                    let estimated_input_tokens = estimated_input_tokens
+                        .filter(|_| continued_usage.is_none())
+                        .map(|tokens| tokens as u32);
+                    let reported_usage = match (continued_usage, reported_usage) {
+                        (Some(continued_usage), Some(usage)) => Some(continued_usage + usage),
+                        (continued_usage, usage) => usage.or(continued_usage),
+                    };
+                    let usage = match reported_usage {
+                        Some(token_usage) => MessageUsage {
+                            token_usage,
+                            estimated: false,
+                            cost: None,
+                            estimated_input_tokens,
+                        },
+                        None => {
+                            let output_tokens = this
+                                .update(cx, |this, cx| {
+                                    let request =
+                                        this.message_completion_request(assistant_message_id, cx);
+                                    model.count_tokens(request, cx)
+                                })?
+                                .await
+                                .log_err()
+                                .unwrap_or(0);
+                            MessageUsage {
+                                token_usage: TokenUsage {
+                                    input_tokens: estimated_input_tokens.unwrap_or(0) as u32,
+                                    output_tokens: output_tokens as u32,
+                                    ..Default::default()
+                                },
+                                estimated: true,
+                                cost: None,
+                                estimated_input_tokens: None,
+                            }
+                        }
+                    };
+                    if usage.has_token_drift() {
+                        log::warn!(
+                            "{} reported {} input tokens, but they were counted locally as {}",
+                            model.telemetry_id(),
+                            usage.input_tokens(),
+                            usage.estimated_input_tokens.unwrap_or(0),
+                        );
                     }
+                    let usage = MessageUsage {
+                        cost: model
+                            .pricing()
+                            .map(|pricing| pricing.cost(&usage.token_usage)),
+                        ..usage
+                    };
+
                     this.update(cx, |this, cx| {
+                        if let Some(cost) = usage.cost {
+                            SpendTracker::record(cost, cx);
+                        }
+                        this.cost_tracker.record(&usage.token_usage, model.pricing());
+                        this.update_metadata(assistant_message_id, cx, |metadata| {
+                            metadata.usage = Some(usage);
+                        });
                         this.pending_completions
                             .retain(|completion| completion.id != pending_completion_id);
                         this.summarize(false, cx);
@@ -2158,6 +3645,7 @@ impl AssistantContext {
                 let result = stream_completion.await;
 
                 this.update(cx, |this, cx| {
+                    this.flush_streamed_markdown(pending_completion_id, cx);
                     let error_message = if let Some(error) = result.as_ref().err() {
                         if error.is::<PaymentRequiredError>() {
                             cx.emit(ContextEvent::ShowPaymentRequiredError);
@@ -2165,6 +3653,21 @@ impl AssistantContext {
                                 metadata.status = MessageStatus::Canceled;
                             });
                             Some(error.to_string())
+                        } else if let Some(LanguageModelError::Cancelled) =
+                            error.downcast_ref::<LanguageModelError>()
+                        {
+                            this.update_metadata(assistant_message_id, cx, |metadata| {
+                                metadata.status = MessageStatus::Canceled;
+                            });
+                            None
+                        } else if let Some(model_error) = error.downcast_ref::<LanguageModelError>()
+                        {
+                            cx.emit(ContextEvent::ShowModelError(model_error.clone()));
+                            this.update_metadata(assistant_message_id, cx, |metadata| {
+                                metadata.status =
+                                    MessageStatus::Error(SharedString::from(error.to_string()));
+                            });
+                            Some(error.to_string())
                         } else {
                             let error_message = error
                                 .chain()
@@ -2184,6 +3687,7 @@ impl AssistantContext {
                         this.update_metadata(assistant_message_id, cx, |metadata| {
                             metadata.status = MessageStatus::Done;
                         });
+                        this.post_process_message(assistant_message_id, response_transaction, cx);
                         None
                     };
 
@@ -2210,13 +3714,31 @@ impl AssistantContext {
                         cx.background_executor(),
                     );
 
+                    cx.emit(ContextEvent::CompletionFinished {
+                        assistant_message_id,
+                        usage: this
+                            .messages_metadata
+                            .get(&assistant_message_id)
+                            .and_then(|metadata| metadata.usage),
+                    });
+
                     if let Ok(stop_reason) = result {
                         match stop_reason {
                             StopReason::ToolUse => {}
                             StopReason::EndTurn => {}
                             StopReason::MaxTokens => {}
-                            StopReason::Refusal => {}
-                        }
+                            StopReason::Refusal => {
+                                let error = LanguageModelError::ContentFiltered {
+                                    message: "The model declined to respond to this request."
+                                        .into(),
+                                };
+                                this.update_metadata(assistant_message_id, cx, |metadata| {
+                                    metadata.status =
+                                        MessageStatus::Error(SharedString::from(error.to_string()));
+                                });
+                                cx.emit(ContextEvent::ShowModelError(error));
+                            }
+                        }
                     }
                 })
                 .ok();
@@ -2226,12 +3748,148 @@ impl AssistantContext {
         self.pending_completions.push(PendingCompletion {
             id: pending_completion_id,
             assistant_message_id: assistant_message.id,
+            transaction_id: response_transaction,
+            markdown: StreamingMarkdown::default(),
             _task: task,
         });
 
         Some(user_message)
     }
 
+    /// Inserts any text that was held back while streaming the given completion.
+    fn flush_streamed_markdown(&mut self, pending_completion_id: usize, cx: &mut Context<Self>) {
+        let Some(pending_completion) = self
+            .pending_completions
+            .iter_mut()
+            .find(|completion| completion.id == pending_completion_id)
+        else {
+            return;
+        };
+        let held_back_text = pending_completion.markdown.finish();
+        let message_id = pending_completion.assistant_message_id;
+        let transaction_id = pending_completion.transaction_id;
+        self.append_to_message(message_id, &held_back_text, transaction_id, cx);
+    }
+
+    fn append_to_message(
+        &mut self,
+        message_id: MessageId,
+        text: &str,
+        transaction_id: Option<TransactionId>,
+        cx: &mut Context<Self>,
+    ) {
+        if text.is_empty() {
+            return;
+        }
+        let Some(message_ix) = self
+            .message_anchors
+            .iter()
+            .position(|message| message.id == message_id)
+        else {
+            return;
+        };
+        self.buffer.update(cx, |buffer, cx| {
+            let message_end_offset = self.message_anchors[message_ix + 1..]
+                .iter()
+                .find(|message| message.start.is_valid(buffer))
+                .map_or(buffer.len(), |message| {
+                    message.start.to_offset(buffer).saturating_sub(1)
+                });
+            extend_transaction(buffer, transaction_id, cx, |buffer, cx| {
+                buffer.edit([(message_end_offset..message_end_offset, text)], None, cx);
+            });
+        });
+    }
+
+    /// Builds a request containing only the given message, used to estimate
+    /// its token count when the provider doesn't report usage.
+    /// Builds a request asking the model to continue the given message, which
+    /// was cut off by the output token limit.
+    fn continuation_request(
+        &self,
+        mut request: LanguageModelRequest,
+        message_id: MessageId,
+        cx: &App,
+    ) -> LanguageModelRequest {
+        const CONTINUATION_PROMPT: &str = "Your response was cut off. Continue exactly where you left off, without repeating anything or adding a preamble.";
+
+        let buffer = self.buffer.read(cx);
+        let text = self
+            .messages(cx)
+            .find(|message| message.id == message_id)
+            .map(|message| {
+                buffer
+                    .text_for_range(message.offset_range)
+                    .collect::<String>()
+            })
+            .unwrap_or_default();
+        request.messages.push(LanguageModelRequestMessage {
+            role: Role::Assistant,
+            content: vec![text.trim_end().to_string().into()],
+            cache: false,
+        });
+        request.messages.push(LanguageModelRequestMessage {
+            role: Role::User,
+            content: vec![CONTINUATION_PROMPT.into()],
+            cache: false,
+        });
+        request
+    }
+
+    /// Marks the end of the given message as the point where the model's
+    /// response was continued by another request.
+    fn insert_continuation_seam(&mut self, message_id: MessageId, cx: &mut Context<Self>) {
+        let Some(message) = self.messages(cx).find(|message| message.id == message_id) else {
+            return;
+        };
+        let buffer = self.buffer.read(cx);
+        // Responses are streamed in before the newline that ends the message.
+        let seam = buffer.anchor_before(
+            message
+                .offset_range
+                .end
+                .saturating_sub(1)
+                .max(message.offset_range.start),
+        );
+        let ix = match self
+            .continuation_seams
+            .binary_search_by(|probe| probe.cmp(&seam, buffer))
+        {
+            Ok(ix) | Err(ix) => ix,
+        };
+        self.continuation_seams.insert(ix, seam);
+        cx.emit(ContextEvent::ContinuationSeamsChanged);
+    }
+
+    fn message_completion_request(&self, message_id: MessageId, cx: &App) -> LanguageModelRequest {
+        let buffer = self.buffer.read(cx);
+        let text = self
+            .messages(cx)
+            .find(|message| message.id == message_id)
+            .map(|message| {
+                buffer
+                    .text_for_range(message.offset_range)
+                    .collect::<String>()
+            })
+            .unwrap_or_default();
+        LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::Assistant,
+                content: vec![text.into()],
+                cache: false,
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// Returns the combined usage of all messages for which usage is known.
+    pub fn total_usage(&self) -> Option<MessageUsage> {
+        self.messages_metadata
+            .values()
+            .filter_map(|metadata| metadata.usage)
+            .reduce(|total, usage| total + usage)
+    }
+
     pub fn to_xml(&self, cx: &App) -> String {
         let mut output = String::new();
         let buffer = self.buffer.read(cx);
@@ -2257,12 +3915,63 @@ impl AssistantContext {
         model: Option<&Arc<dyn LanguageModel>>,
         cx: &App,
     ) -> LanguageModelRequest {
+        self.assemble_completion_request(model, cx).0
+    }
+
+    /// Builds the request using the context's [`AssemblyStrategy`], and
+    /// reports which messages were included in it.
+    pub fn assemble_completion_request(
+        &self,
+        model: Option<&Arc<dyn LanguageModel>>,
+        cx: &App,
+    ) -> (LanguageModelRequest, AssemblyReport) {
         let buffer = self.buffer.read(cx);
 
         let mut contents = self.contents(cx).peekable();
+        let compressed_sections = self
+            .slash_command_output_sections
+            .iter()
+            .filter(|section| self.is_section_compressed(&section.range, cx))
+            .map(|section| section.range.to_offset(buffer))
+            .collect::<Vec<_>>();
+        let documents = if model.is_none_or(|model| model.supports_documents()) {
+            self.slash_command_output_sections
+                .iter()
+                .filter(|section| section.is_valid(buffer))
+                .filter_map(|section| {
+                    let metadata =
+                        serde_json::from_value::<PdfCommandMetadata>(section.metadata.clone()?)
+                            .ok()?;
+                    let document = self
+                        .documents
+                        .get(&metadata.pdf_path)?
+                        .clone()
+                        .now_or_never()
+                        .flatten()?;
+                    Some((section.range.start.to_offset(buffer), document))
+                })
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
 
-        fn collect_text_content(buffer: &Buffer, range: Range<usize>) -> Option<String> {
-            let text: String = buffer.text_for_range(range.clone()).collect();
+        fn collect_text_content(
+            buffer: &Buffer,
+            range: Range<usize>,
+            compressed_sections: &[Range<usize>],
+        ) -> Option<String> {
+            let mut text = String::new();
+            let mut offset = range.start;
+            for section in compressed_sections {
+                if section.start < offset || section.end > range.end {
+                    continue;
+                }
+                text.extend(buffer.text_for_range(offset..section.start));
+                let section_text = buffer.text_for_range(section.clone()).collect::<String>();
+                text.push_str(&prompt_compression::compress(&section_text).text);
+                offset = section.end;
+            }
+            text.extend(buffer.text_for_range(offset..range.end));
             if text.trim().is_empty() {
                 None
             } else {
@@ -2280,7 +3989,20 @@ impl AssistantContext {
             tool_choice: None,
             stop: Vec::new(),
             temperature: model.and_then(|model| AgentSettings::temperature_for_model(model, cx)),
+            seed: model.and_then(|model| self.seed(model, cx)),
+            priority: RequestPriority::Interactive,
+            web_search: self.web_search && model.is_some_and(|model| model.supports_web_search()),
         };
+        completion_request
+            .messages
+            .extend(self.language_preamble_message(cx));
+        completion_request
+            .messages
+            .extend(self.answer_language_message(cx));
+        completion_request
+            .messages
+            .extend(self.debugging_message(cx));
+        let mut candidates = Vec::new();
         for message in self.messages(cx) {
             if message.status != MessageStatus::Done {
                 continue;
@@ -2295,6 +4017,12 @@ impl AssistantContext {
                     .as_ref()
                     .map_or(false, |cache| cache.is_anchor),
             };
+            request_message.content.extend(
+                documents
+                    .iter()
+                    .filter(|(start, _)| message.offset_range.contains(start))
+                    .map(|(_, document)| MessageContent::Document(document.clone())),
+            );
 
             while let Some(content) = contents.peek() {
                 if content
@@ -2306,7 +4034,8 @@ impl AssistantContext {
                     let content = contents.next().unwrap();
                     let range = content.range().to_offset(buffer);
                     request_message.content.extend(
-                        collect_text_content(buffer, offset..range.start).map(MessageContent::Text),
+                        collect_text_content(buffer, offset..range.start, &compressed_sections)
+                            .map(MessageContent::Text),
                     );
 
                     match content {
@@ -2326,14 +4055,92 @@ impl AssistantContext {
             }
 
             request_message.content.extend(
-                collect_text_content(buffer, offset..message.offset_range.end)
-                    .map(MessageContent::Text),
+                collect_text_content(
+                    buffer,
+                    offset..message.offset_range.end,
+                    &compressed_sections,
+                )
+                .map(MessageContent::Text),
             );
 
             if !request_message.contents_empty() {
+                candidates.push((message, request_message));
+            }
+        }
+
+        let budget = model.map(|model| {
+            let preamble_tokens = completion_request
+                .messages
+                .iter()
+                .map(context_assembly::estimate_message_tokens)
+                .sum::<usize>();
+            model
+                .max_token_count()
+                .saturating_sub(model.max_output_tokens().unwrap_or(0) as usize)
+                .saturating_sub(preamble_tokens)
+        });
+        let texts = candidates
+            .iter()
+            .map(|(_, request_message)| request_message.string_contents())
+            .collect::<Vec<_>>();
+        let relevance = self.relevance_to_last(&texts);
+        let assembly_candidates = candidates
+            .iter()
+            .zip(texts.into_iter().zip(relevance))
+            .map(
+                |((message, request_message), (text, relevance))| AssemblyCandidate {
+                    role: message.role,
+                    text,
+                    tokens: context_assembly::estimate_message_tokens(request_message),
+                    pinned: message.pinned,
+                    relevance,
+                },
+            )
+            .collect::<Vec<_>>();
+        let inclusions =
+            context_assembly::assemble(self.assembly_strategy, &assembly_candidates, budget);
+
+        let shortened = assembly_candidates
+            .iter()
+            .zip(&inclusions)
+            .filter(|(_, inclusion)| **inclusion == Inclusion::Shortened)
+            .map(|(candidate, _)| candidate)
+            .collect::<Vec<_>>();
+        if !shortened.is_empty() {
+            completion_request
+                .messages
+                .push(LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::Text(context_assembly::shortened_text(
+                        &shortened,
+                    ))],
+                    cache: false,
+                });
+        }
+
+        let mut entries = Vec::new();
+        for ((message, request_message), (candidate, inclusion)) in candidates
+            .into_iter()
+            .zip(assembly_candidates.into_iter().zip(inclusions))
+        {
+            entries.push(AssemblyEntry {
+                message_id: message.id,
+                role: message.role,
+                preview: context_assembly::preview(&candidate.text, 60),
+                tokens: candidate.tokens,
+                pinned: candidate.pinned,
+                inclusion,
+            });
+            if inclusion == Inclusion::Included {
                 completion_request.messages.push(request_message);
             }
         }
+        let report = AssemblyReport {
+            strategy: self.assembly_strategy,
+            budget,
+            entries,
+        };
+
         let supports_max_mode = if let Some(model) = model {
             model.supports_max_mode()
         } else {
@@ -2343,22 +4150,292 @@ impl AssistantContext {
         if supports_max_mode {
             completion_request.mode = Some(self.completion_mode.into());
         }
-        completion_request
+        (completion_request, report)
     }
 
     pub fn cancel_last_assist(&mut self, cx: &mut Context<Self>) -> bool {
         if let Some(pending_completion) = self.pending_completions.pop() {
-            self.update_metadata(pending_completion.assistant_message_id, cx, |metadata| {
-                if metadata.status == MessageStatus::Pending {
-                    metadata.status = MessageStatus::Canceled;
+            self.cancel_completion(pending_completion, cx);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn cancel_completion(
+        &mut self,
+        mut pending_completion: PendingCompletion,
+        cx: &mut Context<Self>,
+    ) {
+        let held_back_text = pending_completion.markdown.finish();
+        self.append_to_message(
+            pending_completion.assistant_message_id,
+            &held_back_text,
+            pending_completion.transaction_id,
+            cx,
+        );
+        self.update_metadata(pending_completion.assistant_message_id, cx, |metadata| {
+            if metadata.status == MessageStatus::Pending {
+                metadata.status = MessageStatus::Canceled;
+            }
+        });
+        cx.emit(ContextEvent::CompletionFinished {
+            assistant_message_id: pending_completion.assistant_message_id,
+            usage: None,
+        });
+    }
+
+    /// The operations that can be undone, from oldest to newest.
+    pub fn operation_history(&self) -> &[OperationHistoryEntry] {
+        &self.operation_history
+    }
+
+    pub fn last_undone_operation(&self) -> Option<&OperationHistoryEntry> {
+        self.undone_operations.last()
+    }
+
+    /// Runs `f` in a single buffer transaction, and records it in the
+    /// operation history so that its edits are undone together.
+    fn record_operation<R>(
+        &mut self,
+        kind: HistoryOperationKind,
+        label: SharedString,
+        cx: &mut Context<Self>,
+        f: impl FnOnce(&mut Self, &mut Context<Self>) -> R,
+    ) -> (R, Option<TransactionId>) {
+        self.buffer.update(cx, |buffer, _| {
+            buffer.finalize_last_transaction();
+            buffer.start_transaction();
+        });
+        let result = f(self, cx);
+        // When nested in another operation, the edits are part of the outer transaction.
+        let transaction_id = self.buffer.update(cx, |buffer, cx| {
+            let transaction_id = buffer.end_transaction(cx)?;
+            buffer.finalize_last_transaction();
+            Some(transaction_id)
+        });
+        if let Some(transaction_id) = transaction_id {
+            self.push_operation_history_entry(kind, label, transaction_id, cx);
+        }
+        (result, transaction_id)
+    }
+
+    fn push_operation_history_entry(
+        &mut self,
+        kind: HistoryOperationKind,
+        label: SharedString,
+        transaction_id: TransactionId,
+        cx: &mut Context<Self>,
+    ) {
+        self.operation_history.push(OperationHistoryEntry {
+            kind,
+            label,
+            transaction_id,
+        });
+        self.undone_operations.clear();
+        cx.emit(ContextEvent::OperationHistoryChanged);
+    }
+
+    /// Undoes the most recent operation, reverting all of its edits at once.
+    pub fn undo_operation(&mut self, cx: &mut Context<Self>) -> bool {
+        // Operations whose edits were already undone in the editor are skipped.
+        while let Some(entry) = self.operation_history.pop() {
+            if let Some(ix) = self
+                .pending_completions
+                .iter()
+                .position(|completion| completion.transaction_id == Some(entry.transaction_id))
+            {
+                let pending_completion = self.pending_completions.remove(ix);
+                self.cancel_completion(pending_completion, cx);
+            }
+
+            let undone = self.buffer.update(cx, |buffer, cx| {
+                buffer.undo_transaction(entry.transaction_id, cx)
+            });
+            if undone {
+                self.undone_operations.push(entry);
+                cx.emit(ContextEvent::OperationHistoryChanged);
+                return true;
+            }
+        }
+        cx.emit(ContextEvent::OperationHistoryChanged);
+        false
+    }
+
+    /// Undoes the given operation, along with every operation after it.
+    pub fn undo_to_operation(&mut self, transaction_id: TransactionId, cx: &mut Context<Self>) {
+        while self
+            .operation_history
+            .iter()
+            .any(|entry| entry.transaction_id == transaction_id)
+        {
+            if !self.undo_operation(cx) {
+                break;
+            }
+        }
+    }
+
+    /// Redoes the most recently undone operation.
+    pub fn redo_operation(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(entry) = self.undone_operations.pop() else {
+            return false;
+        };
+        let redone = self.buffer.update(cx, |buffer, cx| {
+            buffer.redo_to_transaction(entry.transaction_id, cx)
+        });
+        if redone {
+            self.operation_history.push(entry);
+        } else {
+            // The buffer's redo history is cleared by new edits.
+            self.undone_operations.clear();
+        }
+        cx.emit(ContextEvent::OperationHistoryChanged);
+        redone
+    }
+
+    /// The reactions to a message, in the order they were first added.
+    pub fn message_reactions(&self, message_id: MessageId) -> Vec<MessageReaction> {
+        let mut reactions = Vec::<(clock::Lamport, MessageReaction)>::new();
+        for ((_, reaction, author), state) in self
+            .reactions
+            .range((message_id, SharedString::default(), SharedString::default())..)
+            .take_while(|((id, _, _), _)| *id == message_id)
+            .filter(|(_, state)| state.added)
+        {
+            match reactions
+                .iter_mut()
+                .find(|(_, existing)| existing.reaction == *reaction)
+            {
+                Some((first_added, existing)) => {
+                    *first_added = (*first_added).min(state.timestamp);
+                    existing.authors.push(author.clone());
                 }
+                None => reactions.push((
+                    state.timestamp,
+                    MessageReaction {
+                        reaction: reaction.clone(),
+                        authors: vec![author.clone()],
+                    },
+                )),
+            }
+        }
+        reactions.sort_by_key(|(first_added, _)| *first_added);
+        reactions
+            .into_iter()
+            .map(|(_, reaction)| reaction)
+            .collect()
+    }
+
+    /// Adds the author's reaction to a message, or removes it if they already reacted.
+    pub fn toggle_reaction(
+        &mut self,
+        message_id: MessageId,
+        reaction: SharedString,
+        author: SharedString,
+        cx: &mut Context<Self>,
+    ) {
+        if !self.messages_metadata.contains_key(&message_id) {
+            return;
+        }
+        let added = !self
+            .reactions
+            .get(&(message_id, reaction.clone(), author.clone()))
+            .is_some_and(|state| state.added);
+        let version = self.version.clone();
+        let timestamp = self.next_timestamp();
+        self.apply_reaction(
+            message_id,
+            reaction.clone(),
+            author.clone(),
+            added,
+            timestamp,
+        );
+        self.push_op(
+            ContextOperation::UpdateReaction {
+                message_id,
+                reaction,
+                author,
+                added,
+                timestamp,
+                version,
+            },
+            cx,
+        );
+        cx.emit(ContextEvent::MessageAnnotationsChanged);
+        cx.notify();
+    }
+
+    fn apply_reaction(
+        &mut self,
+        message_id: MessageId,
+        reaction: SharedString,
+        author: SharedString,
+        added: bool,
+        timestamp: clock::Lamport,
+    ) -> bool {
+        let state = self
+            .reactions
+            .entry((message_id, reaction, author))
+            .or_insert(ReactionState {
+                added: false,
+                timestamp: clock::Lamport::MIN,
             });
+        if timestamp > state.timestamp {
+            *state = ReactionState { added, timestamp };
             true
         } else {
             false
         }
     }
 
+    /// The comments on the context's messages, in the order they were written.
+    pub fn comments(&self) -> &[MessageComment] {
+        &self.comments
+    }
+
+    /// Comments on a range of a message, or replies to the thread started by `parent_id`.
+    pub fn add_comment(
+        &mut self,
+        message_id: MessageId,
+        range: Range<language::Anchor>,
+        parent_id: Option<CommentId>,
+        author: SharedString,
+        text: SharedString,
+        cx: &mut Context<Self>,
+    ) -> Option<CommentId> {
+        if !self.messages_metadata.contains_key(&message_id) {
+            return None;
+        }
+        let version = self.version.clone();
+        let comment = MessageComment {
+            id: CommentId(self.next_timestamp()),
+            message_id,
+            range,
+            parent_id,
+            author,
+            text,
+        };
+        let comment_id = comment.id;
+        self.apply_comment(comment.clone());
+        self.push_op(ContextOperation::AddComment { comment, version }, cx);
+        cx.emit(ContextEvent::MessageAnnotationsChanged);
+        cx.notify();
+        Some(comment_id)
+    }
+
+    fn apply_comment(&mut self, comment: MessageComment) -> bool {
+        match self
+            .comments
+            .binary_search_by_key(&comment.id, |existing| existing.id)
+        {
+            Ok(_) => false,
+            Err(ix) => {
+                self.comments.insert(ix, comment);
+                true
+            }
+        }
+    }
+
     pub fn cycle_message_roles(&mut self, ids: HashSet<MessageId>, cx: &mut Context<Self>) {
         for id in &ids {
             if let Some(metadata) = self.messages_metadata.get(id) {
@@ -2407,6 +4484,23 @@ impl AssistantContext {
         role: Role,
         status: MessageStatus,
         cx: &mut Context<Self>,
+    ) -> Option<MessageAnchor> {
+        let label = format!("Insert {} message", role).into();
+        self.record_operation(
+            HistoryOperationKind::InsertMessage,
+            label,
+            cx,
+            |this, cx| this.insert_message_after_internal(message_id, role, status, cx),
+        )
+        .0
+    }
+
+    fn insert_message_after_internal(
+        &mut self,
+        message_id: MessageId,
+        role: Role,
+        status: MessageStatus,
+        cx: &mut Context<Self>,
     ) -> Option<MessageAnchor> {
         if let Some(prev_message_ix) = self
             .message_anchors
@@ -2457,6 +4551,13 @@ impl AssistantContext {
             status,
             timestamp: anchor.id.0,
             cache: None,
+            usage: None,
+            substitution: None,
+            seed: None,
+            post_processing: Vec::new(),
+            web_sources: Vec::new(),
+            stack_trace: None,
+            pinned: false,
         };
         self.insert_message(anchor.clone(), metadata.clone(), cx);
         self.push_op(
@@ -2497,10 +4598,389 @@ impl AssistantContext {
             .cloned()
     }
 
+    /// Returns every piece of content that was attached to the context and
+    /// is currently contributing to the prompt.
+    pub fn attachments(&self, cx: &App) -> Vec<ContextAttachment> {
+        let buffer = self.buffer.read(cx);
+        let mut attachments = self
+            .slash_command_output_sections
+            .iter()
+            .filter(|section| section.is_valid(buffer))
+            .map(|section| ContextAttachment {
+                range: section.range.clone(),
+                icon: section.icon,
+                label: section.label.clone(),
+                kind: ContextAttachmentKind::SlashCommandOutput {
+                    metadata: section.metadata.clone(),
+                },
+            })
+            .collect::<Vec<_>>();
+        attachments.extend(self.contents(cx).map(|content| match content {
+            Content::Image {
+                anchor,
+                image_id,
+                image,
+                ..
+            } => ContextAttachment {
+                range: anchor..anchor,
+                icon: IconName::Image,
+                label: "Image".into(),
+                kind: ContextAttachmentKind::Image { image_id, image },
+            },
+        }));
+        attachments.sort_by(|a, b| a.range.start.cmp(&b.range.start, buffer));
+        attachments
+    }
+
+    /// Returns the guidelines the user configured for the language that most of
+    /// the attached files are in, as a system message.
+    fn language_preamble_message(&self, cx: &App) -> Option<LanguageModelRequestMessage> {
+        let language_name = self.predominant_attachment_language(cx)?;
+        let preamble = AgentSettings::get_global(cx).language_preamble(language_name.as_ref())?;
+        let prompt = self
+            .prompt_builder
+            .generate_language_preamble_prompt(language_name.as_ref(), preamble)
+            .log_err()?;
+        Some(LanguageModelRequestMessage {
+            role: Role::System,
+            content: vec![prompt.into()],
+            cache: false,
+        })
+    }
+
+    /// Returns the instruction to answer in the text thread's answer
+    /// language, as a system message.
+    fn answer_language_message(&self, cx: &App) -> Option<LanguageModelRequestMessage> {
+        let language = self.answer_language(cx)?;
+        let prompt = self
+            .prompt_builder
+            .generate_answer_language_prompt(&language)
+            .log_err()?;
+        Some(LanguageModelRequestMessage {
+            role: Role::System,
+            content: vec![prompt.into()],
+            cache: false,
+        })
+    }
+
+    /// Returns instructions for debugging the latest stack trace the user
+    /// pasted, as a system message.
+    fn debugging_message(&self, cx: &App) -> Option<LanguageModelRequestMessage> {
+        let stack_trace = self
+            .messages(cx)
+            .filter(|message| message.role == Role::User && message.status == MessageStatus::Done)
+            .filter_map(|message| message.stack_trace)
+            .last()?;
+        let prompt = self
+            .prompt_builder
+            .generate_debugging_prompt(stack_trace.kind.label(), &stack_trace.frames)
+            .log_err()?;
+        Some(LanguageModelRequestMessage {
+            role: Role::System,
+            content: vec![prompt.into()],
+            cache: false,
+        })
+    }
+
+    /// Returns the language that more than half of the attached files are in,
+    /// weighted by their size.
+    fn predominant_attachment_language(&self, cx: &App) -> Option<LanguageName> {
+        let buffer = self.buffer.read(cx);
+        let mut total_len = 0;
+        let mut len_by_language = HashMap::<LanguageName, usize>::default();
+        for attachment in self.attachments(cx) {
+            let Some(path) = attachment.file_path() else {
+                continue;
+            };
+            let len = attachment.range.to_offset(buffer).len();
+            total_len += len;
+            let language_name = Path::new(&path)
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .and_then(|extension| {
+                    self.language_registry
+                        .language_name_for_extension(extension)
+                });
+            if let Some(language_name) = language_name {
+                *len_by_language.entry(language_name).or_default() += len;
+            }
+        }
+        len_by_language
+            .into_iter()
+            .max_by_key(|(_, len)| *len)
+            .filter(|(_, len)| *len * 2 > total_len)
+            .map(|(language_name, _)| language_name)
+    }
+
+    /// Whether the attachment is compressed before being sent to the model.
+    pub fn is_attachment_compressed(&self, attachment: &ContextAttachment, cx: &App) -> bool {
+        match attachment.kind {
+            ContextAttachmentKind::SlashCommandOutput { .. } => {
+                self.is_section_compressed(&attachment.range, cx)
+            }
+            ContextAttachmentKind::Image { .. } => false,
+        }
+    }
+
+    /// Whether the attachment is large enough to be compressed, if compression
+    /// is enabled and it wasn't opted out of.
+    pub fn can_compress_attachment(&self, attachment: &ContextAttachment, cx: &App) -> bool {
+        let buffer = self.buffer.read(cx);
+        AgentSettings::get_global(cx).compress_large_attachments
+            && matches!(
+                attachment.kind,
+                ContextAttachmentKind::SlashCommandOutput { .. }
+            )
+            && attachment.range.to_offset(buffer).len() > COMPRESSION_THRESHOLD
+            && !self.is_attachment_pinned(attachment, cx)
+    }
+
+    /// Whether the attachment is pinned, or is in a pinned message, in which
+    /// case it's always sent in full.
+    pub fn is_attachment_pinned(&self, attachment: &ContextAttachment, cx: &App) -> bool {
+        self.is_range_pinned(&attachment.range, cx)
+    }
+
+    /// Whether the attachment is pinned because its message is pinned.
+    pub fn is_attachment_in_pinned_message(
+        &self,
+        attachment: &ContextAttachment,
+        cx: &App,
+    ) -> bool {
+        self.is_in_pinned_message(&attachment.range, cx)
+    }
+
+    pub fn set_attachment_pinned(
+        &mut self,
+        attachment: &ContextAttachment,
+        pinned: bool,
+        cx: &mut Context<Self>,
+    ) {
+        if pinned {
+            self.pinned_attachments.insert(attachment.range.clone());
+        } else {
+            self.pinned_attachments.remove(&attachment.range);
+        }
+        self.count_remaining_tokens(cx);
+        cx.notify();
+    }
+
+    pub fn set_message_pinned(&mut self, id: MessageId, pinned: bool, cx: &mut Context<Self>) {
+        self.update_metadata(id, cx, |metadata| metadata.pinned = pinned);
+        self.count_remaining_tokens(cx);
+    }
+
+    fn is_range_pinned(&self, range: &Range<language::Anchor>, cx: &App) -> bool {
+        self.pinned_attachments.contains(range) || self.is_in_pinned_message(range, cx)
+    }
+
+    fn has_pinned_content(&self, message: &Message, cx: &App) -> bool {
+        let buffer = self.buffer.read(cx);
+        message.pinned
+            || self.pinned_attachments.iter().any(|range| {
+                range.start.is_valid(buffer)
+                    && message
+                        .offset_range
+                        .contains(&range.start.to_offset(buffer))
+            })
+    }
+
+    fn is_in_pinned_message(&self, range: &Range<language::Anchor>, cx: &App) -> bool {
+        let buffer = self.buffer.read(cx);
+        if !range.start.is_valid(buffer) {
+            return false;
+        }
+        let offset = range.start.to_offset(buffer);
+        self.messages(cx)
+            .any(|message| message.pinned && message.offset_range.contains(&offset))
+    }
+
+    pub fn set_attachment_compressed(
+        &mut self,
+        attachment: &ContextAttachment,
+        compressed: bool,
+        cx: &mut Context<Self>,
+    ) {
+        if compressed {
+            self.uncompressed_attachments.remove(&attachment.range);
+        } else {
+            self.uncompressed_attachments
+                .insert(attachment.range.clone());
+        }
+        self.count_remaining_tokens(cx);
+        cx.notify();
+    }
+
+    fn is_section_compressed(&self, range: &Range<language::Anchor>, cx: &App) -> bool {
+        let buffer = self.buffer.read(cx);
+        AgentSettings::get_global(cx).compress_large_attachments
+            && !self.uncompressed_attachments.contains(range)
+            && range.start.is_valid(buffer)
+            && range.to_offset(buffer).len() > COMPRESSION_THRESHOLD
+            && !self.is_range_pinned(range, cx)
+    }
+
+    pub fn remove_attachment(&mut self, attachment: &ContextAttachment, cx: &mut Context<Self>) {
+        match &attachment.kind {
+            ContextAttachmentKind::SlashCommandOutput { .. } => {
+                self.remove_attached_text(attachment.range.clone(), cx);
+            }
+            ContextAttachmentKind::Image { image_id, .. } => {
+                self.contents.retain(|content| match content {
+                    Content::Image { image_id: id, .. } => id != image_id,
+                });
+                cx.emit(ContextEvent::MessagesEdited);
+            }
+        }
+    }
+
+    /// Removes attached text, such as the earlier copy of content that was
+    /// attached twice.
+    pub fn remove_attached_text(&mut self, range: Range<language::Anchor>, cx: &mut Context<Self>) {
+        self.buffer.update(cx, |buffer, cx| {
+            let range = range.to_offset(buffer);
+            // Also remove the newline that separates the attachment from what follows.
+            let end = if buffer.chars_at(range.end).next() == Some('\n') {
+                range.end + 1
+            } else {
+                range.end
+            };
+            buffer.edit([(range.start..end, "")], None, cx);
+        });
+    }
+
+    /// Returns the range of an earlier copy of the text in the given range, if
+    /// the same content was already attached further up in the context.
+    pub fn earlier_duplicate(
+        &self,
+        range: &Range<language::Anchor>,
+        cx: &App,
+    ) -> Option<Range<language::Anchor>> {
+        let buffer = self.buffer.read(cx);
+        if !range.start.is_valid(buffer) || !range.end.is_valid(buffer) {
+            return None;
+        }
+        let range = range.to_offset(buffer);
+        if range.len() < MIN_DUPLICATE_ATTACHMENT_LEN {
+            return None;
+        }
+        let text = buffer.text_for_range(range.clone()).collect::<String>();
+        let preceding_text = buffer.text_for_range(0..range.start).collect::<String>();
+        let start = preceding_text.rfind(&text)?;
+        Some(buffer.anchor_after(start)..buffer.anchor_before(start + text.len()))
+    }
+
+    /// Replaces content that duplicates an earlier attachment with a short
+    /// note pointing the model at the earlier copy.
+    pub fn reference_earlier_duplicate(
+        &mut self,
+        duplicate: Range<language::Anchor>,
+        icon: IconName,
+        label: SharedString,
+        cx: &mut Context<Self>,
+    ) {
+        let reference = format!("(Same as the {label} attached earlier in this conversation.)");
+        let range = self.buffer.update(cx, |buffer, cx| {
+            let range = duplicate.to_offset(buffer);
+            buffer.edit([(range.clone(), reference.as_str())], None, cx);
+            buffer.anchor_after(range.start)..buffer.anchor_before(range.start + reference.len())
+        });
+        self.insert_slash_command_output_section(
+            SlashCommandOutputSection {
+                range,
+                icon,
+                label: format!("Reference to {label}").into(),
+                metadata: None,
+            },
+            cx,
+        );
+    }
+
+    /// Replaces the given attachment with a summary generated by the default model.
+    pub fn summarize_attachment(
+        &mut self,
+        attachment: ContextAttachment,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        let ContextAttachmentKind::SlashCommandOutput { .. } = attachment.kind else {
+            return Task::ready(Err(anyhow!("only text attachments can be summarized")));
+        };
+        let Some(model) = self.model(cx) else {
+            return Task::ready(Err(anyhow!("no default model configured")));
+        };
+
+        let text = self
+            .buffer
+            .read(cx)
+            .text_for_range(attachment.range.clone())
+            .collect::<String>();
+        let request = LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![
+                    format!(
+                        "Summarize the following content so that it can stand in for the original \
+                        in a conversation about it. Preserve names, signatures and any details \
+                        that are likely to matter. Go straight to the summary, without any preamble.\n\n\
+                        {text}"
+                    )
+                    .into(),
+                ],
+                cache: false,
+            }],
+            priority: RequestPriority::Background,
+            ..Default::default()
+        };
+
+        cx.spawn(async move |this, cx| {
+            let mut summary = String::new();
+            let mut stream = model.model.stream_completion_text(request, &cx).await?;
+            while let Some(chunk) = stream.stream.next().await {
+                summary.push_str(&chunk?);
+            }
+            let summary = summary.trim();
+            if summary.is_empty() {
+                bail!("Model generated an empty summary");
+            }
+
+            this.update(cx, |this, cx| {
+                let range = this.buffer.update(cx, |buffer, cx| {
+                    let range = attachment.range.to_offset(buffer);
+                    buffer.edit([(range.clone(), summary)], None, cx);
+                    buffer.anchor_after(range.start)
+                        ..buffer.anchor_before(range.start + summary.len())
+                });
+                this.insert_slash_command_output_section(
+                    SlashCommandOutputSection {
+                        range,
+                        icon: attachment.icon,
+                        label: format!("Summary of {}", attachment.label).into(),
+                        metadata: None,
+                    },
+                    cx,
+                );
+            })
+        })
+    }
+
     pub fn split_message(
         &mut self,
         range: Range<usize>,
         cx: &mut Context<Self>,
+    ) -> (Option<MessageAnchor>, Option<MessageAnchor>) {
+        self.record_operation(
+            HistoryOperationKind::InsertMessage,
+            "Split message".into(),
+            cx,
+            |this, cx| this.split_message_internal(range, cx),
+        )
+        .0
+    }
+
+    fn split_message_internal(
+        &mut self,
+        range: Range<usize>,
+        cx: &mut Context<Self>,
     ) -> (Option<MessageAnchor>, Option<MessageAnchor>) {
         let start_message = self.message_for_offset(range.start, cx);
         let end_message = self.message_for_offset(range.end, cx);
@@ -2549,6 +5029,13 @@ impl AssistantContext {
                 status: MessageStatus::Done,
                 timestamp: suffix.id.0,
                 cache: None,
+                usage: None,
+                substitution: None,
+                seed: None,
+                post_processing: Vec::new(),
+                web_sources: Vec::new(),
+                stack_trace: None,
+                pinned: false,
             };
             self.insert_message(suffix.clone(), suffix_metadata.clone(), cx);
             self.push_op(
@@ -2599,6 +5086,13 @@ impl AssistantContext {
                         status: MessageStatus::Done,
                         timestamp: selection.id.0,
                         cache: None,
+                        usage: None,
+                        substitution: None,
+                        seed: None,
+                        post_processing: Vec::new(),
+                        web_sources: Vec::new(),
+                        stack_trace: None,
+                        pinned: false,
                     };
                     self.insert_message(selection.clone(), selection_metadata.clone(), cx);
                     self.push_op(
@@ -2655,6 +5149,7 @@ impl AssistantContext {
             }
 
             let mut request = self.to_completion_request(Some(&model.model), cx);
+            request.priority = RequestPriority::Background;
             request.messages.push(LanguageModelRequestMessage {
                 role: Role::User,
                 content: vec![
@@ -2840,31 +5335,55 @@ impl AssistantContext {
                     role: metadata.role,
                     status: metadata.status.clone(),
                     cache: metadata.cache.clone(),
+                    usage: metadata.usage,
+                    substitution: metadata.substitution.clone(),
+                    seed: metadata.seed,
+                    post_processing: metadata.post_processing.clone(),
+                    web_sources: metadata.web_sources.clone(),
+                    stack_trace: metadata.stack_trace.clone(),
+                    pinned: metadata.pinned,
                 });
             }
             None
         })
     }
 
-    pub fn save(
-        &mut self,
-        debounce: Option<Duration>,
-        fs: Arc<dyn Fs>,
-        cx: &mut Context<AssistantContext>,
-    ) {
-        if self.replica_id() != ReplicaId::default() {
+    pub fn save(&mut self, reason: SaveReason, cx: &mut Context<AssistantContext>) {
+        if self.replica_id() != ReplicaId::default()
+            && !context_sync::is_local_replica(self.replica_id())
+        {
             // Prevent saving a remote context for now.
             return;
         }
+        if self.is_loading() {
+            return;
+        }
 
+        self.save_deadlines
+            .insert(reason, Instant::now() + reason.debounce());
+        let deadline = self
+            .save_deadlines
+            .values()
+            .min()
+            .copied()
+            .unwrap_or_else(Instant::now);
         self.pending_save = cx.spawn(async move |this, cx| {
-            if let Some(debounce) = debounce {
+            let debounce = deadline.saturating_duration_since(Instant::now());
+            if !debounce.is_zero() {
                 cx.background_executor().timer(debounce).await;
             }
+            this.update(cx, |this, _| this.save_deadlines.clear())?;
 
-            let (old_path, summary) = this.read_with(cx, |this, _| {
+            let (old_path, summary) = this.read_with(cx, |this, cx| {
                 let path = this.path.clone();
-                let summary = if let Some(summary) = this.summary.content() {
+                let discard = path.is_none()
+                    && this.is_empty(cx)
+                    && AgentSettings::get_global(cx)
+                        .text_thread_retention
+                        .delete_empty;
+                let summary = if discard {
+                    None
+                } else if let Some(summary) = this.summary.content() {
                     if summary.done {
                         Some(summary.text.clone())
                     } else {
@@ -2877,39 +5396,28 @@ impl AssistantContext {
             })?;
 
             if let Some(summary) = summary {
-                let context = this.read_with(cx, |this, cx| this.serialize(cx))?;
-                let mut discriminant = 1;
-                let mut new_path;
-                loop {
-                    new_path = contexts_dir().join(&format!(
-                        "{} - {}.zed.json",
-                        summary.trim(),
-                        discriminant
-                    ));
-                    if fs.is_file(&new_path).await {
-                        discriminant += 1;
-                    } else {
-                        break;
-                    }
-                }
-
-                fs.create_dir(contexts_dir().as_ref()).await?;
-                fs.atomic_write(new_path.clone(), serde_json::to_string(&context).unwrap())
+                let context = this
+                    .read_with(cx, |this, cx| this.serialize_in_background(cx))?
+                    .await;
+                let compaction_threshold = cx.update(|cx| {
+                    AgentSettings::get_global(cx)
+                        .text_thread_retention
+                        .compact_outputs_larger_than_kb
+                })?;
+                let database = cx
+                    .update(|cx| TextThreadsDatabase::global_future(cx))?
+                    .await
+                    .map_err(|err| anyhow!(err))?;
+                let new_path = database
+                    .save(
+                        old_path,
+                        summary,
+                        context,
+                        compaction_threshold.map(|kb| kb as usize * 1024),
+                    )
                     .await?;
-                if let Some(old_path) = old_path {
-                    if new_path.as_path() != old_path.as_ref() {
-                        fs.remove_file(
-                            &old_path,
-                            RemoveOptions {
-                                recursive: false,
-                                ignore_if_not_exists: true,
-                            },
-                        )
-                        .await?;
-                    }
-                }
 
-                this.update(cx, |this, _| this.path = Some(new_path.into()))?;
+                this.update(cx, |this, _| this.path = Some(new_path))?;
             }
 
             Ok(())
@@ -2926,6 +5434,44 @@ impl AssistantContext {
     }
 }
 
+fn model_selection(model: &Arc<dyn LanguageModel>) -> LanguageModelSelection {
+    LanguageModelSelection {
+        provider: model.provider_id().0.to_string().into(),
+        model: model.id().0.to_string(),
+    }
+}
+
+/// Returns a free model to use in place of the given one when the user has
+/// opted into switching automatically.
+pub fn free_model_substitute(
+    model: &Arc<dyn LanguageModel>,
+    reason: ModelSubstitutionReason,
+    cx: &App,
+) -> Option<(Arc<dyn LanguageModel>, ModelSubstitution)> {
+    if !AgentSettings::get_global(cx).auto_switch_to_free_model {
+        return None;
+    }
+    let substitute = LanguageModelRegistry::read_global(cx).free_model_substitute(model, cx)?;
+    let substitution = ModelSubstitution {
+        original_model: model.name().0.to_string(),
+        substitute_model: substitute.name().0.to_string(),
+        reason,
+    };
+    Some((substitute, substitution))
+}
+
+/// Whether the error is a dropped connection, after which a response can be
+/// resumed by another request.
+fn is_disconnect(error: &anyhow::Error) -> bool {
+    let is_network =
+        |error: &LanguageModelError| matches!(error, LanguageModelError::Network { .. });
+    match error.downcast_ref::<LanguageModelError>() {
+        Some(error) => is_network(error),
+        None => LanguageModelError::from_message(&format!("{error:#}"))
+            .is_some_and(|error| is_network(&error)),
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct ContextVersion {
     context: clock::Global,
@@ -3022,11 +5568,65 @@ pub struct SavedContext {
         Vec<assistant_slash_command::SlashCommandOutputSection<usize>>,
     #[serde(default)]
     pub thought_process_output_sections: Vec<ThoughtProcessOutputSection<usize>>,
+    /// The offsets at which responses were continued after being cut off by
+    /// the output token limit.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub continuation_seams: Vec<usize>,
+    /// The ranges of slash command outputs whose text was moved into a
+    /// sidecar file when saving, in the coordinates of the full text.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compacted_sections: Vec<Range<usize>>,
+    /// How the messages are assembled into requests.
+    #[serde(default)]
+    pub assembly_strategy: AssemblyStrategy,
+    /// The names of the worktrees of the project the text thread was last
+    /// saved from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// The model the text thread uses instead of the default one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<LanguageModelSelection>,
+    /// The environment variables passed to the slash commands it runs.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub environment: BTreeMap<String, String>,
+    /// The language the model is asked to answer in, instead of the one in
+    /// the settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer_language: Option<String>,
+    /// The seed sent with requests instead of the one in the settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// The patterns that end a response once its text matches one of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_conditions: Vec<StopCondition>,
+    /// The post-processors run on completed responses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_processors: Vec<PostProcessor>,
+    /// Whether the model may search the web with its provider's search tool.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub web_search: bool,
+    /// The tokens used by every response and their cost.
+    #[serde(default, skip_serializing_if = "CostTracker::is_empty")]
+    pub cost_tracker: CostTracker,
 }
 
 impl SavedContext {
     pub const VERSION: &'static str = "0.4.0";
 
+    /// The tokens used by the text thread and their cost, totaled from the
+    /// usage of its messages if it was saved before they were tracked.
+    pub fn cost_tracker(&self) -> CostTracker {
+        if self.cost_tracker.is_empty() {
+            CostTracker::from_message_usages(
+                self.messages
+                    .iter()
+                    .filter_map(|message| message.metadata.usage),
+            )
+        } else {
+            self.cost_tracker
+        }
+    }
+
     pub fn from_json(json: &str) -> Result<Self> {
         let saved_context_json = serde_json::from_str::<serde_json::Value>(json)?;
         match saved_context_json
@@ -3058,56 +5658,257 @@ impl SavedContext {
         }
     }
 
-    fn into_ops(
-        self,
+    /// Concatenates the text threads into a new one, in the given order.
+    /// Their messages are renumbered so that their ids don't collide.
+    pub fn merge(contexts: Vec<SavedContext>) -> SavedContext {
+        let mut merged = SavedContext {
+            id: None,
+            zed: "context".into(),
+            version: SavedContext::VERSION.into(),
+            text: String::new(),
+            messages: Vec::new(),
+            summary: String::new(),
+            slash_command_output_sections: Vec::new(),
+            thought_process_output_sections: Vec::new(),
+            continuation_seams: Vec::new(),
+            compacted_sections: Vec::new(),
+            assembly_strategy: AssemblyStrategy::default(),
+            project: None,
+            model: None,
+            environment: BTreeMap::default(),
+            answer_language: None,
+            seed: None,
+            stop_conditions: Vec::new(),
+            post_processors: Vec::new(),
+            web_search: false,
+            cost_tracker: CostTracker::default(),
+        };
+        let mut next_id = clock::Lamport::new(ReplicaId::default());
+        for context in contexts {
+            if !merged.text.is_empty() && !merged.text.ends_with('\n') {
+                merged.text.push('\n');
+            }
+            let offset = merged.text.len();
+            merged.text.push_str(&context.text);
+            merged.project = merged.project.or(context.project);
+            merged.environment.extend(context.environment);
+            merged.answer_language = merged.answer_language.or(context.answer_language);
+            merged.seed = merged.seed.or(context.seed);
+            for condition in context.stop_conditions {
+                if !merged.stop_conditions.contains(&condition) {
+                    merged.stop_conditions.push(condition);
+                }
+            }
+            for processor in context.post_processors {
+                if !merged.post_processors.contains(&processor) {
+                    merged.post_processors.push(processor);
+                }
+            }
+            merged.web_search |= context.web_search;
+            merged.cost_tracker.merge(&context.cost_tracker());
+            for mut message in context.messages {
+                // The first message of the merged text thread keeps the id
+                // that marks a text thread's first message.
+                let id = if merged.messages.is_empty() {
+                    clock::Lamport::default()
+                } else {
+                    next_id.tick()
+                };
+                message.id = MessageId(id);
+                message.start += offset;
+                message.metadata.timestamp = id;
+                merged.messages.push(message);
+            }
+            merged.slash_command_output_sections.extend(
+                context
+                    .slash_command_output_sections
+                    .into_iter()
+                    .map(|mut section| {
+                        section.range = section.range.start + offset..section.range.end + offset;
+                        section
+                    }),
+            );
+            merged.thought_process_output_sections.extend(
+                context
+                    .thought_process_output_sections
+                    .into_iter()
+                    .map(|mut section| {
+                        section.range = section.range.start + offset..section.range.end + offset;
+                        section
+                    }),
+            );
+            merged.continuation_seams.extend(
+                context
+                    .continuation_seams
+                    .into_iter()
+                    .map(|seam| seam + offset),
+            );
+        }
+        merged
+    }
+
+    /// Drops the messages after the one with the given id, along with their
+    /// text and the sections in it.
+    pub fn truncate_after(&mut self, message_id: MessageId) {
+        let Some(ix) = self
+            .messages
+            .iter()
+            .position(|message| message.id == message_id)
+        else {
+            return;
+        };
+        let end = self
+            .messages
+            .get(ix + 1)
+            .map_or(self.text.len(), |message| message.start);
+        self.messages.truncate(ix + 1);
+        self.text.truncate(end);
+        self.slash_command_output_sections
+            .retain(|section| section.range.end <= end);
+        self.thought_process_output_sections
+            .retain(|section| section.range.end <= end);
+        self.continuation_seams.retain(|seam| *seam <= end);
+    }
+}
+
+/// Loads a saved text thread into a context's buffer, a part of its text at
+/// a time, producing the operations that restore its messages as their text
+/// is loaded.
+struct SavedContextLoader {
+    text: String,
+    loaded_len: usize,
+    messages: iter::Peekable<std::vec::IntoIter<SavedMessage>>,
+    summary: Option<String>,
+    slash_command_output_sections: Vec<assistant_slash_command::SlashCommandOutputSection<usize>>,
+    thought_process_output_sections: Vec<ThoughtProcessOutputSection<usize>>,
+    continuation_seams: Vec<usize>,
+    version: clock::Global,
+    next_timestamp: clock::Lamport,
+}
+
+impl SavedContextLoader {
+    fn new(saved_context: SavedContext) -> Self {
+        // Timestamps are only handed out once all message ids are observed,
+        // so that they don't collide with the ids of messages loaded later.
+        let mut next_timestamp = clock::Lamport::new(ReplicaId::default());
+        for message in &saved_context.messages {
+            next_timestamp.observe(message.id.0);
+        }
+        Self {
+            text: saved_context.text,
+            loaded_len: 0,
+            messages: saved_context.messages.into_iter().peekable(),
+            summary: Some(saved_context.summary),
+            slash_command_output_sections: saved_context.slash_command_output_sections,
+            thought_process_output_sections: saved_context.thought_process_output_sections,
+            continuation_seams: saved_context.continuation_seams,
+            version: clock::Global::new(),
+            next_timestamp,
+        }
+    }
+
+    fn is_done(&mut self) -> bool {
+        self.messages.peek().is_none() && self.loaded_len == self.text.len()
+    }
+
+    /// Appends the text of the next messages to the buffer, stopping once
+    /// about `max_len` bytes of it are loaded, and returns the operations
+    /// that insert those messages.
+    fn load_messages(
+        &mut self,
+        max_len: usize,
         buffer: &Entity<Buffer>,
-        cx: &mut Context<AssistantContext>,
+        cx: &mut App,
     ) -> Vec<ContextOperation> {
+        let loaded_len = self.loaded_len;
+        let mut messages = Vec::new();
+        while let Some(message) = self.messages.next_if(|message| {
+            messages.is_empty() || message.start.saturating_sub(loaded_len) < max_len
+        }) {
+            messages.push(message);
+        }
+        let end = self
+            .messages
+            .peek()
+            .map_or(self.text.len(), |message| message.start)
+            .clamp(loaded_len, self.text.len());
+        let end = (end..self.text.len())
+            .find(|ix| self.text.is_char_boundary(*ix))
+            .unwrap_or(self.text.len());
+        if end > loaded_len {
+            let text = &self.text[loaded_len..end];
+            buffer.update(cx, |buffer, cx| {
+                let len = buffer.len();
+                buffer.edit([(len..len, text)], None, cx);
+            });
+            self.loaded_len = end;
+        }
+
         let mut operations = Vec::new();
-        let mut version = clock::Global::new();
-        let mut next_timestamp = clock::Lamport::new(ReplicaId::default());
+        if let Some(summary) = self.summary.take() {
+            let timestamp = self.next_timestamp.tick();
+            operations.push(ContextOperation::UpdateSummary {
+                summary: ContextSummaryContent {
+                    text: summary,
+                    done: true,
+                    timestamp,
+                },
+                version: self.version.clone(),
+            });
+            self.version.observe(timestamp);
+        }
 
-        let mut first_message_metadata = None;
-        for message in self.messages {
+        for message in messages {
+            let metadata = MessageMetadata {
+                role: message.metadata.role,
+                status: message.metadata.status,
+                timestamp: message.metadata.timestamp,
+                cache: None,
+                usage: message.metadata.usage,
+                substitution: message.metadata.substitution,
+                seed: message.metadata.seed,
+                post_processing: message.metadata.post_processing,
+                web_sources: message.metadata.web_sources,
+                stack_trace: message.metadata.stack_trace,
+                pinned: message.metadata.pinned,
+            };
             if message.id == MessageId(clock::Lamport::default()) {
-                first_message_metadata = Some(message.metadata);
+                let timestamp = self.next_timestamp.tick();
+                operations.push(ContextOperation::UpdateMessage {
+                    message_id: message.id,
+                    metadata: MessageMetadata {
+                        timestamp,
+                        ..metadata
+                    },
+                    version: self.version.clone(),
+                });
+                self.version.observe(timestamp);
             } else {
                 operations.push(ContextOperation::InsertMessage {
                     anchor: MessageAnchor {
                         id: message.id,
                         start: buffer.read(cx).anchor_before(message.start),
                     },
-                    metadata: MessageMetadata {
-                        role: message.metadata.role,
-                        status: message.metadata.status,
-                        timestamp: message.metadata.timestamp,
-                        cache: None,
-                    },
-                    version: version.clone(),
+                    metadata,
+                    version: self.version.clone(),
                 });
-                version.observe(message.id.0);
-                next_timestamp.observe(message.id.0);
+                self.version.observe(message.id.0);
             }
         }
+        operations
+    }
 
-        if let Some(metadata) = first_message_metadata {
-            let timestamp = next_timestamp.tick();
-            operations.push(ContextOperation::UpdateMessage {
-                message_id: MessageId(clock::Lamport::default()),
-                metadata: MessageMetadata {
-                    role: metadata.role,
-                    status: metadata.status,
-                    timestamp,
-                    cache: None,
-                },
-                version: version.clone(),
-            });
-            version.observe(timestamp);
-        }
-
+    /// Returns the operations that restore the output sections once all of
+    /// the text is loaded, along with the offsets of the continuation seams.
+    fn finish(
+        mut self,
+        buffer: &Entity<Buffer>,
+        cx: &mut App,
+    ) -> (Vec<ContextOperation>, Vec<usize>) {
+        let mut operations = Vec::new();
         let buffer = buffer.read(cx);
         for section in self.slash_command_output_sections {
-            let timestamp = next_timestamp.tick();
+            let timestamp = self.next_timestamp.tick();
             operations.push(ContextOperation::SlashCommandOutputSectionAdded {
                 timestamp,
                 section: SlashCommandOutputSection {
@@ -3117,38 +5918,25 @@ impl SavedContext {
                     label: section.label,
                     metadata: section.metadata,
                 },
-                version: version.clone(),
+                version: self.version.clone(),
             });
-
-            version.observe(timestamp);
+            self.version.observe(timestamp);
         }
 
         for section in self.thought_process_output_sections {
-            let timestamp = next_timestamp.tick();
+            let timestamp = self.next_timestamp.tick();
             operations.push(ContextOperation::ThoughtProcessOutputSectionAdded {
                 timestamp,
                 section: ThoughtProcessOutputSection {
                     range: buffer.anchor_after(section.range.start)
                         ..buffer.anchor_before(section.range.end),
                 },
-                version: version.clone(),
+                version: self.version.clone(),
             });
-
-            version.observe(timestamp);
+            self.version.observe(timestamp);
         }
 
-        let timestamp = next_timestamp.tick();
-        operations.push(ContextOperation::UpdateSummary {
-            summary: ContextSummaryContent {
-                text: self.summary,
-                done: true,
-                timestamp,
-            },
-            version: version.clone(),
-        });
-        version.observe(timestamp);
-
-        operations
+        (operations, self.continuation_seams)
     }
 }
 
@@ -3205,6 +5993,13 @@ impl SavedContextV0_3_0 {
                             status: metadata.status.clone(),
                             timestamp,
                             cache: None,
+                            usage: None,
+                            substitution: None,
+                            seed: None,
+                            post_processing: Vec::new(),
+                            web_sources: Vec::new(),
+                            stack_trace: None,
+                            pinned: false,
                         },
                     })
                 })
@@ -3212,6 +6007,18 @@ impl SavedContextV0_3_0 {
             summary: self.summary,
             slash_command_output_sections: self.slash_command_output_sections,
             thought_process_output_sections: Vec::new(),
+            continuation_seams: Vec::new(),
+            compacted_sections: Vec::new(),
+            assembly_strategy: AssemblyStrategy::default(),
+            project: None,
+            model: None,
+            environment: BTreeMap::default(),
+            answer_language: None,
+            seed: None,
+            stop_conditions: Vec::new(),
+            post_processors: Vec::new(),
+            web_search: false,
+            cost_tracker: CostTracker::default(),
         }
     }
 }
@@ -3280,4 +6087,29 @@ pub struct SavedContextMetadata {
     pub title: String,
     pub path: Arc<Path>,
     pub mtime: chrono::DateTime<chrono::Local>,
+    pub tags: Vec<String>,
+    /// The project the text thread was saved from.
+    pub project: Option<String>,
+    pub archived: bool,
+}
+
+/// Applies edits to the buffer as part of an existing transaction, so that
+/// they're undone along with it.
+fn extend_transaction<R>(
+    buffer: &mut Buffer,
+    transaction_id: Option<TransactionId>,
+    cx: &mut Context<Buffer>,
+    edit: impl FnOnce(&mut Buffer, &mut Context<Buffer>) -> R,
+) -> R {
+    let Some(transaction_id) = transaction_id else {
+        return edit(buffer, cx);
+    };
+    buffer.finalize_last_transaction();
+    buffer.start_transaction();
+    let result = edit(buffer, cx);
+    if let Some(transaction) = buffer.end_transaction(cx) {
+        buffer.merge_transactions(transaction, transaction_id);
+        buffer.finalize_last_transaction();
+    }
+    result
 }
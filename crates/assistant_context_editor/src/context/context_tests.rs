@@ -1,13 +1,17 @@
+use super::{COMPRESSION_THRESHOLD, SLASH_COMMAND_OUTPUT_PAGE_LINES, is_disconnect};
 use crate::{
     AssistantContext, CacheStatus, ContextEvent, ContextId, ContextOperation, ContextSummary,
-    InvokedSlashCommandId, MessageCacheMetadata, MessageId, MessageStatus,
+    InvokedSlashCommandId, MessageCacheMetadata, MessageId, MessageStatus, MessageUsage,
+    ModelSubstitution, ModelSubstitutionReason, PostProcessingChange, PostProcessor, SavedContext,
+    StackTraceKind, StackTraceTag, context_sync,
 };
+use agent_settings::AgentSettings;
 use anyhow::Result;
 use assistant_slash_command::{
     ArgumentCompletion, SlashCommand, SlashCommandContent, SlashCommandEvent, SlashCommandOutput,
     SlashCommandOutputSection, SlashCommandRegistry, SlashCommandResult, SlashCommandWorkingSet,
 };
-use assistant_slash_commands::FileSlashCommand;
+use assistant_slash_commands::{DiagnosticsSlashCommand, FileSlashCommand};
 use collections::{HashMap, HashSet};
 use fs::FakeFs;
 use futures::{
@@ -17,7 +21,8 @@ use futures::{
 use gpui::{App, Entity, SharedString, Task, TestAppContext, WeakEntity, prelude::*};
 use language::{Buffer, BufferSnapshot, LanguageRegistry, LspAdapterDelegate};
 use language_model::{
-    ConfiguredModel, LanguageModelCacheConfiguration, LanguageModelRegistry, Role,
+    ConfiguredModel, LanguageModelCacheConfiguration, LanguageModelError, LanguageModelRegistry,
+    LanguageModelRequest, Role, StopReason, TokenUsage,
     fake_provider::{FakeLanguageModel, FakeLanguageModelProvider},
 };
 use parking_lot::Mutex;
@@ -26,7 +31,7 @@ use project::Project;
 use prompt_store::PromptBuilder;
 use rand::prelude::*;
 use serde_json::json;
-use settings::SettingsStore;
+use settings::{Settings as _, SettingsStore};
 use std::{
     cell::RefCell,
     env,
@@ -35,7 +40,7 @@ use std::{
     rc::Rc,
     sync::{Arc, atomic::AtomicBool},
 };
-use text::{ReplicaId, ToOffset, network::Network};
+use text::{OffsetRangeExt, ReplicaId, ToOffset, network::Network};
 use ui::{IconName, Window};
 use unindent::Unindent;
 use util::RandomCharIter;
@@ -179,6 +184,602 @@ fn test_inserting_and_removing_messages(cx: &mut App) {
     );
 }
 
+#[gpui::test]
+fn test_undoing_and_redoing_operations(cx: &mut App) {
+    init_test(cx);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.background_executor().clone()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let context = cx.new(|cx| {
+        AssistantContext::local(
+            registry,
+            None,
+            None,
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            cx,
+        )
+    });
+    let buffer = context.read(cx).buffer.clone();
+
+    let message_1 = context.read(cx).message_anchors[0].clone();
+    buffer.update(cx, |buffer, cx| buffer.edit([(0..0, "a")], None, cx));
+    let message_2 = context.update(cx, |context, cx| {
+        context
+            .insert_message_after(message_1.id, Role::Assistant, MessageStatus::Done, cx)
+            .unwrap()
+    });
+    buffer.update(cx, |buffer, cx| buffer.edit([(2..2, "b")], None, cx));
+    let message_3 = context.update(cx, |context, cx| {
+        context
+            .insert_message_after(message_2.id, Role::User, MessageStatus::Done, cx)
+            .unwrap()
+    });
+    assert_eq!(buffer.read(cx).text(), "a\nb\n");
+    let first_operation = context.read(cx).operation_history()[0].transaction_id;
+    assert_eq!(
+        context
+            .read(cx)
+            .operation_history()
+            .iter()
+            .map(|entry| entry.label.clone())
+            .collect::<Vec<_>>(),
+        ["Insert assistant message", "Insert user message"]
+    );
+
+    // Undoing an operation reverts the whole message insertion, but not the text typed before it.
+    assert!(context.update(cx, |context, cx| context.undo_operation(cx)));
+    assert_eq!(buffer.read(cx).text(), "a\nb");
+    assert_eq!(
+        messages(&context, cx),
+        vec![
+            (message_1.id, Role::User, 0..2),
+            (message_2.id, Role::Assistant, 2..3)
+        ]
+    );
+
+    assert!(context.update(cx, |context, cx| context.redo_operation(cx)));
+    assert_eq!(buffer.read(cx).text(), "a\nb\n");
+    assert_eq!(
+        messages(&context, cx),
+        vec![
+            (message_1.id, Role::User, 0..2),
+            (message_2.id, Role::Assistant, 2..4),
+            (message_3.id, Role::User, 4..4)
+        ]
+    );
+    assert!(!context.update(cx, |context, cx| context.redo_operation(cx)));
+
+    // Jumping back to an operation undoes it along with every later operation.
+    context.update(cx, |context, cx| {
+        context.undo_to_operation(first_operation, cx)
+    });
+    assert_eq!(buffer.read(cx).text(), "ab");
+    assert_eq!(
+        messages(&context, cx),
+        vec![(message_1.id, Role::User, 0..2)]
+    );
+    assert!(context.read(cx).operation_history().is_empty());
+}
+
+#[gpui::test]
+async fn test_message_reactions_and_comments(cx: &mut TestAppContext) {
+    cx.update(init_test);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.background_executor.clone()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let context_id = ContextId::new();
+    let [host, guest] = [0, 1].map(|replica_id| {
+        cx.new(|cx| {
+            AssistantContext::new(
+                context_id.clone(),
+                replica_id,
+                language::Capability::ReadWrite,
+                registry.clone(),
+                prompt_builder.clone(),
+                Arc::new(SlashCommandWorkingSet::default()),
+                None,
+                None,
+                cx,
+            )
+        })
+    });
+
+    let message = host.update(cx, |context, cx| {
+        let message_1 = context.message_anchors[0].clone();
+        context.buffer.update(cx, |buffer, cx| {
+            buffer.edit([(0..0, "Which approach?")], None, cx)
+        });
+        context
+            .insert_message_after(message_1.id, Role::Assistant, MessageStatus::Done, cx)
+            .unwrap()
+    });
+    host.update(cx, |context, cx| {
+        context.buffer.update(cx, |buffer, cx| {
+            buffer.edit([(16..16, "Use a channel.")], None, cx)
+        })
+    });
+
+    // Toggling a reaction twice removes it again.
+    host.update(cx, |context, cx| {
+        context.toggle_reaction(message.id, "👍".into(), "alice".into(), cx);
+        context.toggle_reaction(message.id, "🎉".into(), "alice".into(), cx);
+        context.toggle_reaction(message.id, "🎉".into(), "alice".into(), cx);
+    });
+    let (root_id, range) = host.update(cx, |context, cx| {
+        let range =
+            context.buffer.read(cx).anchor_after(16)..context.buffer.read(cx).anchor_before(30);
+        let root_id = context
+            .add_comment(
+                message.id,
+                range.clone(),
+                None,
+                "alice".into(),
+                "Why not a mutex?".into(),
+                cx,
+            )
+            .unwrap();
+        (root_id, range)
+    });
+
+    sync_contexts(&host, &guest, cx).await;
+    guest.update(cx, |context, cx| {
+        context.toggle_reaction(message.id, "👍".into(), "bob".into(), cx);
+        context.add_comment(
+            message.id,
+            range,
+            Some(root_id),
+            "bob".into(),
+            "Less contention.".into(),
+            cx,
+        );
+    });
+    sync_contexts(&host, &guest, cx).await;
+
+    for context in [&host, &guest] {
+        cx.read(|cx| {
+            let context = context.read(cx);
+            let reactions = context.message_reactions(message.id);
+            assert_eq!(reactions.len(), 1);
+            assert_eq!(reactions[0].reaction, SharedString::from("👍"));
+            assert_eq!(
+                reactions[0].authors,
+                ["alice", "bob"].map(SharedString::from)
+            );
+            assert_eq!(
+                context
+                    .comments()
+                    .iter()
+                    .map(|comment| (comment.parent_id, comment.text.clone()))
+                    .collect::<Vec<_>>(),
+                [
+                    (None, "Why not a mutex?".into()),
+                    (Some(root_id), "Less contention.".into())
+                ]
+            );
+            let comment_range = &context.comments()[0].range;
+            let buffer = context.buffer.read(cx).snapshot();
+            assert_eq!(
+                buffer
+                    .text_for_range(comment_range.clone())
+                    .collect::<String>(),
+                "Use a channel."
+            );
+        });
+    }
+}
+
+#[gpui::test]
+async fn test_local_replicas(cx: &mut TestAppContext) {
+    cx.update(init_test);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.background_executor.clone()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let slash_commands = Arc::new(SlashCommandWorkingSet::default());
+    let path: Arc<Path> = Path::new("/contexts/Channels - 1.zed.json").into();
+    let first = cx.new(|cx| {
+        let mut context = AssistantContext::local(
+            registry.clone(),
+            None,
+            None,
+            prompt_builder.clone(),
+            slash_commands.clone(),
+            cx,
+        );
+        context.set_path(Some(path.clone()));
+        context.buffer.update(cx, |buffer, cx| {
+            buffer.edit([(0..0, "Which channel?")], None, cx)
+        });
+        context
+    });
+    cx.update(|cx| context_sync::register(&first, cx));
+
+    // The second window opens a replica of the context that's already open.
+    let second = cx
+        .update(|cx| {
+            let source = context_sync::find_replica(&path, cx).unwrap();
+            assert_eq!(source, first);
+            context_sync::open_replica(
+                &source,
+                registry.clone(),
+                prompt_builder.clone(),
+                slash_commands.clone(),
+                None,
+                None,
+                cx,
+            )
+        })
+        .await
+        .unwrap();
+    cx.read(|cx| {
+        assert_eq!(second.read(cx).path(), Some(&path));
+        assert_eq!(second.read(cx).buffer.read(cx).text(), "Which channel?");
+        assert!(context_sync::is_local_replica(second.read(cx).replica_id()));
+    });
+
+    // Concurrent edits at the same position, and concurrent new messages, are
+    // resolved the same way in both windows.
+    let first_message = cx.read(|cx| first.read(cx).message_anchors[0].clone());
+    first.update(cx, |context, cx| {
+        context
+            .buffer
+            .update(cx, |buffer, cx| buffer.edit([(14..14, " mpsc")], None, cx));
+        context
+            .insert_message_after(first_message.id, Role::Assistant, MessageStatus::Done, cx)
+            .unwrap();
+    });
+    second.update(cx, |context, cx| {
+        context.buffer.update(cx, |buffer, cx| {
+            buffer.edit([(14..14, " oneshot")], None, cx)
+        });
+        context
+            .insert_message_after(first_message.id, Role::User, MessageStatus::Done, cx)
+            .unwrap();
+    });
+    cx.run_until_parked();
+
+    let (first_text, first_messages) =
+        cx.read(|cx| (first.read(cx).buffer.read(cx).text(), messages(&first, cx)));
+    let (second_text, second_messages) = cx.read(|cx| {
+        (
+            second.read(cx).buffer.read(cx).text(),
+            messages(&second, cx),
+        )
+    });
+    assert_eq!(first_text, second_text);
+    assert_eq!(first_messages, second_messages);
+    assert_eq!(first_messages.len(), 3);
+    assert!(first_text.contains(" mpsc") && first_text.contains(" oneshot"));
+
+    // Once a window is closed, the other one keeps working on its own.
+    drop(first);
+    cx.run_until_parked();
+    second.update(cx, |context, cx| {
+        context
+            .buffer
+            .update(cx, |buffer, cx| buffer.edit([(0..0, "> ")], None, cx));
+    });
+    cx.read(|cx| {
+        assert!(
+            second
+                .read(cx)
+                .buffer
+                .read(cx)
+                .text()
+                .starts_with("> Which")
+        )
+    });
+}
+
+async fn sync_contexts(
+    a: &Entity<AssistantContext>,
+    b: &Entity<AssistantContext>,
+    cx: &mut TestAppContext,
+) {
+    let (ops_for_a, ops_for_b) = cx.update(|cx| {
+        let (a, b) = (a.read(cx), b.read(cx));
+        (
+            b.serialize_ops(&a.version(cx), cx),
+            a.serialize_ops(&b.version(cx), cx),
+        )
+    });
+    for (context, ops) in [(a, ops_for_a.await), (b, ops_for_b.await)] {
+        let ops = ops
+            .into_iter()
+            .map(ContextOperation::from_proto)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        context.update(cx, |context, cx| context.apply_ops(ops, cx));
+    }
+}
+
+#[gpui::test]
+fn test_continuation_seams(cx: &mut App) {
+    init_test(cx);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.background_executor().clone()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let context = cx.new(|cx| {
+        AssistantContext::local(
+            registry,
+            None,
+            None,
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            cx,
+        )
+    });
+    let buffer = context.read(cx).buffer.clone();
+
+    let user_message = context.read(cx).message_anchors[0].clone();
+    let assistant_message = context.update(cx, |context, cx| {
+        let assistant_message = context
+            .insert_message_after(user_message.id, Role::Assistant, MessageStatus::Done, cx)
+            .unwrap();
+        context
+            .insert_message_after(assistant_message.id, Role::User, MessageStatus::Done, cx)
+            .unwrap();
+        assistant_message
+    });
+    buffer.update(cx, |buffer, cx| {
+        buffer.edit([(0..0, "Question"), (1..1, "Part one")], None, cx)
+    });
+    assert_eq!(buffer.read(cx).text(), "Question\nPart one\n");
+
+    context.update(cx, |context, cx| {
+        context.insert_continuation_seam(assistant_message.id, cx)
+    });
+    // The continuation is streamed in after the seam.
+    buffer.update(cx, |buffer, cx| {
+        buffer.edit([(17..17, " and two")], None, cx)
+    });
+    assert_eq!(context.read(cx).serialize(cx).continuation_seams, vec![17]);
+
+    let request = context.read(cx).continuation_request(
+        LanguageModelRequest::default(),
+        assistant_message.id,
+        cx,
+    );
+    let [.., continued, prompt] = request.messages.as_slice() else {
+        panic!("expected the continuation prompt to follow the continued message");
+    };
+    assert_eq!(continued.role, Role::Assistant);
+    assert_eq!(continued.string_contents(), "Part one and two");
+    assert_eq!(prompt.role, Role::User);
+}
+
+#[gpui::test]
+fn test_duplicate_attachments(cx: &mut App) {
+    init_test(cx);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.background_executor().clone()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let context = cx.new(|cx| {
+        AssistantContext::local(
+            registry,
+            None,
+            None,
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            cx,
+        )
+    });
+    let buffer = context.read(cx).buffer.clone();
+
+    let file = format!("```src/lib.rs\n{}```\n", "fn f() {}\n".repeat(40));
+    let question = "Why?\n";
+    buffer.update(cx, |buffer, cx| {
+        buffer.edit(
+            [(0..0, format!("{file}{question}{file}").as_str())],
+            None,
+            cx,
+        )
+    });
+    let range_for = |range: Range<usize>, cx: &App| {
+        let buffer = buffer.read(cx);
+        buffer.anchor_after(range.start)..buffer.anchor_before(range.end)
+    };
+
+    let second_copy = range_for(
+        file.len() + question.len()..2 * file.len() + question.len(),
+        cx,
+    );
+    let earlier = context
+        .read(cx)
+        .earlier_duplicate(&second_copy, cx)
+        .unwrap();
+    assert_eq!(earlier.to_offset(buffer.read(cx)), 0..file.len());
+    assert_eq!(
+        context
+            .read(cx)
+            .earlier_duplicate(&range_for(0..file.len(), cx), cx),
+        None
+    );
+    // Short content isn't worth flagging.
+    let short_copy = range_for(file.len()..file.len() + question.len(), cx);
+    assert_eq!(context.read(cx).earlier_duplicate(&short_copy, cx), None);
+
+    context.update(cx, |context, cx| {
+        context.reference_earlier_duplicate(
+            second_copy.clone(),
+            IconName::File,
+            "src/lib.rs".into(),
+            cx,
+        )
+    });
+    assert_eq!(
+        buffer.read(cx).text(),
+        format!("{file}{question}(Same as the src/lib.rs attached earlier in this conversation.)")
+    );
+    let attachments = context.read(cx).attachments(cx);
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].label.as_ref(), "Reference to src/lib.rs");
+
+    context.update(cx, |context, cx| context.remove_attached_text(earlier, cx));
+    assert_eq!(
+        buffer.read(cx).text(),
+        format!("{question}(Same as the src/lib.rs attached earlier in this conversation.)")
+    );
+}
+
+#[gpui::test]
+fn test_listing_and_removing_attachments(cx: &mut App) {
+    init_test(cx);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.background_executor().clone()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let context = cx.new(|cx| {
+        AssistantContext::local(
+            registry,
+            None,
+            None,
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            cx,
+        )
+    });
+    let buffer = context.read(cx).buffer.clone();
+
+    let diagnostics = "error: expected `;`\n";
+    let file = "```src/lib.rs\nfn f() {}\n```\n";
+    let question = "Why?";
+    buffer.update(cx, |buffer, cx| {
+        buffer.edit(
+            [(0..0, format!("{diagnostics}{file}{question}").as_str())],
+            None,
+            cx,
+        )
+    });
+    let section = |range: Range<usize>, icon: IconName, label: &str, cx: &App| {
+        let buffer = buffer.read(cx);
+        SlashCommandOutputSection {
+            range: buffer.anchor_after(range.start)..buffer.anchor_before(range.end),
+            icon,
+            label: label.to_string().into(),
+            metadata: None,
+        }
+    };
+    let file_section = section(
+        diagnostics.len()..diagnostics.len() + file.len() - 1,
+        IconName::File,
+        "src/lib.rs",
+        cx,
+    );
+    let diagnostics_section = section(
+        0..diagnostics.len() - 1,
+        IconName::XCircle,
+        "Diagnostics",
+        cx,
+    );
+    context.update(cx, |context, cx| {
+        context.insert_slash_command_output_section(file_section, cx);
+        context.insert_slash_command_output_section(diagnostics_section, cx);
+    });
+
+    let attachments = context.read(cx).attachments(cx);
+    assert_eq!(
+        attachments
+            .iter()
+            .map(|attachment| attachment.label.as_ref())
+            .collect::<Vec<_>>(),
+        ["Diagnostics", "src/lib.rs"]
+    );
+
+    context.update(cx, |context, cx| {
+        context.remove_attachment(&attachments[0], cx)
+    });
+    assert_eq!(buffer.read(cx).text(), format!("{file}{question}"));
+    let attachments = context.read(cx).attachments(cx);
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].label.as_ref(), "src/lib.rs");
+
+    context.update(cx, |context, cx| {
+        context.remove_attachment(&attachments[0], cx)
+    });
+    assert_eq!(buffer.read(cx).text(), question);
+    assert!(context.read(cx).attachments(cx).is_empty());
+}
+
+#[gpui::test]
+fn test_pinned_attachments_are_not_compressed(cx: &mut App) {
+    init_test(cx);
+    AgentSettings::override_global(
+        AgentSettings {
+            compress_large_attachments: true,
+            ..AgentSettings::get_global(cx).clone()
+        },
+        cx,
+    );
+
+    let registry = Arc::new(LanguageRegistry::test(cx.background_executor().clone()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let context = cx.new(|cx| {
+        AssistantContext::local(
+            registry,
+            None,
+            None,
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            cx,
+        )
+    });
+    let buffer = context.read(cx).buffer.clone();
+    let message_id = context.read(cx).message_anchors[0].id;
+
+    let output = "x".repeat(COMPRESSION_THRESHOLD + 1);
+    buffer.update(cx, |buffer, cx| {
+        buffer.edit([(0..0, output.as_str())], None, cx)
+    });
+    let section = {
+        let buffer = buffer.read(cx);
+        SlashCommandOutputSection {
+            range: buffer.anchor_after(0)..buffer.anchor_before(output.len()),
+            icon: IconName::File,
+            label: "large.txt".into(),
+            metadata: None,
+        }
+    };
+    context.update(cx, |context, cx| {
+        context.insert_slash_command_output_section(section, cx)
+    });
+    let attachment = context.read(cx).attachments(cx).remove(0);
+    let is_compressed = |cx: &App| {
+        let context = context.read(cx);
+        (
+            context.can_compress_attachment(&attachment, cx),
+            context.is_attachment_compressed(&attachment, cx),
+        )
+    };
+    assert_eq!(is_compressed(cx), (true, true));
+
+    context.update(cx, |context, cx| {
+        context.set_attachment_pinned(&attachment, true, cx)
+    });
+    assert!(context.read(cx).is_attachment_pinned(&attachment, cx));
+    assert!(
+        !context
+            .read(cx)
+            .is_attachment_in_pinned_message(&attachment, cx)
+    );
+    assert_eq!(is_compressed(cx), (false, false));
+
+    context.update(cx, |context, cx| {
+        context.set_attachment_pinned(&attachment, false, cx);
+        context.set_message_pinned(message_id, true, cx);
+    });
+    assert!(
+        context
+            .read(cx)
+            .is_attachment_in_pinned_message(&attachment, cx)
+    );
+    assert_eq!(is_compressed(cx), (false, false));
+
+    context.update(cx, |context, cx| {
+        context.set_message_pinned(message_id, false, cx)
+    });
+    assert_eq!(is_compressed(cx), (true, true));
+}
+
 #[gpui::test]
 fn test_message_splitting(cx: &mut App) {
     init_test(cx);
@@ -660,8 +1261,99 @@ async fn test_slash_commands(cx: &mut TestAppContext) {
             actual_marked_text.extend(buffer.text_for_range(offset..buffer.len()));
         });
 
-        assert_eq!(actual_marked_text, expected_marked_text);
-    }
+        assert_eq!(actual_marked_text, expected_marked_text);
+    }
+}
+
+#[gpui::test]
+async fn test_paginated_slash_command_output(cx: &mut TestAppContext) {
+    cx.update(init_test);
+
+    let slash_command_registry = cx.update(SlashCommandRegistry::default_global);
+    slash_command_registry.register_command(DiagnosticsSlashCommand, false);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.executor()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let context = cx.new(|cx| {
+        AssistantContext::local(
+            registry,
+            None,
+            None,
+            prompt_builder,
+            Arc::new(SlashCommandWorkingSet::default()),
+            cx,
+        )
+    });
+    let buffer = context.read_with(cx, |context, _| context.buffer.clone());
+    buffer.update(cx, |buffer, cx| {
+        buffer.edit([(0..0, "/diagnostics")], None, cx);
+    });
+
+    let text = (0..SLASH_COMMAND_OUTPUT_PAGE_LINES * 2 + 1)
+        .map(|ix| format!("line {ix}\n"))
+        .collect::<String>();
+    let output = SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: 0..text.len(),
+            icon: IconName::XCircle,
+            label: "Diagnostics".into(),
+            metadata: None,
+        }],
+        text: text.clone(),
+        run_commands_in_text: false,
+    };
+    context.update(cx, |context, cx| {
+        let command_source_range = context.parsed_slash_commands[0].source_range.clone();
+        context.insert_command_output(
+            command_source_range,
+            "diagnostics",
+            Task::ready(Ok(output.to_event_stream())),
+            true,
+            cx,
+        );
+    });
+    cx.run_until_parked();
+
+    let inserted_lines = |cx: &mut TestAppContext| {
+        buffer.read_with(cx, |buffer, _| {
+            buffer
+                .text()
+                .lines()
+                .filter(|line| line.starts_with("line "))
+                .count()
+        })
+    };
+    let pending_pages = |cx: &mut TestAppContext| {
+        context.read_with(cx, |context, _| {
+            context
+                .pending_output_pages()
+                .iter()
+                .map(|(command_id, pages)| (*command_id, pages.remaining_lines()))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // Only the first page is inserted at first.
+    assert_eq!(inserted_lines(cx), SLASH_COMMAND_OUTPUT_PAGE_LINES);
+    let [(command_id, remaining_lines)] = pending_pages(cx)[..] else {
+        panic!("expected the output to have pending pages");
+    };
+    assert_eq!(remaining_lines, SLASH_COMMAND_OUTPUT_PAGE_LINES + 1);
+
+    context.update(cx, |context, cx| {
+        context.load_more_command_output(command_id, cx)
+    });
+    assert_eq!(inserted_lines(cx), SLASH_COMMAND_OUTPUT_PAGE_LINES * 2);
+    assert_eq!(pending_pages(cx), vec![(command_id, 1)]);
+
+    context.update(cx, |context, cx| {
+        context.load_more_command_output(command_id, cx)
+    });
+    assert_eq!(pending_pages(cx), vec![]);
+    assert!(buffer.read_with(cx, |buffer, _| buffer.text().contains(&text)));
+    context.read_with(cx, |context, _| {
+        assert_eq!(context.slash_command_output_sections().len(), 3);
+    });
 }
 
 #[gpui::test]
@@ -712,6 +1404,10 @@ async fn test_serialization(cx: &mut TestAppContext) {
         ]
     );
 
+    context.update(cx, |context, cx| {
+        context.set_message_pinned(message_1.id, true, cx)
+    });
+
     let serialized_context = context.read_with(cx, |context, cx| context.serialize(cx));
     let deserialized_context = cx.new(|cx| {
         AssistantContext::deserialize(
@@ -739,6 +1435,298 @@ async fn test_serialization(cx: &mut TestAppContext) {
             (message_2.id, Role::System, 6..6),
         ]
     );
+    assert_eq!(
+        deserialized_context.read_with(cx, |context, cx| context
+            .messages(cx)
+            .map(|message| message.pinned)
+            .collect::<Vec<_>>()),
+        [false, true, false]
+    );
+}
+
+#[gpui::test]
+async fn test_serializing_message_metadata(cx: &mut TestAppContext) {
+    cx.update(init_test);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.executor()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let context = cx.new(|cx| {
+        AssistantContext::local(
+            registry.clone(),
+            None,
+            None,
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            cx,
+        )
+    });
+    let message_0 = context.read_with(cx, |context, _| context.message_anchors[0].id);
+    let message_1 = context.update(cx, |context, cx| {
+        context
+            .insert_message_after(message_0, Role::Assistant, MessageStatus::Done, cx)
+            .unwrap()
+    });
+    let usage = MessageUsage {
+        token_usage: TokenUsage {
+            input_tokens: 1_200,
+            output_tokens: 300,
+            ..Default::default()
+        },
+        estimated: false,
+        cost: Some(0.0081),
+        estimated_input_tokens: Some(1_150),
+    };
+    context.update(cx, |context, cx| {
+        context.update_metadata(message_1.id, cx, |metadata| {
+            metadata.usage = Some(usage);
+            metadata.post_processing = vec![PostProcessingChange {
+                processor: PostProcessor::StripPreamble,
+                summary: "Removed the preamble".into(),
+            }];
+            metadata.seed = Some(42);
+            metadata.stack_trace = Some(StackTraceTag {
+                kind: StackTraceKind::Rust,
+                frames: vec!["src/main.rs:12".into()],
+            });
+            metadata.substitution = Some(ModelSubstitution {
+                original_model: "Claude Opus 4".into(),
+                substitute_model: "Llama 3.3 70B (free)".into(),
+                reason: ModelSubstitutionReason::RateLimitExceeded,
+            });
+        });
+    });
+
+    let saved_context = context.read_with(cx, |context, cx| context.serialize(cx));
+    let json = serde_json::to_string(&saved_context).unwrap();
+    let deserialized_context = cx.new(|cx| {
+        AssistantContext::deserialize(
+            SavedContext::from_json(&json).unwrap(),
+            Path::new("").into(),
+            registry.clone(),
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            None,
+            None,
+            cx,
+        )
+    });
+    let message =
+        deserialized_context.read_with(cx, |context, cx| context.messages(cx).nth(1).unwrap());
+    assert_eq!(message.usage, Some(usage));
+    assert_eq!(
+        message
+            .post_processing
+            .iter()
+            .map(|change| change.processor)
+            .collect::<Vec<_>>(),
+        [PostProcessor::StripPreamble]
+    );
+    assert_eq!(message.seed, Some(42));
+    assert_eq!(
+        message.stack_trace.map(|tag| tag.frames),
+        Some(vec!["src/main.rs:12".to_string()])
+    );
+    assert_eq!(
+        message.substitution.map(|substitution| substitution.reason),
+        Some(ModelSubstitutionReason::RateLimitExceeded)
+    );
+    assert_eq!(
+        deserialized_context.read_with(cx, |context, _| context.total_usage()),
+        Some(usage)
+    );
+}
+
+#[gpui::test]
+async fn test_deserializing_progressively(cx: &mut TestAppContext) {
+    cx.update(init_test);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.executor()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let context = cx.new(|cx| {
+        AssistantContext::local(
+            registry.clone(),
+            None,
+            None,
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            cx,
+        )
+    });
+    context.update(cx, |context, cx| {
+        let mut message_id = context.message_anchors[0].id;
+        for ix in 0..100 {
+            context.buffer.update(cx, |buffer, cx| {
+                let len = buffer.len();
+                buffer.edit([(len..len, format!("{ix}{}", "x".repeat(4096)))], None, cx)
+            });
+            let role = if ix % 2 == 0 {
+                Role::Assistant
+            } else {
+                Role::User
+            };
+            message_id = context
+                .insert_message_after(message_id, role, MessageStatus::Done, cx)
+                .unwrap()
+                .id;
+        }
+    });
+    let saved_context = context.read_with(cx, |context, cx| context.serialize(cx));
+    let text = saved_context.text.clone();
+
+    let loaded_context = cx.new(|cx| {
+        AssistantContext::deserialize_progressively(
+            saved_context,
+            Path::new("").into(),
+            registry.clone(),
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            None,
+            None,
+            cx,
+        )
+    });
+    loaded_context.read_with(cx, |loaded_context, cx| {
+        let buffer = loaded_context.buffer.read(cx);
+        assert!(loaded_context.is_loading());
+        assert!(buffer.read_only());
+        assert!(buffer.len() < text.len());
+        assert!(text.starts_with(&buffer.text()));
+    });
+
+    cx.run_until_parked();
+    loaded_context.read_with(cx, |loaded_context, cx| {
+        let buffer = loaded_context.buffer.read(cx);
+        assert!(!loaded_context.is_loading());
+        assert!(!buffer.read_only());
+        assert_eq!(buffer.text(), text);
+    });
+    assert_eq!(
+        cx.read(|cx| messages(&loaded_context, cx)),
+        cx.read(|cx| messages(&context, cx))
+    );
+}
+
+#[gpui::test]
+async fn test_merging_saved_contexts(cx: &mut TestAppContext) {
+    cx.update(init_test);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.executor()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let mut saved_context = |question: &str, answer: &str| {
+        let context = cx.new(|cx| {
+            AssistantContext::local(
+                registry.clone(),
+                None,
+                None,
+                prompt_builder.clone(),
+                Arc::new(SlashCommandWorkingSet::default()),
+                cx,
+            )
+        });
+        context.update(cx, |context, cx| {
+            context
+                .buffer
+                .update(cx, |buffer, cx| buffer.edit([(0..0, question)], None, cx));
+            let message_0 = context.message_anchors[0].id;
+            context
+                .insert_message_after(message_0, Role::Assistant, MessageStatus::Done, cx)
+                .unwrap();
+            context.buffer.update(cx, |buffer, cx| {
+                let len = buffer.len();
+                buffer.edit([(len..len, answer)], None, cx)
+            });
+            context.serialize(cx)
+        })
+    };
+    let first = saved_context("Why?", "Because.");
+    let second = saved_context("How?", "Like this.");
+    assert!(!first.text.ends_with('\n'));
+    let expected_text = format!("{}\n{}", first.text, second.text);
+    let second_start = first.text.len() + 1;
+
+    let merged = SavedContext::merge(vec![first, second]);
+    let merged_context = cx.new(|cx| {
+        AssistantContext::deserialize(
+            merged,
+            Path::new("").into(),
+            registry.clone(),
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            None,
+            None,
+            cx,
+        )
+    });
+    let messages = cx.read(|cx| messages(&merged_context, cx));
+    assert_eq!(
+        merged_context.read_with(cx, |context, cx| context.buffer.read(cx).text()),
+        expected_text
+    );
+    assert_eq!(
+        messages
+            .iter()
+            .map(|(_, role, _)| *role)
+            .collect::<Vec<_>>(),
+        [Role::User, Role::Assistant, Role::User, Role::Assistant]
+    );
+    assert_eq!(messages[2].2.start, second_start);
+    let ids = messages
+        .iter()
+        .map(|(id, _, _)| *id)
+        .collect::<HashSet<_>>();
+    assert_eq!(ids.len(), 4);
+}
+
+#[gpui::test]
+fn test_truncating_saved_context(cx: &mut App) {
+    init_test(cx);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.background_executor().clone()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let context = cx.new(|cx| {
+        AssistantContext::local(
+            registry.clone(),
+            None,
+            None,
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            cx,
+        )
+    });
+    let answer_id = context.update(cx, |context, cx| {
+        context
+            .buffer
+            .update(cx, |buffer, cx| buffer.edit([(0..0, "Q1")], None, cx));
+        let question_id = context.message_anchors[0].id;
+        let answer = context
+            .insert_message_after(question_id, Role::Assistant, MessageStatus::Done, cx)
+            .unwrap();
+        context.buffer.update(cx, |buffer, cx| {
+            let len = buffer.len();
+            buffer.edit([(len..len, "A1")], None, cx)
+        });
+        context
+            .insert_message_after(answer.id, Role::User, MessageStatus::Done, cx)
+            .unwrap();
+        context.buffer.update(cx, |buffer, cx| {
+            let len = buffer.len();
+            buffer.edit([(len..len, "Q2")], None, cx)
+        });
+        answer.id
+    });
+    assert_eq!(context.read(cx).buffer.read(cx).text(), "Q1\nA1\nQ2");
+
+    let mut saved_context = context.read(cx).serialize(cx);
+    saved_context.truncate_after(answer_id);
+    assert_eq!(saved_context.text, "Q1\nA1\n");
+    assert_eq!(
+        saved_context
+            .messages
+            .iter()
+            .map(|message| message.metadata.role)
+            .collect::<Vec<_>>(),
+        [Role::User, Role::Assistant]
+    );
 }
 
 #[gpui::test(iterations = 100)]
@@ -1180,6 +2168,125 @@ fn test_mark_cache_anchors(cx: &mut App) {
     );
 }
 
+#[gpui::test]
+fn test_mark_cache_anchors_prefers_pinned_messages(cx: &mut App) {
+    init_test(cx);
+
+    let registry = Arc::new(LanguageRegistry::test(cx.background_executor().clone()));
+    let prompt_builder = Arc::new(PromptBuilder::new(None).unwrap());
+    let context = cx.new(|cx| {
+        AssistantContext::local(
+            registry,
+            None,
+            None,
+            prompt_builder.clone(),
+            Arc::new(SlashCommandWorkingSet::default()),
+            cx,
+        )
+    });
+    let buffer = context.read(cx).buffer.clone();
+    let cache_configuration = &Some(LanguageModelCacheConfiguration {
+        max_cache_anchors: 2,
+        should_speculate: false,
+        min_total_token: 10,
+    });
+
+    let message_1 = context.read(cx).message_anchors[0].clone();
+    buffer.update(cx, |buffer, cx| buffer.edit([(0..0, "aaa")], None, cx));
+    let message_2 = context
+        .update(cx, |context, cx| {
+            context.insert_message_after(message_1.id, Role::User, MessageStatus::Pending, cx)
+        })
+        .unwrap();
+    buffer.update(cx, |buffer, cx| buffer.edit([(4..4, "bbbbbbb")], None, cx));
+    context
+        .update(cx, |context, cx| {
+            context.insert_message_after(message_2.id, Role::User, MessageStatus::Pending, cx)
+        })
+        .unwrap();
+    buffer.update(cx, |buffer, cx| buffer.edit([(12..12, "cc")], None, cx));
+    context.update(cx, |context, _| {
+        context.token_count = Some(20);
+    });
+
+    context.update(cx, |context, cx| {
+        context.mark_cache_anchors(cache_configuration, false, cx)
+    });
+    assert_eq!(
+        messages_cache(&context, cx)
+            .iter()
+            .map(|(_, cache)| cache.as_ref().map_or(false, |cache| cache.is_anchor))
+            .collect::<Vec<bool>>(),
+        vec![false, true, false],
+        "The largest message should be the anchor."
+    );
+
+    context.update(cx, |context, cx| {
+        context.set_message_pinned(message_1.id, true, cx);
+        context.mark_cache_anchors(cache_configuration, false, cx)
+    });
+    assert_eq!(
+        messages_cache(&context, cx)
+            .iter()
+            .map(|(_, cache)| cache.as_ref().map_or(false, |cache| cache.is_anchor))
+            .collect::<Vec<bool>>(),
+        vec![true, false, false],
+        "A pinned message should be the anchor ahead of larger ones."
+    );
+}
+
+#[test]
+fn test_is_disconnect() {
+    assert!(is_disconnect(&anyhow::anyhow!(
+        "error decoding response body: error reading a body from connection"
+    )));
+    assert!(is_disconnect(&anyhow::Error::new(
+        LanguageModelError::Network {
+            message: "connection reset by peer".into()
+        }
+    )));
+    assert!(!is_disconnect(&anyhow::anyhow!(
+        "Failed to connect to API: 429 Too Many Requests"
+    )));
+}
+
+#[test]
+fn test_input_token_drift() {
+    let usage = MessageUsage {
+        token_usage: TokenUsage {
+            input_tokens: 600,
+            output_tokens: 100,
+            cache_read_input_tokens: 400,
+            ..Default::default()
+        },
+        estimated: false,
+        cost: None,
+        estimated_input_tokens: Some(1_100),
+    };
+    assert_eq!(usage.input_token_drift(), Some(0.1));
+    assert!(!usage.has_token_drift());
+
+    let usage = MessageUsage {
+        estimated_input_tokens: Some(700),
+        ..usage
+    };
+    assert_eq!(usage.input_token_drift(), Some(-0.3));
+    assert!(usage.has_token_drift());
+
+    let total = usage
+        + MessageUsage {
+            estimated_input_tokens: None,
+            ..usage
+        };
+    assert_eq!(total.input_token_drift(), None);
+
+    let usage = MessageUsage {
+        estimated: true,
+        ..usage
+    };
+    assert!(!usage.has_token_drift());
+}
+
 #[gpui::test]
 async fn test_summarization(cx: &mut TestAppContext) {
     let (context, fake_model) = setup_context_editor_with_fake_model(cx);
@@ -1283,6 +2390,88 @@ async fn test_thread_summary_error_retry(cx: &mut TestAppContext) {
     });
 }
 
+#[gpui::test]
+async fn test_held_back_markdown_is_flushed_when_a_response_ends_early(cx: &mut TestAppContext) {
+    let (context, fake_model) = setup_context_editor_with_fake_model(cx);
+    let buffer_text = |cx: &mut TestAppContext| {
+        context.read_with(cx, |context, cx| context.buffer().read(cx).text())
+    };
+
+    // A response that fails while a code block is still open.
+    context.update(cx, |context, cx| context.assist(cx));
+    cx.run_until_parked();
+    fake_model.stream_last_completion_response("Here's the fix:\n```rust\nfn main() {}\n");
+    cx.run_until_parked();
+    assert!(!buffer_text(cx).contains("fn main() {}"));
+    fake_model.send_last_completion_error(anyhow::anyhow!("the response was blocked").into());
+    cx.run_until_parked();
+    assert!(buffer_text(cx).contains("```rust\nfn main() {}\n"));
+    assert!(
+        context
+            .read_with(cx, |context, cx| context.serialize(cx))
+            .text
+            .contains("fn main() {}")
+    );
+
+    // A response that is cancelled while a code block is still open.
+    context.update(cx, |context, cx| context.assist(cx));
+    cx.run_until_parked();
+    fake_model.stream_last_completion_response("Another one:\n```rust\nfn cancelled() {}\n");
+    cx.run_until_parked();
+    assert!(!buffer_text(cx).contains("fn cancelled() {}"));
+    assert!(context.update(cx, |context, cx| context.cancel_last_assist(cx)));
+    cx.run_until_parked();
+    assert!(buffer_text(cx).contains("```rust\nfn cancelled() {}\n"));
+    assert!(
+        context
+            .read_with(cx, |context, cx| context.serialize(cx))
+            .text
+            .contains("fn cancelled() {}")
+    );
+}
+
+#[gpui::test]
+async fn test_refusals_are_shown_as_content_filter_errors(cx: &mut TestAppContext) {
+    let (context, fake_model) = setup_context_editor_with_fake_model(cx);
+    let model_errors = Rc::new(RefCell::new(Vec::new()));
+    context.update(cx, |_, cx| {
+        cx.subscribe(&context, {
+            let model_errors = model_errors.clone();
+            move |_, _, event, _| {
+                if let ContextEvent::ShowModelError(error) = event {
+                    model_errors.borrow_mut().push(error.clone());
+                }
+            }
+        })
+        .detach();
+    });
+
+    context.update(cx, |context, cx| context.assist(cx));
+    cx.run_until_parked();
+    fake_model.stream_last_completion_response("I can't help with that.");
+    fake_model.send_last_completion_stop(StopReason::Refusal);
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+
+    let model_errors = model_errors.borrow();
+    assert_eq!(model_errors.len(), 1);
+    assert!(matches!(
+        &model_errors[0],
+        LanguageModelError::ContentFiltered { .. }
+    ));
+    let status = context.read_with(cx, |context, cx| {
+        context
+            .messages(cx)
+            .find(|message| message.role == Role::Assistant)
+            .unwrap()
+            .status
+    });
+    assert_eq!(
+        status,
+        MessageStatus::Error(model_errors[0].to_string().into())
+    );
+}
+
 fn test_summarize_error(
     model: &Arc<FakeLanguageModel>,
     context: &Entity<AssistantContext>,
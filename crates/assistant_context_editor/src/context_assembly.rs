@@ -0,0 +1,367 @@
+//! Decides which messages of a text thread are sent to the model when they
+//! don't all fit in its context window.
+
+use collections::HashMap;
+use language_model::{LanguageModelRequestMessage, MessageContent, Role};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+
+use crate::MessageId;
+
+/// The maximum length of the line that stands in for each shortened message.
+const SHORTENED_LINE_LEN: usize = 160;
+/// The fraction of the budget set aside for the shortened old messages.
+const SHORTENED_BUDGET_FRACTION: usize = 8;
+
+/// How a text thread's messages are assembled into a request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssemblyStrategy {
+    /// Sends every message, even if the request doesn't fit.
+    #[default]
+    Full,
+    /// Sends the most recent messages that fit.
+    RecentFirst,
+    /// Sends the messages whose embeddings are closest to the latest one's,
+    /// or that share the most words with it when no embeddings are available.
+    RelevanceRanked,
+    /// Sends the most recent messages that fit, and the first line of each of
+    /// the rest, cut to [`SHORTENED_LINE_LEN`].
+    #[serde(alias = "summarize_old")]
+    ShortenOld,
+}
+
+impl AssemblyStrategy {
+    pub const ALL: [Self; 4] = [
+        Self::Full,
+        Self::RecentFirst,
+        Self::RelevanceRanked,
+        Self::ShortenOld,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Full => "Send Everything",
+            Self::RecentFirst => "Recent First",
+            Self::RelevanceRanked => "Most Relevant",
+            Self::ShortenOld => "Shorten Old Messages",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Inclusion {
+    Included,
+    /// Replaced by its first line, cut to [`SHORTENED_LINE_LEN`].
+    Shortened,
+    Excluded,
+}
+
+/// A message that could be sent to the model.
+#[derive(Clone, Debug)]
+pub struct AssemblyCandidate {
+    pub role: Role,
+    pub text: String,
+    pub tokens: usize,
+    pub pinned: bool,
+    /// How close the message's embedding is to the latest message's, when
+    /// both could be embedded.
+    pub relevance: Option<f32>,
+}
+
+/// Which messages were sent in a request, for the debug readout.
+#[derive(Clone, Debug)]
+pub struct AssemblyReport {
+    pub strategy: AssemblyStrategy,
+    /// The number of tokens available to the messages, if the model is known.
+    pub budget: Option<usize>,
+    pub entries: Vec<AssemblyEntry>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AssemblyEntry {
+    pub message_id: MessageId,
+    pub role: Role,
+    pub preview: String,
+    pub tokens: usize,
+    pub pinned: bool,
+    pub inclusion: Inclusion,
+}
+
+/// A rough estimate of the number of tokens in the text, which is cheap
+/// enough to compute every time a request is assembled.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Estimates the number of tokens in a request message, including images
+/// and documents.
+pub fn estimate_message_tokens(message: &LanguageModelRequestMessage) -> usize {
+    message
+        .content
+        .iter()
+        .map(|content| match content {
+            MessageContent::Image(image) => image.estimate_tokens(),
+            MessageContent::Document(document) => document.estimate_tokens(),
+            content => content.to_str().map_or(0, estimate_tokens),
+        })
+        .sum()
+}
+
+/// Decides which candidates to send. Pinned messages and the latest message
+/// are always sent, and so is everything when it all fits in the budget.
+pub fn assemble(
+    strategy: AssemblyStrategy,
+    candidates: &[AssemblyCandidate],
+    budget: Option<usize>,
+) -> Vec<Inclusion> {
+    let total = candidates.iter().map(|c| c.tokens).sum::<usize>();
+    let Some(budget) = budget.filter(|budget| total > *budget) else {
+        return vec![Inclusion::Included; candidates.len()];
+    };
+    if strategy == AssemblyStrategy::Full || candidates.is_empty() {
+        return vec![Inclusion::Included; candidates.len()];
+    }
+
+    let last_ix = candidates.len() - 1;
+    let mut inclusions = vec![Inclusion::Excluded; candidates.len()];
+    let mut remaining = budget;
+    for (ix, candidate) in candidates.iter().enumerate() {
+        if candidate.pinned || ix == last_ix {
+            inclusions[ix] = Inclusion::Included;
+            remaining = remaining.saturating_sub(candidate.tokens);
+        }
+    }
+
+    match strategy {
+        AssemblyStrategy::Full => {}
+        AssemblyStrategy::RecentFirst => {
+            include_recent(candidates, &mut inclusions, remaining);
+        }
+        AssemblyStrategy::ShortenOld => {
+            let reserve = budget / SHORTENED_BUDGET_FRACTION;
+            include_recent(
+                candidates,
+                &mut inclusions,
+                remaining.saturating_sub(reserve),
+            );
+            for inclusion in &mut inclusions {
+                if *inclusion == Inclusion::Excluded {
+                    *inclusion = Inclusion::Shortened;
+                }
+            }
+        }
+        AssemblyStrategy::RelevanceRanked => {
+            let unranked = (0..last_ix)
+                .filter(|ix| inclusions[*ix] == Inclusion::Excluded)
+                .collect::<Vec<_>>();
+            // Embedding and word overlap scores aren't comparable, so words are
+            // only used when some message couldn't be embedded.
+            let embedded = unranked
+                .iter()
+                .all(|ix| candidates[*ix].relevance.is_some());
+            let query = term_frequencies(&candidates[last_ix].text);
+            let mut ranked = unranked
+                .into_iter()
+                .map(|ix| {
+                    let score = match candidates[ix].relevance {
+                        Some(relevance) if embedded => relevance,
+                        _ => similarity(&query, &term_frequencies(&candidates[ix].text)),
+                    };
+                    (ix, score)
+                })
+                .collect::<Vec<_>>();
+            // Prefer newer messages among equally relevant ones.
+            ranked.sort_by(|(a_ix, a_score), (b_ix, b_score)| {
+                b_score.total_cmp(a_score).then(b_ix.cmp(a_ix))
+            });
+            for (ix, _) in ranked {
+                if candidates[ix].tokens <= remaining {
+                    inclusions[ix] = Inclusion::Included;
+                    remaining -= candidates[ix].tokens;
+                }
+            }
+        }
+    }
+    inclusions
+}
+
+/// Includes the messages before the latest one, newest first, until one
+/// doesn't fit, so that the conversation stays contiguous.
+fn include_recent(
+    candidates: &[AssemblyCandidate],
+    inclusions: &mut [Inclusion],
+    mut remaining: usize,
+) {
+    for ix in (0..candidates.len()).rev() {
+        if inclusions[ix] == Inclusion::Included {
+            continue;
+        }
+        if candidates[ix].tokens > remaining {
+            break;
+        }
+        inclusions[ix] = Inclusion::Included;
+        remaining -= candidates[ix].tokens;
+    }
+}
+
+fn term_frequencies(text: &str) -> HashMap<String, f32> {
+    let mut frequencies = HashMap::default();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| word.len() >= 3)
+    {
+        *frequencies.entry(word.to_lowercase()).or_default() += 1.;
+    }
+    frequencies
+}
+
+/// The cosine similarity of two term frequency vectors.
+fn similarity(a: &HashMap<String, f32>, b: &HashMap<String, f32>) -> f32 {
+    let dot = a
+        .iter()
+        .filter_map(|(word, count)| Some(count * b.get(word)?))
+        .sum::<f32>();
+    let norm = |frequencies: &HashMap<String, f32>| {
+        frequencies
+            .values()
+            .map(|count| count * count)
+            .sum::<f32>()
+            .sqrt()
+    };
+    let norms = norm(a) * norm(b);
+    if norms == 0. { 0. } else { dot / norms }
+}
+
+/// Returns the message that stands in for the shortened candidates, with the
+/// first line of each.
+pub fn shortened_text(candidates: &[&AssemblyCandidate]) -> String {
+    let mut text = "These earlier messages were shortened to fit the context window:\n".to_string();
+    for candidate in candidates {
+        let role = match candidate.role {
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+            Role::System => "System",
+        };
+        writeln!(
+            text,
+            "- {role}: {}",
+            preview(&candidate.text, SHORTENED_LINE_LEN)
+        )
+        .unwrap();
+    }
+    text
+}
+
+/// Returns the first non-empty line of the text, cut to the given length.
+pub fn preview(text: &str, max_len: usize) -> String {
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    if line.len() <= max_len {
+        return line.to_string();
+    }
+    let mut end = max_len;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &line[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Inclusion::*;
+
+    fn candidate(text: &str, tokens: usize, pinned: bool) -> AssemblyCandidate {
+        AssemblyCandidate {
+            role: Role::User,
+            text: text.into(),
+            tokens,
+            pinned,
+            relevance: None,
+        }
+    }
+
+    #[test]
+    fn test_assemble() {
+        let candidates = [
+            candidate("How do I parse the config file?", 40, false),
+            candidate("Pinned project overview", 20, true),
+            candidate("What about the cache eviction policy?", 40, false),
+            candidate("The cache evicts least recently used entries", 40, false),
+            candidate("Can the config parser read TOML files?", 10, false),
+        ];
+
+        // Everything is sent when it fits.
+        assert_eq!(
+            assemble(AssemblyStrategy::RecentFirst, &candidates, Some(200)),
+            [Included; 5]
+        );
+        assert_eq!(
+            assemble(AssemblyStrategy::Full, &candidates, Some(100)),
+            [Included; 5]
+        );
+        assert_eq!(
+            assemble(AssemblyStrategy::RecentFirst, &candidates, Some(100)),
+            [Excluded, Included, Excluded, Included, Included]
+        );
+        assert_eq!(
+            assemble(AssemblyStrategy::RelevanceRanked, &candidates, Some(100)),
+            [Included, Included, Excluded, Excluded, Included]
+        );
+        assert_eq!(
+            assemble(AssemblyStrategy::ShortenOld, &candidates, Some(120)),
+            [Shortened, Included, Shortened, Included, Included]
+        );
+    }
+
+    #[test]
+    fn test_relevance_ranking_prefers_embeddings() {
+        let embedded = |text: &str, relevance: f32| AssemblyCandidate {
+            relevance: Some(relevance),
+            ..candidate(text, 40, false)
+        };
+        let candidates = [
+            embedded("How do I parse the config file?", 0.1),
+            embedded("Which settings does it load at startup?", 0.9),
+            candidate("Can the config parser read TOML files?", 10, false),
+        ];
+        assert_eq!(
+            assemble(AssemblyStrategy::RelevanceRanked, &candidates, Some(60)),
+            [Excluded, Included, Included]
+        );
+
+        // Word overlap is used when some message wasn't embedded.
+        let candidates = [
+            candidate("How do I parse the config file?", 40, false),
+            embedded("Which settings does it load at startup?", 0.9),
+            candidate("Can the config parser read TOML files?", 10, false),
+        ];
+        assert_eq!(
+            assemble(AssemblyStrategy::RelevanceRanked, &candidates, Some(60)),
+            [Included, Excluded, Included]
+        );
+    }
+
+    #[test]
+    fn test_shortened_text() {
+        let long = "x".repeat(200);
+        let candidates = [
+            candidate("\nFirst line\nSecond line", 1, false),
+            AssemblyCandidate {
+                role: Role::Assistant,
+                ..candidate(&long, 1, false)
+            },
+        ];
+        assert_eq!(
+            shortened_text(&candidates.iter().collect::<Vec<_>>()),
+            format!(
+                "These earlier messages were shortened to fit the context window:\n- User: First line\n- Assistant: {}…\n",
+                "x".repeat(SHORTENED_LINE_LEN)
+            )
+        );
+    }
+}
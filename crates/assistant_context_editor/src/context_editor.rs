@@ -1,25 +1,28 @@
 use crate::{
+    keyed_blocks::KeyedBlocks,
     language_model_selector::{
         LanguageModelSelector, ToggleModelSelector, language_model_selector,
     },
     max_mode_tooltip::MaxModeTooltip,
+    prompt_compression,
+    stack_trace::{self, StackTraceTag},
 };
-use agent_settings::{AgentSettings, CompletionMode};
+use agent_settings::{AgentSettings, CompletionMode, MessageHeaderDivider};
 use anyhow::Result;
 use assistant_slash_command::{SlashCommand, SlashCommandOutputSection, SlashCommandWorkingSet};
 use assistant_slash_commands::{
-    DefaultSlashCommand, DocsSlashCommand, DocsSlashCommandArgs, FileSlashCommand,
+    DefaultSlashCommand, DocsSlashCommand, DocsSlashCommandArgs, FileSlashCommand, SnippetOrigin,
     selections_creases,
 };
 use client::{proto, zed_urls};
 use collections::{BTreeSet, HashMap, HashSet, hash_map};
 use editor::{
     Anchor, Editor, EditorEvent, MenuInlineCompletionsPolicy, MultiBuffer, MultiBufferSnapshot,
-    RowExt, ToOffset as _, ToPoint,
+    RowExt, RowHighlightOptions, ToOffset as _, ToPoint,
     actions::{MoveToEndOfLine, Newline, ShowCompletions},
     display_map::{
-        BlockPlacement, BlockProperties, BlockStyle, Crease, CreaseMetadata, CustomBlockId, FoldId,
-        RenderBlock, ToDisplayPoint,
+        BlockContext, BlockPlacement, BlockProperties, BlockStyle, Crease, CreaseMetadata,
+        CustomBlockId, FoldId, RenderBlock, ToDisplayPoint,
     },
     scroll::Autoscroll,
 };
@@ -28,19 +31,22 @@ use fs::Fs;
 use futures::FutureExt;
 use gpui::{
     Animation, AnimationExt, AnyElement, AnyView, App, ClipboardEntry, ClipboardItem, Empty,
-    Entity, EventEmitter, FocusHandle, Focusable, FontWeight, Global, InteractiveElement,
-    IntoElement, ParentElement, Pixels, Render, RenderImage, SharedString, Size,
-    StatefulInteractiveElement, Styled, Subscription, Task, Transformation, WeakEntity, actions,
-    div, img, impl_internal_actions, percentage, point, prelude::*, pulsating_between, size,
+    Entity, EventEmitter, FocusHandle, Focusable, FontWeight, Global, HighlightStyle,
+    InteractiveElement, IntoElement, ParentElement, Pixels, PromptLevel, Render, RenderImage,
+    SharedString, Size, StatefulInteractiveElement, Styled, Subscription, Task, Transformation,
+    UnderlineStyle, WeakEntity, actions, div, img, impl_internal_actions, percentage, point,
+    prelude::*, pulsating_between, size,
 };
 use indexed_docs::IndexedDocsStore;
 use language::{
-    BufferSnapshot, LspAdapterDelegate, ToOffset,
+    BufferSnapshot, LspAdapterDelegate, OffsetRangeExt, ToOffset,
     language_settings::{SoftWrap, all_language_settings},
 };
 use language_model::{
-    LanguageModelImage, LanguageModelProvider, LanguageModelProviderTosView, LanguageModelRegistry,
-    Role,
+    LanguageModel, LanguageModelError, LanguageModelImage, LanguageModelProvider,
+    LanguageModelProviderId, LanguageModelProviderTosView, LanguageModelRegistry,
+    LanguageModelRequest, LanguageModelRequestMessage, MaxMonthlySpendReachedError, Role,
+    WebSource,
 };
 use multi_buffer::MultiBufferRow;
 use picker::{Picker, popover_menu::PickerPopoverMenu};
@@ -52,6 +58,7 @@ use settings::{Settings, SettingsStore, update_settings_file};
 use std::{
     any::TypeId,
     cmp,
+    fmt::Write as _,
     ops::Range,
     path::{Path, PathBuf},
     rc::Rc,
@@ -60,8 +67,8 @@ use std::{
 };
 use text::SelectionGoal;
 use ui::{
-    ButtonLike, Disclosure, ElevationIndex, KeyBinding, PopoverMenuHandle, TintColor, Tooltip,
-    prelude::*,
+    ButtonLike, ContextMenu, ContextMenuEntry, Disclosure, ElevationIndex, Indicator, KeyBinding,
+    PopoverMenu, PopoverMenuHandle, TintColor, Tooltip, prelude::*,
 };
 use util::{ResultExt, maybe};
 use workspace::{
@@ -71,31 +78,51 @@ use workspace::{
 use workspace::{
     Save, Toast, ToolbarItemEvent, ToolbarItemLocation, ToolbarItemView, Workspace,
     item::{self, FollowableItem, Item, ItemHandle},
-    notifications::NotificationId,
+    notifications::{DetachAndPromptErr as _, NotificationId},
     pane,
     searchable::{SearchEvent, SearchableItem},
 };
 
 use crate::{
-    AssistantContext, CacheStatus, Content, ContextEvent, ContextId, InvokedSlashCommandId,
-    InvokedSlashCommandStatus, Message, MessageId, MessageMetadata, MessageStatus,
-    ParsedSlashCommand, PendingSlashCommandStatus,
+    AssemblyStrategy, AssistantContext, CacheStatus, CommentId, Content, ContextAttachment,
+    ContextAttachmentKind, ContextEvent, ContextId, CostTracker, EventLog, HistoryOperationKind,
+    Inclusion, InvokedSlashCommandId, InvokedSlashCommandStatus, Message, MessageComment,
+    MessageId, MessageMetadata, MessageStatus, MessageUsage, ParsedSlashCommand,
+    PendingSlashCommandStatus, PostProcessor, SaveReason,
 };
 use crate::{
-    ThoughtProcessOutputSection, slash_command::SlashCommandCompletionProvider,
+    ThoughtProcessOutputSection, citations, context_environment,
+    context_export::{HtmlExport, find_context_secrets},
+    environment_variables_modal::EnvironmentVariablesModal,
+    message_diffs::{self, DiffLineKind},
+    redaction_review_modal::RedactionReviewModal,
+    reference_check::{self, UnresolvedReference},
+    save_snippet_modal::SaveSnippetModal,
+    slash_command::SlashCommandCompletionProvider,
     slash_command_picker,
+    stop_conditions_modal::StopConditionsModal,
 };
 
 actions!(
     assistant,
     [
         Assist,
+        CommentOnSelection,
         ConfirmCommand,
         CopyCode,
         CycleMessageRole,
+        EditEnvironment,
+        EditStopConditions,
+        ExportContext,
         InsertIntoEditor,
+        PrintContext,
         QuoteSelection,
+        RedoOperation,
+        SaveSnippet,
         Split,
+        ToggleAttachments,
+        ToggleFollowOutput,
+        UndoOperation,
     ]
 );
 
@@ -107,17 +134,17 @@ pub enum InsertDraggedFiles {
 
 impl_internal_actions!(assistant, [InsertDraggedFiles]);
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-struct ScrollPosition {
-    offset_before_cursor: gpui::Point<f32>,
-    cursor: Anchor,
-}
-
 type MessageHeader = MessageMetadata;
 
+enum ContinuationSeam {}
+enum AddedDiffLine {}
+enum RemovedDiffLine {}
+
 #[derive(Clone)]
 enum AssistError {
     PaymentRequired,
+    MaxMonthlySpendReached(MaxMonthlySpendReachedError),
+    Model(LanguageModelError),
     Message(SharedString),
 }
 
@@ -187,8 +214,23 @@ pub struct ContextEditor {
     editor: Entity<Editor>,
     pending_thought_process: Option<(CreaseId, language::Anchor)>,
     blocks: HashMap<MessageId, (MessageHeader, CustomBlockId)>,
-    image_blocks: HashSet<CustomBlockId>,
-    scroll_position: Option<ScrollPosition>,
+    image_blocks: KeyedBlocks<(language::Anchor, u64)>,
+    comment_blocks: HashSet<CustomBlockId>,
+    load_more_blocks: HashSet<CustomBlockId>,
+    citation_blocks: KeyedBlocks<(language::Anchor, Vec<CitedSource>)>,
+    pending_citations: Task<Option<()>>,
+    web_source_blocks: KeyedBlocks<(language::Anchor, Vec<WebSource>)>,
+    pending_diff_highlights: Task<Option<()>>,
+    /// The references to paths and symbols that weren't found in the project,
+    /// by the assistant message they're in, or `None` while it's being checked.
+    unresolved_references: HashMap<MessageId, Option<Vec<UnresolvedReferenceRange>>>,
+    unresolved_reference_blocks: HashSet<CustomBlockId>,
+    comment_composer: Option<CommentComposer>,
+    /// Whether the editor should keep the streaming output in view.
+    follow_output: bool,
+    /// Whether the editor is currently pinned to the streaming output. This is
+    /// disengaged when the user scrolls away from it.
+    following_output: bool,
     remote_id: Option<workspace::ViewId>,
     pending_slash_command_creases: HashMap<Range<language::Anchor>, CreaseId>,
     invoked_slash_command_creases: HashMap<InvokedSlashCommandId, CreaseId>,
@@ -205,10 +247,111 @@ pub struct ContextEditor {
     dragged_file_worktrees: Vec<Entity<Worktree>>,
     language_model_selector: Entity<LanguageModelSelector>,
     language_model_selector_menu_handle: PopoverMenuHandle<LanguageModelSelector>,
+    show_attachments: bool,
+    attachment_token_counts: HashMap<Range<language::Anchor>, usize>,
+    /// The fraction of each compressed attachment that is dropped before sending.
+    attachment_compression_ratios: HashMap<Range<language::Anchor>, f32>,
+    pending_attachment_token_count: Task<Option<()>>,
+    /// The number of tokens in the user message being composed.
+    draft_token_count: Option<usize>,
+    pending_draft_token_count: Task<Option<()>>,
+    /// Content that was just attached a second time, while the user decides
+    /// what to do with the two copies.
+    duplicate_attachment: Option<DuplicateAttachment>,
+    /// The request being sent again after the provider failed transiently.
+    retrying_completion: Option<RetryingCompletion>,
+}
+
+/// A source quoted by an assistant message, shown as a footnote below it.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct CitedSource {
+    label: SharedString,
+    icon: IconName,
+    /// The first range of the source that the message quotes.
+    range: Range<language::Anchor>,
+    /// The file and zero-based row the quoted text was read from.
+    file_location: Option<(String, u32)>,
+    /// The claims in the message that quote the source.
+    claims: Vec<String>,
+}
+
+/// Content that was attached to the context again, and its earlier copy.
+#[derive(Clone)]
+struct DuplicateAttachment {
+    range: Range<language::Anchor>,
+    earlier_range: Range<language::Anchor>,
+    icon: IconName,
+    label: SharedString,
+}
+
+/// A request that failed transiently, and which attempt is being made next.
+#[derive(Clone)]
+struct RetryingCompletion {
+    attempt: u32,
+    max_attempts: u32,
+    message: SharedString,
+}
+
+/// A reference that wasn't found in the project, and where it is in the context.
+#[derive(Clone)]
+struct UnresolvedReferenceRange {
+    range: Range<language::Anchor>,
+    reference: UnresolvedReference,
 }
 
+enum UnresolvedReferenceHighlight {}
+
+/// An editor for a new comment on a message, or a reply to a comment thread.
+struct CommentComposer {
+    message_id: MessageId,
+    range: Range<language::Anchor>,
+    parent_id: Option<CommentId>,
+    editor: Entity<Editor>,
+    block_id: CustomBlockId,
+}
+
+const REACTIONS: [&str; 5] = ["👍", "👎", "🎉", "👀", "✅"];
+
+const SAMPLE_PROMPT: &str = "\
+Hi! Text threads are documents that you edit together with the agent. \
+Start a line with a slash command to add context to this message:
+
+- `/file` inserts the contents of a file or directory
+- `/tab` inserts your open tabs
+- `/diagnostics` inserts the errors and warnings in your project
+- `/fetch` inserts the contents of a web page
+
+For example, type `/tab` on a new line below and press enter, then ask a question about the code \
+and send the message with the Assist button.
+";
+
 pub const DEFAULT_TAB_TITLE: &str = "New Chat";
 const MAX_TAB_TITLE_LEN: usize = 16;
+const MAX_OPERATION_HISTORY_ENTRIES: usize = 10;
+const MAX_WEB_SOURCE_TITLE_LEN: usize = 80;
+/// The languages offered in the answer language menu. Others can be set with
+/// the `answer_language` setting.
+const ANSWER_LANGUAGES: [&str; 16] = [
+    "English",
+    "Arabic",
+    "Chinese",
+    "Dutch",
+    "French",
+    "German",
+    "Hindi",
+    "Italian",
+    "Japanese",
+    "Korean",
+    "Polish",
+    "Portuguese",
+    "Russian",
+    "Spanish",
+    "Turkish",
+    "Ukrainian",
+];
+/// The number of lines above and below a stack frame to attach when a stack
+/// trace is pasted.
+const STACK_FRAME_CONTEXT_LINES: u32 = 5;
 
 impl ContextEditor {
     pub fn for_context(
@@ -262,6 +405,19 @@ impl ContextEditor {
         let slash_command_sections = context.read(cx).slash_command_output_sections().to_vec();
         let thought_process_sections = context.read(cx).thought_process_output_sections().to_vec();
         let slash_commands = context.read(cx).slash_commands().clone();
+        let language_model_selector = cx.new(|cx| {
+            let fs = fs.clone();
+            let selector_context = context.clone();
+            let active_context = context.clone();
+            let language_context = context.clone();
+            language_model_selector(
+                move |cx| active_context.read(cx).model(cx),
+                move |cx| language_context.read(cx).answer_language(cx),
+                move |model, cx| select_model(model, &selector_context, fs.clone(), cx),
+                window,
+                cx,
+            )
+        });
         let mut this = Self {
             context,
             slash_commands,
@@ -269,7 +425,17 @@ impl ContextEditor {
             lsp_adapter_delegate,
             blocks: Default::default(),
             image_blocks: Default::default(),
-            scroll_position: None,
+            comment_blocks: Default::default(),
+            load_more_blocks: Default::default(),
+            citation_blocks: Default::default(),
+            pending_citations: Task::ready(None),
+            web_source_blocks: Default::default(),
+            pending_diff_highlights: Task::ready(None),
+            unresolved_references: HashMap::default(),
+            unresolved_reference_blocks: Default::default(),
+            comment_composer: None,
+            follow_output: true,
+            following_output: false,
             remote_id: None,
             pending_thought_process: None,
             fs: fs.clone(),
@@ -282,24 +448,34 @@ impl ContextEditor {
             show_accept_terms: false,
             slash_menu_handle: Default::default(),
             dragged_file_worktrees: Vec::new(),
-            language_model_selector: cx.new(|cx| {
-                language_model_selector(
-                    |cx| LanguageModelRegistry::read_global(cx).default_model(),
-                    move |model, cx| {
-                        update_settings_file::<AgentSettings>(
-                            fs.clone(),
-                            cx,
-                            move |settings, _| settings.set_model(model.clone()),
-                        );
-                    },
-                    window,
-                    cx,
-                )
-            }),
+            language_model_selector,
             language_model_selector_menu_handle: PopoverMenuHandle::default(),
+            show_attachments: false,
+            attachment_token_counts: HashMap::default(),
+            attachment_compression_ratios: HashMap::default(),
+            pending_attachment_token_count: Task::ready(None),
+            draft_token_count: None,
+            pending_draft_token_count: Task::ready(None),
+            duplicate_attachment: None,
+            retrying_completion: None,
         };
         this.update_message_headers(cx);
         this.update_image_blocks(cx);
+        this.update_web_source_blocks(cx);
+        this.count_draft_tokens(cx);
+        this.update_comment_blocks(cx);
+        this.update_load_more_blocks(cx);
+        this.update_citations(cx);
+        this.update_diff_highlights(cx);
+        // Only check the messages completed while the context is open.
+        this.unresolved_references = this
+            .context
+            .read(cx)
+            .messages(cx)
+            .filter(|message| message.status == MessageStatus::Done)
+            .map(|message| (message.id, Some(Vec::new())))
+            .collect();
+        this.update_continuation_seams(cx);
         this.insert_slash_command_output_sections(slash_command_sections, false, window, cx);
         this.insert_thought_process_output_sections(
             thought_process_sections
@@ -349,6 +525,15 @@ impl ContextEditor {
         );
     }
 
+    /// Inserts the default prompt, followed by a message that introduces new
+    /// users to slash commands.
+    pub fn insert_sample_prompt(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.insert_default_prompt(window, cx);
+        self.editor.update(cx, |editor, cx| {
+            editor.insert(SAMPLE_PROMPT, window, cx);
+        });
+    }
+
     fn assist(&mut self, _: &Assist, window: &mut Window, cx: &mut Context<Self>) {
         if self.sending_disabled(cx) {
             return;
@@ -357,9 +542,12 @@ impl ContextEditor {
     }
 
     fn send_to_model(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        let provider = LanguageModelRegistry::read_global(cx)
-            .default_model()
-            .map(|default| default.provider);
+        if self.uses_host_model(cx) {
+            self.request_host_completion(cx);
+            return;
+        }
+
+        let provider = self.context.read(cx).model(cx).map(|model| model.provider);
         if provider
             .as_ref()
             .map_or(false, |provider| provider.must_accept_terms(cx))
@@ -370,6 +558,7 @@ impl ContextEditor {
         }
 
         self.last_error = None;
+        self.retrying_completion = None;
 
         if let Some(user_message) = self.context.update(cx, |context, cx| context.assist(cx)) {
             let new_selection = {
@@ -379,17 +568,43 @@ impl ContextEditor {
                 cursor..cursor
             };
             self.editor.update(cx, |editor, cx| {
-                editor.change_selections(Some(Autoscroll::fit()), window, cx, |selections| {
+                editor.change_selections(None, window, cx, |selections| {
                     selections.select_ranges([new_selection])
                 });
             });
-            // Avoid scrolling to the new cursor position so the assistant's output is stable.
-            cx.defer_in(window, |this, _, _| this.scroll_position = None);
+            self.following_output = self.follow_output;
+            if self.following_output {
+                self.scroll_to_streaming_output(window, cx);
+            }
         }
 
         cx.notify();
     }
 
+    /// Guests without a language model of their own have their messages
+    /// completed by the host's model.
+    fn uses_host_model(&self, cx: &App) -> bool {
+        configuration_error(&self.context, cx).is_some()
+            && self.context.read(cx).is_shared_by_host(cx)
+    }
+
+    fn request_host_completion(&mut self, cx: &mut Context<Self>) {
+        self.last_error = None;
+        let request = self.context.read(cx).request_host_completion(cx);
+        cx.spawn(async move |this, cx| {
+            if let Err(error) = request.await {
+                this.update(cx, |this, cx| {
+                    this.last_error = Some(AssistError::Message(error.to_string().into()));
+                    cx.notify();
+                })?;
+            }
+            anyhow::Ok(())
+        })
+        .detach_and_log_err(cx);
+        self.following_output = self.follow_output;
+        cx.notify();
+    }
+
     fn cancel(
         &mut self,
         _: &editor::actions::Cancel,
@@ -397,6 +612,7 @@ impl ContextEditor {
         cx: &mut Context<Self>,
     ) {
         self.last_error = None;
+        self.retrying_completion = None;
 
         if self
             .context
@@ -535,14 +751,11 @@ impl ContextEditor {
                 .cloned()
                 .collect::<Vec<_>>();
             let snapshot = context.buffer().read(cx).snapshot();
+            let delegate = self.lsp_adapter_delegate.clone().map(|delegate| {
+                context_environment::with_environment(delegate, context.environment().clone())
+            });
             let output = command.run(
-                arguments,
-                &sections,
-                snapshot,
-                workspace,
-                self.lsp_adapter_delegate.clone(),
-                window,
-                cx,
+                arguments, &sections, snapshot, workspace, delegate, window, cx,
             );
             self.context.update(cx, |context, cx| {
                 context.insert_command_output(
@@ -569,14 +782,22 @@ impl ContextEditor {
             ContextEvent::MessagesEdited => {
                 self.update_message_headers(cx);
                 self.update_image_blocks(cx);
+                self.update_web_source_blocks(cx);
+                self.update_citations(cx);
+                self.update_diff_highlights(cx);
+                self.check_references(cx);
+                if self.show_attachments {
+                    self.count_attachment_tokens(cx);
+                }
+                self.count_draft_tokens(cx);
                 self.context.update(cx, |context, cx| {
-                    context.save(Some(Duration::from_millis(500)), self.fs.clone(), cx);
+                    context.save(SaveReason::Edit, cx);
                 });
             }
             ContextEvent::SummaryChanged => {
                 cx.emit(EditorEvent::TitleChanged);
                 self.context.update(cx, |context, cx| {
-                    context.save(Some(Duration::from_millis(500)), self.fs.clone(), cx);
+                    context.save(SaveReason::Metadata, cx);
                 });
             }
             ContextEvent::SummaryGenerated => {}
@@ -622,19 +843,10 @@ impl ContextEditor {
                 }
             }
             ContextEvent::StreamedCompletion => {
-                self.editor.update(cx, |editor, cx| {
-                    if let Some(scroll_position) = self.scroll_position {
-                        let snapshot = editor.snapshot(window, cx);
-                        let cursor_point = scroll_position.cursor.to_display_point(&snapshot);
-                        let scroll_top =
-                            cursor_point.row().as_f32() - scroll_position.offset_before_cursor.y;
-                        editor.set_scroll_position(
-                            point(scroll_position.offset_before_cursor.x, scroll_top),
-                            window,
-                            cx,
-                        );
-                    }
-                });
+                self.retrying_completion = None;
+                if self.following_output {
+                    self.scroll_to_streaming_output(window, cx);
+                }
             }
             ContextEvent::ParsedSlashCommandsUpdated { removed, updated } => {
                 self.editor.update(cx, |editor, cx| {
@@ -727,13 +939,58 @@ impl ContextEditor {
             }
             ContextEvent::SlashCommandOutputSectionAdded { section } => {
                 self.insert_slash_command_output_sections([section.clone()], false, window, cx);
+                if let Some(earlier_range) =
+                    self.context.read(cx).earlier_duplicate(&section.range, cx)
+                {
+                    self.duplicate_attachment = Some(DuplicateAttachment {
+                        range: section.range.clone(),
+                        earlier_range,
+                        icon: section.icon,
+                        label: section.label.clone(),
+                    });
+                    cx.notify();
+                }
+                if self.show_attachments {
+                    self.count_attachment_tokens(cx);
+                }
+            }
+            ContextEvent::OperationHistoryChanged => cx.notify(),
+            ContextEvent::MessageAnnotationsChanged => {
+                self.update_comment_blocks(cx);
+                // Message headers read their reactions when they're rendered.
+                self.editor.update(cx, |_, cx| cx.notify());
+            }
+            ContextEvent::ContinuationSeamsChanged => self.update_continuation_seams(cx),
+            ContextEvent::PendingOutputPagesChanged => self.update_load_more_blocks(cx),
+            ContextEvent::RetryingCompletion {
+                attempt,
+                max_attempts,
+                message,
+                ..
+            } => {
+                self.retrying_completion = Some(RetryingCompletion {
+                    attempt: *attempt,
+                    max_attempts: *max_attempts,
+                    message: message.clone().into(),
+                });
+                cx.notify();
             }
-            ContextEvent::Operation(_) => {}
+            ContextEvent::Operation(_) | ContextEvent::CompletionFinished { .. } => {}
             ContextEvent::ShowAssistError(error_message) => {
                 self.last_error = Some(AssistError::Message(error_message.clone()));
+                self.retrying_completion = None;
             }
             ContextEvent::ShowPaymentRequiredError => {
                 self.last_error = Some(AssistError::PaymentRequired);
+                self.retrying_completion = None;
+            }
+            ContextEvent::ShowMaxMonthlySpendReachedError(error) => {
+                self.last_error = Some(AssistError::MaxMonthlySpendReached(*error));
+                self.retrying_completion = None;
+            }
+            ContextEvent::ShowModelError(error) => {
+                self.last_error = Some(AssistError::Model(error.clone()));
+                self.retrying_completion = None;
             }
         }
     }
@@ -914,6 +1171,11 @@ impl ContextEditor {
                     .unwrap();
                 let buffer_row = MultiBufferRow(start.to_point(&buffer).row);
                 buffer_rows_to_fold.insert(buffer_row);
+                let duplicate = self
+                    .context
+                    .read(cx)
+                    .earlier_duplicate(&section.range, cx)
+                    .is_some();
                 creases.push(
                     Crease::inline(
                         start..end,
@@ -922,6 +1184,7 @@ impl ContextEditor {
                                 cx.entity().downgrade(),
                                 section.icon.path().into(),
                                 section.label.clone(),
+                                duplicate,
                             ),
                             merge_adjacent: false,
                             ..Default::default()
@@ -955,17 +1218,15 @@ impl ContextEditor {
         cx: &mut Context<Self>,
     ) {
         match event {
-            EditorEvent::ScrollPositionChanged { autoscroll, .. } => {
-                let cursor_scroll_position = self.cursor_scroll_position(window, cx);
-                if *autoscroll {
-                    self.scroll_position = cursor_scroll_position;
-                } else if self.scroll_position != cursor_scroll_position {
-                    self.scroll_position = None;
+            EditorEvent::ScrollPositionChanged { .. } => {
+                if self.follow_output && self.context.read(cx).is_streaming() {
+                    let following_output = self.is_streaming_output_visible(window, cx);
+                    if following_output != self.following_output {
+                        self.following_output = following_output;
+                        cx.notify();
+                    }
                 }
             }
-            EditorEvent::SelectionsChanged { .. } => {
-                self.scroll_position = self.cursor_scroll_position(window, cx);
-            }
             _ => {}
         }
         cx.emit(event.clone());
@@ -981,35 +1242,113 @@ impl ContextEditor {
         cx.emit(event.clone());
     }
 
-    fn cursor_scroll_position(
-        &self,
-        window: &mut Window,
-        cx: &mut Context<Self>,
-    ) -> Option<ScrollPosition> {
+    /// Returns the display row at which the message currently being streamed ends.
+    fn streaming_output_row(&self, window: &mut Window, cx: &mut Context<Self>) -> Option<f32> {
+        let tail = self
+            .context
+            .read(cx)
+            .messages(cx)
+            .filter(|message| {
+                message.role == Role::Assistant && message.status == MessageStatus::Pending
+            })
+            .last()?
+            .anchor_range
+            .end;
         self.editor.update(cx, |editor, cx| {
             let snapshot = editor.snapshot(window, cx);
-            let cursor = editor.selections.newest_anchor().head();
-            let cursor_row = cursor
-                .to_display_point(&snapshot.display_snapshot)
-                .row()
-                .as_f32();
-            let scroll_position = editor
-                .scroll_manager
-                .anchor()
-                .scroll_position(&snapshot.display_snapshot);
-
-            let scroll_bottom = scroll_position.y + editor.visible_line_count().unwrap_or(0.);
-            if (scroll_position.y..scroll_bottom).contains(&cursor_row) {
-                Some(ScrollPosition {
-                    cursor,
-                    offset_before_cursor: point(scroll_position.x, cursor_row - scroll_position.y),
-                })
-            } else {
-                None
-            }
+            let (&excerpt_id, _, _) = snapshot.buffer_snapshot.as_singleton()?;
+            let tail = snapshot
+                .buffer_snapshot
+                .anchor_in_excerpt(excerpt_id, tail)?;
+            Some(tail.to_display_point(&snapshot).row().as_f32())
+        })
+    }
+
+    fn is_streaming_output_visible(&self, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        let Some(tail_row) = self.streaming_output_row(window, cx) else {
+            return true;
+        };
+        self.editor.update(cx, |editor, cx| {
+            let scroll_top = editor.scroll_position(cx).y;
+            let visible_line_count = editor.visible_line_count().unwrap_or(0.);
+            tail_row < scroll_top + visible_line_count
         })
     }
 
+    fn scroll_to_streaming_output(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(tail_row) = self.streaming_output_row(window, cx) else {
+            return;
+        };
+        self.editor.update(cx, |editor, cx| {
+            let scroll_position = editor.scroll_position(cx);
+            let visible_line_count = editor.visible_line_count().unwrap_or(0.);
+            let scroll_top = tail_row + 1. - visible_line_count;
+            if scroll_top > scroll_position.y {
+                editor.set_scroll_position(point(scroll_position.x, scroll_top), window, cx);
+            }
+        });
+    }
+
+    fn toggle_follow_output(
+        &mut self,
+        _: &ToggleFollowOutput,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.follow_output = !self.follow_output;
+        self.following_output = self.follow_output && self.context.read(cx).is_streaming();
+        if self.following_output {
+            self.scroll_to_streaming_output(window, cx);
+        }
+        cx.notify();
+    }
+
+    fn jump_to_streaming_output(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.follow_output = true;
+        self.following_output = true;
+        self.scroll_to_streaming_output(window, cx);
+        cx.notify();
+    }
+
+    fn render_follow_output_toggle(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        IconButton::new("toggle-follow-output", IconName::ArrowDown)
+            .icon_size(IconSize::Small)
+            .icon_color(Color::Muted)
+            .toggle_state(self.follow_output)
+            .tooltip(|window, cx| {
+                Tooltip::for_action("Follow Output", &ToggleFollowOutput, window, cx)
+            })
+            .on_click(cx.listener(|this, _, window, cx| {
+                this.toggle_follow_output(&ToggleFollowOutput, window, cx)
+            }))
+    }
+
+    fn render_jump_to_latest(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        if self.following_output || !self.context.read(cx).is_streaming() {
+            return None;
+        }
+
+        Some(
+            h_flex()
+                .absolute()
+                .bottom_12()
+                .w_full()
+                .justify_center()
+                .child(
+                    Button::new("jump-to-latest", "Jump to Latest")
+                        .style(ButtonStyle::Filled)
+                        .layer(ElevationIndex::ModalSurface)
+                        .icon(IconName::ArrowDown)
+                        .icon_size(IconSize::XSmall)
+                        .icon_position(IconPosition::Start)
+                        .label_size(LabelSize::Small)
+                        .on_click(cx.listener(|this, _, window, cx| {
+                            this.jump_to_streaming_output(window, cx)
+                        })),
+                ),
+        )
+    }
+
     fn esc_kbd(cx: &App) -> Div {
         let colors = cx.theme().colors().clone();
 
@@ -1034,6 +1373,7 @@ impl ContextEditor {
     }
 
     fn update_message_headers(&mut self, cx: &mut Context<Self>) {
+        let context_editor = cx.entity().downgrade();
         self.editor.update(cx, |editor, cx| {
             let buffer = editor.buffer().read(cx).snapshot(cx);
 
@@ -1048,20 +1388,42 @@ impl ContextEditor {
             let render_block = |message: MessageMetadata| -> RenderBlock {
                 Arc::new({
                     let context = self.context.clone();
+                    let project = self.project.clone();
+                    let context_editor = context_editor.clone();
 
                     move |cx| {
                         let message_id = MessageId(message.timestamp);
                         let llm_loading = message.role == Role::Assistant
                             && message.status == MessageStatus::Pending;
+                        let colors = cx.theme().colors();
+                        let (role_color, role_background) = match message.role {
+                            Role::User => (
+                                colors.message_header_user_role,
+                                colors.message_header_user_background,
+                            ),
+                            Role::Assistant => (
+                                colors.message_header_assistant_role,
+                                colors.message_header_assistant_background,
+                            ),
+                            Role::System => (
+                                colors.message_header_system_role,
+                                colors.message_header_system_background,
+                            ),
+                        };
+                        let divider_color = colors.message_header_divider;
+                        let header_settings = AgentSettings::get_global(cx).message_headers.clone();
 
                         let (label, spinner, note) = match message.role {
                             Role::User => (
-                                Label::new("You").color(Color::Default).into_any_element(),
+                                Label::new("You")
+                                    .color(Color::Custom(role_color))
+                                    .into_any_element(),
                                 None,
                                 None,
                             ),
                             Role::Assistant => {
-                                let base_label = Label::new("Agent").color(Color::Info);
+                                let base_label =
+                                    Label::new("Agent").color(Color::Custom(role_color));
                                 let mut spinner = None;
                                 let mut note = None;
                                 let animated_label = if llm_loading {
@@ -1081,7 +1443,7 @@ impl ContextEditor {
                                     spinner = Some(
                                         Icon::new(IconName::ArrowCircle)
                                             .size(IconSize::XSmall)
-                                            .color(Color::Info)
+                                            .color(Color::Custom(role_color))
                                             .with_animation(
                                                 "arrow-circle",
                                                 Animation::new(Duration::from_secs(2)).repeat(),
@@ -1099,7 +1461,7 @@ impl ContextEditor {
                             }
                             Role::System => (
                                 Label::new("System")
-                                    .color(Color::Warning)
+                                    .color(Color::Custom(role_color))
                                     .into_any_element(),
                                 None,
                                 None,
@@ -1142,14 +1504,163 @@ impl ContextEditor {
                             )
                             .children(note);
 
+                        let pinned = message.pinned;
+                        let pin_button = IconButton::new("pin", IconName::Pin)
+                            .icon_size(IconSize::XSmall)
+                            .icon_color(if pinned { Color::Accent } else { Color::Muted })
+                            .toggle_state(pinned)
+                            .when(!pinned, |button| button.visible_on_hover("message-header"))
+                            .tooltip(move |window, cx| {
+                                Tooltip::with_meta(
+                                    if pinned {
+                                        "Unpin Message"
+                                    } else {
+                                        "Pin Message"
+                                    },
+                                    None,
+                                    "Always sent in full, with its attachments",
+                                    window,
+                                    cx,
+                                )
+                            })
+                            .on_click({
+                                let context = context.clone();
+                                move |_, _window, cx| {
+                                    context.update(cx, |context, cx| {
+                                        context.set_message_pinned(message_id, !pinned, cx)
+                                    })
+                                }
+                            });
+
+                        let duplicate_menu =
+                            PopoverMenu::new(("duplicate-with", message_id.as_u64()))
+                                .trigger_with_tooltip(
+                                    IconButton::new("duplicate-with-trigger", IconName::Copy)
+                                        .icon_size(IconSize::XSmall)
+                                        .icon_color(Color::Muted)
+                                        .visible_on_hover("message-header"),
+                                    Tooltip::text("Duplicate with…"),
+                                )
+                                .anchor(gpui::Corner::TopLeft)
+                                .menu({
+                                    let context_editor = context_editor.clone();
+                                    move |window, cx| {
+                                        let context_editor = context_editor.clone();
+                                        Some(ContextMenu::build(window, cx, |menu, _, cx| {
+                                            duplicate_with_menu(
+                                                menu,
+                                                message_id,
+                                                context_editor,
+                                                cx,
+                                            )
+                                        }))
+                                    }
+                                });
+
+                        let event_log_button =
+                            context.read(cx).event_log(message_id).is_some().then(|| {
+                                IconButton::new("export-event-log", IconName::FileCode)
+                                    .icon_size(IconSize::XSmall)
+                                    .icon_color(Color::Muted)
+                                    .visible_on_hover("message-header")
+                                    .tooltip(|window, cx| {
+                                        Tooltip::with_meta(
+                                            "Export Event Log…",
+                                            None,
+                                            "The events streamed from the model, as JSONL",
+                                            window,
+                                            cx,
+                                        )
+                                    })
+                                    .on_click({
+                                        let context_editor = context_editor.clone();
+                                        move |_, _window, cx| {
+                                            context_editor
+                                                .update(cx, |context_editor, cx| {
+                                                    context_editor.export_event_log(message_id, cx)
+                                                })
+                                                .ok();
+                                        }
+                                    })
+                            });
+
+                        let seed_button = message.seed.map(|seed| {
+                            Button::new("reuse-seed", format!("Seed {seed}"))
+                                .label_size(LabelSize::XSmall)
+                                .color(Color::Muted)
+                                .visible_on_hover("message-header")
+                                .tooltip(|window, cx| {
+                                    Tooltip::with_meta(
+                                        "Reuse Seed",
+                                        None,
+                                        "Sends this seed with the next requests, to reproduce the response",
+                                        window,
+                                        cx,
+                                    )
+                                })
+                                .on_click({
+                                    let context = context.clone();
+                                    move |_, _window, cx| {
+                                        context.update(cx, |context, cx| {
+                                            context.set_seed(Some(seed), cx)
+                                        })
+                                    }
+                                })
+                        });
+
+                        let post_processing_indicator =
+                            (!message.post_processing.is_empty()).then(|| {
+                                let summary = message
+                                    .post_processing
+                                    .iter()
+                                    .map(|change| change.summary.as_str())
+                                    .collect::<Vec<_>>()
+                                    .join("\n");
+                                div()
+                                    .id("post-processed")
+                                    .child(
+                                        Icon::new(IconName::Sparkle)
+                                            .size(IconSize::XSmall)
+                                            .color(Color::Muted),
+                                    )
+                                    .tooltip(move |window, cx| {
+                                        Tooltip::with_meta(
+                                            "Post-Processed",
+                                            None,
+                                            summary.clone(),
+                                            window,
+                                            cx,
+                                        )
+                                    })
+                            });
+
                         h_flex()
                             .id(("message_header", message_id.as_u64()))
+                            .group("message-header")
                             .pl(cx.margins.gutter.full_width())
                             .h_11()
                             .w_full()
                             .relative()
                             .gap_1p5()
+                            .when(header_settings.tint_background, |header| {
+                                header.bg(role_background)
+                            })
+                            .map(|header| match header_settings.divider {
+                                MessageHeaderDivider::None => header,
+                                MessageHeaderDivider::Solid => {
+                                    header.border_t_1().border_color(divider_color)
+                                }
+                                MessageHeaderDivider::Dashed => header
+                                    .border_t_1()
+                                    .border_dashed()
+                                    .border_color(divider_color),
+                            })
                             .child(sender)
+                            .child(pin_button)
+                            .child(duplicate_menu)
+                            .children(event_log_button)
+                            .children(seed_button)
+                            .children(post_processing_indicator)
                             .children(match &message.cache {
                                 Some(cache) if cache.is_final_anchor => match cache.status {
                                     CacheStatus::Cached => Some(
@@ -1183,6 +1694,62 @@ impl ContextEditor {
                                 },
                                 _ => None,
                             })
+                            .children(message.usage.map(|usage| {
+                                div()
+                                    .id("usage")
+                                    .child(
+                                        Label::new(humanize_token_count(
+                                            usage.token_usage.total_tokens() as usize,
+                                        ))
+                                        .size(LabelSize::XSmall)
+                                        .color(if usage.has_token_drift() {
+                                            Color::Warning
+                                        } else {
+                                            Color::Hint
+                                        }),
+                                    )
+                                    .tooltip(move |window, cx| {
+                                        Tooltip::with_meta(
+                                            "Token Usage",
+                                            None,
+                                            usage_breakdown(&usage),
+                                            window,
+                                            cx,
+                                        )
+                                    })
+                                    .into_any_element()
+                            }))
+                            .children(message.substitution.clone().map(|substitution| {
+                                h_flex()
+                                    .id("substitution")
+                                    .gap_1()
+                                    .child(
+                                        Icon::new(IconName::ArrowRightLeft)
+                                            .size(IconSize::XSmall)
+                                            .color(Color::Hint),
+                                    )
+                                    .child(
+                                        Label::new(substitution.substitute_model.clone())
+                                            .size(LabelSize::XSmall)
+                                            .color(Color::Hint),
+                                    )
+                                    .tooltip(move |window, cx| {
+                                        Tooltip::with_meta(
+                                            format!(
+                                                "Switched from {}",
+                                                substitution.original_model
+                                            ),
+                                            None,
+                                            format!(
+                                                "Used a free model because the {}",
+                                                substitution.reason.label()
+                                            ),
+                                            window,
+                                            cx,
+                                        )
+                                    })
+                                    .into_any_element()
+                            }))
                             .children(match &message.status {
                                 MessageStatus::Error(error) => Some(
                                     Button::new("show-error", "Error")
@@ -1225,6 +1792,15 @@ impl ContextEditor {
                                 ),
                                 _ => None,
                             })
+                            .when(message.role == Role::Assistant, |header| {
+                                header.child(Self::render_message_annotations(
+                                    message_id,
+                                    &context,
+                                    &project,
+                                    context_editor.clone(),
+                                    cx,
+                                ))
+                            })
                             .into_any_element()
                     }
                 })
@@ -1381,6 +1957,82 @@ impl ContextEditor {
         );
     }
 
+    /// Returns the code block under the cursor and the language of its fence.
+    fn code_block_at_cursor(
+        context_editor_view: &Entity<ContextEditor>,
+        cx: &mut Context<Workspace>,
+    ) -> Option<(String, Option<String>)> {
+        const CODE_FENCE_DELIMITER: &'static str = "```";
+
+        let context_editor = context_editor_view.read(cx).editor.clone();
+        context_editor.update(cx, |context_editor, cx| {
+            let snapshot = context_editor.buffer().read(cx).snapshot(cx);
+            let (_, _, snapshot) = snapshot.as_singleton()?;
+
+            let head = context_editor.selections.newest::<Point>(cx).head();
+            let offset = snapshot.point_to_offset(head);
+
+            let range = find_surrounding_code_block(snapshot, offset)?;
+            let mut code = snapshot.text_for_range(range.clone()).collect::<String>();
+            if code.ends_with(CODE_FENCE_DELIMITER) {
+                code.drain((code.len() - CODE_FENCE_DELIMITER.len())..);
+            }
+            if code.trim().is_empty() {
+                return None;
+            }
+
+            let content_start = snapshot.offset_to_point(range.start);
+            let language = content_start.row.checked_sub(1).and_then(|fence_row| {
+                let fence = snapshot
+                    .text_for_range(
+                        Point::new(fence_row, 0)
+                            ..Point::new(fence_row, snapshot.line_len(fence_row)),
+                    )
+                    .collect::<String>();
+                code_fence_language(&fence)
+            });
+            Some((code, language))
+        })
+    }
+
+    pub fn save_snippet(
+        workspace: &mut Workspace,
+        _: &SaveSnippet,
+        window: &mut Window,
+        cx: &mut Context<Workspace>,
+    ) {
+        let Some(agent_panel_delegate) = <dyn AgentPanelDelegate>::try_global(cx) else {
+            return;
+        };
+        let Some(context_editor_view) =
+            agent_panel_delegate.active_context_editor(workspace, window, cx)
+        else {
+            return;
+        };
+        let Some((code, language)) = Self::code_block_at_cursor(&context_editor_view, cx) else {
+            struct NoCodeBlockToast;
+            workspace.show_toast(
+                Toast::new(
+                    NotificationId::unique::<NoCodeBlockToast>(),
+                    "Place the cursor in a code block to save it as a snippet.",
+                )
+                .autohide(),
+                cx,
+            );
+            return;
+        };
+
+        let context = context_editor_view.read(cx).context.read(cx);
+        let origin = SnippetOrigin {
+            context_id: context.id().to_proto(),
+            title: context.summary().or_default().to_string(),
+            path: context.path().map(|path| path.to_path_buf()),
+        };
+        workspace.toggle_modal(window, cx, |window, cx| {
+            SaveSnippetModal::new(code, language, origin, window, cx)
+        });
+    }
+
     pub fn handle_insert_dragged_files(
         workspace: &mut Workspace,
         action: &InsertDraggedFiles,
@@ -1520,7 +2172,8 @@ impl ContextEditor {
     ) {
         let creases = selections_creases(ranges, snapshot, cx);
 
-        self.editor.update(cx, |editor, cx| {
+        let duplicate_attachment = self.editor.update(cx, |editor, cx| {
+            let mut duplicate_attachment = None;
             editor.insert("\n", window, cx);
             for (text, crease_title) in creases {
                 let point = editor.selections.newest::<Point>(cx).head();
@@ -1538,8 +2191,13 @@ impl ContextEditor {
 
                 editor.insert("\n", window, cx);
 
-                let fold_placeholder =
-                    quote_selection_fold_placeholder(crease_title, cx.entity().downgrade());
+                let range = anchor_before.text_anchor..anchor_after.text_anchor;
+                let earlier_range = self.context.read(cx).earlier_duplicate(&range, cx);
+                let fold_placeholder = quote_selection_fold_placeholder(
+                    crease_title.clone(),
+                    earlier_range.is_some(),
+                    cx.entity().downgrade(),
+                );
                 let crease = Crease::inline(
                     anchor_before..anchor_after,
                     fold_placeholder,
@@ -1548,8 +2206,22 @@ impl ContextEditor {
                 );
                 editor.insert_creases(vec![crease], cx);
                 editor.fold_at(start_row, window, cx);
+
+                if let Some(earlier_range) = earlier_range {
+                    duplicate_attachment = Some(DuplicateAttachment {
+                        range,
+                        earlier_range,
+                        icon: IconName::TextSnippet,
+                        label: crease_title.into(),
+                    });
+                }
             }
-        })
+            duplicate_attachment
+        });
+        if duplicate_attachment.is_some() {
+            self.duplicate_attachment = duplicate_attachment;
+            cx.notify();
+        }
     }
 
     fn copy(&mut self, _: &editor::actions::Copy, _window: &mut Window, cx: &mut Context<Self>) {
@@ -1712,6 +2384,11 @@ impl ContextEditor {
             None
         };
 
+        let pasted_text = cx
+            .read_from_clipboard()
+            .and_then(|item| item.text())
+            .filter(|_| images.is_empty() && metadata.is_none());
+
         if images.is_empty() {
             self.editor.update(cx, |editor, cx| {
                 let paste_position = editor.selections.newest::<usize>(cx).head();
@@ -1740,6 +2417,7 @@ impl ContextEditor {
                                         weak_editor.clone(),
                                         metadata.crease.icon_path.clone(),
                                         metadata.crease.label.clone(),
+                                        false,
                                     ),
                                     ..Default::default()
                                 },
@@ -1755,6 +2433,11 @@ impl ContextEditor {
                     }
                 }
             });
+
+            if let Some(pasted_text) = pasted_text {
+                let paste_end = self.editor.read(cx).selections.newest_anchor().head();
+                self.enrich_pasted_stack_trace(&pasted_text, paste_end, window, cx);
+            }
         } else {
             let mut image_positions = Vec::new();
             self.editor.update(cx, |editor, cx| {
@@ -1798,275 +2481,2471 @@ impl ContextEditor {
         }
     }
 
-    fn update_image_blocks(&mut self, cx: &mut Context<Self>) {
-        self.editor.update(cx, |editor, cx| {
-            let buffer = editor.buffer().read(cx).snapshot(cx);
-            let excerpt_id = *buffer.as_singleton().unwrap().0;
-            let old_blocks = std::mem::take(&mut self.image_blocks);
-            let new_blocks = self
-                .context
-                .read(cx)
-                .contents(cx)
-                .map(
-                    |Content::Image {
-                         anchor,
-                         render_image,
-                         ..
-                     }| (anchor, render_image),
+    /// When the pasted text is a stack trace, attaches the code around its
+    /// innermost project frames as folded sections after it, and tags the
+    /// message so the request includes instructions for debugging it.
+    fn enrich_pasted_stack_trace(
+        &mut self,
+        pasted_text: &str,
+        paste_end: Anchor,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(stack_trace) = stack_trace::parse_stack_trace(pasted_text) else {
+            return;
+        };
+        let frames = stack_trace::resolve_frames(&stack_trace.frames, &self.project, cx);
+        if frames.is_empty() {
+            return;
+        }
+
+        self.context.update(cx, |context, cx| {
+            let offset = paste_end.text_anchor.to_offset(context.buffer().read(cx));
+            let Some(message) = context.messages_for_offsets([offset], cx).pop() else {
+                return;
+            };
+            let tag = StackTraceTag {
+                kind: stack_trace.kind,
+                frames: frames
+                    .iter()
+                    .map(|frame| format!("{}:{}", frame.full_path, frame.line))
+                    .collect(),
+            };
+            context.update_metadata(message.id, cx, |metadata| {
+                metadata.stack_trace = Some(tag);
+            });
+        });
+
+        let buffers = frames
+            .into_iter()
+            .map(|frame| {
+                let buffer = self.project.update(cx, |project, cx| {
+                    project.open_buffer(frame.project_path.clone(), cx)
+                });
+                async move { (frame.line, buffer.await) }
+            })
+            .collect::<Vec<_>>();
+        cx.spawn_in(window, async move |this, cx| {
+            let buffers = futures::future::join_all(buffers).await;
+            this.update_in(cx, |this, window, cx| {
+                this.editor.update(cx, |editor, cx| {
+                    editor.change_selections(None, window, cx, |selections| {
+                        selections.select_anchor_ranges([paste_end..paste_end])
+                    });
+                });
+                for (line, buffer) in buffers {
+                    let Some(buffer) = buffer.log_err() else {
+                        continue;
+                    };
+                    let multibuffer = cx.new(|cx| MultiBuffer::singleton(buffer, cx));
+                    let snapshot = multibuffer.read(cx).snapshot(cx);
+                    let max_row = snapshot.max_point().row;
+                    let row = line.saturating_sub(1).min(max_row);
+                    let end_row = (row + STACK_FRAME_CONTEXT_LINES).min(max_row);
+                    let range = Point::new(row.saturating_sub(STACK_FRAME_CONTEXT_LINES), 0)
+                        ..Point::new(end_row, snapshot.line_len(MultiBufferRow(end_row)));
+                    this.quote_ranges(vec![range], snapshot, window, cx);
+                }
+            })
+        })
+        .detach_and_log_err(cx);
+    }
+
+    fn render_message_annotations(
+        message_id: MessageId,
+        context: &Entity<AssistantContext>,
+        project: &Entity<Project>,
+        context_editor: WeakEntity<Self>,
+        cx: &App,
+    ) -> impl IntoElement {
+        let author = comment_author(project, cx);
+        let comment_count = context
+            .read(cx)
+            .comments()
+            .iter()
+            .filter(|comment| comment.message_id == message_id)
+            .count();
+
+        let reactions = context
+            .read(cx)
+            .message_reactions(message_id)
+            .into_iter()
+            .enumerate()
+            .map(|(ix, reaction)| {
+                let reacted = reaction.authors.contains(&author);
+                let authors = reaction.authors.join(", ");
+                Button::new(
+                    ("reaction", ix),
+                    format!("{} {}", reaction.reaction, reaction.authors.len()),
                 )
-                .filter_map(|(anchor, render_image)| {
-                    const MAX_HEIGHT_IN_LINES: u32 = 8;
-                    let anchor = buffer.anchor_in_excerpt(excerpt_id, anchor).unwrap();
-                    let image = render_image.clone();
-                    anchor.is_valid(&buffer).then(|| BlockProperties {
-                        placement: BlockPlacement::Above(anchor),
-                        height: Some(MAX_HEIGHT_IN_LINES),
+                .style(ButtonStyle::Subtle)
+                .label_size(LabelSize::XSmall)
+                .toggle_state(reacted)
+                .tooltip(Tooltip::text(authors))
+                .on_click({
+                    let context = context.clone();
+                    let author = author.clone();
+                    move |_, _, cx| {
+                        context.update(cx, |context, cx| {
+                            context.toggle_reaction(
+                                message_id,
+                                reaction.reaction.clone(),
+                                author.clone(),
+                                cx,
+                            )
+                        });
+                    }
+                })
+            });
+
+        let reaction_picker = PopoverMenu::new(("reaction-picker", message_id.as_u64()))
+            .trigger_with_tooltip(
+                IconButton::new("add-reaction", IconName::ThumbsUp)
+                    .icon_size(IconSize::XSmall)
+                    .icon_color(Color::Muted),
+                Tooltip::text("Add Reaction"),
+            )
+            .anchor(gpui::Corner::TopRight)
+            .menu({
+                let context = context.clone();
+                move |window, cx| {
+                    let context = context.clone();
+                    let author = author.clone();
+                    Some(ContextMenu::build(window, cx, move |mut menu, _, _| {
+                        for reaction in REACTIONS {
+                            let context = context.clone();
+                            let author = author.clone();
+                            menu = menu.entry(reaction, None, move |_, cx| {
+                                context.update(cx, |context, cx| {
+                                    context.toggle_reaction(
+                                        message_id,
+                                        reaction.into(),
+                                        author.clone(),
+                                        cx,
+                                    )
+                                });
+                            });
+                        }
+                        menu
+                    }))
+                }
+            });
+
+        h_flex()
+            .ml_auto()
+            .mr_2()
+            .gap_0p5()
+            .children(reactions)
+            .child(reaction_picker)
+            .child(
+                IconButton::new("comment", IconName::MessageBubbles)
+                    .icon_size(IconSize::XSmall)
+                    .icon_color(Color::Muted)
+                    .when(comment_count > 0, |button| {
+                        button.indicator(Indicator::dot().color(Color::Info))
+                    })
+                    .tooltip(Tooltip::text("Comment on Message"))
+                    .on_click({
+                        let context_editor = context_editor.clone();
+                        move |_, window, cx| {
+                            context_editor
+                                .update(cx, |this, cx| {
+                                    this.start_comment(message_id, None, None, window, cx)
+                                })
+                                .ok();
+                        }
+                    }),
+            )
+            .child(
+                IconButton::new("file-issue", IconName::Github)
+                    .icon_size(IconSize::XSmall)
+                    .icon_color(Color::Muted)
+                    .tooltip(Tooltip::text("File Issue from This Message"))
+                    .on_click(move |_, window, cx| {
+                        context_editor
+                            .update(cx, |this, cx| this.file_issue(message_id, window, cx))
+                            .ok();
+                    }),
+            )
+    }
+
+    /// Files an issue with the message's text in the tracker of the project's
+    /// repository, after confirming with the user, and inserts the URL of the
+    /// created issue at the end of the context.
+    fn file_issue(&mut self, message_id: MessageId, window: &mut Window, cx: &mut Context<Self>) {
+        let context = self.context.read(cx);
+        let Some(message) = context
+            .messages(cx)
+            .find(|message| message.id == message_id)
+        else {
+            return;
+        };
+        let body = context
+            .buffer()
+            .read(cx)
+            .text_for_range(message.offset_range)
+            .collect::<String>()
+            .trim()
+            .to_string();
+        let summary = context
+            .summary()
+            .content()
+            .map(|summary| summary.text.clone());
+        let Some(title) = issue_title(summary.as_deref(), &body) else {
+            return;
+        };
+
+        let tracker = assistant_slash_commands::repository_tracker(&self.project, cx);
+        let tokens = assistant_slash_commands::IssueTrackerTokens::global(cx);
+        let http_client = self.project.read(cx).client().http_client();
+        cx.spawn_in(window, async move |this, cx| {
+            let tracker = tracker.await?;
+            let answer = cx.update(|window, cx| {
+                window.prompt(
+                    PromptLevel::Info,
+                    &format!("File issue \"{title}\" in {}?", tracker.path()),
+                    Some(&body),
+                    &["File Issue", "Cancel"],
+                    cx,
+                )
+            })?;
+            if answer.await != Ok(0) {
+                return Ok(());
+            }
+
+            let url = tracker
+                .create_issue(http_client.as_ref(), &title, &body, &tokens)
+                .await?;
+            this.update(cx, |this, cx| {
+                this.context.update(cx, |context, cx| {
+                    context.buffer().update(cx, |buffer, cx| {
+                        let end = buffer.len();
+                        let separator = match buffer.reversed_chars_at(end).next() {
+                            Some('\n') | None => "",
+                            Some(_) => "\n",
+                        };
+                        buffer.edit(
+                            [(end..end, format!("{separator}Filed issue: {url}\n"))],
+                            None,
+                            cx,
+                        );
+                    })
+                });
+            })
+        })
+        .detach_and_prompt_err("Failed to file issue", window, cx, |_, _, _| None);
+    }
+
+    fn comment_on_selection(
+        &mut self,
+        _: &CommentOnSelection,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let selection = self.editor.read(cx).selections.newest_anchor().clone();
+        let buffer = self.context.read(cx).buffer().read(cx).snapshot();
+        let start = selection.start.text_anchor.to_offset(&buffer);
+        let end = selection.end.text_anchor.to_offset(&buffer);
+        let Some(message) = self
+            .context
+            .read(cx)
+            .messages(cx)
+            .find(|message| message.offset_range.contains(&start))
+        else {
+            return;
+        };
+        // Comment on the selected part of the message, or on all of it.
+        let range = (start < end).then(|| {
+            buffer.anchor_after(start)..buffer.anchor_before(end.min(message.offset_range.end))
+        });
+        self.start_comment(message.id, range, None, window, cx);
+    }
+
+    fn start_comment(
+        &mut self,
+        message_id: MessageId,
+        range: Option<Range<language::Anchor>>,
+        parent_id: Option<CommentId>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(range) = range.or_else(|| {
+            self.context
+                .read(cx)
+                .messages(cx)
+                .find(|message| message.id == message_id)
+                .map(|message| message.anchor_range)
+        }) else {
+            return;
+        };
+        self.cancel_comment(window, cx);
+
+        let comment_editor = cx.new(|cx| {
+            let mut editor = Editor::single_line(window, cx);
+            editor.set_placeholder_text(
+                if parent_id.is_some() {
+                    "Reply…"
+                } else {
+                    "Add a comment…"
+                },
+                cx,
+            );
+            editor
+        });
+        let context_editor = cx.entity().downgrade();
+        let block_id = self.editor.update(cx, |editor, cx| {
+            let buffer = editor.buffer().read(cx).snapshot(cx);
+            let excerpt_id = *buffer.as_singleton().unwrap().0;
+            let anchor = buffer.anchor_in_excerpt(excerpt_id, range.end)?;
+            let comment_editor = comment_editor.clone();
+            editor
+                .insert_blocks(
+                    [BlockProperties {
+                        placement: BlockPlacement::Below(anchor),
+                        height: Some(2),
                         style: BlockStyle::Sticky,
                         render: Arc::new(move |cx| {
-                            let image_size = size_for_image(
-                                &image,
-                                size(
-                                    cx.max_width - cx.margins.gutter.full_width(),
-                                    MAX_HEIGHT_IN_LINES as f32 * cx.line_height,
-                                ),
-                            );
+                            let confirm_editor = context_editor.clone();
+                            let cancel_editor = context_editor.clone();
                             h_flex()
                                 .pl(cx.margins.gutter.full_width())
+                                .pr_4()
+                                .py_1()
+                                .w_full()
                                 .child(
-                                    img(image.clone())
-                                        .object_fit(gpui::ObjectFit::ScaleDown)
-                                        .w(image_size.width)
-                                        .h(image_size.height),
+                                    div()
+                                        .w_full()
+                                        .px_2()
+                                        .py_1()
+                                        .border_1()
+                                        .rounded_sm()
+                                        .border_color(cx.theme().colors().border_focused)
+                                        .bg(cx.theme().colors().editor_background)
+                                        .on_action(move |_: &menu::Confirm, window, cx| {
+                                            confirm_editor
+                                                .update(cx, |this, cx| {
+                                                    this.submit_comment(window, cx)
+                                                })
+                                                .ok();
+                                        })
+                                        .on_action(
+                                            move |_: &editor::actions::Cancel, window, cx| {
+                                                cancel_editor
+                                                    .update(cx, |this, cx| {
+                                                        this.cancel_comment(window, cx)
+                                                    })
+                                                    .ok();
+                                            },
+                                        )
+                                        .child(comment_editor.clone()),
                                 )
                                 .into_any_element()
                         }),
                         priority: 0,
                         render_in_minimap: false,
-                    })
-                })
-                .collect::<Vec<_>>();
+                    }],
+                    None,
+                    cx,
+                )
+                .pop()
+        });
+        let Some(block_id) = block_id else {
+            return;
+        };
 
-            editor.remove_blocks(old_blocks, None, cx);
-            let ids = editor.insert_blocks(new_blocks, None, cx);
-            self.image_blocks = HashSet::from_iter(ids);
+        window.focus(&comment_editor.focus_handle(cx));
+        self.comment_composer = Some(CommentComposer {
+            message_id,
+            range,
+            parent_id,
+            editor: comment_editor,
+            block_id,
         });
     }
 
-    fn split(&mut self, _: &Split, _window: &mut Window, cx: &mut Context<Self>) {
+    fn submit_comment(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(composer) = self.comment_composer.as_ref() else {
+            return;
+        };
+        let text = composer.editor.read(cx).text(cx);
+        if text.trim().is_empty() {
+            return;
+        }
+        let author = comment_author(&self.project, cx);
+        let (message_id, range, parent_id) = (
+            composer.message_id,
+            composer.range.clone(),
+            composer.parent_id,
+        );
         self.context.update(cx, |context, cx| {
-            let selections = self.editor.read(cx).selections.disjoint_anchors();
-            for selection in selections.as_ref() {
-                let buffer = self.editor.read(cx).buffer().read(cx).snapshot(cx);
-                let range = selection
-                    .map(|endpoint| endpoint.to_offset(&buffer))
-                    .range();
-                context.split_message(range, cx);
-            }
+            context.add_comment(
+                message_id,
+                range,
+                parent_id,
+                author,
+                text.trim().to_string().into(),
+                cx,
+            )
         });
+        self.cancel_comment(window, cx);
     }
 
-    fn save(&mut self, _: &Save, _window: &mut Window, cx: &mut Context<Self>) {
-        self.context.update(cx, |context, cx| {
-            context.save(Some(Duration::from_millis(500)), self.fs.clone(), cx)
+    fn cancel_comment(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(composer) = self.comment_composer.take() else {
+            return;
+        };
+        let had_focus = composer
+            .editor
+            .focus_handle(cx)
+            .contains_focused(window, cx);
+        self.editor.update(cx, |editor, cx| {
+            editor.remove_blocks(HashSet::from_iter([composer.block_id]), None, cx);
         });
+        if had_focus {
+            window.focus(&self.editor.focus_handle(cx));
+        }
     }
 
-    pub fn title(&self, cx: &App) -> SharedString {
-        self.context.read(cx).summary().or_default()
+    /// Renders each comment thread below the range of the message it's about.
+    /// Highlights the rows where a response that was cut off by the output
+    /// token limit was continued by another request.
+    fn update_continuation_seams(&mut self, cx: &mut Context<Self>) {
+        let seams = self.context.read(cx).continuation_seams().to_vec();
+        let color = cx.theme().status().info_background.opacity(0.3);
+        self.editor.update(cx, |editor, cx| {
+            editor.clear_row_highlights::<ContinuationSeam>();
+            let snapshot = editor.buffer().read(cx).snapshot(cx);
+            let Some((&excerpt_id, _, _)) = snapshot.as_singleton() else {
+                return;
+            };
+            for seam in seams {
+                if let Some(anchor) = snapshot.anchor_in_excerpt(excerpt_id, seam) {
+                    editor.highlight_rows::<ContinuationSeam>(
+                        anchor..anchor,
+                        color,
+                        RowHighlightOptions {
+                            autoscroll: false,
+                            include_gutter: true,
+                        },
+                        cx,
+                    );
+                }
+            }
+            cx.notify();
+        });
     }
 
-    pub fn regenerate_summary(&mut self, cx: &mut Context<Self>) {
-        self.context
-            .update(cx, |context, cx| context.summarize(true, cx));
+    /// Shows a "Load More" button below each slash command output that still
+    /// has pages that haven't been inserted.
+    fn update_load_more_blocks(&mut self, cx: &mut Context<Self>) {
+        let context = self.context.downgrade();
+        let pending_output_pages = self
+            .context
+            .read(cx)
+            .pending_output_pages()
+            .iter()
+            .map(|(command_id, pages)| (*command_id, pages.end, pages.remaining_lines()))
+            .collect::<Vec<_>>();
+        self.editor.update(cx, |editor, cx| {
+            let buffer = editor.buffer().read(cx).snapshot(cx);
+            let excerpt_id = *buffer.as_singleton().unwrap().0;
+            let old_blocks = std::mem::take(&mut self.load_more_blocks);
+            let new_blocks = pending_output_pages
+                .into_iter()
+                .enumerate()
+                .filter_map(|(ix, (command_id, end, remaining_lines))| {
+                    let anchor = buffer.anchor_in_excerpt(excerpt_id, end)?;
+                    let context = context.clone();
+                    Some(BlockProperties {
+                        placement: BlockPlacement::Below(anchor),
+                        height: Some(1),
+                        style: BlockStyle::Sticky,
+                        render: Arc::new(move |cx| {
+                            let context = context.clone();
+                            h_flex()
+                                .pl(cx.margins.gutter.full_width())
+                                .pr_4()
+                                .w_full()
+                                .child(
+                                    Button::new(
+                                        ("load-more-output", ix),
+                                        format!("Load More ({remaining_lines} more lines)"),
+                                    )
+                                    .icon(IconName::ChevronDown)
+                                    .icon_position(IconPosition::Start)
+                                    .icon_size(IconSize::Small)
+                                    .label_size(LabelSize::Small)
+                                    .on_click(
+                                        move |_, _window, cx| {
+                                            context
+                                                .update(cx, |context, cx| {
+                                                    context.load_more_command_output(command_id, cx)
+                                                })
+                                                .ok();
+                                        },
+                                    ),
+                                )
+                                .into_any_element()
+                        }),
+                        priority: 0,
+                        render_in_minimap: false,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            editor.remove_blocks(old_blocks, None, cx);
+            let ids = editor.insert_blocks(new_blocks, None, cx);
+            self.load_more_blocks = HashSet::from_iter(ids);
+        });
     }
 
-    fn render_notice(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
-        // This was previously gated behind the `zed-pro` feature flag. Since we
-        // aren't planning to ship that right now, we're just hard-coding this
-        // value to not show the nudge.
-        let nudge = Some(false);
+    /// Matches each assistant message against the sources attached before it,
+    /// and shows the sources it quotes as footnotes below the message.
+    fn update_citations(&mut self, cx: &mut Context<Self>) {
+        let context = self.context.read(cx);
+        let buffer = context.buffer().read(cx).snapshot();
+        let sources = context
+            .attachments(cx)
+            .into_iter()
+            .filter_map(|attachment| match attachment.kind {
+                ContextAttachmentKind::SlashCommandOutput { .. } => Some((
+                    attachment.range.clone(),
+                    attachment.label.clone(),
+                    attachment.icon,
+                    attachment.file_path(),
+                )),
+                ContextAttachmentKind::Image { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        if sources.is_empty() && self.citation_blocks.is_empty() {
+            return;
+        }
+        let messages = context
+            .messages(cx)
+            .filter(|message| message.role == Role::Assistant)
+            .filter(|message| message.status == MessageStatus::Done)
+            .map(|message| message.offset_range)
+            .collect::<Vec<_>>();
+
+        self.pending_citations = cx.spawn(async move |this, cx| {
+            cx.background_executor()
+                .timer(Duration::from_millis(300))
+                .await;
+
+            let citations = cx
+                .background_spawn(async move {
+                    let sources = sources
+                        .into_iter()
+                        .map(|(range, label, icon, path)| {
+                            let range =
+                                range.start.to_offset(&buffer)..range.end.to_offset(&buffer);
+                            let text = buffer.text_for_range(range.clone()).collect::<String>();
+                            (range, text, label, icon, path)
+                        })
+                        .collect::<Vec<_>>();
+                    let mut cited_messages = Vec::new();
+                    for message_range in messages {
+                        let available_sources = sources
+                            .iter()
+                            .filter(|(range, ..)| range.end <= message_range.start)
+                            .collect::<Vec<_>>();
+                        if available_sources.is_empty() {
+                            continue;
+                        }
+                        let message = buffer
+                            .text_for_range(message_range.clone())
+                            .collect::<String>();
+                        let source_texts = available_sources
+                            .iter()
+                            .map(|(_, text, ..)| text.as_str())
+                            .collect::<Vec<_>>();
+
+                        let mut cited_sources = Vec::<CitedSource>::new();
+                        let mut footnotes = HashMap::default();
+                        for citation in citations::find_citations(&message, &source_texts) {
+                            let (range, text, label, icon, path) =
+                                available_sources[citation.source_ix];
+                            let footnote_ix =
+                                *footnotes.entry(citation.source_ix).or_insert_with(|| {
+                                    let start = range.start + citation.source_range.start;
+                                    let end = range.start + citation.source_range.end;
+                                    cited_sources.push(CitedSource {
+                                        label: label.clone(),
+                                        icon: *icon,
+                                        range: buffer.anchor_after(start)
+                                            ..buffer.anchor_before(end),
+                                        file_location: path.clone().zip(citations::file_row(
+                                            text,
+                                            citation.source_range.start,
+                                        )),
+                                        claims: Vec::new(),
+                                    });
+                                    cited_sources.len() - 1
+                                });
+                            cited_sources[footnote_ix]
+                                .claims
+                                .push(message[citation.claim].trim().to_string());
+                        }
+                        if !cited_sources.is_empty() {
+                            let end = message_range.end.saturating_sub(1).max(message_range.start);
+                            cited_messages.push((buffer.anchor_before(end), cited_sources));
+                        }
+                    }
+                    cited_messages
+                })
+                .await;
 
-        if nudge.map_or(false, |value| value) {
-            Some(
-                h_flex()
-                    .p_3()
-                    .border_b_1()
-                    .border_color(cx.theme().colors().border_variant)
-                    .bg(cx.theme().colors().editor_background)
-                    .justify_between()
-                    .child(
-                        h_flex()
-                            .gap_3()
-                            .child(Icon::new(IconName::ZedAssistant).color(Color::Accent))
-                            .child(Label::new("Zed AI is here! Get started by signing in →")),
-                    )
-                    .child(
-                        Button::new("sign-in", "Sign in")
-                            .size(ButtonSize::Compact)
-                            .style(ButtonStyle::Filled)
-                            .on_click(cx.listener(|this, _event, _window, cx| {
-                                let client = this
-                                    .workspace
-                                    .read_with(cx, |workspace, _| workspace.client().clone())
-                                    .log_err();
+            this.update(cx, |this, cx| this.update_citation_blocks(citations, cx))
+                .ok()
+        });
+    }
 
-                                if let Some(client) = client {
-                                    cx.spawn(async move |context_editor, cx| {
-                                        match client.authenticate_and_connect(true, cx).await {
-                                            util::ConnectionResult::Timeout => {
-                                                log::error!("Authentication timeout")
-                                            }
-                                            util::ConnectionResult::ConnectionReset => {
-                                                log::error!("Connection reset")
-                                            }
-                                            util::ConnectionResult::Result(r) => {
-                                                if r.log_err().is_some() {
-                                                    context_editor
-                                                        .update(cx, |_, cx| cx.notify())
-                                                        .ok();
-                                                }
-                                            }
-                                        }
-                                    })
-                                    .detach()
-                                }
-                            })),
-                    )
-                    .into_any_element(),
+    /// Highlights the lines that the edit suggestions in assistant messages
+    /// add and remove, in diff code blocks and in pairs of code blocks
+    /// labeled as before and after.
+    fn update_diff_highlights(&mut self, cx: &mut Context<Self>) {
+        let context = self.context.read(cx);
+        let buffer = context.buffer().read(cx).snapshot();
+        let messages = context
+            .messages(cx)
+            .filter(|message| message.role == Role::Assistant)
+            .filter(|message| message.status == MessageStatus::Done)
+            .map(|message| message.offset_range)
+            .collect::<Vec<_>>();
+
+        self.pending_diff_highlights = cx.spawn(async move |this, cx| {
+            cx.background_executor()
+                .timer(Duration::from_millis(300))
+                .await;
+
+            let lines = cx
+                .background_spawn(async move {
+                    let mut lines = Vec::new();
+                    for message_range in messages {
+                        let message = buffer
+                            .text_for_range(message_range.clone())
+                            .collect::<String>();
+                        lines.extend(message_diffs::find_diff_lines(&message).into_iter().map(
+                            |line| {
+                                let start = message_range.start + line.range.start;
+                                let end = message_range.start + line.range.end;
+                                (
+                                    buffer.anchor_after(start)..buffer.anchor_before(end),
+                                    line.kind,
+                                )
+                            },
+                        ));
+                    }
+                    lines
+                })
+                .await;
+
+            this.update(cx, |this, cx| {
+                let added_color = cx.theme().status().created_background;
+                let removed_color = cx.theme().status().deleted_background;
+                this.editor.update(cx, |editor, cx| {
+                    editor.clear_row_highlights::<AddedDiffLine>();
+                    editor.clear_row_highlights::<RemovedDiffLine>();
+                    let snapshot = editor.buffer().read(cx).snapshot(cx);
+                    let Some((&excerpt_id, _, _)) = snapshot.as_singleton() else {
+                        return;
+                    };
+                    let options = RowHighlightOptions {
+                        autoscroll: false,
+                        include_gutter: true,
+                    };
+                    for (range, kind) in lines {
+                        let (Some(start), Some(end)) = (
+                            snapshot.anchor_in_excerpt(excerpt_id, range.start),
+                            snapshot.anchor_in_excerpt(excerpt_id, range.end),
+                        ) else {
+                            continue;
+                        };
+                        match kind {
+                            DiffLineKind::Added => editor.highlight_rows::<AddedDiffLine>(
+                                start..end,
+                                added_color,
+                                options,
+                                cx,
+                            ),
+                            DiffLineKind::Removed => editor.highlight_rows::<RemovedDiffLine>(
+                                start..end,
+                                removed_color,
+                                options,
+                                cx,
+                            ),
+                        }
+                    }
+                    cx.notify();
+                });
+            })
+            .ok()
+        });
+    }
+
+    /// Lists the web pages each response cites below it.
+    fn update_web_source_blocks(&mut self, cx: &mut Context<Self>) {
+        let context = self.context.read(cx);
+        let buffer = context.buffer().read(cx).snapshot();
+        let sourced_messages = context
+            .messages(cx)
+            .filter(|message| !message.web_sources.is_empty())
+            .map(|message| {
+                let range = message.offset_range;
+                let end = range.end.saturating_sub(1).max(range.start);
+                (buffer.anchor_before(end), message.web_sources)
+            })
+            .collect::<Vec<_>>();
+        if sourced_messages.is_empty() && self.web_source_blocks.is_empty() {
+            return;
+        }
+
+        self.editor.update(cx, |editor, cx| {
+            let buffer = editor.buffer().read(cx).snapshot(cx);
+            let excerpt_id = *buffer.as_singleton().unwrap().0;
+            self.web_source_blocks.update(
+                sourced_messages
+                    .into_iter()
+                    .map(|sourced_message| (sourced_message, ())),
+                |(anchor, sources), ()| {
+                    let anchor = buffer.anchor_in_excerpt(excerpt_id, *anchor)?;
+                    let sources = sources.clone();
+                    Some(BlockProperties {
+                        placement: BlockPlacement::Below(anchor),
+                        height: Some(sources.len() as u32 + 1),
+                        style: BlockStyle::Sticky,
+                        render: Arc::new(move |cx| Self::render_web_sources(&sources, cx)),
+                        priority: 0,
+                        render_in_minimap: false,
+                    })
+                },
+                editor,
+                cx,
+            );
+        });
+    }
+
+    fn render_web_sources(sources: &[WebSource], cx: &mut BlockContext) -> AnyElement {
+        v_flex()
+            .id(cx.block_id)
+            .pl(cx.margins.gutter.full_width())
+            .pr_4()
+            .w_full()
+            .child(
+                Label::new("Web Sources")
+                    .size(LabelSize::XSmall)
+                    .color(Color::Muted),
             )
-        } else if let Some(configuration_error) = configuration_error(cx) {
-            let label = match configuration_error {
-                ConfigurationError::NoProvider => "No LLM provider selected.",
-                ConfigurationError::ProviderNotAuthenticated => "LLM provider is not configured.",
-                ConfigurationError::ProviderPendingTermsAcceptance(_) => {
-                    "LLM provider requires accepting the Terms of Service."
-                }
-            };
-            Some(
+            .children(sources.iter().enumerate().map(|(ix, source)| {
+                let url = source.url.clone();
+                let title = match &source.title {
+                    Some(title) => util::truncate_and_trailoff(title, MAX_WEB_SOURCE_TITLE_LEN),
+                    None => util::truncate_and_trailoff(&url, MAX_WEB_SOURCE_TITLE_LEN),
+                };
+                h_flex().child(
+                    Button::new(("web-source", ix), format!("[{}] {}", ix + 1, title))
+                        .icon(IconName::Globe)
+                        .icon_size(IconSize::XSmall)
+                        .icon_position(IconPosition::Start)
+                        .icon_color(Color::Muted)
+                        .label_size(LabelSize::XSmall)
+                        .tooltip(Tooltip::text(url.clone()))
+                        .on_click(move |_, _, cx| cx.open_url(&url)),
+                )
+            }))
+            .into_any_element()
+    }
+
+    fn update_citation_blocks(
+        &mut self,
+        citations: Vec<(language::Anchor, Vec<CitedSource>)>,
+        cx: &mut Context<Self>,
+    ) {
+        let context_editor = cx.entity().downgrade();
+        self.editor.update(cx, |editor, cx| {
+            let buffer = editor.buffer().read(cx).snapshot(cx);
+            let excerpt_id = *buffer.as_singleton().unwrap().0;
+            self.citation_blocks.update(
+                citations.into_iter().map(|citation| (citation, ())),
+                |(anchor, sources), ()| {
+                    let anchor = buffer.anchor_in_excerpt(excerpt_id, *anchor)?;
+                    let sources = sources.clone();
+                    let context_editor = context_editor.clone();
+                    Some(BlockProperties {
+                        placement: BlockPlacement::Below(anchor),
+                        height: Some(1),
+                        style: BlockStyle::Sticky,
+                        render: Arc::new(move |cx| {
+                            Self::render_citations(&sources, context_editor.clone(), cx)
+                        }),
+                        priority: 0,
+                        render_in_minimap: false,
+                    })
+                },
+                editor,
+                cx,
+            );
+        });
+    }
+
+    fn render_citations(
+        sources: &[CitedSource],
+        context_editor: WeakEntity<Self>,
+        cx: &mut BlockContext,
+    ) -> AnyElement {
+        h_flex()
+            .id(cx.block_id)
+            .pl(cx.margins.gutter.full_width())
+            .pr_4()
+            .w_full()
+            .gap_1()
+            .child(
+                Label::new("Sources")
+                    .size(LabelSize::XSmall)
+                    .color(Color::Muted),
+            )
+            .children(sources.iter().enumerate().map(|(ix, source)| {
+                let label = match &source.file_location {
+                    Some((_, row)) => format!("[{}] {}:{}", ix + 1, source.label, row + 1),
+                    None => format!("[{}] {}", ix + 1, source.label),
+                };
                 h_flex()
-                    .px_3()
-                    .py_2()
-                    .border_b_1()
-                    .border_color(cx.theme().colors().border_variant)
-                    .bg(cx.theme().colors().editor_background)
-                    .justify_between()
-                    .child(
-                        h_flex()
-                            .gap_3()
-                            .child(
-                                Icon::new(IconName::Warning)
-                                    .size(IconSize::Small)
-                                    .color(Color::Warning),
-                            )
-                            .child(Label::new(label)),
-                    )
                     .child(
-                        Button::new("open-configuration", "Configure Providers")
-                            .size(ButtonSize::Compact)
-                            .icon(Some(IconName::SlidersVertical))
-                            .icon_size(IconSize::Small)
+                        Button::new(("cited-source", ix), label)
+                            .icon(source.icon)
+                            .icon_size(IconSize::XSmall)
                             .icon_position(IconPosition::Start)
-                            .style(ButtonStyle::Filled)
+                            .icon_color(Color::Muted)
+                            .label_size(LabelSize::XSmall)
+                            .tooltip(Tooltip::text(source.claims.join("\n")))
                             .on_click({
-                                let focus_handle = self.focus_handle(cx).clone();
-                                move |_event, window, cx| {
-                                    focus_handle.dispatch_action(
-                                        &zed_actions::agent::OpenConfiguration,
-                                        window,
-                                        cx,
-                                    );
+                                let context_editor = context_editor.clone();
+                                let range = source.range.clone();
+                                move |_, window, cx| {
+                                    context_editor
+                                        .update(cx, |this, cx| {
+                                            this.jump_to_cited_source(range.clone(), window, cx)
+                                        })
+                                        .ok();
                                 }
                             }),
                     )
-                    .into_any_element(),
-            )
-        } else {
-            None
+                    .children(source.file_location.clone().map(|(path, row)| {
+                        let context_editor = context_editor.clone();
+                        IconButton::new(("open-cited-file", ix), IconName::ArrowUpRight)
+                            .icon_size(IconSize::XSmall)
+                            .icon_color(Color::Muted)
+                            .tooltip(Tooltip::text("Open File at Cited Line"))
+                            .on_click(move |_, window, cx| {
+                                context_editor
+                                    .update(cx, |this, cx| {
+                                        this.open_cited_file(&path, row, window, cx)
+                                    })
+                                    .ok();
+                            })
+                    }))
+            }))
+            .into_any_element()
+    }
+
+    fn jump_to_cited_source(
+        &mut self,
+        range: Range<language::Anchor>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.editor.update(cx, |editor, cx| {
+            let snapshot = editor.buffer().read(cx).snapshot(cx);
+            let (&excerpt_id, _, _) = snapshot.as_singleton().unwrap();
+            let (Some(start), Some(end)) = (
+                snapshot.anchor_in_excerpt(excerpt_id, range.start),
+                snapshot.anchor_in_excerpt(excerpt_id, range.end),
+            ) else {
+                return;
+            };
+            let range = start..end;
+            editor.unfold_ranges(&[range.clone()], true, false, cx);
+            editor.change_selections(Some(Autoscroll::center()), window, cx, |s| {
+                s.select_anchor_ranges([range])
+            });
+        });
+    }
+
+    fn open_cited_file(
+        &mut self,
+        path: &str,
+        row: u32,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(project_path) = self.project.read(cx).find_project_path(path, cx) else {
+            return;
+        };
+        let Some(open_task) = self
+            .workspace
+            .update(cx, |workspace, cx| {
+                workspace.open_path(project_path, None, true, window, cx)
+            })
+            .log_err()
+        else {
+            return;
+        };
+        cx.spawn_in(window, async move |_, cx| {
+            let item = open_task.await?;
+            if let Some(editor) = item.downcast::<Editor>() {
+                editor.update_in(cx, |editor, window, cx| {
+                    editor.go_to_singleton_buffer_point(Point::new(row, 0), window, cx);
+                })?;
+            }
+            anyhow::Ok(())
+        })
+        .detach_and_log_err(cx);
+    }
+
+    /// Checks the paths and symbols mentioned by the assistant messages that
+    /// were completed since the last check.
+    fn check_references(&mut self, cx: &mut Context<Self>) {
+        let context = self.context.read(cx);
+        let buffer = context.buffer().read(cx).snapshot();
+        let messages = context
+            .messages(cx)
+            .filter(|message| message.status == MessageStatus::Done)
+            .filter(|message| !self.unresolved_references.contains_key(&message.id))
+            .map(|message| (message.id, message.role, message.offset_range))
+            .collect::<Vec<_>>();
+
+        for (message_id, role, range) in messages {
+            if role != Role::Assistant {
+                self.unresolved_references
+                    .insert(message_id, Some(Vec::new()));
+                continue;
+            }
+            self.unresolved_references.insert(message_id, None);
+            let message = buffer.text_for_range(range.clone()).collect::<String>();
+            let check = reference_check::check_references(&message, &self.project, cx);
+            let buffer = buffer.clone();
+            cx.spawn(async move |this, cx| {
+                let unresolved = check
+                    .await
+                    .into_iter()
+                    .map(|reference| UnresolvedReferenceRange {
+                        range: buffer.anchor_after(range.start + reference.reference.range.start)
+                            ..buffer.anchor_before(range.start + reference.reference.range.end),
+                        reference,
+                    })
+                    .collect::<Vec<_>>();
+                this.update(cx, |this, cx| {
+                    this.unresolved_references
+                        .insert(message_id, Some(unresolved));
+                    this.update_unresolved_references(cx);
+                })
+            })
+            .detach_and_log_err(cx);
         }
     }
 
-    fn render_send_button(&self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        let focus_handle = self.focus_handle(cx).clone();
+    /// Underlines the references that weren't found in the project, and offers
+    /// to correct them below the messages they're in.
+    fn update_unresolved_references(&mut self, cx: &mut Context<Self>) {
+        let context_editor = cx.entity().downgrade();
+        let context = self.context.read(cx);
+        let buffer = context.buffer().read(cx).snapshot();
+        let messages = context
+            .messages(cx)
+            .filter_map(|message| {
+                let unresolved = self.unresolved_references.get(&message.id)?.as_ref()?;
+                if unresolved.is_empty() {
+                    return None;
+                }
+                let end = message
+                    .offset_range
+                    .end
+                    .saturating_sub(1)
+                    .max(message.offset_range.start);
+                Some((message.id, buffer.anchor_before(end), unresolved.clone()))
+            })
+            .collect::<Vec<_>>();
+        let underline = HighlightStyle {
+            underline: Some(UnderlineStyle {
+                thickness: px(1.),
+                color: Some(cx.theme().status().warning),
+                wavy: true,
+            }),
+            ..Default::default()
+        };
 
-        let (style, tooltip) = match token_state(&self.context, cx) {
-            Some(TokenState::NoTokensLeft { .. }) => (
-                ButtonStyle::Tinted(TintColor::Error),
-                Some(Tooltip::text("Token limit reached")(window, cx)),
-            ),
-            Some(TokenState::HasMoreTokens {
-                over_warn_threshold,
-                ..
-            }) => {
-                let (style, tooltip) = if over_warn_threshold {
-                    (
-                        ButtonStyle::Tinted(TintColor::Warning),
-                        Some(Tooltip::text("Token limit is close to exhaustion")(
-                            window, cx,
-                        )),
+        self.editor.update(cx, |editor, cx| {
+            let buffer = editor.buffer().read(cx).snapshot(cx);
+            let excerpt_id = *buffer.as_singleton().unwrap().0;
+            let ranges = messages
+                .iter()
+                .flat_map(|(_, _, unresolved)| unresolved)
+                .filter_map(|unresolved| {
+                    Some(
+                        buffer.anchor_in_excerpt(excerpt_id, unresolved.range.start)?
+                            ..buffer.anchor_in_excerpt(excerpt_id, unresolved.range.end)?,
                     )
+                })
+                .collect::<Vec<_>>();
+            editor.highlight_text::<UnresolvedReferenceHighlight>(ranges, underline, cx);
+
+            let old_blocks = std::mem::take(&mut self.unresolved_reference_blocks);
+            let new_blocks = messages
+                .into_iter()
+                .filter_map(|(message_id, anchor, unresolved)| {
+                    let anchor = buffer.anchor_in_excerpt(excerpt_id, anchor)?;
+                    let context_editor = context_editor.clone();
+                    Some(BlockProperties {
+                        placement: BlockPlacement::Below(anchor),
+                        height: Some(1),
+                        style: BlockStyle::Sticky,
+                        render: Arc::new(move |cx| {
+                            Self::render_unresolved_references(
+                                message_id,
+                                &unresolved,
+                                context_editor.clone(),
+                                cx,
+                            )
+                        }),
+                        priority: 0,
+                        render_in_minimap: false,
+                    })
+                })
+                .collect::<Vec<_>>();
+            editor.remove_blocks(old_blocks, None, cx);
+            let ids = editor.insert_blocks(new_blocks, None, cx);
+            self.unresolved_reference_blocks = HashSet::from_iter(ids);
+        });
+    }
+
+    fn render_unresolved_references(
+        message_id: MessageId,
+        unresolved: &[UnresolvedReferenceRange],
+        context_editor: WeakEntity<Self>,
+        cx: &mut BlockContext,
+    ) -> AnyElement {
+        let details = unresolved
+            .iter()
+            .map(|unresolved| {
+                let reference = &unresolved.reference;
+                if reference.suggestions.is_empty() {
+                    format!("{}: nothing similar found", reference.reference.text)
                 } else {
-                    (ButtonStyle::Filled, None)
-                };
-                (style, tooltip)
-            }
-            None => (ButtonStyle::Filled, None),
+                    format!(
+                        "{}: did you mean {}?",
+                        reference.reference.text,
+                        reference.suggestions.join(", ")
+                    )
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let label = if unresolved.len() == 1 {
+            "1 path or symbol wasn't found in the project".to_string()
+        } else {
+            format!(
+                "{} paths or symbols weren't found in the project",
+                unresolved.len()
+            )
+        };
+
+        h_flex()
+            .id(("unresolved-references", message_id.as_u64()))
+            .pl(cx.margins.gutter.full_width())
+            .pr_4()
+            .w_full()
+            .gap_1()
+            .child(
+                Icon::new(IconName::Warning)
+                    .size(IconSize::XSmall)
+                    .color(Color::Warning),
+            )
+            .child(
+                div()
+                    .id("unresolved-references-label")
+                    .child(
+                        Label::new(label)
+                            .size(LabelSize::XSmall)
+                            .color(Color::Muted),
+                    )
+                    .tooltip(Tooltip::text(details)),
+            )
+            .child(
+                Button::new("correct-references", "Correct This")
+                    .icon(IconName::Sparkle)
+                    .icon_size(IconSize::XSmall)
+                    .icon_position(IconPosition::Start)
+                    .icon_color(Color::Muted)
+                    .label_size(LabelSize::XSmall)
+                    .tooltip(Tooltip::text(
+                        "Ask the model to correct the response using the closest matches",
+                    ))
+                    .on_click(move |_, window, cx| {
+                        context_editor
+                            .update(cx, |this, cx| {
+                                this.correct_references(message_id, window, cx)
+                            })
+                            .ok();
+                    }),
+            )
+            .into_any_element()
+    }
+
+    /// Asks the model to correct the references in the message that weren't
+    /// found in the project, listing the closest matches.
+    fn correct_references(
+        &mut self,
+        message_id: MessageId,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(Some(unresolved)) = self.unresolved_references.get_mut(&message_id) else {
+            return;
+        };
+        let unresolved = std::mem::take(unresolved)
+            .into_iter()
+            .map(|unresolved| unresolved.reference)
+            .collect::<Vec<_>>();
+        if unresolved.is_empty() {
+            return;
+        }
+        let prompt = reference_check::correction_prompt(&unresolved);
+        self.context.update(cx, |context, cx| {
+            context.buffer().update(cx, |buffer, cx| {
+                let end = buffer.len();
+                let separator = match buffer.reversed_chars_at(end).next() {
+                    Some('\n') | None => "",
+                    Some(_) => "\n",
+                };
+                buffer.edit([(end..end, format!("{separator}{prompt}"))], None, cx);
+            })
+        });
+        self.update_unresolved_references(cx);
+        self.assist(&Assist, window, cx);
+    }
+
+    fn update_comment_blocks(&mut self, cx: &mut Context<Self>) {
+        let context_editor = cx.entity().downgrade();
+        let comments = self.context.read(cx).comments().to_vec();
+        self.editor.update(cx, |editor, cx| {
+            let buffer = editor.buffer().read(cx).snapshot(cx);
+            let excerpt_id = *buffer.as_singleton().unwrap().0;
+            let old_blocks = std::mem::take(&mut self.comment_blocks);
+            let new_blocks = comments
+                .iter()
+                .filter(|comment| comment.parent_id.is_none())
+                .filter_map(|root| {
+                    let anchor = buffer.anchor_in_excerpt(excerpt_id, root.range.end)?;
+                    if !anchor.is_valid(&buffer) {
+                        return None;
+                    }
+                    let thread = comments
+                        .iter()
+                        .filter(|comment| {
+                            comment.id == root.id || comment.parent_id == Some(root.id)
+                        })
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    let root = root.clone();
+                    let context_editor = context_editor.clone();
+                    Some(BlockProperties {
+                        placement: BlockPlacement::Below(anchor),
+                        height: Some(thread.len() as u32 + 1),
+                        style: BlockStyle::Sticky,
+                        render: Arc::new(move |cx| {
+                            Self::render_comment_thread(&root, &thread, context_editor.clone(), cx)
+                        }),
+                        priority: 0,
+                        render_in_minimap: false,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            editor.remove_blocks(old_blocks, None, cx);
+            let ids = editor.insert_blocks(new_blocks, None, cx);
+            self.comment_blocks = HashSet::from_iter(ids);
+        });
+    }
+
+    fn render_comment_thread(
+        root: &MessageComment,
+        thread: &[MessageComment],
+        context_editor: WeakEntity<Self>,
+        cx: &mut BlockContext,
+    ) -> AnyElement {
+        let (message_id, range, root_id) = (root.message_id, root.range.clone(), root.id);
+        h_flex()
+            .id(("comment-thread", root_id.as_u64()))
+            .pl(cx.margins.gutter.full_width())
+            .pr_4()
+            .w_full()
+            .child(
+                v_flex()
+                    .w_full()
+                    .pl_2()
+                    .border_l_2()
+                    .border_color(cx.theme().colors().border_focused)
+                    .children(thread.iter().map(|comment| {
+                        h_flex()
+                            .gap_1p5()
+                            .h(cx.line_height)
+                            .child(
+                                Label::new(comment.author.clone())
+                                    .size(LabelSize::Small)
+                                    .weight(FontWeight::SEMIBOLD),
+                            )
+                            .child(
+                                Label::new(comment.text.clone())
+                                    .size(LabelSize::Small)
+                                    .truncate(),
+                            )
+                    }))
+                    .child(
+                        h_flex().h(cx.line_height).child(
+                            Button::new("reply", "Reply")
+                                .icon(IconName::ReplyArrowRight)
+                                .icon_size(IconSize::XSmall)
+                                .icon_position(IconPosition::Start)
+                                .icon_color(Color::Muted)
+                                .label_size(LabelSize::XSmall)
+                                .color(Color::Muted)
+                                .on_click(move |_, window, cx| {
+                                    context_editor
+                                        .update(cx, |this, cx| {
+                                            this.start_comment(
+                                                message_id,
+                                                Some(range.clone()),
+                                                Some(root_id),
+                                                window,
+                                                cx,
+                                            )
+                                        })
+                                        .ok();
+                                }),
+                        ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    fn update_image_blocks(&mut self, cx: &mut Context<Self>) {
+        self.editor.update(cx, |editor, cx| {
+            let buffer = editor.buffer().read(cx).snapshot(cx);
+            let excerpt_id = *buffer.as_singleton().unwrap().0;
+            let images = self
+                .context
+                .read(cx)
+                .contents(cx)
+                .map(
+                    |Content::Image {
+                         anchor,
+                         image_id,
+                         render_image,
+                         ..
+                     }| ((anchor, image_id), render_image),
+                )
+                .filter(|((anchor, _), _)| {
+                    buffer
+                        .anchor_in_excerpt(excerpt_id, *anchor)
+                        .is_some_and(|anchor| anchor.is_valid(&buffer))
+                })
+                .collect::<Vec<_>>();
+            self.image_blocks.update(
+                images,
+                |(anchor, _), image| {
+                    const MAX_HEIGHT_IN_LINES: u32 = 8;
+                    let anchor = buffer.anchor_in_excerpt(excerpt_id, *anchor)?;
+                    Some(BlockProperties {
+                        placement: BlockPlacement::Above(anchor),
+                        height: Some(MAX_HEIGHT_IN_LINES),
+                        style: BlockStyle::Sticky,
+                        render: Arc::new(move |cx| {
+                            let image_size = size_for_image(
+                                &image,
+                                size(
+                                    cx.max_width - cx.margins.gutter.full_width(),
+                                    MAX_HEIGHT_IN_LINES as f32 * cx.line_height,
+                                ),
+                            );
+                            h_flex()
+                                .pl(cx.margins.gutter.full_width())
+                                .child(
+                                    img(image.clone())
+                                        .object_fit(gpui::ObjectFit::ScaleDown)
+                                        .w(image_size.width)
+                                        .h(image_size.height),
+                                )
+                                .into_any_element()
+                        }),
+                        priority: 0,
+                        render_in_minimap: false,
+                    })
+                },
+                editor,
+                cx,
+            );
+        });
+    }
+
+    fn split(&mut self, _: &Split, _window: &mut Window, cx: &mut Context<Self>) {
+        self.context.update(cx, |context, cx| {
+            let selections = self.editor.read(cx).selections.disjoint_anchors();
+            for selection in selections.as_ref() {
+                let buffer = self.editor.read(cx).buffer().read(cx).snapshot(cx);
+                let range = selection
+                    .map(|endpoint| endpoint.to_offset(&buffer))
+                    .range();
+                context.split_message(range, cx);
+            }
+        });
+    }
+
+    fn save(&mut self, _: &Save, _window: &mut Window, cx: &mut Context<Self>) {
+        self.context
+            .update(cx, |context, cx| context.save(SaveReason::Explicit, cx));
+    }
+
+    pub fn title(&self, cx: &App) -> SharedString {
+        self.context.read(cx).summary().or_default()
+    }
+
+    pub fn regenerate_summary(&mut self, cx: &mut Context<Self>) {
+        self.context
+            .update(cx, |context, cx| context.summarize(true, cx));
+    }
+
+    fn render_notice(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        // This was previously gated behind the `zed-pro` feature flag. Since we
+        // aren't planning to ship that right now, we're just hard-coding this
+        // value to not show the nudge.
+        let nudge = Some(false);
+
+        if nudge.map_or(false, |value| value) {
+            Some(
+                h_flex()
+                    .p_3()
+                    .border_b_1()
+                    .border_color(cx.theme().colors().border_variant)
+                    .bg(cx.theme().colors().editor_background)
+                    .justify_between()
+                    .child(
+                        h_flex()
+                            .gap_3()
+                            .child(Icon::new(IconName::ZedAssistant).color(Color::Accent))
+                            .child(Label::new("Zed AI is here! Get started by signing in →")),
+                    )
+                    .child(
+                        Button::new("sign-in", "Sign in")
+                            .size(ButtonSize::Compact)
+                            .style(ButtonStyle::Filled)
+                            .on_click(cx.listener(|this, _event, _window, cx| {
+                                let client = this
+                                    .workspace
+                                    .read_with(cx, |workspace, _| workspace.client().clone())
+                                    .log_err();
+
+                                if let Some(client) = client {
+                                    cx.spawn(async move |context_editor, cx| {
+                                        match client.authenticate_and_connect(true, cx).await {
+                                            util::ConnectionResult::Timeout => {
+                                                log::error!("Authentication timeout")
+                                            }
+                                            util::ConnectionResult::ConnectionReset => {
+                                                log::error!("Connection reset")
+                                            }
+                                            util::ConnectionResult::Result(r) => {
+                                                if r.log_err().is_some() {
+                                                    context_editor
+                                                        .update(cx, |_, cx| cx.notify())
+                                                        .ok();
+                                                }
+                                            }
+                                        }
+                                    })
+                                    .detach()
+                                }
+                            })),
+                    )
+                    .into_any_element(),
+            )
+        } else if let Some(configuration_error) =
+            configuration_error(&self.context, cx).filter(|_| !self.uses_host_model(cx))
+        {
+            let label = match configuration_error {
+                ConfigurationError::NoProvider => "No LLM provider selected.",
+                ConfigurationError::ProviderNotAuthenticated => "LLM provider is not configured.",
+                ConfigurationError::ProviderPendingTermsAcceptance(_) => {
+                    "LLM provider requires accepting the Terms of Service."
+                }
+            };
+            Some(
+                h_flex()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(cx.theme().colors().border_variant)
+                    .bg(cx.theme().colors().editor_background)
+                    .justify_between()
+                    .child(
+                        h_flex()
+                            .gap_3()
+                            .child(
+                                Icon::new(IconName::Warning)
+                                    .size(IconSize::Small)
+                                    .color(Color::Warning),
+                            )
+                            .child(Label::new(label)),
+                    )
+                    .child(
+                        Button::new("open-configuration", "Configure Providers")
+                            .size(ButtonSize::Compact)
+                            .icon(Some(IconName::SlidersVertical))
+                            .icon_size(IconSize::Small)
+                            .icon_position(IconPosition::Start)
+                            .style(ButtonStyle::Filled)
+                            .on_click({
+                                let focus_handle = self.focus_handle(cx).clone();
+                                move |_event, window, cx| {
+                                    focus_handle.dispatch_action(
+                                        &zed_actions::agent::OpenConfiguration,
+                                        window,
+                                        cx,
+                                    );
+                                }
+                            }),
+                    )
+                    .into_any_element(),
+            )
+        } else {
+            None
+        }
+    }
+
+    fn render_send_button(&self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let focus_handle = self.focus_handle(cx).clone();
+
+        let (style, tooltip) = match token_state(&self.context, cx) {
+            Some(TokenState::NoTokensLeft { .. }) => (
+                ButtonStyle::Tinted(TintColor::Error),
+                Some(Tooltip::text("Token limit reached")(window, cx)),
+            ),
+            Some(TokenState::HasMoreTokens {
+                over_warn_threshold,
+                ..
+            }) => {
+                let (style, tooltip) = if over_warn_threshold {
+                    (
+                        ButtonStyle::Tinted(TintColor::Warning),
+                        Some(Tooltip::text("Token limit is close to exhaustion")(
+                            window, cx,
+                        )),
+                    )
+                } else {
+                    (ButtonStyle::Filled, None)
+                };
+                (style, tooltip)
+            }
+            None => (ButtonStyle::Filled, None),
+        };
+
+        Button::new("send_button", "Send")
+            .label_size(LabelSize::Small)
+            .disabled(self.sending_disabled(cx))
+            .style(style)
+            .when_some(tooltip, |button, tooltip| {
+                button.tooltip(move |_, _| tooltip.clone())
+            })
+            .layer(ElevationIndex::ModalSurface)
+            .key_binding(
+                KeyBinding::for_action_in(&Assist, &focus_handle, window, cx)
+                    .map(|kb| kb.size(rems_from_px(12.))),
+            )
+            .on_click(move |_event, window, cx| {
+                focus_handle.dispatch_action(&Assist, window, cx);
+            })
+    }
+
+    /// Whether or not we should allow messages to be sent.
+    /// Will return false if the selected provided has a configuration error or
+    /// if the user has not accepted the terms of service for this provider.
+    fn sending_disabled(&self, cx: &mut Context<'_, ContextEditor>) -> bool {
+        let model = self.context.read(cx).model(cx);
+
+        let has_configuration_error =
+            configuration_error(&self.context, cx).is_some() && !self.uses_host_model(cx);
+        let needs_to_accept_terms = self.show_accept_terms
+            && model
+                .as_ref()
+                .map_or(false, |model| model.provider.must_accept_terms(cx));
+        has_configuration_error || needs_to_accept_terms
+    }
+
+    fn render_inject_context_menu(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        slash_command_picker::SlashCommandSelector::new(
+            self.slash_commands.clone(),
+            cx.entity().downgrade(),
+            IconButton::new("trigger", IconName::Plus)
+                .icon_size(IconSize::Small)
+                .icon_color(Color::Muted),
+            move |window, cx| {
+                Tooltip::with_meta(
+                    "Add Context",
+                    None,
+                    "Type / to insert via keyboard",
+                    window,
+                    cx,
+                )
+            },
+        )
+    }
+
+    fn print_context(&mut self, _: &PrintContext, window: &mut Window, cx: &mut Context<Self>) {
+        self.export_html(HtmlExport::Print, window, cx);
+    }
+
+    fn export_context(&mut self, _: &ExportContext, window: &mut Window, cx: &mut Context<Self>) {
+        self.export_html(HtmlExport::Save, window, cx);
+    }
+
+    /// Saves or prints the context as HTML, first asking which of the secrets
+    /// found in it to mask.
+    fn export_html(&mut self, export: HtmlExport, window: &mut Window, cx: &mut Context<Self>) {
+        let redactions = find_context_secrets(self.context.read(cx), cx);
+        if redactions.is_empty() {
+            export.run(&self.context, &[], self.fs.clone(), cx);
+            return;
+        }
+
+        let context = self.context.clone();
+        let fs = self.fs.clone();
+        self.workspace
+            .update(cx, |workspace, cx| {
+                workspace.toggle_modal(window, cx, |_, cx| {
+                    RedactionReviewModal::new(context, fs, redactions, export, cx)
+                });
+            })
+            .ok();
+    }
+
+    fn export_event_log(&mut self, message_id: MessageId, cx: &mut Context<Self>) {
+        let Some(jsonl) = self
+            .context
+            .read(cx)
+            .event_log(message_id)
+            .map(EventLog::to_jsonl)
+        else {
+            return;
+        };
+        let path = cx.prompt_for_new_path(paths::home_dir());
+        let fs = self.fs.clone();
+        cx.spawn(async move |_, _| {
+            let Some(mut path) = path.await?? else {
+                return Ok(());
+            };
+            if path.extension().is_none() {
+                path.set_extension("jsonl");
+            }
+            fs.atomic_write(path, jsonl).await
+        })
+        .detach_and_log_err(cx);
+    }
+
+    fn edit_environment(
+        &mut self,
+        _: &EditEnvironment,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let context = self.context.clone();
+        self.workspace
+            .update(cx, |workspace, cx| {
+                workspace.toggle_modal(window, cx, |window, cx| {
+                    EnvironmentVariablesModal::new(context, window, cx)
+                });
+            })
+            .ok();
+    }
+
+    fn edit_stop_conditions(
+        &mut self,
+        _: &EditStopConditions,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let context = self.context.clone();
+        self.workspace
+            .update(cx, |workspace, cx| {
+                workspace.toggle_modal(window, cx, |window, cx| {
+                    StopConditionsModal::new(context, window, cx)
+                });
+            })
+            .ok();
+    }
+
+    fn render_environment_button(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let focus_handle = self.editor.focus_handle(cx);
+        let variable_count = self.context.read(cx).environment().len();
+        IconButton::new("edit-environment", IconName::Terminal)
+            .icon_size(IconSize::Small)
+            .icon_color(if variable_count > 0 {
+                Color::Accent
+            } else {
+                Color::Muted
+            })
+            .tooltip(move |window, cx| {
+                let meta = match variable_count {
+                    0 => "None set".to_string(),
+                    1 => "1 variable set".to_string(),
+                    count => format!("{count} variables set"),
+                };
+                Tooltip::with_meta_in(
+                    "Environment Variables",
+                    Some(&EditEnvironment),
+                    meta,
+                    &focus_handle,
+                    window,
+                    cx,
+                )
+            })
+            .on_click(cx.listener(|this, _, window, cx| {
+                this.edit_environment(&EditEnvironment, window, cx)
+            }))
+    }
+
+    fn render_answer_language_menu(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let context = self.context.clone();
+        let has_own_language = context.read(cx).has_own_answer_language();
+        PopoverMenu::new("answer-language-menu")
+            .trigger_with_tooltip(
+                IconButton::new("answer-language", IconName::Globe)
+                    .icon_size(IconSize::Small)
+                    .icon_color(if has_own_language {
+                        Color::Accent
+                    } else {
+                        Color::Muted
+                    }),
+                Tooltip::text("Answer Language"),
+            )
+            .anchor(gpui::Corner::BottomLeft)
+            .menu(move |window, cx| {
+                let context = context.clone();
+                Some(ContextMenu::build(window, cx, |mut menu, _, cx| {
+                    let (own_language, default_language) = {
+                        let context = context.read(cx);
+                        (
+                            context
+                                .has_own_answer_language()
+                                .then(|| context.answer_language(cx))
+                                .flatten(),
+                            AgentSettings::get_global(cx).answer_language.clone(),
+                        )
+                    };
+
+                    menu = menu.header("Answer In");
+                    let default_label = match default_language {
+                        Some(language) => format!("Default ({language})"),
+                        None => "Default (Any Language)".to_string(),
+                    };
+                    menu = menu.toggleable_entry(
+                        default_label,
+                        own_language.is_none(),
+                        IconPosition::Start,
+                        None,
+                        {
+                            let context = context.clone();
+                            move |_, cx| {
+                                context.update(cx, |context, cx| {
+                                    context.set_answer_language(None, cx)
+                                });
+                            }
+                        },
+                    );
+                    menu = menu.separator();
+                    for language in ANSWER_LANGUAGES {
+                        let context = context.clone();
+                        menu = menu.toggleable_entry(
+                            language,
+                            own_language.as_deref() == Some(language),
+                            IconPosition::Start,
+                            None,
+                            move |_, cx| {
+                                context.update(cx, |context, cx| {
+                                    context.set_answer_language(Some(language.to_string()), cx)
+                                });
+                            },
+                        );
+                    }
+                    menu
+                }))
+            })
+    }
+
+    fn render_model_parameters_menu(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let focus_handle = self.editor.focus_handle(cx);
+        let context = self.context.clone();
+        let has_own_parameters = {
+            let context = context.read(cx);
+            context.own_seed().is_some()
+                || !context.stop_conditions().is_empty()
+                || !context.post_processors().is_empty()
+                || context.web_search()
+        };
+        PopoverMenu::new("model-parameters-menu")
+            .trigger_with_tooltip(
+                IconButton::new("model-parameters", IconName::SlidersVertical)
+                    .icon_size(IconSize::Small)
+                    .icon_color(if has_own_parameters {
+                        Color::Accent
+                    } else {
+                        Color::Muted
+                    }),
+                Tooltip::text("Response Options"),
+            )
+            .anchor(gpui::Corner::BottomLeft)
+            .menu(move |window, cx| {
+                let context = context.clone();
+                let focus_handle = focus_handle.clone();
+                Some(ContextMenu::build(window, cx, |mut menu, _, cx| {
+                    let model = context.read(cx).model(cx).map(|model| model.model);
+                    let stop_condition_count = context.read(cx).stop_conditions().len();
+                    let post_processors = context.read(cx).post_processors().to_vec();
+                    let own_seed = context.read(cx).own_seed();
+                    let web_search = context.read(cx).web_search();
+                    let default_seed = model
+                        .as_ref()
+                        .and_then(|model| AgentSettings::seed_for_model(model, cx));
+
+                    menu = menu.header("Seed");
+                    if !model.as_ref().is_some_and(|model| model.supports_seed()) {
+                        menu = menu.label("The current model doesn't support seeds");
+                    }
+                    let default_label = match default_seed {
+                        Some(seed) => format!("Default ({seed})"),
+                        None => "Default (Random)".to_string(),
+                    };
+                    menu = menu.toggleable_entry(
+                        default_label,
+                        own_seed.is_none(),
+                        IconPosition::Start,
+                        None,
+                        {
+                            let context = context.clone();
+                            move |_, cx| {
+                                context.update(cx, |context, cx| context.set_seed(None, cx));
+                            }
+                        },
+                    );
+                    if let Some(seed) = own_seed {
+                        menu = menu.toggleable_entry(
+                            format!("Fixed ({seed})"),
+                            true,
+                            IconPosition::Start,
+                            None,
+                            |_, _| {},
+                        );
+                    }
+                    menu = menu.entry("New Fixed Seed", None, {
+                        let context = context.clone();
+                        move |_, cx| {
+                            context.update(cx, |context, cx| {
+                                context.set_seed(Some(rand::random()), cx)
+                            });
+                        }
+                    });
+
+                    menu = menu
+                        .separator()
+                        .header("Stop Conditions")
+                        .context(focus_handle)
+                        .action(
+                            match stop_condition_count {
+                                0 => "Add Stop Condition…".to_string(),
+                                1 => "Edit 1 Stop Condition…".to_string(),
+                                count => format!("Edit {count} Stop Conditions…"),
+                            },
+                            Box::new(EditStopConditions),
+                        );
+
+                    menu = menu.separator().header("Web Search");
+                    if !model
+                        .as_ref()
+                        .is_some_and(|model| model.supports_web_search())
+                    {
+                        menu = menu.label("The current model can't search the web");
+                    }
+                    menu = menu.toggleable_entry(
+                        "Search the Web and Cite Sources",
+                        web_search,
+                        IconPosition::Start,
+                        None,
+                        {
+                            let context = context.clone();
+                            move |_, cx| {
+                                context.update(cx, |context, cx| context.toggle_web_search(cx));
+                            }
+                        },
+                    );
+
+                    menu = menu.separator().header("Post-Processing");
+                    for processor in PostProcessor::ALL {
+                        let context = context.clone();
+                        menu = menu.toggleable_entry(
+                            processor.label(),
+                            post_processors.contains(&processor),
+                            IconPosition::Start,
+                            None,
+                            move |_, cx| {
+                                context.update(cx, |context, cx| {
+                                    context.toggle_post_processor(processor, cx)
+                                });
+                            },
+                        );
+                    }
+                    menu
+                }))
+            })
+    }
+
+    fn render_export_menu(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let focus_handle = self.editor.focus_handle(cx);
+        PopoverMenu::new("export-context-menu")
+            .trigger_with_tooltip(
+                IconButton::new("export-context", IconName::FileText)
+                    .icon_size(IconSize::Small)
+                    .icon_color(Color::Muted),
+                Tooltip::text("Print or Export"),
+            )
+            .anchor(gpui::Corner::BottomLeft)
+            .menu(move |window, cx| {
+                let focus_handle = focus_handle.clone();
+                Some(ContextMenu::build(window, cx, |menu, _, _| {
+                    menu.context(focus_handle)
+                        .action("Print…", Box::new(PrintContext))
+                        .action("Export as HTML…", Box::new(ExportContext))
+                }))
+            })
+    }
+
+    fn undo_operation(&mut self, _: &UndoOperation, _window: &mut Window, cx: &mut Context<Self>) {
+        self.context
+            .update(cx, |context, cx| context.undo_operation(cx));
+    }
+
+    fn redo_operation(&mut self, _: &RedoOperation, _window: &mut Window, cx: &mut Context<Self>) {
+        self.context
+            .update(cx, |context, cx| context.redo_operation(cx));
+    }
+
+    fn render_operation_history_menu(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let focus_handle = self.editor.focus_handle(cx);
+        let context = self.context.clone();
+        PopoverMenu::new("operation-history-menu")
+            .trigger_with_tooltip(
+                IconButton::new("operation-history", IconName::HistoryRerun)
+                    .icon_size(IconSize::Small)
+                    .icon_color(Color::Muted),
+                Tooltip::text("Operation History"),
+            )
+            .anchor(gpui::Corner::BottomLeft)
+            .menu(move |window, cx| {
+                let focus_handle = focus_handle.clone();
+                let context = context.clone();
+                Some(ContextMenu::build(window, cx, |mut menu, _, cx| {
+                    menu = menu.context(focus_handle);
+                    let (history, last_undone) = {
+                        let context = context.read(cx);
+                        (
+                            context.operation_history().to_vec(),
+                            context.last_undone_operation().cloned(),
+                        )
+                    };
+
+                    menu = match history.last() {
+                        Some(entry) => {
+                            menu.action(format!("Undo: {}", entry.label), Box::new(UndoOperation))
+                        }
+                        None => menu.disabled_action("Undo", Box::new(UndoOperation)),
+                    };
+                    menu = match last_undone {
+                        Some(entry) => {
+                            menu.action(format!("Redo: {}", entry.label), Box::new(RedoOperation))
+                        }
+                        None => menu.disabled_action("Redo", Box::new(RedoOperation)),
+                    };
+
+                    if !history.is_empty() {
+                        menu = menu.separator().header("Undo Back To");
+                        for entry in history.iter().rev().take(MAX_OPERATION_HISTORY_ENTRIES) {
+                            let context = context.clone();
+                            let transaction_id = entry.transaction_id;
+                            let icon = match entry.kind {
+                                HistoryOperationKind::InsertMessage => IconName::Plus,
+                                HistoryOperationKind::AssistantResponse => IconName::ZedAssistant,
+                                HistoryOperationKind::SlashCommand => IconName::Slash,
+                            };
+                            menu = menu.item(
+                                ContextMenuEntry::new(entry.label.clone())
+                                    .icon(icon)
+                                    .icon_position(IconPosition::Start)
+                                    .icon_color(Color::Muted)
+                                    .handler(move |_, cx| {
+                                        context.update(cx, |context, cx| {
+                                            context.undo_to_operation(transaction_id, cx)
+                                        });
+                                    }),
+                            );
+                        }
+                    }
+                    menu
+                }))
+            })
+    }
+
+    /// Opens a copy of the text thread, up to and including the given
+    /// message, that uses the given model.
+    fn duplicate_with_model(
+        &mut self,
+        up_to: MessageId,
+        model: Arc<dyn LanguageModel>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(agent_panel_delegate) = <dyn AgentPanelDelegate>::try_global(cx) else {
+            return;
+        };
+        let save = self.context.read(cx).duplicate(up_to, &model, cx);
+        let workspace = self.workspace.clone();
+        cx.spawn_in(window, async move |_, cx| {
+            let path = save.await?;
+            workspace
+                .update_in(cx, |workspace, window, cx| {
+                    agent_panel_delegate.open_saved_context(workspace, path, window, cx)
+                })?
+                .await
+        })
+        .detach_and_log_err(cx);
+    }
+
+    fn render_assembly_menu(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let context = self.context.clone();
+        PopoverMenu::new("context-assembly-menu")
+            .trigger_with_tooltip(
+                IconButton::new("context-assembly", IconName::ListTree)
+                    .icon_size(IconSize::Small)
+                    .icon_color(Color::Muted),
+                Tooltip::text("Context Assembly"),
+            )
+            .anchor(gpui::Corner::BottomLeft)
+            .menu(move |window, cx| {
+                let context = context.clone();
+                Some(ContextMenu::build(window, cx, |mut menu, _, cx| {
+                    let (strategy, report) = {
+                        let context = context.read(cx);
+                        (
+                            context.assembly_strategy(),
+                            context.last_assembly().cloned(),
+                        )
+                    };
+
+                    menu = menu.header("When Messages Don't Fit");
+                    for option in AssemblyStrategy::ALL {
+                        let context = context.clone();
+                        menu = menu.toggleable_entry(
+                            option.label(),
+                            option == strategy,
+                            IconPosition::Start,
+                            None,
+                            move |_, cx| {
+                                context.update(cx, |context, cx| {
+                                    context.set_assembly_strategy(option, cx)
+                                });
+                            },
+                        );
+                    }
+
+                    menu = menu.separator().header("Last Request");
+                    let Some(report) = report else {
+                        return menu.label("No requests sent yet");
+                    };
+                    menu = menu.label(match report.budget {
+                        Some(budget) => format!("{}, {budget} tokens", report.strategy.label()),
+                        None => report.strategy.label().to_string(),
+                    });
+                    for entry in report.entries {
+                        let inclusion = match entry.inclusion {
+                            Inclusion::Included if entry.pinned => "Sent (pinned)",
+                            Inclusion::Included => "Sent",
+                            Inclusion::Shortened => "Shortened",
+                            Inclusion::Excluded => "Left out",
+                        };
+                        menu = menu.label(format!(
+                            "{inclusion} · {} · {} tokens · {}",
+                            entry.role, entry.tokens, entry.preview
+                        ));
+                    }
+                    menu
+                }))
+            })
+    }
+
+    fn toggle_attachments(
+        &mut self,
+        _: &ToggleAttachments,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.show_attachments = !self.show_attachments;
+        if self.show_attachments {
+            self.count_attachment_tokens(cx);
+        }
+        cx.notify();
+    }
+
+    fn count_attachment_tokens(&mut self, cx: &mut Context<Self>) {
+        let Some(model) = self.context.read(cx).model(cx) else {
+            return;
+        };
+        let context = self.context.read(cx);
+        let buffer = context.buffer().read(cx);
+        let attachments = context
+            .attachments(cx)
+            .into_iter()
+            .filter_map(|attachment| match attachment.kind {
+                ContextAttachmentKind::SlashCommandOutput { .. } => {
+                    let text = buffer
+                        .text_for_range(attachment.range.clone())
+                        .collect::<String>();
+                    let compressed = context.is_attachment_compressed(&attachment, cx);
+                    Some((attachment.range, text, compressed))
+                }
+                ContextAttachmentKind::Image { .. } => None,
+            })
+            .collect::<Vec<_>>();
+
+        self.pending_attachment_token_count = cx.spawn(async move |this, cx| {
+            cx.background_executor()
+                .timer(Duration::from_millis(200))
+                .await;
+
+            let mut token_counts = HashMap::default();
+            let mut compression_ratios = HashMap::default();
+            for (range, mut text, compressed) in attachments {
+                if compressed {
+                    let compressed_text = cx
+                        .background_spawn(async move { prompt_compression::compress(&text) })
+                        .await;
+                    compression_ratios.insert(range.clone(), compressed_text.ratio());
+                    text = compressed_text.text;
+                }
+                let request = LanguageModelRequest {
+                    messages: vec![LanguageModelRequestMessage {
+                        role: Role::User,
+                        content: vec![text.into()],
+                        cache: false,
+                    }],
+                    ..Default::default()
+                };
+                let token_count = cx
+                    .update(|cx| model.model.count_tokens(request, cx))
+                    .ok()?
+                    .await
+                    .log_err()?;
+                token_counts.insert(range, token_count);
+            }
+
+            this.update(cx, |this, cx| {
+                this.attachment_token_counts = token_counts;
+                this.attachment_compression_ratios = compression_ratios;
+                cx.notify();
+            })
+            .ok()
+        });
+    }
+
+    /// Counts the tokens in the last message if it's a user message that
+    /// hasn't been sent yet.
+    fn count_draft_tokens(&mut self, cx: &mut Context<Self>) {
+        let Some(model) = self.context.read(cx).model(cx) else {
+            self.draft_token_count = None;
+            return;
+        };
+        let context = self.context.read(cx);
+        let draft = context
+            .messages(cx)
+            .last()
+            .filter(|message| message.role == Role::User && message.status == MessageStatus::Done)
+            .map(|message| {
+                context
+                    .buffer()
+                    .read(cx)
+                    .text_for_range(message.offset_range)
+                    .collect::<String>()
+            })
+            .filter(|text| !text.trim().is_empty());
+        let Some(draft) = draft else {
+            self.draft_token_count = None;
+            self.pending_draft_token_count = Task::ready(None);
+            return;
+        };
+
+        self.pending_draft_token_count = cx.spawn(async move |this, cx| {
+            cx.background_executor()
+                .timer(Duration::from_millis(200))
+                .await;
+            let request = LanguageModelRequest {
+                messages: vec![LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![draft.into()],
+                    cache: false,
+                }],
+                ..Default::default()
+            };
+            let token_count = cx
+                .update(|cx| model.model.count_tokens(request, cx))
+                .ok()?
+                .await
+                .log_err()?;
+            this.update(cx, |this, cx| {
+                this.draft_token_count = Some(token_count);
+                cx.notify();
+            })
+            .ok()
+        });
+    }
+
+    fn render_draft_token_count(&self, cx: &App) -> Option<impl IntoElement + use<>> {
+        let draft_token_count = self.draft_token_count?;
+        let (color, remaining) = match token_state(&self.context, cx) {
+            Some(TokenState::NoTokensLeft {
+                max_token_count,
+                token_count,
+            }) => (
+                Color::Error,
+                Some(max_token_count as isize - token_count as isize),
+            ),
+            Some(TokenState::HasMoreTokens {
+                max_token_count,
+                token_count,
+                over_warn_threshold,
+            }) => (
+                if over_warn_threshold {
+                    Color::Warning
+                } else {
+                    Color::Muted
+                },
+                Some(max_token_count as isize - token_count as isize),
+            ),
+            None => (Color::Muted, None),
+        };
+        let remaining = remaining.map(|remaining| {
+            let left = humanize_token_count(remaining.unsigned_abs());
+            if remaining < 0 {
+                format!("{left} over")
+            } else {
+                format!("{left} left")
+            }
+        });
+
+        Some(
+            h_flex()
+                .id("draft-token-count")
+                .gap_1()
+                .child(
+                    Label::new(format!("+{}", humanize_token_count(draft_token_count)))
+                        .size(LabelSize::Small)
+                        .color(color),
+                )
+                .children(remaining.map(|remaining| {
+                    Label::new(remaining)
+                        .size(LabelSize::Small)
+                        .color(Color::Muted)
+                }))
+                .tooltip(Tooltip::text(
+                    "Tokens in your message, and tokens left after sending it",
+                )),
+        )
+    }
+
+    fn refresh_attachment(
+        &mut self,
+        attachment: ContextAttachment,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(path) = attachment.file_path() else {
+            return;
         };
-
-        Button::new("send_button", "Send")
-            .label_size(LabelSize::Small)
-            .disabled(self.sending_disabled(cx))
-            .style(style)
-            .when_some(tooltip, |button, tooltip| {
-                button.tooltip(move |_, _| tooltip.clone())
+        let command_name = FileSlashCommand.name();
+        let command_range = self.context.update(cx, |context, cx| {
+            context.buffer().update(cx, |buffer, cx| {
+                let range = attachment.range.to_offset(buffer);
+                let command = format!("/{command_name} {path}");
+                buffer.edit([(range.clone(), command.as_str())], None, cx);
+                buffer.anchor_after(range.start)..buffer.anchor_before(range.start + command.len())
             })
-            .layer(ElevationIndex::ModalSurface)
-            .key_binding(
-                KeyBinding::for_action_in(&Assist, &focus_handle, window, cx)
-                    .map(|kb| kb.size(rems_from_px(12.))),
-            )
-            .on_click(move |_event, window, cx| {
-                focus_handle.dispatch_action(&Assist, window, cx);
+        });
+        self.run_command(
+            command_range,
+            &command_name,
+            &[path],
+            false,
+            self.workspace.clone(),
+            window,
+            cx,
+        );
+    }
+
+    fn render_attachments_toggle(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        IconButton::new("toggle-attachments", IconName::ListTree)
+            .icon_size(IconSize::Small)
+            .icon_color(Color::Muted)
+            .toggle_state(self.show_attachments)
+            .tooltip(|window, cx| {
+                Tooltip::for_action("Toggle Attachments", &ToggleAttachments, window, cx)
             })
+            .on_click(cx.listener(|this, _, window, cx| {
+                this.toggle_attachments(&ToggleAttachments, window, cx)
+            }))
     }
 
-    /// Whether or not we should allow messages to be sent.
-    /// Will return false if the selected provided has a configuration error or
-    /// if the user has not accepted the terms of service for this provider.
-    fn sending_disabled(&self, cx: &mut Context<'_, ContextEditor>) -> bool {
-        let model = LanguageModelRegistry::read_global(cx).default_model();
+    fn render_attachments(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        if !self.show_attachments {
+            return None;
+        }
 
-        let has_configuration_error = configuration_error(cx).is_some();
-        let needs_to_accept_terms = self.show_accept_terms
-            && model
-                .as_ref()
-                .map_or(false, |model| model.provider.must_accept_terms(cx));
-        has_configuration_error || needs_to_accept_terms
-    }
+        let attachments = self.context.read(cx).attachments(cx);
+        let total_tokens = attachments
+            .iter()
+            .filter_map(|attachment| self.attachment_token_count(attachment))
+            .sum::<usize>();
 
-    fn render_inject_context_menu(&self, cx: &mut Context<Self>) -> impl IntoElement {
-        slash_command_picker::SlashCommandSelector::new(
-            self.slash_commands.clone(),
-            cx.entity().downgrade(),
-            IconButton::new("trigger", IconName::Plus)
-                .icon_size(IconSize::Small)
-                .icon_color(Color::Muted),
-            move |window, cx| {
-                Tooltip::with_meta(
-                    "Add Context",
-                    None,
-                    "Type / to insert via keyboard",
-                    window,
-                    cx,
+        Some(
+            v_flex()
+                .id("attachments")
+                .w_64()
+                .h_full()
+                .flex_none()
+                .overflow_y_scroll()
+                .border_l_1()
+                .border_color(cx.theme().colors().border_variant)
+                .bg(cx.theme().colors().panel_background)
+                .child(
+                    h_flex()
+                        .px_2()
+                        .py_1p5()
+                        .justify_between()
+                        .border_b_1()
+                        .border_color(cx.theme().colors().border_variant)
+                        .child(Label::new("Attachments").size(LabelSize::Small))
+                        .child(
+                            Label::new(humanize_token_count(total_tokens))
+                                .size(LabelSize::Small)
+                                .color(Color::Muted),
+                        ),
                 )
-            },
+                .when(attachments.is_empty(), |this| {
+                    this.child(
+                        div().p_2().child(
+                            Label::new("Nothing attached yet. Type / to add context.")
+                                .size(LabelSize::Small)
+                                .color(Color::Muted),
+                        ),
+                    )
+                })
+                .children(
+                    attachments
+                        .into_iter()
+                        .enumerate()
+                        .map(|(ix, attachment)| self.render_attachment(ix, attachment, cx)),
+                ),
         )
     }
 
+    fn attachment_token_count(&self, attachment: &ContextAttachment) -> Option<usize> {
+        match &attachment.kind {
+            ContextAttachmentKind::SlashCommandOutput { .. } => {
+                self.attachment_token_counts.get(&attachment.range).copied()
+            }
+            ContextAttachmentKind::Image { image, .. } => image
+                .clone()
+                .now_or_never()
+                .flatten()
+                .map(|image| image.estimate_tokens()),
+        }
+    }
+
+    fn render_attachment(
+        &self,
+        ix: usize,
+        attachment: ContextAttachment,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let token_count = self.attachment_token_count(&attachment);
+        let context = self.context.read(cx);
+        let can_compress = context.can_compress_attachment(&attachment, cx);
+        let compressed = context.is_attachment_compressed(&attachment, cx);
+        let pinned = context.is_attachment_pinned(&attachment, cx);
+        let pinned_with_message = context.is_attachment_in_pinned_message(&attachment, cx);
+        let compression_ratio = compressed
+            .then(|| self.attachment_compression_ratios.get(&attachment.range))
+            .flatten()
+            .copied();
+        let can_refresh = attachment.file_path().is_some();
+        let can_summarize = matches!(
+            attachment.kind,
+            ContextAttachmentKind::SlashCommandOutput { .. }
+        );
+
+        h_flex()
+            .id(("attachment", ix))
+            .group("attachment")
+            .px_2()
+            .py_1()
+            .gap_1()
+            .w_full()
+            .hover(|style| style.bg(cx.theme().colors().element_hover))
+            .cursor_pointer()
+            .child(
+                Icon::new(attachment.icon)
+                    .size(IconSize::XSmall)
+                    .color(Color::Muted),
+            )
+            .child(
+                div().flex_1().min_w_0().child(
+                    Label::new(attachment.label.clone())
+                        .size(LabelSize::Small)
+                        .truncate(),
+                ),
+            )
+            .children(compression_ratio.map(|ratio| {
+                div()
+                    .id(("compression-ratio", ix))
+                    .child(
+                        Label::new(format!("-{:.0}%", ratio * 100.))
+                            .size(LabelSize::XSmall)
+                            .color(Color::Success),
+                    )
+                    .tooltip(Tooltip::text("Compressed Before Sending"))
+            }))
+            .children(token_count.map(|token_count| {
+                Label::new(humanize_token_count(token_count))
+                    .size(LabelSize::XSmall)
+                    .color(Color::Muted)
+            }))
+            .child(
+                IconButton::new(("pin-attachment", ix), IconName::Pin)
+                    .icon_size(IconSize::XSmall)
+                    .icon_color(if pinned { Color::Accent } else { Color::Muted })
+                    .toggle_state(pinned)
+                    .when(!pinned, |button| button.visible_on_hover("attachment"))
+                    .disabled(pinned_with_message)
+                    .tooltip(Tooltip::text(if pinned_with_message {
+                        "Pinned With Its Message"
+                    } else if pinned {
+                        "Unpin Attachment"
+                    } else {
+                        "Pin Attachment"
+                    }))
+                    .on_click(cx.listener({
+                        let attachment = attachment.clone();
+                        move |this, _, _window, cx| {
+                            this.context.update(cx, |context, cx| {
+                                context.set_attachment_pinned(&attachment, !pinned, cx)
+                            });
+                            this.count_attachment_tokens(cx);
+                        }
+                    })),
+            )
+            .child(
+                h_flex()
+                    .visible_on_hover("attachment")
+                    .when(can_compress, |this| {
+                        this.child(
+                            IconButton::new(("compress-attachment", ix), IconName::Minimize)
+                                .icon_size(IconSize::XSmall)
+                                .toggle_state(compressed)
+                                .tooltip(Tooltip::text(if compressed {
+                                    "Send Uncompressed"
+                                } else {
+                                    "Compress Before Sending"
+                                }))
+                                .on_click(cx.listener({
+                                    let attachment = attachment.clone();
+                                    move |this, _, _window, cx| {
+                                        this.context.update(cx, |context, cx| {
+                                            context.set_attachment_compressed(
+                                                &attachment,
+                                                !compressed,
+                                                cx,
+                                            )
+                                        });
+                                        this.count_attachment_tokens(cx);
+                                    }
+                                })),
+                        )
+                    })
+                    .when(can_refresh, |this| {
+                        this.child(
+                            IconButton::new(("refresh-attachment", ix), IconName::RotateCw)
+                                .icon_size(IconSize::XSmall)
+                                .tooltip(Tooltip::text("Reload From Disk"))
+                                .on_click(cx.listener({
+                                    let attachment = attachment.clone();
+                                    move |this, _, window, cx| {
+                                        this.refresh_attachment(attachment.clone(), window, cx);
+                                    }
+                                })),
+                        )
+                    })
+                    .when(can_summarize, |this| {
+                        this.child(
+                            IconButton::new(("summarize-attachment", ix), IconName::Sparkle)
+                                .icon_size(IconSize::XSmall)
+                                .tooltip(Tooltip::text("Replace With Summary"))
+                                .on_click(cx.listener({
+                                    let attachment = attachment.clone();
+                                    move |this, _, _window, cx| {
+                                        this.context
+                                            .update(cx, |context, cx| {
+                                                context.summarize_attachment(attachment.clone(), cx)
+                                            })
+                                            .detach_and_log_err(cx);
+                                    }
+                                })),
+                        )
+                    })
+                    .child(
+                        IconButton::new(("remove-attachment", ix), IconName::Close)
+                            .icon_size(IconSize::XSmall)
+                            .tooltip(Tooltip::text("Remove"))
+                            .on_click(cx.listener({
+                                let attachment = attachment.clone();
+                                move |this, _, _window, cx| {
+                                    this.context.update(cx, |context, cx| {
+                                        context.remove_attachment(&attachment, cx)
+                                    });
+                                }
+                            })),
+                    ),
+            )
+            .on_click(cx.listener(move |this, _, window, cx| {
+                let anchor = attachment.range.start;
+                this.editor.update(cx, |editor, cx| {
+                    let snapshot = editor.buffer().read(cx).snapshot(cx);
+                    let (&excerpt_id, _, _) = snapshot.as_singleton().unwrap();
+                    let Some(anchor) = snapshot.anchor_in_excerpt(excerpt_id, anchor) else {
+                        return;
+                    };
+                    editor.change_selections(Some(Autoscroll::center()), window, cx, |s| {
+                        s.select_anchor_ranges([anchor..anchor])
+                    });
+                });
+            }))
+    }
+
     fn render_max_mode_toggle(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
         let context = self.context().read(cx);
-        let active_model = LanguageModelRegistry::read_global(cx)
-            .default_model()
-            .map(|default| default.model)?;
+        let active_model = context.model(cx).map(|model| model.model)?;
         if !active_model.supports_max_mode() {
             return None;
         }
@@ -2106,9 +4985,7 @@ impl ContextEditor {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) -> impl IntoElement {
-        let active_model = LanguageModelRegistry::read_global(cx)
-            .default_model()
-            .map(|default| default.model);
+        let active_model = self.context.read(cx).model(cx).map(|model| model.model);
         let focus_handle = self.editor().focus_handle(cx).clone();
         let model_name = match active_model {
             Some(model) => model.name().0,
@@ -2164,6 +5041,13 @@ impl ContextEditor {
                 .occlude()
                 .child(match last_error {
                     AssistError::PaymentRequired => self.render_payment_required_error(cx),
+                    AssistError::MaxMonthlySpendReached(error) => {
+                        self.render_max_monthly_spend_reached_error(error, cx)
+                    }
+                    AssistError::Model(LanguageModelError::ContentFiltered { message }) => {
+                        self.render_content_filtered_error(message, cx)
+                    }
+                    AssistError::Model(error) => self.render_model_error(error, cx),
                     AssistError::Message(error_message) => {
                         self.render_assist_error(error_message, cx)
                     }
@@ -2172,8 +5056,274 @@ impl ContextEditor {
         )
     }
 
-    fn render_payment_required_error(&self, cx: &mut Context<Self>) -> AnyElement {
-        const ERROR_MESSAGE: &str = "Free tier exceeded. Subscribe and add payment to continue using Zed LLMs. You'll be billed at cost for tokens used.";
+    fn render_retrying_completion(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let retrying = self.retrying_completion.as_ref()?;
+
+        Some(
+            div()
+                .absolute()
+                .right_3()
+                .bottom_12()
+                .max_w_96()
+                .py_2()
+                .px_3()
+                .elevation_2(cx)
+                .occlude()
+                .child(
+                    v_flex()
+                        .gap_0p5()
+                        .child(
+                            h_flex()
+                                .gap_1p5()
+                                .items_center()
+                                .child(Icon::new(IconName::ArrowCircle).color(Color::Warning))
+                                .child(
+                                    Label::new(format!(
+                                        "Retrying Request ({} of {})",
+                                        retrying.attempt, retrying.max_attempts
+                                    ))
+                                    .weight(FontWeight::MEDIUM),
+                                ),
+                        )
+                        .child(Label::new(retrying.message.clone()).color(Color::Muted)),
+                )
+                .into_any(),
+        )
+    }
+
+    fn render_duplicate_attachment(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let duplicate = self.duplicate_attachment.clone()?;
+        let buffer = self.context.read(cx).buffer().read(cx);
+        // The duplicate may have been removed by hand, or undone, since.
+        if duplicate.range.to_offset(buffer).is_empty()
+            || duplicate.earlier_range.to_offset(buffer).is_empty()
+        {
+            return None;
+        }
+
+        Some(
+            div()
+                .absolute()
+                .right_3()
+                .bottom_12()
+                .max_w_96()
+                .py_2()
+                .px_3()
+                .elevation_2(cx)
+                .occlude()
+                .child(
+                    v_flex()
+                        .gap_0p5()
+                        .child(
+                            h_flex()
+                                .gap_1p5()
+                                .items_center()
+                                .child(Icon::new(IconName::Copy).color(Color::Warning))
+                                .child(Label::new("Attached Twice").weight(FontWeight::MEDIUM)),
+                        )
+                        .child(
+                            Label::new(format!(
+                                "{} is already attached earlier in this text thread. \
+                                Sending both copies doubles the tokens it uses.",
+                                duplicate.label
+                            ))
+                            .color(Color::Muted),
+                        )
+                        .child(
+                            h_flex()
+                                .justify_end()
+                                .gap_1()
+                                .mt_1()
+                                .child(Button::new("keep-both-attachments", "Keep Both").on_click(
+                                    cx.listener(|this, _, _window, cx| {
+                                        this.duplicate_attachment = None;
+                                        cx.notify();
+                                    }),
+                                ))
+                                .child(
+                                    Button::new("replace-earlier-attachment", "Replace Earlier")
+                                        .on_click(cx.listener({
+                                            let earlier_range = duplicate.earlier_range.clone();
+                                            move |this, _, _window, cx| {
+                                                this.duplicate_attachment = None;
+                                                this.context.update(cx, |context, cx| {
+                                                    context.remove_attached_text(
+                                                        earlier_range.clone(),
+                                                        cx,
+                                                    )
+                                                });
+                                            }
+                                        })),
+                                )
+                                .child(
+                                    Button::new(
+                                        "reference-earlier-attachment",
+                                        "Reference Earlier",
+                                    )
+                                    .style(ButtonStyle::Filled)
+                                    .on_click(cx.listener(
+                                        move |this, _, _window, cx| {
+                                            this.duplicate_attachment = None;
+                                            this.context.update(cx, |context, cx| {
+                                                context.reference_earlier_duplicate(
+                                                    duplicate.range.clone(),
+                                                    duplicate.icon,
+                                                    duplicate.label.clone(),
+                                                    cx,
+                                                )
+                                            });
+                                        },
+                                    )),
+                                ),
+                        ),
+                )
+                .into_any(),
+        )
+    }
+
+    fn render_payment_required_error(&self, cx: &mut Context<Self>) -> AnyElement {
+        const ERROR_MESSAGE: &str = "Free tier exceeded. Subscribe and add payment to continue using Zed LLMs. You'll be billed at cost for tokens used.";
+
+        v_flex()
+            .gap_0p5()
+            .child(
+                h_flex()
+                    .gap_1p5()
+                    .items_center()
+                    .child(Icon::new(IconName::XCircle).color(Color::Error))
+                    .child(Label::new("Free Usage Exceeded").weight(FontWeight::MEDIUM)),
+            )
+            .child(
+                div()
+                    .id("error-message")
+                    .max_h_24()
+                    .overflow_y_scroll()
+                    .child(Label::new(ERROR_MESSAGE)),
+            )
+            .child(
+                h_flex()
+                    .justify_end()
+                    .mt_1()
+                    .child(Button::new("subscribe", "Subscribe").on_click(cx.listener(
+                        |this, _, _window, cx| {
+                            this.last_error = None;
+                            cx.open_url(&zed_urls::account_url(cx));
+                            cx.notify();
+                        },
+                    )))
+                    .child(Button::new("dismiss", "Dismiss").on_click(cx.listener(
+                        |this, _, _window, cx| {
+                            this.last_error = None;
+                            cx.notify();
+                        },
+                    ))),
+            )
+            .into_any()
+    }
+
+    fn render_max_monthly_spend_reached_error(
+        &self,
+        error: &MaxMonthlySpendReachedError,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let raised_limit = (error.max_monthly_spend * 2.).max(1.);
+
+        v_flex()
+            .gap_0p5()
+            .child(
+                h_flex()
+                    .gap_1p5()
+                    .items_center()
+                    .child(Icon::new(IconName::XCircle).color(Color::Error))
+                    .child(Label::new("Monthly Spending Limit Reached").weight(FontWeight::MEDIUM)),
+            )
+            .child(
+                div()
+                    .id("error-message")
+                    .max_h_24()
+                    .overflow_y_scroll()
+                    .child(Label::new(error.to_string())),
+            )
+            .child(
+                h_flex()
+                    .justify_end()
+                    .mt_1()
+                    .child(
+                        Button::new("raise-limit", format!("Raise to ${raised_limit:.2}"))
+                            .on_click(cx.listener(move |this, _, _window, cx| {
+                                this.last_error = None;
+                                update_settings_file::<AgentSettings>(
+                                    this.fs.clone(),
+                                    cx,
+                                    move |settings, _| {
+                                        settings.set_max_monthly_spend(Some(raised_limit))
+                                    },
+                                );
+                                cx.notify();
+                            })),
+                    )
+                    .children(self.render_use_free_model_button(cx))
+                    .child(Button::new("dismiss", "Dismiss").on_click(cx.listener(
+                        |this, _, _window, cx| {
+                            this.last_error = None;
+                            cx.notify();
+                        },
+                    ))),
+            )
+            .into_any()
+    }
+
+    /// Renders an error returned by the model's provider, along with the
+    /// actions most likely to recover from it.
+    fn render_model_error(&self, error: &LanguageModelError, cx: &mut Context<Self>) -> AnyElement {
+        let recovery_action = match error {
+            LanguageModelError::Authentication { .. } => Some(
+                Button::new("configure-provider", "Configure Provider")
+                    .on_click({
+                        let focus_handle = self.focus_handle(cx).clone();
+                        cx.listener(move |this, _, window, cx| {
+                            this.last_error = None;
+                            focus_handle.dispatch_action(
+                                &zed_actions::agent::OpenConfiguration,
+                                window,
+                                cx,
+                            );
+                            cx.notify();
+                        })
+                    })
+                    .into_any_element(),
+            ),
+            LanguageModelError::QuotaExceeded { .. }
+            | LanguageModelError::RateLimitExceeded { .. } => self.render_use_free_model_button(cx),
+            LanguageModelError::ContextWindowLimitExceeded { .. } => Some(
+                Button::new("shorten-context", "Shorten Context")
+                    .tooltip(Tooltip::text(
+                        "Review the attachments using the most tokens",
+                    ))
+                    .on_click(cx.listener(|this, _, _window, cx| {
+                        this.last_error = None;
+                        if !this.show_attachments {
+                            this.show_attachments = true;
+                            this.count_attachment_tokens(cx);
+                        }
+                        cx.notify();
+                    }))
+                    .into_any_element(),
+            ),
+            LanguageModelError::Server { .. }
+            | LanguageModelError::Network { .. }
+            | LanguageModelError::Timeout { .. } => Some(
+                Button::new("retry", "Retry")
+                    .on_click(cx.listener(|this, _, window, cx| {
+                        this.last_error = None;
+                        this.assist(&Assist, window, cx);
+                        cx.notify();
+                    }))
+                    .into_any_element(),
+            ),
+            // Content filter errors are rendered by `render_content_filtered_error`.
+            LanguageModelError::ContentFiltered { .. } | LanguageModelError::Cancelled => None,
+        };
 
         v_flex()
             .gap_0p5()
@@ -2182,26 +5332,107 @@ impl ContextEditor {
                     .gap_1p5()
                     .items_center()
                     .child(Icon::new(IconName::XCircle).color(Color::Error))
-                    .child(Label::new("Free Usage Exceeded").weight(FontWeight::MEDIUM)),
+                    .child(Label::new(error.title()).weight(FontWeight::MEDIUM)),
             )
             .child(
                 div()
                     .id("error-message")
                     .max_h_24()
                     .overflow_y_scroll()
-                    .child(Label::new(ERROR_MESSAGE)),
+                    .child(Label::new(error.to_string())),
             )
             .child(
                 h_flex()
                     .justify_end()
                     .mt_1()
-                    .child(Button::new("subscribe", "Subscribe").on_click(cx.listener(
+                    .children(recovery_action)
+                    .child(Button::new("dismiss", "Dismiss").on_click(cx.listener(
                         |this, _, _window, cx| {
                             this.last_error = None;
-                            cx.open_url(&zed_urls::account_url(cx));
+                            cx.notify();
+                        },
+                    ))),
+            )
+            .into_any()
+    }
+
+    /// Renders a response that the provider refused or blocked, with ways to
+    /// get an answer that don't involve retrying the same request verbatim.
+    fn render_content_filtered_error(
+        &self,
+        error_message: &str,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        const REPHRASE_SUGGESTION: &str = "Try explaining what you're working on and why, and remove anything that could be mistaken for sensitive content, such as credentials or personal data.";
+
+        let policy_url = self
+            .context
+            .read(cx)
+            .model(cx)
+            .and_then(|model| content_policy_url(&model.provider.id()));
+
+        v_flex()
+            .gap_0p5()
+            .child(
+                h_flex()
+                    .gap_1p5()
+                    .items_center()
+                    .child(Icon::new(IconName::Warning).color(Color::Warning))
+                    .child(
+                        Label::new("Response Blocked by Content Filter").weight(FontWeight::MEDIUM),
+                    ),
+            )
+            .child(
+                div()
+                    .id("error-message")
+                    .max_h_24()
+                    .overflow_y_scroll()
+                    .child(Label::new(error_message.to_string())),
+            )
+            .child(
+                Label::new(REPHRASE_SUGGESTION)
+                    .size(LabelSize::Small)
+                    .color(Color::Muted),
+            )
+            .child(
+                h_flex()
+                    .flex_wrap()
+                    .justify_end()
+                    .mt_1()
+                    .child(
+                        Button::new("rephrase", "Rephrase")
+                            .tooltip(Tooltip::text("Select your last message to edit it"))
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.last_error = None;
+                                this.select_last_user_message(window, cx);
+                                cx.notify();
+                            })),
+                    )
+                    .child(Button::new("retry", "Retry").on_click(cx.listener(
+                        |this, _, window, cx| {
+                            this.last_error = None;
+                            this.assist(&Assist, window, cx);
                             cx.notify();
                         },
                     )))
+                    .child(
+                        Button::new("switch-model", "Switch Model").on_click(cx.listener(
+                            |this, _, window, cx| {
+                                this.last_error = None;
+                                this.language_model_selector_menu_handle.toggle(window, cx);
+                                cx.notify();
+                            },
+                        )),
+                    )
+                    .when_some(policy_url, |this, policy_url| {
+                        this.child(
+                            Button::new("view-policy", "View Policy")
+                                .icon(IconName::ArrowUpRight)
+                                .icon_size(IconSize::XSmall)
+                                .icon_position(IconPosition::End)
+                                .on_click(move |_, _window, cx| cx.open_url(policy_url)),
+                        )
+                    })
                     .child(Button::new("dismiss", "Dismiss").on_click(cx.listener(
                         |this, _, _window, cx| {
                             this.last_error = None;
@@ -2212,6 +5443,50 @@ impl ContextEditor {
             .into_any()
     }
 
+    fn select_last_user_message(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(message) = self
+            .context
+            .read(cx)
+            .messages(cx)
+            .filter(|message| message.role == Role::User)
+            .last()
+        else {
+            return;
+        };
+        // Leave out the newline separating the message from the next one.
+        let range = message.offset_range.start
+            ..message
+                .offset_range
+                .end
+                .saturating_sub(1)
+                .max(message.offset_range.start);
+        self.editor.update(cx, |editor, cx| {
+            editor.change_selections(Some(Autoscroll::center()), window, cx, |selections| {
+                selections.select_ranges([range])
+            });
+        });
+        window.focus(&self.editor.focus_handle(cx));
+    }
+
+    /// Renders a button switching to a free model with a context window
+    /// comparable to the selected model's, if one is available.
+    fn render_use_free_model_button(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let model = self.context.read(cx).model(cx)?.model;
+        let free_model =
+            LanguageModelRegistry::read_global(cx).free_model_substitute(&model, cx)?;
+
+        Some(
+            Button::new("use-free-model", "Use Free Model")
+                .tooltip(Tooltip::text(format!("Switch to {}", free_model.name().0)))
+                .on_click(cx.listener(move |this, _, _window, cx| {
+                    this.last_error = None;
+                    select_model(free_model.clone(), &this.context, this.fs.clone(), cx);
+                    cx.notify();
+                }))
+                .into_any_element(),
+        )
+    }
+
     fn render_assist_error(
         &self,
         error_message: &SharedString,
@@ -2251,7 +5526,34 @@ impl ContextEditor {
     }
 }
 
+/// Returns the page describing the content the provider allows, for providers
+/// that may refuse or filter responses.
+fn content_policy_url(provider_id: &LanguageModelProviderId) -> Option<&'static str> {
+    match provider_id.0.as_ref() {
+        "zed.dev" => Some("https://zed.dev/terms-of-service"),
+        "anthropic" => Some("https://www.anthropic.com/legal/aup"),
+        "openai" => Some("https://openai.com/policies/usage-policies"),
+        "google" => Some("https://ai.google.dev/gemini-api/docs/safety-settings"),
+        "copilot_chat" => Some(
+            "https://docs.github.com/en/site-policy/acceptable-use-policies/github-acceptable-use-policies",
+        ),
+        "openrouter" => Some("https://openrouter.ai/terms"),
+        "mistral" => Some("https://mistral.ai/terms"),
+        "amazon-bedrock" => {
+            Some("https://docs.aws.amazon.com/bedrock/latest/userguide/guardrails.html")
+        }
+        _ => None,
+    }
+}
+
 /// Returns the contents of the *outermost* fenced code block that contains the given offset.
+/// Returns the language of a fence line such as "```rust".
+fn code_fence_language(fence: &str) -> Option<String> {
+    let info = fence.trim().trim_start_matches(['`', '~']);
+    let language = info.split([' ', ',', '{']).next()?.trim();
+    (!language.is_empty()).then(|| language.to_string())
+}
+
 fn find_surrounding_code_block(snapshot: &BufferSnapshot, offset: usize) -> Option<Range<usize>> {
     const CODE_BLOCK_NODE: &'static str = "fenced_code_block";
     const CODE_BLOCK_CONTENT: &'static str = "code_fence_content";
@@ -2343,6 +5645,7 @@ fn render_fold_icon_button(
     editor: WeakEntity<Editor>,
     icon_path: SharedString,
     label: SharedString,
+    duplicate: bool,
 ) -> Arc<dyn Send + Sync + Fn(FoldId, Range<Anchor>, &mut App) -> AnyElement> {
     Arc::new(move |fold_id, fold_range, _cx| {
         let editor = editor.clone();
@@ -2351,6 +5654,7 @@ fn render_fold_icon_button(
             .layer(ElevationIndex::ElevatedSurface)
             .child(Icon::from_path(icon_path.clone()))
             .child(Label::new(label.clone()).single_line())
+            .when(duplicate, |button| button.child(render_duplicate_badge()))
             .on_click(move |_, window, cx| {
                 editor
                     .update(cx, |editor, cx| {
@@ -2401,7 +5705,11 @@ pub fn fold_toggle(
     }
 }
 
-fn quote_selection_fold_placeholder(title: String, editor: WeakEntity<Editor>) -> FoldPlaceholder {
+fn quote_selection_fold_placeholder(
+    title: String,
+    duplicate: bool,
+    editor: WeakEntity<Editor>,
+) -> FoldPlaceholder {
     FoldPlaceholder {
         render: Arc::new({
             move |fold_id, fold_range, _cx| {
@@ -2411,6 +5719,7 @@ fn quote_selection_fold_placeholder(title: String, editor: WeakEntity<Editor>) -
                     .layer(ElevationIndex::ElevatedSurface)
                     .child(Icon::new(IconName::TextSnippet))
                     .child(Label::new(title.clone()).single_line())
+                    .when(duplicate, |button| button.child(render_duplicate_badge()))
                     .on_click(move |_, window, cx| {
                         editor
                             .update(cx, |editor, cx| {
@@ -2430,6 +5739,13 @@ fn quote_selection_fold_placeholder(title: String, editor: WeakEntity<Editor>) -
     }
 }
 
+/// Marks the crease of content that was already attached earlier in the context.
+fn render_duplicate_badge() -> impl IntoElement {
+    Label::new("Duplicate")
+        .size(LabelSize::XSmall)
+        .color(Color::Warning)
+}
+
 fn render_quote_selection_output_toggle(
     row: MultiBufferRow,
     is_folded: bool,
@@ -2549,9 +5865,7 @@ impl EventEmitter<SearchEvent> for ContextEditor {}
 
 impl Render for ContextEditor {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        let provider = LanguageModelRegistry::read_global(cx)
-            .default_model()
-            .map(|default| default.provider);
+        let provider = self.context.read(cx).model(cx).map(|model| model.provider);
 
         let accept_terms = if self.show_accept_terms {
             provider.as_ref().and_then(|provider| {
@@ -2575,16 +5889,32 @@ impl Render for ContextEditor {
             .capture_action(cx.listener(ContextEditor::confirm_command))
             .on_action(cx.listener(ContextEditor::assist))
             .on_action(cx.listener(ContextEditor::split))
+            .on_action(cx.listener(ContextEditor::toggle_attachments))
+            .on_action(cx.listener(ContextEditor::toggle_follow_output))
+            .on_action(cx.listener(ContextEditor::print_context))
+            .on_action(cx.listener(ContextEditor::export_context))
+            .on_action(cx.listener(ContextEditor::edit_environment))
+            .on_action(cx.listener(ContextEditor::edit_stop_conditions))
+            .on_action(cx.listener(ContextEditor::comment_on_selection))
+            .on_action(cx.listener(ContextEditor::undo_operation))
+            .on_action(cx.listener(ContextEditor::redo_operation))
             .on_action(move |_: &ToggleModelSelector, window, cx| {
                 language_model_selector.toggle(window, cx);
             })
             .size_full()
             .children(self.render_notice(cx))
             .child(
-                div()
+                h_flex()
                     .flex_grow()
-                    .bg(cx.theme().colors().editor_background)
-                    .child(self.editor.clone()),
+                    .w_full()
+                    .child(
+                        div()
+                            .flex_1()
+                            .h_full()
+                            .bg(cx.theme().colors().editor_background)
+                            .child(self.editor.clone()),
+                    )
+                    .children(self.render_attachments(cx)),
             )
             .when_some(accept_terms, |this, element| {
                 this.child(
@@ -2601,7 +5931,12 @@ impl Render for ContextEditor {
                         .child(element),
                 )
             })
-            .children(self.render_last_error(cx))
+            .children(self.render_jump_to_latest(cx))
+            .children(
+                self.render_last_error(cx)
+                    .or_else(|| self.render_retrying_completion(cx))
+                    .or_else(|| self.render_duplicate_attachment(cx)),
+            )
             .child(
                 h_flex()
                     .relative()
@@ -2617,11 +5952,20 @@ impl Render for ContextEditor {
                         h_flex()
                             .gap_0p5()
                             .child(self.render_inject_context_menu(cx))
+                            .child(self.render_attachments_toggle(cx))
+                            .child(self.render_follow_output_toggle(cx))
+                            .child(self.render_export_menu(cx))
+                            .child(self.render_operation_history_menu(cx))
+                            .child(self.render_assembly_menu(cx))
+                            .child(self.render_environment_button(cx))
+                            .child(self.render_answer_language_menu(cx))
+                            .child(self.render_model_parameters_menu(cx))
                             .when_some(max_mode_toggle, |this, element| this.child(element)),
                     )
                     .child(
                         h_flex()
                             .gap_1()
+                            .children(self.render_draft_token_count(cx))
                             .child(self.render_language_model_selector(window, cx))
                             .child(self.render_send_button(window, cx)),
                     ),
@@ -2977,6 +6321,94 @@ pub fn render_remaining_tokens(
     )
 }
 
+pub fn render_context_usage(
+    context_editor: &Entity<ContextEditor>,
+    cx: &App,
+) -> Option<impl IntoElement + use<>> {
+    let tracker = *context_editor.read(cx).context.read(cx).cost_tracker();
+    if tracker.is_empty() {
+        return None;
+    }
+    let tokens = humanize_token_count(tracker.total_tokens() as usize);
+    let label = if tracker.is_priced() {
+        format!("{tokens} · {}", format_cost(tracker.cost))
+    } else {
+        tokens
+    };
+
+    Some(
+        div()
+            .id("context-usage")
+            .child(Label::new(label).size(LabelSize::Small).color(Color::Muted))
+            .tooltip(move |window, cx| {
+                Tooltip::with_meta("Total Usage", None, cost_breakdown(&tracker), window, cx)
+            }),
+    )
+}
+
+fn cost_breakdown(tracker: &CostTracker) -> String {
+    let mut breakdown = format!(
+        "Input: {} tokens · Output: {} tokens",
+        tracker.input_tokens, tracker.output_tokens
+    );
+    if tracker.is_priced() {
+        write!(breakdown, " · Cost: {}", format_cost(tracker.cost)).ok();
+        if tracker.unpriced_tokens > 0 {
+            write!(
+                breakdown,
+                " (excluding {} tokens used with models without pricing)",
+                tracker.unpriced_tokens
+            )
+            .ok();
+        }
+    }
+    breakdown.push_str(". Includes regenerated and deleted responses.");
+    breakdown
+}
+
+fn usage_breakdown(usage: &MessageUsage) -> String {
+    let token_usage = &usage.token_usage;
+    let mut breakdown = format!(
+        "Input: {} tokens · Output: {} tokens",
+        usage.input_tokens(),
+        token_usage.output_tokens
+    );
+    if token_usage.cache_read_input_tokens > 0 {
+        write!(
+            breakdown,
+            " · Cached: {} tokens",
+            token_usage.cache_read_input_tokens
+        )
+        .ok();
+    }
+    if let Some(cost) = usage.cost {
+        write!(breakdown, " · Cost: {}", format_cost(cost)).ok();
+    }
+    if usage.estimated {
+        breakdown.push_str(" (estimated)");
+    } else if let Some(drift) = usage
+        .input_token_drift()
+        .filter(|_| usage.has_token_drift())
+    {
+        write!(
+            breakdown,
+            " · Counted locally as {} input tokens ({:+.0}%), so remaining context may be off",
+            usage.estimated_input_tokens.unwrap_or(0),
+            drift * 100.
+        )
+        .ok();
+    }
+    breakdown
+}
+
+fn format_cost(cost: f64) -> String {
+    if cost < 0.01 {
+        format!("${cost:.4}")
+    } else {
+        format!("${cost:.2}")
+    }
+}
+
 impl Render for ContextEditorToolbarItem {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let left_side = h_flex()
@@ -3017,6 +6449,12 @@ impl Render for ContextEditorToolbarItem {
             //     scan_items_remaining
             //         .map(|remaining_items| format!("Files to scan: {}", remaining_items))
             // })
+            .children(
+                self.active_context_editor
+                    .as_ref()
+                    .and_then(|editor| editor.upgrade())
+                    .and_then(|editor| render_context_usage(&editor, cx)),
+            )
             .children(
                 self.active_context_editor
                     .as_ref()
@@ -3128,12 +6566,63 @@ enum TokenState {
     },
 }
 
+/// Lists the models of the authenticated providers, to pick the one a copy of
+/// the text thread up to the given message is sent to.
+fn duplicate_with_menu(
+    mut menu: ContextMenu,
+    message_id: MessageId,
+    context_editor: WeakEntity<ContextEditor>,
+    cx: &App,
+) -> ContextMenu {
+    let providers = LanguageModelRegistry::read_global(cx).providers();
+    let mut has_models = false;
+    for provider in providers {
+        if !provider.is_authenticated(cx) {
+            continue;
+        }
+        let models = provider.provided_models(cx);
+        if models.is_empty() {
+            continue;
+        }
+        has_models = true;
+        menu = menu.header(provider.name().0);
+        for model in models {
+            let context_editor = context_editor.clone();
+            menu = menu.entry(model.name().0, None, move |window, cx| {
+                let model = model.clone();
+                context_editor
+                    .update(cx, |editor, cx| {
+                        editor.duplicate_with_model(message_id, model, window, cx)
+                    })
+                    .ok();
+            });
+        }
+    }
+    if !has_models {
+        menu = menu.label("No models available");
+    }
+    menu
+}
+
+/// Switches the text thread to the model: just this text thread when it uses
+/// its own model, and every text thread using the default model otherwise.
+fn select_model(
+    model: Arc<dyn LanguageModel>,
+    context: &Entity<AssistantContext>,
+    fs: Arc<dyn Fs>,
+    cx: &mut App,
+) {
+    if context.read(cx).has_own_model() {
+        context.update(cx, |context, cx| context.set_model(&model, cx));
+    } else {
+        update_settings_file::<AgentSettings>(fs, cx, move |settings, _| settings.set_model(model));
+    }
+}
+
 fn token_state(context: &Entity<AssistantContext>, cx: &App) -> Option<TokenState> {
     const WARNING_TOKEN_THRESHOLD: f32 = 0.8;
 
-    let model = LanguageModelRegistry::read_global(cx)
-        .default_model()?
-        .model;
+    let model = context.read(cx).model(cx)?.model;
     let token_count = context.read(cx).token_count()?;
     let max_token_count = model.max_token_count();
 
@@ -3185,8 +6674,46 @@ pub enum ConfigurationError {
     ProviderPendingTermsAcceptance(Arc<dyn LanguageModelProvider>),
 }
 
-fn configuration_error(cx: &App) -> Option<ConfigurationError> {
-    let model = LanguageModelRegistry::read_global(cx).default_model();
+/// The name that the current user's reactions and comments are attributed to.
+/// Returns the title of an issue filed from a message: the context's summary,
+/// or the first line of prose in the message.
+fn issue_title(summary: Option<&str>, message: &str) -> Option<String> {
+    const MAX_TITLE_CHARS: usize = 80;
+
+    let title = summary.map(str::trim).filter(|summary| !summary.is_empty());
+    let title = title.or_else(|| {
+        let mut in_code_block = false;
+        message.lines().find_map(|line| {
+            let line = line.trim();
+            if line.starts_with("```") {
+                in_code_block = !in_code_block;
+                return None;
+            }
+            let line = line.trim_start_matches(['#', '-', '*', '>', ' ']).trim();
+            (!in_code_block && !line.is_empty()).then_some(line)
+        })
+    })?;
+
+    if title.chars().count() > MAX_TITLE_CHARS {
+        let title = title.chars().take(MAX_TITLE_CHARS - 1).collect::<String>();
+        Some(format!("{}…", title.trim_end()))
+    } else {
+        Some(title.to_string())
+    }
+}
+
+fn comment_author(project: &Entity<Project>, cx: &App) -> SharedString {
+    project
+        .read(cx)
+        .user_store()
+        .read(cx)
+        .current_user()
+        .map(|user| user.github_login.clone().into())
+        .unwrap_or_else(|| "You".into())
+}
+
+fn configuration_error(context: &Entity<AssistantContext>, cx: &App) -> Option<ConfigurationError> {
+    let model = context.read(cx).model(cx);
     let is_authenticated = model
         .as_ref()
         .map_or(false, |model| model.provider.is_authenticated(cx));
@@ -3267,6 +6794,7 @@ mod tests {
     use gpui::{App, TestAppContext, VisualTestContext};
     use indoc::indoc;
     use language::{Buffer, LanguageRegistry};
+    use language_model::{ConfiguredModel, fake_provider::FakeLanguageModelProvider};
     use pretty_assertions::assert_eq;
     use prompt_store::PromptBuilder;
     use text::OffsetRangeExt;
@@ -3353,6 +6881,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_code_fence_language() {
+        assert_eq!(code_fence_language("```rust"), Some("rust".to_string()));
+        assert_eq!(
+            code_fence_language("  ```python title=\"a.py\""),
+            Some("python".to_string())
+        );
+        assert_eq!(code_fence_language("~~~ts"), Some("ts".to_string()));
+        assert_eq!(code_fence_language("```"), None);
+    }
+
+    #[test]
+    fn test_issue_title() {
+        assert_eq!(
+            issue_title(Some("Fix panic in parser"), "Some text"),
+            Some("Fix panic in parser".to_string())
+        );
+        assert_eq!(
+            issue_title(None, "```rust\nfn main() {}\n```\n\n## The fix\nMore text"),
+            Some("The fix".to_string())
+        );
+        assert_eq!(issue_title(Some(" "), "```\ncode\n```"), None);
+        assert_eq!(
+            issue_title(None, &"word ".repeat(40)).map(|title| title.chars().count()),
+            Some(80)
+        );
+    }
+
     #[gpui::test]
     fn test_find_code_blocks(cx: &mut App) {
         let markdown = languages::language("markdown", tree_sitter_md::LANGUAGE.into());
@@ -3427,6 +6983,192 @@ mod tests {
         }
     }
 
+    #[gpui::test]
+    async fn test_web_source_blocks_are_only_rebuilt_for_changed_messages(cx: &mut TestAppContext) {
+        let (context, context_editor, mut cx) = setup_context_editor_text(
+            vec![
+                (Role::User, "question"),
+                (Role::Assistant, "first answer"),
+                (Role::Assistant, "second answer"),
+            ],
+            cx,
+        )
+        .await;
+        let message_ids = context.read_with(&cx, |context, cx| {
+            context
+                .messages(cx)
+                .map(|message| message.id)
+                .collect::<Vec<_>>()
+        });
+        let add_source = |message_id: MessageId, url: &str, cx: &mut VisualTestContext| {
+            let source = WebSource {
+                url: url.to_string(),
+                title: None,
+            };
+            context.update(cx, |context, cx| {
+                context
+                    .update_metadata(message_id, cx, |metadata| metadata.web_sources.push(source))
+            });
+            cx.run_until_parked();
+        };
+        let block_ids = |cx: &mut VisualTestContext| {
+            context_editor.read_with(cx, |context_editor, _| {
+                context_editor.web_source_blocks.block_ids()
+            })
+        };
+
+        add_source(message_ids[1], "https://a.example", &mut cx);
+        let first_blocks = block_ids(&mut cx);
+        assert_eq!(first_blocks.len(), 1);
+
+        add_source(message_ids[2], "https://b.example", &mut cx);
+        let blocks = block_ids(&mut cx);
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks.is_superset(&first_blocks));
+
+        context.update(&mut cx, |context, cx| {
+            context.buffer().update(cx, |buffer, cx| {
+                buffer.edit([(0..0, "a ")], None, cx);
+            });
+        });
+        add_source(message_ids[2], "https://c.example", &mut cx);
+        let new_blocks = block_ids(&mut cx);
+        assert_eq!(new_blocks.len(), 2);
+        assert_eq!(
+            new_blocks.intersection(&blocks).collect::<Vec<_>>(),
+            first_blocks.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[gpui::test]
+    async fn test_following_streaming_output(cx: &mut TestAppContext) {
+        let (context, context_editor, mut cx) =
+            setup_context_editor_text(vec![(Role::User, "question")], cx).await;
+        let fake_provider = Arc::new(FakeLanguageModelProvider);
+        let fake_model = Arc::new(fake_provider.test_model());
+        cx.update(|_, cx| {
+            LanguageModelRegistry::global(cx).update(cx, |registry, cx| {
+                registry.set_default_model(
+                    Some(ConfiguredModel {
+                        provider: fake_provider,
+                        model: fake_model.clone(),
+                    }),
+                    cx,
+                )
+            })
+        });
+        let state = |cx: &mut VisualTestContext| {
+            context_editor.update(cx, |context_editor, cx| {
+                (
+                    context_editor.follow_output,
+                    context_editor.following_output,
+                    context_editor.render_jump_to_latest(cx).is_some(),
+                )
+            })
+        };
+
+        context_editor.update_in(&mut cx, |context_editor, window, cx| {
+            context_editor.send_to_model(window, cx)
+        });
+        cx.run_until_parked();
+        fake_model.stream_last_completion_response("answer");
+        cx.run_until_parked();
+        assert!(context.read_with(&cx, |context, _| context.is_streaming()));
+        assert_eq!(state(&mut cx), (true, true, false));
+
+        context_editor.update_in(&mut cx, |context_editor, window, cx| {
+            context_editor.toggle_follow_output(&ToggleFollowOutput, window, cx)
+        });
+        assert_eq!(state(&mut cx), (false, false, true));
+
+        context_editor.update_in(&mut cx, |context_editor, window, cx| {
+            context_editor.jump_to_streaming_output(window, cx)
+        });
+        assert_eq!(state(&mut cx), (true, true, false));
+
+        context_editor.update_in(&mut cx, |context_editor, window, cx| {
+            context_editor.toggle_follow_output(&ToggleFollowOutput, window, cx)
+        });
+        fake_model.end_last_completion_stream();
+        cx.run_until_parked();
+        assert!(!context.read_with(&cx, |context, _| context.is_streaming()));
+        assert_eq!(state(&mut cx), (false, false, false));
+    }
+
+    #[gpui::test]
+    async fn test_editors_for_the_same_context_share_edits(cx: &mut TestAppContext) {
+        let (context, context_editor, mut cx) =
+            setup_context_editor_text(vec![(Role::User, "question")], cx).await;
+        let (workspace, project, fs) = context_editor.read_with(&cx, |context_editor, _| {
+            (
+                context_editor.workspace.clone(),
+                context_editor.project.clone(),
+                context_editor.fs.clone(),
+            )
+        });
+        let split_editor = cx.new_window_entity(|window, cx| {
+            ContextEditor::for_context(context.clone(), fs, workspace, project, None, window, cx)
+        });
+
+        context_editor.update_in(&mut cx, |context_editor, window, cx| {
+            context_editor.editor.update(cx, |editor, cx| {
+                editor.move_to_end(&Default::default(), window, cx);
+                editor.insert("?", window, cx);
+            });
+        });
+        split_editor.update_in(&mut cx, |split_editor, window, cx| {
+            split_editor.editor.update(cx, |editor, cx| {
+                assert_eq!(editor.text(cx), "question?");
+                editor.move_to_beginning(&Default::default(), window, cx);
+                editor.insert("A ", window, cx);
+            });
+        });
+        context_editor.read_with(&cx, |context_editor, cx| {
+            assert_eq!(context_editor.editor.read(cx).text(cx), "A question?");
+        });
+        assert_eq!(
+            context.read_with(&cx, |context, cx| context.buffer().read(cx).text()),
+            "A question?"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_draft_token_count(cx: &mut TestAppContext) {
+        let (context, context_editor, mut cx) = setup_context_editor_text(
+            vec![(Role::User, "question"), (Role::Assistant, "answer")],
+            cx,
+        )
+        .await;
+        let draft_token_count = |cx: &mut VisualTestContext| {
+            cx.executor().advance_clock(Duration::from_secs(1));
+            cx.run_until_parked();
+            context_editor.read_with(cx, |context_editor, _| context_editor.draft_token_count)
+        };
+        assert_eq!(draft_token_count(&mut cx), None);
+
+        let last_message_id =
+            context.read_with(&cx, |context, cx| context.messages(cx).last().unwrap().id);
+        context.update(&mut cx, |context, cx| {
+            context.insert_message_after(last_message_id, Role::User, MessageStatus::Done, cx);
+        });
+        context_editor.update_in(&mut cx, |context_editor, window, cx| {
+            context_editor.editor.update(cx, |editor, cx| {
+                editor.move_to_end(&Default::default(), window, cx);
+                editor.insert("follow-up", window, cx);
+            });
+        });
+        assert_eq!(draft_token_count(&mut cx), Some(0));
+
+        let draft_range = message_range(&context, 2, &mut cx);
+        context_editor.update_in(&mut cx, |context_editor, window, cx| {
+            context_editor.editor.update(cx, |editor, cx| {
+                editor.change_selections(None, window, cx, |s| s.select_ranges([draft_range]));
+                editor.insert("  ", window, cx);
+            });
+        });
+        assert_eq!(draft_token_count(&mut cx), None);
+    }
+
     async fn setup_context_editor_text(
         messages: Vec<(Role, &str)>,
         cx: &mut TestAppContext,
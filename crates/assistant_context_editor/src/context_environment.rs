@@ -0,0 +1,117 @@
+//! Passes a text thread's environment variables to the slash commands it
+//! runs, so that the processes they launch, such as the ones started by
+//! extension commands, can be parameterized per text thread.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use collections::{BTreeMap, HashMap};
+use gpui::App;
+use http_client::HttpClient;
+use language::{BinaryStatus, LanguageServerName, LspAdapter, LspAdapterDelegate};
+use lsp::LanguageServerBinary;
+use settings::WorktreeId;
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Whether the name can be used for an environment variable: letters,
+/// digits and underscores, not starting with a digit.
+pub fn is_valid_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns a delegate whose shell environment includes the given variables,
+/// which take precedence over the ones from the shell.
+pub fn with_environment(
+    delegate: Arc<dyn LspAdapterDelegate>,
+    environment: BTreeMap<String, String>,
+) -> Arc<dyn LspAdapterDelegate> {
+    if environment.is_empty() {
+        return delegate;
+    }
+    Arc::new(EnvironmentDelegate {
+        delegate,
+        environment,
+    })
+}
+
+struct EnvironmentDelegate {
+    delegate: Arc<dyn LspAdapterDelegate>,
+    environment: BTreeMap<String, String>,
+}
+
+#[async_trait]
+impl LspAdapterDelegate for EnvironmentDelegate {
+    fn show_notification(&self, message: &str, cx: &mut App) {
+        self.delegate.show_notification(message, cx)
+    }
+
+    fn http_client(&self) -> Arc<dyn HttpClient> {
+        self.delegate.http_client()
+    }
+
+    fn worktree_id(&self) -> WorktreeId {
+        self.delegate.worktree_id()
+    }
+
+    fn worktree_root_path(&self) -> &Path {
+        self.delegate.worktree_root_path()
+    }
+
+    fn update_status(&self, language: LanguageServerName, status: BinaryStatus) {
+        self.delegate.update_status(language, status)
+    }
+
+    fn registered_lsp_adapters(&self) -> Vec<Arc<dyn LspAdapter>> {
+        self.delegate.registered_lsp_adapters()
+    }
+
+    async fn language_server_download_dir(&self, name: &LanguageServerName) -> Option<Arc<Path>> {
+        self.delegate.language_server_download_dir(name).await
+    }
+
+    async fn npm_package_installed_version(
+        &self,
+        package_name: &str,
+    ) -> Result<Option<(PathBuf, String)>> {
+        self.delegate
+            .npm_package_installed_version(package_name)
+            .await
+    }
+
+    async fn which(&self, command: &OsStr) -> Option<PathBuf> {
+        self.delegate.which(command).await
+    }
+
+    async fn shell_env(&self) -> HashMap<String, String> {
+        let mut env = self.delegate.shell_env().await;
+        env.extend(self.environment.clone());
+        env
+    }
+
+    async fn read_text_file(&self, path: PathBuf) -> Result<String> {
+        self.delegate.read_text_file(path).await
+    }
+
+    async fn try_exec(&self, binary: LanguageServerBinary) -> Result<()> {
+        self.delegate.try_exec(binary).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_variable_name() {
+        assert!(is_valid_variable_name("STAGING_URL"));
+        assert!(is_valid_variable_name("_private1"));
+        assert!(!is_valid_variable_name(""));
+        assert!(!is_valid_variable_name("1PASSWORD"));
+        assert!(!is_valid_variable_name("API-KEY"));
+        assert!(!is_valid_variable_name("NAME=VALUE"));
+    }
+}
@@ -1,12 +1,19 @@
+use std::path::Path;
 use std::sync::Arc;
 
-use gpui::{App, Entity, EventEmitter, FocusHandle, Focusable, Subscription, Task, WeakEntity};
+use chrono::{Datelike as _, Local, NaiveDate, TimeDelta};
+use collections::HashSet;
+use gpui::{
+    AnyElement, App, Entity, EventEmitter, FocusHandle, Focusable, PathPromptOptions, Subscription,
+    Task, WeakEntity,
+};
 use picker::{Picker, PickerDelegate};
 use project::Project;
 use ui::utils::{DateTimeType, format_distance_from_now};
-use ui::{Avatar, ListItem, ListItemSpacing, prelude::*};
+use ui::{Avatar, Checkbox, ListItem, ListItemSpacing, Tooltip, prelude::*};
 use workspace::{Item, Workspace};
 
+use crate::tag_contexts_modal::TagContextsModal;
 use crate::{
     AgentPanelDelegate, ContextStore, DEFAULT_TAB_TITLE, RemoteContextMetadata,
     SavedContextMetadata,
@@ -38,7 +45,7 @@ impl ContextHistory {
     ) -> Self {
         let picker = cx.new(|cx| {
             Picker::uniform_list(
-                SavedContextPickerDelegate::new(project, context_store.clone()),
+                SavedContextPickerDelegate::new(project, context_store.clone(), workspace.clone()),
                 window,
                 cx,
             )
@@ -113,91 +120,473 @@ impl Item for ContextHistory {
     }
 }
 
+/// How recently a text thread was saved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum TimeBucket {
+    Today,
+    Yesterday,
+    ThisWeek,
+    Older,
+}
+
+impl TimeBucket {
+    const ALL: [Self; 4] = [Self::Today, Self::Yesterday, Self::ThisWeek, Self::Older];
+
+    fn from_dates(reference: NaiveDate, date: NaiveDate) -> Self {
+        if date == reference {
+            TimeBucket::Today
+        } else if date == reference - TimeDelta::days(1) {
+            TimeBucket::Yesterday
+        } else if date.iso_week() == reference.iso_week() {
+            TimeBucket::ThisWeek
+        } else {
+            TimeBucket::Older
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            TimeBucket::Today => "Today",
+            TimeBucket::Yesterday => "Yesterday",
+            TimeBucket::ThisWeek => "This Week",
+            TimeBucket::Older => "Older",
+        }
+    }
+}
+
+/// A collapsible group of text threads in the history.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum HistoryGroup {
+    /// The text threads shared by the host of the project.
+    Shared,
+    /// The text threads saved from a project, or from no project.
+    Project(Option<String>),
+    /// The text threads saved from a project within a date bucket.
+    Date(Option<String>, TimeBucket),
+    Archived,
+    /// The deleted text threads that can still be restored.
+    Trash,
+}
+
+impl HistoryGroup {
+    fn contains(&self, metadata: &SavedContextMetadata, today: NaiveDate) -> bool {
+        match self {
+            HistoryGroup::Shared => false,
+            HistoryGroup::Project(project) => !metadata.archived && metadata.project == *project,
+            HistoryGroup::Date(project, bucket) => {
+                !metadata.archived
+                    && metadata.project == *project
+                    && TimeBucket::from_dates(today, metadata.mtime.date_naive()) == *bucket
+            }
+            HistoryGroup::Archived => metadata.archived,
+            HistoryGroup::Trash => false,
+        }
+    }
+
+    fn label(&self) -> SharedString {
+        match self {
+            HistoryGroup::Shared => "Shared".into(),
+            HistoryGroup::Project(Some(project)) => project.clone().into(),
+            HistoryGroup::Project(None) => "No Project".into(),
+            HistoryGroup::Date(_, bucket) => bucket.label().into(),
+            HistoryGroup::Archived => "Archived".into(),
+            HistoryGroup::Trash => "Recently Deleted".into(),
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            HistoryGroup::Date(..) => 1,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum HistoryEntry {
+    Group {
+        group: HistoryGroup,
+        count: usize,
+    },
+    Context {
+        metadata: ContextMetadata,
+        depth: usize,
+    },
+}
+
+/// Groups the text threads by project, and then by when they were saved,
+/// leaving out the contents of the collapsed groups. Projects are ordered by
+/// their most recently saved text thread.
+fn group_entries(
+    contexts: &[SavedContextMetadata],
+    trashed: &[SavedContextMetadata],
+    host_contexts: &[RemoteContextMetadata],
+    collapsed: &HashSet<HistoryGroup>,
+    today: NaiveDate,
+) -> Vec<HistoryEntry> {
+    let mut contexts = contexts.iter().collect::<Vec<_>>();
+    contexts.sort_by(|a, b| b.mtime.cmp(&a.mtime));
+
+    let mut entries = Vec::new();
+    push_group(
+        &mut entries,
+        HistoryGroup::Shared,
+        host_contexts
+            .iter()
+            .cloned()
+            .map(ContextMetadata::Remote)
+            .collect(),
+        collapsed,
+    );
+
+    let mut projects = Vec::new();
+    for metadata in &contexts {
+        if !metadata.archived && !projects.contains(&metadata.project) {
+            projects.push(metadata.project.clone());
+        }
+    }
+    for project in projects {
+        let project_group = HistoryGroup::Project(project.clone());
+        let count = contexts
+            .iter()
+            .filter(|metadata| project_group.contains(metadata, today))
+            .count();
+        let is_collapsed = collapsed.contains(&project_group);
+        entries.push(HistoryEntry::Group {
+            group: project_group,
+            count,
+        });
+        if is_collapsed {
+            continue;
+        }
+        for bucket in TimeBucket::ALL {
+            let group = HistoryGroup::Date(project.clone(), bucket);
+            let members = contexts
+                .iter()
+                .filter(|metadata| group.contains(metadata, today))
+                .map(|metadata| ContextMetadata::Saved((*metadata).clone()))
+                .collect();
+            push_group(&mut entries, group, members, collapsed);
+        }
+    }
+
+    let archived = contexts
+        .iter()
+        .filter(|metadata| metadata.archived)
+        .map(|metadata| ContextMetadata::Saved((*metadata).clone()))
+        .collect();
+    push_group(&mut entries, HistoryGroup::Archived, archived, collapsed);
+
+    let trashed = trashed
+        .iter()
+        .cloned()
+        .map(ContextMetadata::Saved)
+        .collect();
+    push_group(&mut entries, HistoryGroup::Trash, trashed, collapsed);
+    entries
+}
+
+fn push_group(
+    entries: &mut Vec<HistoryEntry>,
+    group: HistoryGroup,
+    members: Vec<ContextMetadata>,
+    collapsed: &HashSet<HistoryGroup>,
+) {
+    if members.is_empty() {
+        return;
+    }
+    let depth = group.depth() + 1;
+    let is_collapsed = collapsed.contains(&group);
+    entries.push(HistoryEntry::Group {
+        group,
+        count: members.len(),
+    });
+    if !is_collapsed {
+        entries.extend(
+            members
+                .into_iter()
+                .map(|metadata| HistoryEntry::Context { metadata, depth }),
+        );
+    }
+}
+
 struct SavedContextPickerDelegate {
     store: Entity<ContextStore>,
     project: Entity<Project>,
-    matches: Vec<ContextMetadata>,
+    workspace: WeakEntity<Workspace>,
+    query: String,
+    /// The saved text threads that match the query.
+    contexts: Vec<SavedContextMetadata>,
+    trashed: Vec<SavedContextMetadata>,
+    collapsed: HashSet<HistoryGroup>,
+    /// The text threads checked for a bulk operation.
+    checked_paths: HashSet<Arc<Path>>,
+    /// The text threads moved to the trash by the last bulk delete, which
+    /// can be restored until the banner is dismissed.
+    last_deletion: Option<Vec<Arc<Path>>>,
+    matches: Vec<HistoryEntry>,
     selected_index: usize,
 }
 
 impl EventEmitter<SavedContextPickerEvent> for Picker<SavedContextPickerDelegate> {}
 
 impl SavedContextPickerDelegate {
-    fn new(project: Entity<Project>, store: Entity<ContextStore>) -> Self {
+    fn new(
+        project: Entity<Project>,
+        store: Entity<ContextStore>,
+        workspace: WeakEntity<Workspace>,
+    ) -> Self {
         Self {
             project,
             store,
+            workspace,
+            query: String::new(),
+            contexts: Vec::new(),
+            trashed: Vec::new(),
+            collapsed: HashSet::from_iter([HistoryGroup::Archived, HistoryGroup::Trash]),
+            checked_paths: HashSet::default(),
+            last_deletion: None,
             matches: Vec::new(),
             selected_index: 0,
         }
     }
-}
 
-impl PickerDelegate for SavedContextPickerDelegate {
-    type ListItem = ListItem;
+    /// Groups the matches when there's no query, and lists them by relevance
+    /// otherwise.
+    fn rebuild_entries(&mut self, cx: &App) {
+        let store = self.store.read(cx);
+        self.trashed = store.trashed_contexts().to_vec();
+        let trashed = &self.trashed;
+        self.checked_paths
+            .retain(|path| !trashed.iter().any(|metadata| metadata.path == *path));
 
-    fn match_count(&self) -> usize {
-        self.matches.len()
+        let host_contexts = store.host_contexts();
+        self.matches = if self.query.trim().is_empty() {
+            group_entries(
+                &self.contexts,
+                &self.trashed,
+                host_contexts,
+                &self.collapsed,
+                Local::now().date_naive(),
+            )
+        } else {
+            host_contexts
+                .iter()
+                .cloned()
+                .map(ContextMetadata::Remote)
+                .chain(self.contexts.iter().cloned().map(ContextMetadata::Saved))
+                .map(|metadata| HistoryEntry::Context { metadata, depth: 0 })
+                .collect()
+        };
+        self.selected_index = self
+            .selected_index
+            .min(self.matches.len().saturating_sub(1));
     }
 
-    fn selected_index(&self) -> usize {
-        self.selected_index
+    fn toggle_group(&mut self, group: HistoryGroup, cx: &mut Context<Picker<Self>>) {
+        if !self.collapsed.remove(&group) {
+            self.collapsed.insert(group);
+        }
+        self.rebuild_entries(cx);
+        cx.notify();
     }
 
-    fn set_selected_index(
-        &mut self,
-        ix: usize,
-        _window: &mut Window,
-        _cx: &mut Context<Picker<Self>>,
-    ) {
-        self.selected_index = ix;
+    fn group_paths(&self, group: &HistoryGroup) -> Vec<Arc<Path>> {
+        if *group == HistoryGroup::Trash {
+            return self
+                .trashed
+                .iter()
+                .map(|metadata| metadata.path.clone())
+                .collect();
+        }
+        let today = Local::now().date_naive();
+        self.contexts
+            .iter()
+            .filter(|metadata| group.contains(metadata, today))
+            .map(|metadata| metadata.path.clone())
+            .collect()
     }
 
-    fn placeholder_text(&self, _window: &mut Window, _cx: &mut App) -> Arc<str> {
-        "Search...".into()
+    fn archive_group(&mut self, group: &HistoryGroup, archived: bool, cx: &mut App) {
+        let paths = self.group_paths(group);
+        self.store
+            .update(cx, |store, cx| store.set_archived(paths, archived, cx))
+            .detach_and_log_err(cx);
     }
 
-    fn update_matches(
-        &mut self,
-        query: String,
-        _window: &mut Window,
-        cx: &mut Context<Picker<Self>>,
-    ) -> Task<()> {
-        let search = self.store.read(cx).search(query, cx);
-        cx.spawn(async move |this, cx| {
-            let matches = search.await;
-            this.update(cx, |this, cx| {
-                let host_contexts = this.delegate.store.read(cx).host_contexts();
-                this.delegate.matches = host_contexts
-                    .iter()
-                    .cloned()
-                    .map(ContextMetadata::Remote)
-                    .chain(matches.into_iter().map(ContextMetadata::Saved))
-                    .collect();
-                this.delegate.selected_index = 0;
-                cx.notify();
+    fn is_trashed(&self, path: &Path) -> bool {
+        self.trashed.iter().any(|metadata| &*metadata.path == path)
+    }
+
+    fn toggle_checked(&mut self, path: Arc<Path>, cx: &mut Context<Picker<Self>>) {
+        if !self.checked_paths.remove(&path) {
+            self.checked_paths.insert(path);
+        }
+        cx.notify();
+    }
+
+    fn checked_paths(&self) -> Vec<Arc<Path>> {
+        self.checked_paths.iter().cloned().collect()
+    }
+
+    /// Moves the text threads to the trash, from which the last deletion can
+    /// be undone.
+    fn trash(&mut self, paths: Vec<Arc<Path>>, cx: &mut Context<Picker<Self>>) {
+        if paths.is_empty() {
+            return;
+        }
+        for path in &paths {
+            self.checked_paths.remove(path);
+        }
+        self.last_deletion = Some(paths.clone());
+        self.store
+            .update(cx, |store, cx| store.trash_local_contexts(paths, cx))
+            .detach_and_log_err(cx);
+        cx.notify();
+    }
+
+    fn restore(&mut self, paths: Vec<Arc<Path>>, cx: &mut Context<Picker<Self>>) {
+        self.store
+            .update(cx, |store, cx| store.restore_local_contexts(paths, cx))
+            .detach_and_log_err(cx);
+    }
+
+    fn undo_deletion(&mut self, cx: &mut Context<Picker<Self>>) {
+        if let Some(paths) = self.last_deletion.take() {
+            self.restore(paths, cx);
+            cx.notify();
+        }
+    }
+
+    fn export(&mut self, paths: Vec<Arc<Path>>, cx: &mut App) {
+        let dir = cx.prompt_for_paths(PathPromptOptions {
+            files: false,
+            directories: true,
+            multiple: false,
+        });
+        let store = self.store.clone();
+        cx.spawn(async move |cx| {
+            let Some(dir) = dir.await??.and_then(|mut dirs| dirs.pop()) else {
+                return Ok(());
+            };
+            store
+                .update(cx, |store, cx| store.export_local_contexts(paths, dir, cx))?
+                .await
+        })
+        .detach_and_log_err(cx);
+    }
+
+    fn tag_checked(&mut self, window: &mut Window, cx: &mut Context<Picker<Self>>) {
+        let store = self.store.clone();
+        let paths = self.checked_paths();
+        self.workspace
+            .update(cx, |workspace, cx| {
+                workspace.toggle_modal(window, cx, |window, cx| {
+                    TagContextsModal::new(store, paths, window, cx)
+                });
             })
             .ok();
-        })
     }
 
-    fn confirm(&mut self, _secondary: bool, _window: &mut Window, cx: &mut Context<Picker<Self>>) {
-        if let Some(metadata) = self.matches.get(self.selected_index) {
-            cx.emit(SavedContextPickerEvent::Confirmed(metadata.clone()));
+    /// Merges the checked text threads into a new one, oldest first.
+    fn merge_checked(&mut self, cx: &mut Context<Picker<Self>>) {
+        let mut contexts = self
+            .store
+            .read(cx)
+            .reverse_chronological_contexts()
+            .into_iter()
+            .filter(|metadata| self.checked_paths.contains(&metadata.path))
+            .collect::<Vec<_>>();
+        if contexts.len() < 2 {
+            return;
         }
+        contexts.reverse();
+        let title = contexts
+            .iter()
+            .map(|metadata| metadata.title.as_str())
+            .collect::<Vec<_>>()
+            .join(" + ");
+        let paths = contexts.into_iter().map(|metadata| metadata.path).collect();
+        self.store
+            .update(cx, |store, cx| store.merge_local_contexts(paths, title, cx))
+            .detach_and_log_err(cx);
+        self.checked_paths.clear();
+        cx.notify();
     }
 
-    fn dismissed(&mut self, _window: &mut Window, _cx: &mut Context<Picker<Self>>) {}
-
-    fn render_match(
+    fn render_group_actions(
         &self,
         ix: usize,
-        selected: bool,
-        _window: &mut Window,
+        group: &HistoryGroup,
         cx: &mut Context<Picker<Self>>,
-    ) -> Option<Self::ListItem> {
-        let context = self.matches.get(ix)?;
-        let item = match context {
+    ) -> AnyElement {
+        if *group == HistoryGroup::Trash {
+            let group = group.clone();
+            return h_flex()
+                .child(
+                    IconButton::new(("restore-group", ix), IconName::Undo)
+                        .icon_size(IconSize::Small)
+                        .icon_color(Color::Muted)
+                        .tooltip(Tooltip::text("Restore All"))
+                        .on_click(cx.listener(move |picker, _, _, cx| {
+                            let paths = picker.delegate.group_paths(&group);
+                            picker.delegate.restore(paths, cx);
+                        })),
+                )
+                .into_any_element();
+        }
+        let archive_button = {
+            let group = group.clone();
+            let archived = *group != HistoryGroup::Archived;
+            let (icon, tooltip) = if archived {
+                (IconName::Archive, "Archive All")
+            } else {
+                (IconName::ArrowUpFromLine, "Unarchive All")
+            };
+            IconButton::new(("archive-group", ix), icon)
+                .icon_size(IconSize::Small)
+                .icon_color(Color::Muted)
+                .tooltip(Tooltip::text(tooltip))
+                .on_click(cx.listener(move |picker, _, _, cx| {
+                    picker.delegate.archive_group(&group, archived, cx);
+                }))
+        };
+        let export_button = {
+            let group = group.clone();
+            IconButton::new(("export-group", ix), IconName::Download)
+                .icon_size(IconSize::Small)
+                .icon_color(Color::Muted)
+                .tooltip(Tooltip::text("Export All…"))
+                .on_click(cx.listener(move |picker, _, _, cx| {
+                    let paths = picker.delegate.group_paths(&group);
+                    picker.delegate.export(paths, cx);
+                }))
+        };
+        let delete_button = {
+            let group = group.clone();
+            IconButton::new(("delete-group", ix), IconName::Trash)
+                .icon_size(IconSize::Small)
+                .icon_color(Color::Muted)
+                .tooltip(Tooltip::text("Delete All"))
+                .on_click(cx.listener(move |picker, _, _, cx| {
+                    let paths = picker.delegate.group_paths(&group);
+                    picker.delegate.trash(paths, cx);
+                }))
+        };
+        h_flex()
+            .gap_0p5()
+            .child(archive_button)
+            .child(export_button)
+            .child(delete_button)
+            .into_any_element()
+    }
+
+    fn render_context(
+        &self,
+        context: &ContextMetadata,
+        cx: &mut Context<Picker<Self>>,
+    ) -> impl IntoElement {
+        match context {
             ContextMetadata::Remote(context) => {
                 let host_user = self.project.read(cx).host().and_then(|collaborator| {
                     self.project
@@ -259,13 +648,343 @@ impl PickerDelegate for SavedContextPickerDelegate {
                     .color(Color::Muted)
                     .size(LabelSize::Small),
                 ),
-        };
+        }
+    }
+}
+
+impl PickerDelegate for SavedContextPickerDelegate {
+    type ListItem = ListItem;
+
+    fn match_count(&self) -> usize {
+        self.matches.len()
+    }
+
+    fn selected_index(&self) -> usize {
+        self.selected_index
+    }
+
+    fn set_selected_index(
+        &mut self,
+        ix: usize,
+        _window: &mut Window,
+        _cx: &mut Context<Picker<Self>>,
+    ) {
+        self.selected_index = ix;
+    }
+
+    fn placeholder_text(&self, _window: &mut Window, _cx: &mut App) -> Arc<str> {
+        "Search...".into()
+    }
+
+    fn update_matches(
+        &mut self,
+        query: String,
+        _window: &mut Window,
+        cx: &mut Context<Picker<Self>>,
+    ) -> Task<()> {
+        let search = self.store.read(cx).search(query.clone(), cx);
+        cx.spawn(async move |this, cx| {
+            let matches = search.await;
+            this.update(cx, |this, cx| {
+                this.delegate.query = query;
+                this.delegate.contexts = matches;
+                this.delegate.selected_index = 0;
+                this.delegate.rebuild_entries(cx);
+                cx.notify();
+            })
+            .ok();
+        })
+    }
+
+    fn confirm(&mut self, secondary: bool, _window: &mut Window, cx: &mut Context<Picker<Self>>) {
+        match self.matches.get(self.selected_index) {
+            Some(HistoryEntry::Group { group, .. }) => {
+                let group = group.clone();
+                self.toggle_group(group, cx);
+            }
+            Some(HistoryEntry::Context {
+                metadata: ContextMetadata::Saved(metadata),
+                ..
+            }) if self.is_trashed(&metadata.path) => {
+                let path = metadata.path.clone();
+                self.restore(vec![path], cx);
+            }
+            Some(HistoryEntry::Context {
+                metadata: ContextMetadata::Saved(metadata),
+                ..
+            }) if secondary => {
+                let path = metadata.path.clone();
+                self.toggle_checked(path, cx);
+            }
+            Some(HistoryEntry::Context { metadata, .. }) => {
+                cx.emit(SavedContextPickerEvent::Confirmed(metadata.clone()));
+            }
+            None => {}
+        }
+    }
+
+    fn dismissed(&mut self, _window: &mut Window, _cx: &mut Context<Picker<Self>>) {}
+
+    fn render_match(
+        &self,
+        ix: usize,
+        selected: bool,
+        _window: &mut Window,
+        cx: &mut Context<Picker<Self>>,
+    ) -> Option<Self::ListItem> {
+        let item = ListItem::new(ix)
+            .inset(true)
+            .spacing(ListItemSpacing::Sparse)
+            .toggle_state(selected);
+        match self.matches.get(ix)? {
+            HistoryEntry::Group { group, count } => {
+                let is_collapsed = self.collapsed.contains(group);
+                let has_actions = *group != HistoryGroup::Shared;
+                Some(
+                    item.indent_level(group.depth())
+                        .start_slot(
+                            Icon::new(if is_collapsed {
+                                IconName::ChevronRight
+                            } else {
+                                IconName::ChevronDown
+                            })
+                            .size(IconSize::Small)
+                            .color(Color::Muted),
+                        )
+                        .child(
+                            h_flex()
+                                .gap_1()
+                                .child(
+                                    Label::new(group.label())
+                                        .size(LabelSize::Small)
+                                        .color(Color::Muted),
+                                )
+                                .child(
+                                    Label::new(count.to_string())
+                                        .size(LabelSize::XSmall)
+                                        .color(Color::Disabled),
+                                ),
+                        )
+                        .when(has_actions, |item| {
+                            item.end_hover_slot(self.render_group_actions(ix, group, cx))
+                        }),
+                )
+            }
+            HistoryEntry::Context { metadata, depth } => {
+                let checkable_path = match metadata {
+                    ContextMetadata::Saved(metadata) if !self.is_trashed(&metadata.path) => {
+                        Some(metadata.path.clone())
+                    }
+                    _ => None,
+                };
+                Some(
+                    item.indent_level(*depth)
+                        .when_some(checkable_path, |item, path| {
+                            let checked = self.checked_paths.contains(&path);
+                            item.start_slot(
+                                Checkbox::new(("check-context", ix), checked.into()).on_click(
+                                    cx.listener(move |picker, _, _, cx| {
+                                        picker.delegate.toggle_checked(path.clone(), cx);
+                                    }),
+                                ),
+                            )
+                        })
+                        .child(self.render_context(metadata, cx)),
+                )
+            }
+        }
+    }
+
+    fn render_footer(
+        &self,
+        _window: &mut Window,
+        cx: &mut Context<Picker<Self>>,
+    ) -> Option<AnyElement> {
+        if self.checked_paths.is_empty() && self.last_deletion.is_none() {
+            return None;
+        }
+
+        let deletion_banner = self.last_deletion.as_ref().map(|paths| {
+            let message = match paths.len() {
+                1 => "Deleted 1 text thread".to_string(),
+                count => format!("Deleted {count} text threads"),
+            };
+            h_flex()
+                .w_full()
+                .gap_2()
+                .justify_between()
+                .child(
+                    Label::new(message)
+                        .size(LabelSize::Small)
+                        .color(Color::Muted),
+                )
+                .child(
+                    h_flex()
+                        .gap_1()
+                        .child(
+                            Button::new("undo-deletion", "Undo")
+                                .label_size(LabelSize::Small)
+                                .on_click(cx.listener(|picker, _, _, cx| {
+                                    picker.delegate.undo_deletion(cx);
+                                })),
+                        )
+                        .child(
+                            IconButton::new("dismiss-deletion", IconName::Close)
+                                .icon_size(IconSize::Small)
+                                .icon_color(Color::Muted)
+                                .on_click(cx.listener(|picker, _, _, cx| {
+                                    picker.delegate.last_deletion = None;
+                                    cx.notify();
+                                })),
+                        ),
+                )
+        });
+
+        let checked_count = self.checked_paths.len();
+        let bulk_actions = (checked_count > 0).then(|| {
+            h_flex()
+                .w_full()
+                .gap_1()
+                .justify_between()
+                .child(
+                    Label::new(format!("{checked_count} selected"))
+                        .size(LabelSize::Small)
+                        .color(Color::Muted),
+                )
+                .child(
+                    h_flex()
+                        .gap_1()
+                        .child(
+                            Button::new("tag-checked", "Tag…")
+                                .label_size(LabelSize::Small)
+                                .on_click(cx.listener(|picker, _, window, cx| {
+                                    picker.delegate.tag_checked(window, cx);
+                                })),
+                        )
+                        .child(
+                            Button::new("merge-checked", "Merge")
+                                .label_size(LabelSize::Small)
+                                .disabled(checked_count < 2)
+                                .on_click(cx.listener(|picker, _, _, cx| {
+                                    picker.delegate.merge_checked(cx);
+                                })),
+                        )
+                        .child(
+                            Button::new("export-checked", "Export…")
+                                .label_size(LabelSize::Small)
+                                .on_click(cx.listener(|picker, _, _, cx| {
+                                    let paths = picker.delegate.checked_paths();
+                                    picker.delegate.export(paths, cx);
+                                })),
+                        )
+                        .child(
+                            Button::new("delete-checked", "Delete")
+                                .label_size(LabelSize::Small)
+                                .on_click(cx.listener(|picker, _, _, cx| {
+                                    let paths = picker.delegate.checked_paths();
+                                    picker.delegate.trash(paths, cx);
+                                })),
+                        )
+                        .child(
+                            Button::new("clear-checked", "Clear")
+                                .label_size(LabelSize::Small)
+                                .on_click(cx.listener(|picker, _, _, cx| {
+                                    picker.delegate.checked_paths.clear();
+                                    cx.notify();
+                                })),
+                        ),
+                )
+        });
+
         Some(
-            ListItem::new(ix)
-                .inset(true)
-                .spacing(ListItemSpacing::Sparse)
-                .toggle_state(selected)
-                .child(item),
+            v_flex()
+                .w_full()
+                .p_2()
+                .gap_1()
+                .border_t_1()
+                .border_color(cx.theme().colors().border_variant)
+                .children(deletion_banner)
+                .children(bulk_actions)
+                .into_any(),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    #[test]
+    fn test_group_entries() {
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let context =
+            |title: &str, project: Option<&str>, day: u32, archived: bool| SavedContextMetadata {
+                title: title.into(),
+                path: Path::new(title).into(),
+                mtime: Local.with_ymd_and_hms(2025, 1, day, 12, 0, 0).unwrap(),
+                tags: Vec::new(),
+                project: project.map(Into::into),
+                archived,
+            };
+        let contexts = [
+            context("a", Some("zed"), 15, false),
+            context("b", None, 14, false),
+            context("c", Some("zed"), 13, false),
+            context("d", Some("zed"), 2, false),
+            context("e", Some("zed"), 15, true),
+        ];
+        let trashed = [context("f", Some("zed"), 10, false)];
+        let describe = |collapsed: &HashSet<HistoryGroup>| {
+            group_entries(&contexts, &trashed, &[], collapsed, today)
+                .into_iter()
+                .map(|entry| match entry {
+                    HistoryEntry::Group { group, count } => {
+                        format!("{}{} ({count})", "  ".repeat(group.depth()), group.label())
+                    }
+                    HistoryEntry::Context {
+                        metadata: ContextMetadata::Saved(metadata),
+                        depth,
+                    } => format!("{}{}", "  ".repeat(depth), metadata.title),
+                    HistoryEntry::Context { .. } => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            describe(&HashSet::from_iter([
+                HistoryGroup::Archived,
+                HistoryGroup::Trash
+            ])),
+            [
+                "zed (3)",
+                "  Today (1)",
+                "    a",
+                "  This Week (1)",
+                "    c",
+                "  Older (1)",
+                "    d",
+                "No Project (1)",
+                "  Yesterday (1)",
+                "    b",
+                "Archived (1)",
+                "Recently Deleted (1)",
+            ]
+        );
+        assert_eq!(
+            describe(&HashSet::from_iter([
+                HistoryGroup::Project(Some("zed".into())),
+                HistoryGroup::Date(None, TimeBucket::Yesterday),
+            ])),
+            [
+                "zed (3)",
+                "No Project (1)",
+                "  Yesterday (1)",
+                "Archived (1)",
+                "  e",
+                "Recently Deleted (1)",
+                "  f",
+            ]
+        );
+    }
+}
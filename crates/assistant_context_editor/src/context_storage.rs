@@ -0,0 +1,247 @@
+use crate::SavedContext;
+use agent_settings::TextThreadRetention;
+use anyhow::{Context as _, Result};
+use std::{
+    ops::Range,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+impl SavedContext {
+    /// Moves the text of the slash command outputs that are at least
+    /// `min_len` bytes long out of the saved context, and returns it so that it
+    /// can be stored separately. Nested outputs move along with the
+    /// output that contains them.
+    pub fn compact(&mut self, min_len: usize) -> Vec<String> {
+        let mut ranges = self
+            .slash_command_output_sections
+            .iter()
+            .map(|section| section.range.clone())
+            .filter(|range| range.len() >= min_len)
+            .collect::<Vec<_>>();
+        ranges.sort_by_key(|range| (range.start, std::cmp::Reverse(range.end)));
+
+        let mut compacted_sections: Vec<Range<usize>> = Vec::new();
+        for range in ranges {
+            if compacted_sections
+                .last()
+                .is_none_or(|last| range.start >= last.end)
+            {
+                compacted_sections.push(range);
+            }
+        }
+        if compacted_sections.is_empty() {
+            return Vec::new();
+        }
+
+        let mut text = String::with_capacity(self.text.len());
+        let mut outputs = Vec::with_capacity(compacted_sections.len());
+        let mut offset = 0;
+        for range in &compacted_sections {
+            text.push_str(&self.text[offset..range.start]);
+            outputs.push(self.text[range.clone()].to_string());
+            offset = range.end;
+        }
+        text.push_str(&self.text[offset..]);
+
+        self.text = text;
+        self.compacted_sections = compacted_sections;
+        outputs
+    }
+
+    /// Puts the outputs returned by [`SavedContext::compact`] back into the
+    /// saved context.
+    pub fn restore(&mut self, outputs: Vec<String>) -> Result<()> {
+        anyhow::ensure!(
+            outputs.len() == self.compacted_sections.len(),
+            "expected {} compacted outputs, found {}",
+            self.compacted_sections.len(),
+            outputs.len()
+        );
+
+        let mut text =
+            String::with_capacity(self.text.len() + outputs.iter().map(String::len).sum::<usize>());
+        let mut offset = 0;
+        for (range, output) in self.compacted_sections.drain(..).zip(outputs) {
+            anyhow::ensure!(output.len() == range.len(), "compacted output was modified");
+            let start = (range.start + offset)
+                .checked_sub(text.len())
+                .context("compacted sections overlap")?;
+            let prefix = self
+                .text
+                .get(offset..start)
+                .context("compacted section is out of bounds")?;
+            text.push_str(prefix);
+            text.push_str(&output);
+            offset = start;
+        }
+        text.push_str(&self.text[offset..]);
+        self.text = text;
+        Ok(())
+    }
+}
+
+/// The disk space used by the saved text threads.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextThreadStorageUsage {
+    pub thread_count: usize,
+    pub total_bytes: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StoredContext {
+    pub path: PathBuf,
+    pub mtime: SystemTime,
+    /// The size of the saved context, including its compacted outputs.
+    pub len: u64,
+    /// Whether the saved context has no text besides whitespace.
+    pub is_empty: bool,
+}
+
+/// Returns the saved contexts to delete to satisfy the retention policy,
+/// starting with the least recently saved ones.
+pub(crate) fn contexts_to_delete(
+    mut contexts: Vec<StoredContext>,
+    retention: &TextThreadRetention,
+    now: SystemTime,
+) -> Vec<PathBuf> {
+    contexts.sort_by_key(|context| std::cmp::Reverse(context.mtime));
+
+    let max_age = retention
+        .max_age_days
+        .map(|days| Duration::from_secs(days * 24 * 60 * 60));
+    let max_bytes = retention
+        .max_disk_size_mb
+        .map(|megabytes| megabytes * 1024 * 1024);
+
+    let mut total_bytes = 0;
+    let mut to_delete = Vec::new();
+    for (ix, context) in contexts.into_iter().enumerate() {
+        total_bytes += context.len;
+        let too_many = retention.max_count.is_some_and(|max_count| ix >= max_count);
+        let too_old = max_age.is_some_and(|max_age| {
+            now.duration_since(context.mtime)
+                .is_ok_and(|age| age > max_age)
+        });
+        let too_large = max_bytes.is_some_and(|max_bytes| total_bytes > max_bytes);
+        let empty = retention.delete_empty && context.is_empty;
+        if too_many || too_old || too_large || empty {
+            total_bytes -= context.len;
+            to_delete.push(context.path);
+        }
+    }
+    to_delete
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssemblyStrategy;
+    use assistant_slash_command::SlashCommandOutputSection;
+    use ui::IconName;
+
+    #[test]
+    fn test_compact_and_restore() {
+        let text = "/file a\nfn a() {}\n/file b\nfn b() {}\nok\n";
+        let section = |range: Range<usize>| SlashCommandOutputSection {
+            range,
+            icon: IconName::File,
+            label: "file".into(),
+            metadata: None,
+        };
+        let mut saved_context = SavedContext {
+            id: None,
+            zed: "context".into(),
+            version: SavedContext::VERSION.into(),
+            text: text.into(),
+            messages: Vec::new(),
+            summary: String::new(),
+            slash_command_output_sections: vec![
+                section(0..18),
+                section(8..18),
+                section(18..36),
+                section(36..39),
+            ],
+            thought_process_output_sections: Vec::new(),
+            continuation_seams: Vec::new(),
+            compacted_sections: Vec::new(),
+            assembly_strategy: AssemblyStrategy::default(),
+            project: None,
+            model: None,
+            environment: Default::default(),
+            answer_language: None,
+            seed: None,
+            stop_conditions: Vec::new(),
+            post_processors: Vec::new(),
+            web_search: false,
+            cost_tracker: Default::default(),
+        };
+
+        let outputs = saved_context.compact(10);
+        assert_eq!(outputs, ["/file a\nfn a() {}\n", "/file b\nfn b() {}\n"]);
+        assert_eq!(saved_context.text, "ok\n");
+        assert_eq!(saved_context.compacted_sections, [0..18, 18..36]);
+
+        saved_context.restore(outputs).unwrap();
+        assert_eq!(saved_context.text, text);
+        assert!(saved_context.compacted_sections.is_empty());
+
+        assert!(saved_context.compact(100).is_empty());
+        assert_eq!(saved_context.text, text);
+    }
+
+    #[test]
+    fn test_contexts_to_delete() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100 * 24 * 60 * 60);
+        let day = Duration::from_secs(24 * 60 * 60);
+        let contexts = vec![
+            StoredContext {
+                path: "old".into(),
+                mtime: now - day * 40,
+                len: 1024 * 1024,
+                is_empty: false,
+            },
+            StoredContext {
+                path: "new".into(),
+                mtime: now - day,
+                len: 1024 * 1024,
+                is_empty: false,
+            },
+            StoredContext {
+                path: "recent".into(),
+                mtime: now - day * 2,
+                len: 1024 * 1024,
+                is_empty: false,
+            },
+        ];
+
+        let retention = |max_count, max_age_days, max_disk_size_mb| TextThreadRetention {
+            max_count,
+            max_age_days,
+            max_disk_size_mb,
+            delete_empty: false,
+            ..Default::default()
+        };
+        assert!(contexts_to_delete(contexts.clone(), &retention(None, None, None), now).is_empty());
+        assert_eq!(
+            contexts_to_delete(contexts.clone(), &retention(Some(1), None, None), now),
+            [PathBuf::from("recent"), PathBuf::from("old")]
+        );
+        assert_eq!(
+            contexts_to_delete(contexts.clone(), &retention(None, Some(30), None), now),
+            [PathBuf::from("old")]
+        );
+        assert_eq!(
+            contexts_to_delete(contexts.clone(), &retention(None, None, Some(2)), now),
+            [PathBuf::from("old")]
+        );
+
+        let mut contexts = contexts;
+        contexts[2].is_empty = true;
+        assert!(contexts_to_delete(contexts.clone(), &retention(None, None, None), now).is_empty());
+        assert_eq!(
+            contexts_to_delete(contexts, &TextThreadRetention::default(), now),
+            [PathBuf::from("recent")]
+        );
+    }
+}
@@ -1,28 +1,38 @@
+use crate::context_assembly::estimate_tokens;
+use crate::context_storage;
+use crate::context_sync;
+use crate::redaction;
+use crate::text_threads_database::TextThreadsDatabase;
 use crate::{
-    AssistantContext, ContextEvent, ContextId, ContextOperation, ContextVersion, SavedContext,
-    SavedContextMetadata,
+    AssistantContext, ContextEvent, ContextId, ContextOperation, ContextVersion, MessageId,
+    MessageUsage, SavedContext, SavedContextMetadata,
 };
-use anyhow::{Context as _, Result};
+use agent_settings::AgentSettings;
+use anyhow::{Context as _, Result, anyhow};
 use assistant_slash_command::{SlashCommandId, SlashCommandWorkingSet};
 use client::{Client, TypedEnvelope, proto, telemetry::Telemetry};
 use clock::ReplicaId;
-use collections::HashMap;
+use collections::{HashMap, HashSet};
 use context_server::ContextServerId;
-use fs::{Fs, RemoveOptions};
-use futures::StreamExt;
+use futures::{StreamExt, channel::oneshot};
 use fuzzy::StringMatchCandidate;
-use gpui::{App, AppContext as _, AsyncApp, Context, Entity, EventEmitter, Task, WeakEntity};
+use gpui::{
+    App, AppContext as _, AsyncApp, Context, Entity, EventEmitter, SharedString, Task, WeakEntity,
+};
 use language::LanguageRegistry;
-use paths::contexts_dir;
 use project::{
     Project,
     context_server_store::{ContextServerStatus, ContextServerStore},
 };
 use prompt_store::PromptBuilder;
-use regex::Regex;
 use rpc::AnyProtoClient;
-use std::sync::LazyLock;
-use std::{cmp::Reverse, ffi::OsStr, mem, path::Path, sync::Arc, time::Duration};
+use settings::Settings as _;
+use std::time::SystemTime;
+use std::{
+    mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use util::{ResultExt, TryFutureExt};
 
 pub(crate) fn init(client: &AnyProtoClient) {
@@ -31,6 +41,7 @@ pub(crate) fn init(client: &AnyProtoClient) {
     client.add_entity_request_handler(ContextStore::handle_create_context);
     client.add_entity_message_handler(ContextStore::handle_update_context);
     client.add_entity_request_handler(ContextStore::handle_synchronize_contexts);
+    client.add_entity_request_handler(ContextStore::handle_request_host_completion);
 }
 
 #[derive(Clone)]
@@ -42,9 +53,11 @@ pub struct RemoteContextMetadata {
 pub struct ContextStore {
     contexts: Vec<ContextHandle>,
     contexts_metadata: Vec<SavedContextMetadata>,
+    /// The text threads that were deleted, and can still be restored.
+    trashed_contexts_metadata: Vec<SavedContextMetadata>,
     context_server_slash_command_ids: HashMap<ContextServerId, Vec<SlashCommandId>>,
     host_contexts: Vec<RemoteContextMetadata>,
-    fs: Arc<dyn Fs>,
+    database: Arc<TextThreadsDatabase>,
     languages: Arc<LanguageRegistry>,
     slash_commands: Arc<SlashCommandWorkingSet>,
     telemetry: Arc<Telemetry>,
@@ -55,10 +68,72 @@ pub struct ContextStore {
     client_subscription: Option<client::Subscription>,
     _project_subscriptions: Vec<gpui::Subscription>,
     prompt_builder: Arc<PromptBuilder>,
+    guests: HashMap<u64, GuestModelAccess>,
+}
+
+/// Tracks a guest's use of the host's language model while the project is shared.
+#[derive(Default)]
+struct GuestModelAccess {
+    approved: Option<bool>,
+    pending_approvals: Vec<oneshot::Sender<bool>>,
+    /// The tokens used by the guest's completions that finished. They're
+    /// counted as each completion finishes, so that editing or deleting the
+    /// responses doesn't give them back.
+    tokens_used: u64,
+    /// The guest's completions that are still streaming.
+    pending_completions: Vec<PendingGuestCompletion>,
+}
+
+struct PendingGuestCompletion {
+    context_id: ContextId,
+    assistant_message_id: MessageId,
+    /// The tokens counted against the guest's budget until the completion
+    /// finishes and its usage is known.
+    reserved_tokens: u64,
+}
+
+impl GuestModelAccess {
+    /// The tokens counted against the guest's budget, including the ones
+    /// reserved for completions that haven't finished.
+    fn tokens_committed(&self) -> u64 {
+        self.tokens_used
+            + self
+                .pending_completions
+                .iter()
+                .map(|completion| completion.reserved_tokens)
+                .sum::<u64>()
+    }
+
+    /// Replaces the tokens reserved for a completion with the ones it used.
+    /// When its usage isn't known, such as when it was cancelled, the
+    /// reserved tokens are counted instead.
+    fn finish_completion(
+        &mut self,
+        context_id: &ContextId,
+        assistant_message_id: MessageId,
+        usage: Option<MessageUsage>,
+    ) {
+        let Some(ix) = self.pending_completions.iter().position(|completion| {
+            completion.context_id == *context_id
+                && completion.assistant_message_id == assistant_message_id
+        }) else {
+            return;
+        };
+        let completion = self.pending_completions.remove(ix);
+        self.tokens_used += usage.map_or(completion.reserved_tokens, |usage| {
+            usage.token_usage.total_tokens() as u64
+        });
+    }
 }
 
 pub enum ContextStoreEvent {
     ContextCreated(ContextId),
+    /// A guest asked to use the host's language model, and is waiting for the
+    /// host to respond with [`ContextStore::respond_to_guest_model_request`].
+    GuestModelRequested {
+        user_id: u64,
+        github_login: SharedString,
+    },
 }
 
 impl EventEmitter<ContextStoreEvent> for ContextStore {}
@@ -91,26 +166,27 @@ impl ContextStore {
         slash_commands: Arc<SlashCommandWorkingSet>,
         cx: &mut App,
     ) -> Task<Result<Entity<Self>>> {
-        let fs = project.read(cx).fs().clone();
+        let database = TextThreadsDatabase::global_future(cx);
         let languages = project.read(cx).languages().clone();
         let telemetry = project.read(cx).client().telemetry().clone();
         cx.spawn(async move |cx| {
-            const CONTEXT_WATCH_DURATION: Duration = Duration::from_millis(100);
-            let (mut events, _) = fs.watch(contexts_dir(), CONTEXT_WATCH_DURATION).await;
+            let database = database.await.map_err(|err| anyhow!(err))?;
+            let mut changes = database.subscribe();
 
             let this = cx.new(|cx: &mut Context<Self>| {
                 let mut this = Self {
                     contexts: Vec::new(),
                     contexts_metadata: Vec::new(),
+                    trashed_contexts_metadata: Vec::new(),
                     context_server_slash_command_ids: HashMap::default(),
                     host_contexts: Vec::new(),
-                    fs,
+                    database,
                     languages,
                     slash_commands,
                     telemetry,
                     _watch_updates: cx.spawn(async move |this, cx| {
                         async move {
-                            while events.next().await.is_some() {
+                            while changes.next().await.is_some() {
                                 this.update(cx, |this, cx| this.reload(cx))?.await.log_err();
                             }
                             anyhow::Ok(())
@@ -126,11 +202,13 @@ impl ContextStore {
                     client: project.read(cx).client(),
                     project: project.clone(),
                     prompt_builder,
+                    guests: HashMap::default(),
                 };
                 this.handle_project_shared(project.clone(), cx);
                 this.synchronize_contexts(cx);
                 this.register_context_server_handlers(cx);
                 this.reload(cx).detach_and_log_err(cx);
+                this.apply_retention_policy(cx).detach_and_log_err(cx);
                 this
             })?;
 
@@ -278,6 +356,131 @@ impl ContextStore {
         })?
     }
 
+    async fn handle_request_host_completion(
+        this: Entity<Self>,
+        envelope: TypedEnvelope<proto::RequestHostCompletion>,
+        mut cx: AsyncApp,
+    ) -> Result<proto::Ack> {
+        let sender_id = envelope.original_sender_id()?;
+        let context_id = ContextId::from_proto(envelope.payload.context_id);
+        let (user_id, approval) = this.update(&mut cx, |this, cx| {
+            anyhow::ensure!(
+                !this.project.read(cx).is_via_collab(),
+                "only the host can complete contexts for guests"
+            );
+            let settings = AgentSettings::get_global(cx);
+            anyhow::ensure!(
+                settings.allow_guest_model_requests,
+                "the host doesn't accept language model requests from guests"
+            );
+            let user_id = this
+                .project
+                .read(cx)
+                .collaborators()
+                .get(&sender_id)
+                .context("request from unknown guest")?
+                .user_id;
+            anyhow::ensure!(
+                this.guest_tokens_committed(user_id) < settings.guest_token_budget,
+                "you've used your token budget for the host's language model"
+            );
+            anyhow::Ok((user_id, this.guest_model_approval(user_id, cx)))
+        })??;
+
+        anyhow::ensure!(
+            approval.await.unwrap_or(false),
+            "the host declined to share their language model"
+        );
+
+        this.update(&mut cx, |this, cx| {
+            let context = this
+                .loaded_context_for_id(&context_id, cx)
+                .context("context not found")?;
+            // The tokens the completion will use aren't known until it
+            // finishes, so the tokens it's expected to send are reserved,
+            // which keeps concurrent requests within the budget.
+            let reserved_tokens = context.read(cx).token_count().map_or_else(
+                || estimate_tokens(&context.read(cx).buffer().read(cx).text()),
+                |token_count| token_count,
+            ) as u64;
+            anyhow::ensure!(
+                this.guest_tokens_committed(user_id) + reserved_tokens
+                    <= AgentSettings::get_global(cx).guest_token_budget,
+                "you've used your token budget for the host's language model"
+            );
+            let user_message = context
+                .update(cx, |context, cx| context.assist(cx))
+                .context("the host has no language model configured")?;
+            let assistant_message_id = context
+                .read(cx)
+                .messages(cx)
+                .take_while(|message| message.id != user_message.id)
+                .last()
+                .map(|message| message.id)
+                .context("no assistant message was inserted")?;
+            this.guests
+                .entry(user_id)
+                .or_default()
+                .pending_completions
+                .push(PendingGuestCompletion {
+                    context_id,
+                    assistant_message_id,
+                    reserved_tokens,
+                });
+            anyhow::Ok(proto::Ack {})
+        })?
+    }
+
+    /// The number of tokens a guest has used or reserved through the host's
+    /// language model.
+    fn guest_tokens_committed(&self, user_id: u64) -> u64 {
+        self.guests
+            .get(&user_id)
+            .map_or(0, GuestModelAccess::tokens_committed)
+    }
+
+    /// Resolves to whether the host lets the guest use their language model,
+    /// asking the host the first time.
+    fn guest_model_approval(
+        &mut self,
+        user_id: u64,
+        cx: &mut Context<Self>,
+    ) -> oneshot::Receiver<bool> {
+        let (tx, rx) = oneshot::channel();
+        let guest = self.guests.entry(user_id).or_default();
+        if let Some(approved) = guest.approved {
+            tx.send(approved).ok();
+            return rx;
+        }
+
+        guest.pending_approvals.push(tx);
+        if guest.pending_approvals.len() == 1 {
+            let github_login = self
+                .project
+                .read(cx)
+                .user_store()
+                .read(cx)
+                .get_cached_user(user_id)
+                .map(|user| user.github_login.clone().into())
+                .unwrap_or_else(|| "A guest".into());
+            cx.emit(ContextStoreEvent::GuestModelRequested {
+                user_id,
+                github_login,
+            });
+        }
+        rx
+    }
+
+    /// Approves or declines a guest's use of the host's language model for
+    /// the rest of the collaboration session.
+    pub fn respond_to_guest_model_request(&mut self, user_id: u64, approved: bool) {
+        let guest = self.guests.entry(user_id).or_default();
+        guest.approved = Some(approved);
+        for tx in guest.pending_approvals.drain(..) {
+            tx.send(approved).ok();
+        }
+    }
+
     fn handle_project_shared(&mut self, _: Entity<Project>, cx: &mut Context<Self>) {
         let is_shared = self.project.read(cx).is_shared();
         let was_shared = mem::replace(&mut self.project_is_shared, is_shared);
@@ -303,6 +506,7 @@ impl ContextStore {
             self.advertise_contexts(cx);
         } else {
             self.client_subscription = None;
+            self.guests.clear();
         }
     }
 
@@ -347,6 +551,10 @@ impl ContextStore {
         self.contexts_metadata.iter()
     }
 
+    pub fn trashed_contexts(&self) -> &[SavedContextMetadata] {
+        &self.trashed_contexts_metadata
+    }
+
     pub fn reverse_chronological_contexts(&self) -> Vec<SavedContextMetadata> {
         let mut contexts = self.contexts_metadata.iter().cloned().collect::<Vec<_>>();
         contexts.sort_unstable_by_key(|thread| std::cmp::Reverse(thread.mtime));
@@ -427,30 +635,49 @@ impl ContextStore {
     pub fn open_local_context(
         &mut self,
         path: Arc<Path>,
-        cx: &Context<Self>,
+        cx: &mut Context<Self>,
     ) -> Task<Result<Entity<AssistantContext>>> {
         if let Some(existing_context) = self.loaded_context_for_path(&path, cx) {
             return Task::ready(Ok(existing_context));
         }
 
-        let fs = self.fs.clone();
+        // When the context is already open in another window, open a replica
+        // of it instead of loading it again, so that the two don't overwrite
+        // each other's changes when they're saved.
+        if let Some(source) = context_sync::find_replica(&path, cx) {
+            let open_replica = context_sync::open_replica(
+                &source,
+                self.languages.clone(),
+                self.prompt_builder.clone(),
+                self.slash_commands.clone(),
+                Some(self.project.clone()),
+                Some(self.telemetry.clone()),
+                cx,
+            );
+            return cx.spawn(async move |this, cx| {
+                let context = open_replica.await?;
+                this.update(cx, |this, cx| {
+                    if let Some(existing_context) = this.loaded_context_for_path(&path, cx) {
+                        existing_context
+                    } else {
+                        this.register_context(&context, cx);
+                        context
+                    }
+                })
+            });
+        }
+
         let languages = self.languages.clone();
         let project = self.project.clone();
         let telemetry = self.telemetry.clone();
-        let load = cx.background_spawn({
-            let path = path.clone();
-            async move {
-                let saved_context = fs.load(&path).await?;
-                SavedContext::from_json(&saved_context)
-            }
-        });
+        let load = self.database.load(path.clone());
         let prompt_builder = self.prompt_builder.clone();
         let slash_commands = self.slash_commands.clone();
 
         cx.spawn(async move |this, cx| {
             let saved_context = load.await?;
             let context = cx.new(|cx| {
-                AssistantContext::deserialize(
+                AssistantContext::deserialize_progressively(
                     saved_context,
                     path.clone(),
                     languages,
@@ -477,17 +704,10 @@ impl ContextStore {
         path: Arc<Path>,
         cx: &mut Context<Self>,
     ) -> Task<Result<()>> {
-        let fs = self.fs.clone();
+        let delete = self.database.delete(path.clone());
 
         cx.spawn(async move |this, cx| {
-            fs.remove_file(
-                &path,
-                RemoveOptions {
-                    recursive: false,
-                    ignore_if_not_exists: true,
-                },
-            )
-            .await?;
+            delete.await?;
 
             this.update(cx, |this, cx| {
                 this.contexts.retain(|context| {
@@ -504,6 +724,198 @@ impl ContextStore {
         })
     }
 
+    /// Moves the saved text threads at the given paths to the trash, where
+    /// they're kept for the number of days in the retention settings.
+    pub fn trash_local_contexts(
+        &mut self,
+        paths: Vec<Arc<Path>>,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        self.contexts.retain(|context| {
+            context
+                .upgrade()
+                .and_then(|context| context.read(cx).path().cloned())
+                .is_none_or(|path| !paths.contains(&path))
+        });
+        let (trashed, kept) = mem::take(&mut self.contexts_metadata)
+            .into_iter()
+            .partition::<Vec<_>, _>(|metadata| paths.contains(&metadata.path));
+        self.contexts_metadata = kept;
+        self.trashed_contexts_metadata.extend(trashed);
+        cx.notify();
+        self.database.trash(paths)
+    }
+
+    pub fn restore_local_contexts(
+        &mut self,
+        paths: Vec<Arc<Path>>,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        let (restored, trashed) = mem::take(&mut self.trashed_contexts_metadata)
+            .into_iter()
+            .partition::<Vec<_>, _>(|metadata| paths.contains(&metadata.path));
+        self.trashed_contexts_metadata = trashed;
+        self.contexts_metadata.extend(restored);
+        cx.notify();
+        self.database.restore(paths)
+    }
+
+    /// Saves a new text thread made of the saved text threads at the given
+    /// paths, in the order they're given, and returns its path.
+    pub fn merge_local_contexts(
+        &mut self,
+        paths: Vec<Arc<Path>>,
+        title: String,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Arc<Path>>> {
+        let loads = paths
+            .iter()
+            .map(|path| self.database.load(path.clone()))
+            .collect::<Vec<_>>();
+        let database = self.database.clone();
+        cx.background_spawn(async move {
+            let mut contexts = Vec::new();
+            for load in loads {
+                contexts.push(load.await?);
+            }
+            let mut merged = SavedContext::merge(contexts);
+            merged.summary = title.clone();
+            database.save(None, title, merged, None).await
+        })
+    }
+
+    /// Adds the tags to the saved text threads at the given paths, keeping
+    /// the tags they already have.
+    pub fn add_tags(
+        &mut self,
+        paths: Vec<Arc<Path>>,
+        tags: Vec<String>,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        let updates = paths
+            .into_iter()
+            .filter_map(|path| {
+                let metadata = self
+                    .contexts_metadata
+                    .iter()
+                    .find(|metadata| metadata.path == path)?;
+                let mut new_tags = metadata.tags.clone();
+                for tag in &tags {
+                    if !new_tags
+                        .iter()
+                        .any(|existing| existing.eq_ignore_ascii_case(tag))
+                    {
+                        new_tags.push(tag.clone());
+                    }
+                }
+                Some((path, new_tags))
+            })
+            .collect::<Vec<_>>();
+        let tasks = updates
+            .into_iter()
+            .map(|(path, tags)| self.set_tags(path, tags, cx))
+            .collect::<Vec<_>>();
+        cx.background_spawn(async move {
+            for task in tasks {
+                task.await?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Archives or unarchives the saved text threads at the given paths.
+    pub fn set_archived(
+        &mut self,
+        paths: Vec<Arc<Path>>,
+        archived: bool,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        for metadata in &mut self.contexts_metadata {
+            if paths.contains(&metadata.path) {
+                metadata.archived = archived;
+            }
+        }
+        cx.notify();
+        self.database.set_archived(paths, archived)
+    }
+
+    /// Writes the saved text threads at the given paths to the directory, in
+    /// the format text threads were saved in before they were stored in the
+    /// database, with the secrets they contain masked.
+    pub fn export_local_contexts(
+        &self,
+        paths: Vec<Arc<Path>>,
+        dir: PathBuf,
+        cx: &App,
+    ) -> Task<Result<()>> {
+        let loads = paths
+            .iter()
+            .map(|path| self.database.load(path.clone()))
+            .collect::<Vec<_>>();
+        cx.background_spawn(async move {
+            for (path, load) in paths.iter().zip(loads) {
+                let mut saved_context = load.await?;
+                let redactions = redaction::find_secrets(
+                    &saved_context.text,
+                    saved_context.environment.values().map(String::as_str),
+                )
+                .into_iter()
+                .map(|redaction| redaction.range)
+                .collect::<Vec<_>>();
+                saved_context.text =
+                    redaction::mask(&saved_context.text, 0, &redactions).into_owned();
+                for value in saved_context.environment.values_mut() {
+                    *value = "*".repeat(value.len());
+                }
+
+                let file_name = path
+                    .file_name()
+                    .with_context(|| format!("invalid text thread path {path:?}"))?;
+                std::fs::write(
+                    dir.join(file_name),
+                    serde_json::to_string_pretty(&saved_context)?,
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Deletes the saved text threads that exceed the retention policy in the
+    /// settings, except for the ones that are open.
+    fn apply_retention_policy(&self, cx: &mut Context<Self>) -> Task<Result<()>> {
+        let retention = AgentSettings::get_global(cx).text_thread_retention.clone();
+        let trash_cutoff = chrono::Utc::now()
+            - chrono::TimeDelta::days(retention.trash_retention_days.min(36500) as i64);
+        self.database
+            .empty_trash(trash_cutoff)
+            .detach_and_log_err(cx);
+        if retention.max_count.is_none()
+            && retention.max_age_days.is_none()
+            && retention.max_disk_size_mb.is_none()
+            && !retention.delete_empty
+        {
+            return Task::ready(Ok(()));
+        }
+
+        let open_paths = self
+            .contexts
+            .iter()
+            .filter_map(|context| context.upgrade()?.read(cx).path().cloned())
+            .collect::<HashSet<_>>();
+        let database = self.database.clone();
+        cx.background_spawn(async move {
+            let stored_contexts = database.stored_contexts().await?;
+            for path in
+                context_storage::contexts_to_delete(stored_contexts, &retention, SystemTime::now())
+            {
+                if !open_paths.contains(path.as_path()) {
+                    database.delete(path.into()).await?;
+                }
+            }
+            Ok(())
+        })
+    }
+
     fn loaded_context_for_path(&self, path: &Path, cx: &App) -> Option<Entity<AssistantContext>> {
         self.contexts.iter().find_map(|context| {
             let context = context.upgrade()?;
@@ -602,6 +1014,9 @@ impl ContextStore {
         self.contexts.push(handle);
         self.advertise_contexts(cx);
         cx.subscribe(context, Self::handle_context_event).detach();
+        if !self.project.read(cx).is_via_collab() {
+            context_sync::register(context, cx);
+        }
     }
 
     fn handle_context_event(
@@ -610,6 +1025,17 @@ impl ContextStore {
         event: &ContextEvent,
         cx: &mut Context<Self>,
     ) {
+        if let ContextEvent::CompletionFinished {
+            assistant_message_id,
+            usage,
+        } = event
+        {
+            let context_id = context.read(cx).id();
+            for guest in self.guests.values_mut() {
+                guest.finish_completion(context_id, *assistant_message_id, *usage);
+            }
+        }
+
         let Some(project_id) = self.project.read(cx).remote_id() else {
             return;
         };
@@ -725,84 +1151,111 @@ impl ContextStore {
         .detach_and_log_err(cx);
     }
 
+    /// Searches the saved text threads by title and content. Words starting
+    /// with `#` only keep the text threads that have that tag.
     pub fn search(&self, query: String, cx: &App) -> Task<Vec<SavedContextMetadata>> {
-        let metadata = self.contexts_metadata.clone();
+        let (tags, words): (Vec<&str>, Vec<&str>) = query
+            .split_whitespace()
+            .partition(|word| word.len() > 1 && word.starts_with('#'));
+        let tags = tags
+            .into_iter()
+            .map(|tag| tag[1..].to_lowercase())
+            .collect::<Vec<_>>();
+        let query = words.join(" ");
+
+        let metadata = self
+            .contexts_metadata
+            .iter()
+            .filter(|metadata| {
+                tags.iter().all(|tag| {
+                    metadata
+                        .tags
+                        .iter()
+                        .any(|candidate| candidate.to_lowercase() == *tag)
+                })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let full_text_matches = if query.is_empty() {
+            Task::ready(Ok(Vec::new()))
+        } else {
+            self.database.search(query.clone())
+        };
         let executor = cx.background_executor().clone();
         cx.background_spawn(async move {
             if query.is_empty() {
-                metadata
-            } else {
-                let candidates = metadata
-                    .iter()
-                    .enumerate()
-                    .map(|(id, metadata)| StringMatchCandidate::new(id, &metadata.title))
-                    .collect::<Vec<_>>();
-                let matches = fuzzy::match_strings(
-                    &candidates,
-                    &query,
-                    false,
-                    100,
-                    &Default::default(),
-                    executor,
-                )
-                .await;
+                return metadata;
+            }
 
-                matches
-                    .into_iter()
-                    .map(|mat| metadata[mat.candidate_id].clone())
-                    .collect()
+            let candidates = metadata
+                .iter()
+                .enumerate()
+                .map(|(id, metadata)| StringMatchCandidate::new(id, &metadata.title))
+                .collect::<Vec<_>>();
+            let matches = fuzzy::match_strings(
+                &candidates,
+                &query,
+                false,
+                100,
+                &Default::default(),
+                executor,
+            )
+            .await;
+
+            // Title matches come first, followed by the text threads whose
+            // content matches the query.
+            let mut results = matches
+                .into_iter()
+                .map(|mat| metadata[mat.candidate_id].clone())
+                .collect::<Vec<_>>();
+            for path in full_text_matches.await.log_err().unwrap_or_default() {
+                if results.iter().any(|result| result.path == path) {
+                    continue;
+                }
+                if let Some(metadata) = metadata.iter().find(|metadata| metadata.path == path) {
+                    results.push(metadata.clone());
+                }
             }
+            results
         })
     }
 
+    /// Replaces the tags of the saved text thread at the given path.
+    pub fn set_tags(
+        &mut self,
+        path: Arc<Path>,
+        tags: Vec<String>,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<()>> {
+        let tags = tags
+            .into_iter()
+            .map(|tag| tag.trim().trim_start_matches('#').to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect::<Vec<_>>();
+        if let Some(metadata) = self
+            .contexts_metadata
+            .iter_mut()
+            .find(|metadata| metadata.path == path)
+        {
+            metadata.tags = tags.clone();
+            cx.notify();
+        }
+        self.database.set_tags(path, tags)
+    }
+
     pub fn host_contexts(&self) -> &[RemoteContextMetadata] {
         &self.host_contexts
     }
 
     fn reload(&mut self, cx: &mut Context<Self>) -> Task<Result<()>> {
-        let fs = self.fs.clone();
+        let list = self.database.list();
+        let list_trash = self.database.list_trash();
         cx.spawn(async move |this, cx| {
-            fs.create_dir(contexts_dir()).await?;
-
-            let mut paths = fs.read_dir(contexts_dir()).await?;
-            let mut contexts = Vec::<SavedContextMetadata>::new();
-            while let Some(path) = paths.next().await {
-                let path = path?;
-                if path.extension() != Some(OsStr::new("json")) {
-                    continue;
-                }
-
-                static ASSISTANT_CONTEXT_REGEX: LazyLock<Regex> =
-                    LazyLock::new(|| Regex::new(r" - \d+.zed.json$").unwrap());
-
-                let metadata = fs.metadata(&path).await?;
-                if let Some((file_name, metadata)) = path
-                    .file_name()
-                    .and_then(|name| name.to_str())
-                    .zip(metadata)
-                {
-                    // This is used to filter out contexts saved by the new assistant.
-                    if !ASSISTANT_CONTEXT_REGEX.is_match(file_name) {
-                        continue;
-                    }
-
-                    if let Some(title) = ASSISTANT_CONTEXT_REGEX
-                        .replace(file_name, "")
-                        .lines()
-                        .next()
-                    {
-                        contexts.push(SavedContextMetadata {
-                            title: title.to_string(),
-                            path: path.into(),
-                            mtime: metadata.mtime.timestamp_for_user().into(),
-                        });
-                    }
-                }
-            }
-            contexts.sort_unstable_by_key(|context| Reverse(context.mtime));
-
+            let contexts = list.await?;
+            let trashed_contexts = list_trash.await?;
             this.update(cx, |this, cx| {
                 this.contexts_metadata = contexts;
+                this.trashed_contexts_metadata = trashed_contexts;
                 cx.notify();
             })
         })
@@ -896,3 +1349,54 @@ impl ContextStore {
         .detach();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use language_model::TokenUsage;
+
+    fn usage(total_tokens: u32) -> MessageUsage {
+        MessageUsage {
+            token_usage: TokenUsage {
+                input_tokens: total_tokens,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_guest_token_accounting() {
+        let context_id = ContextId::new();
+        let mut clock = clock::Lamport::new(ReplicaId::default());
+        let first_message = MessageId(clock.tick());
+        let second_message = MessageId(clock.tick());
+        let mut guest = GuestModelAccess::default();
+
+        // Concurrent completions each reserve their tokens while they stream.
+        for assistant_message_id in [first_message, second_message] {
+            guest.pending_completions.push(PendingGuestCompletion {
+                context_id: context_id.clone(),
+                assistant_message_id,
+                reserved_tokens: 1_000,
+            });
+        }
+        assert_eq!(guest.tokens_committed(), 2_000);
+
+        // Finished completions count the tokens they used instead.
+        guest.finish_completion(&context_id, first_message, Some(usage(1_500)));
+        assert_eq!(guest.tokens_used, 1_500);
+        assert_eq!(guest.tokens_committed(), 2_500);
+
+        // Cancelled completions count the tokens that were reserved.
+        guest.finish_completion(&context_id, second_message, None);
+        assert_eq!(guest.tokens_used, 2_500);
+        assert!(guest.pending_completions.is_empty());
+
+        // Completions that weren't requested by the guest are ignored, and
+        // finished ones aren't counted twice.
+        guest.finish_completion(&ContextId::new(), first_message, Some(usage(500)));
+        guest.finish_completion(&context_id, first_message, Some(usage(500)));
+        assert_eq!(guest.tokens_committed(), 2_500);
+    }
+}
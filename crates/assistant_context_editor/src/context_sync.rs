@@ -0,0 +1,147 @@
+use crate::{AssistantContext, ContextEvent, ContextId, ContextOperation, ContextVersion};
+use anyhow::Result;
+use assistant_slash_command::SlashCommandWorkingSet;
+use client::telemetry::Telemetry;
+use clock::ReplicaId;
+use collections::HashMap;
+use gpui::{App, AppContext as _, Entity, Global, Subscription, Task, WeakEntity};
+use language::LanguageRegistry;
+use project::Project;
+use prompt_store::PromptBuilder;
+use std::path::Path;
+use std::sync::Arc;
+use util::post_inc;
+
+/// Replicas opened in other windows get ids from a range of their own, so that
+/// they don't collide with the replica ids of collaborators.
+const FIRST_LOCAL_REPLICA_ID: ReplicaId = 256;
+
+/// Keeps the replicas of a text thread that's open in several windows in sync,
+/// by relaying the operations of each replica to the others, the same way the
+/// collab server does for the replicas of a shared project.
+#[derive(Default)]
+struct LocalContextReplicas {
+    replicas: HashMap<ContextId, Vec<LocalReplica>>,
+    next_replica_id: ReplicaId,
+}
+
+struct LocalReplica {
+    context: WeakEntity<AssistantContext>,
+    _subscription: Subscription,
+}
+
+impl Global for LocalContextReplicas {}
+
+/// Whether the replica is one that was opened in another window of this
+/// process, as opposed to one opened by a collaborator.
+pub(crate) fn is_local_replica(replica_id: ReplicaId) -> bool {
+    replica_id >= FIRST_LOCAL_REPLICA_ID
+}
+
+/// Registers a context that's open locally, so that its operations are relayed
+/// to the replicas of it that are open in other windows.
+pub(crate) fn register(context: &Entity<AssistantContext>, cx: &mut App) {
+    let context_id = context.read(cx).id().clone();
+    let subscription = cx.subscribe(context, |context, event: &ContextEvent, cx| {
+        if let ContextEvent::Operation(operation) = event {
+            relay(&context, operation, cx);
+        }
+    });
+
+    let replicas = cx
+        .default_global::<LocalContextReplicas>()
+        .replicas
+        .entry(context_id)
+        .or_default();
+    replicas.retain(|replica| replica.context.upgrade().is_some());
+    if replicas
+        .iter()
+        .all(|replica| replica.context != context.downgrade())
+    {
+        replicas.push(LocalReplica {
+            context: context.downgrade(),
+            _subscription: subscription,
+        });
+    }
+}
+
+fn relay(source: &Entity<AssistantContext>, operation: &ContextOperation, cx: &mut App) {
+    let Some(replicas) = cx.try_global::<LocalContextReplicas>() else {
+        return;
+    };
+    let Some(replicas) = replicas.replicas.get(source.read(cx).id()) else {
+        return;
+    };
+    let targets = replicas
+        .iter()
+        .filter_map(|replica| replica.context.upgrade())
+        .filter(|context| context != source)
+        .collect::<Vec<_>>();
+    for target in targets {
+        target.update(cx, |context, cx| {
+            context.apply_ops([operation.clone()], cx);
+        });
+    }
+}
+
+/// Returns a context that's open locally and was saved at the given path, if
+/// there is one.
+pub(crate) fn find_replica(path: &Path, cx: &App) -> Option<Entity<AssistantContext>> {
+    cx.try_global::<LocalContextReplicas>()?
+        .replicas
+        .values()
+        .flatten()
+        .filter_map(|replica| replica.context.upgrade())
+        .find(|context| context.read(cx).path().map(Arc::as_ref) == Some(path))
+}
+
+/// Opens a new replica of the given context, which stays in sync with it.
+pub(crate) fn open_replica(
+    source: &Entity<AssistantContext>,
+    language_registry: Arc<LanguageRegistry>,
+    prompt_builder: Arc<PromptBuilder>,
+    slash_commands: Arc<SlashCommandWorkingSet>,
+    project: Option<Entity<Project>>,
+    telemetry: Option<Arc<Telemetry>>,
+    cx: &mut App,
+) -> Task<Result<Entity<AssistantContext>>> {
+    let (context_id, path) = {
+        let source = source.read(cx);
+        (source.id().clone(), source.path().cloned())
+    };
+    let replicas = cx.default_global::<LocalContextReplicas>();
+    let replica_id = FIRST_LOCAL_REPLICA_ID + post_inc(&mut replicas.next_replica_id);
+    let context = cx.new(|cx| {
+        let mut context = AssistantContext::new(
+            context_id,
+            replica_id,
+            language::Capability::ReadWrite,
+            language_registry,
+            prompt_builder,
+            slash_commands,
+            project,
+            telemetry,
+            cx,
+        );
+        context.set_path(path);
+        context
+    });
+
+    // Registering the replica before the source's operations are applied is
+    // fine, because operations that arrive early are deferred until the ones
+    // they depend on have been applied.
+    let operations = source
+        .read(cx)
+        .serialize_ops(&ContextVersion::default(), cx);
+    register(&context, cx);
+
+    cx.spawn(async move |cx| {
+        let operations = operations
+            .await
+            .into_iter()
+            .map(ContextOperation::from_proto)
+            .collect::<Result<Vec<_>>>()?;
+        context.update(cx, |context, cx| context.apply_ops(operations, cx))?;
+        Ok(context)
+    })
+}
@@ -0,0 +1,127 @@
+//! Keeps a running total of the tokens a text thread used and what they cost,
+//! so that its cost is still known after responses are regenerated or deleted.
+
+use language_model::{LanguageModelPricing, TokenUsage};
+use serde::{Deserialize, Serialize};
+
+use crate::MessageUsage;
+
+/// The tokens used by every response generated in a text thread, and their
+/// cost based on the pricing of the model that generated each response.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostTracker {
+    /// All input tokens, whether they were read from or written to the cache.
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Cost in US dollars of the tokens used with models whose pricing is
+    /// known.
+    pub cost: f64,
+    /// The tokens used with models whose pricing isn't known, which aren't
+    /// included in the cost.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub unpriced_tokens: u64,
+}
+
+fn is_zero(tokens: &u64) -> bool {
+    *tokens == 0
+}
+
+impl CostTracker {
+    /// Totals the usage of the messages of a text thread that was saved
+    /// before its costs were tracked.
+    pub fn from_message_usages(usages: impl IntoIterator<Item = MessageUsage>) -> Self {
+        let mut tracker = Self::default();
+        for usage in usages {
+            tracker.add(&usage.token_usage, usage.cost);
+        }
+        tracker
+    }
+
+    /// Adds the tokens a response used, pricing them with the pricing of the
+    /// model that generated it.
+    pub fn record(&mut self, usage: &TokenUsage, pricing: Option<LanguageModelPricing>) {
+        self.add(usage, pricing.map(|pricing| pricing.cost(usage)));
+    }
+
+    fn add(&mut self, usage: &TokenUsage, cost: Option<f64>) {
+        let input_tokens = u64::from(usage.input_tokens)
+            + u64::from(usage.cache_creation_input_tokens)
+            + u64::from(usage.cache_read_input_tokens);
+        let output_tokens = u64::from(usage.output_tokens);
+        self.input_tokens += input_tokens;
+        self.output_tokens += output_tokens;
+        match cost {
+            Some(cost) => self.cost += cost,
+            None => self.unpriced_tokens += input_tokens + output_tokens,
+        }
+    }
+
+    /// Adds the totals of another text thread, such as one merged into this
+    /// one.
+    pub fn merge(&mut self, other: &Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cost += other.cost;
+        self.unpriced_tokens += other.unpriced_tokens;
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total_tokens() == 0
+    }
+
+    /// Whether any of the tokens were used with a model whose pricing is
+    /// known, so that the cost is worth showing.
+    pub fn is_priced(&self) -> bool {
+        self.unpriced_tokens < self.total_tokens()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_usage_with_and_without_pricing() {
+        let pricing = LanguageModelPricing {
+            input_cost_per_million_tokens: 3.,
+            output_cost_per_million_tokens: 15.,
+        };
+        let mut tracker = CostTracker::default();
+        assert!(tracker.is_empty());
+        assert!(!tracker.is_priced());
+
+        tracker.record(
+            &TokenUsage {
+                input_tokens: 600_000,
+                output_tokens: 100_000,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 400_000,
+            },
+            Some(pricing),
+        );
+        tracker.record(
+            &TokenUsage {
+                input_tokens: 1_000,
+                output_tokens: 500,
+                ..Default::default()
+            },
+            None,
+        );
+
+        assert_eq!(tracker.input_tokens, 1_001_000);
+        assert_eq!(tracker.output_tokens, 100_500);
+        assert_eq!(tracker.unpriced_tokens, 1_500);
+        assert!((tracker.cost - 4.5).abs() < 1e-9);
+        assert!(tracker.is_priced());
+
+        let mut merged = CostTracker::default();
+        merged.merge(&tracker);
+        merged.merge(&tracker);
+        assert_eq!(merged.total_tokens(), 2 * tracker.total_tokens());
+        assert!((merged.cost - 9.).abs() < 1e-9);
+    }
+}
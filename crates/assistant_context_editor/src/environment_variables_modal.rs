@@ -0,0 +1,212 @@
+use gpui::{DismissEvent, Entity, EventEmitter, FocusHandle, Focusable, Subscription, prelude::*};
+use ui::{KeyBinding, ListItem, Modal, ModalFooter, ModalHeader, Section, Tooltip, prelude::*};
+use ui_input::SingleLineInput;
+use workspace::ModalView;
+
+use crate::AssistantContext;
+use crate::context_environment::is_valid_variable_name;
+
+/// Edits the environment variables that a text thread passes to the slash
+/// commands it runs.
+pub struct EnvironmentVariablesModal {
+    context: Entity<AssistantContext>,
+    name_editor: Entity<SingleLineInput>,
+    value_editor: Entity<SingleLineInput>,
+    error: Option<SharedString>,
+    _subscription: Subscription,
+}
+
+impl EnvironmentVariablesModal {
+    pub fn new(
+        context: Entity<AssistantContext>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let name_editor =
+            cx.new(|cx| SingleLineInput::new(window, cx, "STAGING_URL").label("Name"));
+        let value_editor = cx.new(|cx| {
+            SingleLineInput::new(window, cx, "https://staging.example.com").label("Value")
+        });
+        let subscription = cx.observe(&context, |_, _, cx| cx.notify());
+
+        Self {
+            context,
+            name_editor,
+            value_editor,
+            error: None,
+            _subscription: subscription,
+        }
+    }
+
+    fn confirm(&mut self, _: &menu::Confirm, window: &mut Window, cx: &mut Context<Self>) {
+        let name = self
+            .name_editor
+            .read(cx)
+            .editor()
+            .read(cx)
+            .text(cx)
+            .trim()
+            .to_string();
+        if name.is_empty() {
+            return;
+        }
+        if !is_valid_variable_name(&name) {
+            self.error = Some(
+                "Names can only contain letters, digits and underscores, \
+                and can't start with a digit."
+                    .into(),
+            );
+            cx.notify();
+            return;
+        }
+        let value = self.value_editor.read(cx).editor().read(cx).text(cx);
+
+        self.context.update(cx, |context, cx| {
+            context.set_environment_variable(name, value, cx)
+        });
+        self.error = None;
+        for input in [&self.name_editor, &self.value_editor] {
+            input
+                .read(cx)
+                .editor()
+                .clone()
+                .update(cx, |editor, cx| editor.clear(window, cx));
+        }
+        self.name_editor.focus_handle(cx).focus(window);
+    }
+
+    fn cancel(&mut self, _: &menu::Cancel, cx: &mut Context<Self>) {
+        cx.emit(DismissEvent);
+    }
+
+    fn render_variables(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let environment = self.context.read(cx).environment().clone();
+        if environment.is_empty() {
+            return v_flex().child(
+                Label::new("No environment variables")
+                    .size(LabelSize::Small)
+                    .color(Color::Muted),
+            );
+        }
+
+        v_flex().children(
+            environment
+                .into_iter()
+                .enumerate()
+                .map(|(ix, (name, value))| {
+                    ListItem::new(("environment-variable", ix))
+                        .child(
+                            h_flex()
+                                .gap_1()
+                                .overflow_x_hidden()
+                                .child(Label::new(name.clone()).size(LabelSize::Small))
+                                .child(Label::new("=").size(LabelSize::Small).color(Color::Muted))
+                                .child(
+                                    Label::new(value)
+                                        .size(LabelSize::Small)
+                                        .color(Color::Muted)
+                                        .truncate(),
+                                ),
+                        )
+                        .end_slot(
+                            IconButton::new(("remove-environment-variable", ix), IconName::Trash)
+                                .icon_size(IconSize::Small)
+                                .icon_color(Color::Muted)
+                                .tooltip(Tooltip::text("Remove"))
+                                .on_click(cx.listener(move |this, _, _, cx| {
+                                    this.context.update(cx, |context, cx| {
+                                        context.remove_environment_variable(&name, cx)
+                                    });
+                                })),
+                        )
+                }),
+        )
+    }
+}
+
+impl ModalView for EnvironmentVariablesModal {}
+
+impl Focusable for EnvironmentVariablesModal {
+    fn focus_handle(&self, cx: &App) -> FocusHandle {
+        self.name_editor.focus_handle(cx).clone()
+    }
+}
+
+impl EventEmitter<DismissEvent> for EnvironmentVariablesModal {}
+
+impl Render for EnvironmentVariablesModal {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let is_empty = self.name_editor.read(cx).is_empty(cx);
+        let focus_handle = self.focus_handle(cx);
+
+        div()
+            .elevation_3(cx)
+            .w(rems(34.))
+            .key_context("EnvironmentVariablesModal")
+            .on_action(
+                cx.listener(|this, _: &menu::Cancel, _window, cx| this.cancel(&menu::Cancel, cx)),
+            )
+            .on_action(cx.listener(|this, _: &menu::Confirm, window, cx| {
+                this.confirm(&menu::Confirm, window, cx)
+            }))
+            .on_mouse_down_out(cx.listener(|_this, _, _, cx| cx.emit(DismissEvent)))
+            .child(
+                Modal::new("environment-variables", None)
+                    .header(ModalHeader::new().headline("Environment Variables"))
+                    .section(
+                        Section::new()
+                            .meta("Passed to the slash commands run from this text thread.")
+                            .child(self.render_variables(cx)),
+                    )
+                    .section(
+                        Section::new()
+                            .child(
+                                h_flex()
+                                    .gap_2()
+                                    .child(div().flex_1().child(self.name_editor.clone()))
+                                    .child(div().flex_1().child(self.value_editor.clone())),
+                            )
+                            .children(self.error.clone().map(|error| {
+                                Label::new(error).size(LabelSize::Small).color(Color::Error)
+                            })),
+                    )
+                    .footer(
+                        ModalFooter::new().end_slot(
+                            h_flex()
+                                .gap_2()
+                                .child(
+                                    Button::new("done", "Done")
+                                        .key_binding(
+                                            KeyBinding::for_action_in(
+                                                &menu::Cancel,
+                                                &focus_handle,
+                                                window,
+                                                cx,
+                                            )
+                                            .map(|kb| kb.size(rems_from_px(12.))),
+                                        )
+                                        .on_click(cx.listener(|this, _event, _window, cx| {
+                                            this.cancel(&menu::Cancel, cx)
+                                        })),
+                                )
+                                .child(
+                                    Button::new("set-variable", "Set Variable")
+                                        .disabled(is_empty)
+                                        .key_binding(
+                                            KeyBinding::for_action_in(
+                                                &menu::Confirm,
+                                                &focus_handle,
+                                                window,
+                                                cx,
+                                            )
+                                            .map(|kb| kb.size(rems_from_px(12.))),
+                                        )
+                                        .on_click(cx.listener(|this, _event, window, cx| {
+                                            this.confirm(&menu::Confirm, window, cx)
+                                        })),
+                                ),
+                        ),
+                    ),
+            )
+    }
+}
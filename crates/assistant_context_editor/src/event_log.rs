@@ -0,0 +1,189 @@
+//! Records the events streamed from the model for each response, so that they
+//! can be exported as JSONL when reporting bugs about a provider.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use language_model::LanguageModelCompletionEvent;
+use serde::Serialize;
+
+use crate::MessageId;
+
+/// The number of responses whose events are kept.
+const MAX_EVENT_LOGS: usize = 20;
+
+/// The events of one response, in the order they were received.
+#[derive(Clone, Debug)]
+pub struct EventLog {
+    header: EventLogHeader,
+    events: Vec<LoggedEvent>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct EventLogHeader {
+    provider: String,
+    model: String,
+    started_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct LoggedEvent {
+    /// The time since the request was sent.
+    elapsed_ms: u64,
+    #[serde(flatten)]
+    kind: LoggedEventKind,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum LoggedEventKind {
+    Event(LanguageModelCompletionEvent),
+    Error(String),
+}
+
+impl EventLog {
+    fn new(provider: String, model: String, started_at: DateTime<Utc>) -> Self {
+        Self {
+            header: EventLogHeader {
+                provider,
+                model,
+                started_at,
+            },
+            events: Vec::new(),
+        }
+    }
+
+    /// Returns the log as JSONL: a line with the provider, model and start
+    /// time, followed by a line for each event.
+    pub fn to_jsonl(&self) -> String {
+        let mut jsonl = String::new();
+        writeln!(jsonl, "{}", serde_json::to_string(&self.header).unwrap()).unwrap();
+        for event in &self.events {
+            writeln!(jsonl, "{}", serde_json::to_string(event).unwrap()).unwrap();
+        }
+        jsonl
+    }
+}
+
+/// The event logs of the most recent responses.
+#[derive(Default)]
+pub struct EventLogs {
+    logs: VecDeque<(MessageId, EventLog)>,
+}
+
+impl EventLogs {
+    /// Starts a new log for the response streaming into the message,
+    /// replacing any previous log for it.
+    pub fn start(&mut self, message_id: MessageId, provider: String, model: String) {
+        self.logs.retain(|(id, _)| *id != message_id);
+        if self.logs.len() == MAX_EVENT_LOGS {
+            self.logs.pop_front();
+        }
+        self.logs
+            .push_back((message_id, EventLog::new(provider, model, Utc::now())));
+    }
+
+    pub fn record(
+        &mut self,
+        message_id: MessageId,
+        elapsed: Duration,
+        event: Result<&LanguageModelCompletionEvent, String>,
+    ) {
+        let Some(log) = self.get_mut(message_id) else {
+            return;
+        };
+        log.events.push(LoggedEvent {
+            elapsed_ms: elapsed.as_millis() as u64,
+            kind: match event {
+                Ok(event) => LoggedEventKind::Event(event.clone()),
+                Err(error) => LoggedEventKind::Error(error),
+            },
+        });
+    }
+
+    pub fn get(&self, message_id: MessageId) -> Option<&EventLog> {
+        self.logs
+            .iter()
+            .find(|(id, _)| *id == message_id)
+            .map(|(_, log)| log)
+    }
+
+    fn get_mut(&mut self, message_id: MessageId) -> Option<&mut EventLog> {
+        self.logs
+            .iter_mut()
+            .find(|(id, _)| *id == message_id)
+            .map(|(_, log)| log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use language_model::{LanguageModelToolUse, StopReason};
+
+    #[test]
+    fn test_event_log_to_jsonl() {
+        let message_id = MessageId(clock::Lamport::default());
+        let mut logs = EventLogs::default();
+        logs.start(message_id, "openai".into(), "gpt-4o".into());
+        logs.get_mut(message_id).unwrap().header.started_at =
+            "2025-01-01T00:00:00Z".parse().unwrap();
+
+        logs.record(
+            message_id,
+            Duration::from_millis(120),
+            Ok(&LanguageModelCompletionEvent::Text("Héllo".into())),
+        );
+        logs.record(
+            message_id,
+            Duration::from_millis(180),
+            Ok(&LanguageModelCompletionEvent::ToolUse(
+                LanguageModelToolUse {
+                    id: "call_1".into(),
+                    name: "now".into(),
+                    raw_input: "{\"timezone\":".into(),
+                    input: serde_json::json!({}),
+                    is_input_complete: false,
+                },
+            )),
+        );
+        logs.record(
+            message_id,
+            Duration::from_millis(200),
+            Ok(&LanguageModelCompletionEvent::Stop(StopReason::ToolUse)),
+        );
+        logs.record(
+            message_id,
+            Duration::from_millis(250),
+            Err("connection reset".into()),
+        );
+
+        assert_eq!(
+            logs.get(message_id).unwrap().to_jsonl(),
+            indoc! {r#"
+                {"provider":"openai","model":"gpt-4o","started_at":"2025-01-01T00:00:00Z"}
+                {"elapsed_ms":120,"event":{"Text":"Héllo"}}
+                {"elapsed_ms":180,"event":{"ToolUse":{"id":"call_1","name":"now","raw_input":"{\"timezone\":","input":{},"is_input_complete":false}}}
+                {"elapsed_ms":200,"event":{"Stop":"tool_use"}}
+                {"elapsed_ms":250,"error":"connection reset"}
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_event_logs_keep_most_recent() {
+        let mut logs = EventLogs::default();
+        let mut clock = clock::Lamport::default();
+        let ids = (0..=MAX_EVENT_LOGS)
+            .map(|_| MessageId(clock.tick()))
+            .collect::<Vec<_>>();
+        for id in &ids {
+            logs.start(*id, "openai".into(), "gpt-4o".into());
+        }
+        assert!(logs.get(ids[0]).is_none());
+        assert!(logs.get(ids[MAX_EVENT_LOGS]).is_some());
+    }
+}
@@ -0,0 +1,65 @@
+use collections::{HashMap, HashSet};
+use editor::{
+    Anchor, Editor,
+    display_map::{BlockProperties, CustomBlockId},
+};
+use gpui::Context;
+use std::hash::Hash;
+
+/// Blocks that each render a value, such as the web sources of a message,
+/// keyed by that value. Updating them only removes the blocks of the values
+/// that are gone and inserts blocks for the new ones, so that editing a long
+/// context doesn't rebuild the blocks of every message on each keystroke.
+pub struct KeyedBlocks<K> {
+    blocks: HashMap<K, CustomBlockId>,
+}
+
+impl<K> Default for KeyedBlocks<K> {
+    fn default() -> Self {
+        Self {
+            blocks: HashMap::default(),
+        }
+    }
+}
+
+impl<K: Hash + Eq> KeyedBlocks<K> {
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    #[cfg(test)]
+    pub fn block_ids(&self) -> HashSet<CustomBlockId> {
+        self.blocks.values().copied().collect()
+    }
+
+    /// Updates the blocks to render the given values. Blocks are only built
+    /// for the keys that had no block before, from the data they come with.
+    pub fn update<T>(
+        &mut self,
+        items: impl IntoIterator<Item = (K, T)>,
+        mut build: impl FnMut(&K, T) -> Option<BlockProperties<Anchor>>,
+        editor: &mut Editor,
+        cx: &mut Context<Editor>,
+    ) {
+        let mut old_blocks = std::mem::take(&mut self.blocks);
+        let mut new_blocks = HashMap::default();
+        for (key, data) in items {
+            if let Some(block_id) = old_blocks.remove(&key) {
+                self.blocks.insert(key, block_id);
+            } else if !self.blocks.contains_key(&key) && !new_blocks.contains_key(&key) {
+                if let Some(block) = build(&key, data) {
+                    new_blocks.insert(key, block);
+                }
+            }
+        }
+
+        if !old_blocks.is_empty() {
+            editor.remove_blocks(old_blocks.into_values().collect::<HashSet<_>>(), None, cx);
+        }
+        if !new_blocks.is_empty() {
+            let (keys, blocks): (Vec<_>, Vec<_>) = new_blocks.into_iter().unzip();
+            let ids = editor.insert_blocks(blocks, None, cx);
+            self.blocks.extend(keys.into_iter().zip(ids));
+        }
+    }
+}
@@ -14,7 +14,7 @@ use language_model::{
 use ordered_float::OrderedFloat;
 use picker::{Picker, PickerDelegate};
 use proto::Plan;
-use ui::{ListItem, ListItemSpacing, prelude::*};
+use ui::{ListItem, ListItemSpacing, Tooltip, prelude::*};
 
 action_with_deprecated_aliases!(
     agent,
@@ -29,16 +29,35 @@ const TRY_ZED_PRO_URL: &str = "https://zed.dev/pro";
 
 type OnModelChanged = Arc<dyn Fn(Arc<dyn LanguageModel>, &mut App) + 'static>;
 type GetActiveModel = Arc<dyn Fn(&App) -> Option<ConfiguredModel> + 'static>;
+type GetAnswerLanguage = Arc<dyn Fn(&App) -> Option<String> + 'static>;
+
+/// The beginnings of the names of models that are trained mostly on English
+/// text and code, and answer poorly in other languages.
+const ENGLISH_CENTRIC_MODELS: &[&str] = &[
+    "codellama",
+    "codegemma",
+    "deepseek-coder",
+    "phi",
+    "starcoder",
+    "tinyllama",
+];
 
 pub type LanguageModelSelector = Picker<LanguageModelPickerDelegate>;
 
 pub fn language_model_selector(
     get_active_model: impl Fn(&App) -> Option<ConfiguredModel> + 'static,
+    get_answer_language: impl Fn(&App) -> Option<String> + 'static,
     on_model_changed: impl Fn(Arc<dyn LanguageModel>, &mut App) + 'static,
     window: &mut Window,
     cx: &mut Context<LanguageModelSelector>,
 ) -> LanguageModelSelector {
-    let delegate = LanguageModelPickerDelegate::new(get_active_model, on_model_changed, window, cx);
+    let delegate = LanguageModelPickerDelegate::new(
+        get_active_model,
+        get_answer_language,
+        on_model_changed,
+        window,
+        cx,
+    );
     Picker::list(delegate, window, cx)
         .show_scrollbar(true)
         .width(rems(20.))
@@ -86,6 +105,7 @@ struct ModelInfo {
 pub struct LanguageModelPickerDelegate {
     on_model_changed: OnModelChanged,
     get_active_model: GetActiveModel,
+    get_answer_language: GetAnswerLanguage,
     all_models: Arc<GroupedModels>,
     filtered_entries: Vec<LanguageModelPickerEntry>,
     selected_index: usize,
@@ -96,6 +116,7 @@ pub struct LanguageModelPickerDelegate {
 impl LanguageModelPickerDelegate {
     fn new(
         get_active_model: impl Fn(&App) -> Option<ConfiguredModel> + 'static,
+        get_answer_language: impl Fn(&App) -> Option<String> + 'static,
         on_model_changed: impl Fn(Arc<dyn LanguageModel>, &mut App) + 'static,
         window: &mut Window,
         cx: &mut Context<Picker<Self>>,
@@ -110,6 +131,7 @@ impl LanguageModelPickerDelegate {
             selected_index: Self::get_active_model_index(&entries, get_active_model(cx)),
             filtered_entries: entries,
             get_active_model: Arc::new(get_active_model),
+            get_answer_language: Arc::new(get_answer_language),
             _authenticate_all_providers_task: Self::authenticate_all_providers(cx),
             _subscriptions: vec![cx.subscribe_in(
                 &LanguageModelRegistry::global(cx),
@@ -506,6 +528,9 @@ impl PickerDelegate for LanguageModelPickerDelegate {
                 let is_selected = Some(model_info.model.provider_id()) == active_provider_id
                     && Some(model_info.model.id()) == active_model_id;
 
+                let weak_in_language = (self.get_answer_language)(cx)
+                    .filter(|language| is_weak_in_language(&model_info.model.id().0, language));
+
                 let model_icon_color = if is_selected {
                     Color::Accent
                 } else {
@@ -528,7 +553,21 @@ impl PickerDelegate for LanguageModelPickerDelegate {
                                 .pl_0p5()
                                 .gap_1p5()
                                 .w(px(240.))
-                                .child(Label::new(model_info.model.name().0.clone()).truncate()),
+                                .child(Label::new(model_info.model.name().0.clone()).truncate())
+                                .when_some(weak_in_language, |this, language| {
+                                    this.child(
+                                        div()
+                                            .id(("weak-in-language", ix))
+                                            .child(
+                                                Icon::new(IconName::Warning)
+                                                    .color(Color::Warning)
+                                                    .size(IconSize::XSmall),
+                                            )
+                                            .tooltip(Tooltip::text(format!(
+                                                "This model may answer poorly in {language}"
+                                            ))),
+                                    )
+                                }),
                         )
                         .end_slot(div().pr_3().when(is_selected, |this| {
                             this.child(
@@ -600,6 +639,19 @@ impl PickerDelegate for LanguageModelPickerDelegate {
     }
 }
 
+/// Whether the model is known to answer poorly in the language.
+fn is_weak_in_language(model_id: &str, language: &str) -> bool {
+    if language.trim().eq_ignore_ascii_case("english") {
+        return false;
+    }
+    let model_id = model_id.to_lowercase();
+    model_id.split(['/', ':']).any(|name| {
+        ENGLISH_CENTRIC_MODELS
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -830,4 +882,13 @@ mod tests {
         // Recommended models should not appear in "other"
         assert_models_eq(actual_other_models, vec!["zed/gemini", "copilot/claude"]);
     }
+
+    #[test]
+    fn test_is_weak_in_language() {
+        assert!(is_weak_in_language("codellama:13b", "Spanish"));
+        assert!(is_weak_in_language("microsoft/phi-3-mini", "Japanese"));
+        assert!(!is_weak_in_language("codellama:13b", "English"));
+        assert!(!is_weak_in_language("dolphin-mixtral:8x7b", "Spanish"));
+        assert!(!is_weak_in_language("claude-sonnet-4", "Spanish"));
+    }
 }
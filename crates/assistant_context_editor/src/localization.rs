@@ -0,0 +1,129 @@
+//! Translates the agent panel's strings into the language chosen with the
+//! `agent.interface_language` setting.
+//!
+//! A translation is a JSON object that maps each English string to its
+//! translation, named after the language, such as `es.json`. The ones bundled
+//! in `assets/translations` are read first, then the ones in
+//! [`paths::translations_dir`], so that a translation can be tried out and
+//! corrected before it's contributed. Strings without a translation are shown
+//! in English.
+
+use agent_settings::AgentSettings;
+use anyhow::Result;
+use assets::Assets;
+use collections::HashMap;
+use gpui::{App, AppContext as _, AssetSource as _, Global, SharedString};
+use settings::{Settings as _, SettingsStore};
+use util::ResultExt as _;
+
+#[derive(Default)]
+struct Localization {
+    language: Option<String>,
+    strings: HashMap<String, SharedString>,
+}
+
+impl Global for Localization {}
+
+pub(crate) fn init(cx: &mut App) {
+    cx.set_global(Localization::default());
+    load_translation(cx);
+    cx.observe_global::<SettingsStore>(|cx| {
+        let language = &AgentSettings::get_global(cx).interface_language;
+        if cx.global::<Localization>().language != *language {
+            load_translation(cx);
+        }
+    })
+    .detach();
+}
+
+/// Returns the translation of the English string into the interface language.
+pub fn tr(english: &'static str, cx: &App) -> SharedString {
+    cx.try_global::<Localization>()
+        .and_then(|localization| localization.strings.get(english).cloned())
+        .unwrap_or_else(|| SharedString::new_static(english))
+}
+
+fn load_translation(cx: &mut App) {
+    let language = AgentSettings::get_global(cx).interface_language.clone();
+    cx.update_global::<Localization, _>(|localization, _| {
+        localization.language = language.clone();
+        localization.strings.clear();
+    });
+    cx.refresh_windows();
+
+    let Some(language) = language else {
+        return;
+    };
+    // The language names a file, so it can't contain a path.
+    if !language
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        log::error!("invalid interface language {language:?}");
+        return;
+    }
+
+    cx.spawn(async move |cx| {
+        let file_name = format!("{language}.json");
+        let strings = cx
+            .background_spawn(async move {
+                let mut strings = HashMap::default();
+                if let Some(bundled) = Assets
+                    .load(&format!("translations/{file_name}"))
+                    .ok()
+                    .flatten()
+                {
+                    strings
+                        .extend(parse_translation(&String::from_utf8_lossy(&bundled)).log_err()?);
+                }
+                if let Ok(contents) =
+                    std::fs::read_to_string(paths::translations_dir().join(&file_name))
+                {
+                    strings.extend(parse_translation(&contents).log_err()?);
+                }
+                Some(strings)
+            })
+            .await;
+        cx.update(|cx| {
+            cx.update_global::<Localization, _>(|localization, _| {
+                // The setting may have changed while the translation was loading.
+                if localization.language.as_ref() == Some(&language) {
+                    localization.strings = strings.unwrap_or_default();
+                }
+            });
+            cx.refresh_windows();
+        })
+        .log_err();
+    })
+    .detach();
+}
+
+/// Parses a translation, skipping the strings that haven't been translated
+/// yet.
+fn parse_translation(contents: &str) -> Result<HashMap<String, SharedString>> {
+    let strings = serde_json_lenient::from_str::<HashMap<String, String>>(contents)?;
+    Ok(strings
+        .into_iter()
+        .filter(|(_, translation)| !translation.trim().is_empty())
+        .map(|(english, translation)| (english, translation.into()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_translation() {
+        let strings = parse_translation(
+            r#"{
+                // Comments and trailing commas are allowed.
+                "New Thread": "Nuevo hilo",
+                "View All": "",
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(strings.len(), 1);
+        assert_eq!(strings["New Thread"].as_ref(), "Nuevo hilo");
+    }
+}
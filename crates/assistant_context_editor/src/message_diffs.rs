@@ -0,0 +1,230 @@
+//! Finds the lines that an assistant message's edit suggestions add and
+//! remove, so that they can be highlighted like a diff.
+//!
+//! Lines are found in `diff` and `patch` code blocks, and in pairs of code
+//! blocks labeled as the code before and after the change, either in their
+//! info string or in the line just above them.
+
+use std::ops::Range;
+
+/// Labels on the line above a code block that are longer than this are
+/// prose rather than a label.
+const MAX_LABEL_LEN: usize = 80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffLineKind {
+    Added,
+    Removed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffLine {
+    /// The range of the line in the message, without its newline.
+    pub range: Range<usize>,
+    pub kind: DiffLineKind,
+}
+
+#[derive(Debug)]
+struct CodeBlock {
+    /// The words of the info string, lowercased.
+    info: Vec<String>,
+    /// The line above the opening fence, lowercased.
+    label: String,
+    content: Range<usize>,
+}
+
+impl CodeBlock {
+    fn is_diff(&self) -> bool {
+        self.info
+            .first()
+            .is_some_and(|language| language == "diff" || language == "patch")
+    }
+
+    fn is_labeled(&self, word: &str) -> bool {
+        self.info.iter().any(|info| info == word)
+            || (self.label.len() <= MAX_LABEL_LEN
+                && self
+                    .label
+                    .split(|c: char| !c.is_alphanumeric())
+                    .any(|label_word| label_word == word))
+    }
+}
+
+pub fn find_diff_lines(message: &str) -> Vec<DiffLine> {
+    let blocks = code_blocks(message);
+    let mut lines = Vec::new();
+    let mut ix = 0;
+    while ix < blocks.len() {
+        let block = &blocks[ix];
+        if block.is_diff() {
+            for (range, line) in lines_in(message, block.content.clone()) {
+                let kind = if line.starts_with('+') && !line.starts_with("+++") {
+                    DiffLineKind::Added
+                } else if line.starts_with('-') && !line.starts_with("---") {
+                    DiffLineKind::Removed
+                } else {
+                    continue;
+                };
+                lines.push(DiffLine { range, kind });
+            }
+        } else if let Some(after) = blocks
+            .get(ix + 1)
+            .filter(|after| block.is_labeled("before") && after.is_labeled("after"))
+        {
+            let before_lines = lines_in(message, block.content.clone());
+            let after_lines = lines_in(message, after.content.clone());
+            for (old_rows, new_rows) in language::line_diff(
+                &message[block.content.clone()],
+                &message[after.content.clone()],
+            ) {
+                lines.extend(old_rows.map(|row| DiffLine {
+                    range: before_lines[row as usize].0.clone(),
+                    kind: DiffLineKind::Removed,
+                }));
+                lines.extend(new_rows.map(|row| DiffLine {
+                    range: after_lines[row as usize].0.clone(),
+                    kind: DiffLineKind::Added,
+                }));
+            }
+            ix += 1;
+        }
+        ix += 1;
+    }
+    lines.sort_by_key(|line| line.range.start);
+    lines
+}
+
+fn code_blocks(message: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut previous_line = "";
+    let mut open_block: Option<CodeBlock> = None;
+    for (range, line) in lines_in(message, 0..message.len()) {
+        let trimmed = line.trim();
+        match &mut open_block {
+            Some(block) if trimmed.starts_with("```") => {
+                block.content.end = range.start;
+                blocks.extend(open_block.take());
+                // A label only applies to the code block right below it.
+                previous_line = "";
+            }
+            Some(_) => {}
+            None => {
+                if let Some(info) = trimmed.strip_prefix("```") {
+                    open_block = Some(CodeBlock {
+                        info: info
+                            .split(|c: char| c.is_whitespace() || c == ',')
+                            .filter(|word| !word.is_empty())
+                            .map(|word| word.to_lowercase())
+                            .collect(),
+                        label: previous_line.trim().to_lowercase(),
+                        content: (range.end + 1).min(message.len())..message.len(),
+                    });
+                } else if !trimmed.is_empty() {
+                    previous_line = line;
+                }
+            }
+        }
+    }
+    blocks
+}
+
+/// Returns the lines in the range of the message, with their ranges.
+fn lines_in(message: &str, range: Range<usize>) -> Vec<(Range<usize>, &str)> {
+    let mut lines = Vec::new();
+    let mut start = range.start;
+    for line in message[range.clone()].split_inclusive('\n') {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        lines.push((start..start + line.len(), line));
+        start += line.len() + 1;
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn highlighted(message: &str) -> Vec<(DiffLineKind, &str)> {
+        find_diff_lines(message)
+            .into_iter()
+            .map(|line| (line.kind, &message[line.range]))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_code_block() {
+        let message = indoc! {"
+            Apply this patch:
+            ```diff
+            --- a/src/main.rs
+            +++ b/src/main.rs
+             fn main() {
+            -    println!(\"hi\");
+            +    println!(\"hello\");
+             }
+            ```
+            - Not part of the diff
+        "};
+        assert_eq!(
+            highlighted(message),
+            [
+                (DiffLineKind::Removed, "-    println!(\"hi\");"),
+                (DiffLineKind::Added, "+    println!(\"hello\");"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_before_and_after_code_blocks() {
+        let message = indoc! {"
+            **Before:**
+            ```rust
+            let a = 1;
+            let b = 2;
+            ```
+            **After:**
+            ```rust
+            let a = 1;
+            let b = 3;
+            ```
+        "};
+        assert_eq!(
+            highlighted(message),
+            [
+                (DiffLineKind::Removed, "let b = 2;"),
+                (DiffLineKind::Added, "let b = 3;"),
+            ]
+        );
+
+        let message = indoc! {"
+            ```js before
+            f(1)
+            ```
+            ```js after
+            f(2)
+            ```
+        "};
+        assert_eq!(
+            highlighted(message),
+            [
+                (DiffLineKind::Removed, "f(1)"),
+                (DiffLineKind::Added, "f(2)")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unlabeled_code_blocks() {
+        let message = indoc! {"
+            Here's the first version, written before the refactor made it obsolete, which explains a lot:
+            ```rust
+            let a = 1;
+            ```
+            ```rust
+            let a = 2;
+            ```
+        "};
+        assert!(highlighted(message).is_empty());
+    }
+}
@@ -0,0 +1,268 @@
+//! Cleans up the text of completed responses with the post-processors that a
+//! text thread has enabled.
+
+use anyhow::{Context as _, Result};
+use futures::AsyncWriteExt as _;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use util::command::new_smol_command;
+
+/// The phrases that open the filler sentence some models start their answers
+/// with, such as "Sure, here is the updated function:".
+const PREAMBLE_OPENERS: &[&str] = &[
+    "sure",
+    "certainly",
+    "of course",
+    "absolutely",
+    "here is",
+    "here's",
+    "here are",
+];
+/// Longer first lines are part of the answer rather than a preamble.
+const MAX_PREAMBLE_LEN: usize = 120;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostProcessor {
+    /// Removes a leading "Sure, here is…" sentence.
+    StripPreamble,
+    /// Converts Unicode line separators to newlines and removes trailing
+    /// whitespace and blank lines.
+    NormalizeLineEndings,
+    /// Formats Rust code blocks with `rustfmt`.
+    FormatRustCode,
+}
+
+impl PostProcessor {
+    /// The post-processors, in the order they run.
+    pub const ALL: [Self; 3] = [
+        Self::StripPreamble,
+        Self::NormalizeLineEndings,
+        Self::FormatRustCode,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::StripPreamble => "Strip Preamble",
+            Self::NormalizeLineEndings => "Normalize Line Endings",
+            Self::FormatRustCode => "Format Rust Code",
+        }
+    }
+}
+
+/// What a post-processor changed in a message.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PostProcessingChange {
+    pub processor: PostProcessor,
+    pub summary: String,
+}
+
+/// Runs the post-processors on the text of a message, and returns the new
+/// text with the changes that were made.
+pub async fn run(
+    processors: &[PostProcessor],
+    mut text: String,
+) -> (String, Vec<PostProcessingChange>) {
+    let mut changes = Vec::new();
+    for processor in PostProcessor::ALL {
+        if !processors.contains(&processor) {
+            continue;
+        }
+        let summary = match processor {
+            PostProcessor::StripPreamble => strip_preamble(&mut text),
+            PostProcessor::NormalizeLineEndings => normalize_line_endings(&mut text),
+            PostProcessor::FormatRustCode => format_rust_code(&mut text).await,
+        };
+        if let Some(summary) = summary {
+            changes.push(PostProcessingChange { processor, summary });
+        }
+    }
+    (text, changes)
+}
+
+/// The offset where the answer starts, after the thought process that some
+/// models stream before it.
+fn answer_start(text: &str) -> usize {
+    if text.starts_with("<think>\n") {
+        if let Some(ix) = text.find("\n</think>") {
+            return ix + "\n</think>".len();
+        }
+    }
+    0
+}
+
+fn strip_preamble(text: &mut String) -> Option<String> {
+    let start = answer_start(text);
+    let answer = &text[start..];
+    let leading_whitespace = answer.len() - answer.trim_start().len();
+    let line_start = start + leading_whitespace;
+    let line_end = text[line_start..]
+        .find('\n')
+        .map_or(text.len(), |ix| line_start + ix);
+    let line = text[line_start..line_end].trim_end();
+    let lowercase = line.to_lowercase();
+    if line.len() > MAX_PREAMBLE_LEN
+        || !(line.ends_with(':') || line.ends_with('.') || line.ends_with('!'))
+        || !PREAMBLE_OPENERS
+            .iter()
+            .any(|opener| lowercase.starts_with(opener))
+        || text[line_end..].trim().is_empty()
+    {
+        return None;
+    }
+
+    let rest_start = line_end + (text[line_end..].len() - text[line_end..].trim_start().len());
+    let summary = format!("Removed \"{line}\"");
+    text.replace_range(line_start..rest_start, "");
+    Some(summary)
+}
+
+fn normalize_line_endings(text: &mut String) -> Option<String> {
+    let mut normalized = String::with_capacity(text.len());
+    for line in text.split(['\n', '\u{2028}', '\u{2029}', '\u{85}']) {
+        normalized.push_str(line.trim_end());
+        normalized.push('\n');
+    }
+    let trimmed_len = normalized.trim_end().len();
+    normalized.truncate(trimmed_len);
+    if normalized == *text {
+        return None;
+    }
+    *text = normalized;
+    Some("Normalized line endings".into())
+}
+
+async fn format_rust_code(text: &mut String) -> Option<String> {
+    let mut formatted_count = 0;
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some((before, fence_start)) = find_rust_fence(rest) {
+        output.push_str(before);
+        let Some(code_len) = fence_start.find("\n```") else {
+            rest = fence_start;
+            break;
+        };
+        let (code, after) = fence_start.split_at(code_len + 1);
+        match rustfmt(code).await {
+            Ok(formatted) if formatted != code => {
+                output.push_str(&formatted);
+                formatted_count += 1;
+            }
+            Ok(_) => output.push_str(code),
+            Err(error) => {
+                log::debug!("failed to format Rust code block: {error}");
+                output.push_str(code);
+            }
+        }
+        rest = after;
+    }
+    output.push_str(rest);
+
+    if formatted_count == 0 {
+        return None;
+    }
+    *text = output;
+    Some(match formatted_count {
+        1 => "Formatted 1 Rust code block".into(),
+        count => format!("Formatted {count} Rust code blocks"),
+    })
+}
+
+/// Finds the next Rust code fence, returning the text up to and including
+/// the fence's opening line, and the text after it.
+fn find_rust_fence(text: &str) -> Option<(&str, &str)> {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        offset += line.len();
+        let Some(info) = line.trim_start().strip_prefix("```") else {
+            continue;
+        };
+        let language = info
+            .trim()
+            .split([' ', ',', '{'])
+            .next()
+            .unwrap_or_default();
+        if language == "rust" || language == "rs" || language.ends_with(".rs") {
+            return Some(text.split_at(offset));
+        }
+    }
+    None
+}
+
+async fn rustfmt(code: &str) -> Result<String> {
+    let mut child = new_smol_command("rustfmt")
+        .args(["--edition", "2024"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .context("failed to run rustfmt")?;
+    child
+        .stdin
+        .take()
+        .context("rustfmt has no stdin")?
+        .write_all(code.as_bytes())
+        .await?;
+    let output = child.output().await?;
+    anyhow::ensure!(output.status.success(), "rustfmt failed");
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_strip_preamble() {
+        let mut text = "Sure, here is the updated function:\n\n```rust\nfn a() {}\n```".to_string();
+        assert_eq!(
+            strip_preamble(&mut text).as_deref(),
+            Some("Removed \"Sure, here is the updated function:\"")
+        );
+        assert_eq!(text, "```rust\nfn a() {}\n```");
+
+        let mut text =
+            "<think>\nThe user wants code.\n</think>\n\nHere's the fix:\nUse `a()`.".to_string();
+        assert!(strip_preamble(&mut text).is_some());
+        assert_eq!(
+            text,
+            "<think>\nThe user wants code.\n</think>\n\nUse `a()`."
+        );
+
+        for text in [
+            "Sure.",
+            "Here is why the test fails: the fixture is missing\nMore.",
+            "The function panics because the index is out of bounds.\n\nMore.",
+        ] {
+            let mut text = text.to_string();
+            assert_eq!(strip_preamble(&mut text), None);
+        }
+    }
+
+    #[test]
+    fn test_normalize_line_endings() {
+        let mut text = "First line  \nSecond\u{2028}line\t\n\n\n".to_string();
+        assert!(normalize_line_endings(&mut text).is_some());
+        assert_eq!(text, "First line\nSecond\nline");
+        assert_eq!(normalize_line_endings(&mut text), None);
+    }
+
+    #[test]
+    fn test_find_rust_fence() {
+        let text = indoc! {"
+            Some text
+            ```python
+            print()
+            ```
+            ```rust
+            fn a() {}
+            ```
+        "};
+        let (before, after) = find_rust_fence(text).unwrap();
+        assert!(before.ends_with("```rust\n"));
+        assert_eq!(after, "fn a() {}\n```\n");
+        assert_eq!(find_rust_fence("```py\nx\n```"), None);
+    }
+}
@@ -0,0 +1,273 @@
+/// Attachments larger than this many bytes are compressed before being sent to
+/// the model, when compression is enabled.
+pub const COMPRESSION_THRESHOLD: usize = 16 * 1024;
+
+/// Comment lines made only of these characters, such as `// -----`, only
+/// separate other lines visually.
+const SEPARATOR_CHARS: &[char] = &['-', '=', '*', '#', '/', '_', '~', '+'];
+
+/// Lines made only of these characters close a block opened on a previous line.
+const CLOSING_DELIMITER_CHARS: &[char] = &['}', ')', ']', ';', ','];
+
+/// A language whose code blocks can be compressed, because dropping its blank
+/// lines, separator comments and empty comments doesn't change its meaning.
+struct CodeLanguage {
+    /// The language tags of its fenced code blocks.
+    tags: &'static [&'static str],
+    line_comment: &'static str,
+    /// Comment markers with no comment after them.
+    empty_comments: &'static [&'static str],
+}
+
+const CODE_LANGUAGES: &[CodeLanguage] = &[
+    CodeLanguage {
+        tags: &[
+            "c", "cc", "cpp", "cs", "cxx", "dart", "go", "h", "hpp", "java", "js", "jsx", "kt",
+            "kts", "mjs", "proto", "rs", "rust", "scala", "swift", "ts", "tsx", "zig",
+        ],
+        line_comment: "//",
+        empty_comments: &["//", "///", "//!", "*"],
+    },
+    CodeLanguage {
+        tags: &[
+            "bash", "cmake", "ex", "exs", "fish", "nix", "pl", "ps1", "py", "python", "r", "rb",
+            "ruby", "sh", "toml", "zsh",
+        ],
+        line_comment: "#",
+        empty_comments: &["#"],
+    },
+    CodeLanguage {
+        tags: &["elm", "haskell", "hs", "lua", "sql"],
+        line_comment: "--",
+        empty_comments: &["--"],
+    },
+];
+
+/// The result of compressing the text of an attachment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedText {
+    pub text: String,
+    pub original_len: usize,
+}
+
+impl CompressedText {
+    /// The fraction of the original text that was dropped.
+    pub fn ratio(&self) -> f32 {
+        if self.original_len == 0 {
+            return 0.;
+        }
+        1. - self.text.len() as f32 / self.original_len as f32
+    }
+}
+
+/// Drops the lines of the text that carry little information for the model,
+/// in the spirit of LLMLingua's token dropping, but with cheap heuristics.
+/// In the fenced code blocks of the [`CODE_LANGUAGES`], blank lines,
+/// separator comments and empty comments are removed, and lines that only
+/// close a block are joined with the line before. Everywhere, runs of
+/// repeated lines, which are common in logs, are collapsed. Prose and code
+/// blocks in other languages, such as Markdown, YAML or diffs, are otherwise
+/// kept as they are, since their blank and separator lines carry meaning.
+pub fn compress(text: &str) -> CompressedText {
+    let mut lines: Vec<String> = Vec::new();
+    let mut repetitions = 0;
+    // The language of the code block each line is in, which is `None` for
+    // prose and for code blocks whose language isn't known.
+    let mut code_block: Option<Option<&CodeLanguage>> = None;
+    for line in text.lines() {
+        let mut language = None;
+        if line.trim_start().starts_with("```") {
+            code_block = match code_block {
+                Some(_) => None,
+                None => Some(code_language(line.trim_start().trim_start_matches('`'))),
+            };
+        } else {
+            language = code_block.flatten();
+        }
+
+        let line = if language.is_some() {
+            line.trim_end()
+        } else {
+            line
+        };
+        let trimmed = line.trim_start();
+        if let Some(language) = language {
+            if trimmed.is_empty()
+                || is_separator(trimmed, language)
+                || language.empty_comments.contains(&trimmed)
+            {
+                continue;
+            }
+        }
+
+        if !trimmed.is_empty() && lines.last().is_some_and(|last| last == line) {
+            repetitions += 1;
+            continue;
+        }
+        push_repetitions(&mut lines, &mut repetitions);
+
+        if let Some(language) = language {
+            if trimmed
+                .chars()
+                .all(|c| CLOSING_DELIMITER_CHARS.contains(&c))
+            {
+                if let Some(last) = lines.last_mut().filter(|last| {
+                    !last.trim_start().starts_with("```") && !last.contains(language.line_comment)
+                }) {
+                    last.push(' ');
+                    last.push_str(trimmed);
+                    continue;
+                }
+            }
+        }
+        lines.push(line.to_string());
+    }
+    push_repetitions(&mut lines, &mut repetitions);
+
+    let mut compressed = lines.join("\n");
+    if text.ends_with('\n') && !compressed.is_empty() {
+        compressed.push('\n');
+    }
+    CompressedText {
+        text: compressed,
+        original_len: text.len(),
+    }
+}
+
+/// Returns the language of a code block from the text after its opening
+/// fence, such as `rs src/main.rs`.
+fn code_language(info: &str) -> Option<&'static CodeLanguage> {
+    let tag = info.split_whitespace().next()?.to_ascii_lowercase();
+    CODE_LANGUAGES
+        .iter()
+        .find(|language| language.tags.contains(&tag.as_str()))
+}
+
+fn is_separator(line: &str, language: &CodeLanguage) -> bool {
+    line.len() >= 4
+        && line.starts_with(language.line_comment)
+        && line
+            .chars()
+            .all(|c| c.is_whitespace() || SEPARATOR_CHARS.contains(&c))
+}
+
+fn push_repetitions(lines: &mut Vec<String>, repetitions: &mut usize) {
+    match *repetitions {
+        0 => {}
+        1 => {
+            if let Some(last) = lines.last().cloned() {
+                lines.push(last);
+            }
+        }
+        count => lines.push(format!("[previous line repeated {count} more times]")),
+    }
+    *repetitions = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_compress() {
+        let text = indoc! {"
+            ```rs src/main.rs
+            // ------------------------------
+
+            fn main() {
+                let config = Config::load();
+                //
+
+                if config.verbose {
+                    println!(\"verbose\");
+                }
+            }
+            ```
+            warning: unused variable
+            warning: unused variable
+            warning: unused variable
+            done
+            done
+        "};
+        let compressed = compress(text);
+        assert_eq!(
+            compressed.text,
+            indoc! {"
+                ```rs src/main.rs
+                fn main() {
+                    let config = Config::load();
+                    if config.verbose {
+                        println!(\"verbose\"); } }
+                ```
+                warning: unused variable
+                [previous line repeated 2 more times]
+                done
+                done
+            "}
+        );
+        assert!(compressed.ratio() > 0.1);
+        assert_eq!(compress("").ratio(), 0.);
+    }
+
+    #[test]
+    fn test_compress_keeps_markup_and_unknown_languages() {
+        let yaml = indoc! {"
+            ```yaml config.yaml
+            ---
+            name: build
+            # ----
+            #
+
+            steps:
+              - run: make
+            ---
+            name: test
+            ```
+        "};
+        assert_eq!(compress(yaml).text, yaml);
+
+        let setext_heading = indoc! {"
+            Overview
+            ========
+
+            The cache keeps recent entries.
+
+            ----
+
+            * * *
+        "};
+        assert_eq!(compress(setext_heading).text, setext_heading);
+
+        let hunk = "@@ -1,5 +1,5 @@\n fn main() {\n-    run();\n+    run_all();\n \n }\n";
+        assert_eq!(compress(hunk).text, hunk);
+        let diff = format!("```diff\n{hunk}---\n```\n");
+        assert_eq!(compress(&diff).text, diff);
+
+        // Comments are only dropped in the languages they're comments in.
+        assert_eq!(
+            compress(indoc! {"
+                ```sql
+                SELECT 1;
+                --
+                -- -----
+                ```
+                ```sh
+                echo 1
+                #
+                --
+                ```
+            "})
+            .text,
+            indoc! {"
+                ```sql
+                SELECT 1;
+                ```
+                ```sh
+                echo 1
+                --
+                ```
+            "}
+        );
+    }
+}
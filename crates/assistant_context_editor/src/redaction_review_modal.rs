@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use fs::Fs;
+use gpui::{DismissEvent, Entity, EventEmitter, FocusHandle, Focusable, prelude::*};
+use ui::{Checkbox, KeyBinding, ListItem, Modal, ModalFooter, ModalHeader, Section, prelude::*};
+use workspace::ModalView;
+
+use crate::AssistantContext;
+use crate::context_export::HtmlExport;
+use crate::redaction::{self, Redaction};
+
+/// Shows the secrets found in a text thread that's being exported or printed,
+/// each as the line it's on before and after masking it, so they can be
+/// reviewed before the export is written.
+pub struct RedactionReviewModal {
+    context: Entity<AssistantContext>,
+    fs: Arc<dyn Fs>,
+    redactions: Vec<Redaction>,
+    export: HtmlExport,
+    /// The line each redaction is on, before and after masking it.
+    previews: Vec<(SharedString, SharedString)>,
+    /// Whether each redaction is applied.
+    applied: Vec<bool>,
+    focus_handle: FocusHandle,
+}
+
+impl RedactionReviewModal {
+    pub fn new(
+        context: Entity<AssistantContext>,
+        fs: Arc<dyn Fs>,
+        redactions: Vec<Redaction>,
+        export: HtmlExport,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let text = context.read(cx).buffer().read(cx).text();
+        let previews = redactions
+            .iter()
+            .map(|redaction| {
+                let (before, after) = redaction::preview(&text, &redaction.range);
+                (before.into(), after.into())
+            })
+            .collect();
+        let applied = vec![true; redactions.len()];
+        Self {
+            context,
+            fs,
+            redactions,
+            export,
+            previews,
+            applied,
+            focus_handle: cx.focus_handle(),
+        }
+    }
+
+    fn confirm(&mut self, _: &menu::Confirm, cx: &mut Context<Self>) {
+        let ranges = self
+            .redactions
+            .iter()
+            .zip(&self.applied)
+            .filter(|(_, applied)| **applied)
+            .map(|(redaction, _)| redaction.range.clone())
+            .collect::<Vec<_>>();
+        self.export.run(&self.context, &ranges, self.fs.clone(), cx);
+        cx.emit(DismissEvent);
+    }
+
+    fn cancel(&mut self, _: &menu::Cancel, cx: &mut Context<Self>) {
+        cx.emit(DismissEvent);
+    }
+
+    fn render_redactions(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        v_flex().children(self.redactions.iter().enumerate().map(|(ix, redaction)| {
+            let (before, after) = self.previews[ix].clone();
+            let applied = self.applied[ix];
+            ListItem::new(("redaction", ix))
+                .start_slot(
+                    Checkbox::new(("apply-redaction", ix), applied.into()).on_click(cx.listener(
+                        move |this, _, _, cx| {
+                            this.applied[ix] = !this.applied[ix];
+                            cx.notify();
+                        },
+                    )),
+                )
+                .child(
+                    v_flex()
+                        .min_w_0()
+                        .child(
+                            Label::new(redaction.kind)
+                                .size(LabelSize::Small)
+                                .color(Color::Muted),
+                        )
+                        .child(
+                            Label::new(format!("- {before}"))
+                                .size(LabelSize::Small)
+                                .buffer_font(cx)
+                                .color(Color::Deleted)
+                                .truncate(),
+                        )
+                        .when(applied, |this| {
+                            this.child(
+                                Label::new(format!("+ {after}"))
+                                    .size(LabelSize::Small)
+                                    .buffer_font(cx)
+                                    .color(Color::Created)
+                                    .truncate(),
+                            )
+                        }),
+                )
+        }))
+    }
+}
+
+impl ModalView for RedactionReviewModal {}
+
+impl Focusable for RedactionReviewModal {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl EventEmitter<DismissEvent> for RedactionReviewModal {}
+
+impl Render for RedactionReviewModal {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let applied_count = self.applied.iter().filter(|applied| **applied).count();
+        let (output, action) = match self.export {
+            HtmlExport::Save => ("export", "Export"),
+            HtmlExport::Print => ("printout", "Print"),
+        };
+        let description = match self.redactions.len() {
+            1 => format!(
+                "This text thread appears to contain a secret. \
+                It'll be masked in the {output} unless you uncheck it."
+            ),
+            count => format!(
+                "This text thread appears to contain {count} secrets. \
+                They'll be masked in the {output} unless you uncheck them."
+            ),
+        };
+        let focus_handle = self.focus_handle(cx);
+
+        div()
+            .elevation_3(cx)
+            .w(rems(40.))
+            .key_context("RedactionReviewModal")
+            .track_focus(&self.focus_handle)
+            .on_action(
+                cx.listener(|this, _: &menu::Cancel, _window, cx| this.cancel(&menu::Cancel, cx)),
+            )
+            .on_action(
+                cx.listener(|this, _: &menu::Confirm, _window, cx| {
+                    this.confirm(&menu::Confirm, cx)
+                }),
+            )
+            .on_mouse_down_out(cx.listener(|_this, _, _, cx| cx.emit(DismissEvent)))
+            .child(
+                Modal::new("redaction-review", None)
+                    .header(ModalHeader::new().headline("Review Redactions"))
+                    .section(
+                        Section::new().meta(description).child(
+                            div()
+                                .id("redactions")
+                                .max_h(rems(24.))
+                                .overflow_y_scroll()
+                                .child(self.render_redactions(cx)),
+                        ),
+                    )
+                    .footer(
+                        ModalFooter::new().end_slot(
+                            h_flex()
+                                .gap_2()
+                                .child(
+                                    Button::new("cancel", "Cancel")
+                                        .key_binding(
+                                            KeyBinding::for_action_in(
+                                                &menu::Cancel,
+                                                &focus_handle,
+                                                window,
+                                                cx,
+                                            )
+                                            .map(|kb| kb.size(rems_from_px(12.))),
+                                        )
+                                        .on_click(cx.listener(|this, _event, _window, cx| {
+                                            this.cancel(&menu::Cancel, cx)
+                                        })),
+                                )
+                                .child(
+                                    Button::new(
+                                        "export",
+                                        if applied_count == 0 {
+                                            format!("{action} Without Redacting")
+                                        } else {
+                                            action.to_string()
+                                        },
+                                    )
+                                    .key_binding(
+                                        KeyBinding::for_action_in(
+                                            &menu::Confirm,
+                                            &focus_handle,
+                                            window,
+                                            cx,
+                                        )
+                                        .map(|kb| kb.size(rems_from_px(12.))),
+                                    )
+                                    .on_click(cx.listener(
+                                        |this, _event, _window, cx| {
+                                            this.confirm(&menu::Confirm, cx)
+                                        },
+                                    )),
+                                ),
+                        ),
+                    ),
+            )
+    }
+}
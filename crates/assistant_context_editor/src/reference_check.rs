@@ -0,0 +1,352 @@
+//! Checks the file paths and symbols an assistant message mentions in inline
+//! code against the project, to catch references that don't exist.
+
+use futures::future::join_all;
+use fuzzy::StringMatchCandidate;
+use gpui::{App, AppContext as _, Entity, Task};
+use project::Project;
+use std::{
+    fmt::Write as _,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::atomic::AtomicBool,
+};
+
+/// The extensions of the files that are recognized as paths even when they
+/// aren't in a directory, like `main.rs`.
+const FILE_EXTENSIONS: &[&str] = &[
+    "c", "cc", "cpp", "cs", "css", "ex", "exs", "go", "h", "hpp", "html", "java", "js", "json",
+    "jsx", "kt", "lock", "lua", "md", "py", "rb", "rs", "scss", "sh", "sql", "svelte", "swift",
+    "toml", "ts", "tsx", "txt", "vue", "yaml", "yml", "zig",
+];
+const MAX_SUGGESTIONS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReferenceKind {
+    Path,
+    Symbol,
+}
+
+/// A path or symbol mentioned in a message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub kind: ReferenceKind,
+    /// The range of the reference in the message.
+    pub range: Range<usize>,
+    /// The reference as it's written in the message.
+    pub text: String,
+    /// The path without a line number, or the name of the symbol without
+    /// its qualifier.
+    pub name: String,
+}
+
+/// A reference that wasn't found in the project.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnresolvedReference {
+    pub reference: Reference,
+    /// The paths or symbols in the project that are closest to it.
+    pub suggestions: Vec<String>,
+}
+
+/// Returns the paths and symbols in the inline code of the message, ignoring
+/// fenced code blocks.
+pub fn find_references(message: &str) -> Vec<Reference> {
+    let mut references = Vec::new();
+    let mut in_code_block = false;
+    let mut line_start = 0;
+    for line in message.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        } else if !in_code_block {
+            let mut offset = line_start;
+            let mut spans = line.split('`').peekable();
+            let mut is_code = false;
+            while let Some(span) = spans.next() {
+                // An unclosed backtick doesn't start a code span.
+                if is_code && spans.peek().is_some() {
+                    references.extend(parse_reference(span, offset));
+                }
+                offset += span.len() + 1;
+                is_code = !is_code;
+            }
+        }
+        line_start += line.len();
+    }
+    references
+}
+
+fn parse_reference(code: &str, offset: usize) -> Option<Reference> {
+    if code.is_empty() || code.contains(char::is_whitespace) {
+        return None;
+    }
+
+    if let Some(path) = parse_path(code) {
+        return Some(Reference {
+            kind: ReferenceKind::Path,
+            range: offset..offset + code.len(),
+            text: code.to_string(),
+            name: path.to_string(),
+        });
+    }
+
+    let name = code.strip_suffix("()").unwrap_or(code);
+    let is_identifier = |segment: &str| {
+        segment.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+    let segments = name.split("::").flat_map(|segment| segment.split('.'));
+    if !segments.clone().all(is_identifier) {
+        return None;
+    }
+    // Only check the names that can't be mistaken for a plain word.
+    let looks_like_code = code.ends_with("()")
+        || name.contains("::")
+        || name.contains('_')
+        || name.chars().skip(1).any(char::is_uppercase);
+    let name = segments.last()?;
+    (looks_like_code && name.len() >= 3).then(|| Reference {
+        kind: ReferenceKind::Symbol,
+        range: offset..offset + code.len(),
+        text: code.to_string(),
+        name: name.to_string(),
+    })
+}
+
+/// Returns the path in the code, without a line number suffix such as `:12`.
+fn parse_path(code: &str) -> Option<&str> {
+    if code.contains("://")
+        || code.starts_with(['/', '~', '-', '$'])
+        || code.contains(['*', '{', '}', '<', '>', '(', ')', '='])
+    {
+        return None;
+    }
+    let mut path = code;
+    while let Some((prefix, suffix)) = path.rsplit_once(':') {
+        if suffix.is_empty() || !suffix.chars().all(|c| c.is_ascii_digit()) {
+            break;
+        }
+        path = prefix;
+    }
+    if path.contains(':') {
+        return None;
+    }
+    let has_known_extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| FILE_EXTENSIONS.contains(&extension));
+    let is_directory_path = path.contains('/')
+        && path
+            .split('/')
+            .filter(|component| !component.is_empty())
+            .count()
+            > 1;
+    (has_known_extension || is_directory_path).then_some(path)
+}
+
+/// Returns the references in the message that don't exist in the project.
+/// Symbols are only checked when a language server is running.
+pub fn check_references(
+    message: &str,
+    project: &Entity<Project>,
+    cx: &mut App,
+) -> Task<Vec<UnresolvedReference>> {
+    let references = find_references(message);
+    if references.is_empty() {
+        return Task::ready(Vec::new());
+    }
+
+    let worktrees = project
+        .read(cx)
+        .visible_worktrees(cx)
+        .map(|worktree| worktree.read(cx).snapshot())
+        .collect::<Vec<_>>();
+    let has_language_servers = project
+        .read(cx)
+        .lsp_store()
+        .read(cx)
+        .language_server_statuses()
+        .next()
+        .is_some();
+    let symbol_searches = references
+        .iter()
+        .filter(|reference| reference.kind == ReferenceKind::Symbol && has_language_servers)
+        .map(|reference| {
+            let search = project.update(cx, |project, cx| project.symbols(&reference.name, cx));
+            (reference.clone(), search)
+        })
+        .collect::<Vec<_>>();
+    let executor = cx.background_executor().clone();
+
+    cx.background_spawn(async move {
+        let mut unresolved = Vec::new();
+
+        let mut paths = Vec::new();
+        for worktree in &worktrees {
+            let root_name = PathBuf::from(worktree.root_name());
+            for entry in worktree.entries(false, 0) {
+                paths.push(entry.path.to_path_buf());
+                paths.push(root_name.join(&entry.path));
+            }
+        }
+        for reference in &references {
+            if reference.kind != ReferenceKind::Path {
+                continue;
+            }
+            let path = Path::new(reference.name.trim_end_matches('/'));
+            if paths.iter().any(|candidate| candidate.ends_with(path)) {
+                continue;
+            }
+            unresolved.push(UnresolvedReference {
+                reference: reference.clone(),
+                suggestions: closest_paths(path, &paths, executor.clone()).await,
+            });
+        }
+
+        let symbol_results = join_all(
+            symbol_searches
+                .into_iter()
+                .map(|(reference, search)| async move { (reference, search.await) }),
+        )
+        .await;
+        for (reference, symbols) in symbol_results {
+            // The search failing doesn't mean that the symbol doesn't exist.
+            let Ok(symbols) = symbols else {
+                continue;
+            };
+            if symbols.iter().any(|symbol| symbol.name == reference.name) {
+                continue;
+            }
+            let mut suggestions = Vec::new();
+            for symbol in symbols {
+                if suggestions.len() < MAX_SUGGESTIONS && !suggestions.contains(&symbol.name) {
+                    suggestions.push(symbol.name);
+                }
+            }
+            unresolved.push(UnresolvedReference {
+                reference,
+                suggestions,
+            });
+        }
+
+        unresolved.sort_by_key(|unresolved| unresolved.reference.range.start);
+        unresolved
+    })
+}
+
+async fn closest_paths(
+    path: &Path,
+    paths: &[PathBuf],
+    executor: gpui::BackgroundExecutor,
+) -> Vec<String> {
+    let Some(file_name) = path.file_name().and_then(|file_name| file_name.to_str()) else {
+        return Vec::new();
+    };
+    // Only suggest paths relative to the worktree roots.
+    let candidates = paths
+        .iter()
+        .step_by(2)
+        .enumerate()
+        .map(|(id, path)| StringMatchCandidate::new(id, &path.to_string_lossy()))
+        .collect::<Vec<_>>();
+    let stem = Path::new(file_name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(file_name);
+    for query in [file_name, stem] {
+        let matches = fuzzy::match_strings(
+            &candidates,
+            query,
+            false,
+            MAX_SUGGESTIONS,
+            &AtomicBool::new(false),
+            executor.clone(),
+        )
+        .await;
+        if !matches.is_empty() {
+            return matches.into_iter().map(|m| m.string).collect();
+        }
+    }
+    Vec::new()
+}
+
+/// Returns the message asking the model to correct the references.
+pub fn correction_prompt(unresolved: &[UnresolvedReference]) -> String {
+    let mut prompt =
+        "Some of the paths and symbols in your response don't exist in the project:\n".to_string();
+    for unresolved in unresolved {
+        let text = &unresolved.reference.text;
+        let kind = match unresolved.reference.kind {
+            ReferenceKind::Path => "path",
+            ReferenceKind::Symbol => "symbol",
+        };
+        if unresolved.suggestions.is_empty() {
+            writeln!(prompt, "- `{text}`: no similar {kind} was found").unwrap();
+        } else {
+            let suggestions = unresolved
+                .suggestions
+                .iter()
+                .map(|suggestion| format!("`{suggestion}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(prompt, "- `{text}`: the closest matches are {suggestions}").unwrap();
+        }
+    }
+    prompt.push_str("\nPlease correct your response to use the actual paths and symbols.");
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_find_references() {
+        let message = indoc! {"
+            Update `src/config.rs:42` and call `Config::load()` from `main.rs`.
+            It returns `true`, so check `is_valid` and `parseConfig`, not `config`.
+            ```rust
+            let x = `not_a_reference`;
+            ```
+            See `https://example.com/a/b`, `/usr/bin/env` and an unclosed `tick.
+        "};
+        let references = find_references(message)
+            .into_iter()
+            .map(|reference| {
+                assert_eq!(&message[reference.range], reference.text);
+                (reference.kind, reference.text, reference.name)
+            })
+            .collect::<Vec<_>>();
+        let expected = [
+            (ReferenceKind::Path, "src/config.rs:42", "src/config.rs"),
+            (ReferenceKind::Symbol, "Config::load()", "load"),
+            (ReferenceKind::Path, "main.rs", "main.rs"),
+            (ReferenceKind::Symbol, "is_valid", "is_valid"),
+            (ReferenceKind::Symbol, "parseConfig", "parseConfig"),
+        ]
+        .map(|(kind, text, name)| (kind, text.to_string(), name.to_string()));
+        assert_eq!(references, expected);
+    }
+
+    #[test]
+    fn test_correction_prompt() {
+        let message = "Edit `src/confg.rs` and call `load_cfg()`.";
+        let unresolved = find_references(message)
+            .into_iter()
+            .zip([vec!["src/config.rs".to_string()], vec![]])
+            .map(|(reference, suggestions)| UnresolvedReference {
+                reference,
+                suggestions,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            correction_prompt(&unresolved),
+            indoc! {"
+                Some of the paths and symbols in your response don't exist in the project:
+                - `src/confg.rs`: the closest matches are `src/config.rs`
+                - `load_cfg()`: no similar symbol was found
+
+                Please correct your response to use the actual paths and symbols."}
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use assistant_slash_commands::{LibrarySnippet, SnippetLibrary, SnippetOrigin};
+use chrono::Utc;
+use gpui::{DismissEvent, Entity, EventEmitter, FocusHandle, Focusable, prelude::*};
+use ui::{KeyBinding, Modal, ModalFooter, ModalHeader, Section, Tooltip, prelude::*};
+use ui_input::SingleLineInput;
+use workspace::ModalView;
+
+/// Asks for the name and tags of a code block before saving it to the
+/// [`SnippetLibrary`].
+pub struct SaveSnippetModal {
+    code: String,
+    language: Option<String>,
+    origin: SnippetOrigin,
+    name_editor: Entity<SingleLineInput>,
+    tags_editor: Entity<SingleLineInput>,
+}
+
+impl SaveSnippetModal {
+    pub fn new(
+        code: String,
+        language: Option<String>,
+        origin: SnippetOrigin,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let name_editor = cx.new(|cx| SingleLineInput::new(window, cx, "retry-loop").label("Name"));
+        let tags_editor = cx.new(|cx| {
+            SingleLineInput::new(window, cx, "async, http").label("Tags (comma-separated)")
+        });
+
+        Self {
+            code,
+            language,
+            origin,
+            name_editor,
+            tags_editor,
+        }
+    }
+
+    fn confirm(&mut self, _: &menu::Confirm, cx: &mut Context<Self>) {
+        let name = self
+            .name_editor
+            .read(cx)
+            .editor()
+            .read(cx)
+            .text(cx)
+            .trim()
+            .to_string();
+        if name.is_empty() {
+            return;
+        }
+        let tags = self.tags_editor.read(cx).editor().read(cx).text(cx);
+
+        SnippetLibrary::save(
+            LibrarySnippet {
+                name,
+                language: self.language.clone(),
+                tags: parse_tags(&tags),
+                code: self.code.clone(),
+                origin: Some(self.origin.clone()),
+                saved_at: Utc::now(),
+            },
+            cx,
+        );
+        cx.emit(DismissEvent);
+    }
+
+    fn cancel(&mut self, _: &menu::Cancel, cx: &mut Context<Self>) {
+        cx.emit(DismissEvent);
+    }
+}
+
+pub(crate) fn parse_tags(tags: &str) -> Vec<String> {
+    let mut parsed = Vec::<String>::new();
+    for tag in tags.split(',') {
+        let tag = tag.trim().trim_start_matches('#');
+        if !tag.is_empty() && !parsed.iter().any(|parsed| parsed == tag) {
+            parsed.push(tag.to_string());
+        }
+    }
+    parsed
+}
+
+impl ModalView for SaveSnippetModal {}
+
+impl Focusable for SaveSnippetModal {
+    fn focus_handle(&self, cx: &App) -> FocusHandle {
+        self.name_editor.focus_handle(cx).clone()
+    }
+}
+
+impl EventEmitter<DismissEvent> for SaveSnippetModal {}
+
+impl Render for SaveSnippetModal {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let is_name_empty = self.name_editor.read(cx).is_empty(cx);
+        let line_count = self.code.lines().count();
+        let description = match &self.language {
+            Some(language) => format!("{line_count} line(s) of {language}"),
+            None => format!("{line_count} line(s)"),
+        };
+
+        let focus_handle = self.focus_handle(cx);
+
+        div()
+            .elevation_3(cx)
+            .w(rems(34.))
+            .key_context("SaveSnippetModal")
+            .on_action(
+                cx.listener(|this, _: &menu::Cancel, _window, cx| this.cancel(&menu::Cancel, cx)),
+            )
+            .on_action(
+                cx.listener(|this, _: &menu::Confirm, _window, cx| {
+                    this.confirm(&menu::Confirm, cx)
+                }),
+            )
+            .capture_any_mouse_down(cx.listener(|this, _, window, cx| {
+                this.focus_handle(cx).focus(window);
+            }))
+            .on_mouse_down_out(cx.listener(|_this, _, _, cx| cx.emit(DismissEvent)))
+            .child(
+                Modal::new("save-snippet", None)
+                    .header(ModalHeader::new().headline("Save as Snippet"))
+                    .section(
+                        Section::new().meta(description).child(
+                            v_flex()
+                                .gap_2()
+                                .child(self.name_editor.clone())
+                                .child(self.tags_editor.clone()),
+                        ),
+                    )
+                    .footer(
+                        ModalFooter::new().end_slot(
+                            h_flex()
+                                .gap_2()
+                                .child(
+                                    Button::new("cancel", "Cancel")
+                                        .key_binding(
+                                            KeyBinding::for_action_in(
+                                                &menu::Cancel,
+                                                &focus_handle,
+                                                window,
+                                                cx,
+                                            )
+                                            .map(|kb| kb.size(rems_from_px(12.))),
+                                        )
+                                        .on_click(cx.listener(|this, _event, _window, cx| {
+                                            this.cancel(&menu::Cancel, cx)
+                                        })),
+                                )
+                                .child(
+                                    Button::new("save-snippet", "Save Snippet")
+                                        .disabled(is_name_empty)
+                                        .key_binding(
+                                            KeyBinding::for_action_in(
+                                                &menu::Confirm,
+                                                &focus_handle,
+                                                window,
+                                                cx,
+                                            )
+                                            .map(|kb| kb.size(rems_from_px(12.))),
+                                        )
+                                        .when(is_name_empty, |button| {
+                                            button.tooltip(Tooltip::text("Name is required"))
+                                        })
+                                        .on_click(cx.listener(|this, _event, _window, cx| {
+                                            this.confirm(&menu::Confirm, cx)
+                                        })),
+                                ),
+                        ),
+                    ),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(parse_tags(""), Vec::<String>::new());
+        assert_eq!(
+            parse_tags(" async, #http,,async ,retry "),
+            ["async", "http", "retry"]
+        );
+    }
+}
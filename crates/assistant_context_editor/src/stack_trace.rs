@@ -0,0 +1,285 @@
+use std::{
+    path::Path,
+    sync::{Arc, LazyLock},
+};
+
+use collections::HashSet;
+use gpui::{App, Entity};
+use project::{Project, ProjectPath};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// The number of frames whose surrounding code is attached to a pasted stack
+/// trace.
+pub const MAX_ENRICHED_FRAMES: usize = 3;
+
+static RUST_FRAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:panicked at |\bat )([^\s:'\x22()]+\.rs):(\d+)").unwrap());
+static PYTHON_FRAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"File "([^"]+)", line (\d+)"#).unwrap());
+static JAVASCRIPT_FRAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*at (?:.*?\()?([^\s()]+?):(\d+):\d+\)?\s*$").unwrap());
+static JAVA_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*at [\w$.<>/]+\(([\w$]+\.(?:java|kt|scala)):(\d+)\)").unwrap()
+});
+static GO_FRAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s+(\S+\.go):(\d+)").unwrap());
+
+/// The language runtime that produced a stack trace.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StackTraceKind {
+    Rust,
+    Python,
+    JavaScript,
+    Java,
+    Go,
+}
+
+impl StackTraceKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Rust => "Rust panic",
+            Self::Python => "Python traceback",
+            Self::JavaScript => "JavaScript stack trace",
+            Self::Java => "Java stack trace",
+            Self::Go => "Go panic",
+        }
+    }
+}
+
+/// Marks a message that contains a pasted stack trace, so that the request
+/// can include instructions for debugging it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StackTraceTag {
+    pub kind: StackTraceKind,
+    /// The `path:line` locations of the frames whose code was attached.
+    pub frames: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StackFrame {
+    pub path: String,
+    /// The 1-based line number of the frame.
+    pub line: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackTrace {
+    pub kind: StackTraceKind,
+    /// The frames, starting with the innermost one.
+    pub frames: Vec<StackFrame>,
+}
+
+pub struct ResolvedFrame {
+    pub project_path: ProjectPath,
+    pub full_path: String,
+    pub line: u32,
+}
+
+/// Parses the text as a stack trace, returning `None` if it doesn't look like
+/// one or doesn't reference any source locations.
+pub fn parse_stack_trace(text: &str) -> Option<StackTrace> {
+    let (kind, regex) = if text.contains("panicked at") || text.contains("stack backtrace:") {
+        (StackTraceKind::Rust, &*RUST_FRAME)
+    } else if text.contains("Traceback (most recent call last):") {
+        (StackTraceKind::Python, &*PYTHON_FRAME)
+    } else if text.contains("goroutine ") && GO_FRAME.is_match(text) {
+        (StackTraceKind::Go, &*GO_FRAME)
+    } else if JAVA_FRAME.find_iter(text).count() >= 2 {
+        (StackTraceKind::Java, &*JAVA_FRAME)
+    } else if JAVASCRIPT_FRAME.find_iter(text).count() >= 2 {
+        (StackTraceKind::JavaScript, &*JAVASCRIPT_FRAME)
+    } else {
+        return None;
+    };
+
+    let mut frames = regex
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let path = captures[1]
+                .trim_start_matches("file://")
+                .trim_start_matches("./");
+            Some(StackFrame {
+                path: path.to_string(),
+                line: captures[2].parse().ok()?,
+            })
+        })
+        .collect::<Vec<_>>();
+    // Python prints the innermost frame last.
+    if kind == StackTraceKind::Python {
+        frames.reverse();
+    }
+    let mut seen = HashSet::default();
+    frames.retain(|frame| seen.insert(frame.clone()));
+
+    if frames.is_empty() {
+        None
+    } else {
+        Some(StackTrace { kind, frames })
+    }
+}
+
+/// Resolves the innermost frames that point into the project, skipping frames
+/// in the standard library and dependencies.
+pub fn resolve_frames(
+    frames: &[StackFrame],
+    project: &Entity<Project>,
+    cx: &App,
+) -> Vec<ResolvedFrame> {
+    let project = project.read(cx);
+    frames
+        .iter()
+        .filter_map(|frame| {
+            let project_path = resolve_path(&frame.path, project, cx)?;
+            let worktree = project.worktree_for_id(project_path.worktree_id, cx)?;
+            let full_path = Path::new(worktree.read(cx).root_name())
+                .join(&project_path.path)
+                .to_string_lossy()
+                .into_owned();
+            Some(ResolvedFrame {
+                project_path,
+                full_path,
+                line: frame.line,
+            })
+        })
+        .take(MAX_ENRICHED_FRAMES)
+        .collect()
+}
+
+fn resolve_path(path: &str, project: &Project, cx: &App) -> Option<ProjectPath> {
+    let path = Path::new(path);
+    if let Some(project_path) = project.find_project_path(path, cx) {
+        let exists = project
+            .worktree_for_id(project_path.worktree_id, cx)
+            .map_or(false, |worktree| {
+                worktree
+                    .read(cx)
+                    .entry_for_path(&project_path.path)
+                    .map_or(false, |entry| entry.is_file())
+            });
+        if exists {
+            return Some(project_path);
+        }
+    }
+
+    // Paths in stack traces are often relative to a directory we don't know
+    // about, or just file names, so fall back to a unique suffix match.
+    if path.is_absolute() {
+        return None;
+    }
+    let mut matches = project.visible_worktrees(cx).flat_map(|worktree| {
+        let worktree = worktree.read(cx);
+        let worktree_id = worktree.id();
+        worktree
+            .files(false, 0)
+            .filter(|entry| entry.path.ends_with(path))
+            .map(|entry| ProjectPath {
+                worktree_id,
+                path: Arc::clone(&entry.path),
+            })
+            .take(2)
+            .collect::<Vec<_>>()
+    });
+    let project_path = matches.next()?;
+    matches.next().is_none().then_some(project_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use unindent::Unindent as _;
+
+    use super::*;
+
+    fn frames(stack_trace: &StackTrace) -> Vec<(&str, u32)> {
+        stack_trace
+            .frames
+            .iter()
+            .map(|frame| (frame.path.as_str(), frame.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_rust_panic() {
+        let stack_trace = parse_stack_trace(
+            &"
+            thread 'main' panicked at src/main.rs:4:5:
+            index out of bounds
+            stack backtrace:
+               0: rust_begin_unwind
+                         at /rustc/abc/library/std/src/panicking.rs:645:5
+               1: app::parse
+                         at ./src/parse.rs:12:9
+        "
+            .unindent(),
+        )
+        .unwrap();
+        assert_eq!(stack_trace.kind, StackTraceKind::Rust);
+        assert_eq!(
+            frames(&stack_trace),
+            [
+                ("src/main.rs", 4),
+                ("/rustc/abc/library/std/src/panicking.rs", 645),
+                ("src/parse.rs", 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_python_traceback() {
+        let stack_trace = parse_stack_trace(
+            &r#"
+            Traceback (most recent call last):
+              File "app/main.py", line 10, in <module>
+                run()
+              File "app/run.py", line 3, in run
+                1 / 0
+            ZeroDivisionError: division by zero
+        "#
+            .unindent(),
+        )
+        .unwrap();
+        assert_eq!(stack_trace.kind, StackTraceKind::Python);
+        assert_eq!(
+            frames(&stack_trace),
+            [("app/run.py", 3), ("app/main.py", 10)]
+        );
+    }
+
+    #[test]
+    fn test_parse_javascript_and_java_stack_traces() {
+        let stack_trace = parse_stack_trace(
+            &"
+            TypeError: x is undefined
+                at render (file:///home/me/app/src/view.js:20:7)
+                at src/index.js:5:1
+        "
+            .unindent(),
+        )
+        .unwrap();
+        assert_eq!(stack_trace.kind, StackTraceKind::JavaScript);
+        assert_eq!(
+            frames(&stack_trace),
+            [("/home/me/app/src/view.js", 20), ("src/index.js", 5)]
+        );
+
+        let stack_trace = parse_stack_trace(
+            &"
+            java.lang.NullPointerException
+                at com.example.Foo.bar(Foo.java:42)
+                at com.example.Main.main(Main.java:7)
+        "
+            .unindent(),
+        )
+        .unwrap();
+        assert_eq!(stack_trace.kind, StackTraceKind::Java);
+        assert_eq!(frames(&stack_trace), [("Foo.java", 42), ("Main.java", 7)]);
+    }
+
+    #[test]
+    fn test_parse_non_stack_trace() {
+        assert_eq!(parse_stack_trace("see src/main.rs:4 for details"), None);
+        assert_eq!(parse_stack_trace("fn main() {}"), None);
+    }
+}
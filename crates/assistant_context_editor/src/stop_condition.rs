@@ -0,0 +1,137 @@
+//! Stops a response once its text matches one of the text thread's stop
+//! conditions, for models whose stop sequences can't express them.
+
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use util::ResultExt as _;
+
+/// A pattern that ends the response once the streamed text matches it. The
+/// text up to the end of the match is kept.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopCondition {
+    Text(String),
+    Regex(String),
+}
+
+impl StopCondition {
+    /// Parses a condition written as `/pattern/` for a regex, and as the
+    /// text to match otherwise.
+    pub fn parse(input: &str) -> Result<Self> {
+        if let Some(pattern) = input
+            .strip_prefix('/')
+            .and_then(|input| input.strip_suffix('/'))
+            .filter(|pattern| !pattern.is_empty())
+        {
+            Regex::new(pattern)?;
+            return Ok(Self::Regex(pattern.to_string()));
+        }
+        Ok(Self::Text(input.to_string()))
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Self::Text(text) => format!("{text:?}"),
+            Self::Regex(pattern) => format!("/{pattern}/"),
+        }
+    }
+}
+
+/// Matches the stop conditions against a response as it streams in.
+pub struct StopMatcher {
+    texts: Vec<String>,
+    regexes: Vec<Regex>,
+    response: String,
+}
+
+impl StopMatcher {
+    pub fn new(conditions: &[StopCondition]) -> Self {
+        let mut texts = Vec::new();
+        let mut regexes = Vec::new();
+        for condition in conditions {
+            match condition {
+                StopCondition::Text(text) if !text.is_empty() => texts.push(text.clone()),
+                StopCondition::Text(_) => {}
+                StopCondition::Regex(pattern) => regexes.extend(Regex::new(pattern).log_err()),
+            }
+        }
+        Self {
+            texts,
+            regexes,
+            response: String::new(),
+        }
+    }
+
+    /// Appends a chunk of the response, and returns the length of the part of
+    /// the chunk to keep if a condition now matches.
+    pub fn push(&mut self, chunk: &str) -> Option<usize> {
+        if self.texts.is_empty() && self.regexes.is_empty() {
+            return None;
+        }
+        let previous_len = self.response.len();
+        self.response.push_str(chunk);
+
+        let mut match_end = None::<usize>;
+        for text in &self.texts {
+            // Only matches that end in the new chunk are new.
+            let mut search_start = previous_len.saturating_sub(text.len() - 1);
+            while !self.response.is_char_boundary(search_start) {
+                search_start -= 1;
+            }
+            if let Some(ix) = self.response[search_start..].find(text.as_str()) {
+                let end = search_start + ix + text.len();
+                match_end = Some(match_end.map_or(end, |match_end| match_end.min(end)));
+            }
+        }
+        for regex in &self.regexes {
+            if let Some(end) = regex
+                .find_iter(&self.response)
+                .map(|regex_match| regex_match.end())
+                .find(|end| *end > previous_len)
+            {
+                match_end = Some(match_end.map_or(end, |match_end| match_end.min(end)));
+            }
+        }
+        match_end.map(|end| end - previous_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stop_condition() {
+        assert_eq!(
+            StopCondition::parse("END").unwrap(),
+            StopCondition::Text("END".into())
+        );
+        assert_eq!(
+            StopCondition::parse(r"/^\}$/").unwrap(),
+            StopCondition::Regex(r"^\}$".into())
+        );
+        assert_eq!(
+            StopCondition::parse("/").unwrap(),
+            StopCondition::Text("/".into())
+        );
+        assert!(StopCondition::parse("/(/").is_err());
+    }
+
+    #[test]
+    fn test_stop_matcher() {
+        let mut matcher = StopMatcher::new(&[StopCondition::Text("</answer>".into())]);
+        assert_eq!(matcher.push("The answer is 42.</ans"), None);
+        assert_eq!(matcher.push("wer> And more"), Some("wer>".len()));
+
+        let mut matcher = StopMatcher::new(&[
+            StopCondition::Text("never".into()),
+            StopCondition::Regex(r"(?m)^\}$".into()),
+        ]);
+        assert_eq!(matcher.push("fn main() {\n    println!(\"}\");\n"), None);
+        assert_eq!(matcher.push("}\nfn other() {}"), Some(1));
+
+        let mut matcher = StopMatcher::new(&[]);
+        assert_eq!(matcher.push("anything"), None);
+    }
+}
@@ -0,0 +1,178 @@
+use gpui::{DismissEvent, Entity, EventEmitter, FocusHandle, Focusable, Subscription, prelude::*};
+use ui::{KeyBinding, ListItem, Modal, ModalFooter, ModalHeader, Section, Tooltip, prelude::*};
+use ui_input::SingleLineInput;
+use workspace::ModalView;
+
+use crate::AssistantContext;
+use crate::stop_condition::StopCondition;
+
+/// Edits the conditions that stop a text thread's responses.
+pub struct StopConditionsModal {
+    context: Entity<AssistantContext>,
+    condition_editor: Entity<SingleLineInput>,
+    error: Option<SharedString>,
+    _subscription: Subscription,
+}
+
+impl StopConditionsModal {
+    pub fn new(
+        context: Entity<AssistantContext>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let condition_editor = cx.new(|cx| {
+            SingleLineInput::new(window, cx, "</answer> or /^\\}$/").label("Stop When Matching")
+        });
+        let subscription = cx.observe(&context, |_, _, cx| cx.notify());
+
+        Self {
+            context,
+            condition_editor,
+            error: None,
+            _subscription: subscription,
+        }
+    }
+
+    fn confirm(&mut self, _: &menu::Confirm, window: &mut Window, cx: &mut Context<Self>) {
+        let input = self.condition_editor.read(cx).editor().read(cx).text(cx);
+        if input.is_empty() {
+            return;
+        }
+        let condition = match StopCondition::parse(&input) {
+            Ok(condition) => condition,
+            Err(error) => {
+                self.error = Some(format!("Invalid regex: {error}").into());
+                cx.notify();
+                return;
+            }
+        };
+
+        self.context
+            .update(cx, |context, cx| context.add_stop_condition(condition, cx));
+        self.error = None;
+        self.condition_editor
+            .read(cx)
+            .editor()
+            .clone()
+            .update(cx, |editor, cx| editor.clear(window, cx));
+    }
+
+    fn cancel(&mut self, _: &menu::Cancel, cx: &mut Context<Self>) {
+        cx.emit(DismissEvent);
+    }
+
+    fn render_conditions(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let conditions = self.context.read(cx).stop_conditions().to_vec();
+        if conditions.is_empty() {
+            return v_flex().child(
+                Label::new("No stop conditions")
+                    .size(LabelSize::Small)
+                    .color(Color::Muted),
+            );
+        }
+
+        v_flex().children(conditions.into_iter().enumerate().map(|(ix, condition)| {
+            ListItem::new(("stop-condition", ix))
+                .child(
+                    Label::new(condition.label())
+                        .size(LabelSize::Small)
+                        .truncate(),
+                )
+                .end_slot(
+                    IconButton::new(("remove-stop-condition", ix), IconName::Trash)
+                        .icon_size(IconSize::Small)
+                        .icon_color(Color::Muted)
+                        .tooltip(Tooltip::text("Remove"))
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            this.context
+                                .update(cx, |context, cx| context.remove_stop_condition(ix, cx));
+                        })),
+                )
+        }))
+    }
+}
+
+impl ModalView for StopConditionsModal {}
+
+impl Focusable for StopConditionsModal {
+    fn focus_handle(&self, cx: &App) -> FocusHandle {
+        self.condition_editor.focus_handle(cx).clone()
+    }
+}
+
+impl EventEmitter<DismissEvent> for StopConditionsModal {}
+
+impl Render for StopConditionsModal {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let is_empty = self.condition_editor.read(cx).is_empty(cx);
+        let focus_handle = self.focus_handle(cx);
+
+        div()
+            .elevation_3(cx)
+            .w(rems(34.))
+            .key_context("StopConditionsModal")
+            .on_action(
+                cx.listener(|this, _: &menu::Cancel, _window, cx| this.cancel(&menu::Cancel, cx)),
+            )
+            .on_action(cx.listener(|this, _: &menu::Confirm, window, cx| {
+                this.confirm(&menu::Confirm, window, cx)
+            }))
+            .on_mouse_down_out(cx.listener(|_this, _, _, cx| cx.emit(DismissEvent)))
+            .child(
+                Modal::new("stop-conditions", None)
+                    .header(ModalHeader::new().headline("Stop Conditions"))
+                    .section(
+                        Section::new()
+                            .meta(
+                                "Responses stop once their text matches one of these. \
+                                Write regexes between slashes.",
+                            )
+                            .child(self.render_conditions(cx)),
+                    )
+                    .section(
+                        Section::new()
+                            .child(self.condition_editor.clone())
+                            .children(self.error.clone().map(|error| {
+                                Label::new(error).size(LabelSize::Small).color(Color::Error)
+                            })),
+                    )
+                    .footer(
+                        ModalFooter::new().end_slot(
+                            h_flex()
+                                .gap_2()
+                                .child(
+                                    Button::new("done", "Done")
+                                        .key_binding(
+                                            KeyBinding::for_action_in(
+                                                &menu::Cancel,
+                                                &focus_handle,
+                                                window,
+                                                cx,
+                                            )
+                                            .map(|kb| kb.size(rems_from_px(12.))),
+                                        )
+                                        .on_click(cx.listener(|this, _event, _window, cx| {
+                                            this.cancel(&menu::Cancel, cx)
+                                        })),
+                                )
+                                .child(
+                                    Button::new("add-stop-condition", "Add Condition")
+                                        .disabled(is_empty)
+                                        .key_binding(
+                                            KeyBinding::for_action_in(
+                                                &menu::Confirm,
+                                                &focus_handle,
+                                                window,
+                                                cx,
+                                            )
+                                            .map(|kb| kb.size(rems_from_px(12.))),
+                                        )
+                                        .on_click(cx.listener(|this, _event, window, cx| {
+                                            this.confirm(&menu::Confirm, window, cx)
+                                        })),
+                                ),
+                        ),
+                    ),
+            )
+    }
+}
@@ -0,0 +1,176 @@
+/// Buffers streamed Markdown so that incomplete structures aren't displayed
+/// until they are complete.
+///
+/// Inserting a code fence before its closing fence has been streamed causes
+/// everything after it to be highlighted as code and fold placeholders to be
+/// recomputed on every chunk. Similarly, half-streamed links flicker between
+/// plain text and link syntax. This holds back open code fences and partial
+/// links until they complete, while every byte pushed is eventually returned,
+/// either by [`StreamingMarkdown::push`] or [`StreamingMarkdown::finish`].
+#[derive(Debug, Default)]
+pub struct StreamingMarkdown {
+    pending: String,
+    /// Length of the prefix of `pending` made of complete lines that were
+    /// already scanned.
+    scanned_len: usize,
+    /// The marker of the code fence that is currently open, if any.
+    open_fence: Option<String>,
+    /// Whether part of the line at the start of `pending` was already returned.
+    mid_line: bool,
+}
+
+impl StreamingMarkdown {
+    /// Appends a chunk of streamed text, returning the text that is now safe to display.
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.pending.push_str(chunk);
+
+        let mut ready_len = 0;
+        let mut line_start = self.scanned_len;
+        while let Some(newline_ix) = self.pending[line_start..].find('\n') {
+            let line_end = line_start + newline_ix + 1;
+            let line = &self.pending[line_start..line_end - 1];
+            let continues_returned_line = line_start == 0 && self.mid_line;
+            if let Some(fence) = &self.open_fence {
+                if is_closing_fence(line, fence) {
+                    self.open_fence = None;
+                    ready_len = line_end;
+                }
+            } else if let Some(fence) = opening_fence(line).filter(|_| !continues_returned_line) {
+                self.open_fence = Some(fence);
+            } else {
+                ready_len = line_end;
+            }
+            line_start = line_end;
+        }
+
+        if self.open_fence.is_some() {
+            self.scanned_len = line_start - ready_len;
+            self.mid_line = false;
+        } else {
+            let continues_returned_line = line_start == 0 && self.mid_line;
+            let partial_line = &self.pending[line_start..];
+            ready_len = line_start + safe_partial_line_len(partial_line, continues_returned_line);
+            self.scanned_len = 0;
+            self.mid_line = ready_len > line_start || continues_returned_line;
+        }
+
+        self.pending.drain(..ready_len).collect()
+    }
+
+    /// Returns all the text that is still being held back.
+    pub fn finish(&mut self) -> String {
+        self.scanned_len = 0;
+        self.open_fence = None;
+        self.mid_line = false;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+fn strip_indentation(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start_matches(' ');
+    (line.len() - trimmed.len() <= 3).then_some(trimmed)
+}
+
+fn opening_fence(line: &str) -> Option<String> {
+    let line = strip_indentation(line)?;
+    let fence_char = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let fence_len = line.chars().take_while(|c| *c == fence_char).count();
+    if fence_len < 3 {
+        return None;
+    }
+    // Backtick fences can't contain backticks in their info string.
+    if fence_char == '`' && line[fence_len..].contains('`') {
+        return None;
+    }
+    Some(line[..fence_len].to_string())
+}
+
+fn is_closing_fence(line: &str, fence: &str) -> bool {
+    let Some(line) = strip_indentation(line) else {
+        return false;
+    };
+    let Some(fence_char) = fence.chars().next() else {
+        return false;
+    };
+    let fence_len = line.chars().take_while(|c| *c == fence_char).count();
+    fence_len >= fence.len() && line[fence_len..].trim().is_empty()
+}
+
+/// Returns how much of a line that hasn't been fully streamed yet can be displayed.
+fn safe_partial_line_len(line: &str, continues_returned_line: bool) -> usize {
+    // Hold back lines that might turn out to be a code fence.
+    if !continues_returned_line {
+        if let Some(stripped) = strip_indentation(line) {
+            if stripped.starts_with('`') || stripped.starts_with('~') {
+                return 0;
+            }
+        }
+    }
+
+    // Hold back links whose destination hasn't been closed yet.
+    if let Some(link_start) = line.rfind('[') {
+        if !line[link_start..].contains(')') {
+            return link_start;
+        }
+    }
+
+    line.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(chunks: &[&str]) -> Vec<String> {
+        let mut markdown = StreamingMarkdown::default();
+        let mut output = chunks
+            .iter()
+            .map(|chunk| markdown.push(chunk))
+            .collect::<Vec<_>>();
+        output.push(markdown.finish());
+        output
+    }
+
+    #[test]
+    fn test_plain_text_is_not_held_back() {
+        assert_eq!(
+            stream(&["Hello", ", world", "!\n"]),
+            ["Hello", ", world", "!\n", ""]
+        );
+    }
+
+    #[test]
+    fn test_code_fences_are_held_back_until_closed() {
+        assert_eq!(
+            stream(&["Some code:\n``", "`rust\nfn main() {}\n", "``", "`\nDone"]),
+            [
+                "Some code:\n",
+                "",
+                "",
+                "```rust\nfn main() {}\n```\nDone",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn test_longer_fences_require_a_matching_close() {
+        assert_eq!(
+            stream(&["````\n```\n", "````\n"]),
+            ["", "````\n```\n````\n", ""]
+        );
+    }
+
+    #[test]
+    fn test_links_are_held_back_until_complete() {
+        assert_eq!(
+            stream(&["See [the docs", "](https://zed.dev", ") for more"]),
+            ["See ", "", "[the docs](https://zed.dev) for more", ""]
+        );
+    }
+
+    #[test]
+    fn test_finish_flushes_unterminated_structures() {
+        assert_eq!(stream(&["```\nunterminated"]), ["", "```\nunterminated"]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use gpui::{DismissEvent, Entity, EventEmitter, FocusHandle, Focusable, prelude::*};
+use ui::{KeyBinding, Modal, ModalFooter, ModalHeader, Section, prelude::*};
+use ui_input::SingleLineInput;
+use workspace::ModalView;
+
+use crate::ContextStore;
+use crate::save_snippet_modal::parse_tags;
+
+/// Asks for the tags to add to the text threads selected in the history.
+pub struct TagContextsModal {
+    store: Entity<ContextStore>,
+    paths: Vec<Arc<Path>>,
+    tags_editor: Entity<SingleLineInput>,
+}
+
+impl TagContextsModal {
+    pub fn new(
+        store: Entity<ContextStore>,
+        paths: Vec<Arc<Path>>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let tags_editor = cx.new(|cx| {
+            SingleLineInput::new(window, cx, "bugs, design").label("Tags (comma-separated)")
+        });
+        Self {
+            store,
+            paths,
+            tags_editor,
+        }
+    }
+
+    fn confirm(&mut self, _: &menu::Confirm, cx: &mut Context<Self>) {
+        let tags = parse_tags(&self.tags_editor.read(cx).editor().read(cx).text(cx));
+        if tags.is_empty() {
+            return;
+        }
+        let paths = self.paths.clone();
+        self.store
+            .update(cx, |store, cx| store.add_tags(paths, tags, cx))
+            .detach_and_log_err(cx);
+        cx.emit(DismissEvent);
+    }
+
+    fn cancel(&mut self, _: &menu::Cancel, cx: &mut Context<Self>) {
+        cx.emit(DismissEvent);
+    }
+}
+
+impl ModalView for TagContextsModal {}
+
+impl Focusable for TagContextsModal {
+    fn focus_handle(&self, cx: &App) -> FocusHandle {
+        self.tags_editor.focus_handle(cx).clone()
+    }
+}
+
+impl EventEmitter<DismissEvent> for TagContextsModal {}
+
+impl Render for TagContextsModal {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let is_empty = self.tags_editor.read(cx).is_empty(cx);
+        let description = match self.paths.len() {
+            1 => "1 text thread".to_string(),
+            count => format!("{count} text threads"),
+        };
+
+        let focus_handle = self.focus_handle(cx);
+
+        div()
+            .elevation_3(cx)
+            .w(rems(34.))
+            .key_context("TagContextsModal")
+            .on_action(
+                cx.listener(|this, _: &menu::Cancel, _window, cx| this.cancel(&menu::Cancel, cx)),
+            )
+            .on_action(
+                cx.listener(|this, _: &menu::Confirm, _window, cx| {
+                    this.confirm(&menu::Confirm, cx)
+                }),
+            )
+            .capture_any_mouse_down(cx.listener(|this, _, window, cx| {
+                this.focus_handle(cx).focus(window);
+            }))
+            .on_mouse_down_out(cx.listener(|_this, _, _, cx| cx.emit(DismissEvent)))
+            .child(
+                Modal::new("tag-contexts", None)
+                    .header(ModalHeader::new().headline("Add Tags"))
+                    .section(
+                        Section::new()
+                            .meta(description)
+                            .child(self.tags_editor.clone()),
+                    )
+                    .footer(
+                        ModalFooter::new().end_slot(
+                            h_flex()
+                                .gap_2()
+                                .child(
+                                    Button::new("cancel", "Cancel")
+                                        .key_binding(
+                                            KeyBinding::for_action_in(
+                                                &menu::Cancel,
+                                                &focus_handle,
+                                                window,
+                                                cx,
+                                            )
+                                            .map(|kb| kb.size(rems_from_px(12.))),
+                                        )
+                                        .on_click(cx.listener(|this, _event, _window, cx| {
+                                            this.cancel(&menu::Cancel, cx)
+                                        })),
+                                )
+                                .child(
+                                    Button::new("add-tags", "Add Tags")
+                                        .disabled(is_empty)
+                                        .key_binding(
+                                            KeyBinding::for_action_in(
+                                                &menu::Confirm,
+                                                &focus_handle,
+                                                window,
+                                                cx,
+                                            )
+                                            .map(|kb| kb.size(rems_from_px(12.))),
+                                        )
+                                        .on_click(cx.listener(|this, _event, _window, cx| {
+                                            this.confirm(&menu::Confirm, cx)
+                                        })),
+                                ),
+                        ),
+                    ),
+            )
+    }
+}
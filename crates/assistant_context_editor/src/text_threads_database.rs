@@ -0,0 +1,892 @@
+use crate::context_storage::{StoredContext, TextThreadStorageUsage};
+use crate::{SavedContext, SavedContextMetadata};
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use futures::channel::mpsc;
+use futures::future::{self, BoxFuture, Shared};
+use futures::{FutureExt as _, StreamExt as _};
+use gpui::{App, BackgroundExecutor, Global, ReadGlobal, Task};
+use indoc::indoc;
+use paths::contexts_dir;
+use regex::Regex;
+use sqlez::connection::Connection;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Matches the end of the paths that identify text threads, which is where the
+/// disambiguating number that follows the title goes.
+static TEXT_THREAD_PATH_SUFFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r" - \d+.zed.json$").unwrap());
+
+/// The extension of the files that held the large slash command outputs of
+/// text threads, before text threads were stored in the database.
+const SIDECAR_EXTENSION: &str = "json.outputs";
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+struct GlobalTextThreadsDatabase(
+    Shared<BoxFuture<'static, Result<Arc<TextThreadsDatabase>, Arc<anyhow::Error>>>>,
+);
+
+impl Global for GlobalTextThreadsDatabase {}
+
+/// Stores saved text threads in a SQLite database.
+///
+/// Text threads are still identified by the path they were saved at when they
+/// were stored as individual files in the contexts directory, so that
+/// references to them, such as the recently opened entries and `@thread`
+/// mentions, keep working after they're migrated.
+pub struct TextThreadsDatabase {
+    executor: BackgroundExecutor,
+    connection: Arc<Mutex<Connection>>,
+    change_listeners: Arc<Mutex<Vec<mpsc::UnboundedSender<()>>>>,
+}
+
+impl TextThreadsDatabase {
+    pub fn global_future(
+        cx: &App,
+    ) -> Shared<BoxFuture<'static, Result<Arc<TextThreadsDatabase>, Arc<anyhow::Error>>>> {
+        GlobalTextThreadsDatabase::global(cx).0.clone()
+    }
+
+    pub(crate) fn init(cx: &mut App) {
+        let executor = cx.background_executor().clone();
+        let database_future = executor
+            .spawn({
+                let executor = executor.clone();
+                let database_dir = paths::data_dir().join("text_threads");
+                async move { TextThreadsDatabase::new(database_dir, executor) }
+            })
+            .then(|result| future::ready(result.map(Arc::new).map_err(Arc::new)))
+            .boxed()
+            .shared();
+
+        cx.set_global(GlobalTextThreadsDatabase(database_future));
+    }
+
+    pub fn new(database_dir: PathBuf, executor: BackgroundExecutor) -> Result<Self> {
+        std::fs::create_dir_all(&database_dir)?;
+        let sqlite_path = database_dir.join("text_threads.db");
+        let connection = Connection::open_file(&sqlite_path.to_string_lossy());
+        let database = Self::open(connection, executor.clone())?;
+
+        let connection = database.connection.clone();
+        let change_listeners = database.change_listeners.clone();
+        executor
+            .spawn(async move {
+                let migrated = Self::migrate_from_files(&connection, contexts_dir())?;
+                if migrated > 0 {
+                    log::info!("migrated {migrated} text threads to text_threads.db");
+                    Self::notify_changed(&change_listeners);
+                }
+                anyhow::Ok(())
+            })
+            .detach();
+
+        Ok(database)
+    }
+
+    fn open(connection: Connection, executor: BackgroundExecutor) -> Result<Self> {
+        connection.exec(indoc! {"
+            CREATE TABLE IF NOT EXISTS text_threads (
+                path BLOB PRIMARY KEY,
+                context_id TEXT,
+                title TEXT NOT NULL,
+                saved_at TEXT NOT NULL,
+                tags TEXT NOT NULL DEFAULT '[]',
+                data BLOB NOT NULL,
+                outputs BLOB,
+                project TEXT,
+                archived INTEGER NOT NULL DEFAULT 0,
+                deleted_at TEXT
+            )
+        "})?()
+        .context("failed to create text_threads table")?;
+        // Databases created before text threads were grouped by project, or
+        // could be moved to the trash, don't have these columns.
+        for (column, definition) in [
+            ("project", "TEXT"),
+            ("archived", "INTEGER NOT NULL DEFAULT 0"),
+            ("deleted_at", "TEXT"),
+        ] {
+            let has_column = connection.select_row_bound::<&str, bool>(
+                "SELECT 1 FROM pragma_table_info('text_threads') WHERE name = ?",
+            )?(column)?
+            .is_some();
+            if !has_column {
+                connection.exec(&format!(
+                    "ALTER TABLE text_threads ADD COLUMN {column} {definition}"
+                ))?()
+                .with_context(|| format!("failed to add {column} column to text_threads"))?;
+            }
+        }
+        connection.exec(indoc! {"
+            CREATE INDEX IF NOT EXISTS text_threads_by_context_id ON text_threads (context_id)
+        "})?()
+        .context("failed to create text_threads_by_context_id index")?;
+        connection.exec(indoc! {"
+            CREATE VIRTUAL TABLE IF NOT EXISTS text_threads_search
+            USING fts5(path UNINDEXED, title, body)
+        "})?()
+        .context("failed to create text_threads_search table")?;
+        connection.exec(indoc! {"
+            CREATE TABLE IF NOT EXISTS migrated_text_thread_files (
+                path BLOB PRIMARY KEY
+            )
+        "})?()
+        .context("failed to create migrated_text_thread_files table")?;
+
+        Ok(Self {
+            executor,
+            connection: Arc::new(Mutex::new(connection)),
+            change_listeners: Arc::default(),
+        })
+    }
+
+    /// Imports the text threads that were saved as individual files in the
+    /// contexts directory. The files are left in place, and each one is only
+    /// imported once, so that deleting a text thread doesn't bring it back.
+    fn migrate_from_files(connection: &Arc<Mutex<Connection>>, dir: &Path) -> Result<usize> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(error) => return Err(error.into()),
+        };
+
+        let connection = connection.lock().unwrap();
+        let mut is_migrated = connection.select_row_bound::<&Path, bool>(indoc! {"
+            SELECT 1 FROM migrated_text_thread_files WHERE path = ?
+        "})?;
+        let mut migrated = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension() != Some(OsStr::new("json"))
+                || title_for_path(&path).is_none()
+                || is_migrated(path.as_path())?.is_some()
+            {
+                continue;
+            }
+
+            let result = connection.with_savepoint("migrate_text_thread", || {
+                let json = std::fs::read_to_string(&path)?;
+                let saved_context = SavedContext::from_json(&json)?;
+                let outputs = if saved_context.compacted_sections.is_empty() {
+                    None
+                } else {
+                    Some(std::fs::read_to_string(
+                        path.with_extension(SIDECAR_EXTENSION),
+                    )?)
+                };
+                let saved_at = DateTime::<Utc>::from(std::fs::metadata(&path)?.modified()?);
+                let title = title_for_path(&path).unwrap_or_default().to_string();
+                Self::insert(
+                    &connection,
+                    &path,
+                    &title,
+                    saved_at,
+                    "[]",
+                    false,
+                    &saved_context,
+                    outputs.as_deref(),
+                )?;
+                connection.exec_bound::<&Path>(indoc! {"
+                    INSERT INTO migrated_text_thread_files (path) VALUES (?)
+                "})?(path.as_path())
+            });
+            match result {
+                Ok(()) => migrated += 1,
+                Err(error) => log::error!("failed to migrate text thread {path:?}: {error:#}"),
+            }
+        }
+        Ok(migrated)
+    }
+
+    /// Returns a receiver that's notified whenever a text thread is saved or
+    /// deleted, by any project.
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<()> {
+        let (tx, rx) = mpsc::unbounded();
+        self.change_listeners.lock().unwrap().push(tx);
+        rx
+    }
+
+    fn notify_changed(change_listeners: &Mutex<Vec<mpsc::UnboundedSender<()>>>) {
+        change_listeners
+            .lock()
+            .unwrap()
+            .retain(|listener| listener.unbounded_send(()).is_ok());
+    }
+
+    /// Lists the saved text threads, except for the ones in the trash.
+    pub fn list(&self) -> Task<Result<Vec<SavedContextMetadata>>> {
+        self.list_where("deleted_at IS NULL")
+    }
+
+    /// Lists the text threads in the trash.
+    pub fn list_trash(&self) -> Task<Result<Vec<SavedContextMetadata>>> {
+        self.list_where("deleted_at IS NOT NULL")
+    }
+
+    fn list_where(&self, condition: &'static str) -> Task<Result<Vec<SavedContextMetadata>>> {
+        let connection = self.connection.clone();
+        self.executor.spawn(async move {
+            let connection = connection.lock().unwrap();
+            let mut select = connection
+                .select::<(Arc<Path>, String, String, String, Option<String>, bool)>(&format!(
+                    "SELECT path, title, saved_at, tags, project, archived FROM text_threads \
+                     WHERE {condition} ORDER BY saved_at DESC"
+                ))?;
+
+            select()?
+                .into_iter()
+                .map(|(path, title, saved_at, tags, project, archived)| {
+                    Ok(SavedContextMetadata {
+                        title,
+                        path,
+                        mtime: DateTime::parse_from_rfc3339(&saved_at)?.into(),
+                        tags: serde_json::from_str(&tags)?,
+                        project,
+                        archived,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    pub fn load(&self, path: Arc<Path>) -> Task<Result<SavedContext>> {
+        let connection = self.connection.clone();
+        self.executor.spawn(async move {
+            let (data, outputs) = {
+                let connection = connection.lock().unwrap();
+                let mut select = connection
+                    .select_row_bound::<Arc<Path>, (Vec<u8>, Option<Vec<u8>>)>(indoc! {"
+                        SELECT data, outputs FROM text_threads WHERE path = ?
+                    "})?;
+                select(path.clone())?.with_context(|| format!("no text thread at {path:?}"))?
+            };
+
+            let json = String::from_utf8(zstd::decode_all(&data[..])?)?;
+            let mut saved_context = SavedContext::from_json(&json)?;
+            if let Some(outputs) = outputs {
+                let outputs = zstd::decode_all(&outputs[..])?;
+                saved_context.restore(serde_json::from_slice(&outputs)?)?;
+            }
+            Ok(saved_context)
+        })
+    }
+
+    /// Saves a text thread under the given title, moving slash command outputs
+    /// larger than `compaction_threshold` bytes into a separate column so that
+    /// they aren't indexed for search. Returns the path that identifies the
+    /// text thread, which only changes when its title does.
+    ///
+    /// The text thread is looked up by its id when it has one, so that saves
+    /// from replicas of it that are open in several windows don't create
+    /// copies of it, even when they still refer to its previous path.
+    pub fn save(
+        &self,
+        old_path: Option<Arc<Path>>,
+        title: String,
+        mut saved_context: SavedContext,
+        compaction_threshold: Option<usize>,
+    ) -> Task<Result<Arc<Path>>> {
+        let connection = self.connection.clone();
+        let change_listeners = self.change_listeners.clone();
+        self.executor.spawn(async move {
+            let outputs = compaction_threshold
+                .map(|threshold| saved_context.compact(threshold))
+                .unwrap_or_default();
+            let outputs = (!outputs.is_empty())
+                .then(|| serde_json::to_string(&outputs))
+                .transpose()?;
+
+            let path = {
+                let connection = connection.lock().unwrap();
+                connection.with_savepoint("save_text_thread", || {
+                    let title = title.trim();
+                    let old_path = match saved_context.id.as_ref() {
+                        Some(context_id) => {
+                            let mut select_path = connection
+                                .select_row_bound::<String, Arc<Path>>(
+                                    "SELECT path FROM text_threads WHERE context_id = ?",
+                                )?;
+                            select_path(context_id.to_proto())?.or(old_path)
+                        }
+                        None => old_path,
+                    };
+                    let path = match old_path
+                        .clone()
+                        .filter(|path| title_for_path(path) == Some(title))
+                    {
+                        Some(path) => path,
+                        None => Self::available_path(&connection, title)?,
+                    };
+
+                    let mut tags = String::from("[]");
+                    let mut archived = false;
+                    if let Some(old_path) = old_path.as_ref() {
+                        let mut select_tags = connection
+                            .select_row_bound::<&Path, (String, bool)>(
+                                "SELECT tags, archived FROM text_threads WHERE path = ?",
+                            )?;
+                        if let Some((old_tags, old_archived)) = select_tags(&**old_path)? {
+                            tags = old_tags;
+                            archived = old_archived;
+                        }
+                        if old_path != &path {
+                            Self::delete_sync(&connection, old_path)?;
+                        }
+                    }
+
+                    Self::insert(
+                        &connection,
+                        &path,
+                        title,
+                        Utc::now(),
+                        &tags,
+                        archived,
+                        &saved_context,
+                        outputs.as_deref(),
+                    )?;
+                    Ok(path)
+                })?
+            };
+            Self::notify_changed(&change_listeners);
+            Ok(path)
+        })
+    }
+
+    fn available_path(connection: &Connection, title: &str) -> Result<Arc<Path>> {
+        let mut exists = connection
+            .select_row_bound::<&Path, bool>("SELECT 1 FROM text_threads WHERE path = ?")?;
+        let mut discriminant = 1;
+        loop {
+            let path = contexts_dir().join(format!("{title} - {discriminant}.zed.json"));
+            if exists(path.as_path())?.is_none() {
+                return Ok(path.into());
+            }
+            discriminant += 1;
+        }
+    }
+
+    fn insert(
+        connection: &Connection,
+        path: &Path,
+        title: &str,
+        saved_at: DateTime<Utc>,
+        tags: &str,
+        archived: bool,
+        saved_context: &SavedContext,
+        outputs: Option<&str>,
+    ) -> Result<()> {
+        let data = zstd::encode_all(
+            serde_json::to_string(saved_context)?.as_bytes(),
+            COMPRESSION_LEVEL,
+        )?;
+        let outputs = outputs
+            .map(|outputs| zstd::encode_all(outputs.as_bytes(), COMPRESSION_LEVEL))
+            .transpose()?;
+
+        let context_id = saved_context.id.as_ref().map(|id| id.to_proto());
+        connection.exec_bound::<(
+            &Path,
+            Option<String>,
+            &str,
+            String,
+            &str,
+            Vec<u8>,
+            Option<Vec<u8>>,
+            Option<&str>,
+            bool,
+        )>(indoc! {"
+            INSERT OR REPLACE INTO text_threads
+                (path, context_id, title, saved_at, tags, data, outputs, project, archived)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "})?((
+            path,
+            context_id,
+            title,
+            saved_at.to_rfc3339(),
+            tags,
+            data,
+            outputs,
+            saved_context.project.as_deref(),
+            archived,
+        ))?;
+        connection.exec_bound::<&Path>("DELETE FROM text_threads_search WHERE path = ?")?(path)?;
+        connection.exec_bound::<(&Path, &str, &str)>(indoc! {"
+            INSERT INTO text_threads_search (path, title, body) VALUES (?, ?, ?)
+        "})?((path, title, &saved_context.text))?;
+        Ok(())
+    }
+
+    pub fn delete(&self, path: Arc<Path>) -> Task<Result<()>> {
+        let connection = self.connection.clone();
+        let change_listeners = self.change_listeners.clone();
+        self.executor.spawn(async move {
+            {
+                let connection = connection.lock().unwrap();
+                connection.with_savepoint("delete_text_thread", || {
+                    Self::delete_sync(&connection, &path)
+                })?;
+            }
+            Self::notify_changed(&change_listeners);
+            Ok(())
+        })
+    }
+
+    fn delete_sync(connection: &Connection, path: &Path) -> Result<()> {
+        connection.exec_bound::<&Path>("DELETE FROM text_threads WHERE path = ?")?(path)?;
+        connection.exec_bound::<&Path>("DELETE FROM text_threads_search WHERE path = ?")?(path)
+    }
+
+    pub fn set_tags(&self, path: Arc<Path>, tags: Vec<String>) -> Task<Result<()>> {
+        let connection = self.connection.clone();
+        let change_listeners = self.change_listeners.clone();
+        self.executor.spawn(async move {
+            let tags = serde_json::to_string(&tags)?;
+            connection
+                .lock()
+                .unwrap()
+                .exec_bound::<(String, Arc<Path>)>(
+                    "UPDATE text_threads SET tags = ? WHERE path = ?",
+                )?((tags, path))?;
+            Self::notify_changed(&change_listeners);
+            Ok(())
+        })
+    }
+
+    /// Moves the text threads at the given paths to the trash, from which
+    /// they can be restored until [`Self::empty_trash`] deletes them.
+    pub fn trash(&self, paths: Vec<Arc<Path>>) -> Task<Result<()>> {
+        self.set_deleted_at(paths, Some(Utc::now().to_rfc3339()))
+    }
+
+    pub fn restore(&self, paths: Vec<Arc<Path>>) -> Task<Result<()>> {
+        self.set_deleted_at(paths, None)
+    }
+
+    fn set_deleted_at(
+        &self,
+        paths: Vec<Arc<Path>>,
+        deleted_at: Option<String>,
+    ) -> Task<Result<()>> {
+        let connection = self.connection.clone();
+        let change_listeners = self.change_listeners.clone();
+        self.executor.spawn(async move {
+            {
+                let connection = connection.lock().unwrap();
+                connection.with_savepoint("trash_text_threads", || {
+                    let mut update = connection.exec_bound::<(Option<&str>, Arc<Path>)>(
+                        "UPDATE text_threads SET deleted_at = ? WHERE path = ?",
+                    )?;
+                    for path in paths {
+                        update((deleted_at.as_deref(), path))?;
+                    }
+                    Ok(())
+                })?;
+            }
+            Self::notify_changed(&change_listeners);
+            Ok(())
+        })
+    }
+
+    /// Deletes the text threads that were moved to the trash before the
+    /// given time.
+    pub fn empty_trash(&self, deleted_before: DateTime<Utc>) -> Task<Result<()>> {
+        let connection = self.connection.clone();
+        let change_listeners = self.change_listeners.clone();
+        self.executor.spawn(async move {
+            let deleted = {
+                let connection = connection.lock().unwrap();
+                connection.with_savepoint("empty_text_thread_trash", || {
+                    let paths = connection.select_bound::<String, Arc<Path>>(
+                        "SELECT path FROM text_threads WHERE deleted_at < ?",
+                    )?(deleted_before.to_rfc3339())?;
+                    for path in &paths {
+                        Self::delete_sync(&connection, path)?;
+                    }
+                    Ok(paths.len())
+                })?
+            };
+            if deleted > 0 {
+                Self::notify_changed(&change_listeners);
+            }
+            Ok(())
+        })
+    }
+
+    /// Archives or unarchives the text threads at the given paths.
+    pub fn set_archived(&self, paths: Vec<Arc<Path>>, archived: bool) -> Task<Result<()>> {
+        let connection = self.connection.clone();
+        let change_listeners = self.change_listeners.clone();
+        self.executor.spawn(async move {
+            {
+                let connection = connection.lock().unwrap();
+                connection.with_savepoint("archive_text_threads", || {
+                    let mut update = connection.exec_bound::<(bool, Arc<Path>)>(
+                        "UPDATE text_threads SET archived = ? WHERE path = ?",
+                    )?;
+                    for path in paths {
+                        update((archived, path))?;
+                    }
+                    Ok(())
+                })?;
+            }
+            Self::notify_changed(&change_listeners);
+            Ok(())
+        })
+    }
+
+    /// Returns the paths of the text threads whose title or text contain all
+    /// the words in the query, best matches first.
+    pub fn search(&self, query: String) -> Task<Result<Vec<Arc<Path>>>> {
+        let connection = self.connection.clone();
+        self.executor.spawn(async move {
+            let Some(query) = full_text_query(&query) else {
+                return Ok(Vec::new());
+            };
+            let connection = connection.lock().unwrap();
+            let mut select = connection.select_bound::<String, Arc<Path>>(indoc! {"
+                SELECT path FROM text_threads_search
+                WHERE text_threads_search MATCH ?
+                ORDER BY rank
+                LIMIT 100
+            "})?;
+            select(query)
+        })
+    }
+
+    pub(crate) fn stored_contexts(&self) -> Task<Result<Vec<StoredContext>>> {
+        let connection = self.connection.clone();
+        self.executor.spawn(async move {
+            let connection = connection.lock().unwrap();
+            let mut select = connection.select::<(PathBuf, String, u64, bool)>(indoc! {"
+                SELECT
+                    text_threads.path,
+                    saved_at,
+                    length(data) + ifnull(length(outputs), 0),
+                    outputs IS NULL AND trim(ifnull(body, ''), char(32, 9, 10, 13)) = ''
+                FROM text_threads
+                LEFT JOIN text_threads_search ON text_threads_search.path = text_threads.path
+            "})?;
+
+            select()?
+                .into_iter()
+                .map(|(path, saved_at, len, is_empty)| {
+                    Ok(StoredContext {
+                        path,
+                        mtime: DateTime::parse_from_rfc3339(&saved_at)?.into(),
+                        len,
+                        is_empty,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    pub fn storage_usage(&self) -> Task<Result<TextThreadStorageUsage>> {
+        let stored_contexts = self.stored_contexts();
+        self.executor.spawn(async move {
+            let stored_contexts = stored_contexts.await?;
+            Ok(TextThreadStorageUsage {
+                thread_count: stored_contexts.len(),
+                total_bytes: stored_contexts.iter().map(|context| context.len).sum(),
+            })
+        })
+    }
+}
+
+/// Returns the title of the text thread identified by the given path.
+pub(crate) fn title_for_path(path: &Path) -> Option<&str> {
+    let file_name = path.file_name()?.to_str()?;
+    let suffix = TEXT_THREAD_PATH_SUFFIX.find(file_name)?;
+    Some(&file_name[..suffix.start()])
+}
+
+/// Turns each word of the query into a prefix match, quoting it so that it
+/// isn't interpreted as FTS5 syntax.
+fn full_text_query(query: &str) -> Option<String> {
+    let terms = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssemblyStrategy;
+    use crate::ContextId;
+    use assistant_slash_command::SlashCommandOutputSection;
+    use gpui::TestAppContext;
+    use ui::IconName;
+
+    fn saved_context(text: &str, sections: Vec<std::ops::Range<usize>>) -> SavedContext {
+        SavedContext {
+            id: None,
+            zed: "context".into(),
+            version: SavedContext::VERSION.into(),
+            text: text.into(),
+            messages: Vec::new(),
+            summary: String::new(),
+            slash_command_output_sections: sections
+                .into_iter()
+                .map(|range| SlashCommandOutputSection {
+                    range,
+                    icon: IconName::File,
+                    label: "file".into(),
+                    metadata: None,
+                })
+                .collect(),
+            thought_process_output_sections: Vec::new(),
+            continuation_seams: Vec::new(),
+            compacted_sections: Vec::new(),
+            assembly_strategy: AssemblyStrategy::default(),
+            project: None,
+            model: None,
+            environment: Default::default(),
+            answer_language: None,
+            seed: None,
+            stop_conditions: Vec::new(),
+            post_processors: Vec::new(),
+            web_search: false,
+            cost_tracker: Default::default(),
+        }
+    }
+
+    #[gpui::test]
+    async fn test_save_load_and_search(cx: &mut TestAppContext) {
+        let database = TextThreadsDatabase::open(
+            Connection::open_memory(Some("test_save_load_and_search")),
+            cx.executor(),
+        )
+        .unwrap();
+        let mut changes = database.subscribe();
+
+        let text = "/file src/lib.rs\nfn parse_config() {}\nWhy does parsing fail?\n";
+        let path = database
+            .save(
+                None,
+                "Config parsing".into(),
+                saved_context(text, vec![0..38]),
+                Some(20),
+            )
+            .await
+            .unwrap();
+        assert_eq!(title_for_path(&path), Some("Config parsing"));
+        assert!(changes.next().await.is_some());
+
+        // Large outputs are stored separately, and restored when loading.
+        assert_eq!(database.load(path.clone()).await.unwrap().text, text);
+        assert_eq!(
+            database.search("pars fail".into()).await.unwrap(),
+            [path.clone()]
+        );
+        assert!(
+            database
+                .search("parse_config".into())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // Saving again under the same title keeps the path, the tags and
+        // whether it's archived.
+        database
+            .set_tags(path.clone(), vec!["bugs".into()])
+            .await
+            .unwrap();
+        database
+            .set_archived(vec![path.clone()], true)
+            .await
+            .unwrap();
+        let same_path = database
+            .save(
+                Some(path.clone()),
+                "Config parsing".into(),
+                saved_context("Why does parsing fail?\n", Vec::new()),
+                Some(20),
+            )
+            .await
+            .unwrap();
+        assert_eq!(same_path, path);
+
+        let renamed_path = database
+            .save(
+                Some(path.clone()),
+                "Config parsing errors".into(),
+                SavedContext {
+                    project: Some("zed".into()),
+                    ..saved_context("Why does parsing fail?\n", Vec::new())
+                },
+                Some(20),
+            )
+            .await
+            .unwrap();
+        assert_ne!(renamed_path, path);
+        let metadata = database.list().await.unwrap();
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata[0].path, renamed_path);
+        assert_eq!(metadata[0].tags, ["bugs"]);
+        assert_eq!(metadata[0].project.as_deref(), Some("zed"));
+        assert!(metadata[0].archived);
+
+        database.delete(renamed_path).await.unwrap();
+        assert!(database.list().await.unwrap().is_empty());
+        assert!(database.search("parsing".into()).await.unwrap().is_empty());
+    }
+
+    #[gpui::test]
+    async fn test_saving_from_several_replicas(cx: &mut TestAppContext) {
+        let database = TextThreadsDatabase::open(
+            Connection::open_memory(Some("test_saving_from_several_replicas")),
+            cx.executor(),
+        )
+        .unwrap();
+
+        let context_id = ContextId::new();
+        let snapshot = |text: &str| SavedContext {
+            id: Some(context_id.clone()),
+            ..saved_context(text, Vec::new())
+        };
+        let path = database
+            .save(None, "Lifetimes".into(), snapshot("a"), None)
+            .await
+            .unwrap();
+
+        // Both windows save after the title changes, and the second one still
+        // refers to the path from before the first one renamed the thread.
+        let renamed_path = database
+            .save(
+                Some(path.clone()),
+                "Lifetime elision".into(),
+                snapshot("ab"),
+                None,
+            )
+            .await
+            .unwrap();
+        let second_save_path = database
+            .save(
+                Some(path.clone()),
+                "Lifetime elision".into(),
+                snapshot("ab"),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(second_save_path, renamed_path);
+
+        let metadata = database.list().await.unwrap();
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata[0].path, renamed_path);
+        assert_eq!(database.load(renamed_path).await.unwrap().text, "ab");
+    }
+
+    #[gpui::test]
+    async fn test_trash(cx: &mut TestAppContext) {
+        let database =
+            TextThreadsDatabase::open(Connection::open_memory(Some("test_trash")), cx.executor())
+                .unwrap();
+        let first = database
+            .save(None, "First".into(), saved_context("a", Vec::new()), None)
+            .await
+            .unwrap();
+        let second = database
+            .save(None, "Second".into(), saved_context("b", Vec::new()), None)
+            .await
+            .unwrap();
+        let paths = |metadata: Vec<SavedContextMetadata>| {
+            metadata
+                .into_iter()
+                .map(|metadata| metadata.path)
+                .collect::<Vec<_>>()
+        };
+
+        database.trash(vec![first.clone()]).await.unwrap();
+        assert_eq!(paths(database.list().await.unwrap()), [second.clone()]);
+        assert_eq!(paths(database.list_trash().await.unwrap()), [first.clone()]);
+
+        database.restore(vec![first.clone()]).await.unwrap();
+        assert_eq!(database.list().await.unwrap().len(), 2);
+        assert!(database.list_trash().await.unwrap().is_empty());
+
+        // Only the text threads trashed before the cutoff are deleted.
+        database.trash(vec![first.clone()]).await.unwrap();
+        database
+            .empty_trash(Utc::now() - chrono::TimeDelta::days(1))
+            .await
+            .unwrap();
+        assert_eq!(paths(database.list_trash().await.unwrap()), [first.clone()]);
+        database
+            .empty_trash(Utc::now() + chrono::TimeDelta::days(1))
+            .await
+            .unwrap();
+        assert!(database.list_trash().await.unwrap().is_empty());
+        assert_eq!(paths(database.list().await.unwrap()), [second]);
+        assert!(database.load(first).await.is_err());
+    }
+
+    #[gpui::test]
+    async fn test_stored_contexts_report_empty_text_threads(cx: &mut TestAppContext) {
+        let database = TextThreadsDatabase::open(
+            Connection::open_memory(Some("test_stored_contexts_report_empty_text_threads")),
+            cx.executor(),
+        )
+        .unwrap();
+        let blank = database
+            .save(
+                None,
+                "Blank".into(),
+                saved_context(" \n\t\n", Vec::new()),
+                None,
+            )
+            .await
+            .unwrap();
+        let written = database
+            .save(
+                None,
+                "Written".into(),
+                saved_context("Why?\n", Vec::new()),
+                None,
+            )
+            .await
+            .unwrap();
+        // Only the compacted output has any text.
+        let compacted = database
+            .save(
+                None,
+                "Compacted".into(),
+                saved_context(&"x".repeat(32), vec![0..32]),
+                Some(20),
+            )
+            .await
+            .unwrap();
+
+        let mut stored_contexts = database
+            .stored_contexts()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|context| (context.path, context.is_empty))
+            .collect::<Vec<_>>();
+        stored_contexts.sort();
+        let mut expected = vec![
+            (blank.to_path_buf(), true),
+            (written.to_path_buf(), false),
+            (compacted.to_path_buf(), false),
+        ];
+        expected.sort();
+        assert_eq!(stored_contexts, expected);
+    }
+
+    #[test]
+    fn test_full_text_query() {
+        assert_eq!(full_text_query("  "), None);
+        assert_eq!(
+            full_text_query("parse \"config\" OR"),
+            Some("\"parse\"* \"\"\"config\"\"\"* \"OR\"*".into())
+        );
+    }
+}
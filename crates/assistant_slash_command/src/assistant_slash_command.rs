@@ -90,6 +90,12 @@ pub trait SlashCommand: 'static + Send + Sync {
     fn accepts_arguments(&self) -> bool {
         self.requires_argument()
     }
+    /// Whether this command's output can be long enough that it should be
+    /// inserted a page at a time. Paginated output is collected in full
+    /// before its first page is inserted, so it can't start new messages.
+    fn paginate_output(&self) -> bool {
+        false
+    }
     fn run(
         self: Arc<Self>,
         arguments: &[String],
@@ -203,6 +209,52 @@ impl SlashCommandOutput {
         stream::iter(events).boxed()
     }
 
+    /// Splits this output into pages of at most `lines_per_page` lines each.
+    ///
+    /// Sections that span several pages are clipped to the part of them that
+    /// falls on each page.
+    pub fn into_pages(mut self, lines_per_page: usize) -> Vec<SlashCommandOutput> {
+        self.ensure_valid_section_ranges();
+
+        let lines_per_page = lines_per_page.max(1);
+        let mut page_ends = self
+            .text
+            .match_indices('\n')
+            .enumerate()
+            .filter(|(line_ix, _)| (line_ix + 1) % lines_per_page == 0)
+            .map(|(_, (newline_offset, _))| newline_offset + 1)
+            .collect::<Vec<_>>();
+        if page_ends.last() != Some(&self.text.len()) {
+            page_ends.push(self.text.len());
+        }
+
+        let mut pages = Vec::with_capacity(page_ends.len());
+        let mut page_start = 0;
+        for page_end in page_ends {
+            let sections = self
+                .sections
+                .iter()
+                .filter_map(|section| {
+                    let start = section.range.start.max(page_start);
+                    let end = section.range.end.min(page_end);
+                    (start < end).then(|| SlashCommandOutputSection {
+                        range: start - page_start..end - page_start,
+                        icon: section.icon,
+                        label: section.label.clone(),
+                        metadata: section.metadata.clone(),
+                    })
+                })
+                .collect();
+            pages.push(SlashCommandOutput {
+                text: self.text[page_start..page_end].to_string(),
+                sections,
+                run_commands_in_text: self.run_commands_in_text,
+            });
+            page_start = page_end;
+        }
+        pages
+    }
+
     pub async fn from_event_stream(
         mut events: BoxStream<'static, Result<SlashCommandEvent>>,
     ) -> Result<SlashCommandOutput> {
@@ -261,7 +313,7 @@ pub struct SlashCommandOutputSection<T> {
 }
 
 impl SlashCommandOutputSection<language::Anchor> {
-    pub fn is_valid(&self, buffer: &language::TextBuffer) -> bool {
+    pub fn is_valid(&self, buffer: &language::TextBufferSnapshot) -> bool {
         self.range.start.is_valid(buffer) && !self.range.to_offset(buffer).is_empty()
     }
 }
@@ -569,4 +621,42 @@ mod tests {
             assert_eq!(new_output, output);
         }
     }
+
+    #[test]
+    fn test_slash_command_output_into_pages() {
+        let section = |range: Range<usize>, label: &'static str| SlashCommandOutputSection {
+            range,
+            icon: IconName::Check,
+            label: label.into(),
+            metadata: None,
+        };
+
+        let output = SlashCommandOutput {
+            text: "one\ntwo\nthree\nfour\nfive".to_string(),
+            sections: vec![section(0..23, "All"), section(8..19, "Middle")],
+            run_commands_in_text: false,
+        };
+
+        assert_eq!(
+            output.clone().into_pages(2),
+            vec![
+                SlashCommandOutput {
+                    text: "one\ntwo\n".to_string(),
+                    sections: vec![section(0..8, "All")],
+                    run_commands_in_text: false,
+                },
+                SlashCommandOutput {
+                    text: "three\nfour\n".to_string(),
+                    sections: vec![section(0..11, "All"), section(0..11, "Middle")],
+                    run_commands_in_text: false,
+                },
+                SlashCommandOutput {
+                    text: "five".to_string(),
+                    sections: vec![section(0..4, "All")],
+                    run_commands_in_text: false,
+                },
+            ]
+        );
+        assert_eq!(output.clone().into_pages(10), vec![output]);
+    }
 }
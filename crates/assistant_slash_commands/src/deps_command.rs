@@ -0,0 +1,577 @@
+use anyhow::{Context as _, Result, anyhow, bail};
+use assistant_slash_command::{
+    AfterCompletion, ArgumentCompletion, SlashCommand, SlashCommandOutput,
+    SlashCommandOutputSection, SlashCommandResult,
+};
+use fs::Fs;
+use futures::{AsyncReadExt, StreamExt, stream};
+use gpui::{App, Task, WeakEntity};
+use http_client::{AsyncBody, HttpClient, HttpClientWithUrl};
+use language::{BufferSnapshot, LspAdapterDelegate};
+use std::{
+    fmt::Write,
+    path::Path,
+    sync::{Arc, atomic::AtomicBool},
+};
+use ui::prelude::*;
+use util::ResultExt;
+use workspace::Workspace;
+
+use crate::create_label_for_command;
+
+const CHECK_UPDATES_ARGUMENT: &str = "--check-updates";
+/// How many registry requests are in flight at once when checking for updates.
+const MAX_CONCURRENT_UPDATE_CHECKS: usize = 8;
+
+/// Inserts a table of the direct dependencies declared in the project's
+/// manifests, optionally with the latest version published for each of them.
+pub struct DepsSlashCommand;
+
+impl SlashCommand for DepsSlashCommand {
+    fn name(&self) -> String {
+        "deps".into()
+    }
+
+    fn label(&self, cx: &App) -> language::CodeLabel {
+        create_label_for_command("deps", &[CHECK_UPDATES_ARGUMENT], cx)
+    }
+
+    fn description(&self) -> String {
+        "Insert the project's dependencies".into()
+    }
+
+    fn icon(&self) -> IconName {
+        IconName::FileTree
+    }
+
+    fn menu_text(&self) -> String {
+        self.description()
+    }
+
+    fn requires_argument(&self) -> bool {
+        false
+    }
+
+    fn accepts_arguments(&self) -> bool {
+        true
+    }
+
+    fn complete_argument(
+        self: Arc<Self>,
+        arguments: &[String],
+        _cancel: Arc<AtomicBool>,
+        _workspace: Option<WeakEntity<Workspace>>,
+        _window: &mut Window,
+        _cx: &mut App,
+    ) -> Task<Result<Vec<ArgumentCompletion>>> {
+        let query = arguments.last().map(String::as_str).unwrap_or_default();
+        let completions = if CHECK_UPDATES_ARGUMENT.starts_with(query) {
+            vec![ArgumentCompletion {
+                label: CHECK_UPDATES_ARGUMENT.into(),
+                new_text: CHECK_UPDATES_ARGUMENT.to_string(),
+                after_completion: AfterCompletion::Run,
+                replace_previous_arguments: true,
+            }]
+        } else {
+            Vec::new()
+        };
+        Task::ready(Ok(completions))
+    }
+
+    fn run(
+        self: Arc<Self>,
+        arguments: &[String],
+        _context_slash_command_output_sections: &[SlashCommandOutputSection<language::Anchor>],
+        _context_buffer: BufferSnapshot,
+        workspace: WeakEntity<Workspace>,
+        _delegate: Option<Arc<dyn LspAdapterDelegate>>,
+        _window: &mut Window,
+        cx: &mut App,
+    ) -> Task<SlashCommandResult> {
+        let Some(workspace) = workspace.upgrade() else {
+            return Task::ready(Err(anyhow!("workspace was dropped")));
+        };
+        let check_updates = arguments
+            .iter()
+            .any(|argument| argument == CHECK_UPDATES_ARGUMENT);
+
+        let workspace = workspace.read(cx);
+        let project = workspace.project().read(cx);
+        let fs = project.fs().clone();
+        let http_client = workspace.client().http_client();
+        let manifest_paths = project
+            .visible_worktrees(cx)
+            .flat_map(|worktree| {
+                let snapshot = worktree.read(cx).snapshot();
+                snapshot
+                    .files(false, 0)
+                    .filter_map(|entry| {
+                        let file_name = entry.path.file_name()?.to_str()?;
+                        let ecosystem = Ecosystem::for_manifest(file_name)?;
+                        Some((
+                            Path::new(snapshot.root_name())
+                                .join(&entry.path)
+                                .to_string_lossy()
+                                .into_owned(),
+                            snapshot.abs_path().join(&entry.path),
+                            ecosystem,
+                        ))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        cx.background_spawn(async move {
+            let mut manifests = Vec::new();
+            for (path, abs_path, ecosystem) in manifest_paths {
+                let Some(dependencies) = load_dependencies(fs.as_ref(), &abs_path, ecosystem)
+                    .await
+                    .with_context(|| format!("failed to read {path}"))
+                    .log_err()
+                else {
+                    continue;
+                };
+                if !dependencies.is_empty() {
+                    manifests.push(Manifest {
+                        path,
+                        ecosystem,
+                        dependencies,
+                    });
+                }
+            }
+            if manifests.is_empty() {
+                bail!("No dependencies found");
+            }
+
+            if check_updates {
+                for manifest in &mut manifests {
+                    let ecosystem = manifest.ecosystem;
+                    let latest_versions = stream::iter(&manifest.dependencies)
+                        .map(|dependency| {
+                            let http_client = http_client.clone();
+                            async move {
+                                latest_version(&http_client, ecosystem, &dependency.name)
+                                    .await
+                                    .ok()
+                            }
+                        })
+                        .buffered(MAX_CONCURRENT_UPDATE_CHECKS)
+                        .collect::<Vec<_>>()
+                        .await;
+                    for (dependency, latest) in
+                        manifest.dependencies.iter_mut().zip(latest_versions)
+                    {
+                        dependency.latest_version = latest;
+                    }
+                }
+            }
+
+            Ok(deps_output(&manifests, check_updates).to_event_stream())
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Ecosystem {
+    Cargo,
+    Npm,
+    Python,
+}
+
+impl Ecosystem {
+    fn for_manifest(file_name: &str) -> Option<Self> {
+        match file_name {
+            "Cargo.toml" => Some(Self::Cargo),
+            "package.json" => Some(Self::Npm),
+            "pyproject.toml" => Some(Self::Python),
+            _ => None,
+        }
+    }
+
+    fn icon(&self) -> IconName {
+        match self {
+            Self::Cargo | Self::Python => IconName::FileToml,
+            Self::Npm => IconName::FileCode,
+        }
+    }
+}
+
+struct Manifest {
+    path: String,
+    ecosystem: Ecosystem,
+    dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Dependency {
+    name: String,
+    kind: &'static str,
+    requirement: String,
+    latest_version: Option<String>,
+}
+
+impl Dependency {
+    fn new(name: impl Into<String>, kind: &'static str, requirement: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            requirement: requirement.into(),
+            latest_version: None,
+        }
+    }
+}
+
+async fn load_dependencies(
+    fs: &dyn Fs,
+    abs_path: &Path,
+    ecosystem: Ecosystem,
+) -> Result<Vec<Dependency>> {
+    let text = fs.load(abs_path).await?;
+    match ecosystem {
+        Ecosystem::Cargo => parse_cargo_manifest(&text),
+        Ecosystem::Npm => parse_package_json(&text),
+        Ecosystem::Python => parse_pyproject(&text),
+    }
+}
+
+fn parse_cargo_manifest(text: &str) -> Result<Vec<Dependency>> {
+    let manifest: toml::Table = toml::from_str(text)?;
+    let workspace_dependencies = manifest
+        .get("workspace")
+        .and_then(|workspace| workspace.get("dependencies"));
+    let tables = [
+        (workspace_dependencies, "workspace"),
+        (manifest.get("dependencies"), "normal"),
+        (manifest.get("dev-dependencies"), "dev"),
+        (manifest.get("build-dependencies"), "build"),
+    ];
+
+    let mut dependencies = Vec::new();
+    for (table, kind) in tables {
+        let Some(table) = table.and_then(toml::Value::as_table) else {
+            continue;
+        };
+        let mut entries = table.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(name, _)| *name);
+        for (name, value) in entries {
+            let requirement = match value {
+                toml::Value::String(version) => version.clone(),
+                toml::Value::Table(table) => {
+                    if let Some(version) = table.get("version").and_then(toml::Value::as_str) {
+                        version.to_string()
+                    } else if table.contains_key("workspace") {
+                        "workspace".to_string()
+                    } else if table.contains_key("path") {
+                        "path".to_string()
+                    } else if table.contains_key("git") {
+                        "git".to_string()
+                    } else {
+                        "*".to_string()
+                    }
+                }
+                _ => continue,
+            };
+            dependencies.push(Dependency::new(name, kind, requirement));
+        }
+    }
+    Ok(dependencies)
+}
+
+fn parse_package_json(text: &str) -> Result<Vec<Dependency>> {
+    let manifest: serde_json::Value = serde_json::from_str(text)?;
+    let mut dependencies = Vec::new();
+    for (key, kind) in [
+        ("dependencies", "normal"),
+        ("devDependencies", "dev"),
+        ("peerDependencies", "peer"),
+        ("optionalDependencies", "optional"),
+    ] {
+        let Some(table) = manifest.get(key).and_then(serde_json::Value::as_object) else {
+            continue;
+        };
+        let mut entries = table.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(name, _)| *name);
+        for (name, requirement) in entries {
+            if let Some(requirement) = requirement.as_str() {
+                dependencies.push(Dependency::new(name, kind, requirement));
+            }
+        }
+    }
+    Ok(dependencies)
+}
+
+fn parse_pyproject(text: &str) -> Result<Vec<Dependency>> {
+    let manifest: toml::Table = toml::from_str(text)?;
+    let mut dependencies = Vec::new();
+
+    let project = manifest.get("project");
+    let requirements = project
+        .and_then(|project| project.get("dependencies"))
+        .and_then(toml::Value::as_array);
+    for requirement in requirements.into_iter().flatten() {
+        if let Some(dependency) = requirement
+            .as_str()
+            .and_then(|requirement| parse_python_requirement(requirement, "normal"))
+        {
+            dependencies.push(dependency);
+        }
+    }
+    let optional_dependencies = project
+        .and_then(|project| project.get("optional-dependencies"))
+        .and_then(toml::Value::as_table);
+    for requirements in optional_dependencies
+        .into_iter()
+        .flat_map(|table| table.values())
+    {
+        for requirement in requirements.as_array().into_iter().flatten() {
+            if let Some(dependency) = requirement
+                .as_str()
+                .and_then(|requirement| parse_python_requirement(requirement, "optional"))
+            {
+                dependencies.push(dependency);
+            }
+        }
+    }
+
+    // Poetry declares its dependencies in a table of its own.
+    let poetry = manifest
+        .get("tool")
+        .and_then(|tool| tool.get("poetry"))
+        .and_then(|poetry| poetry.get("dependencies"))
+        .and_then(toml::Value::as_table);
+    for (name, value) in poetry.into_iter().flatten() {
+        if name == "python" {
+            continue;
+        }
+        let requirement = match value {
+            toml::Value::String(version) => version.clone(),
+            toml::Value::Table(table) => table
+                .get("version")
+                .and_then(toml::Value::as_str)
+                .unwrap_or("*")
+                .to_string(),
+            _ => continue,
+        };
+        dependencies.push(Dependency::new(name, "normal", requirement));
+    }
+
+    Ok(dependencies)
+}
+
+/// Parses a PEP 508 requirement like `requests[socks]>=2.31; python_version > "3.8"`.
+fn parse_python_requirement(requirement: &str, kind: &'static str) -> Option<Dependency> {
+    let requirement = requirement.trim();
+    let name_end = requirement
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(requirement.len());
+    let name = &requirement[..name_end];
+    if name.is_empty() {
+        return None;
+    }
+    let rest = &requirement[name_end..];
+    let rest = match rest.find(']') {
+        Some(extras_end) if rest.starts_with('[') => &rest[extras_end + 1..],
+        _ => rest,
+    };
+    let version = rest.split(';').next().unwrap_or_default().trim();
+    let version = if version.is_empty() { "*" } else { version };
+    Some(Dependency::new(name, kind, version))
+}
+
+async fn latest_version(
+    http_client: &HttpClientWithUrl,
+    ecosystem: Ecosystem,
+    name: &str,
+) -> Result<String> {
+    let url = match ecosystem {
+        Ecosystem::Cargo => format!("https://crates.io/api/v1/crates/{name}"),
+        Ecosystem::Npm => format!("https://registry.npmjs.org/{name}/latest"),
+        Ecosystem::Python => format!("https://pypi.org/pypi/{name}/json"),
+    };
+    let mut response = http_client.get(&url, AsyncBody::default(), true).await?;
+    let mut body = Vec::new();
+    response.body_mut().read_to_end(&mut body).await?;
+    if !response.status().is_success() {
+        bail!("status error {}", response.status().as_u16());
+    }
+
+    let body: serde_json::Value = serde_json::from_slice(&body)?;
+    let version = match ecosystem {
+        Ecosystem::Cargo => body.pointer("/crate/max_stable_version"),
+        Ecosystem::Npm => body.get("version"),
+        Ecosystem::Python => body.pointer("/info/version"),
+    };
+    version
+        .and_then(serde_json::Value::as_str)
+        .map(ToString::to_string)
+        .context("missing version")
+}
+
+fn deps_output(manifests: &[Manifest], check_updates: bool) -> SlashCommandOutput {
+    let mut output = SlashCommandOutput::default();
+    writeln!(output.text, "deps").unwrap();
+
+    let mut dependency_count = 0;
+    for manifest in manifests {
+        let section_start = output.text.len();
+        writeln!(output.text, "{}", manifest.path).unwrap();
+        if check_updates {
+            writeln!(output.text, "| Name | Kind | Requirement | Latest |").unwrap();
+            writeln!(output.text, "| --- | --- | --- | --- |").unwrap();
+        } else {
+            writeln!(output.text, "| Name | Kind | Requirement |").unwrap();
+            writeln!(output.text, "| --- | --- | --- |").unwrap();
+        }
+        for dependency in &manifest.dependencies {
+            write!(
+                output.text,
+                "| {} | {} | {} |",
+                dependency.name, dependency.kind, dependency.requirement
+            )
+            .unwrap();
+            if check_updates {
+                write!(
+                    output.text,
+                    " {} |",
+                    dependency.latest_version.as_deref().unwrap_or("unknown")
+                )
+                .unwrap();
+            }
+            output.text.push('\n');
+        }
+        dependency_count += manifest.dependencies.len();
+
+        output.sections.push(SlashCommandOutputSection {
+            range: section_start..output.text.len().saturating_sub(1),
+            icon: manifest.ecosystem.icon(),
+            label: format!("{} ({})", manifest.path, manifest.dependencies.len()).into(),
+            metadata: None,
+        });
+    }
+
+    output.sections.insert(
+        0,
+        SlashCommandOutputSection {
+            range: 0..output.text.len(),
+            icon: IconName::FileTree,
+            label: format!("Dependencies: {dependency_count}").into(),
+            metadata: None,
+        },
+    );
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_cargo_manifest() {
+        let dependencies = parse_cargo_manifest(indoc! {r#"
+            [package]
+            name = "example"
+
+            [dependencies]
+            anyhow = "1.0"
+            serde = { version = "1.0", features = ["derive"] }
+            gpui.workspace = true
+            util = { path = "../util" }
+
+            [dev-dependencies]
+            pretty_assertions = "1.3"
+        "#})
+        .unwrap();
+        assert_eq!(
+            dependencies,
+            vec![
+                Dependency::new("anyhow", "normal", "1.0"),
+                Dependency::new("gpui", "normal", "workspace"),
+                Dependency::new("serde", "normal", "1.0"),
+                Dependency::new("util", "normal", "path"),
+                Dependency::new("pretty_assertions", "dev", "1.3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_package_json() {
+        let dependencies = parse_package_json(indoc! {r#"
+            {
+                "dependencies": { "react": "^18.2.0" },
+                "devDependencies": { "typescript": "~5.4.0" }
+            }
+        "#})
+        .unwrap();
+        assert_eq!(
+            dependencies,
+            vec![
+                Dependency::new("react", "normal", "^18.2.0"),
+                Dependency::new("typescript", "dev", "~5.4.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_pyproject() {
+        let dependencies = parse_pyproject(indoc! {r#"
+            [project]
+            dependencies = [
+                "requests[socks]>=2.31; python_version > '3.8'",
+                "rich",
+            ]
+
+            [project.optional-dependencies]
+            test = ["pytest==8.0"]
+
+            [tool.poetry.dependencies]
+            python = "^3.10"
+            httpx = { version = "^0.27" }
+        "#})
+        .unwrap();
+        assert_eq!(
+            dependencies,
+            vec![
+                Dependency::new("requests", "normal", ">=2.31"),
+                Dependency::new("rich", "normal", "*"),
+                Dependency::new("pytest", "optional", "==8.0"),
+                Dependency::new("httpx", "normal", "^0.27"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deps_output() {
+        let manifests = [Manifest {
+            path: "app/package.json".into(),
+            ecosystem: Ecosystem::Npm,
+            dependencies: vec![Dependency {
+                latest_version: Some("19.1.0".into()),
+                ..Dependency::new("react", "normal", "^18.2.0")
+            }],
+        }];
+
+        assert_eq!(
+            deps_output(&manifests, true).text,
+            indoc! {"
+                deps
+                app/package.json
+                | Name | Kind | Requirement | Latest |
+                | --- | --- | --- | --- |
+                | react | normal | ^18.2.0 | 19.1.0 |
+            "}
+        );
+        assert_eq!(
+            deps_output(&manifests, false).text,
+            indoc! {"
+                deps
+                app/package.json
+                | Name | Kind | Requirement |
+                | --- | --- | --- |
+                | react | normal | ^18.2.0 |
+            "}
+        );
+    }
+}
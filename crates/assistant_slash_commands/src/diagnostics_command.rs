@@ -114,6 +114,10 @@ impl SlashCommand for DiagnosticsSlashCommand {
         true
     }
 
+    fn paginate_output(&self) -> bool {
+        true
+    }
+
     fn complete_argument(
         self: Arc<Self>,
         arguments: &[String],
@@ -123,6 +123,10 @@ impl SlashCommand for FetchSlashCommand {
         true
     }
 
+    fn paginate_output(&self) -> bool {
+        true
+    }
+
     fn complete_argument(
         self: Arc<Self>,
         _arguments: &[String],
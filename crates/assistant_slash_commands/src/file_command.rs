@@ -25,7 +25,7 @@ use worktree::ChildEntriesOptions;
 pub struct FileSlashCommand;
 
 impl FileSlashCommand {
-    fn search_paths(
+    pub(crate) fn search_paths(
         &self,
         query: String,
         cancellation_flag: Arc<AtomicBool>,
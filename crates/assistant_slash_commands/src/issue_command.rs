@@ -0,0 +1,871 @@
+use anyhow::{Context as _, Result, anyhow, bail};
+use assistant_slash_command::{
+    ArgumentCompletion, SlashCommand, SlashCommandOutput, SlashCommandOutputSection,
+    SlashCommandResult,
+};
+use futures::AsyncReadExt as _;
+use git::{GitHostingProviderRegistry, parse_git_remote_url};
+use gpui::{App, Entity, Global, Task, WeakEntity};
+use http_client::{AsyncBody, HttpClient, Method, Request, Url};
+use language::{BufferSnapshot, LspAdapterDelegate};
+use project::Project;
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::json;
+use std::{
+    fmt::Write,
+    sync::{Arc, atomic::AtomicBool},
+};
+use ui::prelude::*;
+use workspace::Workspace;
+
+use crate::create_label_for_command;
+
+/// The number of most recent comments that are inserted.
+const MAX_COMMENTS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+    Issue,
+    PullRequest,
+}
+
+impl IssueKind {
+    fn noun(&self) -> &'static str {
+        match self {
+            Self::Issue => "issue",
+            Self::PullRequest => "pull request",
+        }
+    }
+}
+
+/// The tokens used to authenticate with issue trackers. When a token isn't
+/// configured, the `GITHUB_TOKEN` and `GITLAB_TOKEN` environment variables are
+/// used instead.
+#[derive(Clone, Debug, Default)]
+pub struct IssueTrackerTokens {
+    pub github: Option<String>,
+    pub gitlab: Option<String>,
+    /// The self-hosted GitHub Enterprise and GitLab hosts the tokens may be
+    /// sent to, besides github.com, gitlab.com and the host of the project's
+    /// `origin` remote.
+    pub hosts: Vec<String>,
+}
+
+impl Global for IssueTrackerTokens {}
+
+impl IssueTrackerTokens {
+    /// Returns the configured tokens, or no tokens if they haven't been set.
+    pub fn global(cx: &App) -> Self {
+        cx.try_global::<Self>().cloned().unwrap_or_default()
+    }
+
+    fn github(&self) -> Option<String> {
+        self.github
+            .clone()
+            .or_else(|| std::env::var("GITHUB_TOKEN").ok())
+    }
+
+    fn gitlab(&self) -> Option<String> {
+        self.gitlab
+            .clone()
+            .or_else(|| std::env::var("GITLAB_TOKEN").ok())
+    }
+
+    /// Returns the token to authenticate with the given tracker, unless it's
+    /// on a host the tokens may not be sent to. Otherwise a URL pointing at
+    /// any host would receive the user's token.
+    fn token_for(&self, tracker: &IssueTracker, origin: Option<&IssueTracker>) -> Option<String> {
+        let host = tracker.host()?;
+        let is_public_host = match tracker {
+            IssueTracker::GitHub { .. } => host == "api.github.com",
+            IssueTracker::GitLab { .. } => host == "gitlab.com",
+        };
+        let is_trusted = is_public_host
+            || origin.and_then(IssueTracker::host).as_ref() == Some(&host)
+            || self
+                .hosts
+                .iter()
+                .any(|trusted_host| trusted_host.eq_ignore_ascii_case(&host));
+        if !is_trusted {
+            return None;
+        }
+        match tracker {
+            IssueTracker::GitHub { .. } => self.github(),
+            IssueTracker::GitLab { .. } => self.gitlab(),
+        }
+    }
+}
+
+/// Inserts a GitHub or GitLab issue or pull request, along with its most
+/// recent comments.
+pub struct IssueSlashCommand {
+    kind: IssueKind,
+}
+
+impl IssueSlashCommand {
+    pub fn new(kind: IssueKind) -> Self {
+        Self { kind }
+    }
+}
+
+impl SlashCommand for IssueSlashCommand {
+    fn name(&self) -> String {
+        match self.kind {
+            IssueKind::Issue => "issue".into(),
+            IssueKind::PullRequest => "pr".into(),
+        }
+    }
+
+    fn label(&self, cx: &App) -> language::CodeLabel {
+        create_label_for_command(&self.name(), &["<number|url>"], cx)
+    }
+
+    fn description(&self) -> String {
+        match self.kind {
+            IssueKind::Issue => "Insert a GitHub or GitLab issue".into(),
+            IssueKind::PullRequest => "Insert a GitHub pull request or GitLab merge request".into(),
+        }
+    }
+
+    fn icon(&self) -> IconName {
+        icon_for_kind(self.kind)
+    }
+
+    fn menu_text(&self) -> String {
+        self.description()
+    }
+
+    fn requires_argument(&self) -> bool {
+        true
+    }
+
+    fn accepts_arguments(&self) -> bool {
+        true
+    }
+
+    fn complete_argument(
+        self: Arc<Self>,
+        _arguments: &[String],
+        _cancel: Arc<AtomicBool>,
+        _workspace: Option<WeakEntity<Workspace>>,
+        _window: &mut Window,
+        _cx: &mut App,
+    ) -> Task<Result<Vec<ArgumentCompletion>>> {
+        Task::ready(Ok(Vec::new()))
+    }
+
+    fn run(
+        self: Arc<Self>,
+        arguments: &[String],
+        _context_slash_command_output_sections: &[SlashCommandOutputSection<language::Anchor>],
+        _context_buffer: BufferSnapshot,
+        workspace: WeakEntity<Workspace>,
+        _delegate: Option<Arc<dyn LspAdapterDelegate>>,
+        _window: &mut Window,
+        cx: &mut App,
+    ) -> Task<SlashCommandResult> {
+        let Some(argument) = arguments.first() else {
+            return Task::ready(Err(anyhow!("missing {} number or URL", self.kind.noun())));
+        };
+        let Some(workspace) = workspace.upgrade() else {
+            return Task::ready(Err(anyhow!("workspace was dropped")));
+        };
+        let reference = match IssueReference::parse(argument, self.kind) {
+            Ok(reference) => reference,
+            Err(error) => return Task::ready(Err(error)),
+        };
+
+        let origin_tracker = repository_tracker(workspace.read(cx).project(), cx);
+        let http_client = workspace.read(cx).client().http_client();
+        let tokens = IssueTrackerTokens::global(cx);
+        cx.background_spawn(async move {
+            let (tracker, origin_tracker) = match (reference.tracker, origin_tracker.await) {
+                (Some(tracker), origin_tracker) => (tracker, origin_tracker.ok()),
+                (None, origin_tracker) => {
+                    let origin_tracker = origin_tracker?;
+                    (origin_tracker.clone(), Some(origin_tracker))
+                }
+            };
+            let token = tokens.token_for(&tracker, origin_tracker.as_ref());
+            let issue = match &tracker {
+                IssueTracker::GitHub { api_url, repo } => {
+                    fetch_github_issue(http_client.as_ref(), api_url, repo, reference.number, token)
+                        .await?
+                }
+                IssueTracker::GitLab { base_url, project } => {
+                    fetch_gitlab_issue(
+                        http_client.as_ref(),
+                        base_url,
+                        project,
+                        reference.kind,
+                        reference.number,
+                        token,
+                    )
+                    .await?
+                }
+            };
+            Ok(issue_output(&issue, reference.kind).to_event_stream())
+        })
+    }
+}
+
+fn icon_for_kind(kind: IssueKind) -> IconName {
+    match kind {
+        IssueKind::Issue => IconName::MessageBubbles,
+        IssueKind::PullRequest => IconName::PullRequest,
+    }
+}
+
+/// A GitHub repository or GitLab project.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IssueTracker {
+    GitHub {
+        /// The URL of the REST API, e.g. `https://api.github.com`.
+        api_url: String,
+        /// The repository, as `owner/repo`.
+        repo: String,
+    },
+    GitLab {
+        /// The URL of the instance, e.g. `https://gitlab.com`.
+        base_url: String,
+        /// The full path of the project, e.g. `group/subgroup/project`.
+        project: String,
+    },
+}
+
+impl IssueTracker {
+    fn github(base_url: &Url, repo: String) -> Self {
+        let api_url = match base_url.host_str() {
+            Some("github.com") | None => "https://api.github.com".to_string(),
+            Some(_) => format!("{}/api/v3", base_url.origin().ascii_serialization()),
+        };
+        Self::GitHub { api_url, repo }
+    }
+
+    fn gitlab(base_url: &Url, project: String) -> Self {
+        Self::GitLab {
+            base_url: base_url.origin().ascii_serialization(),
+            project,
+        }
+    }
+
+    /// The host requests to the tracker are sent to.
+    fn host(&self) -> Option<String> {
+        let url = match self {
+            Self::GitHub { api_url, .. } => api_url,
+            Self::GitLab { base_url, .. } => base_url,
+        };
+        Some(Url::parse(url).ok()?.host_str()?.to_ascii_lowercase())
+    }
+
+    /// The path of the repository or project, e.g. `owner/repo`.
+    pub fn path(&self) -> &str {
+        match self {
+            Self::GitHub { repo, .. } => repo,
+            Self::GitLab { project, .. } => project,
+        }
+    }
+
+    /// Creates an issue, returning its URL.
+    pub async fn create_issue(
+        &self,
+        http_client: &dyn HttpClient,
+        title: &str,
+        body: &str,
+        tokens: &IssueTrackerTokens,
+    ) -> Result<String> {
+        match self {
+            Self::GitHub { api_url, repo } => {
+                let token = tokens
+                    .github()
+                    .context("filing GitHub issues requires a token")?;
+                let issue: CreatedGithubIssue = request_json(
+                    http_client,
+                    Method::POST,
+                    &format!("{api_url}/repos/{repo}/issues"),
+                    Some(("Authorization", format!("Bearer {token}"))),
+                    Some(json!({ "title": title, "body": body })),
+                )
+                .await?;
+                Ok(issue.html_url)
+            }
+            Self::GitLab { base_url, project } => {
+                let token = tokens
+                    .gitlab()
+                    .context("filing GitLab issues requires a token")?;
+                let issue: CreatedGitlabIssue = request_json(
+                    http_client,
+                    Method::POST,
+                    &format!(
+                        "{base_url}/api/v4/projects/{}/issues",
+                        urlencoding::encode(project)
+                    ),
+                    Some(("PRIVATE-TOKEN", token)),
+                    Some(json!({ "title": title, "description": body })),
+                )
+                .await?;
+                Ok(issue.web_url)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct IssueReference {
+    /// The tracker the issue is in, or `None` for the project's repository.
+    tracker: Option<IssueTracker>,
+    kind: IssueKind,
+    number: u64,
+}
+
+impl IssueReference {
+    fn parse(argument: &str, kind: IssueKind) -> Result<Self> {
+        let argument = argument.trim();
+        if let Ok(number) = argument.trim_start_matches(['#', '!']).parse() {
+            return Ok(Self {
+                tracker: None,
+                kind,
+                number,
+            });
+        }
+
+        let url = if argument.contains("://") {
+            Url::parse(argument)
+        } else {
+            Url::parse(&format!("https://{argument}"))
+        }
+        .with_context(|| format!("invalid {} number or URL: {argument}", kind.noun()))?;
+        let segments = url
+            .path_segments()
+            .map(|segments| {
+                segments
+                    .filter(|segment| !segment.is_empty())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        // GitLab URLs separate the project from the rest of the path with
+        // `-`, e.g. `gitlab.com/group/project/-/merge_requests/12`.
+        if let Some(separator_ix) = segments.iter().position(|segment| *segment == "-") {
+            let (project, rest) = segments.split_at(separator_ix);
+            let kind = match rest.get(1) {
+                Some(&"issues") => IssueKind::Issue,
+                Some(&"merge_requests") => IssueKind::PullRequest,
+                _ => bail!("not a GitLab issue or merge request URL: {argument}"),
+            };
+            let number = rest
+                .get(2)
+                .and_then(|number| number.parse().ok())
+                .with_context(|| format!("missing number in {argument}"))?;
+            return Ok(Self {
+                tracker: Some(IssueTracker::gitlab(&url, project.join("/"))),
+                kind,
+                number,
+            });
+        }
+
+        match segments.as_slice() {
+            [owner, repo, kind @ ("issues" | "pull"), number, ..] => Ok(Self {
+                tracker: Some(IssueTracker::github(&url, format!("{owner}/{repo}"))),
+                kind: if *kind == "pull" {
+                    IssueKind::PullRequest
+                } else {
+                    IssueKind::Issue
+                },
+                number: number
+                    .parse()
+                    .with_context(|| format!("invalid number in {argument}"))?,
+            }),
+            _ => bail!("not a GitHub or GitLab {} URL: {argument}", kind.noun()),
+        }
+    }
+}
+
+/// Returns the tracker of the project's repository, based on its `origin`
+/// remote.
+pub fn repository_tracker(project: &Entity<Project>, cx: &mut App) -> Task<Result<IssueTracker>> {
+    let Some(repository) = project.read(cx).active_repository(cx) else {
+        return Task::ready(Err(anyhow!(
+            "the project has no Git repository, pass a URL instead"
+        )));
+    };
+    let remote_url = repository.update(cx, |repository, _| {
+        repository.remote_url("origin".to_string())
+    });
+    let registry = GitHostingProviderRegistry::default_global(cx);
+    cx.background_spawn(async move {
+        let remote_url = remote_url
+            .await??
+            .context("the repository has no origin remote, pass a URL instead")?;
+        let (provider, remote) = parse_git_remote_url(registry, &remote_url)
+            .with_context(|| format!("unsupported remote {remote_url}"))?;
+        let path = format!("{}/{}", remote.owner, remote.repo);
+        let name = provider.name();
+        if name.starts_with("GitHub") {
+            Ok(IssueTracker::github(&provider.base_url(), path))
+        } else if name.starts_with("GitLab") {
+            Ok(IssueTracker::gitlab(&provider.base_url(), path))
+        } else {
+            bail!("{name} repositories aren't supported, only GitHub and GitLab")
+        }
+    })
+}
+
+#[derive(Debug, PartialEq)]
+struct Issue {
+    title: String,
+    url: String,
+    state: String,
+    author: String,
+    labels: Vec<String>,
+    body: String,
+    comments: Vec<Comment>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Comment {
+    author: String,
+    created_at: String,
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct GithubUser {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct GithubLabel {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct GithubPullRequestLinks {
+    merged_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GithubIssue {
+    title: String,
+    html_url: String,
+    state: String,
+    user: GithubUser,
+    #[serde(default)]
+    labels: Vec<GithubLabel>,
+    body: Option<String>,
+    comments: usize,
+    pull_request: Option<GithubPullRequestLinks>,
+}
+
+#[derive(Deserialize)]
+struct GithubComment {
+    user: GithubUser,
+    created_at: String,
+    body: Option<String>,
+}
+
+async fn fetch_github_issue(
+    http_client: &dyn HttpClient,
+    api_url: &str,
+    repo: &str,
+    number: u64,
+    token: Option<String>,
+) -> Result<Issue> {
+    let auth = token.map(|token| ("Authorization", format!("Bearer {token}")));
+    // Pull requests are issues too, so this works for both.
+    let issue: GithubIssue = get_json(
+        http_client,
+        &format!("{api_url}/repos/{repo}/issues/{number}"),
+        auth.clone(),
+    )
+    .await?;
+
+    let comments: Vec<GithubComment> = if issue.comments == 0 {
+        Vec::new()
+    } else {
+        // Comments are listed oldest first, so fetch the last page.
+        let per_page = 100;
+        let last_page = issue.comments.div_ceil(per_page);
+        get_json(
+            http_client,
+            &format!(
+                "{api_url}/repos/{repo}/issues/{number}/comments?per_page={per_page}&page={last_page}"
+            ),
+            auth,
+        )
+        .await?
+    };
+    let skip = comments.len().saturating_sub(MAX_COMMENTS);
+
+    let state = match &issue.pull_request {
+        Some(GithubPullRequestLinks {
+            merged_at: Some(_), ..
+        }) => "merged".to_string(),
+        _ => issue.state,
+    };
+    Ok(Issue {
+        title: issue.title,
+        url: issue.html_url,
+        state,
+        author: issue.user.login,
+        labels: issue.labels.into_iter().map(|label| label.name).collect(),
+        body: issue.body.unwrap_or_default(),
+        comments: comments
+            .into_iter()
+            .skip(skip)
+            .map(|comment| Comment {
+                author: comment.user.login,
+                created_at: comment.created_at,
+                body: comment.body.unwrap_or_default(),
+            })
+            .collect(),
+    })
+}
+
+#[derive(Deserialize)]
+struct CreatedGithubIssue {
+    html_url: String,
+}
+
+#[derive(Deserialize)]
+struct GitlabUser {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct GitlabIssue {
+    title: String,
+    web_url: String,
+    state: String,
+    author: GitlabUser,
+    #[serde(default)]
+    labels: Vec<String>,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GitlabNote {
+    author: GitlabUser,
+    created_at: String,
+    body: String,
+    /// Whether the note was created by GitLab, e.g. for a label change.
+    #[serde(default)]
+    system: bool,
+}
+
+#[derive(Deserialize)]
+struct CreatedGitlabIssue {
+    web_url: String,
+}
+
+async fn fetch_gitlab_issue(
+    http_client: &dyn HttpClient,
+    base_url: &str,
+    project: &str,
+    kind: IssueKind,
+    number: u64,
+    token: Option<String>,
+) -> Result<Issue> {
+    let auth = token.map(|token| ("PRIVATE-TOKEN", token));
+    let endpoint = match kind {
+        IssueKind::Issue => "issues",
+        IssueKind::PullRequest => "merge_requests",
+    };
+    let issue_url = format!(
+        "{base_url}/api/v4/projects/{}/{endpoint}/{number}",
+        urlencoding::encode(project)
+    );
+    let issue: GitlabIssue = get_json(http_client, &issue_url, auth.clone()).await?;
+    // Fetch extra notes, since system notes are skipped.
+    let notes: Vec<GitlabNote> = get_json(
+        http_client,
+        &format!(
+            "{issue_url}/notes?sort=desc&order_by=created_at&per_page={}",
+            MAX_COMMENTS * 5
+        ),
+        auth,
+    )
+    .await?;
+
+    let mut comments = notes
+        .into_iter()
+        .filter(|note| !note.system)
+        .take(MAX_COMMENTS)
+        .map(|note| Comment {
+            author: note.author.username,
+            created_at: note.created_at,
+            body: note.body,
+        })
+        .collect::<Vec<_>>();
+    comments.reverse();
+
+    Ok(Issue {
+        title: issue.title,
+        url: issue.web_url,
+        state: issue.state,
+        author: issue.author.username,
+        labels: issue.labels,
+        body: issue.description.unwrap_or_default(),
+        comments,
+    })
+}
+
+async fn get_json<T: DeserializeOwned>(
+    http_client: &dyn HttpClient,
+    url: &str,
+    auth: Option<(&'static str, String)>,
+) -> Result<T> {
+    request_json(http_client, Method::GET, url, auth, None).await
+}
+
+async fn request_json<T: DeserializeOwned>(
+    http_client: &dyn HttpClient,
+    method: Method,
+    url: &str,
+    auth: Option<(&'static str, String)>,
+    body: Option<serde_json::Value>,
+) -> Result<T> {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(url)
+        .header("Accept", "application/json")
+        .header("User-Agent", "Zed");
+    if let Some((header, value)) = auth {
+        request = request.header(header, value);
+    }
+    let body = match body {
+        Some(body) => {
+            request = request.header("Content-Type", "application/json");
+            AsyncBody::from(serde_json::to_vec(&body)?)
+        }
+        None => AsyncBody::default(),
+    };
+
+    let mut response = http_client
+        .send(request.body(body)?)
+        .await
+        .with_context(|| format!("error fetching {url}"))?;
+    let mut body = Vec::new();
+    response
+        .body_mut()
+        .read_to_end(&mut body)
+        .await
+        .context("error reading response body")?;
+
+    if !response.status().is_success() {
+        let text = String::from_utf8_lossy(body.as_slice());
+        bail!(
+            "status error {}, response: {text:?}",
+            response.status().as_u16()
+        );
+    }
+    serde_json::from_slice(&body).with_context(|| format!("failed to deserialize {url}"))
+}
+
+fn issue_output(issue: &Issue, kind: IssueKind) -> SlashCommandOutput {
+    let mut text = String::new();
+    let mut sections = Vec::new();
+
+    writeln!(text, "# {}", issue.title).unwrap();
+    writeln!(text).unwrap();
+    writeln!(text, "URL: {}", issue.url).unwrap();
+    writeln!(text, "State: {}", issue.state).unwrap();
+    writeln!(text, "Author: {}", issue.author).unwrap();
+    if !issue.labels.is_empty() {
+        writeln!(text, "Labels: {}", issue.labels.join(", ")).unwrap();
+    }
+    let body = issue.body.trim();
+    if !body.is_empty() {
+        writeln!(text).unwrap();
+        writeln!(text, "{body}").unwrap();
+    }
+
+    if !issue.comments.is_empty() {
+        writeln!(text).unwrap();
+        writeln!(text, "## Comments").unwrap();
+        for comment in &issue.comments {
+            writeln!(text).unwrap();
+            let start = text.len();
+            writeln!(text, "### {} ({})", comment.author, comment.created_at).unwrap();
+            writeln!(text).unwrap();
+            writeln!(text, "{}", comment.body.trim()).unwrap();
+            sections.push(SlashCommandOutputSection {
+                range: start..text.len(),
+                icon: IconName::MessageBubbles,
+                label: format!("Comment by {}", comment.author).into(),
+                metadata: None,
+            });
+        }
+    }
+
+    sections.insert(
+        0,
+        SlashCommandOutputSection {
+            range: 0..text.len(),
+            icon: icon_for_kind(kind),
+            label: issue.title.clone().into(),
+            metadata: None,
+        },
+    );
+
+    SlashCommandOutput {
+        text,
+        sections,
+        run_commands_in_text: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_reference() {
+        assert_eq!(
+            IssueReference::parse("#42", IssueKind::Issue).unwrap(),
+            IssueReference {
+                tracker: None,
+                kind: IssueKind::Issue,
+                number: 42,
+            }
+        );
+        assert_eq!(
+            IssueReference::parse(
+                "https://github.com/zed-industries/zed/pull/7",
+                IssueKind::Issue
+            )
+            .unwrap(),
+            IssueReference {
+                tracker: Some(IssueTracker::GitHub {
+                    api_url: "https://api.github.com".into(),
+                    repo: "zed-industries/zed".into(),
+                }),
+                kind: IssueKind::PullRequest,
+                number: 7,
+            }
+        );
+        assert_eq!(
+            IssueReference::parse(
+                "gitlab.example.com:8443/group/sub/project/-/merge_requests/12#note_1",
+                IssueKind::PullRequest
+            )
+            .unwrap(),
+            IssueReference {
+                tracker: Some(IssueTracker::GitLab {
+                    base_url: "https://gitlab.example.com:8443".into(),
+                    project: "group/sub/project".into(),
+                }),
+                kind: IssueKind::PullRequest,
+                number: 12,
+            }
+        );
+        assert!(IssueReference::parse("github.com/zed-industries", IssueKind::Issue).is_err());
+    }
+
+    #[test]
+    fn test_tokens_are_only_sent_to_trusted_hosts() {
+        let tokens = IssueTrackerTokens {
+            github: Some("ghp_secret".into()),
+            gitlab: Some("glpat_secret".into()),
+            hosts: vec!["GitLab.Corp.Example".into()],
+        };
+        let tracker = |argument: &str| {
+            IssueReference::parse(argument, IssueKind::Issue)
+                .unwrap()
+                .tracker
+                .unwrap()
+        };
+
+        let github = tracker("https://github.com/zed-industries/zed/issues/1");
+        assert_eq!(
+            tokens.token_for(&github, None).as_deref(),
+            Some("ghp_secret")
+        );
+        let gitlab = tracker("https://gitlab.com/group/project/-/issues/1");
+        assert_eq!(
+            tokens.token_for(&gitlab, None).as_deref(),
+            Some("glpat_secret")
+        );
+
+        let foreign_github = tracker("https://evil.example/o/r/issues/1");
+        assert_eq!(tokens.token_for(&foreign_github, None), None);
+        assert_eq!(tokens.token_for(&foreign_github, Some(&github)), None);
+        let foreign_gitlab = tracker("https://evil.example/o/r/-/issues/1");
+        assert_eq!(tokens.token_for(&foreign_gitlab, None), None);
+
+        let enterprise = tracker("https://github.corp.example/o/r/issues/1");
+        assert_eq!(tokens.token_for(&enterprise, None), None);
+        assert_eq!(
+            tokens.token_for(&enterprise, Some(&enterprise)).as_deref(),
+            Some("ghp_secret")
+        );
+        let configured = tracker("https://gitlab.corp.example/g/p/-/issues/1");
+        assert_eq!(
+            tokens.token_for(&configured, None).as_deref(),
+            Some("glpat_secret")
+        );
+    }
+
+    #[test]
+    fn test_github_issue_deserialization() {
+        let issue: GithubIssue = serde_json::from_str(
+            r#"{
+                "title": "Crash on startup",
+                "html_url": "https://github.com/owner/repo/pull/3",
+                "state": "closed",
+                "user": { "login": "octocat" },
+                "labels": [{ "name": "bug" }],
+                "body": null,
+                "comments": 2,
+                "pull_request": { "merged_at": "2024-01-01T00:00:00Z" }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(issue.user.login, "octocat");
+        assert_eq!(issue.labels[0].name, "bug");
+        assert!(issue.pull_request.unwrap().merged_at.is_some());
+    }
+
+    #[test]
+    fn test_issue_output() {
+        let issue = Issue {
+            title: "Crash on startup".into(),
+            url: "https://github.com/owner/repo/issues/1".into(),
+            state: "open".into(),
+            author: "octocat".into(),
+            labels: vec!["bug".into(), "p1".into()],
+            body: "It crashes.\n".into(),
+            comments: vec![Comment {
+                author: "maintainer".into(),
+                created_at: "2024-01-01T00:00:00Z".into(),
+                body: "Can't reproduce.".into(),
+            }],
+        };
+        let output = issue_output(&issue, IssueKind::Issue);
+        assert_eq!(
+            output.text,
+            "# Crash on startup\n\
+            \n\
+            URL: https://github.com/owner/repo/issues/1\n\
+            State: open\n\
+            Author: octocat\n\
+            Labels: bug, p1\n\
+            \n\
+            It crashes.\n\
+            \n\
+            ## Comments\n\
+            \n\
+            ### maintainer (2024-01-01T00:00:00Z)\n\
+            \n\
+            Can't reproduce.\n"
+        );
+        assert_eq!(output.sections.len(), 2);
+        assert_eq!(output.sections[0].range, 0..output.text.len());
+        assert_eq!(
+            &output.text[output.sections[1].range.clone()],
+            "### maintainer (2024-01-01T00:00:00Z)\n\nCan't reproduce.\n"
+        );
+    }
+}
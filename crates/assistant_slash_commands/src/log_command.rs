@@ -0,0 +1,476 @@
+use anyhow::{Context as _, Result, anyhow, bail};
+use assistant_slash_command::{
+    AfterCompletion, ArgumentCompletion, SlashCommand, SlashCommandOutput,
+    SlashCommandOutputSection, SlashCommandResult,
+};
+use collections::HashMap;
+use gpui::{App, Task, WeakEntity};
+use language::{BufferSnapshot, LspAdapterDelegate};
+use regex::Regex;
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, atomic::AtomicBool},
+};
+use ui::prelude::*;
+use workspace::Workspace;
+
+use crate::{FileSlashCommand, codeblock_fence_for_path, create_label_for_command};
+
+const LEVEL_ARGUMENT: &str = "--level=";
+const SINCE_ARGUMENT: &str = "--since=";
+const UNTIL_ARGUMENT: &str = "--until=";
+const GREP_ARGUMENT: &str = "--grep=";
+const MAX_TOKENS_ARGUMENT: &str = "--max-tokens=";
+const DEFAULT_MAX_TOKENS: usize = 4000;
+const BYTES_PER_TOKEN_ESTIMATE: usize = 3;
+
+static LEVEL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(trace|debug|info|warn(?:ing)?|err(?:or)?|fatal|critical)\b").unwrap()
+});
+static TIMESTAMP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?:(\d{4}-\d{2}-\d{2})[T ])?(\d{2}:\d{2}(?::\d{2})?)(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+    )
+    .unwrap()
+});
+
+/// Inserts a log file, filtered by level, time range and pattern, with
+/// repeated lines collapsed and trimmed to a token budget.
+pub struct LogSlashCommand;
+
+impl SlashCommand for LogSlashCommand {
+    fn name(&self) -> String {
+        "log".into()
+    }
+
+    fn label(&self, cx: &App) -> language::CodeLabel {
+        create_label_for_command("log", &["<path>"], cx)
+    }
+
+    fn description(&self) -> String {
+        "Insert a filtered log file".into()
+    }
+
+    fn icon(&self) -> IconName {
+        IconName::FileText
+    }
+
+    fn menu_text(&self) -> String {
+        self.description()
+    }
+
+    fn requires_argument(&self) -> bool {
+        true
+    }
+
+    fn accepts_arguments(&self) -> bool {
+        true
+    }
+
+    fn complete_argument(
+        self: Arc<Self>,
+        arguments: &[String],
+        cancel: Arc<AtomicBool>,
+        workspace: Option<WeakEntity<Workspace>>,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> Task<Result<Vec<ArgumentCompletion>>> {
+        let query = arguments.last().map(String::as_str).unwrap_or_default();
+        if !query.starts_with('-') {
+            return Arc::new(FileSlashCommand).complete_argument(
+                &arguments[arguments.len().saturating_sub(1)..],
+                cancel,
+                workspace,
+                window,
+                cx,
+            );
+        }
+
+        let completions = [
+            LEVEL_ARGUMENT,
+            SINCE_ARGUMENT,
+            UNTIL_ARGUMENT,
+            GREP_ARGUMENT,
+            MAX_TOKENS_ARGUMENT,
+        ]
+        .into_iter()
+        .filter(|argument| argument.starts_with(query))
+        .map(|argument| ArgumentCompletion {
+            label: argument.into(),
+            new_text: argument.to_string(),
+            after_completion: AfterCompletion::Compose,
+            replace_previous_arguments: false,
+        })
+        .collect();
+        Task::ready(Ok(completions))
+    }
+
+    fn run(
+        self: Arc<Self>,
+        arguments: &[String],
+        _context_slash_command_output_sections: &[SlashCommandOutputSection<language::Anchor>],
+        _context_buffer: BufferSnapshot,
+        workspace: WeakEntity<Workspace>,
+        _delegate: Option<Arc<dyn LspAdapterDelegate>>,
+        _window: &mut Window,
+        cx: &mut App,
+    ) -> Task<SlashCommandResult> {
+        let Some(workspace) = workspace.upgrade() else {
+            return Task::ready(Err(anyhow!("workspace was dropped")));
+        };
+        let (path, options) = match Options::parse(arguments) {
+            Ok(options) => options,
+            Err(error) => return Task::ready(Err(error)),
+        };
+
+        let project = workspace.read(cx).project().read(cx);
+        let fs = project.fs().clone();
+        let abs_path = project
+            .find_project_path(&path, cx)
+            .and_then(|project_path| project.absolute_path(&project_path, cx))
+            .or_else(|| Some(PathBuf::from(&path)).filter(|path| path.is_absolute()));
+        let Some(abs_path) = abs_path else {
+            return Task::ready(Err(anyhow!("no such log file: {path}")));
+        };
+
+        cx.background_spawn(async move {
+            let text = fs
+                .load(&abs_path)
+                .await
+                .with_context(|| format!("failed to read {path}"))?;
+            let view = LogView::new(&text, &options);
+            if view.entries.is_empty() {
+                bail!("No log lines matched");
+            }
+            Ok(log_output(&path, &view).to_event_stream())
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn parse(text: &str) -> Option<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "trace" => Some(Self::Trace),
+            "debug" => Some(Self::Debug),
+            "info" => Some(Self::Info),
+            "warn" | "warning" => Some(Self::Warn),
+            "err" | "error" | "fatal" | "critical" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    fn for_line(line: &str) -> Option<Self> {
+        Self::parse(LEVEL.find(line)?.as_str())
+    }
+}
+
+/// A point in time, as written at the start of a log line. Times without a
+/// date only compare their times.
+#[derive(Clone, Debug, PartialEq)]
+struct Timestamp {
+    date: Option<String>,
+    time: String,
+}
+
+impl Timestamp {
+    fn parse(text: &str) -> Option<Self> {
+        let captures = TIMESTAMP.captures(text)?;
+        let mut time = captures[2].to_string();
+        if time.len() == 5 {
+            time.push_str(":00");
+        }
+        Some(Self {
+            date: captures.get(1).map(|date| date.as_str().to_string()),
+            time,
+        })
+    }
+
+    fn for_line(line: &str) -> Option<Self> {
+        // Only look at the start of the line, so that times in the message
+        // aren't mistaken for the line's timestamp.
+        let prefix_end = line.char_indices().nth(40).map_or(line.len(), |(ix, _)| ix);
+        Self::parse(&line[..prefix_end])
+    }
+
+    fn compare(&self, other: &Self) -> std::cmp::Ordering {
+        match (&self.date, &other.date) {
+            (Some(date), Some(other_date)) => date.cmp(other_date).then(self.time.cmp(&other.time)),
+            _ => self.time.cmp(&other.time),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    level: Option<Level>,
+    since: Option<Timestamp>,
+    until: Option<Timestamp>,
+    pattern: Option<Regex>,
+    max_tokens: Option<usize>,
+}
+
+impl Options {
+    fn parse(arguments: &[String]) -> Result<(String, Self)> {
+        let mut path = None;
+        let mut options = Self::default();
+        for argument in arguments {
+            if let Some(level) = argument.strip_prefix(LEVEL_ARGUMENT) {
+                options.level =
+                    Some(Level::parse(level).with_context(|| format!("invalid level {level}"))?);
+            } else if let Some(since) = argument.strip_prefix(SINCE_ARGUMENT) {
+                options.since =
+                    Some(Timestamp::parse(since).with_context(|| format!("invalid time {since}"))?);
+            } else if let Some(until) = argument.strip_prefix(UNTIL_ARGUMENT) {
+                options.until =
+                    Some(Timestamp::parse(until).with_context(|| format!("invalid time {until}"))?);
+            } else if let Some(pattern) = argument.strip_prefix(GREP_ARGUMENT) {
+                options.pattern = Some(Regex::new(pattern)?);
+            } else if let Some(max_tokens) = argument.strip_prefix(MAX_TOKENS_ARGUMENT) {
+                options.max_tokens = Some(
+                    max_tokens
+                        .parse()
+                        .with_context(|| format!("invalid token budget {max_tokens}"))?,
+                );
+            } else if path.is_none() {
+                path = Some(argument.clone());
+            } else {
+                bail!("unexpected argument {argument}");
+            }
+        }
+        Ok((path.context("missing path")?, options))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct LogEntry {
+    line: String,
+    count: usize,
+}
+
+#[derive(Debug, PartialEq)]
+struct LogView {
+    entries: Vec<LogEntry>,
+    /// The number of lines that matched the filters, before deduplication.
+    matched_lines: usize,
+    /// The number of earlier entries that were dropped to fit the budget.
+    omitted_entries: usize,
+}
+
+impl LogView {
+    fn new(text: &str, options: &Options) -> Self {
+        let mut entries = Vec::<LogEntry>::new();
+        let mut entry_ixs = HashMap::<String, usize>::default();
+        let mut matched_lines = 0;
+        // Lines without a level or timestamp, like the lines of a backtrace,
+        // belong to the line before them.
+        let mut level = None;
+        let mut timestamp = None;
+        for line in text.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(line_timestamp) = Timestamp::for_line(line) {
+                timestamp = Some(line_timestamp);
+                level = Level::for_line(line);
+            } else if let Some(line_level) = Level::for_line(line) {
+                level = Some(line_level);
+            }
+
+            if options
+                .level
+                .is_some_and(|min_level| level.is_none_or(|level| level < min_level))
+            {
+                continue;
+            }
+            if let Some(timestamp) = timestamp.as_ref() {
+                if options
+                    .since
+                    .as_ref()
+                    .is_some_and(|since| timestamp.compare(since).is_lt())
+                    || options
+                        .until
+                        .as_ref()
+                        .is_some_and(|until| timestamp.compare(until).is_gt())
+                {
+                    continue;
+                }
+            }
+            if options
+                .pattern
+                .as_ref()
+                .is_some_and(|pattern| !pattern.is_match(line))
+            {
+                continue;
+            }
+
+            matched_lines += 1;
+            let key = TIMESTAMP.replace(line, "").trim().to_string();
+            if let Some(ix) = entry_ixs.get(&key) {
+                entries[*ix].count += 1;
+            } else {
+                entry_ixs.insert(key, entries.len());
+                entries.push(LogEntry {
+                    line: line.to_string(),
+                    count: 1,
+                });
+            }
+        }
+
+        // Keep the most recent entries that fit in the budget.
+        let max_len = options.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS) * BYTES_PER_TOKEN_ESTIMATE;
+        let mut len = 0;
+        let kept_entries = entries
+            .iter()
+            .rev()
+            .take_while(|entry| {
+                len += entry.line.len() + 1;
+                len <= max_len
+            })
+            .count();
+        let omitted_entries = entries.len() - kept_entries;
+        entries.drain(..omitted_entries);
+
+        Self {
+            entries,
+            matched_lines,
+            omitted_entries,
+        }
+    }
+}
+
+fn log_output(path: &str, view: &LogView) -> SlashCommandOutput {
+    let mut text = codeblock_fence_for_path(Some(Path::new(path)), None);
+    if view.omitted_entries > 0 {
+        writeln!(text, "[{} earlier lines omitted]", view.omitted_entries).unwrap();
+    }
+    for entry in &view.entries {
+        if entry.count > 1 {
+            writeln!(text, "{} [repeated {} times]", entry.line, entry.count).unwrap();
+        } else {
+            writeln!(text, "{}", entry.line).unwrap();
+        }
+    }
+    text.push_str("```");
+
+    SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: 0..text.len(),
+            icon: IconName::FileText,
+            label: format!("{path} ({} matching lines)", view.matched_lines).into(),
+            metadata: None,
+        }],
+        text,
+        run_commands_in_text: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const LOG: &str = indoc! {"
+        2024-05-01T10:00:00Z INFO starting
+        2024-05-01T10:00:01Z WARN slow request
+        2024-05-01T10:00:02Z ERROR connection refused
+        2024-05-01T10:00:03Z ERROR connection refused
+            at db::connect
+        2024-05-01T10:05:00Z DEBUG retrying
+        2024-05-01T10:06:00Z ERROR connection refused
+    "};
+
+    fn options(arguments: &[&str]) -> Options {
+        let mut arguments = arguments
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect::<Vec<_>>();
+        arguments.insert(0, "app.log".into());
+        Options::parse(&arguments).unwrap().1
+    }
+
+    fn lines(view: &LogView) -> Vec<(&str, usize)> {
+        view.entries
+            .iter()
+            .map(|entry| (entry.line.as_str(), entry.count))
+            .collect()
+    }
+
+    #[test]
+    fn test_log_view_filters_and_deduplicates() {
+        let view = LogView::new(LOG, &options(&["--level=warn"]));
+        assert_eq!(
+            lines(&view),
+            [
+                ("2024-05-01T10:00:01Z WARN slow request", 1),
+                ("2024-05-01T10:00:02Z ERROR connection refused", 3),
+                ("    at db::connect", 1),
+            ]
+        );
+        assert_eq!(view.matched_lines, 5);
+
+        let view = LogView::new(
+            LOG,
+            &options(&["--since=10:00:02", "--until=10:05", "--grep=retry|db"]),
+        );
+        assert_eq!(
+            lines(&view),
+            [
+                ("    at db::connect", 1),
+                ("2024-05-01T10:05:00Z DEBUG retrying", 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_log_view_budget() {
+        let view = LogView::new(LOG, &options(&["--max-tokens=20"]));
+        assert_eq!(
+            lines(&view),
+            [
+                ("    at db::connect", 1),
+                ("2024-05-01T10:05:00Z DEBUG retrying", 1)
+            ]
+        );
+        assert_eq!(view.omitted_entries, 3);
+
+        let output = log_output("app.log", &view);
+        assert_eq!(
+            output.text,
+            indoc! {"
+                ```log app.log
+                [3 earlier lines omitted]
+                    at db::connect
+                2024-05-01T10:05:00Z DEBUG retrying
+                ```"}
+        );
+    }
+
+    #[test]
+    fn test_parse_options() {
+        assert!(Options::parse(&[]).is_err());
+        assert!(Options::parse(&["app.log".into(), "--level=loud".into()]).is_err());
+        let (path, options) =
+            Options::parse(&["app.log".into(), "--since=2024-05-01 10:00".into()]).unwrap();
+        assert_eq!(path, "app.log");
+        assert_eq!(
+            options.since,
+            Some(Timestamp {
+                date: Some("2024-05-01".into()),
+                time: "10:00:00".into()
+            })
+        );
+    }
+}
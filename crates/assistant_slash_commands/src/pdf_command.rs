@@ -0,0 +1,260 @@
+use anyhow::{Context as _, Result, anyhow};
+use assistant_slash_command::{
+    AfterCompletion, ArgumentCompletion, SlashCommand, SlashCommandOutput,
+    SlashCommandOutputSection, SlashCommandResult,
+};
+use gpui::{App, Entity, Task, WeakEntity};
+use language::{BufferSnapshot, CodeLabel, HighlightId, LspAdapterDelegate};
+use project::Project;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::AtomicBool},
+};
+use ui::prelude::*;
+use workspace::Workspace;
+
+use crate::FileSlashCommand;
+
+/// The largest PDF that can be attached, which is the largest request
+/// Anthropic accepts.
+const MAX_PDF_SIZE: usize = 32 * 1024 * 1024;
+
+/// Roughly how many tokens a page of a PDF takes up. Anthropic counts both
+/// the text of a page and an image of it, which comes to between 1,500 and
+/// 3,000 tokens for most pages.
+const TOKENS_PER_PDF_PAGE: usize = 2_000;
+
+/// Attaches a PDF, which is sent as a document to the models that accept
+/// documents, so that they see its layout, tables and figures along with its
+/// text.
+pub struct PdfSlashCommand;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PdfCommandMetadata {
+    /// The absolute path of the PDF.
+    pub pdf_path: PathBuf,
+    pub page_count: usize,
+    pub estimated_tokens: usize,
+}
+
+impl SlashCommand for PdfSlashCommand {
+    fn name(&self) -> String {
+        "pdf".into()
+    }
+
+    fn description(&self) -> String {
+        "Attach a PDF".into()
+    }
+
+    fn menu_text(&self) -> String {
+        self.description()
+    }
+
+    fn icon(&self) -> IconName {
+        IconName::FileDoc
+    }
+
+    fn requires_argument(&self) -> bool {
+        true
+    }
+
+    fn complete_argument(
+        self: Arc<Self>,
+        arguments: &[String],
+        cancellation_flag: Arc<AtomicBool>,
+        workspace: Option<WeakEntity<Workspace>>,
+        _: &mut Window,
+        cx: &mut App,
+    ) -> Task<Result<Vec<ArgumentCompletion>>> {
+        let Some(workspace) = workspace.and_then(|workspace| workspace.upgrade()) else {
+            return Task::ready(Err(anyhow!("workspace was dropped")));
+        };
+
+        let paths =
+            FileSlashCommand.search_paths(arguments.join(" "), cancellation_flag, &workspace, cx);
+        let comment_id = cx.theme().syntax().highlight_id("comment").map(HighlightId);
+        cx.background_spawn(async move {
+            Ok(paths
+                .await
+                .into_iter()
+                .filter(|path_match| !path_match.is_dir && is_pdf_path(&path_match.path))
+                .filter_map(|path_match| {
+                    let text = format!(
+                        "{}{}",
+                        path_match.path_prefix,
+                        path_match.path.to_string_lossy()
+                    );
+                    let file_name = path_match.path.file_name()?.to_string_lossy();
+
+                    let mut label = CodeLabel::default();
+                    label.push_str(&format!("{file_name} "), None);
+                    label.push_str(&text, comment_id);
+                    label.filter_range = 0..file_name.len();
+
+                    Some(ArgumentCompletion {
+                        label,
+                        new_text: text,
+                        after_completion: AfterCompletion::Run,
+                        replace_previous_arguments: true,
+                    })
+                })
+                .collect())
+        })
+    }
+
+    fn run(
+        self: Arc<Self>,
+        arguments: &[String],
+        _context_slash_command_output_sections: &[SlashCommandOutputSection<language::Anchor>],
+        _context_buffer: BufferSnapshot,
+        workspace: WeakEntity<Workspace>,
+        _delegate: Option<Arc<dyn LspAdapterDelegate>>,
+        _: &mut Window,
+        cx: &mut App,
+    ) -> Task<SlashCommandResult> {
+        let Some(workspace) = workspace.upgrade() else {
+            return Task::ready(Err(anyhow!("workspace was dropped")));
+        };
+        let path = arguments.join(" ");
+        if path.is_empty() {
+            return Task::ready(Err(anyhow!("missing path")));
+        }
+
+        let project = workspace.read(cx).project().clone();
+        let Some(abs_path) = resolve_path(&project, &path, cx) else {
+            return Task::ready(Err(anyhow!("no such file: {path}")));
+        };
+        let fs = project.read(cx).fs().clone();
+        cx.background_spawn(async move {
+            let bytes = fs
+                .load_bytes(&abs_path)
+                .await
+                .with_context(|| format!("failed to read {path}"))?;
+            anyhow::ensure!(bytes.starts_with(b"%PDF"), "{path} is not a PDF");
+            anyhow::ensure!(
+                bytes.len() <= MAX_PDF_SIZE,
+                "{path} is larger than {} MB",
+                MAX_PDF_SIZE / 1024 / 1024
+            );
+
+            let page_count = pdf_page_count(&bytes);
+            let estimated_tokens = page_count * TOKENS_PER_PDF_PAGE;
+            let file_name = abs_path
+                .file_name()
+                .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned());
+            let text = format!("{path}\n");
+            let metadata = PdfCommandMetadata {
+                pdf_path: abs_path,
+                page_count,
+                estimated_tokens,
+            };
+            Ok(SlashCommandOutput {
+                sections: vec![SlashCommandOutputSection {
+                    range: 0..text.len(),
+                    icon: IconName::FileDoc,
+                    label: pdf_label(&file_name, page_count, estimated_tokens).into(),
+                    metadata: Some(serde_json::to_value(metadata)?),
+                }],
+                text,
+                run_commands_in_text: false,
+            }
+            .to_event_stream())
+        })
+    }
+}
+
+fn is_pdf_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"))
+}
+
+/// Resolves a path that's either absolute or relative to one of the project's
+/// worktrees, optionally starting with the worktree's name.
+fn resolve_path(project: &Entity<Project>, path: &str, cx: &App) -> Option<PathBuf> {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return Some(path.to_path_buf());
+    }
+    project.read(cx).visible_worktrees(cx).find_map(|worktree| {
+        let worktree = worktree.read(cx);
+        let relative_path = path.strip_prefix(worktree.root_name()).unwrap_or(path);
+        worktree
+            .entry_for_path(relative_path)
+            .map(|_| worktree.abs_path().join(relative_path))
+    })
+}
+
+fn pdf_label(file_name: &str, page_count: usize, estimated_tokens: usize) -> String {
+    let pages = if page_count == 1 { "page" } else { "pages" };
+    format!(
+        "{file_name} · {page_count} {pages} · ~{}k tokens",
+        estimated_tokens.div_ceil(1000)
+    )
+}
+
+/// Counts the pages of a PDF from its page objects, or else from the page
+/// count of its page tree, without parsing the whole document. Documents
+/// whose page objects are all compressed may be counted as a single page.
+fn pdf_page_count(bytes: &[u8]) -> usize {
+    let page_objects = bytes
+        .windows(b"/Type".len())
+        .enumerate()
+        .filter(|(_, window)| *window == b"/Type")
+        .filter(|(ix, _)| {
+            let rest = &bytes[ix + b"/Type".len()..];
+            let rest = &rest[rest
+                .iter()
+                .position(|byte| !byte.is_ascii_whitespace())
+                .unwrap_or(rest.len())..];
+            rest.starts_with(b"/Page") && !rest[b"/Page".len()..].starts_with(b"s")
+        })
+        .count();
+    if page_objects > 0 {
+        return page_objects;
+    }
+
+    bytes
+        .windows(b"/Count".len())
+        .position(|window| window == b"/Count")
+        .and_then(|ix| {
+            bytes[ix + b"/Count".len()..]
+                .iter()
+                .skip_while(|byte| byte.is_ascii_whitespace())
+                .take_while(|byte| byte.is_ascii_digit())
+                .map(|byte| *byte as char)
+                .collect::<String>()
+                .parse()
+                .ok()
+        })
+        .unwrap_or(1)
+        .max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pdf_page_count() {
+        let pdf = b"1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj\n\
+            2 0 obj << /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >> endobj\n\
+            3 0 obj << /Type /Page /Parent 2 0 R >> endobj\n\
+            4 0 obj << /Type/Page /Parent 2 0 R >> endobj";
+        assert_eq!(pdf_page_count(pdf), 2);
+        assert_eq!(
+            pdf_page_count(b"2 0 obj << /Type /Pages /Count 12 >> endobj"),
+            12
+        );
+        assert_eq!(pdf_page_count(b"%PDF-1.7"), 1);
+
+        assert_eq!(
+            pdf_label("spec.pdf", 1, 2_000),
+            "spec.pdf · 1 page · ~2k tokens"
+        );
+        assert_eq!(
+            pdf_label("spec.pdf", 12, 24_000),
+            "spec.pdf · 12 pages · ~24k tokens"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::{Result, anyhow};
+use assistant_slash_command::{
+    ArgumentCompletion, SlashCommand, SlashCommandOutput, SlashCommandOutputSection,
+    SlashCommandResult,
+};
+use gpui::{App, Task, WeakEntity};
+use language::{BufferSnapshot, CodeLabel, LspAdapterDelegate};
+use semantic_index::{LoadedSearchResult, SemanticDb};
+use std::sync::{Arc, atomic::AtomicBool};
+use ui::prelude::*;
+use workspace::Workspace;
+
+use crate::{build_entry_output_section, codeblock_fence_for_path, create_label_for_command};
+
+const DEFAULT_LIMIT: usize = 5;
+
+/// Inserts the excerpts of the project that are most relevant to a query,
+/// found with the semantic index. `--N` asks for N excerpts.
+pub struct SearchSlashCommand;
+
+impl SlashCommand for SearchSlashCommand {
+    fn name(&self) -> String {
+        "search".into()
+    }
+
+    fn label(&self, cx: &App) -> CodeLabel {
+        create_label_for_command("search", &["--n"], cx)
+    }
+
+    fn description(&self) -> String {
+        "Search your project semantically".into()
+    }
+
+    fn icon(&self) -> IconName {
+        IconName::SearchCode
+    }
+
+    fn menu_text(&self) -> String {
+        self.description()
+    }
+
+    fn requires_argument(&self) -> bool {
+        true
+    }
+
+    fn complete_argument(
+        self: Arc<Self>,
+        _arguments: &[String],
+        _cancel: Arc<AtomicBool>,
+        _workspace: Option<WeakEntity<Workspace>>,
+        _window: &mut Window,
+        _cx: &mut App,
+    ) -> Task<Result<Vec<ArgumentCompletion>>> {
+        Task::ready(Ok(Vec::new()))
+    }
+
+    fn run(
+        self: Arc<Self>,
+        arguments: &[String],
+        _context_slash_command_output_sections: &[SlashCommandOutputSection<language::Anchor>],
+        _context_buffer: BufferSnapshot,
+        workspace: WeakEntity<Workspace>,
+        _delegate: Option<Arc<dyn LspAdapterDelegate>>,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> Task<SlashCommandResult> {
+        let Some(workspace) = workspace.upgrade() else {
+            return Task::ready(Err(anyhow!("workspace was dropped")));
+        };
+        let Some((query, limit)) = parse_arguments(arguments) else {
+            return Task::ready(Err(anyhow!("missing search query")));
+        };
+        if !cx.has_global::<SemanticDb>() {
+            return Task::ready(Err(anyhow!("the semantic index isn't loaded yet")));
+        }
+
+        let project = workspace.read(cx).project().clone();
+        let project_index = cx.update_global(|semantic_db: &mut SemanticDb, cx| {
+            semantic_db.project_index(project, cx)
+        });
+
+        window.spawn(cx, async move |cx| {
+            let results = project_index
+                .read_with(cx, |project_index, cx| {
+                    project_index.retrieve(query.clone(), limit, cx)
+                })?
+                .await?;
+
+            let output = cx
+                .background_spawn(async move {
+                    let mut text = format!("Search results for {query}:\n");
+                    let mut sections = Vec::new();
+                    for result in &results {
+                        add_search_result_section(result, &mut text, &mut sections);
+                    }
+
+                    sections.push(SlashCommandOutputSection {
+                        range: 0..text.len(),
+                        icon: IconName::MagnifyingGlass,
+                        label: query.into(),
+                        metadata: None,
+                    });
+
+                    SlashCommandOutput {
+                        text,
+                        sections,
+                        run_commands_in_text: false,
+                    }
+                    .to_event_stream()
+                })
+                .await;
+
+            Ok(output)
+        })
+    }
+}
+
+/// Splits the arguments into the query and the number of results, which is
+/// given as `--N`.
+fn parse_arguments(arguments: &[String]) -> Option<(String, usize)> {
+    let mut limit = None;
+    let mut query = Vec::new();
+    for argument in arguments {
+        if let Some(count) = argument
+            .strip_prefix("--")
+            .and_then(|count| count.parse::<usize>().ok())
+        {
+            limit = Some(count);
+        } else {
+            query.push(argument.as_str());
+        }
+    }
+
+    if query.is_empty() {
+        return None;
+    }
+    Some((query.join(" "), limit.unwrap_or(DEFAULT_LIMIT).max(1)))
+}
+
+pub fn add_search_result_section(
+    loaded_result: &LoadedSearchResult,
+    text: &mut String,
+    sections: &mut Vec<SlashCommandOutputSection<usize>>,
+) {
+    let LoadedSearchResult {
+        path,
+        full_path,
+        excerpt_content,
+        row_range,
+        ..
+    } = loaded_result;
+    let section_start_ix = text.len();
+    text.push_str(&codeblock_fence_for_path(
+        Some(path.as_ref()),
+        Some(row_range.clone()),
+    ));
+    text.push_str(excerpt_content);
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str("```\n\n");
+    let section_end_ix = text.len() - 1;
+    sections.push(build_entry_output_section(
+        section_start_ix..section_end_ix,
+        Some(full_path.as_path()),
+        false,
+        Some(row_range.start() + 1..row_range.end() + 1),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arguments() {
+        let arguments = |arguments: &[&str]| {
+            parse_arguments(
+                &arguments
+                    .iter()
+                    .map(|argument| argument.to_string())
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        assert_eq!(
+            arguments(&["where", "are", "embeddings", "stored"]),
+            Some(("where are embeddings stored".to_string(), DEFAULT_LIMIT))
+        );
+        assert_eq!(
+            arguments(&["--12", "tokenizer", "--verbose"]),
+            Some(("tokenizer --verbose".to_string(), 12))
+        );
+        assert_eq!(arguments(&["--3"]), None);
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context as _, Result, anyhow};
+use assistant_slash_command::{
+    ArgumentCompletion, SlashCommand, SlashCommandOutput, SlashCommandOutputSection,
+    SlashCommandResult,
+};
+use gpui::{Task, WeakEntity};
+use language::{BufferSnapshot, LspAdapterDelegate};
+use std::fmt::Write as _;
+use std::sync::{Arc, atomic::AtomicBool};
+use ui::prelude::*;
+use workspace::Workspace;
+
+use crate::{LibrarySnippet, SnippetLibrary};
+
+pub struct SnippetSlashCommand;
+
+impl SlashCommand for SnippetSlashCommand {
+    fn name(&self) -> String {
+        "snippet".into()
+    }
+
+    fn description(&self) -> String {
+        "Insert snippet from library".into()
+    }
+
+    fn icon(&self) -> IconName {
+        IconName::Code
+    }
+
+    fn menu_text(&self) -> String {
+        self.description()
+    }
+
+    fn requires_argument(&self) -> bool {
+        true
+    }
+
+    fn complete_argument(
+        self: Arc<Self>,
+        arguments: &[String],
+        _cancellation_flag: Arc<AtomicBool>,
+        _workspace: Option<WeakEntity<Workspace>>,
+        _: &mut Window,
+        cx: &mut App,
+    ) -> Task<Result<Vec<ArgumentCompletion>>> {
+        let Some(library) = SnippetLibrary::global(cx) else {
+            return Task::ready(Ok(Vec::new()));
+        };
+        let query = arguments.join(" ");
+        Task::ready(Ok(library
+            .search(&query)
+            .into_iter()
+            .map(|snippet| ArgumentCompletion {
+                label: completion_label(snippet).into(),
+                new_text: snippet.name.clone(),
+                after_completion: true.into(),
+                replace_previous_arguments: true,
+            })
+            .collect()))
+    }
+
+    fn run(
+        self: Arc<Self>,
+        arguments: &[String],
+        _context_slash_command_output_sections: &[SlashCommandOutputSection<language::Anchor>],
+        _context_buffer: BufferSnapshot,
+        _workspace: WeakEntity<Workspace>,
+        _delegate: Option<Arc<dyn LspAdapterDelegate>>,
+        _: &mut Window,
+        cx: &mut App,
+    ) -> Task<SlashCommandResult> {
+        let name = arguments.join(" ");
+        if name.trim().is_empty() {
+            return Task::ready(Err(anyhow!("missing snippet name")));
+        }
+
+        let snippet = SnippetLibrary::global(cx)
+            .and_then(|library| library.get(name.trim()))
+            .cloned()
+            .with_context(|| format!("no snippet found with name {name:?}"));
+        Task::ready(snippet.map(|snippet| {
+            let text = snippet_text(&snippet);
+            SlashCommandOutput {
+                sections: vec![SlashCommandOutputSection {
+                    range: 0..text.len(),
+                    icon: IconName::Code,
+                    label: snippet.name.into(),
+                    metadata: None,
+                }],
+                text,
+                run_commands_in_text: false,
+            }
+            .to_event_stream()
+        }))
+    }
+}
+
+fn completion_label(snippet: &LibrarySnippet) -> String {
+    let mut label = snippet.name.clone();
+    if let Some(language) = &snippet.language {
+        write!(label, " ({language})").unwrap();
+    }
+    if !snippet.tags.is_empty() {
+        write!(label, " #{}", snippet.tags.join(" #")).unwrap();
+    }
+    label
+}
+
+fn snippet_text(snippet: &LibrarySnippet) -> String {
+    let mut text = String::new();
+    if let Some(origin) = &snippet.origin {
+        match &origin.path {
+            Some(path) => writeln!(
+                text,
+                "From text thread \"{}\" ({})",
+                origin.title,
+                path.display()
+            ),
+            None => writeln!(text, "From text thread \"{}\"", origin.title),
+        }
+        .unwrap();
+    }
+    writeln!(
+        text,
+        "```{}",
+        snippet.language.as_deref().unwrap_or_default()
+    )
+    .unwrap();
+    text.push_str(&snippet.code);
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str("```\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SnippetOrigin;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_snippet_text() {
+        let mut snippet = LibrarySnippet {
+            name: "retry loop".into(),
+            language: Some("rust".into()),
+            tags: vec!["async".into(), "http".into()],
+            code: "loop {}".into(),
+            origin: None,
+            saved_at: Default::default(),
+        };
+        assert_eq!(completion_label(&snippet), "retry loop (rust) #async #http");
+        assert_eq!(snippet_text(&snippet), "```rust\nloop {}\n```\n");
+
+        snippet.origin = Some(SnippetOrigin {
+            context_id: "1".into(),
+            title: "Retries".into(),
+            path: Some(PathBuf::from("/threads/Retries.zed.json")),
+        });
+        assert_eq!(
+            snippet_text(&snippet),
+            "From text thread \"Retries\" (/threads/Retries.zed.json)\n```rust\nloop {}\n```\n"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use gpui::{App, AppContext as _, Global};
+use serde::{Deserialize, Serialize};
+use util::ResultExt as _;
+
+/// A code block saved from a text thread, which can be inserted again with
+/// the `/snippet` command.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LibrarySnippet {
+    pub name: String,
+    /// The language of the code block's fence, such as `rust`.
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub code: String,
+    /// The text thread the snippet was saved from.
+    #[serde(default)]
+    pub origin: Option<SnippetOrigin>,
+    pub saved_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnippetOrigin {
+    pub context_id: String,
+    pub title: String,
+    /// Where the text thread is saved, if it has been saved.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+impl LibrarySnippet {
+    fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        query.is_empty()
+            || self.name.to_lowercase().contains(&query)
+            || self
+                .language
+                .as_ref()
+                .is_some_and(|language| language.to_lowercase() == query)
+            || self
+                .tags
+                .iter()
+                .any(|tag| tag.to_lowercase().contains(&query))
+    }
+}
+
+/// The snippets saved from text threads, persisted to
+/// [`paths::snippet_library_file`].
+#[derive(Default)]
+pub struct SnippetLibrary {
+    snippets: Vec<LibrarySnippet>,
+}
+
+impl Global for SnippetLibrary {}
+
+impl SnippetLibrary {
+    pub fn init(cx: &mut App) {
+        cx.set_global(SnippetLibrary::default());
+        cx.spawn(async move |cx| {
+            let snippets = cx
+                .background_spawn(async move {
+                    let contents = std::fs::read_to_string(paths::snippet_library_file())?;
+                    anyhow::Ok(serde_json::from_str::<Vec<LibrarySnippet>>(&contents)?)
+                })
+                .await;
+            if let Ok(snippets) = snippets {
+                cx.update_global::<Self, _>(|library, _| {
+                    // Keep the snippets saved while the file was being read.
+                    for snippet in snippets {
+                        if library.get(&snippet.name).is_none() {
+                            library.snippets.push(snippet);
+                        }
+                    }
+                })
+                .log_err();
+            }
+        })
+        .detach();
+    }
+
+    pub fn global(cx: &App) -> Option<&Self> {
+        cx.try_global::<Self>()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LibrarySnippet> {
+        self.snippets.iter().find(|snippet| snippet.name == name)
+    }
+
+    /// Returns the snippets whose name, language or tags match the query,
+    /// most recently saved first.
+    pub fn search(&self, query: &str) -> Vec<&LibrarySnippet> {
+        let mut snippets = self
+            .snippets
+            .iter()
+            .filter(|snippet| snippet.matches(query))
+            .collect::<Vec<_>>();
+        snippets.sort_by(|a, b| b.saved_at.cmp(&a.saved_at));
+        snippets
+    }
+
+    fn insert(&mut self, snippet: LibrarySnippet) {
+        self.snippets
+            .retain(|existing| existing.name != snippet.name);
+        self.snippets.push(snippet);
+    }
+
+    /// Saves the snippet, replacing any snippet with the same name.
+    pub fn save(snippet: LibrarySnippet, cx: &mut App) {
+        if !cx.has_global::<Self>() {
+            return;
+        }
+
+        let snippets = cx.update_global::<Self, _>(|library, _| {
+            library.insert(snippet);
+            library.snippets.clone()
+        });
+
+        cx.background_spawn(async move {
+            let path = paths::snippet_library_file();
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, serde_json::to_string_pretty(&snippets)?)?;
+            anyhow::Ok(())
+        })
+        .detach_and_log_err(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(name: &str, language: Option<&str>, tags: &[&str], day: u32) -> LibrarySnippet {
+        LibrarySnippet {
+            name: name.into(),
+            language: language.map(Into::into),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            code: "fn main() {}\n".into(),
+            origin: None,
+            saved_at: format!("2025-01-{day:02}T00:00:00Z").parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        let mut library = SnippetLibrary::default();
+        library.insert(snippet("retry loop", Some("rust"), &["async", "http"], 1));
+        library.insert(snippet("debounce", Some("typescript"), &["ui"], 2));
+        library.insert(snippet("Backoff", None, &["HTTP"], 3));
+
+        let names = |query| {
+            library
+                .search(query)
+                .into_iter()
+                .map(|snippet| snippet.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(""), ["Backoff", "debounce", "retry loop"]);
+        assert_eq!(names("http"), ["Backoff", "retry loop"]);
+        assert_eq!(names("RUST"), ["retry loop"]);
+        assert_eq!(names("back"), ["Backoff"]);
+        assert!(names("python").is_empty());
+
+        library.insert(snippet("debounce", Some("javascript"), &[], 4));
+        assert_eq!(library.search("debounce").len(), 1);
+        assert_eq!(
+            library.get("debounce").unwrap().language.as_deref(),
+            Some("javascript")
+        );
+    }
+}
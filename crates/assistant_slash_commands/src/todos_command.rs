@@ -0,0 +1,386 @@
+use anyhow::{Context as _, Result, anyhow};
+use assistant_slash_command::{
+    AfterCompletion, ArgumentCompletion, SlashCommand, SlashCommandOutput,
+    SlashCommandOutputSection, SlashCommandResult,
+};
+use futures::StreamExt;
+use gpui::{App, Task, WeakEntity};
+use language::{BufferSnapshot, LspAdapterDelegate, Point, ToPoint};
+use project::{
+    WorktreeSettings,
+    search::{SearchQuery, SearchResult},
+};
+use settings::Settings;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, atomic::AtomicBool},
+};
+use ui::prelude::*;
+use util::ResultExt;
+use util::paths::PathMatcher;
+use workspace::Workspace;
+
+use crate::create_label_for_command;
+
+const BLAME_ARGUMENT: &str = "--blame";
+const TODO_MARKERS: [&str; 3] = ["TODO", "FIXME", "HACK"];
+const MAX_TODO_LENGTH: usize = 200;
+
+/// Inserts a report of the TODO, FIXME and HACK comments in the project,
+/// grouped by file or, with `--blame`, by owner.
+pub struct TodosSlashCommand;
+
+impl SlashCommand for TodosSlashCommand {
+    fn name(&self) -> String {
+        "todos".into()
+    }
+
+    fn label(&self, cx: &App) -> language::CodeLabel {
+        create_label_for_command("todos", &[BLAME_ARGUMENT], cx)
+    }
+
+    fn description(&self) -> String {
+        "Insert TODO, FIXME and HACK comments".into()
+    }
+
+    fn icon(&self) -> IconName {
+        IconName::ListTodo
+    }
+
+    fn menu_text(&self) -> String {
+        self.description()
+    }
+
+    fn requires_argument(&self) -> bool {
+        false
+    }
+
+    fn accepts_arguments(&self) -> bool {
+        true
+    }
+
+    fn paginate_output(&self) -> bool {
+        true
+    }
+
+    fn complete_argument(
+        self: Arc<Self>,
+        arguments: &[String],
+        _cancel: Arc<AtomicBool>,
+        _workspace: Option<WeakEntity<Workspace>>,
+        _window: &mut Window,
+        _cx: &mut App,
+    ) -> Task<Result<Vec<ArgumentCompletion>>> {
+        let query = arguments.last().map(String::as_str).unwrap_or_default();
+        let completions = if BLAME_ARGUMENT.starts_with(query)
+            && !arguments.iter().any(|argument| argument == BLAME_ARGUMENT)
+        {
+            vec![ArgumentCompletion {
+                label: BLAME_ARGUMENT.into(),
+                new_text: BLAME_ARGUMENT.to_string(),
+                after_completion: AfterCompletion::Run,
+                replace_previous_arguments: false,
+            }]
+        } else {
+            Vec::new()
+        };
+        Task::ready(Ok(completions))
+    }
+
+    fn run(
+        self: Arc<Self>,
+        arguments: &[String],
+        _context_slash_command_output_sections: &[SlashCommandOutputSection<language::Anchor>],
+        _context_buffer: BufferSnapshot,
+        workspace: WeakEntity<Workspace>,
+        _delegate: Option<Arc<dyn LspAdapterDelegate>>,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> Task<SlashCommandResult> {
+        let Some(workspace) = workspace.upgrade() else {
+            return Task::ready(Err(anyhow!("workspace was dropped")));
+        };
+        let options = Options::parse(arguments);
+        let query = match options.search_query(cx) {
+            Ok(query) => query,
+            Err(error) => return Task::ready(Err(error)),
+        };
+
+        let project = workspace.read(cx).project().clone();
+        let results = project.update(cx, |project, cx| project.search(query, cx));
+        window.spawn(cx, async move |cx| {
+            futures::pin_mut!(results);
+
+            let mut todos = Vec::new();
+            while let Some(result) = results.next().await {
+                let SearchResult::Buffer { buffer, ranges } = result else {
+                    continue;
+                };
+                let Some(path) = buffer.read_with(cx, |buffer, cx| {
+                    buffer
+                        .file()
+                        .map(|file| file.full_path(cx).to_string_lossy().into_owned())
+                })?
+                else {
+                    continue;
+                };
+                let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+                let blame = if options.blame {
+                    project
+                        .update(cx, |project, cx| project.blame_buffer(&buffer, None, cx))?
+                        .await
+                        .log_err()
+                        .flatten()
+                } else {
+                    None
+                };
+
+                for range in ranges {
+                    let start = range.start.to_point(&snapshot);
+                    let line_end = Point::new(start.row, snapshot.line_len(start.row));
+                    let line = snapshot.text_for_range(start..line_end).collect::<String>();
+                    let Some(todo) = Todo::parse(path.clone(), start.row, &line) else {
+                        continue;
+                    };
+                    let todo = if todo.owner.is_none() {
+                        let author = blame.as_ref().and_then(|blame| {
+                            blame
+                                .entries
+                                .iter()
+                                .find(|entry| entry.range.contains(&start.row))?
+                                .author
+                                .clone()
+                        });
+                        Todo {
+                            owner: author,
+                            ..todo
+                        }
+                    } else {
+                        todo
+                    };
+                    todos.push(todo);
+                }
+            }
+
+            todos_output(todos, options.blame)
+                .map(|output| output.to_event_stream())
+                .context("No TODOs found")
+        })
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    blame: bool,
+    path_matcher: Option<PathMatcher>,
+}
+
+impl Options {
+    fn parse(arguments: &[String]) -> Self {
+        let mut blame = false;
+        let mut path_matcher = None;
+        for argument in arguments {
+            if argument == BLAME_ARGUMENT {
+                blame = true;
+            } else {
+                path_matcher = PathMatcher::new(&[argument.to_owned()]).log_err();
+            }
+        }
+        Self {
+            blame,
+            path_matcher,
+        }
+    }
+
+    /// Searches the project for the markers, skipping the files that are
+    /// ignored, excluded from scanning or private.
+    fn search_query(&self, cx: &App) -> Result<SearchQuery> {
+        let global_settings = WorktreeSettings::get_global(cx);
+        let exclude_patterns = global_settings
+            .file_scan_exclusions
+            .sources()
+            .iter()
+            .chain(global_settings.private_files.sources().iter());
+
+        SearchQuery::regex(
+            format!(r"\b({})\b", TODO_MARKERS.join("|")),
+            false,
+            true,
+            false,
+            true,
+            self.path_matcher.clone().unwrap_or_default(),
+            PathMatcher::new(exclude_patterns)?,
+            true,
+            None,
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Todo {
+    path: String,
+    row: u32,
+    marker: &'static str,
+    text: String,
+    owner: Option<String>,
+}
+
+impl Todo {
+    /// Parses the comment that starts at one of the markers, taking its owner
+    /// from a `TODO(owner)` annotation, if it has one.
+    fn parse(path: String, row: u32, line: &str) -> Option<Self> {
+        let marker = TODO_MARKERS
+            .into_iter()
+            .find(|marker| line.starts_with(marker))?;
+        let owner = line[marker.len()..]
+            .strip_prefix('(')
+            .and_then(|rest| Some(rest[..rest.find(')')?].trim()))
+            .filter(|owner| !owner.is_empty())
+            .map(ToString::to_string);
+        Some(Self {
+            path,
+            row,
+            marker,
+            text: util::truncate_and_trailoff(line.trim_end(), MAX_TODO_LENGTH),
+            owner,
+        })
+    }
+
+    fn group(&self, by_owner: bool) -> &str {
+        if by_owner {
+            self.owner.as_deref().unwrap_or("Unknown")
+        } else {
+            &self.path
+        }
+    }
+}
+
+fn todos_output(todos: Vec<Todo>, by_owner: bool) -> Option<SlashCommandOutput> {
+    if todos.is_empty() {
+        return None;
+    }
+
+    let mut marker_counts = [0; TODO_MARKERS.len()];
+    let mut groups = BTreeMap::<String, Vec<Todo>>::new();
+    for todo in todos {
+        if let Some(ix) = TODO_MARKERS
+            .iter()
+            .position(|marker| *marker == todo.marker)
+        {
+            marker_counts[ix] += 1;
+        }
+        groups
+            .entry(todo.group(by_owner).to_string())
+            .or_default()
+            .push(todo);
+    }
+
+    let mut output = SlashCommandOutput::default();
+    writeln!(output.text, "todos").unwrap();
+    for (group, mut todos) in groups {
+        todos.sort_by(|a, b| a.path.cmp(&b.path).then(a.row.cmp(&b.row)));
+
+        let section_start = output.text.len();
+        writeln!(output.text, "{group}").unwrap();
+        for todo in &todos {
+            if by_owner {
+                write!(output.text, "{}:", todo.path).unwrap();
+            }
+            writeln!(output.text, "{}: {}", todo.row + 1, todo.text).unwrap();
+        }
+        output.sections.push(SlashCommandOutputSection {
+            range: section_start..output.text.len().saturating_sub(1),
+            icon: if by_owner {
+                IconName::Person
+            } else {
+                IconName::File
+            },
+            label: format!("{group} ({})", todos.len()).into(),
+            metadata: None,
+        });
+    }
+
+    let counts = TODO_MARKERS
+        .iter()
+        .zip(marker_counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(marker, count)| format!("{count} {marker}"))
+        .collect::<Vec<_>>();
+    output.sections.insert(
+        0,
+        SlashCommandOutputSection {
+            range: 0..output.text.len(),
+            icon: IconName::ListTodo,
+            label: format!("TODOs: {}", counts.join(", ")).into(),
+            metadata: None,
+        },
+    );
+
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_todo() {
+        assert_eq!(
+            Todo::parse("a.rs".into(), 3, "TODO(ana): handle errors"),
+            Some(Todo {
+                path: "a.rs".into(),
+                row: 3,
+                marker: "TODO",
+                text: "TODO(ana): handle errors".into(),
+                owner: Some("ana".into()),
+            })
+        );
+        assert_eq!(
+            Todo::parse("a.rs".into(), 3, "FIXME: off by one  ").map(|todo| (
+                todo.marker,
+                todo.text,
+                todo.owner
+            )),
+            Some(("FIXME", "FIXME: off by one".to_string(), None))
+        );
+        assert_eq!(
+            Todo::parse("a.rs".into(), 3, "HACK()").and_then(|todo| todo.owner),
+            None
+        );
+        assert_eq!(Todo::parse("a.rs".into(), 3, "NOTE: fine"), None);
+    }
+
+    #[test]
+    fn test_todos_output() {
+        let todos = vec![
+            Todo::parse("b.rs".into(), 9, "FIXME(ana): b").unwrap(),
+            Todo::parse("a.rs".into(), 4, "TODO: a").unwrap(),
+            Todo::parse("b.rs".into(), 1, "TODO(bo): c").unwrap(),
+        ];
+
+        let output = todos_output(todos.clone(), false).unwrap();
+        assert_eq!(
+            output.text,
+            "todos\na.rs\n5: TODO: a\nb.rs\n2: TODO(bo): c\n10: FIXME(ana): b\n"
+        );
+        assert_eq!(
+            output
+                .sections
+                .iter()
+                .map(|section| section.label.to_string())
+                .collect::<Vec<_>>(),
+            ["TODOs: 2 TODO, 1 FIXME", "a.rs (1)", "b.rs (2)"]
+        );
+
+        let output = todos_output(todos, true).unwrap();
+        assert_eq!(
+            output.text,
+            "todos\nUnknown\na.rs:5: TODO: a\nana\nb.rs:10: FIXME(ana): b\nbo\nb.rs:2: TODO(bo): c\n"
+        );
+
+        assert_eq!(todos_output(Vec::new(), false), None);
+    }
+}
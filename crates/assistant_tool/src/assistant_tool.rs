@@ -74,6 +74,13 @@ pub struct ToolResultOutput {
 pub enum ToolResultContent {
     Text(String),
     Image(LanguageModelImage),
+    /// Text sent as the tool's result, followed by images attached to the
+    /// request after it, for tools whose output doesn't fit in a single
+    /// result.
+    TextWithImages {
+        text: String,
+        images: Vec<LanguageModelImage>,
+    },
 }
 
 impl ToolResultContent {
@@ -81,6 +88,9 @@ impl ToolResultContent {
         match self {
             ToolResultContent::Text(str) => str.len(),
             ToolResultContent::Image(image) => image.len(),
+            ToolResultContent::TextWithImages { text, images } => {
+                text.len() + images.iter().map(LanguageModelImage::len).sum::<usize>()
+            }
         }
     }
 
@@ -88,6 +98,9 @@ impl ToolResultContent {
         match self {
             ToolResultContent::Text(str) => str.is_empty(),
             ToolResultContent::Image(image) => image.is_empty(),
+            ToolResultContent::TextWithImages { text, images } => {
+                text.is_empty() && images.iter().all(LanguageModelImage::is_empty)
+            }
         }
     }
 
@@ -95,6 +108,7 @@ impl ToolResultContent {
         match self {
             ToolResultContent::Text(str) => Some(str),
             ToolResultContent::Image(_) => None,
+            ToolResultContent::TextWithImages { text, .. } => Some(text),
         }
     }
 }
@@ -221,6 +235,11 @@ pub trait Tool: 'static + Send + Sync {
     /// Returns true if the tool may perform edits.
     fn may_perform_edits(&self) -> bool;
 
+    /// Returns true if the tool may run shell commands or other programs.
+    fn may_run_commands(&self) -> bool {
+        false
+    }
+
     /// Returns the JSON schema that describes the tool's input.
     fn input_schema(&self, _: LanguageModelToolSchemaFormat) -> Result<serde_json::Value> {
         Ok(serde_json::Value::Object(serde_json::Map::default()))
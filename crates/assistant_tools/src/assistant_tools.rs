@@ -11,8 +11,11 @@ mod list_directory_tool;
 mod move_path_tool;
 mod now_tool;
 mod open_tool;
+mod propose_tasks_tool;
 mod read_file_tool;
+mod run_python_tool;
 mod schema;
+mod sql_query_tool;
 mod templates;
 mod terminal_tool;
 mod thinking_tool;
@@ -39,6 +42,9 @@ use crate::fetch_tool::FetchTool;
 use crate::find_path_tool::FindPathTool;
 use crate::list_directory_tool::ListDirectoryTool;
 use crate::now_tool::NowTool;
+use crate::propose_tasks_tool::ProposeTasksTool;
+use crate::run_python_tool::RunPythonTool;
+use crate::sql_query_tool::SqlQueryTool;
 use crate::thinking_tool::ThinkingTool;
 
 pub use edit_file_tool::{EditFileMode, EditFileToolInput};
@@ -61,9 +67,12 @@ pub fn init(http_client: Arc<HttpClientWithUrl>, cx: &mut App) {
     registry.register_tool(ListDirectoryTool);
     registry.register_tool(NowTool);
     registry.register_tool(OpenTool);
+    registry.register_tool(ProposeTasksTool);
     registry.register_tool(FindPathTool);
     registry.register_tool(ReadFileTool);
     registry.register_tool(GrepTool);
+    registry.register_tool(RunPythonTool);
+    registry.register_tool(SqlQueryTool::default());
     registry.register_tool(ThinkingTool);
     registry.register_tool(FetchTool::new(http_client));
     registry.register_tool(EditFileTool);
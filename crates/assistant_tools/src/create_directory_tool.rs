@@ -42,7 +42,7 @@ impl Tool for CreateDirectoryTool {
     }
 
     fn may_perform_edits(&self) -> bool {
-        false
+        true
     }
 
     fn icon(&self) -> IconName {
@@ -19,7 +19,7 @@ use gpui::{AppContext, AsyncApp, Entity, Task};
 use language::{Anchor, Buffer, BufferSnapshot, LineIndent, Point, TextBufferSnapshot};
 use language_model::{
     LanguageModel, LanguageModelCompletionError, LanguageModelRequest, LanguageModelRequestMessage,
-    LanguageModelToolChoice, MessageContent, Role,
+    LanguageModelToolChoice, MessageContent, RequestPriority, Role,
 };
 use project::{AgentLocation, Project};
 use schemars::JsonSchema;
@@ -681,6 +681,9 @@ impl EditAgent {
             tools,
             stop: Vec::new(),
             temperature: None,
+            seed: None,
+            priority: RequestPriority::Interactive,
+            web_search: false,
         };
 
         Ok(self.model.stream_completion_text(request, cx).await?.stream)
@@ -29,6 +29,9 @@ impl Tool for OpenTool {
     fn may_perform_edits(&self) -> bool {
         false
     }
+    fn may_run_commands(&self) -> bool {
+        true
+    }
     fn description(&self) -> String {
         include_str!("./open_tool/description.md").to_string()
     }
@@ -0,0 +1,303 @@
+use crate::{schema::json_schema_for, ui::ToolCallCardHeader};
+use anyhow::{Result, anyhow};
+use assistant_tool::{
+    ActionLog, Tool, ToolCard, ToolResult, ToolResultContent, ToolResultOutput, ToolUseStatus,
+};
+use collections::{HashMap, HashSet};
+use gpui::{AnyWindowHandle, App, AppContext, Context, Entity, IntoElement, Task, WeakEntity};
+use language_model::{LanguageModel, LanguageModelRequest, LanguageModelToolSchemaFormat};
+use project::{Project, TaskSourceKind};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, sync::Arc};
+use task::{TaskContext, TaskTemplate};
+use ui::{Tooltip, prelude::*};
+use util::ResultExt as _;
+use workspace::Workspace;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ProposeTasksToolInput {
+    /// The tasks to show to the user, in the order they should be run.
+    tasks: Vec<ProposedTask>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ProposedTask {
+    /// A short description of what the task does, such as "Install dependencies".
+    label: String,
+    /// The program to run, such as `npm`.
+    command: String,
+    /// The arguments to pass to the program.
+    #[serde(default)]
+    args: Vec<String>,
+    /// The directory to run the task in, relative to the project's root
+    /// directory. Defaults to the project's root directory.
+    #[serde(default)]
+    cwd: Option<String>,
+    /// Environment variables to set for the task.
+    #[serde(default)]
+    env: HashMap<String, String>,
+}
+
+impl ProposedTask {
+    fn command_line(&self) -> String {
+        let mut command_line = self.command.clone();
+        for arg in &self.args {
+            command_line.push(' ');
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                command_line.push_str(&format!("{arg:?}"));
+            } else {
+                command_line.push_str(arg);
+            }
+        }
+        command_line
+    }
+
+    fn task_template(&self, project_root: Option<&PathBuf>) -> TaskTemplate {
+        let cwd = self.cwd.as_ref().map(|cwd| {
+            project_root
+                .map_or_else(|| PathBuf::from(cwd), |root| root.join(cwd))
+                .to_string_lossy()
+                .into_owned()
+        });
+        TaskTemplate {
+            label: self.label.clone(),
+            command: self.command.clone(),
+            args: self.args.clone(),
+            env: self.env.clone(),
+            cwd,
+            ..TaskTemplate::default()
+        }
+    }
+}
+
+pub struct ProposeTasksTool;
+
+impl Tool for ProposeTasksTool {
+    fn name(&self) -> String {
+        "propose_tasks".into()
+    }
+
+    fn needs_confirmation(&self, _: &serde_json::Value, _: &App) -> bool {
+        false
+    }
+
+    fn may_perform_edits(&self) -> bool {
+        false
+    }
+
+    fn may_run_commands(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> String {
+        include_str!("./propose_tasks_tool/description.md").into()
+    }
+
+    fn icon(&self) -> IconName {
+        IconName::Play
+    }
+
+    fn input_schema(&self, format: LanguageModelToolSchemaFormat) -> Result<serde_json::Value> {
+        json_schema_for::<ProposeTasksToolInput>(format)
+    }
+
+    fn ui_text(&self, input: &serde_json::Value) -> String {
+        match serde_json::from_value::<ProposeTasksToolInput>(input.clone()) {
+            Ok(input) if input.tasks.len() == 1 => "Propose 1 task".into(),
+            Ok(input) => format!("Propose {} tasks", input.tasks.len()),
+            Err(_) => "Propose tasks".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: serde_json::Value,
+        _request: Arc<LanguageModelRequest>,
+        project: Entity<Project>,
+        _action_log: Entity<ActionLog>,
+        _model: Arc<dyn LanguageModel>,
+        _window: Option<AnyWindowHandle>,
+        cx: &mut App,
+    ) -> ToolResult {
+        let input = match serde_json::from_value::<ProposeTasksToolInput>(input) {
+            Ok(input) => input,
+            Err(err) => return Task::ready(Err(anyhow!(err))).into(),
+        };
+        if input.tasks.is_empty() {
+            return Task::ready(Err(anyhow!("no tasks were proposed"))).into();
+        }
+
+        let text = format!(
+            "Showed {} task(s) to the user, who can run them from the thread. \
+            You won't see their output unless the user shares it.",
+            input.tasks.len()
+        );
+        let output = serde_json::to_value(&input.tasks).ok();
+        let card = cx.new(|cx| ProposeTasksToolCard::new(input.tasks, &project, cx));
+        ToolResult {
+            output: Task::ready(Ok(ToolResultOutput {
+                content: ToolResultContent::Text(text),
+                output,
+            })),
+            card: Some(card.into()),
+        }
+    }
+
+    fn deserialize_card(
+        self: Arc<Self>,
+        output: serde_json::Value,
+        project: Entity<Project>,
+        _window: &mut Window,
+        cx: &mut App,
+    ) -> Option<assistant_tool::AnyToolCard> {
+        let tasks = serde_json::from_value::<Vec<ProposedTask>>(output).ok()?;
+        let card = cx.new(|cx| ProposeTasksToolCard::new(tasks, &project, cx));
+        Some(card.into())
+    }
+}
+
+struct ProposeTasksToolCard {
+    tasks: Vec<ProposedTask>,
+    project_root: Option<PathBuf>,
+    /// The indices of the tasks that were run from this card.
+    ran: HashSet<usize>,
+}
+
+impl ProposeTasksToolCard {
+    fn new(tasks: Vec<ProposedTask>, project: &Entity<Project>, cx: &App) -> Self {
+        let project_root = project
+            .read(cx)
+            .visible_worktrees(cx)
+            .next()
+            .map(|worktree| worktree.read(cx).abs_path().to_path_buf());
+        Self {
+            tasks,
+            project_root,
+            ran: HashSet::default(),
+        }
+    }
+
+    fn run_task(
+        &mut self,
+        ix: usize,
+        workspace: &WeakEntity<Workspace>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(task) = self.tasks.get(ix) else {
+            return;
+        };
+        let template = task.task_template(self.project_root.as_ref());
+        let task_cx = TaskContext {
+            cwd: self.project_root.clone(),
+            ..TaskContext::default()
+        };
+        workspace
+            .update(cx, |workspace, cx| {
+                workspace.schedule_task(
+                    TaskSourceKind::UserInput,
+                    &template,
+                    &task_cx,
+                    false,
+                    window,
+                    cx,
+                );
+            })
+            .log_err();
+        self.ran.insert(ix);
+        cx.notify();
+    }
+}
+
+impl ToolCard for ProposeTasksToolCard {
+    fn render(
+        &mut self,
+        _status: &ToolUseStatus,
+        _window: &mut Window,
+        workspace: WeakEntity<Workspace>,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let header = ToolCallCardHeader::new(IconName::Play, "Proposed Tasks").with_secondary_text(
+            if self.tasks.len() == 1 {
+                "1 task".to_string()
+            } else {
+                format!("{} tasks", self.tasks.len())
+            },
+        );
+
+        let rows = self.tasks.iter().enumerate().map(|(ix, task)| {
+            let ran = self.ran.contains(&ix);
+            h_flex()
+                .gap_2()
+                .justify_between()
+                .child(
+                    v_flex()
+                        .min_w_0()
+                        .child(Label::new(task.label.clone()).size(LabelSize::Small))
+                        .child(
+                            Label::new(task.command_line())
+                                .size(LabelSize::XSmall)
+                                .color(Color::Muted)
+                                .buffer_font(cx)
+                                .truncate(),
+                        ),
+                )
+                .child(
+                    Button::new(("run-task", ix), if ran { "Run Again" } else { "Run Task" })
+                        .icon(IconName::Play)
+                        .icon_size(IconSize::XSmall)
+                        .icon_position(IconPosition::Start)
+                        .label_size(LabelSize::Small)
+                        .style(ButtonStyle::Filled)
+                        .tooltip(Tooltip::text(match &task.cwd {
+                            Some(cwd) => format!("Run in a terminal in {cwd}"),
+                            None => "Run in a terminal in the project".to_string(),
+                        }))
+                        .on_click(cx.listener({
+                            let workspace = workspace.clone();
+                            move |this, _, window, cx| this.run_task(ix, &workspace, window, cx)
+                        })),
+                )
+        });
+
+        v_flex().mb_3().gap_1().child(header).child(
+            v_flex()
+                .ml_1p5()
+                .pl(px(5.))
+                .border_l_1()
+                .border_color(cx.theme().colors().border_variant)
+                .gap_1()
+                .children(rows),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_template() {
+        let task = ProposedTask {
+            label: "Run tests".into(),
+            command: "cargo".into(),
+            args: vec!["test".into(), "--features".into(), "a b".into()],
+            cwd: Some("crates/app".into()),
+            env: HashMap::from_iter([("RUST_LOG".to_string(), "debug".to_string())]),
+        };
+        assert_eq!(task.command_line(), "cargo test --features \"a b\"");
+
+        let root = PathBuf::from("/project");
+        let template = task.task_template(Some(&root));
+        assert_eq!(template.label, "Run tests");
+        assert_eq!(template.command, "cargo");
+        assert_eq!(template.args, task.args);
+        assert_eq!(template.env.get("RUST_LOG").unwrap(), "debug");
+        assert_eq!(
+            template.cwd,
+            Some(root.join("crates/app").to_string_lossy().into_owned())
+        );
+        assert_eq!(task.task_template(None).cwd.as_deref(), Some("crates/app"));
+    }
+}
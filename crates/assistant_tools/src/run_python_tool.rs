@@ -0,0 +1,334 @@
+use crate::schema::json_schema_for;
+use agent_settings::{AgentSettings, PythonSandbox, PythonSandboxBackend};
+use anyhow::{Context as _, Result, anyhow, bail};
+use assistant_tool::{ActionLog, Tool, ToolResult, ToolResultContent, ToolResultOutput};
+use futures::{FutureExt as _, select_biased};
+use gpui::{AnyWindowHandle, App, AppContext as _, Entity, Image, ImageFormat, Task};
+use language_model::{
+    LanguageModel, LanguageModelImage, LanguageModelRequest, LanguageModelToolSchemaFormat,
+};
+use project::Project;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::Duration,
+};
+use ui::IconName;
+use util::markdown::MarkdownInlineCode;
+
+const OUTPUT_LIMIT: usize = 16 * 1024;
+const RUNNER: &str = include_str!("./run_python_tool/runner.py");
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RunPythonToolInput {
+    /// The Python code to run.
+    code: String,
+}
+
+pub struct RunPythonTool;
+
+impl Tool for RunPythonTool {
+    fn name(&self) -> String {
+        "run_python".to_string()
+    }
+
+    fn needs_confirmation(&self, _: &serde_json::Value, _: &App) -> bool {
+        true
+    }
+
+    fn may_perform_edits(&self) -> bool {
+        false
+    }
+
+    fn may_run_commands(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> String {
+        include_str!("./run_python_tool/description.md").to_string()
+    }
+
+    fn icon(&self) -> IconName {
+        IconName::Terminal
+    }
+
+    fn input_schema(&self, format: LanguageModelToolSchemaFormat) -> Result<serde_json::Value> {
+        json_schema_for::<RunPythonToolInput>(format)
+    }
+
+    fn ui_text(&self, input: &serde_json::Value) -> String {
+        match serde_json::from_value::<RunPythonToolInput>(input.clone()) {
+            Ok(input) => {
+                let first_line = input.code.lines().next().unwrap_or_default();
+                MarkdownInlineCode(&format!("python: {first_line}")).to_string()
+            }
+            Err(_) => "Run Python".to_string(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: serde_json::Value,
+        _request: Arc<LanguageModelRequest>,
+        project: Entity<Project>,
+        _action_log: Entity<ActionLog>,
+        model: Arc<dyn LanguageModel>,
+        _window: Option<AnyWindowHandle>,
+        cx: &mut App,
+    ) -> ToolResult {
+        let input = match serde_json::from_value::<RunPythonToolInput>(input) {
+            Ok(input) => input,
+            Err(err) => return Task::ready(Err(anyhow!(err))).into(),
+        };
+        let project = project.read(cx);
+        if !project.is_local() {
+            return Task::ready(Err(anyhow!(
+                "Python snippets can only be run in local projects"
+            )))
+            .into();
+        }
+
+        let sandbox = AgentSettings::get_global(cx).python_sandbox.clone();
+        let project_root = project
+            .visible_worktrees(cx)
+            .next()
+            .map(|worktree| worktree.read(cx).abs_path().to_path_buf());
+        let supports_images = model.supports_images();
+        let executor = cx.background_executor().clone();
+        let output = cx.background_spawn(async move {
+            let timeout = executor.timer(Duration::from_secs(sandbox.timeout_secs));
+            run_snippet(&input.code, &sandbox, project_root.as_deref(), timeout).await
+        });
+
+        cx.spawn(async move |cx| {
+            let output = output.await?;
+            if output.plots.is_empty() {
+                return Ok(output.text.into());
+            }
+            if !supports_images {
+                return Ok(format!(
+                    "{}\n\nThe snippet created {} plot(s), which can't be shown because the model doesn't support images.",
+                    output.text,
+                    output.plots.len()
+                )
+                .into());
+            }
+
+            let mut images = Vec::with_capacity(output.plots.len());
+            for plot in output.plots {
+                let image = Arc::new(Image::from_bytes(ImageFormat::Png, plot));
+                let image = cx
+                    .update(|cx| LanguageModelImage::from_image(image, cx))?
+                    .await
+                    .context("processing plot")?;
+                images.push(image);
+            }
+            Ok(ToolResultOutput {
+                content: ToolResultContent::TextWithImages {
+                    text: output.text,
+                    images,
+                },
+                output: None,
+            })
+        })
+        .into()
+    }
+}
+
+struct SnippetOutput {
+    text: String,
+    /// The PNGs of the matplotlib figures the snippet created.
+    plots: Vec<Vec<u8>>,
+}
+
+async fn run_snippet(
+    code: &str,
+    sandbox: &PythonSandbox,
+    project_root: Option<&Path>,
+    timeout: impl Future<Output = ()>,
+) -> Result<SnippetOutput> {
+    let dir = tempfile::tempdir()?;
+    let plots_dir = dir.path().join("plots");
+    std::fs::create_dir(&plots_dir)?;
+    std::fs::write(dir.path().join("runner.py"), RUNNER)?;
+    std::fs::write(dir.path().join("snippet.py"), code)?;
+
+    let mut command = match sandbox.backend {
+        PythonSandboxBackend::Venv => {
+            let python = python_path(sandbox.venv_path.as_deref(), project_root);
+            let mut command = util::command::new_smol_command(python);
+            command
+                .arg(dir.path().join("runner.py"))
+                .arg(dir.path().join("snippet.py"))
+                .env("MPLBACKEND", "Agg")
+                .env("ZED_PLOTS_DIR", &plots_dir)
+                .current_dir(project_root.unwrap_or(dir.path()));
+            command
+        }
+        PythonSandboxBackend::Docker => {
+            // The container can't reach the network, and can only read the
+            // project, so the snippet can't change anything outside of it.
+            let mut command = util::command::new_smol_command("docker");
+            command
+                .args(["run", "--rm", "--network=none", "--memory=1g", "--cpus=1"])
+                .args(["-e", "MPLBACKEND=Agg", "-e", "ZED_PLOTS_DIR=/sandbox/plots"])
+                .arg("-v")
+                .arg(format!("{}:/sandbox", dir.path().display()));
+            if let Some(project_root) = project_root {
+                command
+                    .arg("-v")
+                    .arg(format!("{}:/project:ro", project_root.display()))
+                    .args(["-w", "/project"]);
+            } else {
+                command.args(["-w", "/sandbox"]);
+            }
+            command
+                .arg(&sandbox.docker_image)
+                .args(["timeout", &sandbox.timeout_secs.to_string()])
+                .args(["python", "/sandbox/runner.py", "/sandbox/snippet.py"]);
+            command
+        }
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let output = command.output().fuse();
+    let timeout = timeout.fuse();
+    futures::pin_mut!(output, timeout);
+    let output = select_biased! {
+        output = output => output.context("failed to start Python")?,
+        _ = timeout => bail!(
+            "The snippet didn't finish within {} seconds",
+            sandbox.timeout_secs
+        ),
+    };
+
+    let mut plot_paths = std::fs::read_dir(&plots_dir)?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect::<Vec<_>>();
+    plot_paths.sort();
+    let plots = plot_paths
+        .iter()
+        .map(std::fs::read)
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    Ok(SnippetOutput {
+        text: format_output(
+            &String::from_utf8_lossy(&output.stdout),
+            &String::from_utf8_lossy(&output.stderr),
+            output.status.code(),
+        ),
+        plots,
+    })
+}
+
+/// Returns the interpreter of the configured virtual environment, or of the
+/// project's `.venv` directory, falling back to the one on the `PATH`.
+fn python_path(venv_path: Option<&Path>, project_root: Option<&Path>) -> PathBuf {
+    let venv = match (venv_path, project_root) {
+        (Some(venv_path), Some(project_root)) => Some(project_root.join(venv_path)),
+        (Some(venv_path), None) => Some(venv_path.to_path_buf()),
+        (None, Some(project_root)) => Some(project_root.join(".venv")).filter(|venv| venv.is_dir()),
+        (None, None) => None,
+    };
+    match venv {
+        Some(venv) if cfg!(windows) => venv.join("Scripts").join("python.exe"),
+        Some(venv) => venv.join("bin").join("python"),
+        None if cfg!(windows) => PathBuf::from("python"),
+        None => PathBuf::from("python3"),
+    }
+}
+
+fn format_output(stdout: &str, stderr: &str, exit_code: Option<i32>) -> String {
+    let mut content = stdout.trim_end().to_string();
+    if !stderr.trim().is_empty() {
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        content.push_str(stderr.trim_end());
+    }
+
+    let should_truncate = content.len() > OUTPUT_LIMIT;
+    if should_truncate {
+        let mut end_ix = OUTPUT_LIMIT;
+        while !content.is_char_boundary(end_ix) {
+            end_ix -= 1;
+        }
+        // Don't truncate mid-line, clear the remainder of the last line
+        end_ix = content[..end_ix].rfind('\n').unwrap_or(end_ix);
+        content.truncate(end_ix);
+    }
+    let content = if content.is_empty() {
+        String::new()
+    } else if should_truncate {
+        format!(
+            "\n\nOutput too long. The first {} bytes:\n\n```\n{content}\n```",
+            content.len()
+        )
+    } else {
+        format!("\n\n```\n{content}\n```")
+    };
+
+    match exit_code {
+        Some(0) if content.is_empty() => "The snippet ran successfully.".to_string(),
+        Some(0) => content.trim_start().to_string(),
+        Some(code) => format!("The snippet failed with exit code {code}.{content}"),
+        None => format!("The snippet was interrupted.{content}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_python_path() {
+        let project_root = tempfile::tempdir().unwrap();
+        let project_root = project_root.path();
+        let bin = if cfg!(windows) {
+            Path::new("Scripts").join("python.exe")
+        } else {
+            Path::new("bin").join("python")
+        };
+        let default = if cfg!(windows) { "python" } else { "python3" };
+
+        assert_eq!(python_path(None, None), PathBuf::from(default));
+        assert_eq!(
+            python_path(None, Some(project_root)),
+            PathBuf::from(default)
+        );
+        assert_eq!(
+            python_path(Some(Path::new("env")), Some(project_root)),
+            project_root.join("env").join(&bin)
+        );
+
+        std::fs::create_dir(project_root.join(".venv")).unwrap();
+        assert_eq!(
+            python_path(None, Some(project_root)),
+            project_root.join(".venv").join(&bin)
+        );
+    }
+
+    #[test]
+    fn test_format_output() {
+        assert_eq!(
+            format_output("", "", Some(0)),
+            "The snippet ran successfully."
+        );
+        assert_eq!(format_output("42\n", "", Some(0)), "```\n42\n```");
+        assert_eq!(
+            format_output("1\n", "ZeroDivisionError\n", Some(1)),
+            "The snippet failed with exit code 1.\n\n```\n1\nZeroDivisionError\n```"
+        );
+        assert!(
+            format_output(&"x\n".repeat(OUTPUT_LIMIT), "", Some(0)).starts_with("Output too long.")
+        );
+    }
+}
@@ -0,0 +1,14 @@
+# Runs a snippet from the `run_python` tool, then saves the matplotlib figures
+# it created as PNGs, so that they can be returned to the model.
+import os
+import runpy
+import sys
+
+try:
+    runpy.run_path(sys.argv[1], run_name="__main__")
+finally:
+    pyplot = sys.modules.get("matplotlib.pyplot")
+    if pyplot is not None:
+        for index, number in enumerate(pyplot.get_fignums()):
+            path = os.path.join(os.environ["ZED_PLOTS_DIR"], f"plot_{index:03}.png")
+            pyplot.figure(number).savefig(path, bbox_inches="tight")
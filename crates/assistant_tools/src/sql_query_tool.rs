@@ -0,0 +1,511 @@
+use crate::schema::json_schema_for;
+use agent_settings::{AgentSettings, SqlQuerySettings};
+use anyhow::{Context as _, Result, anyhow, bail};
+use assistant_tool::{ActionLog, Tool, ToolResult};
+use collections::HashSet;
+use futures::{FutureExt as _, select_biased};
+use gpui::{AnyWindowHandle, App, AppContext as _, Entity, Task};
+use language_model::{LanguageModel, LanguageModelRequest, LanguageModelToolSchemaFormat};
+use parking_lot::Mutex;
+use project::Project;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, path::PathBuf, process::Stdio, sync::Arc, time::Duration};
+use ui::IconName;
+use url::Url;
+use util::markdown::MarkdownInlineCode;
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SqlQueryToolInput {
+    /// The name of the connection to query, as configured by the user.
+    connection: String,
+    /// The `SELECT` query to run.
+    query: String,
+}
+
+#[derive(Default)]
+pub struct SqlQueryTool {
+    /// The connections the user allowed the model to query during this
+    /// session. Querying any other connection needs their confirmation.
+    confirmed_connections: Mutex<HashSet<String>>,
+}
+
+impl Tool for SqlQueryTool {
+    fn name(&self) -> String {
+        "sql_query".to_string()
+    }
+
+    fn needs_confirmation(&self, input: &serde_json::Value, _: &App) -> bool {
+        match serde_json::from_value::<SqlQueryToolInput>(input.clone()) {
+            Ok(input) => !self
+                .confirmed_connections
+                .lock()
+                .contains(&input.connection),
+            Err(_) => true,
+        }
+    }
+
+    fn may_perform_edits(&self) -> bool {
+        false
+    }
+
+    fn may_run_commands(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> String {
+        include_str!("./sql_query_tool/description.md").to_string()
+    }
+
+    fn icon(&self) -> IconName {
+        IconName::DatabaseZap
+    }
+
+    fn input_schema(&self, format: LanguageModelToolSchemaFormat) -> Result<serde_json::Value> {
+        json_schema_for::<SqlQueryToolInput>(format)
+    }
+
+    fn ui_text(&self, input: &serde_json::Value) -> String {
+        match serde_json::from_value::<SqlQueryToolInput>(input.clone()) {
+            Ok(input) => {
+                let first_line = input.query.lines().next().unwrap_or_default();
+                format!(
+                    "Query {}: {}",
+                    MarkdownInlineCode(&input.connection),
+                    MarkdownInlineCode(first_line)
+                )
+            }
+            Err(_) => "Query database".to_string(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: serde_json::Value,
+        _request: Arc<LanguageModelRequest>,
+        _project: Entity<Project>,
+        _action_log: Entity<ActionLog>,
+        _model: Arc<dyn LanguageModel>,
+        _window: Option<AnyWindowHandle>,
+        cx: &mut App,
+    ) -> ToolResult {
+        let input = match serde_json::from_value::<SqlQueryToolInput>(input) {
+            Ok(input) => input,
+            Err(err) => return Task::ready(Err(anyhow!(err))).into(),
+        };
+        let settings = AgentSettings::get_global(cx).sql_query.clone();
+        let Some(connection_string) = settings.connections.get(&input.connection) else {
+            let names = settings
+                .connections
+                .keys()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>();
+            return Task::ready(Err(if names.is_empty() {
+                anyhow!("No database connections are configured")
+            } else {
+                anyhow!(
+                    "Unknown connection {}. The configured connections are: {}",
+                    input.connection,
+                    names.join(", ")
+                )
+            }))
+            .into();
+        };
+        let connection = match Connection::parse(connection_string) {
+            Ok(connection) => connection,
+            Err(error) => return Task::ready(Err(error)).into(),
+        };
+        let query = match capped_query(&input.query, settings.max_rows)
+            .and_then(|query| connection.check_query(&query).map(|_| query))
+        {
+            Ok(query) => query,
+            Err(error) => return Task::ready(Err(error)).into(),
+        };
+        self.confirmed_connections.lock().insert(input.connection);
+
+        let timeout = cx.background_executor().timer(QUERY_TIMEOUT);
+        cx.background_spawn(async move {
+            let mut command = connection.command(&query);
+            let output = command.output().fuse();
+            let timeout = timeout.fuse();
+            futures::pin_mut!(output, timeout);
+            let output = select_biased! {
+                output = output => output.context("failed to start the database client")?,
+                _ = timeout => bail!(
+                    "The query didn't finish within {} seconds",
+                    QUERY_TIMEOUT.as_secs()
+                ),
+            };
+            if !output.status.success() {
+                bail!(
+                    "The query failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let rows = match connection {
+                Connection::MySql(_) => parse_mysql_batch(&stdout),
+                Connection::Postgres(_) | Connection::Sqlite(_) => parse_csv(&stdout),
+            };
+            Ok(markdown_table(rows, &settings).into())
+        })
+        .into()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Connection {
+    Postgres(Url),
+    MySql(Url),
+    Sqlite(PathBuf),
+}
+
+impl Connection {
+    fn parse(connection_string: &str) -> Result<Self> {
+        if let Some(path) = connection_string.strip_prefix("sqlite://") {
+            return Ok(Self::Sqlite(PathBuf::from(path)));
+        }
+        let url = Url::parse(connection_string).context("invalid connection string")?;
+        match url.scheme() {
+            "postgres" | "postgresql" => Ok(Self::Postgres(url)),
+            "mysql" | "mariadb" => Ok(Self::MySql(url)),
+            scheme => bail!("unsupported database {scheme}"),
+        }
+    }
+
+    /// Rejects queries that the database's client would treat as more than
+    /// SQL. The `mysql` client runs backslash commands anywhere in the query,
+    /// such as `\!`, which runs a shell command, and SQLite can load
+    /// extensions from a query.
+    fn check_query(&self, query: &str) -> Result<()> {
+        match self {
+            Self::MySql(_) if query.contains('\\') => {
+                bail!("Backslashes can't be used in MySQL queries")
+            }
+            Self::Sqlite(_) if query.to_ascii_lowercase().contains("load_extension") => {
+                bail!("SQLite extensions can't be loaded")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the command that runs the query with the database's own
+    /// client, in a read-only session.
+    fn command(&self, query: &str) -> smol::process::Command {
+        let mut command = match self {
+            Self::Postgres(url) => {
+                // The password is passed in the environment rather than in
+                // the arguments, which other processes can read.
+                let mut url = url.clone();
+                let password = decoded_password(&url);
+                url.set_password(None).ok();
+                let mut command = util::command::new_smol_command("psql");
+                command
+                    .args(["--no-psqlrc", "--csv", "--quiet", "--set=ON_ERROR_STOP=1"])
+                    .arg(format!("--dbname={url}"))
+                    .arg(format!("--command={query}"))
+                    .env(
+                        "PGOPTIONS",
+                        "-c default_transaction_read_only=on -c statement_timeout=30s",
+                    );
+                if let Some(password) = password {
+                    command.env("PGPASSWORD", password);
+                }
+                command
+            }
+            Self::MySql(url) => {
+                let mut command = util::command::new_smol_command("mysql");
+                command
+                    .args([
+                        "--batch",
+                        "--init-command=SET SESSION TRANSACTION READ ONLY",
+                    ])
+                    .arg(format!("--host={}", url.host_str().unwrap_or("localhost")))
+                    .arg(format!("--port={}", url.port().unwrap_or(3306)));
+                if !url.username().is_empty() {
+                    command.arg(format!("--user={}", decode(url.username())));
+                }
+                if let Some(password) = decoded_password(url) {
+                    command.env("MYSQL_PWD", password);
+                }
+                let database = url.path().trim_start_matches('/');
+                if !database.is_empty() {
+                    command.arg(format!("--database={database}"));
+                }
+                command.arg(format!("--execute={query}"));
+                command
+            }
+            Self::Sqlite(path) => {
+                // `-safe` stops the query from loading extensions or
+                // reading and writing other files.
+                let mut command = util::command::new_smol_command("sqlite3");
+                command
+                    .args(["-readonly", "-safe", "-csv", "-header"])
+                    .arg(path)
+                    .arg(query);
+                command
+            }
+        };
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        command
+    }
+}
+
+/// Decodes a percent-encoded part of a connection string, such as a password
+/// containing `@`.
+fn decode(text: &str) -> String {
+    urlencoding::decode(text)
+        .map(|text| text.into_owned())
+        .unwrap_or_else(|_| text.to_string())
+}
+
+fn decoded_password(url: &Url) -> Option<String> {
+    url.password().map(decode)
+}
+
+/// Checks that the query is a single `SELECT` statement, and wraps it so that
+/// it returns at most one row more than the limit, to tell whether the result
+/// was truncated.
+fn capped_query(query: &str, max_rows: usize) -> Result<String> {
+    let query = query.trim().trim_end_matches(';').trim_end();
+    if query.contains(';') {
+        bail!("Only a single statement can be run");
+    }
+    let keyword = query
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if keyword != "select" && keyword != "with" {
+        bail!("Only SELECT queries can be run");
+    }
+    Ok(format!(
+        "SELECT * FROM (\n{query}\n) AS capped_query LIMIT {}",
+        max_rows + 1
+    ))
+}
+
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(char),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Parses the tab-separated output of `mysql --batch`, which escapes tabs,
+/// newlines and backslashes in values.
+fn parse_mysql_batch(text: &str) -> Vec<Vec<String>> {
+    text.lines()
+        .map(|line| {
+            line.split('\t')
+                .map(|field| {
+                    let mut value = String::new();
+                    let mut chars = field.chars();
+                    while let Some(char) = chars.next() {
+                        if char != '\\' {
+                            value.push(char);
+                            continue;
+                        }
+                        match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some('0') => value.push('\0'),
+                            Some(char) => value.push(char),
+                            None => value.push('\\'),
+                        }
+                    }
+                    value
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn markdown_table(rows: Vec<Vec<String>>, settings: &SqlQuerySettings) -> String {
+    let mut rows = rows.into_iter();
+    let Some(header) = rows.next() else {
+        return "The query returned no rows.".to_string();
+    };
+    let rows = rows.collect::<Vec<_>>();
+    if rows.is_empty() {
+        return "The query returned no rows.".to_string();
+    }
+
+    fn write_row(table: &mut String, row: &[String]) {
+        table.push('|');
+        for cell in row {
+            let cell = cell.replace('|', "\\|").replace(['\r', '\n'], " ");
+            write!(table, " {} |", cell).unwrap();
+        }
+        table.push('\n');
+    }
+
+    let mut table = String::new();
+    write_row(&mut table, &header);
+    table.push('|');
+    for _ in &header {
+        table.push_str(" --- |");
+    }
+    table.push('\n');
+
+    let max_len = settings.max_result_kb * 1024;
+    let mut row_count = 0;
+    for row in rows.iter().take(settings.max_rows) {
+        let len = table.len();
+        write_row(&mut table, row);
+        if table.len() > max_len {
+            table.truncate(len);
+            break;
+        }
+        row_count += 1;
+    }
+
+    if row_count < rows.len() {
+        write!(
+            table,
+            "\nShowing the first {row_count} rows. The result was truncated."
+        )
+        .unwrap();
+    } else {
+        write!(
+            table,
+            "\n{row_count} {}.",
+            if row_count == 1 { "row" } else { "rows" }
+        )
+        .unwrap();
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_connection() {
+        assert_eq!(
+            Connection::parse("sqlite:///tmp/app.db").unwrap(),
+            Connection::Sqlite(PathBuf::from("/tmp/app.db"))
+        );
+        assert_eq!(
+            Connection::parse("postgresql://reader@localhost/app").unwrap(),
+            Connection::Postgres(Url::parse("postgresql://reader@localhost/app").unwrap())
+        );
+        assert!(matches!(
+            Connection::parse("mysql://reader:secret@db:3307/app").unwrap(),
+            Connection::MySql(_)
+        ));
+        assert!(Connection::parse("redis://localhost").is_err());
+    }
+
+    #[test]
+    fn test_decode_credentials() {
+        let url = Url::parse("mysql://read%40er:p%40ss%2Fw%25rd@db/app").unwrap();
+        assert_eq!(decode(url.username()), "read@er");
+        assert_eq!(decoded_password(&url).as_deref(), Some("p@ss/w%rd"));
+        assert_eq!(
+            decoded_password(&Url::parse("postgres://reader@db/app").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_capped_query() {
+        assert_eq!(
+            capped_query("  select * from users;\n", 10).unwrap(),
+            "SELECT * FROM (\nselect * from users\n) AS capped_query LIMIT 11"
+        );
+        assert!(capped_query("WITH t AS (SELECT 1) SELECT * FROM t", 10).is_ok());
+        assert!(capped_query("DELETE FROM users", 10).is_err());
+        assert!(capped_query("SELECT 1; DROP TABLE users", 10).is_err());
+    }
+
+    #[test]
+    fn test_client_commands_are_rejected() {
+        let mysql = Connection::parse("mysql://reader@db/app").unwrap();
+        let sqlite = Connection::parse("sqlite:///tmp/app.db").unwrap();
+        let postgres = Connection::parse("postgres://reader@db/app").unwrap();
+
+        let query = capped_query("SELECT 1 \\! ls", 10).unwrap();
+        assert!(mysql.check_query(&query).is_err());
+        let query = capped_query("SELECT * FROM t WHERE name = 'a'", 10).unwrap();
+        assert!(mysql.check_query(&query).is_ok());
+
+        let query = capped_query("SELECT load_extension('/tmp/evil.so')", 10).unwrap();
+        assert!(sqlite.check_query(&query).is_err());
+        assert!(postgres.check_query(&query).is_ok());
+    }
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!(
+            parse_csv("id,name\n1,\"Doe, \"\"J\"\"\"\n2,\"multi\nline\"\n"),
+            [
+                vec!["id", "name"],
+                vec!["1", "Doe, \"J\""],
+                vec!["2", "multi\nline"],
+            ]
+        );
+        assert_eq!(
+            parse_mysql_batch("id\tname\n1\ta\\tb\\\\c\n"),
+            [vec!["id", "name"], vec!["1", "a\tb\\c"]]
+        );
+    }
+
+    #[test]
+    fn test_markdown_table() {
+        let settings = SqlQuerySettings {
+            max_rows: 2,
+            ..SqlQuerySettings::default()
+        };
+        let rows = |count: usize| {
+            std::iter::once(vec!["id".to_string(), "name".to_string()])
+                .chain((0..count).map(|ix| vec![ix.to_string(), format!("a|{ix}")]))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            markdown_table(rows(1), &settings),
+            "| id | name |\n| --- | --- |\n| 0 | a\\|0 |\n\n1 row."
+        );
+        assert_eq!(
+            markdown_table(rows(3), &settings),
+            "| id | name |\n| --- | --- |\n| 0 | a\\|0 |\n| 1 | a\\|1 |\n\nShowing the first 2 rows. The result was truncated."
+        );
+        assert_eq!(
+            markdown_table(rows(0), &settings),
+            "The query returned no rows."
+        );
+    }
+}
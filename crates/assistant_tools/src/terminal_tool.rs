@@ -84,6 +84,10 @@ impl Tool for TerminalTool {
         false
     }
 
+    fn may_run_commands(&self) -> bool {
+        true
+    }
+
     fn description(&self) -> String {
         include_str!("./terminal_tool/description.md").to_string()
     }
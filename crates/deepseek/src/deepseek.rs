@@ -101,6 +101,14 @@ impl Model {
         }
     }
 
+    /// Whether the model supports fill-in-the-middle completions.
+    pub fn supports_fim(&self) -> bool {
+        match self {
+            Self::Chat => true,
+            Self::Reasoner | Self::Custom { .. } => false,
+        }
+    }
+
     pub fn max_output_tokens(&self) -> Option<u32> {
         match self {
             Self::Chat => Some(8_192),
@@ -303,3 +311,63 @@ pub async fn stream_completion(
         );
     }
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FimRequest {
+    pub model: String,
+    pub prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FimResponse {
+    pub id: String,
+    pub choices: Vec<FimChoice>,
+    pub usage: Usage,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FimChoice {
+    pub index: u32,
+    pub text: String,
+    pub finish_reason: Option<String>,
+}
+
+/// Completes the text between a prompt and a suffix using the FIM beta API,
+/// which is only available for [`Model::Chat`].
+pub async fn fim_completion(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: FimRequest,
+) -> Result<FimResponse> {
+    let uri = format!("{api_url}/beta/completions");
+    let request_builder = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {}", api_key));
+
+    let request = request_builder.body(AsyncBody::from(serde_json::to_string(&request)?))?;
+    let mut response = client.send(request).await?;
+
+    let mut body = String::new();
+    response.body_mut().read_to_string(&mut body).await?;
+
+    if response.status().is_success() {
+        Ok(serde_json::from_str(&body)?)
+    } else {
+        anyhow::bail!(
+            "Failed to connect to DeepSeek API: {} {}",
+            response.status(),
+            body,
+        );
+    }
+}
@@ -2,11 +2,13 @@ mod assertions;
 mod example;
 mod examples;
 mod explorer;
+mod feedback;
 mod ids;
 mod instance;
 mod tool_metrics;
 
 use assertions::{AssertionsReport, display_error_row};
+use feedback::FeedbackReport;
 use instance::{ExampleInstance, JudgeOutput, RunOutput, run_git};
 pub(crate) use tool_metrics::*;
 
@@ -60,6 +62,9 @@ struct Args {
     /// Maximum number of examples to run concurrently.
     #[arg(long, default_value = "4")]
     concurrency: usize,
+    /// Summarizes how each model's answers were rated in this feedback export, instead of running examples.
+    #[arg(long, value_name = "PATH")]
+    feedback: Option<PathBuf>,
 }
 
 fn main() {
@@ -99,6 +104,13 @@ fn main() {
     let zed_commit_sha = commit_sha_for_path(&root_dir);
     let zed_branch_name = git_branch_for_path(&root_dir);
     let args = Args::parse();
+    if let Some(path) = &args.feedback {
+        match std::fs::read_to_string(path) {
+            Ok(text) => print!("{}", FeedbackReport::new(&agent::parse_feedback(&text))),
+            Err(error) => eprintln!("Failed to read {}: {error}", path.display()),
+        }
+        return;
+    }
     let languages: HashSet<String> = args.languages.into_iter().collect();
 
     let http_client = Arc::new(ReqwestClient::new());
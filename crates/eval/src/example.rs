@@ -246,7 +246,9 @@ impl ExampleContext {
                 | ThreadEvent::StreamedAssistantThinking(_, _)
                 | ThreadEvent::UsePendingTools { .. }
                 | ThreadEvent::CompletionCanceled => {}
-                ThreadEvent::ToolUseLimitReached => {}
+                ThreadEvent::ToolUseLimitReached
+                | ThreadEvent::TimeBudgetReached
+                | ThreadEvent::PlanChanged => {}
                 ThreadEvent::ToolFinished {
                     tool_use_id,
                     pending_tool_use,
@@ -0,0 +1,84 @@
+use agent::{FeedbackRecord, ThreadFeedback};
+use collections::HashMap;
+use std::fmt::{self, Display};
+
+/// How the answers of each model were rated in a feedback export.
+#[derive(Debug, Default, PartialEq)]
+pub struct FeedbackReport {
+    models: Vec<ModelFeedback>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ModelFeedback {
+    model: String,
+    positive: usize,
+    negative: usize,
+    /// The reasons given for negative ratings.
+    complaints: Vec<String>,
+}
+
+impl FeedbackReport {
+    pub fn new(records: &[FeedbackRecord]) -> Self {
+        let mut models = HashMap::<String, ModelFeedback>::default();
+        for record in records {
+            let model = record.model.clone().unwrap_or_else(|| "unknown".into());
+            let feedback = models
+                .entry(model.clone())
+                .or_insert_with(|| ModelFeedback {
+                    model,
+                    ..Default::default()
+                });
+            match record.rating {
+                ThreadFeedback::Positive => feedback.positive += 1,
+                ThreadFeedback::Negative => {
+                    feedback.negative += 1;
+                    feedback.complaints.extend(record.reason.clone());
+                }
+            }
+        }
+
+        let mut models = models.into_values().collect::<Vec<_>>();
+        models.sort_by(|a, b| {
+            b.positive_rate()
+                .total_cmp(&a.positive_rate())
+                .then_with(|| a.model.cmp(&b.model))
+        });
+        Self { models }
+    }
+}
+
+impl ModelFeedback {
+    fn positive_rate(&self) -> f64 {
+        self.positive as f64 / (self.positive + self.negative).max(1) as f64
+    }
+}
+
+impl Display for FeedbackReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<50} {:>8} {:>8} {:>8}",
+            "Model", "Positive", "Negative", "Rate"
+        )?;
+        for model in &self.models {
+            writeln!(
+                f,
+                "{:<50} {:>8} {:>8} {:>7.1}%",
+                model.model,
+                model.positive,
+                model.negative,
+                model.positive_rate() * 100.
+            )?;
+        }
+        for model in &self.models {
+            if model.complaints.is_empty() {
+                continue;
+            }
+            writeln!(f, "\nComplaints about {}:", model.model)?;
+            for complaint in &model.complaints {
+                writeln!(f, "  - {}", complaint.trim())?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -9,7 +9,7 @@ use handlebars::Handlebars;
 use language::{Buffer, DiagnosticSeverity, OffsetRangeExt as _};
 use language_model::{
     LanguageModel, LanguageModelCompletionEvent, LanguageModelRequest, LanguageModelRequestMessage,
-    LanguageModelToolResultContent, MessageContent, Role, TokenUsage,
+    LanguageModelToolResultContent, MessageContent, RequestPriority, Role, TokenUsage,
 };
 use project::lsp_store::OpenLspBufferHandle;
 use project::{DiagnosticSummary, Project, ProjectPath};
@@ -585,9 +585,12 @@ impl ExampleInstance {
                     cache: false,
                 }],
                 temperature: None,
+                seed: None,
+                priority: RequestPriority::Interactive,
                 tools: Vec::new(),
                 tool_choice: None,
                 stop: Vec::new(),
+                web_search: false,
             };
 
             let model = model.clone();
@@ -933,6 +936,9 @@ impl RequestMarkdown {
                     MessageContent::Image(_) => {
                         messages.push_str("[IMAGE DATA]\n\n");
                     }
+                    MessageContent::Document(document) => {
+                        messages.push_str(&format!("[DOCUMENT: {}]\n\n", document.name));
+                    }
                     MessageContent::Thinking { text, signature } => {
                         messages.push_str("**Thinking**:\n\n");
                         if let Some(sig) = signature {
@@ -1045,7 +1051,9 @@ pub fn response_events_to_markdown(
             Ok(
                 LanguageModelCompletionEvent::UsageUpdate(_)
                 | LanguageModelCompletionEvent::StartMessage { .. }
-                | LanguageModelCompletionEvent::StatusUpdate { .. },
+                | LanguageModelCompletionEvent::StatusUpdate { .. }
+                | LanguageModelCompletionEvent::WebSources(_)
+                | LanguageModelCompletionEvent::Retrying { .. },
             ) => {}
             Err(error) => {
                 flush_buffers(&mut response, &mut text_buffer, &mut thinking_buffer);
@@ -1122,6 +1130,8 @@ impl ThreadDialog {
                 Ok(LanguageModelCompletionEvent::UsageUpdate(_))
                 | Ok(LanguageModelCompletionEvent::StatusUpdate { .. })
                 | Ok(LanguageModelCompletionEvent::StartMessage { .. })
+                | Ok(LanguageModelCompletionEvent::WebSources(_))
+                | Ok(LanguageModelCompletionEvent::Retrying { .. })
                 | Ok(LanguageModelCompletionEvent::Stop(_)) => {}
 
                 Err(error) => {
@@ -37,7 +37,7 @@ use itertools::Itertools;
 use language::{Buffer, File};
 use language_model::{
     ConfiguredModel, LanguageModel, LanguageModelRegistry, LanguageModelRequest,
-    LanguageModelRequestMessage, Role,
+    LanguageModelRequestMessage, RequestPriority, Role,
 };
 use menu::{Confirm, SecondaryConfirm, SelectFirst, SelectLast, SelectNext, SelectPrevious};
 use multi_buffer::ExcerptInfo;
@@ -1809,6 +1809,9 @@ impl GitPanel {
                     tool_choice: None,
                     stop: Vec::new(),
                     temperature,
+                    seed: None,
+                    priority: RequestPriority::Interactive,
+                    web_search: false,
                 };
 
                 let stream = model.stream_completion_text(request, &cx);
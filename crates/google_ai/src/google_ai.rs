@@ -93,6 +93,135 @@ pub async fn count_tokens(
     Ok(serde_json::from_str::<CountTokensResponse>(&text)?)
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadedFile {
+    pub name: String,
+    pub uri: String,
+    pub mime_type: String,
+}
+
+/// Uploads a file through the Files API, which keeps it for 48 hours.
+pub async fn upload_file(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    bytes: Vec<u8>,
+    mime_type: &str,
+    display_name: &str,
+) -> Result<UploadedFile> {
+    let start_request = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(format!("{api_url}/upload/v1beta/files?key={api_key}"))
+        .header("X-Goog-Upload-Protocol", "resumable")
+        .header("X-Goog-Upload-Command", "start")
+        .header("X-Goog-Upload-Header-Content-Length", bytes.len())
+        .header("X-Goog-Upload-Header-Content-Type", mime_type)
+        .header("Content-Type", "application/json")
+        .body(AsyncBody::from(serde_json::to_string(
+            &serde_json::json!({ "file": { "display_name": display_name } }),
+        )?))?;
+    let mut response = client.send(start_request).await?;
+    if !response.status().is_success() {
+        let mut text = String::new();
+        response.body_mut().read_to_string(&mut text).await?;
+        bail!(
+            "error starting file upload, status code: {:?}, body: {}",
+            response.status(),
+            text
+        );
+    }
+    let upload_url = response
+        .headers()
+        .get("X-Goog-Upload-URL")
+        .and_then(|url| url.to_str().ok())
+        .ok_or_else(|| anyhow!("file upload response has no upload URL"))?
+        .to_string();
+
+    let upload_request = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(upload_url)
+        .header("Content-Length", bytes.len())
+        .header("X-Goog-Upload-Offset", 0)
+        .header("X-Goog-Upload-Command", "upload, finalize")
+        .body(AsyncBody::from(bytes))?;
+    let mut response = client.send(upload_request).await?;
+    let mut text = String::new();
+    response.body_mut().read_to_string(&mut text).await?;
+    anyhow::ensure!(
+        response.status().is_success(),
+        "error during file upload, status code: {:?}, body: {}",
+        response.status(),
+        text
+    );
+
+    #[derive(Deserialize)]
+    struct UploadResponse {
+        file: UploadedFile,
+    }
+    Ok(serde_json::from_str::<UploadResponse>(&text)?.file)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateCachedContentRequest {
+    /// The model's resource name, such as `models/gemini-2.5-pro`.
+    pub model: String,
+    pub contents: Vec<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<SystemInstruction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_config: Option<ToolConfig>,
+    /// How long the content is kept, such as `"3600s"`.
+    pub ttl: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContent {
+    /// The name requests reference the content by, such as
+    /// `cachedContents/abc123`.
+    pub name: String,
+    #[serde(default)]
+    pub usage_metadata: Option<CachedContentUsageMetadata>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContentUsageMetadata {
+    #[serde(default)]
+    pub total_token_count: usize,
+}
+
+/// Caches a request prefix, so that requests referencing it are billed for
+/// its tokens at the cached rate. Creating it bills its tokens as input, and
+/// storing it bills them by the hour until it expires.
+pub async fn create_cached_content(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: &CreateCachedContentRequest,
+) -> Result<CachedContent> {
+    let request_builder = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(format!("{api_url}/v1beta/cachedContents?key={api_key}"))
+        .header("Content-Type", "application/json");
+    let http_request = request_builder.body(AsyncBody::from(serde_json::to_string(request)?))?;
+
+    let mut response = client.send(http_request).await?;
+    let mut text = String::new();
+    response.body_mut().read_to_string(&mut text).await?;
+    anyhow::ensure!(
+        response.status().is_success(),
+        "error creating cached content, status code: {:?}, body: {}",
+        response.status(),
+        text
+    );
+    Ok(serde_json::from_str::<CachedContent>(&text)?)
+}
+
 pub fn validate_generate_content_request(request: &GenerateContentRequest) -> Result<()> {
     if request.model.is_empty() {
         bail!("Model must be specified");
@@ -145,6 +274,11 @@ pub struct GenerateContentRequest {
     pub tools: Option<Vec<Tool>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_config: Option<ToolConfig>,
+    /// The name of a cached content the contents continue. The system
+    /// instruction, tools and tool config must be left out, since they're
+    /// part of it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_content: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -172,6 +306,8 @@ pub struct GenerateContentCandidate {
     pub safety_ratings: Option<Vec<SafetyRating>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub citation_metadata: Option<CitationMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grounding_metadata: Option<GroundingMetadata>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -200,6 +336,7 @@ pub enum Role {
 pub enum Part {
     TextPart(TextPart),
     InlineDataPart(InlineDataPart),
+    FileDataPart(FileDataPart),
     FunctionCallPart(FunctionCallPart),
     FunctionResponsePart(FunctionResponsePart),
     ThoughtPart(ThoughtPart),
@@ -224,6 +361,21 @@ pub struct GenerativeContentBlob {
     pub data: String,
 }
 
+/// A file uploaded through the Files API, for files too large to be sent
+/// inline.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDataPart {
+    pub file_data: FileData,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileData {
+    pub mime_type: String,
+    pub file_uri: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FunctionCallPart {
@@ -262,6 +414,31 @@ pub struct CitationMetadata {
     pub citation_sources: Vec<CitationSource>,
 }
 
+/// The web search results a response was grounded in.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroundingMetadata {
+    #[serde(default)]
+    pub web_search_queries: Vec<String>,
+    #[serde(default)]
+    pub grounding_chunks: Vec<GroundingChunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroundingChunk {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web: Option<WebChunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebChunk {
+    pub uri: String,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PromptFeedback {
@@ -413,9 +590,16 @@ pub struct FunctionResponse {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Tool {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub function_declarations: Vec<FunctionDeclaration>,
+    /// Grounds responses in Google Search results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub google_search: Option<GoogleSearch>,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GoogleSearch {}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolConfig {
@@ -534,6 +718,14 @@ impl Model {
         Model::Gemini20Flash
     }
 
+    /// Whether the model can ground its responses in Google Search results.
+    pub fn supports_web_search(&self) -> bool {
+        !matches!(
+            self,
+            Model::Gemini15Pro | Model::Gemini15Flash | Model::Gemini20FlashLite
+        )
+    }
+
     pub fn id(&self) -> &str {
         match self {
             Model::Gemini15Pro => "gemini-1.5-pro",
@@ -41,6 +41,16 @@ impl AsyncBody {
     pub fn from_bytes(bytes: Bytes) -> Self {
         Self(Inner::Bytes(Cursor::new(bytes.clone())))
     }
+
+    /// Copies the body so that it can be sent again, unless it's read from
+    /// a stream.
+    pub fn try_clone(&self) -> Option<Self> {
+        match &self.0 {
+            Inner::Empty => Some(Self::empty()),
+            Inner::Bytes(cursor) => Some(Self::from_bytes(cursor.get_ref().clone())),
+            Inner::AsyncReader(_) => None,
+        }
+    }
 }
 
 impl Default for AsyncBody {
@@ -1,5 +1,6 @@
 mod async_body;
 pub mod github;
+pub mod middleware;
 
 pub use anyhow::{Result, anyhow};
 pub use async_body::{AsyncBody, Inner};
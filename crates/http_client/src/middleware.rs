@@ -0,0 +1,272 @@
+use std::{sync::Arc, time::Instant};
+
+use anyhow::Result;
+use futures::{FutureExt as _, future::BoxFuture};
+use http::{HeaderName, HeaderValue, header};
+
+use crate::{AsyncBody, HttpClient, Request, Response, Url};
+
+/// Code that runs around the requests sent by an [`HttpClientWithMiddleware`],
+/// such as retries, logging or authentication, so that it's written once
+/// rather than by each caller.
+pub trait Middleware: 'static + Send + Sync {
+    /// Handles the request, usually by passing it on to the rest of the chain
+    /// with [`Next::run`].
+    fn handle(
+        &self,
+        request: Request<AsyncBody>,
+        next: Next,
+    ) -> BoxFuture<'static, Result<Response<AsyncBody>>>;
+}
+
+/// The rest of a middleware chain, which ends with the client that sends the
+/// request.
+#[derive(Clone)]
+pub struct Next {
+    client: Arc<dyn HttpClient>,
+    middleware: Arc<[Arc<dyn Middleware>]>,
+    index: usize,
+}
+
+impl Next {
+    pub fn run(
+        self,
+        request: Request<AsyncBody>,
+    ) -> BoxFuture<'static, Result<Response<AsyncBody>>> {
+        match self.middleware.get(self.index).cloned() {
+            Some(middleware) => middleware.handle(
+                request,
+                Next {
+                    index: self.index + 1,
+                    ..self
+                },
+            ),
+            None => self.client.send(request),
+        }
+    }
+}
+
+/// An [`HttpClient`] that sends its requests through a chain of
+/// [`Middleware`], in the order it was added.
+pub struct HttpClientWithMiddleware {
+    client: Arc<dyn HttpClient>,
+    middleware: Arc<[Arc<dyn Middleware>]>,
+}
+
+impl HttpClientWithMiddleware {
+    pub fn new(client: Arc<dyn HttpClient>) -> Self {
+        Self {
+            client,
+            middleware: Arc::new([]),
+        }
+    }
+
+    /// Adds the middleware to the end of the chain, so that it runs after
+    /// the middleware added before it.
+    pub fn with(self, middleware: impl Middleware) -> Self {
+        Self {
+            client: self.client,
+            middleware: self
+                .middleware
+                .iter()
+                .cloned()
+                .chain([Arc::new(middleware) as Arc<dyn Middleware>])
+                .collect(),
+        }
+    }
+}
+
+impl HttpClient for HttpClientWithMiddleware {
+    fn send(
+        &self,
+        req: Request<AsyncBody>,
+    ) -> BoxFuture<'static, anyhow::Result<Response<AsyncBody>>> {
+        Next {
+            client: self.client.clone(),
+            middleware: self.middleware.clone(),
+            index: 0,
+        }
+        .run(req)
+    }
+
+    fn proxy(&self) -> Option<&Url> {
+        self.client.proxy()
+    }
+
+    fn type_name(&self) -> &'static str {
+        self.client.type_name()
+    }
+}
+
+/// Copies the request so that it can be sent again, unless its body is read
+/// from a stream.
+pub fn try_clone_request(request: &Request<AsyncBody>) -> Option<Request<AsyncBody>> {
+    let mut clone = Request::new(request.body().try_clone()?);
+    *clone.method_mut() = request.method().clone();
+    *clone.uri_mut() = request.uri().clone();
+    *clone.version_mut() = request.version();
+    *clone.headers_mut() = request.headers().clone();
+    *clone.extensions_mut() = request.extensions().clone();
+    Some(clone)
+}
+
+/// Sets a header on the requests that don't already have it, such as the
+/// credentials of an API.
+pub struct SetHeader {
+    name: HeaderName,
+    value: HeaderValue,
+}
+
+impl SetHeader {
+    pub fn new(name: HeaderName, mut value: HeaderValue) -> Self {
+        if name == header::AUTHORIZATION {
+            value.set_sensitive(true);
+        }
+        Self { name, value }
+    }
+
+    /// Authenticates the requests with the given bearer token.
+    pub fn bearer_auth(token: &str) -> Result<Self> {
+        Ok(Self::new(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}"))?,
+        ))
+    }
+}
+
+impl Middleware for SetHeader {
+    fn handle(
+        &self,
+        mut request: Request<AsyncBody>,
+        next: Next,
+    ) -> BoxFuture<'static, Result<Response<AsyncBody>>> {
+        request
+            .headers_mut()
+            .entry(&self.name)
+            .or_insert_with(|| self.value.clone());
+        next.run(request)
+    }
+}
+
+/// Logs the requests, along with how they were answered and how long it
+/// took. Query strings are left out, since they may contain credentials.
+pub struct LogRequests {
+    name: Arc<str>,
+}
+
+impl LogRequests {
+    /// Logs the requests under the given name, such as the name of the API.
+    pub fn new(name: impl Into<Arc<str>>) -> Self {
+        Self { name: name.into() }
+    }
+}
+
+impl Middleware for LogRequests {
+    fn handle(
+        &self,
+        request: Request<AsyncBody>,
+        next: Next,
+    ) -> BoxFuture<'static, Result<Response<AsyncBody>>> {
+        let name = self.name.clone();
+        let description = format!(
+            "{} {}{}",
+            request.method(),
+            request.uri().host().unwrap_or_default(),
+            request.uri().path()
+        );
+        let started_at = Instant::now();
+        next.run(request)
+            .inspect(move |result| match result {
+                Ok(response) => log::debug!(
+                    "{name}: {description} returned {} in {:?}",
+                    response.status(),
+                    started_at.elapsed()
+                ),
+                Err(error) => log::debug!(
+                    "{name}: {description} failed in {:?}: {error:#}",
+                    started_at.elapsed()
+                ),
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::{any::type_name, sync::Mutex};
+
+    struct RecordingClient {
+        requests: Mutex<Vec<Request<AsyncBody>>>,
+    }
+
+    impl HttpClient for RecordingClient {
+        fn send(
+            &self,
+            req: Request<AsyncBody>,
+        ) -> BoxFuture<'static, anyhow::Result<Response<AsyncBody>>> {
+            self.requests.lock().unwrap().push(req);
+            async { Ok(Response::new(AsyncBody::empty())) }.boxed()
+        }
+
+        fn proxy(&self) -> Option<&Url> {
+            None
+        }
+
+        fn type_name(&self) -> &'static str {
+            type_name::<Self>()
+        }
+    }
+
+    struct Tag(&'static str);
+
+    impl Middleware for Tag {
+        fn handle(
+            &self,
+            mut request: Request<AsyncBody>,
+            next: Next,
+        ) -> BoxFuture<'static, Result<Response<AsyncBody>>> {
+            request
+                .headers_mut()
+                .append("x-tag", HeaderValue::from_static(self.0));
+            next.run(request)
+        }
+    }
+
+    #[test]
+    fn test_middleware_runs_in_order() {
+        let recording_client = Arc::new(RecordingClient {
+            requests: Mutex::default(),
+        });
+        let client = HttpClientWithMiddleware::new(recording_client.clone())
+            .with(Tag("first"))
+            .with(SetHeader::bearer_auth("secret").unwrap())
+            .with(Tag("second"));
+
+        let request = Request::builder()
+            .uri("https://example.com/v1/messages")
+            .header(header::AUTHORIZATION, "Bearer explicit")
+            .body(AsyncBody::from("body"))
+            .unwrap();
+        block_on(client.send(try_clone_request(&request).unwrap())).unwrap();
+        block_on(client.send(Request::new(AsyncBody::empty()))).unwrap();
+
+        let requests = recording_client.requests.lock().unwrap();
+        let tags = requests[0]
+            .headers()
+            .get_all("x-tag")
+            .iter()
+            .collect::<Vec<_>>();
+        assert_eq!(tags, ["first", "second"]);
+        assert_eq!(
+            requests[0].headers()[header::AUTHORIZATION],
+            "Bearer explicit"
+        );
+        assert_eq!(requests[0].uri(), "https://example.com/v1/messages");
+        assert_eq!(
+            requests[1].headers()[header::AUTHORIZATION],
+            "Bearer secret"
+        );
+    }
+}
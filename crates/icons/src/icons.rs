@@ -4,7 +4,17 @@ use serde::{Deserialize, Serialize};
 use strum::{EnumIter, EnumString, IntoStaticStr};
 
 #[derive(
-    Debug, PartialEq, Eq, Copy, Clone, EnumIter, EnumString, IntoStaticStr, Serialize, Deserialize,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Copy,
+    Clone,
+    EnumIter,
+    EnumString,
+    IntoStaticStr,
+    Serialize,
+    Deserialize,
 )]
 #[strum(serialize_all = "snake_case")]
 pub enum IconName {
@@ -20,6 +30,7 @@ pub enum IconName {
     AiOpenAi,
     AiOpenRouter,
     AiZed,
+    Archive,
     ArrowCircle,
     ArrowDown,
     ArrowDownFromLine,
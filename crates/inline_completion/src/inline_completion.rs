@@ -275,3 +275,27 @@ where
         self.update(cx, |this, cx| this.suggest(buffer, cursor_position, cx))
     }
 }
+
+/// Trims a completion to the rest of the cursor's line, unless it starts on a
+/// new line, in which case the following lines are kept too.
+pub fn trim_to_end_of_line_unless_leading_newline(text: &str) -> &str {
+    if has_leading_newline(text) {
+        text
+    } else if let Some(i) = text.find('\n') {
+        &text[..i]
+    } else {
+        text
+    }
+}
+
+fn has_leading_newline(text: &str) -> bool {
+    for c in text.chars() {
+        if c == '\n' {
+            return true;
+        }
+        if !c.is_whitespace() {
+            return false;
+        }
+    }
+    false
+}
@@ -18,6 +18,7 @@ use language::{
     EditPredictionsMode, File, Language,
     language_settings::{self, AllLanguageSettings, EditPredictionProvider, all_language_settings},
 };
+use language_model_completion::LanguageModelCompletionProvider;
 use regex::Regex;
 use settings::{Settings, SettingsStore, update_settings_file};
 use std::{
@@ -224,6 +225,55 @@ impl Render for InlineCompletionButton {
                 );
             }
 
+            EditPredictionProvider::LanguageModel => {
+                let model = LanguageModelCompletionProvider::model(cx);
+                let tooltip_text: SharedString = match &model {
+                    Some(model) => format!("Edit Prediction ({})", model.name().0).into(),
+                    None => "No model supports fill-in-the-middle completions".into(),
+                };
+                let this = cx.entity().clone();
+
+                let is_refreshing = self
+                    .edit_prediction_provider
+                    .as_ref()
+                    .map_or(false, |provider| provider.is_refreshing(cx));
+
+                let icon_button = IconButton::new("language-model-completion-icon", IconName::Ai)
+                    .shape(IconButtonShape::Square)
+                    .when(model.is_none(), |this| {
+                        this.indicator(Indicator::dot().color(Color::Muted))
+                            .indicator_border_color(Some(cx.theme().colors().status_bar_background))
+                    });
+                let popover_menu = PopoverMenu::new("language-model-completion")
+                    .menu(move |window, cx| {
+                        Some(this.update(cx, |this, cx| {
+                            this.build_language_model_context_menu(window, cx)
+                        }))
+                    })
+                    .anchor(Corner::BottomRight)
+                    .with_handle(self.popover_menu_handle.clone());
+                let popover_menu = if is_refreshing {
+                    popover_menu.trigger_with_tooltip(
+                        icon_button.with_animation(
+                            "pulsating-label",
+                            Animation::new(Duration::from_secs(2))
+                                .repeat()
+                                .with_easing(pulsating_between(0.2, 1.0)),
+                            |icon_button, delta| icon_button.alpha(delta),
+                        ),
+                        move |window, cx| {
+                            Tooltip::for_action(tooltip_text.clone(), &ToggleMenu, window, cx)
+                        },
+                    )
+                } else {
+                    popover_menu.trigger_with_tooltip(icon_button, move |window, cx| {
+                        Tooltip::for_action(tooltip_text.clone(), &ToggleMenu, window, cx)
+                    })
+                };
+
+                div().child(popover_menu)
+            }
+
             EditPredictionProvider::Zed => {
                 let enabled = self.editor_enabled.unwrap_or(true);
 
@@ -690,6 +740,21 @@ impl InlineCompletionButton {
         })
     }
 
+    fn build_language_model_context_menu(
+        &self,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Entity<ContextMenu> {
+        ContextMenu::build(window, cx, |menu, window, cx| {
+            self.build_language_settings_menu(menu, window, cx)
+                .separator()
+                .action(
+                    "Configure Providers",
+                    zed_actions::agent::OpenConfiguration.boxed_clone(),
+                )
+        })
+    }
+
     fn build_zeta_context_menu(
         &self,
         window: &mut Window,
@@ -216,6 +216,8 @@ pub enum EditPredictionProvider {
     Copilot,
     Supermaven,
     Zed,
+    /// Fill-in-the-middle completions from a model configured in the language model providers.
+    LanguageModel,
 }
 
 impl EditPredictionProvider {
@@ -224,7 +226,8 @@ impl EditPredictionProvider {
             EditPredictionProvider::Zed => true,
             EditPredictionProvider::None
             | EditPredictionProvider::Copilot
-            | EditPredictionProvider::Supermaven => false,
+            | EditPredictionProvider::Supermaven
+            | EditPredictionProvider::LanguageModel => false,
         }
     }
 }
@@ -243,6 +246,8 @@ pub struct EditPredictionSettings {
     pub mode: EditPredictionsMode,
     /// Settings specific to GitHub Copilot.
     pub copilot: CopilotSettings,
+    /// Settings specific to language model edit predictions.
+    pub language_model: LanguageModelEditPredictionSettings,
     /// Whether edit predictions are enabled in the assistant panel.
     /// This setting has no effect if globally disabled.
     pub enabled_in_text_threads: bool,
@@ -290,6 +295,16 @@ pub struct CopilotSettings {
     pub proxy_no_verify: Option<bool>,
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct LanguageModelEditPredictionSettings {
+    /// The language model provider to request completions from.
+    pub provider: Option<String>,
+    /// The model to request completions from.
+    pub model: Option<String>,
+    /// The maximum number of tokens to generate for a single prediction.
+    pub max_tokens: Option<u32>,
+}
+
 /// The settings for all languages.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct AllLanguageSettingsContent {
@@ -589,6 +604,9 @@ pub struct EditPredictionSettingsContent {
     /// Settings specific to GitHub Copilot.
     #[serde(default)]
     pub copilot: CopilotSettingsContent,
+    /// Settings specific to language model edit predictions.
+    #[serde(default)]
+    pub language_model: LanguageModelEditPredictionSettingsContent,
     /// Whether edit predictions are enabled in the assistant prompt editor.
     /// This has no effect if globally disabled.
     #[serde(default = "default_true")]
@@ -609,6 +627,27 @@ pub struct CopilotSettingsContent {
     pub proxy_no_verify: Option<bool>,
 }
 
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct LanguageModelEditPredictionSettingsContent {
+    /// The language model provider to request completions from, such as
+    /// `deepseek`, `mistral` or `ollama`. When unset, the first provider with
+    /// a model that supports fill-in-the-middle completions is used.
+    ///
+    /// Default: none
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// The model to request completions from.
+    ///
+    /// Default: none
+    #[serde(default)]
+    pub model: Option<String>,
+    /// The maximum number of tokens to generate for a single prediction.
+    ///
+    /// Default: none
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+}
+
 /// The settings for enabling/disabling features.
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -1233,6 +1272,17 @@ impl settings::Settings for AllLanguageSettings {
             })
             .unwrap_or_default();
 
+        let mut language_model_settings = default_value
+            .edit_predictions
+            .as_ref()
+            .map(|settings| settings.language_model.clone())
+            .map(|language_model| LanguageModelEditPredictionSettings {
+                provider: language_model.provider,
+                model: language_model.model,
+                max_tokens: language_model.max_tokens,
+            })
+            .unwrap_or_default();
+
         let mut enabled_in_text_threads = default_value
             .edit_predictions
             .as_ref()
@@ -1285,6 +1335,22 @@ impl settings::Settings for AllLanguageSettings {
                 copilot_settings.proxy_no_verify = Some(proxy_no_verify);
             }
 
+            if let Some(language_model) = user_settings
+                .edit_predictions
+                .as_ref()
+                .map(|settings| &settings.language_model)
+            {
+                if let Some(provider) = language_model.provider.clone() {
+                    language_model_settings.provider = Some(provider);
+                }
+                if let Some(model) = language_model.model.clone() {
+                    language_model_settings.model = Some(model);
+                }
+                if let Some(max_tokens) = language_model.max_tokens {
+                    language_model_settings.max_tokens = Some(max_tokens);
+                }
+            }
+
             // A user's global settings override the default global settings and
             // all default language-specific settings.
             merge_settings(&mut defaults, &user_settings.defaults);
@@ -1341,6 +1407,7 @@ impl settings::Settings for AllLanguageSettings {
                     .collect(),
                 mode: edit_predictions_mode,
                 copilot: copilot_settings,
+                language_model: language_model_settings,
                 enabled_in_text_threads,
             },
             defaults,
@@ -0,0 +1,119 @@
+/// A model that its provider has deprecated or removed, along with the model
+/// that the provider recommends instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LanguageModelDeprecation {
+    pub provider: &'static str,
+    pub model: &'static str,
+    pub replacement: &'static str,
+    /// Whether requests to the model are no longer accepted, as opposed to the
+    /// model being scheduled for removal.
+    pub removed: bool,
+}
+
+/// The deprecations announced by providers, kept in sync with the models that
+/// each provider lists.
+const DEPRECATIONS: &[LanguageModelDeprecation] = &[
+    deprecation("anthropic", "claude-2.0", "claude-sonnet-4-latest", true),
+    deprecation("anthropic", "claude-2.1", "claude-sonnet-4-latest", true),
+    deprecation(
+        "anthropic",
+        "claude-instant-1.2",
+        "claude-3-5-haiku-latest",
+        true,
+    ),
+    deprecation(
+        "anthropic",
+        "claude-3-sonnet-20240229",
+        "claude-sonnet-4-latest",
+        true,
+    ),
+    deprecation(
+        "anthropic",
+        "claude-3-opus-latest",
+        "claude-opus-4-latest",
+        false,
+    ),
+    deprecation("openai", "gpt-4-32k", "gpt-4.1", true),
+    deprecation("openai", "gpt-4-vision-preview", "gpt-4o", true),
+    deprecation("openai", "gpt-4.5-preview", "gpt-4.1", true),
+    deprecation("openai", "gpt-3.5-turbo", "gpt-4o-mini", false),
+    deprecation("openai", "o1-preview", "o3", false),
+    deprecation("google", "gemini-1.0-pro", "gemini-2.0-flash", true),
+    deprecation(
+        "google",
+        "gemini-1.5-pro",
+        "gemini-2.5-pro-preview-latest",
+        false,
+    ),
+    deprecation("google", "gemini-1.5-flash", "gemini-2.0-flash", false),
+    deprecation(
+        "google",
+        "gemini-2.5-pro-exp-03-25",
+        "gemini-2.5-pro-preview-latest",
+        true,
+    ),
+    deprecation(
+        "google",
+        "gemini-2.5-pro-preview-03-25",
+        "gemini-2.5-pro-preview-latest",
+        false,
+    ),
+];
+
+const fn deprecation(
+    provider: &'static str,
+    model: &'static str,
+    replacement: &'static str,
+    removed: bool,
+) -> LanguageModelDeprecation {
+    LanguageModelDeprecation {
+        provider,
+        model,
+        replacement,
+        removed,
+    }
+}
+
+impl LanguageModelDeprecation {
+    /// Returns the deprecation of the given model, following the chain of
+    /// replacements so that the recommended model is never itself deprecated.
+    pub fn for_model(provider: &str, model: &str) -> Option<Self> {
+        let mut deprecation = *Self::find(provider, model)?;
+        while let Some(next) = Self::find(provider, deprecation.replacement) {
+            if next.model == model {
+                break;
+            }
+            deprecation.replacement = next.replacement;
+        }
+        Some(deprecation)
+    }
+
+    fn find(provider: &str, model: &str) -> Option<&'static Self> {
+        DEPRECATIONS
+            .iter()
+            .find(|deprecation| deprecation.provider == provider && deprecation.model == model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replacements_are_current() {
+        for deprecation in DEPRECATIONS {
+            let resolved =
+                LanguageModelDeprecation::for_model(deprecation.provider, deprecation.model)
+                    .unwrap();
+            assert!(
+                LanguageModelDeprecation::find(resolved.provider, resolved.replacement).is_none(),
+                "{} is replaced by a deprecated model",
+                deprecation.model
+            );
+        }
+        assert_eq!(
+            LanguageModelDeprecation::for_model("openai", "gpt-4.1"),
+            None
+        );
+    }
+}
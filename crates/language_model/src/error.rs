@@ -0,0 +1,271 @@
+use std::time::Duration;
+
+use thiserror::Error;
+
+/// An error returned by a language model provider, classified so that it can
+/// be presented with the same recovery actions regardless of the provider.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum LanguageModelError {
+    #[error("Authentication failed: {message}")]
+    Authentication { message: String },
+    #[error("Quota exceeded: {message}")]
+    QuotaExceeded { message: String },
+    #[error("Rate limit exceeded: {message}")]
+    RateLimitExceeded { message: String },
+    #[error("Content filtered: {message}")]
+    ContentFiltered { message: String },
+    #[error(
+        "Context window limit exceeded{}",
+        .tokens.map(|tokens| format!(" ({tokens})")).unwrap_or_default()
+    )]
+    ContextWindowLimitExceeded { tokens: Option<usize> },
+    #[error("Server error: {message}")]
+    Server {
+        status: Option<u16>,
+        message: String,
+    },
+    #[error("Network error: {message}")]
+    Network { message: String },
+    #[error(
+        "No {} within {}s. You can raise the `{}` request timeout in your settings.",
+        .kind.waiting_for(),
+        .timeout.as_secs(),
+        .kind.setting_name()
+    )]
+    Timeout {
+        kind: TimeoutKind,
+        timeout: Duration,
+    },
+    #[error("Request cancelled")]
+    Cancelled,
+}
+
+impl LanguageModelError {
+    /// Maps an error returned by a provider's API into a [`LanguageModelError`],
+    /// based on the status code and message in the provider's response. Errors
+    /// that are already classified, or that can't be, are returned unchanged.
+    pub fn classify(error: anyhow::Error) -> anyhow::Error {
+        if error.is::<Self>() {
+            return error;
+        }
+        match Self::from_message(&format!("{error:#}")) {
+            Some(classified) => anyhow::Error::new(classified),
+            None => error,
+        }
+    }
+
+    /// A short description of the error, suitable for the header of an error
+    /// message.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Authentication { .. } => "Authentication Failed",
+            Self::QuotaExceeded { .. } => "Quota Exceeded",
+            Self::RateLimitExceeded { .. } => "Rate Limit Exceeded",
+            Self::ContentFiltered { .. } => "Response Blocked by Content Filter",
+            Self::ContextWindowLimitExceeded { .. } => "Context Window Exceeded",
+            Self::Server { .. } => "Provider Error",
+            Self::Network { .. } => "Connection Failed",
+            Self::Timeout { .. } => "Request Timed Out",
+            Self::Cancelled => "Request Cancelled",
+        }
+    }
+
+    pub fn from_message(message: &str) -> Option<Self> {
+        let lowercase = message.to_lowercase();
+        let mentions =
+            |patterns: &[&str]| patterns.iter().any(|pattern| lowercase.contains(pattern));
+        let status = status_code(&lowercase);
+        let has_status = |codes: &[u16]| status.is_some_and(|status| codes.contains(&status));
+        let message = message.to_string();
+
+        // Context length and content filter errors are usually reported as bad
+        // requests, so they're checked before the status code is considered.
+        let error = if mentions(&[
+            "context_length_exceeded",
+            "maximum context length",
+            "context window",
+            "prompt is too long",
+            "input is too long",
+            "too many tokens",
+        ]) {
+            Self::ContextWindowLimitExceeded { tokens: None }
+        } else if mentions(&[
+            "content_filter",
+            "content filter",
+            "content management policy",
+            "prohibited_content",
+            "safety settings",
+            "blocked by",
+        ]) {
+            Self::ContentFiltered { message }
+        } else if has_status(&[402])
+            || mentions(&[
+                "insufficient_quota",
+                "quota exceeded",
+                "credit balance",
+                "billing",
+                "payment required",
+            ])
+        {
+            Self::QuotaExceeded { message }
+        } else if has_status(&[429]) || mentions(&["rate limit", "rate_limit", "too many requests"])
+        {
+            Self::RateLimitExceeded { message }
+        } else if has_status(&[401, 403])
+            || mentions(&[
+                "unauthorized",
+                "invalid api key",
+                "invalid x-api-key",
+                "incorrect api key",
+                "invalid_api_key",
+                "api key not valid",
+                "authentication_error",
+                "missing api key",
+            ])
+        {
+            Self::Authentication { message }
+        } else if status.is_some_and(|status| (500..600).contains(&status))
+            || mentions(&[
+                "internal server error",
+                "bad gateway",
+                "service unavailable",
+                "overloaded",
+            ])
+        {
+            Self::Server { status, message }
+        } else if mentions(&[
+            "error sending request",
+            "dns error",
+            "failed to lookup address",
+            "connection refused",
+            "connection reset",
+            "connection closed",
+            "tcp connect error",
+            "timed out",
+            "error reading a body from connection",
+            "unexpected end of file",
+            "unexpected eof",
+            "broken pipe",
+        ]) {
+            Self::Network { message }
+        } else if mentions(&["request was cancelled", "request was canceled"]) {
+            Self::Cancelled
+        } else {
+            return None;
+        };
+        Some(error)
+    }
+}
+
+/// Which of a request's timeouts fired.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeoutKind {
+    /// The provider didn't respond to the request.
+    Connect,
+    /// The provider responded, but didn't stream the first part of the
+    /// response.
+    FirstToken,
+    /// The response stopped streaming partway through.
+    Idle,
+    /// The whole response took too long.
+    Total,
+}
+
+impl TimeoutKind {
+    /// The name of the setting that configures the timeout.
+    pub fn setting_name(&self) -> &'static str {
+        match self {
+            Self::Connect => "connect_secs",
+            Self::FirstToken => "first_token_secs",
+            Self::Idle => "idle_secs",
+            Self::Total => "total_secs",
+        }
+    }
+
+    fn waiting_for(&self) -> &'static str {
+        match self {
+            Self::Connect => "response from the provider",
+            Self::FirstToken => "tokens from the model",
+            Self::Idle => "further tokens from the model",
+            Self::Total => "complete response from the model",
+        }
+    }
+}
+
+/// Finds the first HTTP error status code mentioned in the message, as a
+/// standalone number.
+fn status_code(message: &str) -> Option<u16> {
+    message
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|word| word.len() == 3)
+        .filter_map(|word| word.parse().ok())
+        .find(|status| (400..600).contains(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_classify_provider_errors() {
+        let classify = |message: &str| LanguageModelError::from_message(message);
+
+        assert!(matches!(
+            classify("Failed to connect to OpenAI API: 401 Incorrect API key provided"),
+            Some(LanguageModelError::Authentication { .. })
+        ));
+        assert!(matches!(
+            classify("Failed to connect to OpenAI API: 429 insufficient_quota"),
+            Some(LanguageModelError::QuotaExceeded { .. })
+        ));
+        assert!(matches!(
+            classify("Failed to connect to API: 429 Too Many Requests"),
+            Some(LanguageModelError::RateLimitExceeded { .. })
+        ));
+        assert_eq!(
+            classify("400 This model's maximum context length is 128000 tokens"),
+            Some(LanguageModelError::ContextWindowLimitExceeded { tokens: None })
+        );
+        assert!(matches!(
+            classify(
+                "400 The response was filtered due to the prompt triggering content management policy"
+            ),
+            Some(LanguageModelError::ContentFiltered { .. })
+        ));
+        assert!(matches!(
+            classify("cloud language model request failed with status 503 Service Unavailable: "),
+            Some(LanguageModelError::Server {
+                status: Some(503),
+                ..
+            })
+        ));
+        assert!(matches!(
+            classify("error sending request for url (https://api.openai.com/v1/chat/completions)"),
+            Some(LanguageModelError::Network { .. })
+        ));
+        assert!(matches!(
+            classify("error decoding response body: error reading a body from connection"),
+            Some(LanguageModelError::Network { .. })
+        ));
+        // Numbers that merely contain a status code aren't mistaken for one.
+        assert_eq!(classify("the prompt has 4010 lines"), None);
+    }
+
+    #[test]
+    fn test_classify_preserves_classified_errors() {
+        let error =
+            LanguageModelError::classify(anyhow!(LanguageModelError::ContextWindowLimitExceeded {
+                tokens: Some(250_000)
+            }));
+        assert_eq!(
+            error.downcast_ref::<LanguageModelError>(),
+            Some(&LanguageModelError::ContextWindowLimitExceeded {
+                tokens: Some(250_000)
+            })
+        );
+
+        let error = LanguageModelError::classify(anyhow!("something unexpected happened"));
+        assert!(!error.is::<LanguageModelError>());
+    }
+}
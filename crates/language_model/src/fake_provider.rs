@@ -1,8 +1,8 @@
 use crate::{
     AuthenticateError, LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent,
-    LanguageModelId, LanguageModelName, LanguageModelProvider, LanguageModelProviderId,
-    LanguageModelProviderName, LanguageModelProviderState, LanguageModelRequest,
-    LanguageModelToolChoice,
+    LanguageModelError, LanguageModelId, LanguageModelName, LanguageModelPricing,
+    LanguageModelProvider, LanguageModelProviderId, LanguageModelProviderName,
+    LanguageModelProviderState, LanguageModelRequest, LanguageModelToolChoice, StopReason,
 };
 use futures::{FutureExt, StreamExt, channel::mpsc, future::BoxFuture, stream::BoxStream};
 use gpui::{AnyView, App, AsyncApp, Entity, Task, Window};
@@ -81,6 +81,79 @@ impl FakeLanguageModelProvider {
     }
 }
 
+/// A provider whose only model is free to use, so that it's picked to stand
+/// in for other models.
+#[derive(Clone)]
+pub struct FakeFreeLanguageModelProvider {
+    model: Arc<FakeLanguageModel>,
+}
+
+impl FakeFreeLanguageModelProvider {
+    pub fn new() -> Self {
+        Self {
+            model: Arc::new(FakeLanguageModel {
+                id: "fake-free",
+                name: "Fake Free",
+                pricing: Some(LanguageModelPricing {
+                    input_cost_per_million_tokens: 0.,
+                    output_cost_per_million_tokens: 0.,
+                }),
+                ..FakeLanguageModel::default()
+            }),
+        }
+    }
+
+    pub fn model(&self) -> Arc<FakeLanguageModel> {
+        self.model.clone()
+    }
+}
+
+impl LanguageModelProviderState for FakeFreeLanguageModelProvider {
+    type ObservableEntity = ();
+
+    fn observable_entity(&self) -> Option<Entity<Self::ObservableEntity>> {
+        None
+    }
+}
+
+impl LanguageModelProvider for FakeFreeLanguageModelProvider {
+    fn id(&self) -> LanguageModelProviderId {
+        LanguageModelProviderId::from("fake-free".to_string())
+    }
+
+    fn name(&self) -> LanguageModelProviderName {
+        LanguageModelProviderName::from("Fake Free".to_string())
+    }
+
+    fn default_model(&self, _cx: &App) -> Option<Arc<dyn LanguageModel>> {
+        Some(self.model.clone())
+    }
+
+    fn default_fast_model(&self, _cx: &App) -> Option<Arc<dyn LanguageModel>> {
+        Some(self.model.clone())
+    }
+
+    fn provided_models(&self, _: &App) -> Vec<Arc<dyn LanguageModel>> {
+        vec![self.model.clone()]
+    }
+
+    fn is_authenticated(&self, _: &App) -> bool {
+        true
+    }
+
+    fn authenticate(&self, _: &mut App) -> Task<Result<(), AuthenticateError>> {
+        Task::ready(Ok(()))
+    }
+
+    fn configuration_view(&self, _window: &mut Window, _: &mut App) -> AnyView {
+        unimplemented!()
+    }
+
+    fn reset_credentials(&self, _: &mut App) -> Task<Result<()>> {
+        Task::ready(Ok(()))
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct ToolUseRequest {
     pub request: LanguageModelRequest,
@@ -89,12 +162,40 @@ pub struct ToolUseRequest {
     pub schema: serde_json::Value,
 }
 
-#[derive(Default)]
 pub struct FakeLanguageModel {
-    current_completion_txs: Mutex<Vec<(LanguageModelRequest, mpsc::UnboundedSender<String>)>>,
+    id: &'static str,
+    name: &'static str,
+    pricing: Option<LanguageModelPricing>,
+    current_completion_txs: Mutex<
+        Vec<(
+            LanguageModelRequest,
+            mpsc::UnboundedSender<
+                Result<LanguageModelCompletionEvent, LanguageModelCompletionError>,
+            >,
+        )>,
+    >,
+    next_completion_error: Mutex<Option<LanguageModelError>>,
+}
+
+impl Default for FakeLanguageModel {
+    fn default() -> Self {
+        Self {
+            id: "fake",
+            name: "Fake",
+            pricing: None,
+            current_completion_txs: Mutex::default(),
+            next_completion_error: Mutex::default(),
+        }
+    }
 }
 
 impl FakeLanguageModel {
+    /// Makes the next completion request fail before its response starts
+    /// streaming, as if the provider rejected it.
+    pub fn fail_next_completion(&self, error: LanguageModelError) {
+        *self.next_completion_error.lock() = Some(error);
+    }
+
     pub fn pending_completions(&self) -> Vec<LanguageModelRequest> {
         self.current_completion_txs
             .lock()
@@ -111,6 +212,27 @@ impl FakeLanguageModel {
         &self,
         request: &LanguageModelRequest,
         chunk: impl Into<String>,
+    ) {
+        self.send_completion_event(
+            request,
+            Ok(LanguageModelCompletionEvent::Text(chunk.into())),
+        );
+    }
+
+    /// Fails the completion with the given error, as if the provider's
+    /// stream broke off.
+    pub fn send_completion_error(
+        &self,
+        request: &LanguageModelRequest,
+        error: LanguageModelCompletionError,
+    ) {
+        self.send_completion_event(request, Err(error));
+    }
+
+    fn send_completion_event(
+        &self,
+        request: &LanguageModelRequest,
+        event: Result<LanguageModelCompletionEvent, LanguageModelCompletionError>,
     ) {
         let current_completion_txs = self.current_completion_txs.lock();
         let tx = current_completion_txs
@@ -118,7 +240,7 @@ impl FakeLanguageModel {
             .find(|(req, _)| req == request)
             .map(|(_, tx)| tx)
             .unwrap();
-        tx.unbounded_send(chunk.into()).unwrap();
+        tx.unbounded_send(event).unwrap();
     }
 
     pub fn end_completion_stream(&self, request: &LanguageModelRequest) {
@@ -131,6 +253,19 @@ impl FakeLanguageModel {
         self.stream_completion_response(self.pending_completions().last().unwrap(), chunk);
     }
 
+    pub fn send_last_completion_error(&self, error: LanguageModelCompletionError) {
+        self.send_completion_error(self.pending_completions().last().unwrap(), error);
+    }
+
+    /// Ends the response of the last completion with the given stop reason,
+    /// without closing its stream.
+    pub fn send_last_completion_stop(&self, reason: StopReason) {
+        self.send_completion_event(
+            self.pending_completions().last().unwrap(),
+            Ok(LanguageModelCompletionEvent::Stop(reason)),
+        );
+    }
+
     pub fn end_last_completion_stream(&self) {
         self.end_completion_stream(self.pending_completions().last().unwrap());
     }
@@ -138,25 +273,29 @@ impl FakeLanguageModel {
 
 impl LanguageModel for FakeLanguageModel {
     fn id(&self) -> LanguageModelId {
-        language_model_id()
+        LanguageModelId::from(self.id.to_string())
     }
 
     fn name(&self) -> LanguageModelName {
-        language_model_name()
+        LanguageModelName::from(self.name.to_string())
     }
 
     fn provider_id(&self) -> LanguageModelProviderId {
-        provider_id()
+        LanguageModelProviderId::from(self.id.to_string())
     }
 
     fn provider_name(&self) -> LanguageModelProviderName {
-        provider_name()
+        LanguageModelProviderName::from(self.name.to_string())
     }
 
     fn supports_tools(&self) -> bool {
         false
     }
 
+    fn pricing(&self) -> Option<LanguageModelPricing> {
+        self.pricing
+    }
+
     fn supports_tool_choice(&self, _choice: LanguageModelToolChoice) -> bool {
         false
     }
@@ -187,14 +326,12 @@ impl LanguageModel for FakeLanguageModel {
             BoxStream<'static, Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
         >,
     > {
+        if let Some(error) = self.next_completion_error.lock().take() {
+            return async move { Err(error.into()) }.boxed();
+        }
         let (tx, rx) = mpsc::unbounded();
         self.current_completion_txs.lock().push((request, tx));
-        async move {
-            Ok(rx
-                .map(|text| Ok(LanguageModelCompletionEvent::Text(text)))
-                .boxed())
-        }
-        .boxed()
+        async move { Ok(rx.boxed()) }.boxed()
     }
 
     fn as_fake(&self) -> &Self {
@@ -1,14 +1,19 @@
+mod deprecation;
+mod error;
 mod model;
+mod model_catalog;
 mod rate_limiter;
 mod registry;
 mod request;
+mod request_timeout;
 mod role;
+mod spend;
 mod telemetry;
 
 #[cfg(any(test, feature = "test-support"))]
 pub mod fake_provider;
 
-use anyhow::{Context as _, Result};
+use anyhow::{Context as _, Result, anyhow};
 use client::Client;
 use futures::FutureExt;
 use futures::{StreamExt, future::BoxFuture, stream::BoxStream};
@@ -22,6 +27,7 @@ use std::fmt;
 use std::ops::{Add, Sub};
 use std::str::FromStr as _;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 use util::serde::is_default;
 use zed_llm_client::{
@@ -29,11 +35,16 @@ use zed_llm_client::{
     MODEL_REQUESTS_USAGE_LIMIT_HEADER_NAME, UsageLimit,
 };
 
+pub use crate::deprecation::*;
+pub use crate::error::*;
 pub use crate::model::*;
+pub use crate::model_catalog::*;
 pub use crate::rate_limiter::*;
 pub use crate::registry::*;
 pub use crate::request::*;
+pub use crate::request_timeout::*;
 pub use crate::role::*;
+pub use crate::spend::*;
 pub use crate::telemetry::*;
 
 pub const ZED_CLOUD_PROVIDER_ID: &str = "zed.dev";
@@ -41,6 +52,7 @@ pub const ZED_CLOUD_PROVIDER_ID: &str = "zed.dev";
 pub fn init(client: Arc<Client>, cx: &mut App) {
     init_settings(cx);
     RefreshLlmTokenListener::register(client.clone(), cx);
+    SpendTracker::init(cx);
 }
 
 pub fn init_settings(cx: &mut App) {
@@ -55,6 +67,28 @@ pub struct LanguageModelCacheConfiguration {
     pub min_total_token: usize,
 }
 
+/// Per-token pricing for a language model, expressed in US dollars per million tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LanguageModelPricing {
+    pub input_cost_per_million_tokens: f64,
+    pub output_cost_per_million_tokens: f64,
+}
+
+impl LanguageModelPricing {
+    /// Returns the cost in US dollars of the given usage.
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let input_tokens =
+            usage.input_tokens + usage.cache_creation_input_tokens + usage.cache_read_input_tokens;
+        (input_tokens as f64 * self.input_cost_per_million_tokens
+            + usage.output_tokens as f64 * self.output_cost_per_million_tokens)
+            / 1_000_000.
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.input_cost_per_million_tokens == 0. && self.output_cost_per_million_tokens == 0.
+    }
+}
+
 /// A completion event from a language model.
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum LanguageModelCompletionEvent {
@@ -70,6 +104,25 @@ pub enum LanguageModelCompletionEvent {
         message_id: String,
     },
     UsageUpdate(TokenUsage),
+    /// Web pages the response draws on, found by the provider's web search.
+    WebSources(Vec<WebSource>),
+    /// The request failed transiently and is about to be sent again.
+    Retrying {
+        /// The attempt that's about to be made, counting from 1.
+        attempt: u32,
+        max_attempts: u32,
+        /// How long until the request is sent again.
+        delay: Duration,
+        /// Why the previous attempt failed.
+        message: String,
+    },
+}
+
+/// A web page cited by a response.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+pub struct WebSource {
+    pub url: String,
+    pub title: Option<String>,
 }
 
 #[derive(Error, Debug)]
@@ -247,6 +300,22 @@ pub trait LanguageModel: Send + Sync {
         false
     }
 
+    /// Whether the request's seed makes this model's sampling deterministic.
+    fn supports_seed(&self) -> bool {
+        false
+    }
+
+    /// Whether this model accepts documents, such as PDFs, as attachments.
+    fn supports_documents(&self) -> bool {
+        false
+    }
+
+    /// Whether this model can search the web with its provider's own search
+    /// tool, and cite the pages it found.
+    fn supports_web_search(&self) -> bool {
+        false
+    }
+
     fn tool_input_format(&self) -> LanguageModelToolSchemaFormat {
         LanguageModelToolSchemaFormat::JsonSchema
     }
@@ -312,6 +381,8 @@ pub trait LanguageModel: Send + Sync {
                                 Ok(LanguageModelCompletionEvent::Thinking { .. }) => None,
                                 Ok(LanguageModelCompletionEvent::Stop(_)) => None,
                                 Ok(LanguageModelCompletionEvent::ToolUse(_)) => None,
+                                Ok(LanguageModelCompletionEvent::WebSources(_)) => None,
+                                Ok(LanguageModelCompletionEvent::Retrying { .. }) => None,
                                 Ok(LanguageModelCompletionEvent::UsageUpdate(token_usage)) => {
                                     *last_token_usage.lock() = token_usage;
                                     None
@@ -332,22 +403,46 @@ pub trait LanguageModel: Send + Sync {
         .boxed()
     }
 
+    /// Whether this model can complete the text between a prefix and a suffix.
+    fn supports_fill_in_the_middle(&self) -> bool {
+        false
+    }
+
+    /// Completes the text between the request's prefix and suffix, returning
+    /// the text to insert between them.
+    fn fill_in_the_middle(
+        &self,
+        _request: FillInTheMiddleRequest,
+        _cx: &AsyncApp,
+    ) -> BoxFuture<'static, Result<String>> {
+        futures::future::ready(Err(anyhow!(
+            "{} does not support fill-in-the-middle completions",
+            self.name().0
+        )))
+        .boxed()
+    }
+
+    /// Loads the model ahead of its first request, for providers that run
+    /// models locally and would otherwise load it when the request comes in.
+    fn warm_up(&self, _cx: &AsyncApp) -> BoxFuture<'static, Result<()>> {
+        futures::future::ready(Ok(())).boxed()
+    }
+
     fn cache_configuration(&self) -> Option<LanguageModelCacheConfiguration> {
         None
     }
 
+    /// Returns the pricing of this model, if the provider exposes it.
+    fn pricing(&self) -> Option<LanguageModelPricing> {
+        None
+    }
+
     #[cfg(any(test, feature = "test-support"))]
     fn as_fake(&self) -> &fake_provider::FakeLanguageModel {
         unimplemented!()
     }
 }
 
-#[derive(Debug, Error)]
-pub enum LanguageModelKnownError {
-    #[error("Context window limit exceeded ({tokens})")]
-    ContextWindowLimitExceeded { tokens: usize },
-}
-
 pub trait LanguageModelTool: 'static + DeserializeOwned + JsonSchema {
     fn name() -> String;
     fn description() -> String;
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use serde::{Serialize, de::DeserializeOwned};
+use std::path::PathBuf;
+
+use crate::LanguageModelProviderId;
+
+fn model_catalog_path(provider_id: &LanguageModelProviderId) -> PathBuf {
+    paths::language_model_catalogs_dir().join(format!("{}.json", provider_id.0))
+}
+
+/// Reads the catalog of a provider's models saved by [`cache_model_catalog`].
+/// This reads from the file system, so it should be run in the background.
+pub fn read_cached_model_catalog<T: DeserializeOwned>(
+    provider_id: &LanguageModelProviderId,
+) -> Result<T> {
+    let contents = std::fs::read_to_string(model_catalog_path(provider_id))?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Saves the catalog of a provider's models, so that its models are listed
+/// as soon as Zed starts, before they've been fetched again. This writes to
+/// the file system, so it should be run in the background.
+pub fn cache_model_catalog<T: Serialize>(
+    provider_id: &LanguageModelProviderId,
+    catalog: &T,
+) -> Result<()> {
+    let path = model_catalog_path(provider_id);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string(catalog)?)?;
+    Ok(())
+}
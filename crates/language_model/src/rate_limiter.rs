@@ -1,21 +1,94 @@
 use anyhow::Result;
+use collections::HashMap;
 use futures::Stream;
+use parking_lot::RwLock;
 use smol::lock::{Semaphore, SemaphoreGuardArc};
 use std::{
     future::Future,
+    mem,
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, LazyLock},
     task::{Context, Poll},
 };
 
+use crate::{LanguageModelProviderId, RequestPriority};
+
+/// A limit on the number of requests in flight, shared by the rate limiters
+/// of many models.
+struct SharedLimit {
+    limit: usize,
+    semaphore: Arc<Semaphore>,
+    /// Keeps background requests from taking the last free slot, like
+    /// [`RateLimiter::background_semaphore`].
+    background_semaphore: Arc<Semaphore>,
+}
+
+#[derive(Default)]
+struct ConcurrencyLimits {
+    global: Option<SharedLimit>,
+    per_provider: HashMap<LanguageModelProviderId, SharedLimit>,
+}
+
+static CONCURRENCY_LIMITS: LazyLock<RwLock<ConcurrencyLimits>> = LazyLock::new(Default::default);
+
+/// Sets the maximum number of requests that may be in flight at once across
+/// all providers, and for each of the given providers. Requests that are
+/// already in flight keep counting against the limit they started under.
+pub fn set_concurrency_limits(
+    global: Option<usize>,
+    per_provider: HashMap<LanguageModelProviderId, usize>,
+) {
+    let mut limits = CONCURRENCY_LIMITS.write();
+    limits.global = shared_limit(limits.global.take(), global);
+    let mut previous = mem::take(&mut limits.per_provider);
+    limits.per_provider = per_provider
+        .into_iter()
+        .filter_map(|(provider_id, limit)| {
+            let limit = shared_limit(previous.remove(&provider_id), Some(limit))?;
+            Some((provider_id, limit))
+        })
+        .collect();
+}
+
+/// Keeps the existing limit if it's unchanged, so the requests in flight keep
+/// counting against it.
+fn shared_limit(existing: Option<SharedLimit>, limit: Option<usize>) -> Option<SharedLimit> {
+    let limit = limit?.max(1);
+    match existing {
+        Some(existing) if existing.limit == limit => Some(existing),
+        _ => Some(SharedLimit {
+            limit,
+            semaphore: Arc::new(Semaphore::new(limit)),
+            background_semaphore: Arc::new(Semaphore::new(background_limit(limit))),
+        }),
+    }
+}
+
+/// The number of slots background requests may take, leaving one for
+/// interactive requests unless there's only one.
+fn background_limit(limit: usize) -> usize {
+    limit.saturating_sub(1).max(1)
+}
+
 #[derive(Clone)]
 pub struct RateLimiter {
     semaphore: Arc<Semaphore>,
+    /// Keeps background requests from taking the last free slot, so that an
+    /// interactive request never waits behind them.
+    background_semaphore: Arc<Semaphore>,
+    /// The provider whose concurrency limit the requests count against.
+    provider_id: Option<LanguageModelProviderId>,
+}
+
+struct RateLimitGuards {
+    _background: Option<SemaphoreGuardArc>,
+    _model: SemaphoreGuardArc,
+    _shared: Vec<(Option<SemaphoreGuardArc>, SemaphoreGuardArc)>,
 }
 
 pub struct RateLimitGuard<T> {
     inner: T,
-    _guard: SemaphoreGuardArc,
+    _guards: RateLimitGuards,
 }
 
 impl<T> Stream for RateLimitGuard<T>
@@ -33,38 +106,185 @@ impl RateLimiter {
     pub fn new(limit: usize) -> Self {
         Self {
             semaphore: Arc::new(Semaphore::new(limit)),
+            background_semaphore: Arc::new(Semaphore::new(background_limit(limit))),
+            provider_id: None,
         }
     }
 
-    pub fn run<'a, Fut, T>(&self, future: Fut) -> impl 'a + Future<Output = Result<T>>
+    /// Counts the requests against the provider's concurrency limit, if one
+    /// is set.
+    pub fn for_provider(mut self, provider_id: LanguageModelProviderId) -> Self {
+        self.provider_id = Some(provider_id);
+        self
+    }
+
+    pub fn run<'a, Fut, T>(
+        &self,
+        priority: RequestPriority,
+        future: Fut,
+    ) -> impl 'a + Future<Output = Result<T>>
     where
         Fut: 'a + Future<Output = Result<T>>,
     {
-        let guard = self.semaphore.acquire_arc();
+        let guards = self.acquire(priority);
         async move {
-            let guard = guard.await;
+            let guards = guards.await;
             let result = future.await?;
-            drop(guard);
+            drop(guards);
             Ok(result)
         }
     }
 
     pub fn stream<'a, Fut, T>(
         &self,
+        priority: RequestPriority,
         future: Fut,
     ) -> impl 'a + Future<Output = Result<impl Stream<Item = T::Item> + use<Fut, T>>>
     where
         Fut: 'a + Future<Output = Result<T>>,
         T: Stream,
     {
-        let guard = self.semaphore.acquire_arc();
+        let guards = self.acquire(priority);
         async move {
-            let guard = guard.await;
+            let guards = guards.await;
             let inner = future.await?;
             Ok(RateLimitGuard {
                 inner,
-                _guard: guard,
+                _guards: guards,
             })
         }
     }
+
+    /// Waits for a slot in each limit the request counts against, always in
+    /// the same order so that requests can't deadlock each other.
+    fn acquire(
+        &self,
+        priority: RequestPriority,
+    ) -> impl 'static + Future<Output = RateLimitGuards> {
+        let is_background = match priority {
+            RequestPriority::Interactive => false,
+            RequestPriority::Background => true,
+        };
+        let semaphore = self.semaphore.clone();
+        let background_semaphore = is_background.then(|| self.background_semaphore.clone());
+        let shared_semaphores = {
+            let limits = CONCURRENCY_LIMITS.read();
+            let provider = self
+                .provider_id
+                .as_ref()
+                .and_then(|provider_id| limits.per_provider.get(provider_id));
+            provider
+                .into_iter()
+                .chain(limits.global.as_ref())
+                .map(|limit| {
+                    let background_semaphore =
+                        is_background.then(|| limit.background_semaphore.clone());
+                    (background_semaphore, limit.semaphore.clone())
+                })
+                .collect::<Vec<_>>()
+        };
+        async move {
+            let background = match background_semaphore {
+                Some(background_semaphore) => Some(background_semaphore.acquire_arc().await),
+                None => None,
+            };
+            let model = semaphore.acquire_arc().await;
+            let mut shared = Vec::with_capacity(shared_semaphores.len());
+            for (background_semaphore, semaphore) in shared_semaphores {
+                let background = match background_semaphore {
+                    Some(background_semaphore) => Some(background_semaphore.acquire_arc().await),
+                    None => None,
+                };
+                shared.push((background, semaphore.acquire_arc().await));
+            }
+            RateLimitGuards {
+                _background: background,
+                _model: model,
+                _shared: shared,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{FutureExt as _, channel::oneshot};
+
+    #[test]
+    fn test_background_requests_leave_a_slot_for_interactive_ones() {
+        let limiter = RateLimiter::new(2);
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let release_rx = release_rx.shared();
+
+        let mut first_background = limiter
+            .run(RequestPriority::Background, {
+                let release_rx = release_rx.clone();
+                async move { Ok(release_rx.await.ok()) }
+            })
+            .boxed_local();
+        assert!((&mut first_background).now_or_never().is_none());
+
+        // The only other slot is kept for interactive requests.
+        let mut second_background = limiter
+            .run(RequestPriority::Background, async { Ok(()) })
+            .boxed_local();
+        assert!((&mut second_background).now_or_never().is_none());
+        let interactive = limiter.run(RequestPriority::Interactive, async { Ok(()) });
+        assert!(interactive.now_or_never().is_some());
+
+        release_tx.send(()).unwrap();
+        assert!(first_background.now_or_never().is_some());
+        assert!(second_background.now_or_never().is_some());
+    }
+
+    #[test]
+    fn test_provider_concurrency_limit_is_shared_between_models() {
+        let provider_id = LanguageModelProviderId("concurrency-test".into());
+        set_concurrency_limits(None, HashMap::from_iter([(provider_id.clone(), 1)]));
+        let first_model = RateLimiter::new(4).for_provider(provider_id.clone());
+        let second_model = RateLimiter::new(4).for_provider(provider_id.clone());
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+
+        let mut first_request = first_model
+            .run(RequestPriority::Interactive, async move {
+                Ok(release_rx.await.ok())
+            })
+            .boxed_local();
+        assert!((&mut first_request).now_or_never().is_none());
+
+        let mut second_request = second_model
+            .run(RequestPriority::Interactive, async { Ok(()) })
+            .boxed_local();
+        assert!((&mut second_request).now_or_never().is_none());
+        let other_provider =
+            RateLimiter::new(4).run(RequestPriority::Interactive, async { Ok(()) });
+        assert!(other_provider.now_or_never().is_some());
+
+        release_tx.send(()).unwrap();
+        assert!(first_request.now_or_never().is_some());
+        assert!(second_request.now_or_never().is_some());
+
+        // Background requests from any of the provider's models leave its
+        // last slot for interactive requests.
+        set_concurrency_limits(None, HashMap::from_iter([(provider_id.clone(), 2)]));
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let mut first_background = first_model
+            .run(RequestPriority::Background, async move {
+                Ok(release_rx.await.ok())
+            })
+            .boxed_local();
+        assert!((&mut first_background).now_or_never().is_none());
+
+        let mut second_background = second_model
+            .run(RequestPriority::Background, async { Ok(()) })
+            .boxed_local();
+        assert!((&mut second_background).now_or_never().is_none());
+        let interactive = second_model.run(RequestPriority::Interactive, async { Ok(()) });
+        assert!(interactive.now_or_never().is_some());
+
+        release_tx.send(()).unwrap();
+        assert!(first_background.now_or_never().is_some());
+        assert!(second_background.now_or_never().is_some());
+    }
 }
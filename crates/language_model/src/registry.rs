@@ -161,6 +161,60 @@ impl LanguageModelRegistry {
             .flat_map(|provider| provider.provided_models(cx))
     }
 
+    /// Returns the models of authenticated providers whose pricing is known to be free.
+    pub fn free_models<'a>(
+        &'a self,
+        cx: &'a App,
+    ) -> impl Iterator<Item = Arc<dyn LanguageModel>> + 'a {
+        self.providers
+            .values()
+            .filter(|provider| provider.is_authenticated(cx))
+            .flat_map(|provider| provider.provided_models(cx))
+            .filter(|model| model.pricing().is_some_and(|pricing| pricing.is_free()))
+    }
+
+    /// Returns a free model that can stand in for the given one, preferring the
+    /// smallest context window that is at least as large as the model's.
+    pub fn free_model_substitute(
+        &self,
+        model: &Arc<dyn LanguageModel>,
+        cx: &App,
+    ) -> Option<Arc<dyn LanguageModel>> {
+        let max_token_count = model.max_token_count();
+        self.free_models(cx)
+            .filter(|candidate| {
+                candidate.provider_id() != model.provider_id() || candidate.id() != model.id()
+            })
+            .max_by_key(|candidate| {
+                let fits = candidate.max_token_count() >= max_token_count;
+                // Among models that fit, prefer the closest context window;
+                // otherwise prefer the largest one.
+                let closeness = if fits {
+                    usize::MAX - candidate.max_token_count()
+                } else {
+                    candidate.max_token_count()
+                };
+                (fits, closeness)
+            })
+    }
+
+    /// Returns a model that supports fill-in-the-middle completions, optionally
+    /// restricted to the given provider and model ids.
+    pub fn fill_in_the_middle_model(
+        &self,
+        provider_id: Option<&str>,
+        model_id: Option<&str>,
+        cx: &App,
+    ) -> Option<Arc<dyn LanguageModel>> {
+        self.providers
+            .values()
+            .filter(|provider| provider_id.map_or(true, |id| provider.id().0.as_ref() == id))
+            .filter(|provider| provider.is_authenticated(cx))
+            .flat_map(|provider| provider.provided_models(cx))
+            .filter(|model| model_id.map_or(true, |id| model.id().0.as_ref() == id))
+            .find(|model| model.supports_fill_in_the_middle())
+    }
+
     pub fn provider(&self, id: &LanguageModelProviderId) -> Option<Arc<dyn LanguageModelProvider>> {
         self.providers.get(id).cloned()
     }
@@ -4,11 +4,12 @@ use std::sync::Arc;
 use crate::role::Role;
 use crate::{LanguageModelToolUse, LanguageModelToolUseId};
 use anyhow::Result;
+use base64::Engine as _;
 use base64::write::EncoderWriter;
 use gpui::{
-    App, AppContext as _, DevicePixels, Image, ImageFormat, ObjectFit, SharedString, Size, Task,
-    point, px, size,
+    App, AppContext as _, DevicePixels, Image, ImageFormat, SharedString, Size, Task, size,
 };
+use image::ImageDecoder as _;
 use image::codecs::png::PngEncoder;
 use serde::{Deserialize, Serialize};
 use util::ResultExt;
@@ -75,8 +76,14 @@ impl std::fmt::Debug for LanguageModelImage {
     }
 }
 
-/// Anthropic wants uploaded images to be smaller than this in both dimensions.
-const ANTHROPIC_SIZE_LIMT: f32 = 1568.;
+/// The largest width or height images are sent at. Anthropic wants uploaded
+/// images to be smaller than this in both dimensions, and the other providers
+/// accept images of this size.
+const MAX_IMAGE_DIMENSION: u32 = 1568;
+
+/// The largest number of pixels images are sent at. Anthropic downscales
+/// larger images anyway, so sending them only costs upload time.
+const MAX_IMAGE_PIXELS: u64 = 1_150_000;
 
 impl LanguageModelImage {
     pub fn empty() -> Self {
@@ -86,60 +93,67 @@ impl LanguageModelImage {
         }
     }
 
+    /// Converts an image to a PNG that every provider accepts: decoded from
+    /// any format Zed can display, rotated upright according to its EXIF
+    /// orientation, downscaled to fit the size limits, and re-encoded without
+    /// its metadata.
     pub fn from_image(data: Arc<Image>, cx: &mut App) -> Task<Option<Self>> {
         cx.background_spawn(async move {
-            let image_bytes = Cursor::new(data.bytes());
-            let dynamic_image = match data.format() {
-                ImageFormat::Png => image::codecs::png::PngDecoder::new(image_bytes)
-                    .and_then(image::DynamicImage::from_decoder),
-                ImageFormat::Jpeg => image::codecs::jpeg::JpegDecoder::new(image_bytes)
-                    .and_then(image::DynamicImage::from_decoder),
-                ImageFormat::Webp => image::codecs::webp::WebPDecoder::new(image_bytes)
-                    .and_then(image::DynamicImage::from_decoder),
-                ImageFormat::Gif => image::codecs::gif::GifDecoder::new(image_bytes)
-                    .and_then(image::DynamicImage::from_decoder),
-                _ => return None,
-            }
-            .log_err()?;
-
-            let width = dynamic_image.width();
-            let height = dynamic_image.height();
-            let image_size = size(DevicePixels(width as i32), DevicePixels(height as i32));
-
-            let base64_image = {
-                if image_size.width.0 > ANTHROPIC_SIZE_LIMT as i32
-                    || image_size.height.0 > ANTHROPIC_SIZE_LIMT as i32
-                {
-                    let new_bounds = ObjectFit::ScaleDown.get_bounds(
-                        gpui::Bounds {
-                            origin: point(px(0.0), px(0.0)),
-                            size: size(px(ANTHROPIC_SIZE_LIMT), px(ANTHROPIC_SIZE_LIMT)),
-                        },
-                        image_size,
-                    );
-                    let resized_image = dynamic_image.resize(
-                        new_bounds.size.width.0 as u32,
-                        new_bounds.size.height.0 as u32,
-                        image::imageops::FilterType::Triangle,
-                    );
-
-                    encode_as_base64(data, resized_image)
-                } else {
-                    encode_as_base64(data, dynamic_image)
-                }
+            let format = match data.format() {
+                ImageFormat::Png => image::ImageFormat::Png,
+                ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+                ImageFormat::Webp => image::ImageFormat::WebP,
+                ImageFormat::Gif => image::ImageFormat::Gif,
+                ImageFormat::Bmp => image::ImageFormat::Bmp,
+                ImageFormat::Tiff => image::ImageFormat::Tiff,
+                ImageFormat::Svg => return None,
+            };
+            let mut decoder = image::ImageReader::with_format(Cursor::new(data.bytes()), format)
+                .into_decoder()
+                .log_err()?;
+            let orientation = decoder
+                .orientation()
+                .unwrap_or(image::metadata::Orientation::NoTransforms);
+            let mut dynamic_image = image::DynamicImage::from_decoder(decoder).log_err()?;
+            dynamic_image.apply_orientation(orientation);
+
+            let (width, height) =
+                fit_image_dimensions(dynamic_image.width(), dynamic_image.height());
+            if (width, height) != (dynamic_image.width(), dynamic_image.height()) {
+                dynamic_image = dynamic_image.resize_exact(
+                    width,
+                    height,
+                    image::imageops::FilterType::Triangle,
+                );
             }
-            .log_err()?;
+
+            let base64_image = encode_as_base64(dynamic_image).log_err()?;
 
             // SAFETY: The base64 encoder should not produce non-UTF8.
             let source = unsafe { String::from_utf8_unchecked(base64_image) };
 
             Some(LanguageModelImage {
-                size: image_size,
+                size: size(DevicePixels(width as i32), DevicePixels(height as i32)),
                 source: source.into(),
             })
         })
     }
 
+    /// Decodes a base64-encoded image with the given MIME type, such as an
+    /// image returned by a context server tool.
+    pub fn from_base64(data: &str, mime_type: &str, cx: &mut App) -> Task<Option<Self>> {
+        let Some(format) = ImageFormat::from_mime_type(mime_type) else {
+            return Task::ready(None);
+        };
+        let Some(bytes) = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .log_err()
+        else {
+            return Task::ready(None);
+        };
+        Self::from_image(Arc::new(Image::from_bytes(format, bytes)), cx)
+    }
+
     pub fn estimate_tokens(&self) -> usize {
         let width = self.size.width.0.unsigned_abs() as usize;
         let height = self.size.height.0.unsigned_abs() as usize;
@@ -155,23 +169,94 @@ impl LanguageModelImage {
     }
 }
 
-fn encode_as_base64(data: Arc<Image>, image: image::DynamicImage) -> Result<Vec<u8>> {
+/// Scales the dimensions down, keeping the aspect ratio, to fit within
+/// `MAX_IMAGE_DIMENSION` and `MAX_IMAGE_PIXELS`.
+fn fit_image_dimensions(width: u32, height: u32) -> (u32, u32) {
+    if width == 0 || height == 0 {
+        return (width, height);
+    }
+    let pixels = width as u64 * height as u64;
+    let scale = (MAX_IMAGE_DIMENSION as f64 / width as f64)
+        .min(MAX_IMAGE_DIMENSION as f64 / height as f64)
+        .min((MAX_IMAGE_PIXELS as f64 / pixels as f64).sqrt());
+    if scale >= 1. {
+        return (width, height);
+    }
+    let scaled = |dimension: u32| ((dimension as f64 * scale).floor() as u32).max(1);
+    (scaled(width), scaled(height))
+}
+
+fn encode_as_base64(image: image::DynamicImage) -> Result<Vec<u8>> {
     let mut base64_image = Vec::new();
     {
         let mut base64_encoder = EncoderWriter::new(
             Cursor::new(&mut base64_image),
             &base64::engine::general_purpose::STANDARD,
         );
-        if data.format() == ImageFormat::Png {
-            base64_encoder.write_all(data.bytes())?;
-        } else {
-            let mut png = Vec::new();
-            image.write_with_encoder(PngEncoder::new(&mut png))?;
+        let mut png = Vec::new();
+        image.write_with_encoder(PngEncoder::new(&mut png))?;
+        base64_encoder.write_all(png.as_slice())?;
+    }
+    Ok(base64_image)
+}
+
+/// A document, such as a PDF, sent to the model as a file rather than as
+/// text, so that the model sees its layout, tables and figures too.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct LanguageModelDocument {
+    /// The base64-encoded contents of the document.
+    pub source: SharedString,
+    pub media_type: SharedString,
+    pub name: SharedString,
+    /// How many tokens the document takes up, as estimated by whatever
+    /// attached it.
+    pub estimated_tokens: usize,
+}
+
+impl std::fmt::Debug for LanguageModelDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LanguageModelDocument")
+            .field("source", &format!("<{} bytes>", self.source.len()))
+            .field("media_type", &self.media_type)
+            .field("name", &self.name)
+            .field("estimated_tokens", &self.estimated_tokens)
+            .finish()
+    }
+}
 
-            base64_encoder.write_all(png.as_slice())?;
+impl LanguageModelDocument {
+    pub fn new(
+        name: impl Into<SharedString>,
+        media_type: impl Into<SharedString>,
+        bytes: &[u8],
+        estimated_tokens: usize,
+    ) -> Self {
+        Self {
+            source: base64::engine::general_purpose::STANDARD
+                .encode(bytes)
+                .into(),
+            media_type: media_type.into(),
+            name: name.into(),
+            estimated_tokens,
         }
     }
-    Ok(base64_image)
+
+    /// The size of the document once decoded, in bytes.
+    pub fn len(&self) -> usize {
+        self.source.len() / 4 * 3
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.source.is_empty()
+    }
+
+    pub fn estimate_tokens(&self) -> usize {
+        self.estimated_tokens
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(base64::engine::general_purpose::STANDARD.decode(self.source.as_bytes())?)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
@@ -305,6 +390,7 @@ pub enum MessageContent {
     },
     RedactedThinking(Vec<u8>),
     Image(LanguageModelImage),
+    Document(LanguageModelDocument),
     ToolUse(LanguageModelToolUse),
     ToolResult(LanguageModelToolResult),
 }
@@ -316,7 +402,9 @@ impl MessageContent {
             MessageContent::Thinking { text, .. } => Some(text.as_str()),
             MessageContent::RedactedThinking(_) => None,
             MessageContent::ToolResult(tool_result) => tool_result.content.to_str(),
-            MessageContent::ToolUse(_) | MessageContent::Image(_) => None,
+            MessageContent::ToolUse(_) | MessageContent::Image(_) | MessageContent::Document(_) => {
+                None
+            }
         }
     }
 
@@ -327,7 +415,8 @@ impl MessageContent {
             MessageContent::ToolResult(tool_result) => tool_result.content.is_empty(),
             MessageContent::RedactedThinking(_)
             | MessageContent::ToolUse(_)
-            | MessageContent::Image(_) => false,
+            | MessageContent::Image(_)
+            | MessageContent::Document(_) => false,
         }
     }
 }
@@ -364,6 +453,19 @@ impl LanguageModelRequestMessage {
     pub fn contents_empty(&self) -> bool {
         self.content.iter().all(|content| content.is_empty())
     }
+
+    /// A rough estimate of the number of tokens in the message, for deciding
+    /// how to send it without counting its tokens with the provider.
+    pub fn estimate_tokens(&self) -> usize {
+        self.content
+            .iter()
+            .map(|content| match content {
+                MessageContent::Image(image) => image.estimate_tokens(),
+                MessageContent::Document(document) => document.estimate_tokens(),
+                content => content.to_str().map_or(0, |text| text.len() / 4),
+            })
+            .sum()
+    }
 }
 
 #[derive(Debug, PartialEq, Hash, Clone, Serialize, Deserialize)]
@@ -391,6 +493,44 @@ pub struct LanguageModelRequest {
     pub tool_choice: Option<LanguageModelToolChoice>,
     pub stop: Vec<String>,
     pub temperature: Option<f32>,
+    /// Makes sampling deterministic, for the providers that support it, so
+    /// that a response can be reproduced.
+    pub seed: Option<u64>,
+    pub priority: RequestPriority,
+    /// Lets the model search the web with the provider's own search tool,
+    /// for the models that support it.
+    pub web_search: bool,
+}
+
+impl LanguageModelRequest {
+    /// A rough estimate of the number of tokens in the request's messages.
+    pub fn estimate_tokens(&self) -> usize {
+        self.messages
+            .iter()
+            .map(LanguageModelRequestMessage::estimate_tokens)
+            .sum()
+    }
+}
+
+/// Whether the user is waiting on a request. Interactive requests are sent
+/// ahead of background ones when a provider's request slots run short.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RequestPriority {
+    /// Chat messages, inline assists and other requests the user is waiting on.
+    #[default]
+    Interactive,
+    /// Title generation, summaries and batch jobs.
+    Background,
+}
+
+/// A request to complete the text between a prefix and a suffix.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FillInTheMiddleRequest {
+    pub prefix: String,
+    pub suffix: String,
+    pub max_tokens: Option<u32>,
+    pub stop: Vec<String>,
+    pub temperature: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
@@ -403,6 +543,17 @@ pub struct LanguageModelResponseMessage {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_fit_image_dimensions() {
+        assert_eq!(fit_image_dimensions(800, 600), (800, 600));
+        assert_eq!(fit_image_dimensions(3136, 400), (1568, 200));
+        assert_eq!(fit_image_dimensions(400, 6272), (100, 1568));
+        let (width, height) = fit_image_dimensions(1500, 1500);
+        assert!(width as u64 * height as u64 <= MAX_IMAGE_PIXELS);
+        assert_eq!(width, height);
+        assert_eq!(fit_image_dimensions(10000, 1), (1568, 1));
+    }
+
     #[test]
     fn test_language_model_tool_result_content_deserialization() {
         let json = r#""This is plain text""#;
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use collections::HashMap;
+use futures::{Stream, StreamExt as _};
+use parking_lot::RwLock;
+use smol::Timer;
+use std::{
+    future::Future,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use crate::{LanguageModelError, LanguageModelProviderId, LanguageModelRequest, TimeoutKind};
+
+/// The number of tokens in a request for which the timeouts that depend on
+/// the size of the request are extended by their configured length again.
+const TOKENS_PER_TIMEOUT_EXTENSION: usize = 100_000;
+
+/// Limits on how long a request may wait on the provider. Requests have no
+/// timeouts unless they're configured in the settings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RequestTimeouts {
+    /// How long to wait for the provider to respond to the request.
+    pub connect: Option<Duration>,
+    /// How long to wait for the first event of the response, from when the
+    /// request is sent.
+    pub first_token: Option<Duration>,
+    /// How long to wait between the events of the response.
+    pub idle: Option<Duration>,
+    /// How long to wait for the whole response, from when the request is
+    /// sent.
+    pub total: Option<Duration>,
+}
+
+#[derive(Default)]
+struct TimeoutSettings {
+    default: RequestTimeouts,
+    per_provider: HashMap<LanguageModelProviderId, RequestTimeouts>,
+}
+
+static REQUEST_TIMEOUTS: LazyLock<RwLock<TimeoutSettings>> = LazyLock::new(Default::default);
+
+/// Sets the timeouts of the requests sent to every provider, and those of
+/// the given providers, which take precedence. Requests that are already in
+/// flight keep the timeouts they started with.
+pub fn set_request_timeouts(
+    default: RequestTimeouts,
+    per_provider: HashMap<LanguageModelProviderId, RequestTimeouts>,
+) {
+    let mut settings = REQUEST_TIMEOUTS.write();
+    settings.default = default;
+    settings.per_provider = per_provider;
+}
+
+impl RequestTimeouts {
+    /// The timeouts of a request to the given provider. Models take longer
+    /// to start responding to long requests, so the first token and total
+    /// timeouts grow with the size of the request.
+    pub fn for_request(
+        provider_id: &LanguageModelProviderId,
+        request: &LanguageModelRequest,
+    ) -> Self {
+        let timeouts = {
+            let settings = REQUEST_TIMEOUTS.read();
+            match settings.per_provider.get(provider_id) {
+                Some(timeouts) => timeouts.or(settings.default),
+                None => settings.default,
+            }
+        };
+        let scale = 1. + request.estimate_tokens() as f64 / TOKENS_PER_TIMEOUT_EXTENSION as f64;
+        Self {
+            first_token: timeouts.first_token.map(|timeout| timeout.mul_f64(scale)),
+            total: timeouts.total.map(|timeout| timeout.mul_f64(scale)),
+            ..timeouts
+        }
+    }
+
+    /// Fills in the timeouts that aren't set with the given ones.
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            connect: self.connect.or(fallback.connect),
+            first_token: self.first_token.or(fallback.first_token),
+            idle: self.idle.or(fallback.idle),
+            total: self.total.or(fallback.total),
+        }
+    }
+
+    /// Fails the request with a [`LanguageModelError::Timeout`] when one of
+    /// the timeouts fires, either before the provider responds or as the
+    /// response streams, after which the response ends.
+    pub fn apply<Fut, S, T, E>(
+        self,
+        future: Fut,
+    ) -> impl Future<Output = Result<impl Stream<Item = Result<T, E>>>>
+    where
+        Fut: Future<Output = Result<S>>,
+        S: Stream<Item = Result<T, E>>,
+        E: From<anyhow::Error>,
+    {
+        async move {
+            let sent_at = Instant::now();
+            let stream = match self.connect {
+                Some(timeout) => {
+                    let Some(stream) = with_timeout(future, timeout).await else {
+                        return Err(LanguageModelError::Timeout {
+                            kind: TimeoutKind::Connect,
+                            timeout,
+                        }
+                        .into());
+                    };
+                    stream?
+                }
+                None => future.await?,
+            };
+            Ok(self.watch(stream, sent_at))
+        }
+    }
+
+    fn watch<S, T, E>(self, stream: S, sent_at: Instant) -> impl Stream<Item = Result<T, E>>
+    where
+        S: Stream<Item = Result<T, E>>,
+        E: From<anyhow::Error>,
+    {
+        let stream = Box::pin(stream);
+        futures::stream::unfold(Some((stream, true)), move |state| async move {
+            let (mut stream, is_first) = state?;
+            let deadline = if is_first {
+                self.first_token
+                    .map(|timeout| (TimeoutKind::FirstToken, timeout, sent_at + timeout))
+            } else {
+                self.idle
+                    .map(|timeout| (TimeoutKind::Idle, timeout, Instant::now() + timeout))
+            };
+            let total = self
+                .total
+                .map(|timeout| (TimeoutKind::Total, timeout, sent_at + timeout));
+            let deadline = deadline
+                .into_iter()
+                .chain(total)
+                .min_by_key(|(_, _, deadline)| *deadline);
+
+            let next = match deadline {
+                Some((_, _, deadline)) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    with_timeout(stream.next(), remaining).await
+                }
+                None => Some(stream.next().await),
+            };
+            match (next, deadline) {
+                (Some(Some(item)), _) => Some((item, Some((stream, false)))),
+                (Some(None), _) => None,
+                (None, Some((kind, timeout, _))) => {
+                    let error = anyhow::Error::new(LanguageModelError::Timeout { kind, timeout });
+                    Some((Err(E::from(error)), None))
+                }
+                (None, None) => None,
+            }
+        })
+    }
+}
+
+/// Waits for the future, or returns `None` if it doesn't finish in time.
+async fn with_timeout<T>(future: impl Future<Output = T>, timeout: Duration) -> Option<T> {
+    smol::future::or(async { Some(future.await) }, async {
+        Timer::after(timeout).await;
+        None
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LanguageModelRequestMessage, MessageContent, Role};
+    use futures::{channel::mpsc, executor::block_on};
+
+    #[test]
+    fn test_timeouts_grow_with_request_size() {
+        let provider_id = LanguageModelProviderId("timeout-test".into());
+        set_request_timeouts(
+            RequestTimeouts {
+                connect: Some(Duration::from_secs(10)),
+                first_token: Some(Duration::from_secs(30)),
+                ..Default::default()
+            },
+            HashMap::from_iter([(
+                provider_id.clone(),
+                RequestTimeouts {
+                    first_token: Some(Duration::from_secs(60)),
+                    ..Default::default()
+                },
+            )]),
+        );
+
+        let request = LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![MessageContent::Text("a".repeat(400_000))],
+                cache: false,
+            }],
+            ..Default::default()
+        };
+        let timeouts = RequestTimeouts::for_request(&provider_id, &request);
+        assert_eq!(timeouts.connect, Some(Duration::from_secs(10)));
+        assert_eq!(timeouts.first_token, Some(Duration::from_secs(120)));
+        assert_eq!(timeouts.idle, None);
+
+        let timeouts = RequestTimeouts::for_request(
+            &LanguageModelProviderId("other-provider".into()),
+            &LanguageModelRequest::default(),
+        );
+        assert_eq!(timeouts.first_token, Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_idle_timeout_ends_the_response() {
+        let timeouts = RequestTimeouts {
+            idle: Some(Duration::from_millis(10)),
+            ..Default::default()
+        };
+        let (tx, rx) = mpsc::unbounded::<Result<&str, anyhow::Error>>();
+        tx.unbounded_send(Ok("first")).unwrap();
+
+        let events = block_on(async {
+            let stream = timeouts.apply(async move { Ok(rx) }).await.unwrap();
+            stream.collect::<Vec<_>>().await
+        });
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].as_ref().unwrap(), &"first");
+        assert_eq!(
+            events[1]
+                .as_ref()
+                .unwrap_err()
+                .downcast_ref::<LanguageModelError>(),
+            Some(&LanguageModelError::Timeout {
+                kind: TimeoutKind::Idle,
+                timeout: Duration::from_millis(10),
+            })
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt;
+
+use chrono::{Datelike as _, Local};
+use gpui::{App, AppContext as _, Global};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use util::ResultExt as _;
+
+use crate::LanguageModel;
+
+#[derive(Error, Debug, Clone, Copy)]
+pub struct MaxMonthlySpendReachedError {
+    pub max_monthly_spend: f64,
+    pub current_spend: f64,
+}
+
+impl fmt::Display for MaxMonthlySpendReachedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Maximum monthly spend of ${:.2} reached (${:.2} spent this month).",
+            self.max_monthly_spend, self.current_spend
+        )
+    }
+}
+
+/// The amount spent on language models during a calendar month.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MonthlySpend {
+    pub year: i32,
+    pub month: u32,
+    /// Total cost in US dollars.
+    pub total: f64,
+}
+
+impl MonthlySpend {
+    fn current_month() -> (i32, u32) {
+        let now = Local::now();
+        (now.year(), now.month())
+    }
+
+    fn is_current(&self) -> bool {
+        (self.year, self.month) == Self::current_month()
+    }
+}
+
+/// Tracks the cost of language model requests across all providers, based on
+/// the usage they report and the pricing of their models.
+#[derive(Default)]
+pub struct SpendTracker {
+    spend: MonthlySpend,
+}
+
+impl Global for SpendTracker {}
+
+impl SpendTracker {
+    pub fn init(cx: &mut App) {
+        cx.set_global(SpendTracker::default());
+        cx.spawn(async move |cx| {
+            let spend = cx
+                .background_spawn(async move {
+                    let contents = std::fs::read_to_string(paths::language_model_spend_file())?;
+                    anyhow::Ok(serde_json::from_str::<MonthlySpend>(&contents)?)
+                })
+                .await;
+            if let Ok(spend) = spend {
+                cx.update_global::<Self, _>(|tracker, _| {
+                    // Don't clobber costs recorded while the file was being read.
+                    if tracker.spend.is_current() && spend.is_current() {
+                        tracker.spend.total += spend.total;
+                    } else if spend.is_current() {
+                        tracker.spend = spend;
+                    }
+                })
+                .log_err();
+            }
+        })
+        .detach();
+    }
+
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn set_current_spend(total: f64, cx: &mut App) {
+        let (year, month) = MonthlySpend::current_month();
+        cx.set_global(SpendTracker {
+            spend: MonthlySpend { year, month, total },
+        });
+    }
+
+    /// Returns the amount spent in the current month, in US dollars.
+    pub fn current_spend(cx: &App) -> f64 {
+        cx.try_global::<Self>()
+            .filter(|tracker| tracker.spend.is_current())
+            .map_or(0., |tracker| tracker.spend.total)
+    }
+
+    /// Returns an error if the current month's spend has reached the given
+    /// maximum, unless the model is free to use.
+    pub fn check(
+        model: &dyn LanguageModel,
+        max_monthly_spend: Option<f64>,
+        cx: &App,
+    ) -> Result<(), MaxMonthlySpendReachedError> {
+        let Some(max_monthly_spend) = max_monthly_spend else {
+            return Ok(());
+        };
+        if model.pricing().is_some_and(|pricing| pricing.is_free()) {
+            return Ok(());
+        }
+        let current_spend = Self::current_spend(cx);
+        if current_spend >= max_monthly_spend {
+            Err(MaxMonthlySpendReachedError {
+                max_monthly_spend,
+                current_spend,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Records the cost of a request, in US dollars.
+    pub fn record(cost: f64, cx: &mut App) {
+        if cost <= 0. || !cx.has_global::<Self>() {
+            return;
+        }
+
+        let spend = cx.update_global::<Self, _>(|tracker, _| {
+            if !tracker.spend.is_current() {
+                let (year, month) = MonthlySpend::current_month();
+                tracker.spend = MonthlySpend {
+                    year,
+                    month,
+                    total: 0.,
+                };
+            }
+            tracker.spend.total += cost;
+            tracker.spend
+        });
+
+        cx.background_spawn(async move {
+            let path = paths::language_model_spend_file();
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, serde_json::to_string(&spend)?)?;
+            anyhow::Ok(())
+        })
+        .detach_and_log_err(cx);
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::Result;
+use gpui::{App, Context, Entity, EntityId, Task};
+use inline_completion::{
+    Direction, EditPredictionProvider, InlineCompletion, trim_to_end_of_line_unless_leading_newline,
+};
+use language::{
+    Anchor, Buffer, OffsetRangeExt as _, ToOffset,
+    language_settings::{AllLanguageSettings, LanguageModelEditPredictionSettings},
+};
+use language_model::{FillInTheMiddleRequest, LanguageModel, LanguageModelRegistry};
+use project::Project;
+use settings::Settings;
+use std::{sync::Arc, time::Duration};
+use text::Bias;
+
+pub const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(150);
+
+/// The number of bytes of context sent before and after the cursor.
+const MAX_PREFIX_LEN: usize = 8 * 1024;
+const MAX_SUFFIX_LEN: usize = 2 * 1024;
+const DEFAULT_MAX_TOKENS: u32 = 128;
+
+struct CurrentCompletion {
+    buffer_id: EntityId,
+    position: Anchor,
+    text: String,
+}
+
+/// Supplies edit predictions by requesting fill-in-the-middle completions from
+/// a language model configured in one of the language model providers.
+#[derive(Default)]
+pub struct LanguageModelCompletionProvider {
+    completion: Option<CurrentCompletion>,
+    pending_refresh: Option<Task<Result<()>>>,
+}
+
+impl LanguageModelCompletionProvider {
+    /// Returns the model that should be used for completions, according to
+    /// the `edit_predictions.language_model` settings.
+    pub fn model(cx: &App) -> Option<Arc<dyn LanguageModel>> {
+        let settings = Self::settings(cx);
+        LanguageModelRegistry::read_global(cx).fill_in_the_middle_model(
+            settings.provider.as_deref(),
+            settings.model.as_deref(),
+            cx,
+        )
+    }
+
+    fn settings(cx: &App) -> &LanguageModelEditPredictionSettings {
+        &AllLanguageSettings::get_global(cx)
+            .edit_predictions
+            .language_model
+    }
+}
+
+impl EditPredictionProvider for LanguageModelCompletionProvider {
+    fn name() -> &'static str {
+        "language-model"
+    }
+
+    fn display_name() -> &'static str {
+        "Language Model"
+    }
+
+    fn show_completions_in_menu() -> bool {
+        false
+    }
+
+    fn is_enabled(&self, _buffer: &Entity<Buffer>, _cursor_position: Anchor, cx: &App) -> bool {
+        Self::model(cx).is_some()
+    }
+
+    fn is_refreshing(&self) -> bool {
+        self.pending_refresh.is_some()
+    }
+
+    fn refresh(
+        &mut self,
+        _project: Option<Entity<Project>>,
+        buffer_handle: Entity<Buffer>,
+        cursor_position: Anchor,
+        debounce: bool,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(model) = Self::model(cx) else {
+            return;
+        };
+
+        let snapshot = buffer_handle.read(cx).snapshot();
+        let cursor_offset = cursor_position.to_offset(&snapshot);
+        let prefix_start =
+            snapshot.clip_offset(cursor_offset.saturating_sub(MAX_PREFIX_LEN), Bias::Right);
+        let suffix_end = snapshot.clip_offset(
+            (cursor_offset + MAX_SUFFIX_LEN).min(snapshot.len()),
+            Bias::Left,
+        );
+        let request = FillInTheMiddleRequest {
+            prefix: snapshot
+                .text_for_range(prefix_start..cursor_offset)
+                .collect(),
+            suffix: snapshot.text_for_range(cursor_offset..suffix_end).collect(),
+            max_tokens: Some(Self::settings(cx).max_tokens.unwrap_or(DEFAULT_MAX_TOKENS)),
+            stop: vec!["\n\n".into()],
+            temperature: None,
+        };
+
+        // Replacing the pending task drops it, cancelling any request in flight.
+        self.pending_refresh = Some(cx.spawn(async move |this, cx| {
+            if debounce {
+                cx.background_executor().timer(DEBOUNCE_TIMEOUT).await;
+            }
+
+            let completion = model.fill_in_the_middle(request, cx).await;
+
+            this.update(cx, |this, cx| {
+                this.pending_refresh = None;
+                if let Ok(text) = &completion {
+                    this.completion = Some(CurrentCompletion {
+                        buffer_id: buffer_handle.entity_id(),
+                        position: cursor_position,
+                        text: text.clone(),
+                    });
+                    cx.notify();
+                }
+            })?;
+
+            completion.map(|_| ())
+        }));
+    }
+
+    fn cycle(
+        &mut self,
+        _buffer: Entity<Buffer>,
+        _cursor_position: Anchor,
+        _direction: Direction,
+        _cx: &mut Context<Self>,
+    ) {
+    }
+
+    fn accept(&mut self, _cx: &mut Context<Self>) {
+        self.pending_refresh = None;
+        self.completion = None;
+    }
+
+    fn discard(&mut self, _cx: &mut Context<Self>) {
+        self.pending_refresh = None;
+        self.completion = None;
+    }
+
+    fn suggest(
+        &mut self,
+        buffer: &Entity<Buffer>,
+        cursor_position: Anchor,
+        cx: &mut Context<Self>,
+    ) -> Option<InlineCompletion> {
+        let completion = self.completion.as_ref()?;
+        if completion.buffer_id != buffer.entity_id() {
+            return None;
+        }
+
+        // Keep suggesting the completion while the user types text that matches it.
+        let snapshot = buffer.read(cx).snapshot();
+        let typed_range = (completion.position..cursor_position).to_offset(&snapshot);
+        if typed_range.start > typed_range.end {
+            return None;
+        }
+        let typed_text = snapshot.text_for_range(typed_range).collect::<String>();
+        let completion_text = completion.text.strip_prefix(typed_text.as_str())?;
+
+        let completion_text = trim_to_end_of_line_unless_leading_newline(completion_text);
+        let completion_text = completion_text.trim_end();
+        if completion_text.trim().is_empty() {
+            return None;
+        }
+
+        let position = snapshot.anchor_after(cursor_position);
+        Some(InlineCompletion {
+            id: None,
+            edits: vec![(position..position, completion_text.to_string())],
+            edit_preview: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::{AppContext as _, TestAppContext};
+
+    fn suggested_edits(
+        provider: &Entity<LanguageModelCompletionProvider>,
+        buffer: &Entity<Buffer>,
+        cursor_offset: usize,
+        cx: &mut TestAppContext,
+    ) -> Option<Vec<(std::ops::Range<usize>, String)>> {
+        let cursor_position = buffer.read_with(cx, |buffer, _| buffer.anchor_after(cursor_offset));
+        let completion = provider.update(cx, |provider, cx| {
+            provider.suggest(buffer, cursor_position, cx)
+        })?;
+        let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot());
+        Some(
+            completion
+                .edits
+                .into_iter()
+                .map(|(range, text)| (range.to_offset(&snapshot), text))
+                .collect(),
+        )
+    }
+
+    #[gpui::test]
+    fn test_suggest(cx: &mut TestAppContext) {
+        let buffer = cx.new(|cx| Buffer::local("fn main() {\n    let x = \n}", cx));
+        let other_buffer = cx.new(|cx| Buffer::local("", cx));
+        let provider = cx.new(|_| LanguageModelCompletionProvider::default());
+        let cursor_offset = "fn main() {\n    let x = ".len();
+        let set_completion = |text: &str, cx: &mut TestAppContext| {
+            let position = buffer.read_with(cx, |buffer, _| buffer.anchor_before(cursor_offset));
+            provider.update(cx, |provider, _| {
+                provider.completion = Some(CurrentCompletion {
+                    buffer_id: buffer.entity_id(),
+                    position,
+                    text: text.to_string(),
+                });
+            });
+        };
+
+        // Only the rest of the cursor's line is suggested.
+        set_completion("42;  \n    println!(\"{x}\");", cx);
+        assert_eq!(
+            suggested_edits(&provider, &buffer, cursor_offset, cx),
+            Some(vec![(cursor_offset..cursor_offset, "42;".to_string())])
+        );
+        assert_eq!(suggested_edits(&provider, &other_buffer, 0, cx), None);
+
+        // The completion keeps being suggested while the user types it.
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit([(cursor_offset..cursor_offset, "4")], None, cx)
+        });
+        assert_eq!(
+            suggested_edits(&provider, &buffer, cursor_offset + 1, cx),
+            Some(vec![(
+                cursor_offset + 1..cursor_offset + 1,
+                "2;".to_string()
+            )])
+        );
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit([(cursor_offset + 1..cursor_offset + 1, "7")], None, cx)
+        });
+        assert_eq!(
+            suggested_edits(&provider, &buffer, cursor_offset + 2, cx),
+            None
+        );
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit([(cursor_offset..cursor_offset + 2, "")], None, cx)
+        });
+
+        // Completions starting on a new line are suggested in full.
+        set_completion("\n    println!(\"{x}\");\n", cx);
+        assert_eq!(
+            suggested_edits(&provider, &buffer, cursor_offset, cx),
+            Some(vec![(
+                cursor_offset..cursor_offset,
+                "\n    println!(\"{x}\");".to_string()
+            )])
+        );
+
+        // Blank completions aren't suggested.
+        set_completion("   \n", cx);
+        assert_eq!(suggested_edits(&provider, &buffer, cursor_offset, cx), None);
+
+        provider.update(cx, |provider, cx| provider.accept(cx));
+        assert_eq!(suggested_edits(&provider, &buffer, cursor_offset, cx), None);
+    }
+}
@@ -7,7 +7,11 @@ use language_model::LanguageModelRegistry;
 use provider::deepseek::DeepSeekLanguageModelProvider;
 
 pub mod provider;
+mod provider_http;
 mod settings;
+#[cfg(test)]
+mod stream_tests;
+pub mod tool_call_format;
 pub mod ui;
 
 use crate::provider::anthropic::AnthropicLanguageModelProvider;
@@ -1,8 +1,9 @@
 use crate::AllLanguageModelSettings;
+use crate::provider_http::provider_http_client;
+use crate::tool_call_format::{AnthropicToolFormat, ToolCallFormat, parse_tool_input};
 use crate::ui::InstructionListItem;
 use anthropic::{
-    AnthropicError, AnthropicModelMode, ContentDelta, Event, ResponseContent, ToolResultContent,
-    ToolResultPart, Usage,
+    AnthropicError, AnthropicModelMode, Citation, ContentDelta, Event, ResponseContent, Usage,
 };
 use anyhow::{Context as _, Result, anyhow};
 use collections::{BTreeMap, HashMap};
@@ -16,12 +17,13 @@ use gpui::{
 use http_client::HttpClient;
 use language_model::{
     AuthenticateError, LanguageModel, LanguageModelCacheConfiguration,
-    LanguageModelCompletionError, LanguageModelId, LanguageModelKnownError, LanguageModelName,
-    LanguageModelProvider, LanguageModelProviderId, LanguageModelProviderName,
-    LanguageModelProviderState, LanguageModelRequest, LanguageModelToolChoice,
-    LanguageModelToolResultContent, MessageContent, RateLimiter, Role,
+    LanguageModelCompletionError, LanguageModelError, LanguageModelId, LanguageModelName,
+    LanguageModelPricing, LanguageModelProvider, LanguageModelProviderId,
+    LanguageModelProviderName, LanguageModelProviderState, LanguageModelRequest,
+    LanguageModelToolChoice, LanguageModelToolResultContent, MessageContent, RateLimiter,
+    RequestTimeouts, Role,
 };
-use language_model::{LanguageModelCompletionEvent, LanguageModelToolUse, StopReason};
+use language_model::{LanguageModelCompletionEvent, LanguageModelToolUse, StopReason, WebSource};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::{Settings, SettingsStore};
@@ -187,6 +189,7 @@ impl State {
 
 impl AnthropicLanguageModelProvider {
     pub fn new(http_client: Arc<dyn HttpClient>, cx: &mut App) -> Self {
+        let http_client = provider_http_client(http_client, PROVIDER_NAME);
         let state = cx.new(|cx| State {
             api_key: None,
             api_key_from_env: false,
@@ -204,7 +207,8 @@ impl AnthropicLanguageModelProvider {
             model,
             state: self.state.clone(),
             http_client: self.http_client.clone(),
-            request_limiter: RateLimiter::new(4),
+            request_limiter: RateLimiter::new(4)
+                .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
         })
     }
 }
@@ -346,6 +350,9 @@ pub fn count_anthropic_tokens(
                     MessageContent::Image(image) => {
                         tokens_from_images += image.estimate_tokens();
                     }
+                    MessageContent::Document(document) => {
+                        tokens_from_images += document.estimate_tokens();
+                    }
                     MessageContent::ToolUse(_tool_use) => {
                         // TODO: Estimate token usage from tool uses.
                     }
@@ -433,6 +440,14 @@ impl LanguageModel for AnthropicModel {
         true
     }
 
+    fn supports_documents(&self) -> bool {
+        self.model.supports_documents()
+    }
+
+    fn supports_web_search(&self) -> bool {
+        self.model.supports_web_search()
+    }
+
     fn supports_tool_choice(&self, choice: LanguageModelToolChoice) -> bool {
         match choice {
             LanguageModelToolChoice::Auto
@@ -457,6 +472,29 @@ impl LanguageModel for AnthropicModel {
         Some(self.model.max_output_tokens())
     }
 
+    /// Anthropic's list prices. Cached input tokens are counted at the
+    /// regular input price, which overestimates the cost of cache reads.
+    fn pricing(&self) -> Option<LanguageModelPricing> {
+        let (input, output) = match self.model {
+            anthropic::Model::ClaudeOpus4
+            | anthropic::Model::ClaudeOpus4Thinking
+            | anthropic::Model::Claude3Opus => (15., 75.),
+            anthropic::Model::ClaudeSonnet4
+            | anthropic::Model::ClaudeSonnet4Thinking
+            | anthropic::Model::Claude3_7Sonnet
+            | anthropic::Model::Claude3_7SonnetThinking
+            | anthropic::Model::Claude3_5Sonnet
+            | anthropic::Model::Claude3Sonnet => (3., 15.),
+            anthropic::Model::Claude3_5Haiku => (0.8, 4.),
+            anthropic::Model::Claude3Haiku => (0.25, 1.25),
+            anthropic::Model::Custom { .. } => return None,
+        };
+        Some(LanguageModelPricing {
+            input_cost_per_million_tokens: input,
+            output_cost_per_million_tokens: output,
+        })
+    }
+
     fn count_tokens(
         &self,
         request: LanguageModelRequest,
@@ -475,6 +513,8 @@ impl LanguageModel for AnthropicModel {
             BoxStream<'static, Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
         >,
     > {
+        let priority = request.priority;
+        let timeouts = RequestTimeouts::for_request(&self.provider_id(), &request);
         let request = into_anthropic(
             request,
             self.model.request_id().into(),
@@ -483,16 +523,20 @@ impl LanguageModel for AnthropicModel {
             self.model.mode(),
         );
         let request = self.stream_completion(request, cx);
-        let future = self.request_limiter.stream(async move {
-            let response = request
-                .await
-                .map_err(|err| match err.downcast::<AnthropicError>() {
-                    Ok(anthropic_err) => anthropic_err_to_anyhow(anthropic_err),
-                    Err(err) => anyhow!(err),
-                })?;
-            Ok(AnthropicEventMapper::new().map_stream(response))
-        });
-        async move { Ok(future.await?.boxed()) }.boxed()
+        let future = self.request_limiter.stream(
+            priority,
+            timeouts.apply(async move {
+                let response =
+                    request
+                        .await
+                        .map_err(|err| match err.downcast::<AnthropicError>() {
+                            Ok(anthropic_err) => anthropic_err_to_anyhow(anthropic_err),
+                            Err(err) => anyhow!(err),
+                        })?;
+                Ok(AnthropicEventMapper::new().map_stream(response))
+            }),
+        );
+        async move { Ok(future.await.map_err(LanguageModelError::classify)?.boxed()) }.boxed()
     }
 
     fn cache_configuration(&self) -> Option<LanguageModelCacheConfiguration> {
@@ -568,34 +612,22 @@ pub fn into_anthropic(
                             },
                             cache_control: None,
                         }),
-                        MessageContent::ToolUse(tool_use) => {
-                            Some(anthropic::RequestContent::ToolUse {
-                                id: tool_use.id.to_string(),
-                                name: tool_use.name.to_string(),
-                                input: tool_use.input,
+                        MessageContent::Document(document) => {
+                            Some(anthropic::RequestContent::Document {
+                                source: anthropic::DocumentSource {
+                                    source_type: "base64".to_string(),
+                                    media_type: document.media_type.to_string(),
+                                    data: document.source.to_string(),
+                                },
+                                title: Some(document.name.to_string()),
                                 cache_control: None,
                             })
                         }
+                        MessageContent::ToolUse(tool_use) => {
+                            Some(AnthropicToolFormat::to_tool_call(&tool_use))
+                        }
                         MessageContent::ToolResult(tool_result) => {
-                            Some(anthropic::RequestContent::ToolResult {
-                                tool_use_id: tool_result.tool_use_id.to_string(),
-                                is_error: tool_result.is_error,
-                                content: match tool_result.content {
-                                    LanguageModelToolResultContent::Text(text) => {
-                                        ToolResultContent::Plain(text.to_string())
-                                    }
-                                    LanguageModelToolResultContent::Image(image) => {
-                                        ToolResultContent::Multipart(vec![ToolResultPart::Image {
-                                            source: anthropic::ImageSource {
-                                                source_type: "base64".to_string(),
-                                                media_type: "image/png".to_string(),
-                                                data: image.source.to_string(),
-                                            },
-                                        }])
-                                    }
-                                },
-                                cache_control: None,
-                            })
+                            Some(AnthropicToolFormat::to_tool_result(&tool_result))
                         }
                     })
                     .collect();
@@ -624,6 +656,7 @@ pub fn into_anthropic(
                             anthropic::RequestContent::Text { cache_control, .. }
                             | anthropic::RequestContent::Thinking { cache_control, .. }
                             | anthropic::RequestContent::Image { cache_control, .. }
+                            | anthropic::RequestContent::Document { cache_control, .. }
                             | anthropic::RequestContent::ToolUse { cache_control, .. }
                             | anthropic::RequestContent::ToolResult { cache_control, .. } => {
                                 *cache_control = cache_control_value;
@@ -664,11 +697,12 @@ pub fn into_anthropic(
         tools: request
             .tools
             .into_iter()
-            .map(|tool| anthropic::Tool {
-                name: tool.name,
-                description: tool.description,
-                input_schema: tool.input_schema,
-            })
+            .map(|tool| AnthropicToolFormat::to_tool_definition(tool).into())
+            .chain(
+                request
+                    .web_search
+                    .then(|| anthropic::ToolDefinition::WebSearch(Default::default())),
+            )
             .collect(),
         tool_choice: request.tool_choice.map(|choice| match choice {
             LanguageModelToolChoice::Auto => anthropic::ToolChoice::Auto,
@@ -745,6 +779,8 @@ impl AnthropicEventMapper {
                     );
                     Vec::new()
                 }
+                ResponseContent::ServerToolUse { .. }
+                | ResponseContent::WebSearchToolResult { .. } => Vec::new(),
             },
             Event::ContentBlockDelta { index, delta } => match delta {
                 ContentDelta::TextDelta { text } => {
@@ -786,34 +822,25 @@ impl AnthropicEventMapper {
                     }
                     return vec![];
                 }
+                ContentDelta::CitationsDelta { citation } => match citation {
+                    Citation::WebSearchResultLocation { url, title, .. } => {
+                        vec![Ok(LanguageModelCompletionEvent::WebSources(vec![
+                            WebSource { url, title },
+                        ]))]
+                    }
+                    Citation::Other => Vec::new(),
+                },
             },
             Event::ContentBlockStop { index } => {
                 if let Some(tool_use) = self.tool_uses_by_index.remove(&index) {
-                    let input_json = tool_use.input_json.trim();
-                    let input_value = if input_json.is_empty() {
-                        Ok(serde_json::Value::Object(serde_json::Map::default()))
-                    } else {
-                        serde_json::Value::from_str(input_json)
-                    };
-                    let event_result = match input_value {
-                        Ok(input) => Ok(LanguageModelCompletionEvent::ToolUse(
-                            LanguageModelToolUse {
-                                id: tool_use.id.into(),
-                                name: tool_use.name.into(),
-                                is_input_complete: true,
-                                input,
-                                raw_input: tool_use.input_json.clone(),
-                            },
-                        )),
-                        Err(json_parse_err) => Err(LanguageModelCompletionError::BadInputJson {
-                            id: tool_use.id.into(),
-                            tool_name: tool_use.name.into(),
-                            raw_input: input_json.into(),
-                            json_parse_error: json_parse_err.to_string(),
-                        }),
-                    };
-
-                    vec![event_result]
+                    vec![
+                        parse_tool_input(
+                            tool_use.id.into(),
+                            tool_use.name.into(),
+                            tool_use.input_json,
+                        )
+                        .map(LanguageModelCompletionEvent::ToolUse),
+                    ]
                 } else {
                     Vec::new()
                 }
@@ -837,6 +864,9 @@ impl AnthropicEventMapper {
                         "max_tokens" => StopReason::MaxTokens,
                         "tool_use" => StopReason::ToolUse,
                         "refusal" => StopReason::Refusal,
+                        // The API paused a long-running turn, such as one
+                        // searching the web, which ends the response.
+                        "pause_turn" => StopReason::EndTurn,
                         _ => {
                             log::error!("Unexpected anthropic stop_reason: {stop_reason}");
                             StopReason::EndTurn
@@ -869,7 +899,9 @@ struct RawToolUse {
 pub fn anthropic_err_to_anyhow(err: AnthropicError) -> anyhow::Error {
     if let AnthropicError::ApiError(api_err) = &err {
         if let Some(tokens) = api_err.match_window_exceeded() {
-            return anyhow!(LanguageModelKnownError::ContextWindowLimitExceeded { tokens });
+            return anyhow!(LanguageModelError::ContextWindowLimitExceeded {
+                tokens: Some(tokens)
+            });
         }
     }
 
@@ -1089,7 +1121,9 @@ impl Render for ConfigurationView {
 mod tests {
     use super::*;
     use anthropic::AnthropicModelMode;
-    use language_model::{LanguageModelRequestMessage, MessageContent};
+    use language_model::{
+        LanguageModelRequestMessage, MessageContent, RequestPriority, SpendTracker,
+    };
 
     #[test]
     fn test_cache_control_only_on_last_segment() {
@@ -1111,8 +1145,11 @@ mod tests {
             mode: None,
             stop: vec![],
             temperature: None,
+            seed: None,
+            priority: RequestPriority::Interactive,
             tools: vec![],
             tool_choice: None,
+            web_search: false,
         };
 
         let anthropic_request = into_anthropic(
@@ -1155,4 +1192,91 @@ mod tests {
             }
         ));
     }
+
+    #[test]
+    fn test_web_search() {
+        let request = LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![MessageContent::Text("What's new in Rust?".to_string())],
+                cache: false,
+            }],
+            web_search: true,
+            ..Default::default()
+        };
+        let anthropic_request = into_anthropic(
+            request,
+            "claude-sonnet-4".to_string(),
+            1.0,
+            4096,
+            AnthropicModelMode::Default,
+        );
+        assert_eq!(
+            serde_json::to_value(&anthropic_request.tools).unwrap(),
+            serde_json::json!([{
+                "type": "web_search_20250305",
+                "name": "web_search",
+                "max_uses": 5
+            }])
+        );
+
+        let mut mapper = AnthropicEventMapper::new();
+        let events = [
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"server_tool_use","id":"srvtoolu_1","name":"web_search","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":"{\"query\": \"rust\"}"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"web_search_tool_result","tool_use_id":"srvtoolu_1","content":[]}}"#,
+            r#"{"type":"content_block_start","index":2,"content_block":{"type":"text","text":"","citations":[]}}"#,
+            r#"{"type":"content_block_delta","index":2,"delta":{"type":"citations_delta","citation":{"type":"web_search_result_location","url":"https://blog.rust-lang.org","title":"Rust Blog","cited_text":"Rust 1.88","encrypted_index":"abc"}}}"#,
+            r#"{"type":"content_block_delta","index":2,"delta":{"type":"text_delta","text":"Rust 1.88 shipped."}}"#,
+        ]
+        .into_iter()
+        .flat_map(|event| mapper.map_event(serde_json::from_str(event).unwrap()))
+        .map(|event| event.unwrap())
+        .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                LanguageModelCompletionEvent::Text(String::new()),
+                LanguageModelCompletionEvent::WebSources(vec![WebSource {
+                    url: "https://blog.rust-lang.org".into(),
+                    title: Some("Rust Blog".into()),
+                }]),
+                LanguageModelCompletionEvent::Text("Rust 1.88 shipped.".into()),
+            ]
+        );
+    }
+
+    #[gpui::test]
+    fn test_priced_models_are_subject_to_the_spend_cap(cx: &mut App) {
+        let provider = AnthropicLanguageModelProvider::new(
+            http_client::FakeHttpClient::with_404_response(),
+            cx,
+        );
+        let model = provider.create_language_model(anthropic::Model::ClaudeSonnet4);
+        let pricing = model.pricing().unwrap();
+        assert!(!pricing.is_free());
+
+        SpendTracker::set_current_spend(9.5, cx);
+        assert!(SpendTracker::check(model.as_ref(), Some(10.), cx).is_ok());
+
+        SpendTracker::set_current_spend(10., cx);
+        let error = SpendTracker::check(model.as_ref(), Some(10.), cx).unwrap_err();
+        assert_eq!(error.max_monthly_spend, 10.);
+        assert_eq!(error.current_spend, 10.);
+        assert!(SpendTracker::check(model.as_ref(), None, cx).is_ok());
+
+        let custom_model = provider.create_language_model(anthropic::Model::Custom {
+            name: "claude-custom".into(),
+            max_tokens: 200_000,
+            display_name: None,
+            tool_override: None,
+            cache_configuration: None,
+            max_output_tokens: None,
+            default_temperature: None,
+            extra_beta_headers: Vec::new(),
+            mode: AnthropicModelMode::Default,
+        });
+        assert_eq!(custom_model.pricing(), None);
+    }
 }
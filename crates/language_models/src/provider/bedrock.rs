@@ -33,11 +33,11 @@ use gpui_tokio::Tokio;
 use http_client::HttpClient;
 use language_model::{
     AuthenticateError, LanguageModel, LanguageModelCacheConfiguration,
-    LanguageModelCompletionError, LanguageModelCompletionEvent, LanguageModelId, LanguageModelName,
-    LanguageModelProvider, LanguageModelProviderId, LanguageModelProviderName,
-    LanguageModelProviderState, LanguageModelRequest, LanguageModelToolChoice,
-    LanguageModelToolResultContent, LanguageModelToolUse, MessageContent, RateLimiter, Role,
-    TokenUsage,
+    LanguageModelCompletionError, LanguageModelCompletionEvent, LanguageModelError,
+    LanguageModelId, LanguageModelName, LanguageModelProvider, LanguageModelProviderId,
+    LanguageModelProviderName, LanguageModelProviderState, LanguageModelRequest,
+    LanguageModelToolChoice, LanguageModelToolResultContent, LanguageModelToolUse, MessageContent,
+    RateLimiter, RequestTimeouts, Role, TokenUsage,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -267,7 +267,8 @@ impl BedrockLanguageModelProvider {
             handler: self.handler.clone(),
             state: self.state.clone(),
             client: OnceCell::new(),
-            request_limiter: RateLimiter::new(4),
+            request_limiter: RateLimiter::new(4)
+                .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
         })
     }
 }
@@ -552,6 +553,9 @@ impl LanguageModel for BedrockModel {
             }
         };
 
+        let priority = request.priority;
+        let timeouts = RequestTimeouts::for_request(&self.provider_id(), &request);
+
         let request = match into_bedrock(
             request,
             model_id,
@@ -566,14 +570,17 @@ impl LanguageModel for BedrockModel {
         let owned_handle = self.handler.clone();
 
         let request = self.stream_completion(request, cx);
-        let future = self.request_limiter.stream(async move {
-            let response = request.map_err(|err| anyhow!(err))?.await;
-            Ok(map_to_language_model_completion_events(
-                response,
-                owned_handle,
-            ))
-        });
-        async move { Ok(future.await?.boxed()) }.boxed()
+        let future = self.request_limiter.stream(
+            priority,
+            timeouts.apply(async move {
+                let response = request.map_err(|err| anyhow!(err))?.await;
+                Ok(map_to_language_model_completion_events(
+                    response,
+                    owned_handle,
+                ))
+            }),
+        );
+        async move { Ok(future.await.map_err(LanguageModelError::classify)?.boxed()) }.boxed()
     }
 
     fn cache_configuration(&self) -> Option<LanguageModelCacheConfiguration> {
@@ -770,6 +777,9 @@ pub fn get_bedrock_tokens(
                         MessageContent::Image(image) => {
                             tokens_from_images += image.estimate_tokens();
                         }
+                        MessageContent::Document(document) => {
+                            tokens_from_images += document.estimate_tokens();
+                        }
                         MessageContent::ToolUse(_tool_use) => {
                             // TODO: Estimate token usage from tool uses.
                         }
@@ -963,7 +973,7 @@ pub fn map_to_language_model_completion_events(
                                         let reason = match message_stop.stop_reason {
                                             StopReason::ContentFiltered => {
                                                 LanguageModelCompletionEvent::Stop(
-                                                    language_model::StopReason::EndTurn,
+                                                    language_model::StopReason::Refusal,
                                                 )
                                             }
                                             StopReason::EndTurn => {
@@ -973,12 +983,12 @@ pub fn map_to_language_model_completion_events(
                                             }
                                             StopReason::GuardrailIntervened => {
                                                 LanguageModelCompletionEvent::Stop(
-                                                    language_model::StopReason::EndTurn,
+                                                    language_model::StopReason::Refusal,
                                                 )
                                             }
                                             StopReason::MaxTokens => {
                                                 LanguageModelCompletionEvent::Stop(
-                                                    language_model::StopReason::EndTurn,
+                                                    language_model::StopReason::MaxTokens,
                                                 )
                                             }
                                             StopReason::StopSequence => {
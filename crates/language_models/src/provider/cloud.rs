@@ -8,14 +8,16 @@ use google_ai::GoogleModelMode;
 use gpui::{
     AnyElement, AnyView, App, AsyncApp, Context, Entity, SemanticVersion, Subscription, Task,
 };
-use http_client::{AsyncBody, HttpClient, Method, Response, StatusCode};
+use http_client::{
+    AsyncBody, HttpClient, Method, Response, StatusCode, middleware::HttpClientWithMiddleware,
+};
 use language_model::{
     AuthenticateError, LanguageModel, LanguageModelCacheConfiguration,
-    LanguageModelCompletionError, LanguageModelId, LanguageModelKnownError, LanguageModelName,
+    LanguageModelCompletionError, LanguageModelError, LanguageModelId, LanguageModelName,
     LanguageModelProviderId, LanguageModelProviderName, LanguageModelProviderState,
     LanguageModelProviderTosView, LanguageModelRequest, LanguageModelToolChoice,
-    LanguageModelToolSchemaFormat, ModelRequestLimitReachedError, RateLimiter, RequestUsage,
-    ZED_CLOUD_PROVIDER_ID,
+    LanguageModelToolSchemaFormat, ModelRequestLimitReachedError, RateLimiter, RequestTimeouts,
+    RequestUsage, ZED_CLOUD_PROVIDER_ID,
 };
 use language_model::{
     LanguageModelCompletionEvent, LanguageModelProvider, LlmApiToken, PaymentRequiredError,
@@ -26,7 +28,6 @@ use release_channel::AppVersion;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use settings::SettingsStore;
-use smol::Timer;
 use smol::io::{AsyncReadExt, BufReader};
 use std::pin::Pin;
 use std::str::FromStr as _;
@@ -46,6 +47,7 @@ use zed_llm_client::{
 use crate::provider::anthropic::{AnthropicEventMapper, count_anthropic_tokens, into_anthropic};
 use crate::provider::google::{GoogleEventMapper, into_google};
 use crate::provider::open_ai::{OpenAiEventMapper, count_open_ai_tokens, into_open_ai};
+use crate::provider_http::Retry;
 
 pub const PROVIDER_NAME: &str = "Zed";
 
@@ -336,7 +338,8 @@ impl CloudLanguageModelProvider {
             model,
             llm_api_token: llm_api_token.clone(),
             client: self.client.clone(),
-            request_limiter: RateLimiter::new(4),
+            request_limiter: RateLimiter::new(4)
+                .for_provider(LanguageModelProviderId(ZED_CLOUD_PROVIDER_ID.into())),
         })
     }
 }
@@ -545,10 +548,11 @@ impl CloudLanguageModel {
         body: CompletionBody,
     ) -> Result<PerformLlmCompletionResponse> {
         let http_client = &client.http_client();
+        let retrying_http_client = HttpClientWithMiddleware::new(http_client.clone())
+            .with(Retry::new(Self::MAX_RETRIES, Duration::from_secs(1)));
 
         let mut token = llm_api_token.acquire(&client).await?;
         let mut retries_remaining = Self::MAX_RETRIES;
-        let mut retry_delay = Duration::from_secs(1);
 
         loop {
             let request_builder = http_client::Request::builder()
@@ -565,7 +569,7 @@ impl CloudLanguageModel {
                 .header("Authorization", format!("Bearer {token}"))
                 .header(CLIENT_SUPPORTS_STATUS_MESSAGES_HEADER_NAME, "true")
                 .body(serde_json::to_string(&body)?.into())?;
-            let mut response = http_client.send(request).await?;
+            let mut response = retrying_http_client.send(request).await?;
             let status = response.status();
             if status.is_success() {
                 let includes_status_messages = response
@@ -595,6 +599,11 @@ impl CloudLanguageModel {
                 .get(EXPIRED_LLM_TOKEN_HEADER_NAME)
                 .is_some()
             {
+                anyhow::ensure!(
+                    retries_remaining > 0,
+                    "cloud language model token expired after {} refreshes",
+                    Self::MAX_RETRIES
+                );
                 retries_remaining -= 1;
                 token = llm_api_token.refresh(&client).await?;
             } else if status == StatusCode::FORBIDDEN
@@ -624,26 +633,6 @@ impl CloudLanguageModel {
                 }
 
                 anyhow::bail!("Forbidden");
-            } else if status.as_u16() >= 500 && status.as_u16() < 600 {
-                // If we encounter an error in the 500 range, retry after a delay.
-                // We've seen at least these in the wild from API providers:
-                // * 500 Internal Server Error
-                // * 502 Bad Gateway
-                // * 529 Service Overloaded
-
-                if retries_remaining == 0 {
-                    let mut body = String::new();
-                    response.body_mut().read_to_string(&mut body).await?;
-                    anyhow::bail!(
-                        "cloud language model completion failed after {} retries with status {status}: {body}",
-                        Self::MAX_RETRIES
-                    );
-                }
-
-                Timer::after(retry_delay).await;
-
-                retries_remaining -= 1;
-                retry_delay *= 2; // If it fails again, wait longer.
             } else if status == StatusCode::PAYMENT_REQUIRED {
                 return Err(anyhow!(PaymentRequiredError));
             } else {
@@ -813,6 +802,8 @@ impl LanguageModel for CloudLanguageModel {
         let prompt_id = request.prompt_id.clone();
         let intent = request.intent;
         let mode = request.mode;
+        let priority = request.priority;
+        let timeouts = RequestTimeouts::for_request(&self.provider_id(), &request);
         let app_version = cx.update(|cx| AppVersion::global(cx)).ok();
         match self.model.provider {
             zed_llm_client::LanguageModelProvider::Anthropic => {
@@ -831,53 +822,58 @@ impl LanguageModel for CloudLanguageModel {
                 );
                 let client = self.client.clone();
                 let llm_api_token = self.llm_api_token.clone();
-                let future = self.request_limiter.stream(async move {
-                    let PerformLlmCompletionResponse {
-                        response,
-                        usage,
-                        includes_status_messages,
-                        tool_use_limit_reached,
-                    } = Self::perform_llm_completion(
-                        client.clone(),
-                        llm_api_token,
-                        app_version,
-                        CompletionBody {
-                            thread_id,
-                            prompt_id,
-                            intent,
-                            mode,
-                            provider: zed_llm_client::LanguageModelProvider::Anthropic,
-                            model: request.model.clone(),
-                            provider_request: serde_json::to_value(&request)?,
-                        },
-                    )
-                    .await
-                    .map_err(|err| match err.downcast::<ApiError>() {
-                        Ok(api_err) => {
-                            if api_err.status == StatusCode::BAD_REQUEST {
-                                if let Some(tokens) = parse_prompt_too_long(&api_err.body) {
-                                    return anyhow!(
-                                        LanguageModelKnownError::ContextWindowLimitExceeded {
-                                            tokens
+                let future = self.request_limiter.stream(
+                    priority,
+                    timeouts.apply(async move {
+                        let PerformLlmCompletionResponse {
+                            response,
+                            usage,
+                            includes_status_messages,
+                            tool_use_limit_reached,
+                        } = Self::perform_llm_completion(
+                            client.clone(),
+                            llm_api_token,
+                            app_version,
+                            CompletionBody {
+                                thread_id,
+                                prompt_id,
+                                intent,
+                                mode,
+                                provider: zed_llm_client::LanguageModelProvider::Anthropic,
+                                model: request.model.clone(),
+                                provider_request: serde_json::to_value(&request)?,
+                            },
+                        )
+                        .await
+                        .map_err(|err| {
+                            match err.downcast::<ApiError>() {
+                                Ok(api_err) => {
+                                    if api_err.status == StatusCode::BAD_REQUEST {
+                                        if let Some(tokens) = parse_prompt_too_long(&api_err.body) {
+                                            return anyhow!(
+                                                LanguageModelError::ContextWindowLimitExceeded {
+                                                    tokens: Some(tokens)
+                                                }
+                                            );
                                         }
-                                    );
+                                    }
+                                    LanguageModelError::classify(anyhow!(api_err))
                                 }
+                                Err(err) => LanguageModelError::classify(err),
                             }
-                            anyhow!(api_err)
-                        }
-                        Err(err) => anyhow!(err),
-                    })?;
-
-                    let mut mapper = AnthropicEventMapper::new();
-                    Ok(map_cloud_completion_events(
-                        Box::pin(
-                            response_lines(response, includes_status_messages)
-                                .chain(usage_updated_event(usage))
-                                .chain(tool_use_limit_reached_event(tool_use_limit_reached)),
-                        ),
-                        move |event| mapper.map_event(event),
-                    ))
-                });
+                        })?;
+
+                        let mut mapper = AnthropicEventMapper::new();
+                        Ok(map_cloud_completion_events(
+                            Box::pin(
+                                response_lines(response, includes_status_messages)
+                                    .chain(usage_updated_event(usage))
+                                    .chain(tool_use_limit_reached_event(tool_use_limit_reached)),
+                            ),
+                            move |event| mapper.map_event(event),
+                        ))
+                    }),
+                );
                 async move { Ok(future.await?.boxed()) }.boxed()
             }
             zed_llm_client::LanguageModelProvider::OpenAi => {
@@ -888,38 +884,41 @@ impl LanguageModel for CloudLanguageModel {
                 };
                 let request = into_open_ai(request, &model, None);
                 let llm_api_token = self.llm_api_token.clone();
-                let future = self.request_limiter.stream(async move {
-                    let PerformLlmCompletionResponse {
-                        response,
-                        usage,
-                        includes_status_messages,
-                        tool_use_limit_reached,
-                    } = Self::perform_llm_completion(
-                        client.clone(),
-                        llm_api_token,
-                        app_version,
-                        CompletionBody {
-                            thread_id,
-                            prompt_id,
-                            intent,
-                            mode,
-                            provider: zed_llm_client::LanguageModelProvider::OpenAi,
-                            model: request.model.clone(),
-                            provider_request: serde_json::to_value(&request)?,
-                        },
-                    )
-                    .await?;
-
-                    let mut mapper = OpenAiEventMapper::new();
-                    Ok(map_cloud_completion_events(
-                        Box::pin(
-                            response_lines(response, includes_status_messages)
-                                .chain(usage_updated_event(usage))
-                                .chain(tool_use_limit_reached_event(tool_use_limit_reached)),
-                        ),
-                        move |event| mapper.map_event(event),
-                    ))
-                });
+                let future = self.request_limiter.stream(
+                    priority,
+                    timeouts.apply(async move {
+                        let PerformLlmCompletionResponse {
+                            response,
+                            usage,
+                            includes_status_messages,
+                            tool_use_limit_reached,
+                        } = Self::perform_llm_completion(
+                            client.clone(),
+                            llm_api_token,
+                            app_version,
+                            CompletionBody {
+                                thread_id,
+                                prompt_id,
+                                intent,
+                                mode,
+                                provider: zed_llm_client::LanguageModelProvider::OpenAi,
+                                model: request.model.clone(),
+                                provider_request: serde_json::to_value(&request)?,
+                            },
+                        )
+                        .await?;
+
+                        let mut mapper = OpenAiEventMapper::new();
+                        Ok(map_cloud_completion_events(
+                            Box::pin(
+                                response_lines(response, includes_status_messages)
+                                    .chain(usage_updated_event(usage))
+                                    .chain(tool_use_limit_reached_event(tool_use_limit_reached)),
+                            ),
+                            move |event| mapper.map_event(event),
+                        ))
+                    }),
+                );
                 async move { Ok(future.await?.boxed()) }.boxed()
             }
             zed_llm_client::LanguageModelProvider::Google => {
@@ -927,38 +926,41 @@ impl LanguageModel for CloudLanguageModel {
                 let request =
                     into_google(request, self.model.id.to_string(), GoogleModelMode::Default);
                 let llm_api_token = self.llm_api_token.clone();
-                let future = self.request_limiter.stream(async move {
-                    let PerformLlmCompletionResponse {
-                        response,
-                        usage,
-                        includes_status_messages,
-                        tool_use_limit_reached,
-                    } = Self::perform_llm_completion(
-                        client.clone(),
-                        llm_api_token,
-                        app_version,
-                        CompletionBody {
-                            thread_id,
-                            prompt_id,
-                            intent,
-                            mode,
-                            provider: zed_llm_client::LanguageModelProvider::Google,
-                            model: request.model.model_id.clone(),
-                            provider_request: serde_json::to_value(&request)?,
-                        },
-                    )
-                    .await?;
-
-                    let mut mapper = GoogleEventMapper::new();
-                    Ok(map_cloud_completion_events(
-                        Box::pin(
-                            response_lines(response, includes_status_messages)
-                                .chain(usage_updated_event(usage))
-                                .chain(tool_use_limit_reached_event(tool_use_limit_reached)),
-                        ),
-                        move |event| mapper.map_event(event),
-                    ))
-                });
+                let future = self.request_limiter.stream(
+                    priority,
+                    timeouts.apply(async move {
+                        let PerformLlmCompletionResponse {
+                            response,
+                            usage,
+                            includes_status_messages,
+                            tool_use_limit_reached,
+                        } = Self::perform_llm_completion(
+                            client.clone(),
+                            llm_api_token,
+                            app_version,
+                            CompletionBody {
+                                thread_id,
+                                prompt_id,
+                                intent,
+                                mode,
+                                provider: zed_llm_client::LanguageModelProvider::Google,
+                                model: request.model.model_id.clone(),
+                                provider_request: serde_json::to_value(&request)?,
+                            },
+                        )
+                        .await?;
+
+                        let mut mapper = GoogleEventMapper::new();
+                        Ok(map_cloud_completion_events(
+                            Box::pin(
+                                response_lines(response, includes_status_messages)
+                                    .chain(usage_updated_event(usage))
+                                    .chain(tool_use_limit_reached_event(tool_use_limit_reached)),
+                            ),
+                            move |event| mapper.map_event(event),
+                        ))
+                    }),
+                );
                 async move { Ok(future.await?.boxed()) }.boxed()
             }
         }
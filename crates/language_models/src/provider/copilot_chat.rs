@@ -21,11 +21,11 @@ use gpui::{
 };
 use language_model::{
     AuthenticateError, LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent,
-    LanguageModelId, LanguageModelName, LanguageModelProvider, LanguageModelProviderId,
-    LanguageModelProviderName, LanguageModelProviderState, LanguageModelRequest,
-    LanguageModelRequestMessage, LanguageModelToolChoice, LanguageModelToolResultContent,
-    LanguageModelToolSchemaFormat, LanguageModelToolUse, MessageContent, RateLimiter, Role,
-    StopReason,
+    LanguageModelError, LanguageModelId, LanguageModelName, LanguageModelProvider,
+    LanguageModelProviderId, LanguageModelProviderName, LanguageModelProviderState,
+    LanguageModelRequest, LanguageModelRequestMessage, LanguageModelToolChoice,
+    LanguageModelToolResultContent, LanguageModelToolSchemaFormat, LanguageModelToolUse,
+    MessageContent, RateLimiter, RequestTimeouts, Role, StopReason,
 };
 use settings::{Settings, SettingsStore, update_settings_file};
 use std::time::Duration;
@@ -79,7 +79,8 @@ impl CopilotChatLanguageModelProvider {
     fn create_language_model(&self, model: CopilotChatModel) -> Arc<dyn LanguageModel> {
         Arc::new(CopilotChatLanguageModel {
             model,
-            request_limiter: RateLimiter::new(4),
+            request_limiter: RateLimiter::new(4)
+                .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
         })
     }
 }
@@ -275,6 +276,9 @@ impl LanguageModel for CopilotChatLanguageModel {
             }
         }
 
+        let priority = request.priority;
+        let timeouts = RequestTimeouts::for_request(&self.provider_id(), &request);
+
         let copilot_request = match into_copilot_chat(&self.model, request) {
             Ok(request) => request,
             Err(err) => return futures::future::ready(Err(err)).boxed(),
@@ -285,16 +289,19 @@ impl LanguageModel for CopilotChatLanguageModel {
         let future = cx.spawn(async move |cx| {
             let request = CopilotChat::stream_completion(copilot_request, cx.clone());
             request_limiter
-                .stream(async move {
-                    let response = request.await?;
-                    Ok(map_to_language_model_completion_events(
-                        response,
-                        is_streaming,
-                    ))
-                })
+                .stream(
+                    priority,
+                    timeouts.apply(async move {
+                        let response = request.await?;
+                        Ok(map_to_language_model_completion_events(
+                            response,
+                            is_streaming,
+                        ))
+                    }),
+                )
                 .await
         });
-        async move { Ok(future.await?.boxed()) }.boxed()
+        async move { Ok(future.await.map_err(LanguageModelError::classify)?.boxed()) }.boxed()
     }
 }
 
@@ -375,6 +382,16 @@ pub fn map_to_language_model_completion_events(
                                     StopReason::EndTurn,
                                 )));
                             }
+                            Some("length") => {
+                                events.push(Ok(LanguageModelCompletionEvent::Stop(
+                                    StopReason::MaxTokens,
+                                )));
+                            }
+                            Some("content_filter") => {
+                                events.push(Ok(LanguageModelCompletionEvent::Stop(
+                                    StopReason::Refusal,
+                                )));
+                            }
                             Some("tool_calls") => {
                                 events.extend(state.tool_calls_by_index.drain().map(
                                     |(_, tool_call)| {
@@ -543,7 +560,8 @@ fn into_copilot_chat(
                         MessageContent::ToolUse(_)
                         | MessageContent::RedactedThinking(_)
                         | MessageContent::ToolResult(_)
-                        | MessageContent::Image(_) => None,
+                        | MessageContent::Image(_)
+                        | MessageContent::Document(_) => None,
                     }) {
                         buffer.push_str(string);
                     }
@@ -10,11 +10,12 @@ use gpui::{
 };
 use http_client::HttpClient;
 use language_model::{
-    AuthenticateError, LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent,
-    LanguageModelId, LanguageModelName, LanguageModelProvider, LanguageModelProviderId,
+    AuthenticateError, FillInTheMiddleRequest, LanguageModel, LanguageModelCompletionError,
+    LanguageModelCompletionEvent, LanguageModelError, LanguageModelId, LanguageModelName,
+    LanguageModelPricing, LanguageModelProvider, LanguageModelProviderId,
     LanguageModelProviderName, LanguageModelProviderState, LanguageModelRequest,
     LanguageModelToolChoice, LanguageModelToolResultContent, LanguageModelToolUse, MessageContent,
-    RateLimiter, Role, StopReason,
+    RateLimiter, RequestPriority, RequestTimeouts, Role, StopReason,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -26,7 +27,9 @@ use theme::ThemeSettings;
 use ui::{Icon, IconName, List, prelude::*};
 use util::ResultExt;
 
-use crate::{AllLanguageModelSettings, ui::InstructionListItem};
+use crate::{
+    AllLanguageModelSettings, provider_http::provider_http_client, ui::InstructionListItem,
+};
 
 const PROVIDER_ID: &str = "deepseek";
 const PROVIDER_NAME: &str = "DeepSeek";
@@ -142,6 +145,7 @@ impl State {
 
 impl DeepSeekLanguageModelProvider {
     pub fn new(http_client: Arc<dyn HttpClient>, cx: &mut App) -> Self {
+        let http_client = provider_http_client(http_client, PROVIDER_NAME);
         let state = cx.new(|cx| State {
             api_key: None,
             api_key_from_env: false,
@@ -159,7 +163,8 @@ impl DeepSeekLanguageModelProvider {
             model,
             state: self.state.clone(),
             http_client: self.http_client.clone(),
-            request_limiter: RateLimiter::new(4),
+            request_limiter: RateLimiter::new(4)
+                .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
         }) as Arc<dyn LanguageModel>
     }
 }
@@ -251,6 +256,8 @@ impl DeepSeekLanguageModel {
     fn stream_completion(
         &self,
         request: deepseek::Request,
+        priority: RequestPriority,
+        timeouts: RequestTimeouts,
         cx: &AsyncApp,
     ) -> BoxFuture<'static, Result<BoxStream<'static, Result<deepseek::StreamResponse>>>> {
         let http_client = self.http_client.clone();
@@ -261,15 +268,18 @@ impl DeepSeekLanguageModel {
             return futures::future::ready(Err(anyhow!("App state dropped"))).boxed();
         };
 
-        let future = self.request_limiter.stream(async move {
-            let api_key = api_key.context("Missing DeepSeek API Key")?;
-            let request =
-                deepseek::stream_completion(http_client.as_ref(), &api_url, &api_key, request);
-            let response = request.await?;
-            Ok(response)
-        });
+        let future = self.request_limiter.stream(
+            priority,
+            timeouts.apply(async move {
+                let api_key = api_key.context("Missing DeepSeek API Key")?;
+                let request =
+                    deepseek::stream_completion(http_client.as_ref(), &api_url, &api_key, request);
+                let response = request.await?;
+                Ok(response)
+            }),
+        );
 
-        async move { Ok(future.await?.boxed()) }.boxed()
+        async move { Ok(future.await.map_err(LanguageModelError::classify)?.boxed()) }.boxed()
     }
 }
 
@@ -314,6 +324,19 @@ impl LanguageModel for DeepSeekLanguageModel {
         self.model.max_output_tokens()
     }
 
+    /// DeepSeek's standard list prices, without the off-peak discount.
+    fn pricing(&self) -> Option<LanguageModelPricing> {
+        let (input, output) = match self.model {
+            deepseek::Model::Chat => (0.27, 1.1),
+            deepseek::Model::Reasoner => (0.55, 2.19),
+            deepseek::Model::Custom { .. } => return None,
+        };
+        Some(LanguageModelPricing {
+            input_cost_per_million_tokens: input,
+            output_cost_per_million_tokens: output,
+        })
+    }
+
     fn count_tokens(
         &self,
         request: LanguageModelRequest,
@@ -350,8 +373,10 @@ impl LanguageModel for DeepSeekLanguageModel {
             BoxStream<'static, Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
         >,
     > {
+        let priority = request.priority;
+        let timeouts = RequestTimeouts::for_request(&self.provider_id(), &request);
         let request = into_deepseek(request, &self.model, self.max_output_tokens());
-        let stream = self.stream_completion(request, cx);
+        let stream = self.stream_completion(request, priority, timeouts, cx);
 
         async move {
             let mapper = DeepSeekEventMapper::new();
@@ -359,6 +384,48 @@ impl LanguageModel for DeepSeekLanguageModel {
         }
         .boxed()
     }
+
+    fn supports_fill_in_the_middle(&self) -> bool {
+        self.model.supports_fim()
+    }
+
+    fn fill_in_the_middle(
+        &self,
+        request: FillInTheMiddleRequest,
+        cx: &AsyncApp,
+    ) -> BoxFuture<'static, Result<String>> {
+        let request = deepseek::FimRequest {
+            model: self.model.id().to_string(),
+            prompt: request.prefix,
+            suffix: Some(request.suffix),
+            max_tokens: request.max_tokens,
+            stop: request.stop,
+            temperature: request.temperature,
+        };
+
+        let http_client = self.http_client.clone();
+        let Ok((api_key, api_url)) = cx.read_entity(&self.state, |state, cx| {
+            let settings = &AllLanguageModelSettings::get_global(cx).deepseek;
+            (state.api_key.clone(), settings.api_url.clone())
+        }) else {
+            return futures::future::ready(Err(anyhow!("App state dropped"))).boxed();
+        };
+
+        self.request_limiter
+            .run(RequestPriority::Interactive, async move {
+                let api_key = api_key.context("Missing DeepSeek API Key")?;
+                let response =
+                    deepseek::fim_completion(http_client.as_ref(), &api_url, &api_key, request)
+                        .await?;
+                response
+                    .choices
+                    .into_iter()
+                    .next()
+                    .map(|choice| choice.text)
+                    .context("DeepSeek returned no completion")
+            })
+            .boxed()
+    }
 }
 
 pub fn into_deepseek(
@@ -382,7 +449,7 @@ pub fn into_deepseek(
                         Role::System => deepseek::RequestMessage::System { content: text },
                     }),
                 MessageContent::RedactedThinking(_) => {}
-                MessageContent::Image(_) => {}
+                MessageContent::Image(_) | MessageContent::Document(_) => {}
                 MessageContent::ToolUse(tool_use) => {
                     let tool_call = deepseek::ToolCall {
                         id: tool_use.id.to_string(),
@@ -509,6 +576,14 @@ impl DeepSeekEventMapper {
             Some("stop") => {
                 events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::EndTurn)));
             }
+            Some("length") => {
+                events.push(Ok(LanguageModelCompletionEvent::Stop(
+                    StopReason::MaxTokens,
+                )));
+            }
+            Some("content_filter") => {
+                events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::Refusal)));
+            }
             Some("tool_calls") => {
                 events.extend(self.tool_calls_by_index.drain().map(|(_, tool_call)| {
                     match serde_json::Value::from_str(&tool_call.arguments) {
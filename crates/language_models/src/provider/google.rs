@@ -1,45 +1,66 @@
 use anyhow::{Context as _, Result, anyhow};
-use collections::BTreeMap;
+use collections::{BTreeMap, HashMap};
 use credentials_provider::CredentialsProvider;
 use editor::{Editor, EditorElement, EditorStyle};
 use futures::{FutureExt, Stream, StreamExt, future::BoxFuture};
 use google_ai::{
-    FunctionDeclaration, GenerateContentResponse, GoogleModelMode, Part, SystemInstruction,
-    ThinkingConfig, UsageMetadata,
+    GenerateContentResponse, GoogleModelMode, Part, SystemInstruction, ThinkingConfig,
+    UsageMetadata,
 };
 use gpui::{
     AnyView, App, AsyncApp, Context, Entity, FontStyle, Subscription, Task, TextStyle, WhiteSpace,
 };
 use http_client::HttpClient;
 use language_model::{
-    AuthenticateError, LanguageModelCompletionError, LanguageModelCompletionEvent,
-    LanguageModelToolChoice, LanguageModelToolSchemaFormat, LanguageModelToolUse,
-    LanguageModelToolUseId, MessageContent, StopReason,
+    AuthenticateError, LanguageModelCacheConfiguration, LanguageModelCompletionError,
+    LanguageModelCompletionEvent, LanguageModelDocument, LanguageModelError,
+    LanguageModelRequestMessage, LanguageModelToolChoice, LanguageModelToolSchemaFormat,
+    MessageContent, StopReason, WebSource,
 };
 use language_model::{
-    LanguageModel, LanguageModelId, LanguageModelName, LanguageModelProvider,
+    LanguageModel, LanguageModelId, LanguageModelName, LanguageModelPricing, LanguageModelProvider,
     LanguageModelProviderId, LanguageModelProviderName, LanguageModelProviderState,
-    LanguageModelRequest, RateLimiter, Role,
+    LanguageModelRequest, RateLimiter, RequestTimeouts, Role,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::{Settings, SettingsStore};
+use std::hash::{DefaultHasher, Hash as _, Hasher as _};
 use std::pin::Pin;
-use std::sync::{
-    Arc,
-    atomic::{self, AtomicU64},
-};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use strum::IntoEnumIterator;
 use theme::ThemeSettings;
 use ui::{Icon, IconName, List, Tooltip, prelude::*};
 use util::ResultExt;
 
 use crate::AllLanguageModelSettings;
+use crate::provider_http::provider_http_client;
+use crate::tool_call_format::{GoogleToolFormat, ToolCallFormat};
 use crate::ui::InstructionListItem;
 
 const PROVIDER_ID: &str = "google";
 const PROVIDER_NAME: &str = "Google AI";
 
+/// The largest document sent inline. Larger documents are uploaded through
+/// the Files API, since a request can't be larger than 20 MB.
+const MAX_INLINE_DOCUMENT_SIZE: usize = 15 * 1024 * 1024;
+
+/// How long an uploaded file is reused for. The Files API keeps files for
+/// 48 hours.
+const UPLOADED_FILE_TTL: Duration = Duration::from_secs(47 * 60 * 60);
+
+/// The fewest tokens worth caching explicitly. Gemini 2.5 Pro requires
+/// 4,096, and other models require fewer.
+const MIN_CACHED_CONTENT_TOKENS: usize = 4_096;
+
+/// How long a cached content is kept. Its storage is billed by the hour.
+const CACHED_CONTENT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How long before it expires a cached content is replaced, so that it
+/// doesn't expire while a request that references it is in flight.
+const CACHED_CONTENT_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
 #[derive(Default, Clone, Debug, PartialEq)]
 pub struct GoogleSettings {
     pub api_url: String,
@@ -86,6 +107,8 @@ pub struct AvailableModel {
 pub struct GoogleLanguageModelProvider {
     http_client: Arc<dyn HttpClient>,
     state: gpui::Entity<State>,
+    uploaded_files: UploadedFiles,
+    cached_contents: CachedContents,
 }
 
 pub struct State {
@@ -175,6 +198,7 @@ impl State {
 
 impl GoogleLanguageModelProvider {
     pub fn new(http_client: Arc<dyn HttpClient>, cx: &mut App) -> Self {
+        let http_client = provider_http_client(http_client, PROVIDER_NAME);
         let state = cx.new(|cx| State {
             api_key: None,
             api_key_from_env: false,
@@ -183,7 +207,12 @@ impl GoogleLanguageModelProvider {
             }),
         });
 
-        Self { http_client, state }
+        Self {
+            http_client,
+            state,
+            uploaded_files: UploadedFiles::default(),
+            cached_contents: CachedContents::default(),
+        }
     }
 
     fn create_language_model(&self, model: google_ai::Model) -> Arc<dyn LanguageModel> {
@@ -192,7 +221,10 @@ impl GoogleLanguageModelProvider {
             model,
             state: self.state.clone(),
             http_client: self.http_client.clone(),
-            request_limiter: RateLimiter::new(4),
+            uploaded_files: self.uploaded_files.clone(),
+            cached_contents: self.cached_contents.clone(),
+            request_limiter: RateLimiter::new(4)
+                .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
         })
     }
 }
@@ -260,7 +292,10 @@ impl LanguageModelProvider for GoogleLanguageModelProvider {
                     model,
                     state: self.state.clone(),
                     http_client: self.http_client.clone(),
-                    request_limiter: RateLimiter::new(4),
+                    uploaded_files: self.uploaded_files.clone(),
+                    cached_contents: self.cached_contents.clone(),
+                    request_limiter: RateLimiter::new(4)
+                        .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
                 }) as Arc<dyn LanguageModel>
             })
             .collect()
@@ -289,17 +324,229 @@ pub struct GoogleLanguageModel {
     model: google_ai::Model,
     state: gpui::Entity<State>,
     http_client: Arc<dyn HttpClient>,
+    uploaded_files: UploadedFiles,
+    cached_contents: CachedContents,
     request_limiter: RateLimiter,
 }
 
+/// The documents uploaded through the Files API, keyed by the hash of their
+/// contents, so that a document attached to a thread is uploaded once.
+#[derive(Clone, Default)]
+struct UploadedFiles(Arc<Mutex<HashMap<u64, (String, Instant)>>>);
+
+impl UploadedFiles {
+    fn get(&self, key: u64) -> Option<String> {
+        let uploaded_files = self.0.lock().ok()?;
+        let (uri, uploaded_at) = uploaded_files.get(&key)?;
+        (uploaded_at.elapsed() < UPLOADED_FILE_TTL).then(|| uri.clone())
+    }
+
+    fn insert(&self, key: u64, uri: String) {
+        if let Ok(mut uploaded_files) = self.0.lock() {
+            uploaded_files.insert(key, (uri, Instant::now()));
+        }
+    }
+}
+
+fn document_key(document: &LanguageModelDocument) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    document.source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Replaces the inline data of the documents too large to be sent inline with
+/// the files they were uploaded to.
+async fn upload_large_documents(
+    request: &mut google_ai::GenerateContentRequest,
+    documents: Vec<LanguageModelDocument>,
+    uploaded_files: &UploadedFiles,
+    http_client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+) -> Result<()> {
+    for document in documents {
+        let key = document_key(&document);
+        let file_uri = match uploaded_files.get(key) {
+            Some(file_uri) => file_uri,
+            None => {
+                let file = google_ai::upload_file(
+                    http_client,
+                    api_url,
+                    api_key,
+                    document.to_bytes()?,
+                    &document.media_type,
+                    &document.name,
+                )
+                .await
+                .with_context(|| format!("failed to upload {}", document.name))?;
+                uploaded_files.insert(key, file.uri.clone());
+                file.uri
+            }
+        };
+
+        for part in request
+            .contents
+            .iter_mut()
+            .flat_map(|content| content.parts.iter_mut())
+        {
+            if let Part::InlineDataPart(inline_data_part) = part {
+                if inline_data_part.inline_data.data.as_str() == document.source.as_ref() {
+                    *part = Part::FileDataPart(google_ai::FileDataPart {
+                        file_data: google_ai::FileData {
+                            mime_type: document.media_type.to_string(),
+                            file_uri: file_uri.clone(),
+                        },
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+enum CachedPrefix {
+    /// The prefix was sent without being cached.
+    Sent {
+        at: Instant,
+    },
+    Cached {
+        name: String,
+        expires_at: Instant,
+    },
+}
+
+enum PrefixLookup {
+    Cached(String),
+    SentBefore,
+    New,
+}
+
+/// The request prefixes sent to Gemini, keyed by the hash of their contents.
+/// A prefix is cached the second time it's sent, since caching it costs a
+/// request of its own and storage for as long as it's kept, which a prefix
+/// that's only sent once never pays back.
+#[derive(Clone, Default)]
+struct CachedContents(Arc<Mutex<HashMap<u64, CachedPrefix>>>);
+
+impl CachedContents {
+    fn lookup(&self, key: u64) -> PrefixLookup {
+        let Ok(mut prefixes) = self.0.lock() else {
+            return PrefixLookup::New;
+        };
+        let now = Instant::now();
+        prefixes.retain(|_, prefix| match prefix {
+            CachedPrefix::Sent { at } => now.duration_since(*at) < CACHED_CONTENT_TTL,
+            CachedPrefix::Cached { expires_at, .. } => {
+                // A prefix whose cached content expires is cached again the
+                // next time it's sent.
+                if *expires_at < now + CACHED_CONTENT_EXPIRY_MARGIN {
+                    *prefix = CachedPrefix::Sent { at: now };
+                }
+                true
+            }
+        });
+        match prefixes.get(&key) {
+            Some(CachedPrefix::Cached { name, .. }) => PrefixLookup::Cached(name.clone()),
+            Some(CachedPrefix::Sent { .. }) => PrefixLookup::SentBefore,
+            None => {
+                prefixes.insert(key, CachedPrefix::Sent { at: now });
+                PrefixLookup::New
+            }
+        }
+    }
+
+    fn insert(&self, key: u64, name: String) {
+        if let Ok(mut prefixes) = self.0.lock() {
+            prefixes.insert(
+                key,
+                CachedPrefix::Cached {
+                    name,
+                    expires_at: Instant::now() + CACHED_CONTENT_TTL,
+                },
+            );
+        }
+    }
+}
+
+fn cached_prefix_key(request: &google_ai::CreateCachedContentRequest) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(request).log_err()?.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// Moves the first `cached_prefix_len` contents, along with the system
+/// instruction and tools, into a cached content that the request references
+/// instead, if the same prefix was cached or sent before. Returns the number
+/// of tokens cached when a cached content is created.
+async fn use_cached_content(
+    request: &mut google_ai::GenerateContentRequest,
+    cached_prefix_len: usize,
+    cached_contents: &CachedContents,
+    http_client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+) -> Option<usize> {
+    let cache_request = google_ai::CreateCachedContentRequest {
+        model: format!("models/{}", request.model.model_id),
+        contents: request.contents.drain(..cached_prefix_len).collect(),
+        system_instruction: request.system_instruction.take(),
+        tools: request.tools.take(),
+        tool_config: request.tool_config.take(),
+        ttl: format!("{}s", CACHED_CONTENT_TTL.as_secs()),
+    };
+    let key = cached_prefix_key(&cache_request);
+    let lookup = key.map_or(PrefixLookup::New, |key| cached_contents.lookup(key));
+    let cached_content = match (lookup, key) {
+        (PrefixLookup::Cached(name), _) => Some((name, None)),
+        (PrefixLookup::SentBefore, Some(key)) => {
+            google_ai::create_cached_content(http_client, api_url, api_key, &cache_request)
+                .await
+                .context("failed to cache request prefix")
+                .log_err()
+                .map(|cached_content| {
+                    let tokens = cached_content
+                        .usage_metadata
+                        .map_or(0, |usage| usage.total_token_count);
+                    log::info!(
+                        "cached {tokens} tokens as {} for {}s",
+                        cached_content.name,
+                        CACHED_CONTENT_TTL.as_secs()
+                    );
+                    cached_contents.insert(key, cached_content.name.clone());
+                    (cached_content.name, Some(tokens))
+                })
+        }
+        _ => None,
+    };
+
+    match cached_content {
+        Some((name, created_tokens)) => {
+            request.cached_content = Some(name);
+            created_tokens
+        }
+        None => {
+            request.contents.splice(0..0, cache_request.contents);
+            request.system_instruction = cache_request.system_instruction;
+            request.tools = cache_request.tools;
+            request.tool_config = cache_request.tool_config;
+            None
+        }
+    }
+}
+
 impl GoogleLanguageModel {
     fn stream_completion(
         &self,
-        request: google_ai::GenerateContentRequest,
+        mut request: google_ai::GenerateContentRequest,
+        large_documents: Vec<LanguageModelDocument>,
+        cached_prefix_len: Option<usize>,
         cx: &AsyncApp,
     ) -> BoxFuture<
         'static,
-        Result<futures::stream::BoxStream<'static, Result<GenerateContentResponse>>>,
+        Result<(
+            futures::stream::BoxStream<'static, Result<GenerateContentResponse>>,
+            Option<usize>,
+        )>,
     > {
         let http_client = self.http_client.clone();
 
@@ -310,15 +557,42 @@ impl GoogleLanguageModel {
             return futures::future::ready(Err(anyhow!("App state dropped"))).boxed();
         };
 
+        let uploaded_files = self.uploaded_files.clone();
+        let cached_contents = self.cached_contents.clone();
         async move {
             let api_key = api_key.context("Missing Google API key")?;
-            let request = google_ai::stream_generate_content(
+            upload_large_documents(
+                &mut request,
+                large_documents,
+                &uploaded_files,
+                http_client.as_ref(),
+                &api_url,
+                &api_key,
+            )
+            .await?;
+            let cache_creation_tokens = match cached_prefix_len {
+                Some(cached_prefix_len) => {
+                    use_cached_content(
+                        &mut request,
+                        cached_prefix_len,
+                        &cached_contents,
+                        http_client.as_ref(),
+                        &api_url,
+                        &api_key,
+                    )
+                    .await
+                }
+                None => None,
+            };
+            let response = google_ai::stream_generate_content(
                 http_client.as_ref(),
                 &api_url,
                 &api_key,
                 request,
-            );
-            request.await.context("failed to stream completion")
+            )
+            .await
+            .context("failed to stream completion")?;
+            Ok((response, cache_creation_tokens))
         }
         .boxed()
     }
@@ -349,6 +623,14 @@ impl LanguageModel for GoogleLanguageModel {
         true
     }
 
+    fn supports_documents(&self) -> bool {
+        true
+    }
+
+    fn supports_web_search(&self) -> bool {
+        self.model.supports_web_search()
+    }
+
     fn supports_tool_choice(&self, choice: LanguageModelToolChoice) -> bool {
         match choice {
             LanguageModelToolChoice::Auto
@@ -369,6 +651,38 @@ impl LanguageModel for GoogleLanguageModel {
         self.model.max_token_count()
     }
 
+    /// Google's list prices for prompts of up to 200k tokens. Experimental
+    /// models have no published pricing.
+    fn pricing(&self) -> Option<LanguageModelPricing> {
+        let (input, output) = match self.model {
+            google_ai::Model::Gemini15Pro => (1.25, 5.),
+            google_ai::Model::Gemini15Flash | google_ai::Model::Gemini20FlashLite => (0.075, 0.3),
+            google_ai::Model::Gemini20Flash => (0.1, 0.4),
+            google_ai::Model::Gemini25FlashPreview0417 | google_ai::Model::Gemini25FlashPreview => {
+                (0.15, 3.5)
+            }
+            google_ai::Model::Gemini25ProPreview0325 | google_ai::Model::Gemini25ProPreview => {
+                (1.25, 10.)
+            }
+            google_ai::Model::Gemini20Pro
+            | google_ai::Model::Gemini20FlashThinking
+            | google_ai::Model::Gemini25ProExp0325
+            | google_ai::Model::Custom { .. } => return None,
+        };
+        Some(LanguageModelPricing {
+            input_cost_per_million_tokens: input,
+            output_cost_per_million_tokens: output,
+        })
+    }
+
+    fn cache_configuration(&self) -> Option<LanguageModelCacheConfiguration> {
+        Some(LanguageModelCacheConfiguration {
+            max_cache_anchors: 2,
+            should_speculate: false,
+            min_total_token: MIN_CACHED_CONTENT_TOKENS,
+        })
+    }
+
     fn count_tokens(
         &self,
         request: LanguageModelRequest,
@@ -411,22 +725,81 @@ impl LanguageModel for GoogleLanguageModel {
             >,
         >,
     > {
-        let request = into_google(
+        let priority = request.priority;
+        let timeouts = RequestTimeouts::for_request(&self.provider_id(), &request);
+        let large_documents = request
+            .messages
+            .iter()
+            .flat_map(|message| &message.content)
+            .filter_map(|content| match content {
+                MessageContent::Document(document) if document.len() > MAX_INLINE_DOCUMENT_SIZE => {
+                    Some(document.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        let (request, cached_prefix_len) = into_google_with_cached_prefix(
             request,
             self.model.request_id().to_string(),
             self.model.mode(),
         );
-        let request = self.stream_completion(request, cx);
-        let future = self.request_limiter.stream(async move {
-            let response = request
-                .await
-                .map_err(|err| LanguageModelCompletionError::Other(anyhow!(err)))?;
-            Ok(GoogleEventMapper::new().map_stream(response))
-        });
-        async move { Ok(future.await?.boxed()) }.boxed()
+        let request = self.stream_completion(request, large_documents, cached_prefix_len, cx);
+        let future = self.request_limiter.stream(
+            priority,
+            timeouts.apply(async move {
+                let (response, cache_creation_tokens) = request
+                    .await
+                    .map_err(|err| LanguageModelCompletionError::Other(anyhow!(err)))?;
+                Ok(GoogleEventMapper::new()
+                    .with_cache_creation_tokens(cache_creation_tokens.unwrap_or(0))
+                    .map_stream(response))
+            }),
+        );
+        async move { Ok(future.await.map_err(LanguageModelError::classify)?.boxed()) }.boxed()
     }
 }
 
+/// Converts the request, along with the number of its contents up to the
+/// last message marked to be cached, if they're large enough to be worth
+/// caching and there are contents after them.
+fn into_google_with_cached_prefix(
+    mut request: LanguageModelRequest,
+    model_id: String,
+    mode: GoogleModelMode,
+) -> (google_ai::GenerateContentRequest, Option<usize>) {
+    let cached_message_count = request
+        .messages
+        .iter()
+        .rposition(|message| message.cache)
+        .map_or(0, |ix| ix + 1);
+    let cached_tokens = request.messages[..cached_message_count]
+        .iter()
+        .map(LanguageModelRequestMessage::estimate_tokens)
+        .sum::<usize>();
+    if cached_tokens < MIN_CACHED_CONTENT_TOKENS || cached_message_count == request.messages.len() {
+        return (into_google(request, model_id, mode), None);
+    }
+
+    let uncached_messages = request.messages.split_off(cached_message_count);
+    let mut google_request = into_google(request, model_id.clone(), mode);
+    let cached_prefix_len = google_request.contents.len();
+    google_request.contents.extend(
+        into_google(
+            LanguageModelRequest {
+                messages: uncached_messages,
+                ..Default::default()
+            },
+            model_id,
+            mode,
+        )
+        .contents,
+    );
+    (
+        google_request,
+        Some(cached_prefix_len).filter(|len| *len > 0),
+    )
+}
+
 pub fn into_google(
     mut request: LanguageModelRequest,
     model_id: String,
@@ -453,49 +826,36 @@ pub fn into_google(
                         },
                     })]
                 }
+                language_model::MessageContent::Document(document) => {
+                    vec![Part::InlineDataPart(google_ai::InlineDataPart {
+                        inline_data: google_ai::GenerativeContentBlob {
+                            mime_type: document.media_type.to_string(),
+                            data: document.source.to_string(),
+                        },
+                    })]
+                }
                 language_model::MessageContent::ToolUse(tool_use) => {
                     vec![Part::FunctionCallPart(google_ai::FunctionCallPart {
-                        function_call: google_ai::FunctionCall {
-                            name: tool_use.name.to_string(),
-                            args: tool_use.input,
-                        },
+                        function_call: GoogleToolFormat::to_tool_call(&tool_use),
                     })]
                 }
                 language_model::MessageContent::ToolResult(tool_result) => {
-                    match tool_result.content {
-                        language_model::LanguageModelToolResultContent::Text(text) => {
-                            vec![Part::FunctionResponsePart(
-                                google_ai::FunctionResponsePart {
-                                    function_response: google_ai::FunctionResponse {
-                                        name: tool_result.tool_name.to_string(),
-                                        // The API expects a valid JSON object
-                                        response: serde_json::json!({
-                                            "output": text
-                                        }),
-                                    },
-                                },
-                            )]
-                        }
-                        language_model::LanguageModelToolResultContent::Image(image) => {
-                            vec![
-                                Part::FunctionResponsePart(google_ai::FunctionResponsePart {
-                                    function_response: google_ai::FunctionResponse {
-                                        name: tool_result.tool_name.to_string(),
-                                        // The API expects a valid JSON object
-                                        response: serde_json::json!({
-                                            "output": "Tool responded with an image"
-                                        }),
-                                    },
-                                }),
-                                Part::InlineDataPart(google_ai::InlineDataPart {
-                                    inline_data: google_ai::GenerativeContentBlob {
-                                        mime_type: "image/png".to_string(),
-                                        data: image.source.to_string(),
-                                    },
-                                }),
-                            ]
-                        }
+                    let mut parts = vec![Part::FunctionResponsePart(
+                        google_ai::FunctionResponsePart {
+                            function_response: GoogleToolFormat::to_tool_result(&tool_result),
+                        },
+                    )];
+                    if let language_model::LanguageModelToolResultContent::Image(image) =
+                        tool_result.content
+                    {
+                        parts.push(Part::InlineDataPart(google_ai::InlineDataPart {
+                            inline_data: google_ai::GenerativeContentBlob {
+                                mime_type: "image/png".to_string(),
+                                data: image.source.to_string(),
+                            },
+                        }));
                     }
+                    parts
                 }
             })
             .collect()
@@ -551,18 +911,25 @@ pub fn into_google(
             top_k: None,
         }),
         safety_settings: None,
-        tools: (request.tools.len() > 0).then(|| {
-            vec![google_ai::Tool {
-                function_declarations: request
-                    .tools
-                    .into_iter()
-                    .map(|tool| FunctionDeclaration {
-                        name: tool.name,
-                        description: tool.description,
-                        parameters: tool.input_schema,
-                    })
-                    .collect(),
-            }]
+        tools: (request.tools.len() > 0 || request.web_search).then(|| {
+            let mut tools = Vec::new();
+            if !request.tools.is_empty() {
+                tools.push(google_ai::Tool {
+                    function_declarations: request
+                        .tools
+                        .into_iter()
+                        .map(GoogleToolFormat::to_tool_definition)
+                        .collect(),
+                    google_search: None,
+                });
+            }
+            if request.web_search {
+                tools.push(google_ai::Tool {
+                    function_declarations: Vec::new(),
+                    google_search: Some(google_ai::GoogleSearch::default()),
+                });
+            }
+            tools
         }),
         tool_config: request.tool_choice.map(|choice| google_ai::ToolConfig {
             function_calling_config: google_ai::FunctionCallingConfig {
@@ -574,11 +941,13 @@ pub fn into_google(
                 allowed_function_names: None,
             },
         }),
+        cached_content: None,
     }
 }
 
 pub struct GoogleEventMapper {
     usage: UsageMetadata,
+    cache_creation_tokens: usize,
     stop_reason: StopReason,
 }
 
@@ -586,10 +955,18 @@ impl GoogleEventMapper {
     pub fn new() -> Self {
         Self {
             usage: UsageMetadata::default(),
+            cache_creation_tokens: 0,
             stop_reason: StopReason::EndTurn,
         }
     }
 
+    /// Reports the tokens cached for the request along with its usage, since
+    /// caching them is billed separately from the request.
+    pub fn with_cache_creation_tokens(mut self, cache_creation_tokens: usize) -> Self {
+        self.cache_creation_tokens = cache_creation_tokens;
+        self
+    }
+
     pub fn map_stream(
         mut self,
         events: Pin<Box<dyn Send + Stream<Item = Result<GenerateContentResponse>>>>,
@@ -613,14 +990,12 @@ impl GoogleEventMapper {
         &mut self,
         event: GenerateContentResponse,
     ) -> Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
-        static TOOL_CALL_COUNTER: AtomicU64 = AtomicU64::new(0);
-
         let mut events: Vec<_> = Vec::new();
         let mut wants_to_use_tool = false;
         if let Some(usage_metadata) = event.usage_metadata {
             update_usage(&mut self.usage, &usage_metadata);
             events.push(Ok(LanguageModelCompletionEvent::UsageUpdate(
-                convert_usage(&self.usage),
+                convert_usage(&self.usage, self.cache_creation_tokens),
             )))
         }
         if let Some(candidates) = event.candidates {
@@ -629,6 +1004,9 @@ impl GoogleEventMapper {
                     self.stop_reason = match finish_reason {
                         "STOP" => StopReason::EndTurn,
                         "MAX_TOKENS" => StopReason::MaxTokens,
+                        "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" => {
+                            StopReason::Refusal
+                        }
                         _ => {
                             log::error!("Unexpected google finish_reason: {finish_reason}");
                             StopReason::EndTurn
@@ -643,28 +1021,31 @@ impl GoogleEventMapper {
                         Part::TextPart(text_part) => {
                             events.push(Ok(LanguageModelCompletionEvent::Text(text_part.text)))
                         }
-                        Part::InlineDataPart(_) => {}
+                        Part::InlineDataPart(_) | Part::FileDataPart(_) => {}
                         Part::FunctionCallPart(function_call_part) => {
                             wants_to_use_tool = true;
-                            let name: Arc<str> = function_call_part.function_call.name.into();
-                            let next_tool_id =
-                                TOOL_CALL_COUNTER.fetch_add(1, atomic::Ordering::SeqCst);
-                            let id: LanguageModelToolUseId =
-                                format!("{}-{}", name, next_tool_id).into();
-
-                            events.push(Ok(LanguageModelCompletionEvent::ToolUse(
-                                LanguageModelToolUse {
-                                    id,
-                                    name,
-                                    is_input_complete: true,
-                                    raw_input: function_call_part.function_call.args.to_string(),
-                                    input: function_call_part.function_call.args,
-                                },
-                            )));
+                            events.push(
+                                GoogleToolFormat::from_tool_call(function_call_part.function_call)
+                                    .map(LanguageModelCompletionEvent::ToolUse),
+                            );
                         }
                         Part::FunctionResponsePart(_) => {}
                         Part::ThoughtPart(_) => {}
                     });
+                if let Some(grounding_metadata) = candidate.grounding_metadata {
+                    let sources = grounding_metadata
+                        .grounding_chunks
+                        .into_iter()
+                        .filter_map(|chunk| chunk.web)
+                        .map(|web| WebSource {
+                            url: web.uri,
+                            title: web.title,
+                        })
+                        .collect::<Vec<_>>();
+                    if !sources.is_empty() {
+                        events.push(Ok(LanguageModelCompletionEvent::WebSources(sources)));
+                    }
+                }
             }
         }
 
@@ -728,7 +1109,10 @@ fn update_usage(usage: &mut UsageMetadata, new: &UsageMetadata) {
     }
 }
 
-fn convert_usage(usage: &UsageMetadata) -> language_model::TokenUsage {
+fn convert_usage(
+    usage: &UsageMetadata,
+    cache_creation_tokens: usize,
+) -> language_model::TokenUsage {
     let prompt_tokens = usage.prompt_token_count.unwrap_or(0) as u32;
     let cached_tokens = usage.cached_content_token_count.unwrap_or(0) as u32;
     let input_tokens = prompt_tokens - cached_tokens;
@@ -738,7 +1122,7 @@ fn convert_usage(usage: &UsageMetadata) -> language_model::TokenUsage {
         input_tokens,
         output_tokens,
         cache_read_input_tokens: cached_tokens,
-        cache_creation_input_tokens: 0,
+        cache_creation_input_tokens: cache_creation_tokens as u32,
     }
 }
 
@@ -6,13 +6,13 @@ use gpui::{AnyView, App, AsyncApp, Context, Subscription, Task};
 use http_client::HttpClient;
 use language_model::{
     AuthenticateError, LanguageModelCompletionError, LanguageModelCompletionEvent,
-    LanguageModelToolChoice, LanguageModelToolResultContent, LanguageModelToolUse, MessageContent,
-    StopReason,
+    LanguageModelError, LanguageModelToolChoice, LanguageModelToolResultContent,
+    LanguageModelToolUse, MessageContent, StopReason,
 };
 use language_model::{
     LanguageModel, LanguageModelId, LanguageModelName, LanguageModelProvider,
     LanguageModelProviderId, LanguageModelProviderName, LanguageModelProviderState,
-    LanguageModelRequest, RateLimiter, Role,
+    LanguageModelRequest, RateLimiter, RequestPriority, RequestTimeouts, Role,
 };
 use lmstudio::{
     ChatCompletionRequest, ChatMessage, ModelType, ResponseStreamEvent, get_models,
@@ -28,6 +28,7 @@ use ui::{ButtonLike, Indicator, List, prelude::*};
 use util::ResultExt;
 
 use crate::AllLanguageModelSettings;
+use crate::provider_http::provider_http_client;
 use crate::ui::InstructionListItem;
 
 const LMSTUDIO_DOWNLOAD_URL: &str = "https://lmstudio.ai/download";
@@ -118,6 +119,7 @@ impl State {
 
 impl LmStudioLanguageModelProvider {
     pub fn new(http_client: Arc<dyn HttpClient>, cx: &mut App) -> Self {
+        let http_client = provider_http_client(http_client, PROVIDER_NAME);
         let this = Self {
             http_client: http_client.clone(),
             state: cx.new(|cx| {
@@ -212,7 +214,8 @@ impl LanguageModelProvider for LmStudioLanguageModelProvider {
                     id: LanguageModelId::from(model.name.clone()),
                     model: model.clone(),
                     http_client: self.http_client.clone(),
-                    request_limiter: RateLimiter::new(4),
+                    request_limiter: RateLimiter::new(4)
+                        .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
                 }) as Arc<dyn LanguageModel>
             })
             .collect()
@@ -260,7 +263,7 @@ impl LmStudioLanguageModel {
                             Role::System => ChatMessage::System { content: text },
                         }),
                     MessageContent::RedactedThinking(_) => {}
-                    MessageContent::Image(_) => {}
+                    MessageContent::Image(_) | MessageContent::Document(_) => {}
                     MessageContent::ToolUse(tool_use) => {
                         let tool_call = lmstudio::ToolCall {
                             id: tool_use.id.to_string(),
@@ -333,6 +336,8 @@ impl LmStudioLanguageModel {
     fn stream_completion(
         &self,
         request: ChatCompletionRequest,
+        priority: RequestPriority,
+        timeouts: RequestTimeouts,
         cx: &AsyncApp,
     ) -> BoxFuture<'static, Result<futures::stream::BoxStream<'static, Result<ResponseStreamEvent>>>>
     {
@@ -344,13 +349,16 @@ impl LmStudioLanguageModel {
             return futures::future::ready(Err(anyhow!("App state dropped"))).boxed();
         };
 
-        let future = self.request_limiter.stream(async move {
-            let request = stream_chat_completion(http_client.as_ref(), &api_url, request);
-            let response = request.await?;
-            Ok(response)
-        });
+        let future = self.request_limiter.stream(
+            priority,
+            timeouts.apply(async move {
+                let request = stream_chat_completion(http_client.as_ref(), &api_url, request);
+                let response = request.await?;
+                Ok(response)
+            }),
+        );
 
-        async move { Ok(future.await?.boxed()) }.boxed()
+        async move { Ok(future.await.map_err(LanguageModelError::classify)?.boxed()) }.boxed()
     }
 }
 
@@ -422,8 +430,10 @@ impl LanguageModel for LmStudioLanguageModel {
             BoxStream<'static, Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
         >,
     > {
+        let priority = request.priority;
+        let timeouts = RequestTimeouts::for_request(&self.provider_id(), &request);
         let request = self.to_lmstudio_request(request);
-        let completions = self.stream_completion(request, cx);
+        let completions = self.stream_completion(request, priority, timeouts, cx);
         async move {
             let mapper = LmStudioEventMapper::new();
             Ok(mapper.map_stream(completions.await?).boxed())
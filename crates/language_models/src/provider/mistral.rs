@@ -9,11 +9,12 @@ use gpui::{
 };
 use http_client::HttpClient;
 use language_model::{
-    AuthenticateError, LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent,
-    LanguageModelId, LanguageModelName, LanguageModelProvider, LanguageModelProviderId,
-    LanguageModelProviderName, LanguageModelProviderState, LanguageModelRequest,
-    LanguageModelToolChoice, LanguageModelToolResultContent, LanguageModelToolUse, MessageContent,
-    RateLimiter, Role, StopReason,
+    AuthenticateError, FillInTheMiddleRequest, LanguageModel, LanguageModelCompletionError,
+    LanguageModelCompletionEvent, LanguageModelError, LanguageModelId, LanguageModelName,
+    LanguageModelProvider, LanguageModelProviderId, LanguageModelProviderName,
+    LanguageModelProviderState, LanguageModelRequest, LanguageModelToolChoice,
+    LanguageModelToolResultContent, LanguageModelToolUse, MessageContent, RateLimiter,
+    RequestPriority, RequestTimeouts, Role, StopReason,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -25,7 +26,9 @@ use theme::ThemeSettings;
 use ui::{Icon, IconName, List, Tooltip, prelude::*};
 use util::ResultExt;
 
-use crate::{AllLanguageModelSettings, ui::InstructionListItem};
+use crate::{
+    AllLanguageModelSettings, provider_http::provider_http_client, ui::InstructionListItem,
+};
 
 use std::collections::HashMap;
 use std::pin::Pin;
@@ -140,6 +143,7 @@ impl State {
 
 impl MistralLanguageModelProvider {
     pub fn new(http_client: Arc<dyn HttpClient>, cx: &mut App) -> Self {
+        let http_client = provider_http_client(http_client, PROVIDER_NAME);
         let state = cx.new(|cx| State {
             api_key: None,
             api_key_from_env: false,
@@ -157,7 +161,8 @@ impl MistralLanguageModelProvider {
             model,
             state: self.state.clone(),
             http_client: self.http_client.clone(),
-            request_limiter: RateLimiter::new(4),
+            request_limiter: RateLimiter::new(4)
+                .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
         })
     }
 }
@@ -227,7 +232,8 @@ impl LanguageModelProvider for MistralLanguageModelProvider {
                     model,
                     state: self.state.clone(),
                     http_client: self.http_client.clone(),
-                    request_limiter: RateLimiter::new(4),
+                    request_limiter: RateLimiter::new(4)
+                        .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
                 }) as Arc<dyn LanguageModel>
             })
             .collect()
@@ -263,6 +269,8 @@ impl MistralLanguageModel {
     fn stream_completion(
         &self,
         request: mistral::Request,
+        priority: RequestPriority,
+        timeouts: RequestTimeouts,
         cx: &AsyncApp,
     ) -> BoxFuture<
         'static,
@@ -276,15 +284,18 @@ impl MistralLanguageModel {
             return futures::future::ready(Err(anyhow!("App state dropped"))).boxed();
         };
 
-        let future = self.request_limiter.stream(async move {
-            let api_key = api_key.context("Missing Mistral API Key")?;
-            let request =
-                mistral::stream_completion(http_client.as_ref(), &api_url, &api_key, request);
-            let response = request.await?;
-            Ok(response)
-        });
+        let future = self.request_limiter.stream(
+            priority,
+            timeouts.apply(async move {
+                let api_key = api_key.context("Missing Mistral API Key")?;
+                let request =
+                    mistral::stream_completion(http_client.as_ref(), &api_url, &api_key, request);
+                let response = request.await?;
+                Ok(response)
+            }),
+        );
 
-        async move { Ok(future.await?.boxed()) }.boxed()
+        async move { Ok(future.await.map_err(LanguageModelError::classify)?.boxed()) }.boxed()
     }
 }
 
@@ -365,12 +376,14 @@ impl LanguageModel for MistralLanguageModel {
             BoxStream<'static, Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
         >,
     > {
+        let priority = request.priority;
+        let timeouts = RequestTimeouts::for_request(&self.provider_id(), &request);
         let request = into_mistral(
             request,
             self.model.id().to_string(),
             self.max_output_tokens(),
         );
-        let stream = self.stream_completion(request, cx);
+        let stream = self.stream_completion(request, priority, timeouts, cx);
 
         async move {
             let stream = stream.await?;
@@ -379,6 +392,53 @@ impl LanguageModel for MistralLanguageModel {
         }
         .boxed()
     }
+
+    fn supports_fill_in_the_middle(&self) -> bool {
+        self.model.supports_fim()
+    }
+
+    fn fill_in_the_middle(
+        &self,
+        request: FillInTheMiddleRequest,
+        cx: &AsyncApp,
+    ) -> BoxFuture<'static, Result<String>> {
+        let request = mistral::FimRequest {
+            model: self.model.id().to_string(),
+            prompt: request.prefix,
+            suffix: Some(request.suffix),
+            max_tokens: request.max_tokens,
+            stop: request.stop,
+            temperature: request.temperature,
+        };
+
+        let http_client = self.http_client.clone();
+        let Ok((api_key, api_url)) = cx.read_entity(&self.state, |state, cx| {
+            let settings = &AllLanguageModelSettings::get_global(cx).mistral;
+            (state.api_key.clone(), settings.api_url.clone())
+        }) else {
+            return futures::future::ready(Err(anyhow!("App state dropped"))).boxed();
+        };
+
+        self.request_limiter
+            .run(RequestPriority::Interactive, async move {
+                let api_key = api_key.context("Missing Mistral API Key")?;
+                let response =
+                    mistral::fim_completion(http_client.as_ref(), &api_url, &api_key, request)
+                        .await?;
+                match response
+                    .choices
+                    .into_iter()
+                    .next()
+                    .map(|choice| choice.message)
+                {
+                    Some(mistral::RequestMessage::Assistant { content, .. }) => {
+                        Ok(content.unwrap_or_default())
+                    }
+                    _ => Err(anyhow!("Mistral returned no completion")),
+                }
+            })
+            .boxed()
+    }
 }
 
 pub fn into_mistral(
@@ -402,7 +462,7 @@ pub fn into_mistral(
                         Role::System => mistral::RequestMessage::System { content: text },
                     }),
                 MessageContent::RedactedThinking(_) => {}
-                MessageContent::Image(_) => {}
+                MessageContent::Image(_) | MessageContent::Document(_) => {}
                 MessageContent::ToolUse(tool_use) => {
                     let tool_call = mistral::ToolCall {
                         id: tool_use.id.to_string(),
@@ -574,6 +634,14 @@ impl MistralEventMapper {
                 "stop" => {
                     events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::EndTurn)));
                 }
+                "length" | "model_length" => {
+                    events.push(Ok(LanguageModelCompletionEvent::Stop(
+                        StopReason::MaxTokens,
+                    )));
+                }
+                "content_filter" => {
+                    events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::Refusal)));
+                }
                 "tool_calls" => {
                     events.extend(self.process_tool_calls());
                     events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::ToolUse)));
@@ -841,6 +909,8 @@ mod tests {
                 },
             ],
             temperature: Some(0.7),
+            seed: None,
+            priority: language_model::RequestPriority::Interactive,
             tools: Vec::new(),
             tool_choice: None,
             thread_id: None,
@@ -848,6 +918,7 @@ mod tests {
             intent: None,
             mode: None,
             stop: Vec::new(),
+            web_search: false,
         };
 
         let model_name = "mistral-medium-latest".to_string();
@@ -4,26 +4,31 @@ use futures::{Stream, TryFutureExt, stream};
 use gpui::{AnyView, App, AsyncApp, Context, Subscription, Task};
 use http_client::HttpClient;
 use language_model::{
-    AuthenticateError, LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent,
-    LanguageModelId, LanguageModelName, LanguageModelProvider, LanguageModelProviderId,
-    LanguageModelProviderName, LanguageModelProviderState, LanguageModelRequest,
+    AuthenticateError, FillInTheMiddleRequest, LanguageModel, LanguageModelCompletionError,
+    LanguageModelCompletionEvent, LanguageModelError, LanguageModelId, LanguageModelName,
+    LanguageModelProvider, LanguageModelProviderId, LanguageModelProviderName,
+    LanguageModelProviderState, LanguageModelRegistry, LanguageModelRequest,
     LanguageModelRequestTool, LanguageModelToolChoice, LanguageModelToolUse,
-    LanguageModelToolUseId, MessageContent, RateLimiter, Role, StopReason,
+    LanguageModelToolUseId, MessageContent, RateLimiter, RequestPriority, RequestTimeouts, Role,
+    StopReason,
 };
 use ollama::{
-    ChatMessage, ChatOptions, ChatRequest, ChatResponseDelta, KeepAlive, OllamaFunctionTool,
-    OllamaToolCall, get_models, show_model, stream_chat_completion,
+    ChatMessage, ChatOptions, ChatRequest, ChatResponseDelta, GenerateRequest, KeepAlive,
+    LoadModelRequest, OllamaFunctionTool, OllamaToolCall, generate, get_models, load_model,
+    show_model, stream_chat_completion,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::{Settings, SettingsStore};
 use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use std::{collections::HashMap, sync::Arc};
 use ui::{ButtonLike, Indicator, List, prelude::*};
 use util::ResultExt;
 
 use crate::AllLanguageModelSettings;
+use crate::provider_http::provider_http_client;
 use crate::ui::InstructionListItem;
 
 const OLLAMA_DOWNLOAD_URL: &str = "https://ollama.com/download";
@@ -37,6 +42,9 @@ const PROVIDER_NAME: &str = "Ollama";
 pub struct OllamaSettings {
     pub api_url: String,
     pub available_models: Vec<AvailableModel>,
+    /// How often the models selected for the agent and the inline assistant
+    /// are pinged to keep them loaded, if at all.
+    pub keep_alive_ping_interval: Option<Duration>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
@@ -55,6 +63,8 @@ pub struct AvailableModel {
     pub supports_images: Option<bool>,
     /// Whether to enable think mode
     pub supports_thinking: Option<bool>,
+    /// Whether the model supports fill-in-the-middle completions
+    pub supports_insert: Option<bool>,
 }
 
 pub struct OllamaLanguageModelProvider {
@@ -66,6 +76,7 @@ pub struct State {
     http_client: Arc<dyn HttpClient>,
     available_models: Vec<ollama::Model>,
     fetch_model_task: Option<Task<Result<()>>>,
+    keep_alive_task: Option<Task<()>>,
     _subscription: Subscription,
 }
 
@@ -102,6 +113,7 @@ impl State {
                             Some(capabilities.supports_tools()),
                             Some(capabilities.supports_vision()),
                             Some(capabilities.supports_thinking()),
+                            Some(capabilities.supports_insert()),
                         );
                         Ok(ollama_model)
                     }
@@ -130,6 +142,25 @@ impl State {
         self.fetch_model_task.replace(task);
     }
 
+    fn restart_keep_alive_task(&mut self, cx: &mut Context<Self>) {
+        let interval = AllLanguageModelSettings::get_global(cx)
+            .ollama
+            .keep_alive_ping_interval;
+        self.keep_alive_task = interval.map(|interval| {
+            cx.spawn(async move |_this, cx| {
+                loop {
+                    cx.background_executor().timer(interval).await;
+                    let Ok(models) = cx.update(|cx| selected_models(cx)) else {
+                        return;
+                    };
+                    for model in models {
+                        model.warm_up(cx).await.log_err();
+                    }
+                }
+            })
+        });
+    }
+
     fn authenticate(&mut self, cx: &mut Context<Self>) -> Task<Result<(), AuthenticateError>> {
         if self.is_authenticated() {
             return Task::ready(Ok(()));
@@ -142,6 +173,7 @@ impl State {
 
 impl OllamaLanguageModelProvider {
     pub fn new(http_client: Arc<dyn HttpClient>, cx: &mut App) -> Self {
+        let http_client = provider_http_client(http_client, PROVIDER_NAME);
         let this = Self {
             http_client: http_client.clone(),
             state: cx.new(|cx| {
@@ -152,6 +184,7 @@ impl OllamaLanguageModelProvider {
                         if &settings != new_settings {
                             settings = new_settings.clone();
                             this.restart_fetch_models_task(cx);
+                            this.restart_keep_alive_task(cx);
                             cx.notify();
                         }
                     }
@@ -161,12 +194,15 @@ impl OllamaLanguageModelProvider {
                     http_client,
                     available_models: Default::default(),
                     fetch_model_task: None,
+                    keep_alive_task: None,
                     _subscription: subscription,
                 }
             }),
         };
-        this.state
-            .update(cx, |state, cx| state.restart_fetch_models_task(cx));
+        this.state.update(cx, |state, cx| {
+            state.restart_fetch_models_task(cx);
+            state.restart_keep_alive_task(cx);
+        });
         this
     }
 }
@@ -224,6 +260,7 @@ impl LanguageModelProvider for OllamaLanguageModelProvider {
                     supports_tools: model.supports_tools,
                     supports_vision: model.supports_images,
                     supports_thinking: model.supports_thinking,
+                    supports_insert: model.supports_insert,
                 },
             );
         }
@@ -235,7 +272,8 @@ impl LanguageModelProvider for OllamaLanguageModelProvider {
                     id: LanguageModelId::from(model.name.clone()),
                     model: model.clone(),
                     http_client: self.http_client.clone(),
-                    request_limiter: RateLimiter::new(4),
+                    request_limiter: RateLimiter::new(4)
+                        .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
                 }) as Arc<dyn LanguageModel>
             })
             .collect::<Vec<_>>();
@@ -262,6 +300,24 @@ impl LanguageModelProvider for OllamaLanguageModelProvider {
     }
 }
 
+/// The Ollama models selected for the agent and the inline assistant.
+fn selected_models(cx: &App) -> Vec<Arc<dyn LanguageModel>> {
+    let registry = LanguageModelRegistry::read_global(cx);
+    let provider_id = LanguageModelProviderId(PROVIDER_ID.into());
+    let mut models: Vec<Arc<dyn LanguageModel>> = Vec::new();
+    for model in [registry.default_model(), registry.inline_assistant_model()]
+        .into_iter()
+        .flatten()
+    {
+        if model.model.provider_id() == provider_id
+            && !models.iter().any(|other| other.id() == model.model.id())
+        {
+            models.push(model.model);
+        }
+    }
+    models
+}
+
 pub struct OllamaLanguageModel {
     id: LanguageModelId,
     model: ollama::Model,
@@ -408,6 +464,8 @@ impl LanguageModel for OllamaLanguageModel {
             BoxStream<'static, Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
         >,
     > {
+        let priority = request.priority;
+        let timeouts = RequestTimeouts::for_request(&self.provider_id(), &request);
         let request = self.to_ollama_request(request);
 
         let http_client = self.http_client.clone();
@@ -418,13 +476,81 @@ impl LanguageModel for OllamaLanguageModel {
             return futures::future::ready(Err(anyhow!("App state dropped"))).boxed();
         };
 
-        let future = self.request_limiter.stream(async move {
-            let stream = stream_chat_completion(http_client.as_ref(), &api_url, request).await?;
-            let stream = map_to_language_model_completion_events(stream);
-            Ok(stream)
-        });
+        let future = self.request_limiter.stream(
+            priority,
+            timeouts.apply(async move {
+                let stream =
+                    stream_chat_completion(http_client.as_ref(), &api_url, request).await?;
+                let stream = map_to_language_model_completion_events(stream);
+                Ok(stream)
+            }),
+        );
+
+        future
+            .map_ok(|f| f.boxed())
+            .map_err(LanguageModelError::classify)
+            .boxed()
+    }
 
-        future.map_ok(|f| f.boxed()).boxed()
+    fn supports_fill_in_the_middle(&self) -> bool {
+        self.model.supports_insert.unwrap_or(false)
+    }
+
+    fn fill_in_the_middle(
+        &self,
+        request: FillInTheMiddleRequest,
+        cx: &AsyncApp,
+    ) -> BoxFuture<'static, Result<String>> {
+        let request = GenerateRequest {
+            model: self.model.name.clone(),
+            prompt: request.prefix,
+            suffix: Some(request.suffix),
+            stream: false,
+            keep_alive: self.model.keep_alive.clone().unwrap_or_default(),
+            options: Some(ChatOptions {
+                num_ctx: Some(self.model.max_tokens),
+                num_predict: request.max_tokens.map(|max_tokens| max_tokens as isize),
+                stop: Some(request.stop),
+                temperature: request.temperature,
+                ..Default::default()
+            }),
+        };
+
+        let http_client = self.http_client.clone();
+        let Ok(api_url) = cx.update(|cx| {
+            let settings = &AllLanguageModelSettings::get_global(cx).ollama;
+            settings.api_url.clone()
+        }) else {
+            return futures::future::ready(Err(anyhow!("App state dropped"))).boxed();
+        };
+
+        self.request_limiter
+            .run(RequestPriority::Interactive, async move {
+                let response = generate(http_client.as_ref(), &api_url, request).await?;
+                Ok(response.response)
+            })
+            .boxed()
+    }
+
+    fn warm_up(&self, cx: &AsyncApp) -> BoxFuture<'static, Result<()>> {
+        let request = LoadModelRequest {
+            model: self.model.name.clone(),
+            keep_alive: self.model.keep_alive.clone().unwrap_or_default(),
+        };
+
+        let http_client = self.http_client.clone();
+        let Ok(api_url) = cx.update(|cx| {
+            let settings = &AllLanguageModelSettings::get_global(cx).ollama;
+            settings.api_url.clone()
+        }) else {
+            return futures::future::ready(Err(anyhow!("App state dropped"))).boxed();
+        };
+
+        self.request_limiter
+            .run(RequestPriority::Background, async move {
+                load_model(http_client.as_ref(), &api_url, request).await
+            })
+            .boxed()
     }
 }
 
@@ -10,24 +10,31 @@ use gpui::{
 use http_client::HttpClient;
 use language_model::{
     AuthenticateError, LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent,
-    LanguageModelId, LanguageModelName, LanguageModelProvider, LanguageModelProviderId,
-    LanguageModelProviderName, LanguageModelProviderState, LanguageModelRequest,
-    LanguageModelToolChoice, LanguageModelToolResultContent, LanguageModelToolUse, MessageContent,
-    RateLimiter, Role, StopReason,
+    LanguageModelError, LanguageModelId, LanguageModelName, LanguageModelPricing,
+    LanguageModelProvider, LanguageModelProviderId, LanguageModelProviderName,
+    LanguageModelProviderState, LanguageModelRequest, LanguageModelToolChoice,
+    LanguageModelToolResultContent, MessageContent, RateLimiter, RequestPriority, RequestTimeouts,
+    Role, StopReason, TokenUsage, WebSource,
 };
+use open_ai::responses::{self, InputContent, InputItem, InputRole};
 use open_ai::{ImageUrl, Model, ResponseStreamEvent, stream_completion};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::{Settings, SettingsStore};
+use std::hash::{DefaultHasher, Hash as _, Hasher as _};
 use std::pin::Pin;
-use std::str::FromStr as _;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use strum::IntoEnumIterator;
 use theme::ThemeSettings;
 use ui::{Icon, IconName, List, Tooltip, prelude::*};
 use util::ResultExt;
 
-use crate::{AllLanguageModelSettings, ui::InstructionListItem};
+use crate::tool_call_format::{
+    OPEN_AI_TOOL_IMAGE_PLACEHOLDER, OpenAiToolFormat, ToolCallFormat, parse_tool_input,
+};
+use crate::{
+    AllLanguageModelSettings, provider_http::provider_http_client, ui::InstructionListItem,
+};
 
 const PROVIDER_ID: &str = "openai";
 const PROVIDER_NAME: &str = "OpenAI";
@@ -36,6 +43,8 @@ const PROVIDER_NAME: &str = "OpenAI";
 pub struct OpenAiSettings {
     pub api_url: String,
     pub available_models: Vec<AvailableModel>,
+    /// The ids of the models whose requests go through the Responses API.
+    pub responses_api_models: Vec<String>,
     pub needs_setting_migration: bool,
 }
 
@@ -51,6 +60,7 @@ pub struct AvailableModel {
 pub struct OpenAiLanguageModelProvider {
     http_client: Arc<dyn HttpClient>,
     state: gpui::Entity<State>,
+    stored_responses: StoredResponses,
 }
 
 pub struct State {
@@ -139,6 +149,7 @@ impl State {
 
 impl OpenAiLanguageModelProvider {
     pub fn new(http_client: Arc<dyn HttpClient>, cx: &mut App) -> Self {
+        let http_client = provider_http_client(http_client, PROVIDER_NAME);
         let state = cx.new(|cx| State {
             api_key: None,
             api_key_from_env: false,
@@ -147,16 +158,28 @@ impl OpenAiLanguageModelProvider {
             }),
         });
 
-        Self { http_client, state }
+        Self {
+            http_client,
+            state,
+            stored_responses: StoredResponses::default(),
+        }
     }
 
-    fn create_language_model(&self, model: open_ai::Model) -> Arc<dyn LanguageModel> {
+    fn create_language_model(&self, model: open_ai::Model, cx: &App) -> Arc<dyn LanguageModel> {
+        let uses_responses_api = AllLanguageModelSettings::get_global(cx)
+            .openai
+            .responses_api_models
+            .iter()
+            .any(|id| id == model.id());
         Arc::new(OpenAiLanguageModel {
             id: LanguageModelId::from(model.id().to_string()),
             model,
+            uses_responses_api,
             state: self.state.clone(),
             http_client: self.http_client.clone(),
-            request_limiter: RateLimiter::new(4),
+            stored_responses: self.stored_responses.clone(),
+            request_limiter: RateLimiter::new(4)
+                .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
         })
     }
 }
@@ -182,12 +205,12 @@ impl LanguageModelProvider for OpenAiLanguageModelProvider {
         IconName::AiOpenAi
     }
 
-    fn default_model(&self, _cx: &App) -> Option<Arc<dyn LanguageModel>> {
-        Some(self.create_language_model(open_ai::Model::default()))
+    fn default_model(&self, cx: &App) -> Option<Arc<dyn LanguageModel>> {
+        Some(self.create_language_model(open_ai::Model::default(), cx))
     }
 
-    fn default_fast_model(&self, _cx: &App) -> Option<Arc<dyn LanguageModel>> {
-        Some(self.create_language_model(open_ai::Model::default_fast()))
+    fn default_fast_model(&self, cx: &App) -> Option<Arc<dyn LanguageModel>> {
+        Some(self.create_language_model(open_ai::Model::default_fast(), cx))
     }
 
     fn provided_models(&self, cx: &App) -> Vec<Arc<dyn LanguageModel>> {
@@ -219,7 +242,7 @@ impl LanguageModelProvider for OpenAiLanguageModelProvider {
 
         models
             .into_values()
-            .map(|model| self.create_language_model(model))
+            .map(|model| self.create_language_model(model, cx))
             .collect()
     }
 
@@ -244,8 +267,12 @@ impl LanguageModelProvider for OpenAiLanguageModelProvider {
 pub struct OpenAiLanguageModel {
     id: LanguageModelId,
     model: open_ai::Model,
+    /// Whether requests go through the Responses API, rather than through
+    /// Chat Completions.
+    uses_responses_api: bool,
     state: gpui::Entity<State>,
     http_client: Arc<dyn HttpClient>,
+    stored_responses: StoredResponses,
     request_limiter: RateLimiter,
 }
 
@@ -253,6 +280,8 @@ impl OpenAiLanguageModel {
     fn stream_completion(
         &self,
         request: open_ai::Request,
+        priority: RequestPriority,
+        timeouts: RequestTimeouts,
         cx: &AsyncApp,
     ) -> BoxFuture<'static, Result<futures::stream::BoxStream<'static, Result<ResponseStreamEvent>>>>
     {
@@ -264,14 +293,49 @@ impl OpenAiLanguageModel {
             return futures::future::ready(Err(anyhow!("App state dropped"))).boxed();
         };
 
-        let future = self.request_limiter.stream(async move {
-            let api_key = api_key.context("Missing OpenAI API Key")?;
-            let request = stream_completion(http_client.as_ref(), &api_url, &api_key, request);
-            let response = request.await?;
-            Ok(response)
-        });
+        let future = self.request_limiter.stream(
+            priority,
+            timeouts.apply(async move {
+                let api_key = api_key.context("Missing OpenAI API Key")?;
+                let request = stream_completion(http_client.as_ref(), &api_url, &api_key, request);
+                let response = request.await?;
+                Ok(response)
+            }),
+        );
 
-        async move { Ok(future.await?.boxed()) }.boxed()
+        async move { Ok(future.await.map_err(LanguageModelError::classify)?.boxed()) }.boxed()
+    }
+
+    fn stream_response(
+        &self,
+        request: responses::Request,
+        priority: RequestPriority,
+        timeouts: RequestTimeouts,
+        cx: &AsyncApp,
+    ) -> BoxFuture<
+        'static,
+        Result<futures::stream::BoxStream<'static, Result<responses::StreamEvent>>>,
+    > {
+        let http_client = self.http_client.clone();
+        let Ok((api_key, api_url)) = cx.read_entity(&self.state, |state, cx| {
+            let settings = &AllLanguageModelSettings::get_global(cx).openai;
+            (state.api_key.clone(), settings.api_url.clone())
+        }) else {
+            return futures::future::ready(Err(anyhow!("App state dropped"))).boxed();
+        };
+
+        let future = self.request_limiter.stream(
+            priority,
+            timeouts.apply(async move {
+                let api_key = api_key.context("Missing OpenAI API Key")?;
+                let request =
+                    responses::stream_response(http_client.as_ref(), &api_url, &api_key, request);
+                let response = request.await?;
+                Ok(response)
+            }),
+        );
+
+        async move { Ok(future.await.map_err(LanguageModelError::classify)?.boxed()) }.boxed()
     }
 }
 
@@ -300,6 +364,18 @@ impl LanguageModel for OpenAiLanguageModel {
         false
     }
 
+    fn supports_seed(&self) -> bool {
+        true
+    }
+
+    fn supports_web_search(&self) -> bool {
+        if self.uses_responses_api {
+            self.model.supports_web_search_tool()
+        } else {
+            self.model.supports_web_search()
+        }
+    }
+
     fn supports_tool_choice(&self, choice: LanguageModelToolChoice) -> bool {
         match choice {
             LanguageModelToolChoice::Auto => true,
@@ -320,6 +396,28 @@ impl LanguageModel for OpenAiLanguageModel {
         self.model.max_output_tokens()
     }
 
+    /// OpenAI's list prices. Cached input tokens are counted at the regular
+    /// input price.
+    fn pricing(&self) -> Option<LanguageModelPricing> {
+        let (input, output) = match self.model {
+            open_ai::Model::ThreePointFiveTurbo => (0.5, 1.5),
+            open_ai::Model::Four => (30., 60.),
+            open_ai::Model::FourTurbo => (10., 30.),
+            open_ai::Model::FourOmni | open_ai::Model::FourOmniSearchPreview => (2.5, 10.),
+            open_ai::Model::FourOmniMini | open_ai::Model::FourOmniMiniSearchPreview => (0.15, 0.6),
+            open_ai::Model::FourPointOne | open_ai::Model::O3 => (2., 8.),
+            open_ai::Model::FourPointOneMini => (0.4, 1.6),
+            open_ai::Model::FourPointOneNano => (0.1, 0.4),
+            open_ai::Model::O1 | open_ai::Model::O1Preview => (15., 60.),
+            open_ai::Model::O1Mini | open_ai::Model::O3Mini | open_ai::Model::O4Mini => (1.1, 4.4),
+            open_ai::Model::Custom { .. } => return None,
+        };
+        Some(LanguageModelPricing {
+            input_cost_per_million_tokens: input,
+            output_cost_per_million_tokens: output,
+        })
+    }
+
     fn count_tokens(
         &self,
         request: LanguageModelRequest,
@@ -341,8 +439,23 @@ impl LanguageModel for OpenAiLanguageModel {
             >,
         >,
     > {
+        let priority = request.priority;
+        let timeouts = RequestTimeouts::for_request(&self.provider_id(), &request);
+        if self.uses_responses_api {
+            let thread_id = request.thread_id.clone();
+            let mut request =
+                into_open_ai_responses(request, &self.model, self.max_output_tokens());
+            let pending_response = self.stored_responses.prepare(thread_id, &mut request);
+            let response = self.stream_response(request, priority, timeouts, cx);
+            return async move {
+                let mapper = OpenAiResponsesEventMapper::new(pending_response);
+                Ok(mapper.map_stream(response.await?).boxed())
+            }
+            .boxed();
+        }
+
         let request = into_open_ai(request, &self.model, self.max_output_tokens());
-        let completions = self.stream_completion(request, cx);
+        let completions = self.stream_completion(request, priority, timeouts, cx);
         async move {
             let mapper = OpenAiEventMapper::new();
             Ok(mapper.map_stream(completions.await?).boxed())
@@ -360,6 +473,9 @@ pub fn into_open_ai(
 
     let mut messages = Vec::new();
     for message in request.messages {
+        // Tool messages can only contain text, so the images returned by
+        // tools are sent in a user message after them.
+        let mut tool_result_images = Vec::new();
         for content in message.content {
             match content {
                 MessageContent::Text(text) | MessageContent::Thinking { text, .. } => {
@@ -369,7 +485,7 @@ pub fn into_open_ai(
                         &mut messages,
                     )
                 }
-                MessageContent::RedactedThinking(_) => {}
+                MessageContent::RedactedThinking(_) | MessageContent::Document(_) => {}
                 MessageContent::Image(image) => {
                     add_message_content_part(
                         open_ai::MessagePart::Image {
@@ -383,16 +499,7 @@ pub fn into_open_ai(
                     );
                 }
                 MessageContent::ToolUse(tool_use) => {
-                    let tool_call = open_ai::ToolCall {
-                        id: tool_use.id.to_string(),
-                        content: open_ai::ToolCallContent::Function {
-                            function: open_ai::FunctionContent {
-                                name: tool_use.name.to_string(),
-                                arguments: serde_json::to_string(&tool_use.input)
-                                    .unwrap_or_default(),
-                            },
-                        },
-                    };
+                    let tool_call = OpenAiToolFormat::to_tool_call(&tool_use);
 
                     if let Some(open_ai::RequestMessage::Assistant { tool_calls, .. }) =
                         messages.last_mut()
@@ -406,29 +513,31 @@ pub fn into_open_ai(
                     }
                 }
                 MessageContent::ToolResult(tool_result) => {
-                    let content = match &tool_result.content {
-                        LanguageModelToolResultContent::Text(text) => {
-                            vec![open_ai::MessagePart::Text {
-                                text: text.to_string(),
-                            }]
-                        }
-                        LanguageModelToolResultContent::Image(image) => {
-                            vec![open_ai::MessagePart::Image {
-                                image_url: ImageUrl {
-                                    url: image.to_base64_url(),
-                                    detail: None,
-                                },
-                            }]
-                        }
-                    };
-
-                    messages.push(open_ai::RequestMessage::Tool {
-                        content: content.into(),
-                        tool_call_id: tool_result.tool_use_id.to_string(),
-                    });
+                    if let LanguageModelToolResultContent::Image(image) = &tool_result.content {
+                        tool_result_images
+                            .push((tool_result.tool_use_id.clone(), image.to_base64_url()));
+                    }
+                    messages.push(OpenAiToolFormat::to_tool_result(&tool_result));
                 }
             }
         }
+
+        for (tool_use_id, url) in tool_result_images {
+            add_message_content_part(
+                open_ai::MessagePart::Text {
+                    text: format!("Image returned by tool call {tool_use_id}:"),
+                },
+                Role::User,
+                &mut messages,
+            );
+            add_message_content_part(
+                open_ai::MessagePart::Image {
+                    image_url: ImageUrl { url, detail: None },
+                },
+                Role::User,
+                &mut messages,
+            );
+        }
     }
 
     open_ai::Request {
@@ -436,7 +545,8 @@ pub fn into_open_ai(
         messages,
         stream,
         stop: request.stop,
-        temperature: request.temperature.unwrap_or(1.0),
+        temperature: (!model.supports_web_search()).then(|| request.temperature.unwrap_or(1.0)),
+        seed: request.seed,
         max_tokens: max_output_tokens,
         parallel_tool_calls: if model.supports_parallel_tool_calls() && !request.tools.is_empty() {
             // Disable parallel tool calls, as the Agent currently expects a maximum of one per turn.
@@ -447,19 +557,15 @@ pub fn into_open_ai(
         tools: request
             .tools
             .into_iter()
-            .map(|tool| open_ai::ToolDefinition::Function {
-                function: open_ai::FunctionDefinition {
-                    name: tool.name,
-                    description: Some(tool.description),
-                    parameters: Some(tool.input_schema),
-                },
-            })
+            .map(OpenAiToolFormat::to_tool_definition)
             .collect(),
         tool_choice: request.tool_choice.map(|choice| match choice {
             LanguageModelToolChoice::Auto => open_ai::ToolChoice::Auto,
             LanguageModelToolChoice::Any => open_ai::ToolChoice::Required,
             LanguageModelToolChoice::None => open_ai::ToolChoice::None,
         }),
+        web_search_options: (request.web_search && model.supports_web_search())
+            .then(open_ai::WebSearchOptions::default),
     }
 }
 
@@ -536,6 +642,22 @@ impl OpenAiEventMapper {
             events.push(Ok(LanguageModelCompletionEvent::Text(content)));
         }
 
+        if let Some(annotations) = choice.delta.annotations.as_ref() {
+            let sources = annotations
+                .iter()
+                .filter_map(|annotation| match annotation {
+                    open_ai::Annotation::UrlCitation { url_citation } => Some(WebSource {
+                        url: url_citation.url.clone(),
+                        title: url_citation.title.clone(),
+                    }),
+                    open_ai::Annotation::Other => None,
+                })
+                .collect::<Vec<_>>();
+            if !sources.is_empty() {
+                events.push(Ok(LanguageModelCompletionEvent::WebSources(sources)));
+            }
+        }
+
         if let Some(tool_calls) = choice.delta.tool_calls.as_ref() {
             for tool_call in tool_calls {
                 let entry = self.tool_calls_by_index.entry(tool_call.index).or_default();
@@ -560,25 +682,26 @@ impl OpenAiEventMapper {
             Some("stop") => {
                 events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::EndTurn)));
             }
+            Some("length") => {
+                events.push(Ok(LanguageModelCompletionEvent::Stop(
+                    StopReason::MaxTokens,
+                )));
+            }
+            Some("content_filter") => {
+                events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::Refusal)));
+            }
             Some("tool_calls") => {
                 events.extend(self.tool_calls_by_index.drain().map(|(_, tool_call)| {
-                    match serde_json::Value::from_str(&tool_call.arguments) {
-                        Ok(input) => Ok(LanguageModelCompletionEvent::ToolUse(
-                            LanguageModelToolUse {
-                                id: tool_call.id.clone().into(),
-                                name: tool_call.name.as_str().into(),
-                                is_input_complete: true,
-                                input,
-                                raw_input: tool_call.arguments.clone(),
+                    OpenAiToolFormat::from_tool_call(open_ai::ToolCall {
+                        id: tool_call.id,
+                        content: open_ai::ToolCallContent::Function {
+                            function: open_ai::FunctionContent {
+                                name: tool_call.name,
+                                arguments: tool_call.arguments,
                             },
-                        )),
-                        Err(error) => Err(LanguageModelCompletionError::BadInputJson {
-                            id: tool_call.id.into(),
-                            tool_name: tool_call.name.as_str().into(),
-                            raw_input: tool_call.arguments.into(),
-                            json_parse_error: error.to_string(),
-                        }),
-                    }
+                        },
+                    })
+                    .map(LanguageModelCompletionEvent::ToolUse)
                 }));
 
                 events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::ToolUse)));
@@ -594,6 +717,401 @@ impl OpenAiEventMapper {
     }
 }
 
+pub fn into_open_ai_responses(
+    request: LanguageModelRequest,
+    model: &Model,
+    max_output_tokens: Option<u32>,
+) -> responses::Request {
+    let mut instructions = Vec::new();
+    let mut input = Vec::new();
+    for message in request.messages {
+        let mut tool_result_images = Vec::new();
+        for content in message.content {
+            match content {
+                MessageContent::Text(text) => match message.role {
+                    Role::System => instructions.push(text),
+                    Role::User => add_input_content(
+                        InputContent::InputText { text },
+                        InputRole::User,
+                        &mut input,
+                    ),
+                    Role::Assistant => add_input_content(
+                        InputContent::OutputText { text },
+                        InputRole::Assistant,
+                        &mut input,
+                    ),
+                },
+                // Reasoning is kept on the server, along with the response it
+                // led to.
+                MessageContent::Thinking { .. } | MessageContent::RedactedThinking(_) => {}
+                MessageContent::Document(_) => {}
+                MessageContent::Image(image) => {
+                    if message.role == Role::User {
+                        add_input_content(
+                            InputContent::InputImage {
+                                image_url: image.to_base64_url(),
+                            },
+                            InputRole::User,
+                            &mut input,
+                        );
+                    }
+                }
+                MessageContent::ToolUse(tool_use) => input.push(InputItem::FunctionCall {
+                    call_id: tool_use.id.to_string(),
+                    name: tool_use.name.to_string(),
+                    arguments: serde_json::to_string(&tool_use.input).unwrap_or_default(),
+                }),
+                MessageContent::ToolResult(tool_result) => {
+                    let output = match &tool_result.content {
+                        LanguageModelToolResultContent::Text(text) => text.to_string(),
+                        LanguageModelToolResultContent::Image(image) => {
+                            tool_result_images
+                                .push((tool_result.tool_use_id.clone(), image.to_base64_url()));
+                            OPEN_AI_TOOL_IMAGE_PLACEHOLDER.into()
+                        }
+                    };
+                    input.push(InputItem::FunctionCallOutput {
+                        call_id: tool_result.tool_use_id.to_string(),
+                        output,
+                    });
+                }
+            }
+        }
+
+        for (tool_use_id, image_url) in tool_result_images {
+            add_input_content(
+                InputContent::InputText {
+                    text: format!("Image returned by tool call {tool_use_id}:"),
+                },
+                InputRole::User,
+                &mut input,
+            );
+            add_input_content(
+                InputContent::InputImage { image_url },
+                InputRole::User,
+                &mut input,
+            );
+        }
+    }
+
+    let mut tools = request
+        .tools
+        .into_iter()
+        .map(|tool| responses::Tool::Function {
+            name: tool.name,
+            description: Some(tool.description),
+            parameters: tool.input_schema,
+        })
+        .collect::<Vec<_>>();
+    if request.web_search && model.supports_web_search_tool() {
+        tools.push(responses::Tool::WebSearchPreview);
+    }
+
+    responses::Request {
+        model: model.id().into(),
+        input,
+        instructions: (!instructions.is_empty()).then(|| instructions.join("\n\n")),
+        previous_response_id: None,
+        store: request.thread_id.is_some(),
+        stream: true,
+        temperature: (!model.is_reasoning_model()).then(|| request.temperature.unwrap_or(1.0)),
+        max_output_tokens,
+        parallel_tool_calls: if model.supports_parallel_tool_calls() && !tools.is_empty() {
+            // Disable parallel tool calls, as the Agent currently expects a maximum of one per turn.
+            Some(false)
+        } else {
+            None
+        },
+        tools,
+        tool_choice: request.tool_choice.map(|choice| match choice {
+            LanguageModelToolChoice::Auto => responses::ToolChoice::Auto,
+            LanguageModelToolChoice::Any => responses::ToolChoice::Required,
+            LanguageModelToolChoice::None => responses::ToolChoice::None,
+        }),
+        reasoning: model.is_reasoning_model().then(|| responses::Reasoning {
+            effort: None,
+            summary: Some(responses::ReasoningSummary::Auto),
+        }),
+    }
+}
+
+fn add_input_content(content: InputContent, role: InputRole, input: &mut Vec<InputItem>) {
+    match input.last_mut() {
+        Some(InputItem::Message {
+            role: last_role,
+            content: last_content,
+        }) if *last_role == role => last_content.push(content),
+        _ => input.push(InputItem::Message {
+            role,
+            content: vec![content],
+        }),
+    }
+}
+
+/// The latest response stored on the server for each thread, so that the
+/// thread's next request can continue it rather than resend the whole thread.
+#[derive(Clone, Default)]
+struct StoredResponses(Arc<Mutex<HashMap<String, StoredResponse>>>);
+
+struct StoredResponse {
+    id: String,
+    /// The number of input items the response was given, and their hash.
+    input_len: usize,
+    input_hash: u64,
+}
+
+/// A response to store once it completes.
+struct PendingResponse {
+    stored_responses: StoredResponses,
+    thread_id: String,
+    input_len: usize,
+    input_hash: u64,
+}
+
+impl StoredResponses {
+    /// Continues the thread's stored response when the request extends its
+    /// input, leaving only the items the server hasn't seen in the request.
+    fn prepare(
+        &self,
+        thread_id: Option<String>,
+        request: &mut responses::Request,
+    ) -> Option<PendingResponse> {
+        let thread_id = thread_id?;
+        let pending = PendingResponse {
+            stored_responses: self.clone(),
+            input_len: request.input.len(),
+            input_hash: hash_input(&request.input),
+            thread_id,
+        };
+
+        if let Ok(stored_responses) = self.0.lock() {
+            if let Some(stored) = stored_responses.get(&pending.thread_id) {
+                let input = &request.input;
+                let continues_stored = input.len() > stored.input_len
+                    && hash_input(&input[..stored.input_len]) == stored.input_hash;
+                // The server already has the stored response's own output.
+                let new_input_start = input
+                    .iter()
+                    .skip(stored.input_len)
+                    .position(|item| !is_output(item))
+                    .map(|ix| stored.input_len + ix);
+                if let Some(new_input_start) = new_input_start.filter(|_| continues_stored) {
+                    request.input.drain(..new_input_start);
+                    request.previous_response_id = Some(stored.id.clone());
+                }
+            }
+        }
+
+        Some(pending)
+    }
+}
+
+impl PendingResponse {
+    fn store(self, id: String) {
+        if let Ok(mut stored_responses) = self.stored_responses.0.lock() {
+            stored_responses.insert(
+                self.thread_id,
+                StoredResponse {
+                    id,
+                    input_len: self.input_len,
+                    input_hash: self.input_hash,
+                },
+            );
+        }
+    }
+}
+
+fn hash_input(input: &[InputItem]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    input.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn is_output(item: &InputItem) -> bool {
+    matches!(
+        item,
+        InputItem::Message {
+            role: InputRole::Assistant,
+            ..
+        } | InputItem::FunctionCall { .. }
+    )
+}
+
+struct OpenAiResponsesEventMapper {
+    tool_calls_by_index: HashMap<usize, RawToolCall>,
+    has_tool_calls: bool,
+    pending_response: Option<PendingResponse>,
+}
+
+impl OpenAiResponsesEventMapper {
+    fn new(pending_response: Option<PendingResponse>) -> Self {
+        Self {
+            tool_calls_by_index: HashMap::default(),
+            has_tool_calls: false,
+            pending_response,
+        }
+    }
+
+    fn map_stream(
+        mut self,
+        events: Pin<Box<dyn Send + Stream<Item = Result<responses::StreamEvent>>>>,
+    ) -> impl Stream<Item = Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>
+    {
+        events.flat_map(move |event| {
+            futures::stream::iter(match event {
+                Ok(event) => self.map_event(event),
+                Err(error) => vec![Err(LanguageModelCompletionError::Other(anyhow!(error)))],
+            })
+        })
+    }
+
+    fn map_event(
+        &mut self,
+        event: responses::StreamEvent,
+    ) -> Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
+        use responses::StreamEvent;
+
+        match event {
+            StreamEvent::Created { response } => {
+                vec![Ok(LanguageModelCompletionEvent::StartMessage {
+                    message_id: response.id,
+                })]
+            }
+            StreamEvent::OutputItemAdded {
+                output_index,
+                item:
+                    responses::OutputItem::FunctionCall {
+                        call_id,
+                        name,
+                        arguments,
+                        ..
+                    },
+            } => {
+                self.tool_calls_by_index.insert(
+                    output_index,
+                    RawToolCall {
+                        id: call_id,
+                        name,
+                        arguments,
+                    },
+                );
+                Vec::new()
+            }
+            StreamEvent::FunctionCallArgumentsDelta {
+                output_index,
+                delta,
+            } => {
+                if let Some(tool_call) = self.tool_calls_by_index.get_mut(&output_index) {
+                    tool_call.arguments.push_str(&delta);
+                }
+                Vec::new()
+            }
+            StreamEvent::OutputItemDone {
+                output_index,
+                item:
+                    responses::OutputItem::FunctionCall {
+                        call_id,
+                        name,
+                        arguments,
+                        ..
+                    },
+            } => {
+                let arguments = match self.tool_calls_by_index.remove(&output_index) {
+                    Some(tool_call) if arguments.is_empty() => tool_call.arguments,
+                    _ => arguments,
+                };
+                self.has_tool_calls = true;
+                vec![
+                    parse_tool_input(call_id.into(), name.into(), arguments)
+                        .map(LanguageModelCompletionEvent::ToolUse),
+                ]
+            }
+            StreamEvent::OutputTextDelta { delta, .. } => {
+                vec![Ok(LanguageModelCompletionEvent::Text(delta))]
+            }
+            StreamEvent::OutputTextAnnotationAdded {
+                annotation: responses::Annotation::UrlCitation { url, title },
+            } => vec![Ok(LanguageModelCompletionEvent::WebSources(vec![
+                WebSource { url, title },
+            ]))],
+            StreamEvent::ReasoningSummaryTextDelta { delta, .. } => {
+                vec![Ok(LanguageModelCompletionEvent::Thinking {
+                    text: delta,
+                    signature: None,
+                })]
+            }
+            StreamEvent::ReasoningSummaryPartDone { .. } => {
+                vec![Ok(LanguageModelCompletionEvent::Thinking {
+                    text: "\n\n".into(),
+                    signature: None,
+                })]
+            }
+            StreamEvent::Completed { response } => {
+                let mut events = usage_events(&response);
+                if let Some(pending_response) = self.pending_response.take() {
+                    pending_response.store(response.id);
+                }
+                let stop_reason = if self.has_tool_calls {
+                    StopReason::ToolUse
+                } else {
+                    StopReason::EndTurn
+                };
+                events.push(Ok(LanguageModelCompletionEvent::Stop(stop_reason)));
+                events
+            }
+            StreamEvent::Incomplete { response } => {
+                let mut events = usage_events(&response);
+                let stop_reason = match response
+                    .incomplete_details
+                    .as_ref()
+                    .map(|details| details.reason.as_str())
+                {
+                    Some("max_output_tokens") => StopReason::MaxTokens,
+                    Some("content_filter") => StopReason::Refusal,
+                    reason => {
+                        log::error!("Unexpected OpenAI incomplete reason: {reason:?}");
+                        StopReason::EndTurn
+                    }
+                };
+                events.push(Ok(LanguageModelCompletionEvent::Stop(stop_reason)));
+                events
+            }
+            StreamEvent::Failed { response } => {
+                let message = response
+                    .error
+                    .map(|error| error.message)
+                    .unwrap_or_else(|| "Response failed".into());
+                vec![Err(LanguageModelCompletionError::Other(anyhow!(message)))]
+            }
+            StreamEvent::Error { message, .. } => {
+                vec![Err(LanguageModelCompletionError::Other(anyhow!(message)))]
+            }
+            StreamEvent::OutputItemAdded { .. }
+            | StreamEvent::OutputItemDone { .. }
+            | StreamEvent::OutputTextAnnotationAdded { .. }
+            | StreamEvent::Unknown => Vec::new(),
+        }
+    }
+}
+
+fn usage_events(
+    response: &responses::Response,
+) -> Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
+    let Some(usage) = response.usage.as_ref() else {
+        return Vec::new();
+    };
+    let cached_tokens = usage
+        .input_tokens_details
+        .as_ref()
+        .map_or(0, |details| details.cached_tokens);
+    vec![Ok(LanguageModelCompletionEvent::UsageUpdate(TokenUsage {
+        input_tokens: usage.input_tokens.saturating_sub(cached_tokens),
+        output_tokens: usage.output_tokens,
+        cache_creation_input_tokens: 0,
+        cache_read_input_tokens: cached_tokens,
+    }))]
+}
+
 #[derive(Default)]
 struct RawToolCall {
     id: String,
@@ -634,6 +1152,10 @@ pub fn count_open_ai_tokens(
                 };
                 tiktoken_rs::num_tokens_from_messages(model, &messages)
             }
+            // The search models use the same tokenizer as the models they're based on.
+            Model::FourOmniSearchPreview | Model::FourOmniMiniSearchPreview => {
+                tiktoken_rs::num_tokens_from_messages("gpt-4o", &messages)
+            }
             // Currently supported by tiktoken_rs
             // Sometimes tiktoken-rs is behind on model support. If that is the case, make a new branch
             // arm with an override. We enumerate all supported models here so that we can check if new
@@ -851,7 +1373,10 @@ impl Render for ConfigurationView {
 #[cfg(test)]
 mod tests {
     use gpui::TestAppContext;
-    use language_model::LanguageModelRequestMessage;
+    use language_model::{
+        LanguageModelImage, LanguageModelRequestMessage, LanguageModelToolResult,
+        LanguageModelToolUse,
+    };
 
     use super::*;
 
@@ -871,6 +1396,9 @@ mod tests {
             tool_choice: None,
             stop: vec![],
             temperature: None,
+            seed: None,
+            priority: RequestPriority::Interactive,
+            web_search: false,
         };
 
         // Validate that all models are supported by tiktoken-rs
@@ -886,4 +1414,221 @@ mod tests {
             assert!(count > 0);
         }
     }
+
+    #[test]
+    fn test_tool_result_images() {
+        let image = LanguageModelImage {
+            source: "aW1hZ2U=".into(),
+            size: gpui::size(gpui::DevicePixels(1), gpui::DevicePixels(1)),
+        };
+        let request = LanguageModelRequest {
+            thread_id: None,
+            prompt_id: None,
+            intent: None,
+            mode: None,
+            messages: vec![
+                LanguageModelRequestMessage {
+                    role: Role::Assistant,
+                    content: vec![MessageContent::ToolUse(LanguageModelToolUse {
+                        id: "call_1".into(),
+                        name: "screenshot".into(),
+                        raw_input: "{}".into(),
+                        input: serde_json::json!({}),
+                        is_input_complete: true,
+                    })],
+                    cache: false,
+                },
+                LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::ToolResult(LanguageModelToolResult {
+                        tool_use_id: "call_1".into(),
+                        tool_name: "screenshot".into(),
+                        is_error: false,
+                        content: LanguageModelToolResultContent::Image(image.clone()),
+                        output: None,
+                    })],
+                    cache: false,
+                },
+            ],
+            tools: vec![],
+            tool_choice: None,
+            stop: vec![],
+            temperature: None,
+            seed: None,
+            priority: RequestPriority::Interactive,
+            web_search: false,
+        };
+
+        let request = into_open_ai(request, &Model::default(), None);
+        assert_eq!(request.messages.len(), 3);
+        assert_eq!(
+            request.messages[1],
+            open_ai::RequestMessage::Tool {
+                content: vec![open_ai::MessagePart::Text {
+                    text: "The tool returned an image, which is attached below.".into(),
+                }]
+                .into(),
+                tool_call_id: "call_1".into(),
+            }
+        );
+        assert_eq!(
+            request.messages[2],
+            open_ai::RequestMessage::User {
+                content: vec![
+                    open_ai::MessagePart::Text {
+                        text: "Image returned by tool call call_1:".into(),
+                    },
+                    open_ai::MessagePart::Image {
+                        image_url: ImageUrl {
+                            url: image.to_base64_url(),
+                            detail: None,
+                        },
+                    },
+                ]
+                .into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_responses_event_mapping() {
+        let events: Vec<responses::StreamEvent> = serde_json::from_value(serde_json::json!([
+            {"type": "response.created", "response": {"id": "resp_1"}},
+            {"type": "response.reasoning_summary_text.delta", "output_index": 0, "delta": "Planning"},
+            {"type": "response.output_text.delta", "output_index": 1, "delta": "Let me check."},
+            {
+                "type": "response.output_text.annotation.added",
+                "annotation": {"type": "url_citation", "url": "https://zed.dev", "title": "Zed"}
+            },
+            {
+                "type": "response.output_item.added",
+                "output_index": 2,
+                "item": {"type": "function_call", "id": "fc_1", "call_id": "call_1", "name": "grep"}
+            },
+            {"type": "response.function_call_arguments.delta", "output_index": 2, "delta": "{\"regex\":"},
+            {"type": "response.function_call_arguments.delta", "output_index": 2, "delta": "\"foo\"}"},
+            {
+                "type": "response.output_item.done",
+                "output_index": 2,
+                "item": {"type": "function_call", "id": "fc_1", "call_id": "call_1", "name": "grep"}
+            },
+            {"type": "response.web_search_call.completed", "output_index": 3},
+            {
+                "type": "response.completed",
+                "response": {
+                    "id": "resp_1",
+                    "usage": {
+                        "input_tokens": 100,
+                        "output_tokens": 20,
+                        "input_tokens_details": {"cached_tokens": 60}
+                    }
+                }
+            }
+        ]))
+        .unwrap();
+
+        let mut mapper = OpenAiResponsesEventMapper::new(None);
+        let events = events
+            .into_iter()
+            .flat_map(|event| mapper.map_event(event))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            [
+                LanguageModelCompletionEvent::StartMessage {
+                    message_id: "resp_1".into()
+                },
+                LanguageModelCompletionEvent::Thinking {
+                    text: "Planning".into(),
+                    signature: None
+                },
+                LanguageModelCompletionEvent::Text("Let me check.".into()),
+                LanguageModelCompletionEvent::WebSources(vec![WebSource {
+                    url: "https://zed.dev".into(),
+                    title: Some("Zed".into()),
+                }]),
+                LanguageModelCompletionEvent::ToolUse(LanguageModelToolUse {
+                    id: "call_1".into(),
+                    name: "grep".into(),
+                    raw_input: "{\"regex\":\"foo\"}".into(),
+                    input: serde_json::json!({"regex": "foo"}),
+                    is_input_complete: true,
+                }),
+                LanguageModelCompletionEvent::UsageUpdate(TokenUsage {
+                    input_tokens: 40,
+                    output_tokens: 20,
+                    cache_creation_input_tokens: 0,
+                    cache_read_input_tokens: 60,
+                }),
+                LanguageModelCompletionEvent::Stop(StopReason::ToolUse),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stored_responses() {
+        fn message(role: Role, text: &str) -> LanguageModelRequestMessage {
+            LanguageModelRequestMessage {
+                role,
+                content: vec![MessageContent::Text(text.into())],
+                cache: false,
+            }
+        }
+
+        fn request(messages: Vec<LanguageModelRequestMessage>) -> responses::Request {
+            let request = LanguageModelRequest {
+                thread_id: Some("thread".into()),
+                prompt_id: None,
+                intent: None,
+                mode: None,
+                messages,
+                tools: vec![],
+                tool_choice: None,
+                stop: vec![],
+                temperature: None,
+                seed: None,
+                priority: RequestPriority::Interactive,
+                web_search: false,
+            };
+            into_open_ai_responses(request, &Model::default(), None)
+        }
+
+        let stored_responses = StoredResponses::default();
+        let mut first = request(vec![
+            message(Role::System, "Be brief."),
+            message(Role::User, "Hi"),
+        ]);
+        assert_eq!(first.instructions.as_deref(), Some("Be brief."));
+        stored_responses
+            .prepare(Some("thread".into()), &mut first)
+            .unwrap()
+            .store("resp_1".into());
+        assert_eq!(first.previous_response_id, None);
+
+        let mut second = request(vec![
+            message(Role::System, "Be brief."),
+            message(Role::User, "Hi"),
+            message(Role::Assistant, "Hello!"),
+            message(Role::User, "Bye"),
+        ]);
+        stored_responses.prepare(Some("thread".into()), &mut second);
+        assert_eq!(second.previous_response_id.as_deref(), Some("resp_1"));
+        assert_eq!(
+            second.input,
+            [InputItem::Message {
+                role: InputRole::User,
+                content: vec![InputContent::InputText { text: "Bye".into() }],
+            }]
+        );
+
+        let mut edited = request(vec![
+            message(Role::User, "Hey"),
+            message(Role::Assistant, "Hello!"),
+            message(Role::User, "Bye"),
+        ]);
+        stored_responses.prepare(Some("thread".into()), &mut edited);
+        assert_eq!(edited.previous_response_id, None);
+        assert_eq!(edited.input.len(), 3);
+    }
 }
@@ -2,30 +2,39 @@ use anyhow::{Context as _, Result, anyhow};
 use collections::HashMap;
 use credentials_provider::CredentialsProvider;
 use editor::{Editor, EditorElement, EditorStyle};
-use futures::{FutureExt, Stream, StreamExt, future::BoxFuture};
+use futures::{FutureExt, Stream, StreamExt, future::BoxFuture, stream::BoxStream};
 use gpui::{
     AnyView, App, AsyncApp, Context, Entity, FontStyle, Subscription, Task, TextStyle, WhiteSpace,
 };
 use http_client::HttpClient;
 use language_model::{
     AuthenticateError, LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent,
-    LanguageModelId, LanguageModelName, LanguageModelProvider, LanguageModelProviderId,
-    LanguageModelProviderName, LanguageModelProviderState, LanguageModelRequest,
-    LanguageModelToolChoice, LanguageModelToolResultContent, LanguageModelToolUse, MessageContent,
-    RateLimiter, Role, StopReason,
+    LanguageModelError, LanguageModelId, LanguageModelName, LanguageModelPricing,
+    LanguageModelProvider, LanguageModelProviderId, LanguageModelProviderName,
+    LanguageModelProviderState, LanguageModelRequest, LanguageModelToolChoice,
+    LanguageModelToolResultContent, LanguageModelToolUse, MessageContent, RateLimiter,
+    RequestPriority, RequestTimeouts, Role, StopReason, TokenUsage, cache_model_catalog,
+    read_cached_model_catalog,
+};
+use open_router::{
+    Model, ModelCatalog, RateLimitError, RateLimitExceeded, ResponseStreamEvent, RetryPolicy,
+    stream_completion,
 };
-use open_router::{Model, ResponseStreamEvent, list_models, stream_completion};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::{Settings, SettingsStore};
 use std::pin::Pin;
 use std::str::FromStr as _;
 use std::sync::Arc;
+use std::time::Duration;
 use theme::ThemeSettings;
 use ui::{Icon, IconName, List, Tooltip, prelude::*};
 use util::ResultExt;
 
-use crate::{AllLanguageModelSettings, ui::InstructionListItem};
+use crate::{
+    AllLanguageModelSettings, provider_http::provider_http_client_without_retries,
+    ui::InstructionListItem,
+};
 
 const PROVIDER_ID: &str = "openrouter";
 const PROVIDER_NAME: &str = "OpenRouter";
@@ -34,6 +43,7 @@ const PROVIDER_NAME: &str = "OpenRouter";
 pub struct OpenRouterSettings {
     pub api_url: String,
     pub available_models: Vec<AvailableModel>,
+    pub retry_policy: RetryPolicy,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
@@ -48,13 +58,18 @@ pub struct AvailableModel {
 pub struct OpenRouterLanguageModelProvider {
     http_client: Arc<dyn HttpClient>,
     state: gpui::Entity<State>,
+    /// Shared by all of the provider's models, since OpenRouter's rate limits
+    /// apply to every model of a tier.
+    rate_limiter: open_router::RateLimiter,
 }
 
 pub struct State {
     api_key: Option<String>,
     api_key_from_env: bool,
     http_client: Arc<dyn HttpClient>,
-    available_models: Vec<open_router::Model>,
+    model_catalog: Option<ModelCatalog>,
+    /// The URL of the API that the models are fetched from.
+    models_api_url: String,
     fetch_models_task: Option<Task<Result<()>>>,
     _subscription: Subscription,
 }
@@ -137,42 +152,92 @@ impl State {
         })
     }
 
-    fn fetch_models(&mut self, cx: &mut Context<Self>) -> Task<Result<()>> {
+    /// Fetches the models offered by OpenRouter, unless the ones cached on
+    /// disk were fetched recently enough and `refresh` is false.
+    fn fetch_models(&mut self, refresh: bool, cx: &mut Context<Self>) -> Task<Result<()>> {
         let settings = &AllLanguageModelSettings::get_global(cx).open_router;
         let http_client = self.http_client.clone();
         let api_url = settings.api_url.clone();
+        self.models_api_url = api_url.clone();
+        let provider_id = LanguageModelProviderId(PROVIDER_ID.into());
 
         cx.spawn(async move |this, cx| {
-            let models = list_models(http_client.as_ref(), &api_url).await?;
+            if !refresh {
+                let cached_catalog = cx
+                    .background_spawn({
+                        let provider_id = provider_id.clone();
+                        async move { read_cached_model_catalog::<ModelCatalog>(&provider_id) }
+                    })
+                    .await
+                    .ok()
+                    .filter(|catalog| catalog.api_url == api_url);
+                if let Some(catalog) = cached_catalog {
+                    let is_stale = catalog.is_stale();
+                    this.update(cx, |this, cx| {
+                        this.model_catalog = Some(catalog);
+                        cx.notify();
+                    })?;
+                    if !is_stale {
+                        return Ok(());
+                    }
+                }
+            }
+
+            let catalog = ModelCatalog::fetch(http_client.as_ref(), &api_url).await?;
+            cx.background_spawn({
+                let catalog = catalog.clone();
+                async move { cache_model_catalog(&provider_id, &catalog) }
+            })
+            .await
+            .log_err();
 
             this.update(cx, |this, cx| {
-                this.available_models = models;
+                this.model_catalog = Some(catalog);
                 cx.notify();
             })
         })
     }
 
-    fn restart_fetch_models_task(&mut self, cx: &mut Context<Self>) {
-        let task = self.fetch_models(cx);
+    fn restart_fetch_models_task(&mut self, refresh: bool, cx: &mut Context<Self>) {
+        let task = self.fetch_models(refresh, cx);
         self.fetch_models_task.replace(task);
     }
+
+    /// Fetches the models offered by OpenRouter again, such as to list the
+    /// ones added since they were last fetched.
+    pub fn refresh_models(&mut self, cx: &mut Context<Self>) {
+        self.restart_fetch_models_task(true, cx);
+    }
 }
 
 impl OpenRouterLanguageModelProvider {
     pub fn new(http_client: Arc<dyn HttpClient>, cx: &mut App) -> Self {
-        let state = cx.new(|cx| State {
-            api_key: None,
-            api_key_from_env: false,
-            http_client: http_client.clone(),
-            available_models: Vec::new(),
-            fetch_models_task: None,
-            _subscription: cx.observe_global::<SettingsStore>(|this: &mut State, cx| {
-                this.restart_fetch_models_task(cx);
-                cx.notify();
-            }),
+        let http_client = provider_http_client_without_retries(http_client, PROVIDER_NAME);
+        let state = cx.new(|cx| {
+            let mut state = State {
+                api_key: None,
+                api_key_from_env: false,
+                http_client: http_client.clone(),
+                model_catalog: None,
+                models_api_url: String::new(),
+                fetch_models_task: None,
+                _subscription: cx.observe_global::<SettingsStore>(|this: &mut State, cx| {
+                    let api_url = &AllLanguageModelSettings::get_global(cx).open_router.api_url;
+                    if *api_url != this.models_api_url {
+                        this.restart_fetch_models_task(false, cx);
+                    }
+                    cx.notify();
+                }),
+            };
+            state.restart_fetch_models_task(false, cx);
+            state
         });
 
-        Self { http_client, state }
+        Self {
+            http_client,
+            state,
+            rate_limiter: open_router::RateLimiter::default(),
+        }
     }
 
     fn create_language_model(&self, model: open_router::Model) -> Arc<dyn LanguageModel> {
@@ -181,7 +246,9 @@ impl OpenRouterLanguageModelProvider {
             model,
             state: self.state.clone(),
             http_client: self.http_client.clone(),
-            request_limiter: RateLimiter::new(4),
+            request_limiter: RateLimiter::new(4)
+                .for_provider(LanguageModelProviderId(PROVIDER_ID.into())),
+            rate_limiter: self.rate_limiter.clone(),
         })
     }
 }
@@ -216,36 +283,15 @@ impl LanguageModelProvider for OpenRouterLanguageModelProvider {
     }
 
     fn provided_models(&self, cx: &App) -> Vec<Arc<dyn LanguageModel>> {
-        let mut models_from_api = self.state.read(cx).available_models.clone();
-        let mut settings_models = Vec::new();
-
-        for model in &AllLanguageModelSettings::get_global(cx)
-            .open_router
-            .available_models
-        {
-            settings_models.push(open_router::Model {
-                name: model.name.clone(),
-                display_name: model.display_name.clone(),
-                max_tokens: model.max_tokens,
-                supports_tools: Some(false),
-            });
-        }
-
-        for settings_model in &settings_models {
-            if let Some(pos) = models_from_api
-                .iter()
-                .position(|m| m.name == settings_model.name)
-            {
-                models_from_api[pos] = settings_model.clone();
-            } else {
-                models_from_api.push(settings_model.clone());
-            }
-        }
-
-        models_from_api
-            .into_iter()
-            .map(|model| self.create_language_model(model))
-            .collect()
+        merge_models(
+            self.state.read(cx).model_catalog.as_ref(),
+            &AllLanguageModelSettings::get_global(cx)
+                .open_router
+                .available_models,
+        )
+        .into_iter()
+        .map(|model| self.create_language_model(model))
+        .collect()
     }
 
     fn is_authenticated(&self, cx: &App) -> bool {
@@ -266,43 +312,229 @@ impl LanguageModelProvider for OpenRouterLanguageModelProvider {
     }
 }
 
+/// The models in the catalog, along with those configured in the settings,
+/// which replace the catalog's models of the same name. The configured models
+/// are priced and use tools as the catalog says they do.
+fn merge_models(
+    catalog: Option<&ModelCatalog>,
+    settings_models: &[AvailableModel],
+) -> Vec<open_router::Model> {
+    let mut models = catalog.map_or_else(Vec::new, |catalog| catalog.models.clone());
+    for settings_model in settings_models {
+        let model = open_router::Model {
+            name: settings_model.name.clone(),
+            display_name: settings_model.display_name.clone(),
+            max_tokens: settings_model.max_tokens,
+            supports_tools: catalog
+                .and_then(|catalog| catalog.supports_tools(&settings_model.name))
+                .or(Some(false)),
+            pricing: catalog.and_then(|catalog| catalog.pricing(&settings_model.name)),
+        };
+        if let Some(existing) = models.iter_mut().find(|m| m.name == model.name) {
+            *existing = model;
+        } else {
+            models.push(model);
+        }
+    }
+    models
+}
+
 pub struct OpenRouterLanguageModel {
     id: LanguageModelId,
     model: open_router::Model,
     state: gpui::Entity<State>,
     http_client: Arc<dyn HttpClient>,
     request_limiter: RateLimiter,
+    rate_limiter: open_router::RateLimiter,
 }
 
 impl OpenRouterLanguageModel {
-    fn stream_completion(
+    fn completion_client(
         &self,
-        request: open_router::Request,
+        priority: RequestPriority,
+        timeouts: RequestTimeouts,
         cx: &AsyncApp,
-    ) -> BoxFuture<'static, Result<futures::stream::BoxStream<'static, Result<ResponseStreamEvent>>>>
-    {
-        let http_client = self.http_client.clone();
-        let Ok((api_key, api_url)) = cx.read_entity(&self.state, |state, cx| {
-            let settings = &AllLanguageModelSettings::get_global(cx).open_router;
-            (state.api_key.clone(), settings.api_url.clone())
-        }) else {
-            return futures::future::ready(Err(anyhow!(
-                "App state dropped: Unable to read API key or API URL from the application state"
-            )))
-            .boxed();
-        };
+    ) -> Result<CompletionClient> {
+        let (api_key, api_url, retry_policy) = cx
+            .read_entity(&self.state, |state, cx| {
+                let settings = &AllLanguageModelSettings::get_global(cx).open_router;
+                (
+                    state.api_key.clone(),
+                    settings.api_url.clone(),
+                    settings.retry_policy,
+                )
+            })
+            .context(
+                "App state dropped: Unable to read API key or API URL from the application state",
+            )?;
+        Ok(CompletionClient {
+            http_client: self.http_client.clone(),
+            api_key,
+            api_url,
+            retry_policy,
+            request_limiter: self.request_limiter.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            priority,
+            timeouts,
+        })
+    }
+}
 
-        let future = self.request_limiter.stream(async move {
-            let api_key = api_key.ok_or_else(|| anyhow!("Missing OpenRouter API Key"))?;
-            let request = stream_completion(http_client.as_ref(), &api_url, &api_key, request);
-            let response = request.await?;
-            Ok(response)
+/// What's needed to send a completion request to OpenRouter, so that the
+/// request can be sent again when it fails transiently.
+#[derive(Clone)]
+struct CompletionClient {
+    http_client: Arc<dyn HttpClient>,
+    api_key: Option<String>,
+    api_url: String,
+    retry_policy: RetryPolicy,
+    request_limiter: RateLimiter,
+    rate_limiter: open_router::RateLimiter,
+    priority: RequestPriority,
+    timeouts: RequestTimeouts,
+}
+
+impl CompletionClient {
+    /// Sends the request and waits for the first event of the response, so
+    /// that errors OpenRouter reports at the start of the stream fail the
+    /// request like the ones it responds with.
+    fn connect(
+        &self,
+        request: open_router::Request,
+    ) -> BoxFuture<'static, Result<BoxStream<'static, Result<ResponseStreamEvent>>>> {
+        let this = self.clone();
+        let future = self.request_limiter.stream(self.priority, async move {
+            let delay = this
+                .rate_limiter
+                .reserve(&request.model, request.estimated_tokens())
+                .map_err(open_router_err_to_anyhow)?;
+            if !delay.is_zero() {
+                smol::Timer::after(delay).await;
+            }
+            let timeouts = this.timeouts;
+            timeouts
+                .apply(async move {
+                    let api_key = this
+                        .api_key
+                        .ok_or_else(|| anyhow!("Missing OpenRouter API Key"))?;
+                    let request = stream_completion(
+                        this.http_client.as_ref(),
+                        &this.api_url,
+                        &api_key,
+                        request,
+                        &this.rate_limiter,
+                    );
+                    let response = request.await.map_err(open_router_err_to_anyhow)?;
+                    Ok(response)
+                })
+                .await
         });
 
-        async move { Ok(future.await?.boxed()) }.boxed()
+        async move {
+            let mut events = future.await.map_err(LanguageModelError::classify)?.boxed();
+            match events.next().await {
+                Some(Err(error)) => Err(LanguageModelError::classify(error)),
+                first => Ok(futures::stream::iter(first).chain(events).boxed()),
+            }
+        }
+        .boxed()
+    }
+
+    /// Streams the response to the request, sending it again as often as the
+    /// retry policy allows when it fails transiently. This is the only place
+    /// OpenRouter requests are retried, so `max_attempts` bounds how many
+    /// times a request is sent.
+    fn stream(
+        self,
+        request: open_router::Request,
+    ) -> BoxFuture<
+        'static,
+        Result<
+            BoxStream<'static, Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
+        >,
+    > {
+        async move {
+            let error = match self.connect(request.clone()).await {
+                Ok(events) => return Ok(OpenRouterEventMapper::new().map_stream(events).boxed()),
+                Err(error) => error,
+            };
+            match self.retry_delay(1, &error) {
+                Some(delay) => Ok(retry_completion(self, request, 1, delay, error)),
+                None => Err(error),
+            }
+        }
+        .boxed()
+    }
+
+    /// How long to wait before retrying a request that failed on the given
+    /// attempt, or `None` if the error isn't one that retrying recovers from.
+    fn retry_delay(&self, attempt: u32, error: &anyhow::Error) -> Option<Duration> {
+        match error.downcast_ref::<LanguageModelError>()? {
+            LanguageModelError::Network { .. }
+            | LanguageModelError::Server { .. }
+            | LanguageModelError::RateLimitExceeded { .. } => {}
+            _ => return None,
+        }
+        let retry_after = match error.downcast_ref::<RateLimitExceeded>() {
+            Some(exceeded) => Some(exceeded.retry_after),
+            None => error
+                .downcast_ref::<RateLimitError>()
+                .and_then(|error| error.retry_after),
+        };
+        self.retry_policy
+            .delay(attempt, retry_after, open_router::jitter())
     }
 }
 
+/// Streams the response to a request that failed transiently on the given
+/// attempt, reporting the retry before sending the request again once the
+/// delay has passed.
+fn retry_completion(
+    client: CompletionClient,
+    request: open_router::Request,
+    attempt: u32,
+    delay: Duration,
+    error: anyhow::Error,
+) -> BoxStream<'static, Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
+    let retrying = LanguageModelCompletionEvent::Retrying {
+        attempt: attempt + 1,
+        max_attempts: client.retry_policy.max_attempts,
+        delay,
+        message: error.to_string(),
+    };
+    let retry =
+        async move {
+            smol::Timer::after(delay).await;
+            match client.connect(request.clone()).await {
+                Ok(events) => OpenRouterEventMapper::new().map_stream(events).boxed(),
+                Err(error) => match client.retry_delay(attempt + 1, &error) {
+                    Some(delay) => retry_completion(client, request, attempt + 1, delay, error),
+                    None => futures::stream::once(async move {
+                        Err(LanguageModelCompletionError::Other(error))
+                    })
+                    .boxed(),
+                },
+            }
+        };
+    futures::stream::once(async move { Ok(retrying) })
+        .chain(futures::stream::once(retry).flatten())
+        .boxed()
+}
+
+/// Maps OpenRouter's rate limit errors to [`LanguageModelError`]s, keeping
+/// the original error underneath for how long it asked to wait.
+fn open_router_err_to_anyhow(error: impl Into<anyhow::Error>) -> anyhow::Error {
+    let error = error.into();
+    let message = if let Some(exceeded) = error.downcast_ref::<RateLimitExceeded>() {
+        exceeded.to_string()
+    } else if let Some(RateLimitError { message, .. }) = error.downcast_ref::<RateLimitError>() {
+        message.clone()
+    } else {
+        return error;
+    };
+    error.context(LanguageModelError::RateLimitExceeded { message })
+}
+
 impl LanguageModel for OpenRouterLanguageModel {
     fn id(&self) -> LanguageModelId {
         self.id.clone()
@@ -348,6 +580,18 @@ impl LanguageModel for OpenRouterLanguageModel {
         false
     }
 
+    /// OpenRouter forwards the seed to the backends that support it.
+    fn supports_seed(&self) -> bool {
+        true
+    }
+
+    fn pricing(&self) -> Option<LanguageModelPricing> {
+        self.model.pricing.map(|pricing| LanguageModelPricing {
+            input_cost_per_million_tokens: pricing.prompt * 1_000_000.,
+            output_cost_per_million_tokens: pricing.completion * 1_000_000.,
+        })
+    }
+
     fn count_tokens(
         &self,
         request: LanguageModelRequest,
@@ -369,13 +613,11 @@ impl LanguageModel for OpenRouterLanguageModel {
             >,
         >,
     > {
+        let priority = request.priority;
+        let timeouts = RequestTimeouts::for_request(&self.provider_id(), &request);
         let request = into_open_router(request, &self.model, self.max_output_tokens());
-        let completions = self.stream_completion(request, cx);
-        async move {
-            let mapper = OpenRouterEventMapper::new();
-            Ok(mapper.map_stream(completions.await?).boxed())
-        }
-        .boxed()
+        let client = self.completion_client(priority, timeouts, cx);
+        async move { client?.stream(request).await }.boxed()
     }
 }
 
@@ -398,7 +640,7 @@ pub fn into_open_router(
                         Role::System => open_router::RequestMessage::System { content: text },
                     }),
                 MessageContent::RedactedThinking(_) => {}
-                MessageContent::Image(_) => {}
+                MessageContent::Image(_) | MessageContent::Document(_) => {}
                 MessageContent::ToolUse(tool_use) => {
                     let tool_call = open_router::ToolCall {
                         id: tool_use.id.to_string(),
@@ -447,6 +689,7 @@ pub fn into_open_router(
         stream: true,
         stop: request.stop,
         temperature: request.temperature.unwrap_or(0.4),
+        seed: request.seed,
         max_tokens: max_output_tokens,
         parallel_tool_calls: if model.supports_parallel_tool_calls() && !request.tools.is_empty() {
             Some(false)
@@ -500,13 +743,25 @@ impl OpenRouterEventMapper {
         &mut self,
         event: ResponseStreamEvent,
     ) -> Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
+        let mut events = Vec::new();
+        if let Some(usage) = event.usage.as_ref() {
+            events.push(Ok(LanguageModelCompletionEvent::UsageUpdate(TokenUsage {
+                input_tokens: usage.prompt_tokens,
+                output_tokens: usage.completion_tokens,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 0,
+            })));
+        }
+
         let Some(choice) = event.choices.first() else {
-            return vec![Err(LanguageModelCompletionError::Other(anyhow!(
-                "Response contained no choices"
-            )))];
+            if events.is_empty() {
+                events.push(Err(LanguageModelCompletionError::Other(anyhow!(
+                    "Response contained no choices"
+                ))));
+            }
+            return events;
         };
 
-        let mut events = Vec::new();
         if let Some(content) = choice.delta.content.clone() {
             events.push(Ok(LanguageModelCompletionEvent::Text(content)));
         }
@@ -535,6 +790,14 @@ impl OpenRouterEventMapper {
             Some("stop") => {
                 events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::EndTurn)));
             }
+            Some("length") => {
+                events.push(Ok(LanguageModelCompletionEvent::Stop(
+                    StopReason::MaxTokens,
+                )));
+            }
+            Some("content_filter") => {
+                events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::Refusal)));
+            }
             Some("tool_calls") => {
                 events.extend(self.tool_calls_by_index.drain().map(|(_, tool_call)| {
                     match serde_json::Value::from_str(&tool_call.arguments) {
@@ -771,18 +1034,155 @@ impl Render for ConfigurationView {
                         })),
                 )
                 .child(
-                    Button::new("reset-key", "Reset Key")
-                        .label_size(LabelSize::Small)
-                        .icon(Some(IconName::Trash))
-                        .icon_size(IconSize::Small)
-                        .icon_position(IconPosition::Start)
-                        .disabled(env_var_set)
-                        .when(env_var_set, |this| {
-                            this.tooltip(Tooltip::text(format!("To reset your API key, unset the {OPENROUTER_API_KEY_VAR} environment variable.")))
-                        })
-                        .on_click(cx.listener(|this, _, window, cx| this.reset_api_key(window, cx))),
+                    h_flex()
+                        .gap_1()
+                        .child(
+                            Button::new("refresh-models", "Refresh Models")
+                                .label_size(LabelSize::Small)
+                                .icon(Some(IconName::RotateCw))
+                                .icon_size(IconSize::Small)
+                                .icon_position(IconPosition::Start)
+                                .tooltip(Tooltip::text("Fetch the models offered by OpenRouter again."))
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.state.update(cx, |state, cx| state.refresh_models(cx));
+                                })),
+                        )
+                        .child(
+                            Button::new("reset-key", "Reset Key")
+                                .label_size(LabelSize::Small)
+                                .icon(Some(IconName::Trash))
+                                .icon_size(IconSize::Small)
+                                .icon_position(IconPosition::Start)
+                                .disabled(env_var_set)
+                                .when(env_var_set, |this| {
+                                    this.tooltip(Tooltip::text(format!("To reset your API key, unset the {OPENROUTER_API_KEY_VAR} environment variable.")))
+                                })
+                                .on_click(cx.listener(|this, _, window, cx| this.reset_api_key(window, cx))),
+                        ),
                 )
                 .into_any()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_client::{AsyncBody, FakeHttpClient, Response};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_failed_requests_are_sent_at_most_max_attempts_times() {
+        let requests_sent = Arc::new(AtomicUsize::new(0));
+        let http_client = FakeHttpClient::create({
+            let requests_sent = requests_sent.clone();
+            move |_| {
+                requests_sent.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    Ok(Response::builder()
+                        .status(503)
+                        .body(AsyncBody::from("Service Unavailable"))?)
+                }
+            }
+        });
+        let client = CompletionClient {
+            http_client: provider_http_client_without_retries(http_client, PROVIDER_NAME),
+            api_key: Some("api-key".into()),
+            api_url: open_router::OPEN_ROUTER_API_URL.into(),
+            retry_policy: RetryPolicy {
+                max_attempts: 3,
+                initial_delay: Duration::from_millis(1),
+                max_delay: Duration::from_secs(1),
+            },
+            request_limiter: RateLimiter::new(4),
+            rate_limiter: open_router::RateLimiter::default(),
+            priority: RequestPriority::Interactive,
+            timeouts: RequestTimeouts::default(),
+        };
+        let request = into_open_router(
+            LanguageModelRequest::default(),
+            &Model::new("openai/gpt-4o", None, None, Some(true)),
+            None,
+        );
+
+        let events = smol::block_on(async move {
+            let events = client.stream(request).await.unwrap();
+            events.collect::<Vec<_>>().await
+        });
+
+        assert_eq!(requests_sent.load(Ordering::SeqCst), 3);
+        let retries = events
+            .iter()
+            .filter_map(|event| match event {
+                Ok(LanguageModelCompletionEvent::Retrying {
+                    attempt,
+                    max_attempts,
+                    ..
+                }) => Some((*attempt, *max_attempts)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(retries, [(2, 3), (3, 3)]);
+        assert!(matches!(events.last(), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_settings_models_replace_catalog_models() {
+        let pricing = open_router::Pricing {
+            prompt: 0.000003,
+            completion: 0.000015,
+        };
+        let catalog = ModelCatalog {
+            api_url: open_router::OPEN_ROUTER_API_URL.into(),
+            fetched_at: SystemTime::now(),
+            models: vec![
+                open_router::Model {
+                    pricing: Some(pricing),
+                    ..open_router::Model::new(
+                        "anthropic/claude-sonnet-4",
+                        Some("Claude Sonnet 4"),
+                        Some(200_000),
+                        Some(true),
+                    )
+                },
+                open_router::Model::new("openrouter/auto", None, Some(2_000_000), Some(false)),
+            ],
+        };
+        let settings_models = [
+            AvailableModel {
+                name: "anthropic/claude-sonnet-4".into(),
+                display_name: Some("Sonnet".into()),
+                max_tokens: 64_000,
+                max_output_tokens: None,
+                max_completion_tokens: None,
+            },
+            AvailableModel {
+                name: "my/fine-tune".into(),
+                display_name: None,
+                max_tokens: 8_000,
+                max_output_tokens: None,
+                max_completion_tokens: None,
+            },
+        ];
+
+        let models = merge_models(Some(&catalog), &settings_models);
+        assert_eq!(
+            models,
+            [
+                open_router::Model {
+                    pricing: Some(pricing),
+                    ..open_router::Model::new(
+                        "anthropic/claude-sonnet-4",
+                        Some("Sonnet"),
+                        Some(64_000),
+                        Some(true),
+                    )
+                },
+                open_router::Model::new("openrouter/auto", None, Some(2_000_000), Some(false)),
+                open_router::Model::new("my/fine-tune", None, Some(8_000), Some(false)),
+            ]
+        );
+        assert_eq!(merge_models(None, &settings_models).len(), 2);
+    }
+}
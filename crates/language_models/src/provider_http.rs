@@ -0,0 +1,146 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use futures::{FutureExt as _, future::BoxFuture};
+use http_client::{
+    AsyncBody, HttpClient, Request, Response, StatusCode,
+    http::header,
+    middleware::{HttpClientWithMiddleware, LogRequests, Middleware, Next, try_clone_request},
+};
+use smol::Timer;
+
+/// Wraps an HTTP client with the middleware that the requests of every
+/// provider go through, so that providers only map their requests and
+/// responses.
+pub fn provider_http_client(
+    http_client: Arc<dyn HttpClient>,
+    provider_name: &str,
+) -> Arc<dyn HttpClient> {
+    Arc::new(
+        HttpClientWithMiddleware::new(http_client)
+            .with(Retry::default())
+            .with(LogRequests::new(provider_name)),
+    )
+}
+
+/// Like [`provider_http_client`], but without [`Retry`], for providers that
+/// retry failed requests with a policy of their own.
+pub fn provider_http_client_without_retries(
+    http_client: Arc<dyn HttpClient>,
+    provider_name: &str,
+) -> Arc<dyn HttpClient> {
+    Arc::new(HttpClientWithMiddleware::new(http_client).with(LogRequests::new(provider_name)))
+}
+
+/// The longest `Retry-After` that's waited out, rather than reported to the
+/// user.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Sends a request again when the provider fails with a server error, or
+/// asks for it to be sent again shortly with a `Retry-After` header, waiting
+/// longer before each attempt. Requests with streamed bodies are sent once.
+pub struct Retry {
+    max_retries: usize,
+    initial_delay: Duration,
+}
+
+impl Retry {
+    pub fn new(max_retries: usize, initial_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_delay,
+        }
+    }
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self::new(3, Duration::from_secs(1))
+    }
+}
+
+impl Middleware for Retry {
+    fn handle(
+        &self,
+        mut request: Request<AsyncBody>,
+        next: Next,
+    ) -> BoxFuture<'static, Result<Response<AsyncBody>>> {
+        let mut retries_remaining = self.max_retries;
+        let mut delay = self.initial_delay;
+        async move {
+            loop {
+                let retry = if retries_remaining > 0 {
+                    try_clone_request(&request)
+                } else {
+                    None
+                };
+                let response = next.clone().run(request).await?;
+                let Some((retry, wait)) = retry.zip(retry_delay(&response, delay)) else {
+                    return Ok(response);
+                };
+                log::info!(
+                    "retrying request to {} in {wait:?} after {}",
+                    retry.uri().path(),
+                    response.status()
+                );
+                Timer::after(wait).await;
+                request = retry;
+                retries_remaining -= 1;
+                delay *= 2;
+            }
+        }
+        .boxed()
+    }
+}
+
+/// How long to wait before sending the request again, if the response is
+/// worth retrying.
+fn retry_delay(response: &Response<AsyncBody>, backoff: Duration) -> Option<Duration> {
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs)
+        .filter(|retry_after| *retry_after <= MAX_RETRY_AFTER);
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        retry_after
+    } else if status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED {
+        Some(retry_after.unwrap_or(backoff))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let response = |status: u16, retry_after: Option<&str>| {
+            let mut response = Response::builder().status(status);
+            if let Some(retry_after) = retry_after {
+                response = response.header(header::RETRY_AFTER, retry_after);
+            }
+            response.body(AsyncBody::empty()).unwrap()
+        };
+        let backoff = Duration::from_secs(2);
+
+        assert_eq!(retry_delay(&response(200, None), backoff), None);
+        assert_eq!(retry_delay(&response(400, None), backoff), None);
+        assert_eq!(retry_delay(&response(529, None), backoff), Some(backoff));
+        assert_eq!(
+            retry_delay(&response(503, Some("5")), backoff),
+            Some(Duration::from_secs(5))
+        );
+        // Rate limits are only waited out when the provider says how long for.
+        assert_eq!(retry_delay(&response(429, None), backoff), None);
+        assert_eq!(
+            retry_delay(&response(429, Some("10")), backoff),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(retry_delay(&response(429, Some("3600")), backoff), None);
+    }
+}
@@ -1,12 +1,13 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Result;
+use collections::HashMap;
 use gpui::App;
-use language_model::LanguageModelCacheConfiguration;
+use language_model::{LanguageModelCacheConfiguration, LanguageModelProviderId, RequestTimeouts};
 use project::Fs;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use settings::{Settings, SettingsSources, update_settings_file};
+use settings::{Settings, SettingsSources, SettingsStore, update_settings_file};
 
 use crate::provider::{
     self,
@@ -26,6 +27,13 @@ use crate::provider::{
 /// Initializes the language model settings.
 pub fn init(fs: Arc<dyn Fs>, cx: &mut App) {
     AllLanguageModelSettings::register(cx);
+    apply_concurrency_limits(cx);
+    apply_request_timeouts(cx);
+    cx.observe_global::<SettingsStore>(|cx| {
+        apply_concurrency_limits(cx);
+        apply_request_timeouts(cx);
+    })
+    .detach();
 
     if AllLanguageModelSettings::get_global(cx)
         .openai
@@ -56,6 +64,40 @@ pub fn init(fs: Arc<dyn Fs>, cx: &mut App) {
     }
 }
 
+/// Passes the concurrency limits in the settings to the rate limiters of the
+/// providers' models.
+fn apply_concurrency_limits(cx: &App) {
+    let settings = AllLanguageModelSettings::get_global(cx);
+    language_model::set_concurrency_limits(
+        settings.max_concurrent_requests,
+        settings
+            .max_concurrent_requests_per_provider
+            .iter()
+            .map(|(provider_id, limit)| {
+                (LanguageModelProviderId(provider_id.clone().into()), *limit)
+            })
+            .collect(),
+    );
+}
+
+/// Passes the request timeouts in the settings to the providers' models.
+fn apply_request_timeouts(cx: &App) {
+    let settings = AllLanguageModelSettings::get_global(cx);
+    language_model::set_request_timeouts(
+        settings.request_timeouts.to_request_timeouts(),
+        settings
+            .request_timeouts_per_provider
+            .iter()
+            .map(|(provider_id, timeouts)| {
+                (
+                    LanguageModelProviderId(provider_id.clone().into()),
+                    timeouts.to_request_timeouts(),
+                )
+            })
+            .collect(),
+    );
+}
+
 #[derive(Default)]
 pub struct AllLanguageModelSettings {
     pub anthropic: AnthropicSettings,
@@ -69,6 +111,10 @@ pub struct AllLanguageModelSettings {
     pub lmstudio: LmStudioSettings,
     pub deepseek: DeepSeekSettings,
     pub mistral: MistralSettings,
+    pub max_concurrent_requests: Option<usize>,
+    pub max_concurrent_requests_per_provider: HashMap<String, usize>,
+    pub request_timeouts: RequestTimeoutsContent,
+    pub request_timeouts_per_provider: HashMap<String, RequestTimeoutsContent>,
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
@@ -85,6 +131,62 @@ pub struct AllLanguageModelSettingsContent {
     pub deepseek: Option<DeepseekSettingsContent>,
     pub copilot_chat: Option<CopilotChatSettingsContent>,
     pub mistral: Option<MistralSettingsContent>,
+    /// The maximum number of requests that may be in flight at once, across
+    /// all providers.
+    ///
+    /// Default: null (unlimited)
+    pub max_concurrent_requests: Option<usize>,
+    /// The maximum number of requests that may be in flight at once for each
+    /// provider, keyed by provider ID, such as "anthropic" or "openai".
+    ///
+    /// Default: {}
+    pub max_concurrent_requests_per_provider: Option<HashMap<String, usize>>,
+    /// How long to wait on the providers before a request fails.
+    ///
+    /// Default: {} (no timeouts)
+    pub request_timeouts: Option<RequestTimeoutsContent>,
+    /// How long to wait on each provider before a request fails, keyed by
+    /// provider ID, such as "anthropic" or "openai". Timeouts that aren't set
+    /// for a provider fall back to `request_timeouts`.
+    ///
+    /// Default: {}
+    pub request_timeouts_per_provider: Option<HashMap<String, RequestTimeoutsContent>>,
+}
+
+/// How long to wait on a provider before a request fails, in seconds. The
+/// first token and total timeouts are extended by their length again for
+/// every 100,000 tokens in the request, since long requests take longer to
+/// process.
+#[derive(Default, Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct RequestTimeoutsContent {
+    /// How long to wait for the provider to respond to a request.
+    pub connect_secs: Option<u64>,
+    /// How long to wait for the first token of a response, from when the
+    /// request is sent.
+    pub first_token_secs: Option<u64>,
+    /// How long to wait between the tokens of a response.
+    pub idle_secs: Option<u64>,
+    /// How long to wait for the whole response, from when the request is
+    /// sent.
+    pub total_secs: Option<u64>,
+}
+
+impl RequestTimeoutsContent {
+    fn to_request_timeouts(&self) -> RequestTimeouts {
+        RequestTimeouts {
+            connect: self.connect_secs.map(Duration::from_secs),
+            first_token: self.first_token_secs.map(Duration::from_secs),
+            idle: self.idle_secs.map(Duration::from_secs),
+            total: self.total_secs.map(Duration::from_secs),
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.connect_secs = other.connect_secs.or(self.connect_secs);
+        self.first_token_secs = other.first_token_secs.or(self.first_token_secs);
+        self.idle_secs = other.idle_secs.or(self.idle_secs);
+        self.total_secs = other.total_secs.or(self.total_secs);
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
@@ -177,6 +279,10 @@ pub struct AmazonBedrockSettingsContent {
 pub struct OllamaSettingsContent {
     pub api_url: Option<String>,
     pub available_models: Option<Vec<provider::ollama::AvailableModel>>,
+    /// How often, in seconds, to ping Ollama so the selected models stay loaded.
+    ///
+    /// Default: null (disabled)
+    pub keep_alive_ping_interval_seconds: Option<u64>,
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
@@ -231,6 +337,7 @@ impl OpenAiSettingsContent {
                             })
                             .collect()
                     }),
+                    responses_api_models: None,
                 },
                 true,
             ),
@@ -258,6 +365,10 @@ pub enum VersionedOpenAiSettingsContent {
 pub struct OpenAiSettingsContentV1 {
     pub api_url: Option<String>,
     pub available_models: Option<Vec<provider::open_ai::AvailableModel>>,
+    /// The ids of the models to use through the Responses API, rather than
+    /// through Chat Completions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub responses_api_models: Option<Vec<String>>,
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
@@ -282,6 +393,37 @@ pub struct CopilotChatSettingsContent {
 pub struct OpenRouterSettingsContent {
     pub api_url: Option<String>,
     pub available_models: Option<Vec<provider::open_router::AvailableModel>>,
+    /// How requests that fail transiently are retried.
+    pub retry: Option<OpenRouterRetryContent>,
+}
+
+/// How requests to OpenRouter that fail transiently, because the connection
+/// dropped, OpenRouter is overloaded, or a rate limit was hit, are retried.
+#[derive(Default, Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct OpenRouterRetryContent {
+    /// The most times a request is sent, including the first attempt. Set
+    /// this to 1 to turn retries off.
+    pub max_attempts: Option<u32>,
+    /// How long to wait before the first retry, in milliseconds. The delay
+    /// doubles with each retry after that.
+    pub initial_delay_ms: Option<u64>,
+    /// The longest to wait before a retry, in seconds. Requests that
+    /// OpenRouter asks to hold off on for longer aren't retried.
+    pub max_delay_secs: Option<u64>,
+}
+
+impl OpenRouterRetryContent {
+    fn apply(&self, policy: &mut open_router::RetryPolicy) {
+        if let Some(max_attempts) = self.max_attempts {
+            policy.max_attempts = max_attempts;
+        }
+        if let Some(initial_delay_ms) = self.initial_delay_ms {
+            policy.initial_delay = Duration::from_millis(initial_delay_ms);
+        }
+        if let Some(max_delay_secs) = self.max_delay_secs {
+            policy.max_delay = Duration::from_secs(max_delay_secs);
+        }
+    }
 }
 
 impl settings::Settings for AllLanguageModelSettings {
@@ -301,6 +443,25 @@ impl settings::Settings for AllLanguageModelSettings {
         let mut settings = AllLanguageModelSettings::default();
 
         for value in sources.defaults_and_customizations() {
+            if let Some(limit) = value.max_concurrent_requests {
+                settings.max_concurrent_requests = Some(limit);
+            }
+            if let Some(limits) = value.max_concurrent_requests_per_provider.clone() {
+                settings.max_concurrent_requests_per_provider.extend(limits);
+            }
+            if let Some(timeouts) = value.request_timeouts.as_ref() {
+                settings.request_timeouts.merge(timeouts);
+            }
+            if let Some(timeouts) = value.request_timeouts_per_provider.as_ref() {
+                for (provider_id, timeouts) in timeouts {
+                    settings
+                        .request_timeouts_per_provider
+                        .entry(provider_id.clone())
+                        .or_default()
+                        .merge(timeouts);
+                }
+            }
+
             // Anthropic
             let (anthropic, upgraded) = match value.anthropic.clone().map(|s| s.upgrade()) {
                 Some((content, upgraded)) => (Some(content), upgraded),
@@ -350,6 +511,13 @@ impl settings::Settings for AllLanguageModelSettings {
                 &mut settings.ollama.available_models,
                 ollama.as_ref().and_then(|s| s.available_models.clone()),
             );
+            merge(
+                &mut settings.ollama.keep_alive_ping_interval,
+                ollama
+                    .as_ref()
+                    .and_then(|s| s.keep_alive_ping_interval_seconds)
+                    .map(|seconds| Some(Duration::from_secs(seconds.max(1)))),
+            );
 
             // LM Studio
             let lmstudio = value.lmstudio.clone();
@@ -393,6 +561,10 @@ impl settings::Settings for AllLanguageModelSettings {
                 &mut settings.openai.available_models,
                 openai.as_ref().and_then(|s| s.available_models.clone()),
             );
+            merge(
+                &mut settings.openai.responses_api_models,
+                openai.as_ref().and_then(|s| s.responses_api_models.clone()),
+            );
             merge(
                 &mut settings.zed_dot_dev.available_models,
                 value
@@ -435,6 +607,9 @@ impl settings::Settings for AllLanguageModelSettings {
                     .as_ref()
                     .and_then(|s| s.available_models.clone()),
             );
+            if let Some(retry) = open_router.as_ref().and_then(|s| s.retry.as_ref()) {
+                retry.apply(&mut settings.open_router.retry_policy);
+            }
 
             // Copilot Chat
             let copilot_chat = value.copilot_chat.clone().unwrap_or_default();
@@ -0,0 +1,612 @@
+//! Replays recorded provider responses through the same parsing that the
+//! providers use, split into chunks wherever they can be split on the way
+//! from the provider, to catch the responses that stop parsing when a
+//! provider changes the format of its stream.
+
+use std::{
+    collections::VecDeque,
+    io,
+    pin::{Pin, pin},
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use anthropic::AnthropicModelMode;
+use collections::HashMap;
+use futures::{AsyncRead, Stream, StreamExt as _, executor::block_on};
+use google_ai::GoogleModelMode;
+use http_client::{AsyncBody, FakeHttpClient, HttpClientWithUrl, Response};
+use language_model::{
+    LanguageModelCompletionError, LanguageModelCompletionEvent, LanguageModelRequest,
+    LanguageModelRequestMessage, MessageContent, Role, StopReason, TokenUsage,
+};
+use serde_json::{Value, json};
+
+use crate::provider::{
+    anthropic::{AnthropicEventMapper, into_anthropic},
+    google::{GoogleEventMapper, into_google},
+    open_ai::{OpenAiEventMapper, into_open_ai},
+};
+
+const ANTHROPIC_TOOL_USE: &str = include_str!("../test_data/anthropic_tool_use.sse");
+const ANTHROPIC_ERROR: &str = include_str!("../test_data/anthropic_error.sse");
+const OPEN_AI_TOOL_CALLS: &str = include_str!("../test_data/open_ai_tool_calls.sse");
+const OPEN_AI_ERROR: &str = include_str!("../test_data/open_ai_error.sse");
+const GOOGLE_FUNCTION_CALLS: &str = include_str!("../test_data/google_function_calls.sse");
+
+const ITERATIONS: u64 = 200;
+
+#[derive(Clone, Copy, Debug)]
+enum Provider {
+    Anthropic,
+    OpenAi,
+    Google,
+}
+
+/// What a response amounts to, which is the same however it was streamed.
+#[derive(Debug, Default, PartialEq)]
+struct StreamSummary {
+    message_id: Option<String>,
+    text: String,
+    thinking: String,
+    signature: Option<String>,
+    tool_uses: Vec<ToolUse>,
+    usage: Option<TokenUsage>,
+    stop_reason: Option<StopReason>,
+    errors: Vec<String>,
+}
+
+/// A tool use whose input was streamed completely.
+#[derive(Debug, PartialEq)]
+struct ToolUse {
+    id: String,
+    name: String,
+    input: Value,
+}
+
+fn tool_use(id: &str, name: &str, input: Value) -> ToolUse {
+    ToolUse {
+        id: id.into(),
+        name: name.into(),
+        input,
+    }
+}
+
+/// A response body that arrives in the given chunks, one per read.
+struct ChunkedBody {
+    chunks: VecDeque<Vec<u8>>,
+}
+
+impl AsyncRead for ChunkedBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let Some(chunk) = self.chunks.front_mut() else {
+            return Poll::Ready(Ok(0));
+        };
+        let len = chunk.len().min(buf.len());
+        buf[..len].copy_from_slice(&chunk[..len]);
+        chunk.drain(..len);
+        if chunk.is_empty() {
+            self.chunks.pop_front();
+        }
+        Poll::Ready(Ok(len))
+    }
+}
+
+/// A client that responds to every request with the body, split at the
+/// given byte offsets.
+fn http_client(body: &str, split_at: &[usize]) -> Arc<HttpClientWithUrl> {
+    let mut chunks = VecDeque::new();
+    let mut start = 0;
+    for end in split_at.iter().copied().chain([body.len()]) {
+        if end > start {
+            chunks.push_back(body.as_bytes()[start..end].to_vec());
+            start = end;
+        }
+    }
+    FakeHttpClient::create(move |_| {
+        let body = ChunkedBody {
+            chunks: chunks.clone(),
+        };
+        async move {
+            Ok(Response::builder()
+                .status(200)
+                .body(AsyncBody::from_reader(body))?)
+        }
+    })
+}
+
+fn request() -> LanguageModelRequest {
+    LanguageModelRequest {
+        messages: vec![LanguageModelRequestMessage {
+            role: Role::User,
+            content: vec![MessageContent::Text("Compare the two files.".into())],
+            cache: false,
+        }],
+        ..Default::default()
+    }
+}
+
+/// Streams the body through the provider's parsing, as if it had responded
+/// with it in chunks split at the given byte offsets.
+fn replay(provider: Provider, body: &str, split_at: &[usize]) -> StreamSummary {
+    let client = http_client(body, split_at);
+    block_on(async move {
+        match provider {
+            Provider::Anthropic => {
+                let request = into_anthropic(
+                    request(),
+                    "claude-sonnet-4-20250514".into(),
+                    1.0,
+                    4096,
+                    AnthropicModelMode::Default,
+                );
+                let events = anthropic::stream_completion(
+                    client.as_ref(),
+                    anthropic::ANTHROPIC_API_URL,
+                    "api-key",
+                    request,
+                )
+                .await
+                .unwrap();
+                summarize(AnthropicEventMapper::new().map_stream(events)).await
+            }
+            Provider::OpenAi => {
+                let request = into_open_ai(request(), &open_ai::Model::FourOmni, None);
+                let events = open_ai::stream_completion(
+                    client.as_ref(),
+                    open_ai::OPEN_AI_API_URL,
+                    "api-key",
+                    request,
+                )
+                .await
+                .unwrap();
+                let mut summary = summarize(OpenAiEventMapper::new().map_stream(events)).await;
+                // The tool calls that finish together are reported in no
+                // particular order.
+                summary
+                    .tool_uses
+                    .sort_by_key(|tool_use| tool_use.id.clone());
+                summary
+            }
+            Provider::Google => {
+                let request = into_google(
+                    request(),
+                    "gemini-2.5-flash".into(),
+                    GoogleModelMode::Default,
+                );
+                let events = google_ai::stream_generate_content(
+                    client.as_ref(),
+                    google_ai::API_URL,
+                    "api-key",
+                    request,
+                )
+                .await
+                .unwrap();
+                let mut summary = summarize(GoogleEventMapper::new().map_stream(events)).await;
+                // Gemini's function calls have no ids, so the ones they're
+                // given differ between replays.
+                for tool_use in &mut summary.tool_uses {
+                    assert!(tool_use.id.starts_with(&tool_use.name));
+                    tool_use.id.clear();
+                }
+                summary
+            }
+        }
+    })
+}
+
+async fn summarize(
+    events: impl Stream<Item = Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
+) -> StreamSummary {
+    let mut summary = StreamSummary::default();
+    let mut partial_inputs = HashMap::default();
+    let mut events = pin!(events);
+    while let Some(event) = events.next().await {
+        match event {
+            Ok(LanguageModelCompletionEvent::StartMessage { message_id }) => {
+                summary.message_id = Some(message_id);
+            }
+            Ok(LanguageModelCompletionEvent::Text(text)) => summary.text.push_str(&text),
+            Ok(LanguageModelCompletionEvent::Thinking { text, signature }) => {
+                summary.thinking.push_str(&text);
+                if signature.is_some() {
+                    summary.signature = signature;
+                }
+            }
+            Ok(LanguageModelCompletionEvent::ToolUse(tool_use)) => {
+                let id = tool_use.id.to_string();
+                if tool_use.is_input_complete {
+                    if let Some(partial_input) = partial_inputs.remove(&id) {
+                        assert!(
+                            tool_use.raw_input.starts_with(&partial_input),
+                            "partial input {partial_input:?} of {id} isn't a prefix of {:?}",
+                            tool_use.raw_input
+                        );
+                    }
+                    summary.tool_uses.push(ToolUse {
+                        id,
+                        name: tool_use.name.to_string(),
+                        input: tool_use.input,
+                    });
+                } else {
+                    let previous = partial_inputs.insert(id, tool_use.raw_input.clone());
+                    if let Some(previous) = previous {
+                        assert!(tool_use.raw_input.starts_with(&previous));
+                    }
+                }
+            }
+            Ok(LanguageModelCompletionEvent::UsageUpdate(usage)) => summary.usage = Some(usage),
+            Ok(LanguageModelCompletionEvent::Stop(stop_reason)) => {
+                summary.stop_reason = Some(stop_reason);
+            }
+            Ok(
+                LanguageModelCompletionEvent::StatusUpdate(_)
+                | LanguageModelCompletionEvent::WebSources(_)
+                | LanguageModelCompletionEvent::Retrying { .. },
+            ) => {}
+            Err(error) => summary.errors.push(error.to_string()),
+        }
+    }
+    summary
+}
+
+/// A SplitMix64 generator, so that failing cases can be reproduced from the
+/// seed they're reported with.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        ((z ^ (z >> 31)) % bound as u64) as usize
+    }
+
+    /// Up to `max_count` of the candidates, in order.
+    fn split_points(&mut self, candidates: &[usize], max_count: usize) -> Vec<usize> {
+        let count = self.below(max_count) + 1;
+        let mut points = (0..count)
+            .map(|_| candidates[self.below(candidates.len())])
+            .collect::<Vec<_>>();
+        points.sort_unstable();
+        points.dedup();
+        points
+    }
+}
+
+/// Splits the text into pieces at the given char boundaries, the way a
+/// provider splits what it generates into deltas.
+fn split_text<'a>(text: &'a str, split_at: &[usize]) -> Vec<&'a str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for end in split_at.iter().copied().chain([text.len()]) {
+        pieces.push(&text[start..end]);
+        start = end;
+    }
+    pieces
+}
+
+fn sse(events: impl IntoIterator<Item = Value>) -> String {
+    events
+        .into_iter()
+        .map(|event| format!("data: {event}\n\n"))
+        .collect()
+}
+
+/// Gemini ends the lines of its stream with CRLF.
+fn google_function_calls() -> String {
+    GOOGLE_FUNCTION_CALLS.replace('\n', "\r\n")
+}
+
+fn fixtures() -> Vec<(Provider, String)> {
+    vec![
+        (Provider::Anthropic, ANTHROPIC_TOOL_USE.into()),
+        (Provider::Anthropic, ANTHROPIC_ERROR.into()),
+        (Provider::OpenAi, OPEN_AI_TOOL_CALLS.into()),
+        (Provider::OpenAi, OPEN_AI_ERROR.into()),
+        (Provider::Google, google_function_calls()),
+    ]
+}
+
+/// A tool input with the characters that are easy to get wrong when the JSON
+/// is reassembled from pieces.
+fn tool_input() -> Value {
+    json!({
+        "path": "src/naïve/日本語.rs",
+        "edits": [{
+            "old_text": "fn main() {\n\t\"quoted\" \\ {}\n}",
+            "new_text": "🦀 \u{7}",
+        }],
+        "create": false,
+        "line": 42,
+    })
+}
+
+#[test]
+fn test_anthropic_tool_use() {
+    assert_eq!(
+        replay(Provider::Anthropic, ANTHROPIC_TOOL_USE, &[]),
+        StreamSummary {
+            message_id: Some("msg_01XFDUDYJgAACzvnptvVoYEL".into()),
+            text: "Je vais lire les deux fichiers — 日本語のファイル名も大丈夫です 🦀".into(),
+            thinking: "The user wants both files compared. I'll read them first.".into(),
+            signature: Some("EqQBCgIYAhIM1gbcDa9GJwZA2b3hGgxuKaHHRnF1Rtu3bw==".into()),
+            tool_uses: vec![
+                tool_use(
+                    "toolu_01A09q90qw90lq917835lq9",
+                    "read_file",
+                    json!({ "path": "src/café.rs" }),
+                ),
+                tool_use(
+                    "toolu_01B4Hb9nU8gqXRmVdYkmWh1g",
+                    "read_file",
+                    json!({ "path": "docs/日本.md", "start_line": 10 }),
+                ),
+            ],
+            usage: Some(TokenUsage {
+                input_tokens: 472,
+                output_tokens: 89,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 128,
+            }),
+            stop_reason: Some(StopReason::ToolUse),
+            errors: Vec::new(),
+        }
+    );
+}
+
+#[test]
+fn test_anthropic_error_event() {
+    assert_eq!(
+        replay(Provider::Anthropic, ANTHROPIC_ERROR, &[]),
+        StreamSummary {
+            message_id: Some("msg_01Mv7sSDZz5VJrNaUXaCVYLk".into()),
+            text: "Sure — let me".into(),
+            usage: Some(TokenUsage {
+                input_tokens: 25,
+                output_tokens: 1,
+                ..Default::default()
+            }),
+            errors: vec![
+                "an error occurred while interacting with the Anthropic API: \
+                 overloaded_error: Overloaded"
+                    .into()
+            ],
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_open_ai_interleaved_tool_calls() {
+    assert_eq!(
+        replay(Provider::OpenAi, OPEN_AI_TOOL_CALLS, &[]),
+        StreamSummary {
+            text: "Lecture des fichiers… données 📂".into(),
+            tool_uses: vec![
+                tool_use(
+                    "call_Qx7B2mN4",
+                    "read_file",
+                    json!({ "path": "src/naïve.rs" }),
+                ),
+                tool_use("call_Zt91kLp0", "grep", json!({ "regex": "fn main" })),
+            ],
+            stop_reason: Some(StopReason::ToolUse),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_open_ai_error_event() {
+    assert_eq!(
+        replay(Provider::OpenAi, OPEN_AI_ERROR, &[]),
+        StreamSummary {
+            text: "Voilà ".into(),
+            errors: vec![
+                "The server had an error while processing your request. Sorry about that!".into()
+            ],
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_google_function_calls() {
+    assert_eq!(
+        replay(Provider::Google, &google_function_calls(), &[]),
+        StreamSummary {
+            text: "Voici les résultats — 完了しました ✅".into(),
+            tool_uses: vec![
+                tool_use("", "read_file", json!({ "path": "src/über.rs" })),
+                tool_use(
+                    "",
+                    "grep",
+                    json!({ "regex": "fn main", "include": ["*.rs"] }),
+                ),
+            ],
+            usage: Some(TokenUsage {
+                input_tokens: 120,
+                output_tokens: 31,
+                ..Default::default()
+            }),
+            stop_reason: Some(StopReason::ToolUse),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_replays_are_independent_of_chunk_boundaries() {
+    for (provider, body) in fixtures() {
+        let expected = replay(provider, &body, &[]);
+
+        // Splitting the body in two everywhere splits every multi-byte
+        // character and every CRLF.
+        for ix in 1..body.len() {
+            assert_eq!(
+                replay(provider, &body, &[ix]),
+                expected,
+                "{provider:?} response split at byte {ix}"
+            );
+        }
+
+        let offsets = (1..body.len()).collect::<Vec<_>>();
+        for seed in 0..ITERATIONS {
+            let mut rng = Rng(seed);
+            let split_at = rng.split_points(&offsets, 64);
+            assert_eq!(
+                replay(provider, &body, &split_at),
+                expected,
+                "{provider:?} response split with seed {seed}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_anthropic_tool_input_reassembly() {
+    let input = tool_input();
+    let raw_input = input.to_string();
+    let boundaries = raw_input
+        .char_indices()
+        .map(|(ix, _)| ix)
+        .chain([raw_input.len()])
+        .collect::<Vec<_>>();
+
+    for seed in 0..ITERATIONS {
+        let mut rng = Rng(seed);
+        let deltas = split_text(&raw_input, &rng.split_points(&boundaries, 16));
+        let body = sse([
+            json!({
+                "type": "message_start",
+                "message": {
+                    "id": "msg_1",
+                    "type": "message",
+                    "role": "assistant",
+                    "content": [],
+                    "model": "claude-sonnet-4-20250514",
+                    "usage": {},
+                },
+            }),
+            json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": {
+                    "type": "tool_use",
+                    "id": "toolu_1",
+                    "name": "edit_file",
+                    "input": {},
+                },
+            }),
+        ]
+        .into_iter()
+        .chain(deltas.iter().map(|delta| {
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": { "type": "input_json_delta", "partial_json": delta },
+            })
+        }))
+        .chain([
+            json!({ "type": "content_block_stop", "index": 0 }),
+            json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": "tool_use" },
+                "usage": {},
+            }),
+            json!({ "type": "message_stop" }),
+        ]));
+        let offsets = (1..body.len()).collect::<Vec<_>>();
+        let summary = replay(Provider::Anthropic, &body, &rng.split_points(&offsets, 32));
+
+        assert_eq!(
+            summary.tool_uses,
+            [tool_use("toolu_1", "edit_file", input.clone())],
+            "seed {seed}"
+        );
+        assert_eq!(summary.errors, Vec::<String>::new(), "seed {seed}");
+        assert_eq!(
+            summary.stop_reason,
+            Some(StopReason::ToolUse),
+            "seed {seed}"
+        );
+    }
+}
+
+#[test]
+fn test_open_ai_tool_arguments_reassembly() {
+    let input = tool_input();
+    let raw_input = input.to_string();
+    let boundaries = raw_input
+        .char_indices()
+        .map(|(ix, _)| ix)
+        .chain([raw_input.len()])
+        .collect::<Vec<_>>();
+    let chunk = |delta: Value, finish_reason: Option<&str>| {
+        json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "created": 1751986800,
+            "model": "gpt-4o",
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+        })
+    };
+
+    for seed in 0..ITERATIONS {
+        let mut rng = Rng(seed);
+        let ids = ["call_1", "call_2", "call_3"];
+        let mut deltas = ids
+            .iter()
+            .map(|_| {
+                split_text(&raw_input, &rng.split_points(&boundaries, 16))
+                    .into_iter()
+                    .collect::<VecDeque<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut events = ids
+            .iter()
+            .enumerate()
+            .map(|(index, id)| {
+                let tool_call = json!({
+                    "index": index,
+                    "id": id,
+                    "type": "function",
+                    "function": { "name": "edit_file", "arguments": "" },
+                });
+                chunk(json!({ "tool_calls": [tool_call] }), None)
+            })
+            .collect::<Vec<_>>();
+        // The arguments of the calls are interleaved, but those of each call
+        // stay in order.
+        while deltas.iter().any(|deltas| !deltas.is_empty()) {
+            let index = rng.below(ids.len());
+            if let Some(delta) = deltas[index].pop_front() {
+                let tool_call = json!({ "index": index, "function": { "arguments": delta } });
+                events.push(chunk(json!({ "tool_calls": [tool_call] }), None));
+            }
+        }
+        events.push(chunk(json!({}), Some("tool_calls")));
+        let body = sse(events) + "data: [DONE]\n\n";
+        let offsets = (1..body.len()).collect::<Vec<_>>();
+        let summary = replay(Provider::OpenAi, &body, &rng.split_points(&offsets, 32));
+
+        assert_eq!(
+            summary.tool_uses,
+            ids.map(|id| tool_use(id, "edit_file", input.clone())),
+            "seed {seed}"
+        );
+        assert_eq!(summary.errors, Vec::<String>::new(), "seed {seed}");
+        assert_eq!(
+            summary.stop_reason,
+            Some(StopReason::ToolUse),
+            "seed {seed}"
+        );
+    }
+}
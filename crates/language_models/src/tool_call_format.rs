@@ -0,0 +1,606 @@
+//! Converts tool definitions, calls and results between the canonical
+//! representation used by `language_model` and the wire formats of the
+//! OpenAI, Anthropic and Gemini APIs. The wire formats of other providers are
+//! OpenAI's, so the agent's tools only ever see the canonical one.
+
+use std::str::FromStr as _;
+use std::sync::Arc;
+use std::sync::atomic::{self, AtomicU64};
+
+use anyhow::{Result, anyhow, bail};
+use language_model::{
+    LanguageModelCompletionError, LanguageModelImage, LanguageModelRequestTool,
+    LanguageModelToolResult, LanguageModelToolResultContent, LanguageModelToolUse,
+    LanguageModelToolUseId,
+};
+use serde_json::json;
+
+/// Sent to OpenAI in place of an image returned by a tool, since its tool
+/// messages can only contain text. The image is sent in a message after it.
+pub const OPEN_AI_TOOL_IMAGE_PLACEHOLDER: &str =
+    "The tool returned an image, which is attached below.";
+
+/// Sent to Gemini in place of an image returned by a tool. The image is sent
+/// in a part after it.
+pub const GOOGLE_TOOL_IMAGE_PLACEHOLDER: &str = "Tool responded with an image";
+
+/// A provider's wire format for tools.
+///
+/// Converting to the wire format and back yields the canonical value again,
+/// except for what the format has no room for: Gemini's function calls have
+/// no ids, so ids are generated when they're parsed, and tool results are
+/// parsed against the tool use they answer, for the tool name or id they
+/// don't carry.
+pub trait ToolCallFormat {
+    type ToolDefinition;
+    type ToolCall;
+    type ToolResult;
+
+    fn to_tool_definition(tool: LanguageModelRequestTool) -> Self::ToolDefinition;
+
+    fn from_tool_definition(definition: Self::ToolDefinition) -> LanguageModelRequestTool;
+
+    fn to_tool_call(tool_use: &LanguageModelToolUse) -> Self::ToolCall;
+
+    fn from_tool_call(
+        call: Self::ToolCall,
+    ) -> Result<LanguageModelToolUse, LanguageModelCompletionError>;
+
+    fn to_tool_result(result: &LanguageModelToolResult) -> Self::ToolResult;
+
+    fn from_tool_result(
+        result: Self::ToolResult,
+        tool_use: &LanguageModelToolUse,
+    ) -> Result<LanguageModelToolResult>;
+}
+
+pub fn convert_tool_definition<From: ToolCallFormat, To: ToolCallFormat>(
+    definition: From::ToolDefinition,
+) -> To::ToolDefinition {
+    To::to_tool_definition(From::from_tool_definition(definition))
+}
+
+pub fn convert_tool_call<From: ToolCallFormat, To: ToolCallFormat>(
+    call: From::ToolCall,
+) -> Result<To::ToolCall, LanguageModelCompletionError> {
+    Ok(To::to_tool_call(&From::from_tool_call(call)?))
+}
+
+pub fn convert_tool_result<From: ToolCallFormat, To: ToolCallFormat>(
+    result: From::ToolResult,
+    tool_use: &LanguageModelToolUse,
+) -> Result<To::ToolResult> {
+    Ok(To::to_tool_result(&From::from_tool_result(
+        result, tool_use,
+    )?))
+}
+
+/// Parses the JSON input of a tool call, which is streamed as a string by
+/// OpenAI and Anthropic.
+pub fn parse_tool_input(
+    id: LanguageModelToolUseId,
+    name: Arc<str>,
+    raw_input: String,
+) -> Result<LanguageModelToolUse, LanguageModelCompletionError> {
+    let input = if raw_input.trim().is_empty() {
+        Ok(json!({}))
+    } else {
+        serde_json::Value::from_str(&raw_input)
+    };
+    match input {
+        Ok(input) => Ok(LanguageModelToolUse {
+            id,
+            name,
+            is_input_complete: true,
+            input,
+            raw_input,
+        }),
+        Err(error) => Err(LanguageModelCompletionError::BadInputJson {
+            id,
+            tool_name: name,
+            raw_input: raw_input.into(),
+            json_parse_error: error.to_string(),
+        }),
+    }
+}
+
+fn image_from_base64(data: String) -> LanguageModelImage {
+    LanguageModelImage {
+        source: data.into(),
+        size: Default::default(),
+    }
+}
+
+pub struct OpenAiToolFormat;
+
+impl ToolCallFormat for OpenAiToolFormat {
+    type ToolDefinition = open_ai::ToolDefinition;
+    type ToolCall = open_ai::ToolCall;
+    type ToolResult = open_ai::RequestMessage;
+
+    fn to_tool_definition(tool: LanguageModelRequestTool) -> Self::ToolDefinition {
+        open_ai::ToolDefinition::Function {
+            function: open_ai::FunctionDefinition {
+                name: tool.name,
+                description: Some(tool.description),
+                parameters: Some(tool.input_schema),
+            },
+        }
+    }
+
+    fn from_tool_definition(definition: Self::ToolDefinition) -> LanguageModelRequestTool {
+        let open_ai::ToolDefinition::Function { function } = definition;
+        LanguageModelRequestTool {
+            name: function.name,
+            description: function.description.unwrap_or_default(),
+            input_schema: function
+                .parameters
+                .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+        }
+    }
+
+    fn to_tool_call(tool_use: &LanguageModelToolUse) -> Self::ToolCall {
+        open_ai::ToolCall {
+            id: tool_use.id.to_string(),
+            content: open_ai::ToolCallContent::Function {
+                function: open_ai::FunctionContent {
+                    name: tool_use.name.to_string(),
+                    arguments: serde_json::to_string(&tool_use.input).unwrap_or_default(),
+                },
+            },
+        }
+    }
+
+    fn from_tool_call(
+        call: Self::ToolCall,
+    ) -> Result<LanguageModelToolUse, LanguageModelCompletionError> {
+        let open_ai::ToolCallContent::Function { function } = call.content;
+        parse_tool_input(call.id.into(), function.name.into(), function.arguments)
+    }
+
+    fn to_tool_result(result: &LanguageModelToolResult) -> Self::ToolResult {
+        let text = match &result.content {
+            LanguageModelToolResultContent::Text(text) => text.to_string(),
+            LanguageModelToolResultContent::Image(_) => OPEN_AI_TOOL_IMAGE_PLACEHOLDER.into(),
+        };
+        open_ai::RequestMessage::Tool {
+            content: open_ai::MessageContent::Plain(text),
+            tool_call_id: result.tool_use_id.to_string(),
+        }
+    }
+
+    fn from_tool_result(
+        result: Self::ToolResult,
+        tool_use: &LanguageModelToolUse,
+    ) -> Result<LanguageModelToolResult> {
+        let open_ai::RequestMessage::Tool {
+            content,
+            tool_call_id,
+        } = result
+        else {
+            bail!("not a tool message");
+        };
+        let text = match content {
+            open_ai::MessageContent::Plain(text) => text,
+            open_ai::MessageContent::Multipart(parts) => parts
+                .into_iter()
+                .filter_map(|part| match part {
+                    open_ai::MessagePart::Text { text } => Some(text),
+                    open_ai::MessagePart::Image { .. } => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        Ok(LanguageModelToolResult {
+            tool_use_id: tool_call_id.into(),
+            tool_name: tool_use.name.clone(),
+            is_error: false,
+            content: LanguageModelToolResultContent::Text(text.into()),
+            output: None,
+        })
+    }
+}
+
+pub struct AnthropicToolFormat;
+
+impl ToolCallFormat for AnthropicToolFormat {
+    type ToolDefinition = anthropic::Tool;
+    type ToolCall = anthropic::RequestContent;
+    type ToolResult = anthropic::RequestContent;
+
+    fn to_tool_definition(tool: LanguageModelRequestTool) -> Self::ToolDefinition {
+        anthropic::Tool {
+            name: tool.name,
+            description: tool.description,
+            input_schema: tool.input_schema,
+        }
+    }
+
+    fn from_tool_definition(definition: Self::ToolDefinition) -> LanguageModelRequestTool {
+        LanguageModelRequestTool {
+            name: definition.name,
+            description: definition.description,
+            input_schema: definition.input_schema,
+        }
+    }
+
+    fn to_tool_call(tool_use: &LanguageModelToolUse) -> Self::ToolCall {
+        anthropic::RequestContent::ToolUse {
+            id: tool_use.id.to_string(),
+            name: tool_use.name.to_string(),
+            input: tool_use.input.clone(),
+            cache_control: None,
+        }
+    }
+
+    fn from_tool_call(
+        call: Self::ToolCall,
+    ) -> Result<LanguageModelToolUse, LanguageModelCompletionError> {
+        let anthropic::RequestContent::ToolUse {
+            id, name, input, ..
+        } = call
+        else {
+            return Err(LanguageModelCompletionError::Other(anyhow!(
+                "not a tool use"
+            )));
+        };
+        Ok(LanguageModelToolUse {
+            id: id.into(),
+            name: name.into(),
+            raw_input: input.to_string(),
+            input,
+            is_input_complete: true,
+        })
+    }
+
+    fn to_tool_result(result: &LanguageModelToolResult) -> Self::ToolResult {
+        anthropic::RequestContent::ToolResult {
+            tool_use_id: result.tool_use_id.to_string(),
+            is_error: result.is_error,
+            content: match &result.content {
+                LanguageModelToolResultContent::Text(text) => {
+                    anthropic::ToolResultContent::Plain(text.to_string())
+                }
+                LanguageModelToolResultContent::Image(image) => {
+                    anthropic::ToolResultContent::Multipart(vec![
+                        anthropic::ToolResultPart::Image {
+                            source: anthropic::ImageSource {
+                                source_type: "base64".to_string(),
+                                media_type: "image/png".to_string(),
+                                data: image.source.to_string(),
+                            },
+                        },
+                    ])
+                }
+            },
+            cache_control: None,
+        }
+    }
+
+    fn from_tool_result(
+        result: Self::ToolResult,
+        tool_use: &LanguageModelToolUse,
+    ) -> Result<LanguageModelToolResult> {
+        let anthropic::RequestContent::ToolResult {
+            tool_use_id,
+            is_error,
+            content,
+            ..
+        } = result
+        else {
+            bail!("not a tool result");
+        };
+        let content = match content {
+            anthropic::ToolResultContent::Plain(text) => {
+                LanguageModelToolResultContent::Text(text.into())
+            }
+            anthropic::ToolResultContent::Multipart(parts) => {
+                let mut texts = Vec::new();
+                let mut image = None;
+                for part in parts {
+                    match part {
+                        anthropic::ToolResultPart::Text { text } => texts.push(text),
+                        anthropic::ToolResultPart::Image { source } => {
+                            image.get_or_insert(source.data);
+                        }
+                    }
+                }
+                match image {
+                    Some(data) if texts.is_empty() => {
+                        LanguageModelToolResultContent::Image(image_from_base64(data))
+                    }
+                    _ => LanguageModelToolResultContent::Text(texts.join("\n").into()),
+                }
+            }
+        };
+        Ok(LanguageModelToolResult {
+            tool_use_id: tool_use_id.into(),
+            tool_name: tool_use.name.clone(),
+            is_error,
+            content,
+            output: None,
+        })
+    }
+}
+
+pub struct GoogleToolFormat;
+
+static GOOGLE_TOOL_CALL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl ToolCallFormat for GoogleToolFormat {
+    type ToolDefinition = google_ai::FunctionDeclaration;
+    type ToolCall = google_ai::FunctionCall;
+    type ToolResult = google_ai::FunctionResponse;
+
+    fn to_tool_definition(tool: LanguageModelRequestTool) -> Self::ToolDefinition {
+        google_ai::FunctionDeclaration {
+            name: tool.name,
+            description: tool.description,
+            parameters: tool.input_schema,
+        }
+    }
+
+    fn from_tool_definition(definition: Self::ToolDefinition) -> LanguageModelRequestTool {
+        LanguageModelRequestTool {
+            name: definition.name,
+            description: definition.description,
+            input_schema: definition.parameters,
+        }
+    }
+
+    fn to_tool_call(tool_use: &LanguageModelToolUse) -> Self::ToolCall {
+        google_ai::FunctionCall {
+            name: tool_use.name.to_string(),
+            args: tool_use.input.clone(),
+        }
+    }
+
+    fn from_tool_call(
+        call: Self::ToolCall,
+    ) -> Result<LanguageModelToolUse, LanguageModelCompletionError> {
+        let id = GOOGLE_TOOL_CALL_COUNTER.fetch_add(1, atomic::Ordering::SeqCst);
+        Ok(LanguageModelToolUse {
+            id: format!("{}-{}", call.name, id).into(),
+            name: call.name.into(),
+            raw_input: call.args.to_string(),
+            input: call.args,
+            is_input_complete: true,
+        })
+    }
+
+    fn to_tool_result(result: &LanguageModelToolResult) -> Self::ToolResult {
+        let output = match &result.content {
+            LanguageModelToolResultContent::Text(text) => text.to_string(),
+            LanguageModelToolResultContent::Image(_) => GOOGLE_TOOL_IMAGE_PLACEHOLDER.into(),
+        };
+        google_ai::FunctionResponse {
+            name: result.tool_name.to_string(),
+            // The API expects a valid JSON object
+            response: json!({ "output": output }),
+        }
+    }
+
+    fn from_tool_result(
+        result: Self::ToolResult,
+        tool_use: &LanguageModelToolUse,
+    ) -> Result<LanguageModelToolResult> {
+        let output = match result.response.get("output") {
+            Some(serde_json::Value::String(output)) => output.clone(),
+            _ => result.response.to_string(),
+        };
+        Ok(LanguageModelToolResult {
+            tool_use_id: tool_use.id.clone(),
+            tool_name: result.name.into(),
+            is_error: false,
+            content: LanguageModelToolResultContent::Text(output.into()),
+            output: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool() -> LanguageModelRequestTool {
+        LanguageModelRequestTool {
+            name: "read_file".into(),
+            description: "Reads a file in the project.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": { "path": { "type": "string" } },
+                "required": ["path"],
+            }),
+        }
+    }
+
+    fn tool_use() -> LanguageModelToolUse {
+        let input = json!({ "path": "src/main.rs", "start_line": 3 });
+        LanguageModelToolUse {
+            id: "call_1".into(),
+            name: "read_file".into(),
+            raw_input: input.to_string(),
+            input,
+            is_input_complete: true,
+        }
+    }
+
+    fn tool_result(content: LanguageModelToolResultContent) -> LanguageModelToolResult {
+        LanguageModelToolResult {
+            tool_use_id: "call_1".into(),
+            tool_name: "read_file".into(),
+            is_error: false,
+            content,
+            output: None,
+        }
+    }
+
+    fn image() -> LanguageModelImage {
+        image_from_base64("iVBORw0KGgo=".into())
+    }
+
+    /// Sends the value over the wire: serializes it and parses it back.
+    fn over_the_wire<T: serde::Serialize + serde::de::DeserializeOwned>(value: T) -> T {
+        serde_json::from_value(serde_json::to_value(value).unwrap()).unwrap()
+    }
+
+    fn round_trip_tool_definition<F: ToolCallFormat>(tool: LanguageModelRequestTool)
+    where
+        F::ToolDefinition: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let definition = over_the_wire(F::to_tool_definition(tool.clone()));
+        assert_eq!(F::from_tool_definition(definition), tool);
+    }
+
+    fn round_trip_tool_call<F: ToolCallFormat>(
+        tool_use: &LanguageModelToolUse,
+    ) -> LanguageModelToolUse
+    where
+        F::ToolCall: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        F::from_tool_call(over_the_wire(F::to_tool_call(tool_use))).unwrap()
+    }
+
+    fn round_trip_tool_result<F: ToolCallFormat>(
+        result: &LanguageModelToolResult,
+    ) -> LanguageModelToolResult
+    where
+        F::ToolResult: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        F::from_tool_result(over_the_wire(F::to_tool_result(result)), &tool_use()).unwrap()
+    }
+
+    #[test]
+    fn test_open_ai_round_trip() {
+        round_trip_tool_definition::<OpenAiToolFormat>(tool());
+        assert_eq!(
+            round_trip_tool_call::<OpenAiToolFormat>(&tool_use()),
+            tool_use()
+        );
+
+        let text_result = tool_result(LanguageModelToolResultContent::Text("fn main() {}".into()));
+        assert_eq!(
+            round_trip_tool_result::<OpenAiToolFormat>(&text_result),
+            text_result
+        );
+        assert_eq!(
+            round_trip_tool_result::<OpenAiToolFormat>(&tool_result(
+                LanguageModelToolResultContent::Image(image())
+            )),
+            tool_result(LanguageModelToolResultContent::Text(
+                OPEN_AI_TOOL_IMAGE_PLACEHOLDER.into()
+            ))
+        );
+
+        let malformed_call = open_ai::ToolCall {
+            id: "call_1".into(),
+            content: open_ai::ToolCallContent::Function {
+                function: open_ai::FunctionContent {
+                    name: "read_file".into(),
+                    arguments: "{\"path\":".into(),
+                },
+            },
+        };
+        assert!(matches!(
+            OpenAiToolFormat::from_tool_call(malformed_call),
+            Err(LanguageModelCompletionError::BadInputJson { raw_input, .. })
+                if raw_input.as_ref() == "{\"path\":"
+        ));
+    }
+
+    #[test]
+    fn test_anthropic_round_trip() {
+        round_trip_tool_definition::<AnthropicToolFormat>(tool());
+        assert_eq!(
+            round_trip_tool_call::<AnthropicToolFormat>(&tool_use()),
+            tool_use()
+        );
+
+        let text_result = tool_result(LanguageModelToolResultContent::Text("fn main() {}".into()));
+        assert_eq!(
+            round_trip_tool_result::<AnthropicToolFormat>(&text_result),
+            text_result
+        );
+        let image_result = tool_result(LanguageModelToolResultContent::Image(image()));
+        assert_eq!(
+            round_trip_tool_result::<AnthropicToolFormat>(&image_result),
+            image_result
+        );
+        let error_result = LanguageModelToolResult {
+            is_error: true,
+            ..tool_result(LanguageModelToolResultContent::Text("No such file".into()))
+        };
+        assert_eq!(
+            round_trip_tool_result::<AnthropicToolFormat>(&error_result),
+            error_result
+        );
+    }
+
+    #[test]
+    fn test_google_round_trip() {
+        round_trip_tool_definition::<GoogleToolFormat>(tool());
+
+        let tool_use = tool_use();
+        let call = round_trip_tool_call::<GoogleToolFormat>(&tool_use);
+        assert!(call.id.to_string().starts_with("read_file-"));
+        assert_eq!(
+            call,
+            LanguageModelToolUse {
+                id: call.id.clone(),
+                ..tool_use
+            }
+        );
+
+        let text_result = tool_result(LanguageModelToolResultContent::Text("fn main() {}".into()));
+        assert_eq!(
+            round_trip_tool_result::<GoogleToolFormat>(&text_result),
+            text_result
+        );
+        assert_eq!(
+            round_trip_tool_result::<GoogleToolFormat>(&tool_result(
+                LanguageModelToolResultContent::Image(image())
+            )),
+            tool_result(LanguageModelToolResultContent::Text(
+                GOOGLE_TOOL_IMAGE_PLACEHOLDER.into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_convert_between_formats() {
+        let tool_use = tool_use();
+        let anthropic_call = convert_tool_call::<OpenAiToolFormat, AnthropicToolFormat>(
+            OpenAiToolFormat::to_tool_call(&tool_use),
+        )
+        .unwrap();
+        let google_call =
+            convert_tool_call::<AnthropicToolFormat, GoogleToolFormat>(anthropic_call).unwrap();
+        let open_ai_call =
+            convert_tool_call::<GoogleToolFormat, OpenAiToolFormat>(google_call).unwrap();
+        let round_tripped = OpenAiToolFormat::from_tool_call(open_ai_call).unwrap();
+        assert_eq!(round_tripped.name, tool_use.name);
+        assert_eq!(round_tripped.input, tool_use.input);
+
+        let definition = convert_tool_definition::<GoogleToolFormat, AnthropicToolFormat>(
+            GoogleToolFormat::to_tool_definition(tool()),
+        );
+        assert_eq!(
+            AnthropicToolFormat::from_tool_definition(definition),
+            tool()
+        );
+
+        let result = tool_result(LanguageModelToolResultContent::Text("fn main() {}".into()));
+        let google_result = convert_tool_result::<AnthropicToolFormat, GoogleToolFormat>(
+            AnthropicToolFormat::to_tool_result(&result),
+            &tool_use,
+        )
+        .unwrap();
+        let open_ai_result =
+            convert_tool_result::<GoogleToolFormat, OpenAiToolFormat>(google_result, &tool_use)
+                .unwrap();
+        assert_eq!(
+            OpenAiToolFormat::from_tool_result(open_ai_result, &tool_use).unwrap(),
+            result
+        );
+    }
+}
@@ -140,6 +140,20 @@ impl Model {
         }
     }
 
+    /// Whether the model supports fill-in-the-middle completions.
+    pub fn supports_fim(&self) -> bool {
+        match self {
+            Self::CodestralLatest => true,
+            Self::MistralLargeLatest
+            | Self::MistralMediumLatest
+            | Self::MistralSmallLatest
+            | Self::OpenMistralNemo
+            | Self::OpenCodestralMamba
+            | Self::DevstralSmallLatest => false,
+            Self::Custom { name, .. } => name.starts_with("codestral"),
+        }
+    }
+
     pub fn supports_tools(&self) -> bool {
         match self {
             Self::CodestralLatest
@@ -376,3 +390,47 @@ pub async fn stream_completion(
         );
     }
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FimRequest {
+    pub model: String,
+    pub prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+}
+
+pub async fn fim_completion(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: FimRequest,
+) -> Result<Response> {
+    let uri = format!("{api_url}/fim/completions");
+    let request_builder = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {}", api_key));
+
+    let request = request_builder.body(AsyncBody::from(serde_json::to_string(&request)?))?;
+    let mut response = client.send(request).await?;
+
+    let mut body = String::new();
+    response.body_mut().read_to_string(&mut body).await?;
+
+    if response.status().is_success() {
+        Ok(serde_json::from_str(&body)?)
+    } else {
+        anyhow::bail!(
+            "Failed to connect to Mistral API: {} {}",
+            response.status(),
+            body,
+        );
+    }
+}
@@ -40,6 +40,7 @@ pub struct Model {
     pub supports_tools: Option<bool>,
     pub supports_vision: Option<bool>,
     pub supports_thinking: Option<bool>,
+    pub supports_insert: Option<bool>,
 }
 
 fn get_max_tokens(name: &str) -> usize {
@@ -71,6 +72,7 @@ impl Model {
         supports_tools: Option<bool>,
         supports_vision: Option<bool>,
         supports_thinking: Option<bool>,
+        supports_insert: Option<bool>,
     ) -> Self {
         Self {
             name: name.to_owned(),
@@ -82,6 +84,7 @@ impl Model {
             supports_tools,
             supports_vision,
             supports_thinking,
+            supports_insert,
         }
     }
 
@@ -172,6 +175,32 @@ pub struct ChatOptions {
     pub top_p: Option<f32>,
 }
 
+#[derive(Serialize, Debug)]
+pub struct GenerateRequest {
+    pub model: String,
+    pub prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+    pub stream: bool,
+    pub keep_alive: KeepAlive,
+    pub options: Option<ChatOptions>,
+}
+
+/// A generate request without a prompt, which makes Ollama load the model
+/// without generating anything.
+#[derive(Serialize, Debug)]
+pub struct LoadModelRequest {
+    pub model: String,
+    pub keep_alive: KeepAlive,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GenerateResponse {
+    pub response: String,
+    #[allow(unused)]
+    pub done: bool,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct ChatResponseDelta {
     #[allow(unused)]
@@ -235,6 +264,10 @@ impl ModelShow {
     pub fn supports_thinking(&self) -> bool {
         self.capabilities.iter().any(|v| v == "thinking")
     }
+
+    pub fn supports_insert(&self) -> bool {
+        self.capabilities.iter().any(|v| v == "insert")
+    }
 }
 
 pub async fn complete(
@@ -269,6 +302,68 @@ pub async fn complete(
     }
 }
 
+pub async fn generate(
+    client: &dyn HttpClient,
+    api_url: &str,
+    request: GenerateRequest,
+) -> Result<GenerateResponse> {
+    let uri = format!("{api_url}/api/generate");
+    let request_builder = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("Content-Type", "application/json");
+
+    let serialized_request = serde_json::to_string(&request)?;
+    let request = request_builder.body(AsyncBody::from(serialized_request))?;
+
+    let mut response = client.send(request).await?;
+
+    let mut body = Vec::new();
+    response.body_mut().read_to_end(&mut body).await?;
+
+    if response.status().is_success() {
+        Ok(serde_json::from_slice(&body)?)
+    } else {
+        let body_str = std::str::from_utf8(&body)?;
+        anyhow::bail!(
+            "Failed to connect to Ollama API: {} {}",
+            response.status(),
+            body_str
+        );
+    }
+}
+
+/// Loads the model into memory, so that the next request doesn't wait for it
+/// to load.
+pub async fn load_model(
+    client: &dyn HttpClient,
+    api_url: &str,
+    request: LoadModelRequest,
+) -> Result<()> {
+    let uri = format!("{api_url}/api/generate");
+    let request_builder = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("Content-Type", "application/json");
+
+    let serialized_request = serde_json::to_string(&request)?;
+    let request = request_builder.body(AsyncBody::from(serialized_request))?;
+
+    let mut response = client.send(request).await?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        let mut body = String::new();
+        response.body_mut().read_to_string(&mut body).await?;
+        anyhow::bail!(
+            "Failed to load model with Ollama API: {} {}",
+            response.status(),
+            body
+        );
+    }
+}
+
 pub async fn stream_chat_completion(
     client: &dyn HttpClient,
     api_url: &str,
@@ -539,6 +634,19 @@ mod tests {
         assert!(serialized.contains(base64_image));
     }
 
+    #[test]
+    fn serialize_load_model_request() {
+        let request = LoadModelRequest {
+            model: "llama3.2".to_string(),
+            keep_alive: KeepAlive::Duration("10m".to_string()),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({ "model": "llama3.2", "keep_alive": "10m" })
+        );
+    }
+
     #[test]
     fn serialize_chat_request_without_images() {
         let request = ChatRequest {
@@ -1,3 +1,5 @@
+pub mod responses;
+
 use anyhow::{Context as _, Result, anyhow};
 use futures::{
     AsyncBufReadExt, AsyncReadExt, StreamExt,
@@ -67,6 +69,13 @@ pub enum Model {
     FourOmni,
     #[serde(rename = "gpt-4o-mini", alias = "gpt-4o-mini")]
     FourOmniMini,
+    #[serde(rename = "gpt-4o-search-preview", alias = "gpt-4o-search-preview")]
+    FourOmniSearchPreview,
+    #[serde(
+        rename = "gpt-4o-mini-search-preview",
+        alias = "gpt-4o-mini-search-preview"
+    )]
+    FourOmniMiniSearchPreview,
     #[serde(rename = "gpt-4.1", alias = "gpt-4.1")]
     FourPointOne,
     #[serde(rename = "gpt-4.1-mini", alias = "gpt-4.1-mini")]
@@ -109,6 +118,8 @@ impl Model {
             "gpt-4-turbo-preview" => Ok(Self::FourTurbo),
             "gpt-4o" => Ok(Self::FourOmni),
             "gpt-4o-mini" => Ok(Self::FourOmniMini),
+            "gpt-4o-search-preview" => Ok(Self::FourOmniSearchPreview),
+            "gpt-4o-mini-search-preview" => Ok(Self::FourOmniMiniSearchPreview),
             "gpt-4.1" => Ok(Self::FourPointOne),
             "gpt-4.1-mini" => Ok(Self::FourPointOneMini),
             "gpt-4.1-nano" => Ok(Self::FourPointOneNano),
@@ -129,6 +140,8 @@ impl Model {
             Self::FourTurbo => "gpt-4-turbo",
             Self::FourOmni => "gpt-4o",
             Self::FourOmniMini => "gpt-4o-mini",
+            Self::FourOmniSearchPreview => "gpt-4o-search-preview",
+            Self::FourOmniMiniSearchPreview => "gpt-4o-mini-search-preview",
             Self::FourPointOne => "gpt-4.1",
             Self::FourPointOneMini => "gpt-4.1-mini",
             Self::FourPointOneNano => "gpt-4.1-nano",
@@ -149,6 +162,8 @@ impl Model {
             Self::FourTurbo => "gpt-4-turbo",
             Self::FourOmni => "gpt-4o",
             Self::FourOmniMini => "gpt-4o-mini",
+            Self::FourOmniSearchPreview => "gpt-4o-search-preview",
+            Self::FourOmniMiniSearchPreview => "gpt-4o-mini-search-preview",
             Self::FourPointOne => "gpt-4.1",
             Self::FourPointOneMini => "gpt-4.1-mini",
             Self::FourPointOneNano => "gpt-4.1-nano",
@@ -171,6 +186,8 @@ impl Model {
             Self::FourTurbo => 128_000,
             Self::FourOmni => 128_000,
             Self::FourOmniMini => 128_000,
+            Self::FourOmniSearchPreview => 128_000,
+            Self::FourOmniMiniSearchPreview => 128_000,
             Self::FourPointOne => 1_047_576,
             Self::FourPointOneMini => 1_047_576,
             Self::FourPointOneNano => 1_047_576,
@@ -210,6 +227,38 @@ impl Model {
             _ => false,
         }
     }
+
+    /// Returns whether the model searches the web before answering. These
+    /// models don't accept a temperature.
+    pub fn supports_web_search(&self) -> bool {
+        matches!(
+            self,
+            Self::FourOmniSearchPreview | Self::FourOmniMiniSearchPreview
+        )
+    }
+
+    /// Returns whether the model reasons before answering. These models don't
+    /// accept a temperature.
+    pub fn is_reasoning_model(&self) -> bool {
+        matches!(
+            self,
+            Self::O1 | Self::O1Preview | Self::O1Mini | Self::O3Mini | Self::O3 | Self::O4Mini
+        )
+    }
+
+    /// Returns whether the model can use the Responses API's built-in web
+    /// search tool.
+    pub fn supports_web_search_tool(&self) -> bool {
+        matches!(
+            self,
+            Self::FourOmni
+                | Self::FourOmniMini
+                | Self::FourPointOne
+                | Self::FourPointOneMini
+                | Self::O3
+                | Self::O4Mini
+        )
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -221,7 +270,10 @@ pub struct Request {
     pub max_tokens: Option<u32>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub stop: Vec<String>,
-    pub temperature: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<ToolChoice>,
     /// Whether to enable parallel function calling during tool use.
@@ -229,8 +281,14 @@ pub struct Request {
     pub parallel_tool_calls: Option<bool>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub tools: Vec<ToolDefinition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web_search_options: Option<WebSearchOptions>,
 }
 
+/// Enables web search, for the models that support it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WebSearchOptions {}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CompletionRequest {
     pub model: String,
@@ -370,6 +428,31 @@ pub struct ResponseMessageDelta {
     pub content: Option<String>,
     #[serde(default, skip_serializing_if = "is_none_or_empty")]
     pub tool_calls: Option<Vec<ToolCallChunk>>,
+    #[serde(default, skip_serializing_if = "is_none_or_empty")]
+    pub annotations: Option<Vec<Annotation>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Annotation {
+    UrlCitation {
+        url_citation: UrlCitation,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// A web page cited by the response, found by web search.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct UrlCitation {
+    pub url: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// The range of the response's text that cites the page.
+    #[serde(default)]
+    pub start_index: Option<usize>,
+    #[serde(default)]
+    pub end_index: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
@@ -595,6 +678,7 @@ fn adapt_response_to_stream(response: Response) -> ResponseStreamEvent {
                             Some(text_content)
                         },
                         tool_calls: None,
+                        annotations: None,
                     },
                     finish_reason: choice.finish_reason,
                 }
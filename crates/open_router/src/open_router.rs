@@ -1,12 +1,38 @@
+mod rate_limiter;
+mod retry;
+
 use anyhow::{Context, Result, anyhow};
 use futures::{AsyncBufReadExt, AsyncReadExt, StreamExt, io::BufReader, stream::BoxStream};
-use http_client::{AsyncBody, HttpClient, Method, Request as HttpRequest};
+use http_client::{AsyncBody, HttpClient, Method, Request as HttpRequest, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::convert::TryFrom;
+use std::{
+    convert::TryFrom,
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+pub use crate::rate_limiter::*;
+pub use crate::retry::*;
 
 pub const OPEN_ROUTER_API_URL: &str = "https://openrouter.ai/api/v1";
 
+/// Returned when OpenRouter rejects a request because a rate limit was exceeded.
+#[derive(Debug)]
+pub struct RateLimitError {
+    pub message: String,
+    /// How long OpenRouter asked to wait before retrying, if it said.
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for RateLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OpenRouter rate limit exceeded: {}", self.message)
+    }
+}
+
+impl std::error::Error for RateLimitError {}
+
 fn is_none_or_empty<T: AsRef<[U]>, U>(opt: &Option<T>) -> bool {
     opt.as_ref().map_or(true, |v| v.as_ref().is_empty())
 }
@@ -52,6 +78,16 @@ pub struct Model {
     pub display_name: Option<String>,
     pub max_tokens: usize,
     pub supports_tools: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<Pricing>,
+}
+
+/// Price of a model in US dollars per token.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Pricing {
+    pub prompt: f64,
+    pub completion: f64,
 }
 
 impl Model {
@@ -79,6 +115,7 @@ impl Model {
             display_name: display_name.map(|s| s.to_owned()),
             max_tokens: max_tokens.unwrap_or(2000000),
             supports_tools,
+            pricing: None,
         }
     }
 
@@ -107,7 +144,7 @@ impl Model {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Request {
     pub model: String,
     pub messages: Vec<RequestMessage>,
@@ -118,6 +155,8 @@ pub struct Request {
     pub stop: Vec<String>,
     pub temperature: f32,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<ToolChoice>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub parallel_tool_calls: Option<bool>,
@@ -125,7 +164,27 @@ pub struct Request {
     pub tools: Vec<ToolDefinition>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Request {
+    /// A rough estimate of the tokens the request uses, counting four bytes
+    /// of its messages per token along with the tokens it may generate.
+    pub fn estimated_tokens(&self) -> u64 {
+        let message_len = self
+            .messages
+            .iter()
+            .map(|message| match message {
+                RequestMessage::Assistant { content, .. } => {
+                    content.as_ref().map_or(0, String::len)
+                }
+                RequestMessage::User { content }
+                | RequestMessage::System { content }
+                | RequestMessage::Tool { content, .. } => content.len(),
+            })
+            .sum::<usize>();
+        (message_len / 4) as u64 + self.max_tokens.unwrap_or(0) as u64
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ToolChoice {
     Auto,
@@ -150,7 +209,7 @@ pub struct FunctionDefinition {
     pub parameters: Option<Value>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
 #[serde(tag = "role", rename_all = "lowercase")]
 pub enum RequestMessage {
     Assistant {
@@ -170,20 +229,20 @@ pub enum RequestMessage {
     },
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub struct ToolCall {
     pub id: String,
     #[serde(flatten)]
     pub content: ToolCallContent,
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum ToolCallContent {
     Function { function: FunctionContent },
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub struct FunctionContent {
     pub name: String,
     pub arguments: String,
@@ -266,6 +325,72 @@ pub struct ModelEntry {
     pub context_length: Option<usize>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub supported_parameters: Vec<String>,
+    #[serde(default)]
+    pub pricing: Option<ModelEntryPricing>,
+}
+
+/// Pricing as reported by the `/models` endpoint, in US dollars per token.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct ModelEntryPricing {
+    #[serde(default)]
+    pub prompt: String,
+    #[serde(default)]
+    pub completion: String,
+}
+
+impl ModelEntryPricing {
+    fn parse(&self) -> Option<Pricing> {
+        Some(Pricing {
+            prompt: self.prompt.parse().ok()?,
+            completion: self.completion.parse().ok()?,
+        })
+    }
+}
+
+/// The models offered by OpenRouter, with their context length, pricing and
+/// support for tools, as reported by its `/models` endpoint.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ModelCatalog {
+    /// The URL of the API the models were fetched from.
+    pub api_url: String,
+    pub fetched_at: SystemTime,
+    pub models: Vec<Model>,
+}
+
+impl ModelCatalog {
+    /// How long the catalog is used for before the models are fetched again.
+    pub const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+    pub async fn fetch(client: &dyn HttpClient, api_url: &str) -> Result<Self> {
+        let models = list_models(client, api_url).await?;
+        Ok(Self {
+            api_url: api_url.to_string(),
+            fetched_at: SystemTime::now(),
+            models,
+        })
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.fetched_at
+            .elapsed()
+            .map_or(true, |age| age > Self::MAX_AGE)
+    }
+
+    pub fn model(&self, id: &str) -> Option<&Model> {
+        self.models.iter().find(|model| model.id() == id)
+    }
+
+    pub fn context_length(&self, id: &str) -> Option<usize> {
+        self.model(id).map(Model::max_token_count)
+    }
+
+    pub fn pricing(&self, id: &str) -> Option<Pricing> {
+        self.model(id)?.pricing
+    }
+
+    pub fn supports_tools(&self, id: &str) -> Option<bool> {
+        self.model(id)?.supports_tools
+    }
 }
 
 pub async fn complete(
@@ -332,12 +457,16 @@ pub async fn complete(
     }
 }
 
+/// Streams a completion, holding back requests to the model's tier once
+/// OpenRouter rejects one for exceeding a rate limit.
 pub async fn stream_completion(
     client: &dyn HttpClient,
     api_url: &str,
     api_key: &str,
     request: Request,
+    rate_limiter: &RateLimiter,
 ) -> Result<BoxStream<'static, Result<ResponseStreamEvent>>> {
+    let model_id = request.model.clone();
     let uri = format!("{api_url}/chat/completions");
     let request_builder = HttpRequest::builder()
         .method(Method::POST)
@@ -395,6 +524,8 @@ pub async fn stream_completion(
             })
             .boxed())
     } else {
+        let status = response.status();
+        let retry_after = retry_after(response.headers(), SystemTime::now());
         let mut body = String::new();
         response.body_mut().read_to_string(&mut body).await?;
 
@@ -410,7 +541,22 @@ pub async fn stream_completion(
             code: String,
         }
 
-        match serde_json::from_str::<OpenRouterResponse>(&body) {
+        let response = serde_json::from_str::<OpenRouterResponse>(&body);
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let message = match response {
+                Ok(response) if !response.error.message.is_empty() => response.error.message,
+                _ => body,
+            };
+            if let Some(retry_after) = retry_after {
+                rate_limiter.pause(&model_id, retry_after);
+            }
+            return Err(anyhow!(RateLimitError {
+                message,
+                retry_after
+            }));
+        }
+
+        match response {
             Ok(response) if !response.error.message.is_empty() => {
                 let error_message = if !response.error.code.is_empty() {
                     format!("{}: {}", response.error.code, response.error.message)
@@ -425,13 +571,25 @@ pub async fn stream_completion(
             }
             _ => Err(anyhow!(
                 "Failed to connect to OpenRouter API: {} {}",
-                response.status(),
+                status,
                 body,
             )),
         }
     }
 }
 
+/// Reads how long to wait before retrying from a response's `Retry-After`
+/// header, in seconds, or else from OpenRouter's `X-RateLimit-Reset` header,
+/// the time at which the limit resets in milliseconds since the Unix epoch.
+fn retry_after(headers: &http_client::http::HeaderMap, now: SystemTime) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+    if let Some(seconds) = header("retry-after") {
+        return Some(Duration::from_secs(seconds));
+    }
+    let reset = SystemTime::UNIX_EPOCH + Duration::from_millis(header("x-ratelimit-reset")?);
+    Some(reset.duration_since(now).unwrap_or_default())
+}
+
 pub async fn list_models(client: &dyn HttpClient, api_url: &str) -> Result<Vec<Model>> {
     let uri = format!("{api_url}/models");
     let request_builder = HttpRequest::builder()
@@ -470,6 +628,7 @@ pub async fn list_models(client: &dyn HttpClient, api_url: &str) -> Result<Vec<M
                 ),
                 max_tokens: entry.context_length.unwrap_or(2000000),
                 supports_tools: Some(entry.supported_parameters.contains(&"tools".to_string())),
+                pricing: entry.pricing.as_ref().and_then(ModelEntryPricing::parse),
             })
             .collect();
 
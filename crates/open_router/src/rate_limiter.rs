@@ -0,0 +1,318 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// The number of requests per minute OpenRouter allows for free models.
+pub const FREE_MODEL_REQUESTS_PER_MINUTE: u32 = 20;
+
+/// The longest a request is held back to stay within the rate limits before
+/// it's rejected instead.
+pub const MAX_RATE_LIMIT_DELAY: Duration = Duration::from_secs(10);
+
+/// The tiers of models that OpenRouter applies different rate limits to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ModelTier {
+    /// Models whose ids end in `:free`, which are rate limited regardless of
+    /// the account's credits.
+    Free,
+    Paid,
+}
+
+impl ModelTier {
+    pub fn for_model(model_id: &str) -> Self {
+        if model_id.ends_with(":free") {
+            Self::Free
+        } else {
+            Self::Paid
+        }
+    }
+
+    pub fn default_limits(self) -> RateLimits {
+        match self {
+            Self::Free => RateLimits {
+                requests_per_minute: Some(FREE_MODEL_REQUESTS_PER_MINUTE),
+                tokens_per_minute: None,
+            },
+            Self::Paid => RateLimits::default(),
+        }
+    }
+}
+
+/// The requests and tokens per minute allowed for a tier, where `None`
+/// means unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u64>,
+}
+
+/// Returned instead of sending a request that would exceed the rate limits
+/// of its model's tier, or that OpenRouter recently asked to hold off on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimitExceeded {
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for RateLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "OpenRouter rate limit reached, retry in {} seconds",
+            self.retry_after.as_secs_f64().ceil()
+        )
+    }
+}
+
+impl std::error::Error for RateLimitExceeded {}
+
+/// A bucket of requests or tokens that refills at a steady rate up to the
+/// number allowed per minute. It goes into debt for the requests that are
+/// held back, so that they're let through in the order they were made.
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn new(per_minute: u64, now: Instant) -> Self {
+        let capacity = per_minute.max(1) as f64;
+        Self {
+            capacity,
+            available: capacity,
+            updated_at: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.available = (self.available + elapsed * self.capacity / 60.).min(self.capacity);
+        self.updated_at = now;
+    }
+
+    /// How long until the given amount is available. Amounts larger than
+    /// the bucket only wait for it to be full.
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0. {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing * 60. / self.capacity)
+        }
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.available -= amount.min(self.capacity);
+    }
+}
+
+#[derive(Debug, Default)]
+struct TierState {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    paused_until: Option<Instant>,
+}
+
+/// Keeps requests to OpenRouter within the requests and tokens per minute
+/// allowed for each model tier. Requests are held back when they'd exceed
+/// the limits, and rejected when that would take longer than the maximum
+/// delay, instead of waiting for OpenRouter to reject them.
+#[derive(Clone)]
+pub struct RateLimiter {
+    limits: Arc<HashMap<ModelTier, RateLimits>>,
+    max_delay: Duration,
+    tiers: Arc<Mutex<HashMap<ModelTier, TierState>>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(
+            [ModelTier::Free, ModelTier::Paid].map(|tier| (tier, tier.default_limits())),
+            MAX_RATE_LIMIT_DELAY,
+        )
+    }
+}
+
+impl RateLimiter {
+    pub fn new(
+        limits: impl IntoIterator<Item = (ModelTier, RateLimits)>,
+        max_delay: Duration,
+    ) -> Self {
+        Self {
+            limits: Arc::new(limits.into_iter().collect()),
+            max_delay,
+            tiers: Arc::default(),
+        }
+    }
+
+    /// Reserves room for a request to the given model that's expected to use
+    /// about `tokens` tokens, returning how long to wait before sending it.
+    /// Nothing is reserved when the wait would be longer than the maximum
+    /// delay.
+    pub fn reserve(&self, model_id: &str, tokens: u64) -> Result<Duration, RateLimitExceeded> {
+        self.reserve_at(model_id, tokens, Instant::now())
+    }
+
+    fn reserve_at(
+        &self,
+        model_id: &str,
+        tokens: u64,
+        now: Instant,
+    ) -> Result<Duration, RateLimitExceeded> {
+        let tier = ModelTier::for_model(model_id);
+        let limits = self.limits.get(&tier).copied().unwrap_or_default();
+        let mut tiers = self.tiers.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = tiers.entry(tier).or_default();
+
+        let requests = sync_bucket(&mut state.requests, limits.requests_per_minute, now);
+        let tokens_bucket = sync_bucket(&mut state.tokens, limits.tokens_per_minute, now);
+        let paused_for = state
+            .paused_until
+            .map_or(Duration::ZERO, |until| until.saturating_duration_since(now));
+        let delay = [
+            paused_for,
+            requests.as_ref().map_or(Duration::ZERO, |b| b.wait_for(1.)),
+            tokens_bucket
+                .as_ref()
+                .map_or(Duration::ZERO, |b| b.wait_for(tokens as f64)),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or_default();
+        if delay > self.max_delay {
+            return Err(RateLimitExceeded { retry_after: delay });
+        }
+
+        if let Some(requests) = requests {
+            requests.take(1.);
+        }
+        if let Some(tokens_bucket) = tokens_bucket {
+            tokens_bucket.take(tokens as f64);
+        }
+        Ok(delay)
+    }
+
+    /// Holds back requests to the given model's tier for the given duration,
+    /// after OpenRouter rejected one for exceeding a rate limit.
+    pub fn pause(&self, model_id: &str, retry_after: Duration) {
+        self.pause_at(model_id, retry_after, Instant::now());
+    }
+
+    fn pause_at(&self, model_id: &str, retry_after: Duration, now: Instant) {
+        let tier = ModelTier::for_model(model_id);
+        let mut tiers = self.tiers.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = tiers.entry(tier).or_default();
+        let until = now + retry_after;
+        state.paused_until = Some(state.paused_until.map_or(until, |paused| paused.max(until)));
+    }
+}
+
+/// Refills the bucket for a limit, creating it when the tier is first used.
+fn sync_bucket(
+    bucket: &mut Option<Bucket>,
+    per_minute: Option<impl Into<u64>>,
+    now: Instant,
+) -> Option<&mut Bucket> {
+    let per_minute = per_minute?.into();
+    let bucket = bucket.get_or_insert_with(|| Bucket::new(per_minute, now));
+    bucket.refill(now);
+    Some(bucket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FREE_MODEL: &str = "meta-llama/llama-3.3-70b-instruct:free";
+    const PAID_MODEL: &str = "anthropic/claude-sonnet-4";
+
+    fn limiter(limits: RateLimits, max_delay: Duration) -> RateLimiter {
+        RateLimiter::new(
+            [
+                (ModelTier::Free, limits),
+                (ModelTier::Paid, RateLimits::default()),
+            ],
+            max_delay,
+        )
+    }
+
+    #[test]
+    fn test_requests_are_delayed_in_order_once_the_limit_is_reached() {
+        let limits = RateLimits {
+            requests_per_minute: Some(2),
+            tokens_per_minute: None,
+        };
+        let limiter = limiter(limits, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve_at(FREE_MODEL, 0, now), Ok(Duration::ZERO));
+        assert_eq!(limiter.reserve_at(FREE_MODEL, 0, now), Ok(Duration::ZERO));
+        assert_eq!(
+            limiter.reserve_at(FREE_MODEL, 0, now),
+            Ok(Duration::from_secs(30))
+        );
+        assert_eq!(
+            limiter.reserve_at(FREE_MODEL, 0, now),
+            Ok(Duration::from_secs(60))
+        );
+        assert_eq!(
+            limiter.reserve_at(FREE_MODEL, 0, now + Duration::from_secs(30)),
+            Ok(Duration::from_secs(60))
+        );
+        assert_eq!(limiter.reserve_at(PAID_MODEL, 0, now), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_requests_are_rejected_when_the_delay_is_too_long() {
+        let limits = RateLimits {
+            requests_per_minute: None,
+            tokens_per_minute: Some(6_000),
+        };
+        let limiter = limiter(limits, Duration::from_secs(10));
+        let now = Instant::now();
+
+        assert_eq!(
+            limiter.reserve_at(FREE_MODEL, 5_000, now),
+            Ok(Duration::ZERO)
+        );
+        assert_eq!(
+            limiter.reserve_at(FREE_MODEL, 2_000, now),
+            Ok(Duration::from_secs(10))
+        );
+        assert_eq!(
+            limiter.reserve_at(FREE_MODEL, 1_000, now),
+            Err(RateLimitExceeded {
+                retry_after: Duration::from_secs(20)
+            })
+        );
+        assert_eq!(
+            limiter.reserve_at(FREE_MODEL, 1_000, now + Duration::from_secs(10)),
+            Ok(Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn test_pausing_holds_back_the_tier() {
+        let limiter = limiter(RateLimits::default(), Duration::from_secs(10));
+        let now = Instant::now();
+
+        limiter.pause_at(FREE_MODEL, Duration::from_secs(5), now);
+        assert_eq!(
+            limiter.reserve_at(FREE_MODEL, 0, now),
+            Ok(Duration::from_secs(5))
+        );
+        assert_eq!(limiter.reserve_at(PAID_MODEL, 0, now), Ok(Duration::ZERO));
+
+        limiter.pause_at(FREE_MODEL, Duration::from_secs(60), now);
+        assert_eq!(
+            limiter.reserve_at(FREE_MODEL, 0, now),
+            Err(RateLimitExceeded {
+                retry_after: Duration::from_secs(60)
+            })
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+/// How requests that fail transiently, such as when the connection drops or
+/// OpenRouter is overloaded or rate limited, are retried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The most times a request is sent, including the first attempt.
+    pub max_attempts: u32,
+    /// How long to wait before the first retry. The delay doubles with each
+    /// retry after that.
+    pub initial_delay: Duration,
+    /// The longest to wait before a retry. Requests that OpenRouter asks to
+    /// hold off on for longer than this aren't retried.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before retrying a request that failed on the given
+    /// attempt, counting from 1, or `None` if it shouldn't be retried.
+    ///
+    /// The delay is picked at random between half and all of the exponential
+    /// backoff, using `jitter` between 0 and 1, so that requests that failed
+    /// together aren't retried together. It's never shorter than the
+    /// `retry_after` OpenRouter asked for.
+    pub fn delay(
+        &self,
+        attempt: u32,
+        retry_after: Option<Duration>,
+        jitter: f64,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        if retry_after.is_some_and(|retry_after| retry_after > self.max_delay) {
+            return None;
+        }
+
+        let backoff = self
+            .initial_delay
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max_delay);
+        let delay = backoff.mul_f64(0.5 + jitter.clamp(0., 1.) / 2.);
+        Some(delay.max(retry_after.unwrap_or_default()))
+    }
+}
+
+/// A random number between 0 and 1 for jittering retry delays.
+pub fn jitter() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u8(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn test_delay_backs_off_exponentially_up_to_the_max() {
+        let policy = policy();
+        assert_eq!(policy.delay(1, None, 1.), Some(Duration::from_secs(2)));
+        assert_eq!(policy.delay(2, None, 1.), Some(Duration::from_secs(4)));
+        assert_eq!(policy.delay(3, None, 1.), Some(Duration::from_secs(5)));
+        assert_eq!(policy.delay(4, None, 1.), None);
+    }
+
+    #[test]
+    fn test_delay_is_jittered_between_half_and_all_of_the_backoff() {
+        let policy = policy();
+        assert_eq!(policy.delay(2, None, 0.), Some(Duration::from_secs(2)));
+        assert_eq!(policy.delay(2, None, 0.5), Some(Duration::from_secs(3)));
+        for _ in 0..100 {
+            let jitter = jitter();
+            assert!((0. ..1.).contains(&jitter));
+        }
+    }
+
+    #[test]
+    fn test_delay_honors_retry_after() {
+        let policy = policy();
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(3)), 0.),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_millis(500)), 1.),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(6)), 0.), None);
+    }
+}
@@ -265,6 +265,15 @@ pub fn snippets_dir() -> &'static PathBuf {
     SNIPPETS_DIR.get_or_init(|| config_dir().join("snippets"))
 }
 
+/// Returns the path to the translations directory.
+///
+/// This is where translations of the agent panel that aren't bundled with Zed
+/// are stored.
+pub fn translations_dir() -> &'static PathBuf {
+    static TRANSLATIONS_DIR: OnceLock<PathBuf> = OnceLock::new();
+    TRANSLATIONS_DIR.get_or_init(|| config_dir().join("translations"))
+}
+
 /// Returns the path to the contexts directory.
 ///
 /// This is where the saved contexts from the Assistant are stored.
@@ -279,6 +288,24 @@ pub fn contexts_dir() -> &'static PathBuf {
     })
 }
 
+/// Returns the path to the file tracking how much has been spent on language models.
+pub fn language_model_spend_file() -> &'static PathBuf {
+    static LANGUAGE_MODEL_SPEND_FILE: OnceLock<PathBuf> = OnceLock::new();
+    LANGUAGE_MODEL_SPEND_FILE.get_or_init(|| data_dir().join("language_model_spend.json"))
+}
+
+/// Returns the path to the directory caching the models offered by language model providers.
+pub fn language_model_catalogs_dir() -> &'static PathBuf {
+    static LANGUAGE_MODEL_CATALOGS_DIR: OnceLock<PathBuf> = OnceLock::new();
+    LANGUAGE_MODEL_CATALOGS_DIR.get_or_init(|| data_dir().join("language_model_catalogs"))
+}
+
+/// Returns the path to the file storing the snippets saved from text threads.
+pub fn snippet_library_file() -> &'static PathBuf {
+    static SNIPPET_LIBRARY_FILE: OnceLock<PathBuf> = OnceLock::new();
+    SNIPPET_LIBRARY_FILE.get_or_init(|| data_dir().join("snippet_library.json"))
+}
+
 /// Returns the path to the contexts directory.
 ///
 /// This is where the prompts for use with the Assistant are stored.
@@ -3793,6 +3793,19 @@ impl Repository {
         })
     }
 
+    /// Returns the URL of the remote with the given name, if it exists. Only
+    /// supported for local repositories.
+    pub fn remote_url(&mut self, name: String) -> oneshot::Receiver<Result<Option<String>>> {
+        self.send_job(None, move |repo, _cx| async move {
+            match repo {
+                RepositoryState::Local { backend, .. } => Ok(backend.remote_url(&name)),
+                RepositoryState::Remote { .. } => {
+                    Err(anyhow!("remote URLs of remote repositories aren't available"))
+                }
+            }
+        })
+    }
+
     pub fn branches(&mut self) -> oneshot::Receiver<Result<Vec<Branch>>> {
         let id = self.id;
         self.send_job(None, move |repo, _| async move {
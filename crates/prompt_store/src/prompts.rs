@@ -29,6 +29,8 @@ pub struct ProjectContext {
     pub os: String,
     pub arch: String,
     pub shell: String,
+    /// An outline of the project's files and the definitions in them.
+    pub repo_map: Option<String>,
 }
 
 impl ProjectContext {
@@ -44,6 +46,7 @@ impl ProjectContext {
             os: std::env::consts::OS.to_string(),
             arch: std::env::consts::ARCH.to_string(),
             shell: get_system_shell(),
+            repo_map: None,
         }
     }
 }
@@ -98,6 +101,7 @@ pub struct ContentPromptDiagnosticContext {
 pub struct ContentPromptContext {
     pub content_type: String,
     pub language_name: Option<String>,
+    pub language_preamble: Option<String>,
     pub is_insert: bool,
     pub is_truncated: bool,
     pub document_content: String,
@@ -106,6 +110,23 @@ pub struct ContentPromptContext {
     pub diagnostic_errors: Vec<ContentPromptDiagnosticContext>,
 }
 
+#[derive(Serialize)]
+pub struct LanguagePreambleContext {
+    pub language_name: String,
+    pub preamble: String,
+}
+
+#[derive(Serialize)]
+pub struct AnswerLanguageContext {
+    pub language: String,
+}
+
+#[derive(Serialize)]
+pub struct DebuggingPromptContext {
+    pub kind: String,
+    pub frames: Vec<String>,
+}
+
 #[derive(Serialize)]
 pub struct TerminalAssistantPromptContext {
     pub os: String,
@@ -114,6 +135,18 @@ pub struct TerminalAssistantPromptContext {
     pub working_directory: Option<String>,
     pub latest_output: Vec<String>,
     pub user_prompt: String,
+    pub remote: Option<TerminalAssistantRemoteContext>,
+}
+
+/// The remote host or container that a terminal is attached to.
+#[derive(Clone, Debug, Serialize)]
+pub struct TerminalAssistantRemoteContext {
+    /// A description of the kind of target, such as "SSH host" or "container".
+    pub kind: String,
+    pub host: String,
+    pub os: Option<String>,
+    pub arch: Option<String>,
+    pub shell: Option<String>,
 }
 
 pub struct PromptLoadingParams<'a> {
@@ -342,6 +375,7 @@ impl PromptBuilder {
         &self,
         user_prompt: String,
         language_name: Option<&LanguageName>,
+        language_preamble: Option<&str>,
         buffer: BufferSnapshot,
         range: Range<usize>,
     ) -> Result<String, RenderError> {
@@ -413,6 +447,7 @@ impl PromptBuilder {
         let context = ContentPromptContext {
             content_type: content_type.to_string(),
             language_name: language_name.map(|s| s.to_string()),
+            language_preamble: language_preamble.map(|s| s.to_string()),
             is_insert,
             is_truncated,
             document_content,
@@ -423,12 +458,49 @@ impl PromptBuilder {
         self.handlebars.lock().render("content_prompt", &context)
     }
 
+    /// Generates the guidelines to follow when working with files in the given
+    /// language, as configured by the user.
+    pub fn generate_language_preamble_prompt(
+        &self,
+        language_name: &str,
+        preamble: &str,
+    ) -> Result<String, RenderError> {
+        let context = LanguagePreambleContext {
+            language_name: language_name.to_string(),
+            preamble: preamble.to_string(),
+        };
+        self.handlebars.lock().render("language_preamble", &context)
+    }
+
+    /// Generates the instruction to answer in the given language.
+    pub fn generate_answer_language_prompt(&self, language: &str) -> Result<String, RenderError> {
+        let context = AnswerLanguageContext {
+            language: language.to_string(),
+        };
+        self.handlebars.lock().render("answer_language", &context)
+    }
+
+    /// Generates the instructions for debugging a stack trace the user pasted,
+    /// whose code around the given `path:line` frames is attached.
+    pub fn generate_debugging_prompt(
+        &self,
+        kind: &str,
+        frames: &[String],
+    ) -> Result<String, RenderError> {
+        let context = DebuggingPromptContext {
+            kind: kind.to_string(),
+            frames: frames.to_vec(),
+        };
+        self.handlebars.lock().render("debugging_prompt", &context)
+    }
+
     pub fn generate_terminal_assistant_prompt(
         &self,
         user_prompt: &str,
         shell: Option<&str>,
         working_directory: Option<&str>,
         latest_output: &[String],
+        remote: Option<TerminalAssistantRemoteContext>,
     ) -> Result<String, RenderError> {
         let context = TerminalAssistantPromptContext {
             os: std::env::consts::OS.to_string(),
@@ -437,6 +509,7 @@ impl PromptBuilder {
             working_directory: working_directory.map(|s| s.to_string()),
             latest_output: latest_output.to_vec(),
             user_prompt: user_prompt.to_string(),
+            remote,
         };
 
         self.handlebars
@@ -536,4 +609,33 @@ mod test {
         let result = handlebars.render("test_template", &data).unwrap();
         assert_eq!(result, "grep is disabled");
     }
+
+    #[test]
+    fn test_language_preamble_prompt_renders() {
+        let prompt = PromptBuilder::new(None)
+            .unwrap()
+            .generate_language_preamble_prompt("Rust", "Don't use `unwrap` in library code.")
+            .unwrap();
+        assert!(prompt.contains("Rust"));
+        assert!(prompt.contains("Don't use `unwrap` in library code."));
+    }
+
+    #[test]
+    fn test_answer_language_prompt_renders() {
+        let prompt = PromptBuilder::new(None)
+            .unwrap()
+            .generate_answer_language_prompt("Spanish")
+            .unwrap();
+        assert!(prompt.starts_with("Always answer in Spanish,"));
+    }
+
+    #[test]
+    fn test_debugging_prompt_renders() {
+        let prompt = PromptBuilder::new(None)
+            .unwrap()
+            .generate_debugging_prompt("Rust panic", &["app/src/main.rs:4".to_string()])
+            .unwrap();
+        assert!(prompt.contains("Rust panic"));
+        assert!(prompt.contains("- app/src/main.rs:4"));
+    }
 }
@@ -224,6 +224,7 @@ messages!(
     (RefreshCodeLens, Background),
     (GetCodeLens, Background),
     (GetCodeLensResponse, Background),
+    (RequestHostCompletion, Foreground),
     (RespondToChannelInvite, Foreground),
     (RespondToContactRequest, Foreground),
     (RestartLanguageServers, Foreground),
@@ -432,6 +433,7 @@ request_messages!(
     (OpenContext, OpenContextResponse),
     (CreateContext, CreateContextResponse),
     (SynchronizeContexts, SynchronizeContextsResponse),
+    (RequestHostCompletion, Ack),
     (LspExtSwitchSourceHeader, LspExtSwitchSourceHeaderResponse),
     (LspExtGoToParentModule, LspExtGoToParentModuleResponse),
     (LspExtCancelFlycheck, Ack),
@@ -560,6 +562,7 @@ entity_messages!(
     CreateContext,
     UpdateContext,
     SynchronizeContexts,
+    RequestHostCompletion,
     LspExtSwitchSourceHeader,
     LspExtGoToParentModule,
     LspExtCancelFlycheck,
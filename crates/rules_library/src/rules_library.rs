@@ -9,7 +9,8 @@ use gpui::{
 };
 use language::{Buffer, LanguageRegistry, language_settings::SoftWrap};
 use language_model::{
-    ConfiguredModel, LanguageModelRegistry, LanguageModelRequest, LanguageModelRequestMessage, Role,
+    ConfiguredModel, LanguageModelRegistry, LanguageModelRequest, LanguageModelRequestMessage,
+    RequestPriority, Role,
 };
 use picker::{Picker, PickerDelegate};
 use release_channel::ReleaseChannel;
@@ -948,6 +949,9 @@ impl RulesLibrary {
                                     tool_choice: None,
                                     stop: Vec::new(),
                                     temperature: None,
+                                    seed: None,
+                                    priority: RequestPriority::Interactive,
+                                    web_search: false,
                                 },
                                 cx,
                             )
@@ -77,7 +77,6 @@ fn main() {
 
             let project_index = cx
                 .update(|cx| semantic_index.project_index(project.clone(), cx))
-                .unwrap()
                 .unwrap();
 
             let (tx, rx) = oneshot::channel();
@@ -22,14 +22,79 @@ const CHUNK_SIZE_RANGE: ChunkSizeRange = ChunkSizeRange {
     max: 8192,
 };
 
+/// Chunks smaller than this would split most lines, so smaller configured
+/// sizes are raised to it.
+const MIN_CHUNK_SIZE: usize = 64;
+
+/// How files are split into chunks before they're embedded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkingConfig {
+    /// The largest size of a chunk in bytes, not counting its overlap.
+    pub size: usize,
+    /// How many bytes of the text before each chunk are included in it too,
+    /// so that text near a chunk boundary keeps some of its context.
+    pub overlap: usize,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            size: CHUNK_SIZE_RANGE.max,
+            overlap: 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chunk {
     pub range: Range<usize>,
     pub digest: [u8; 32],
 }
 
-pub fn chunk_text(text: &str, language: Option<&Arc<Language>>, path: &Path) -> Vec<Chunk> {
-    chunk_text_with_size_range(text, language, path, CHUNK_SIZE_RANGE)
+pub fn chunk_text(
+    text: &str,
+    language: Option<&Arc<Language>>,
+    path: &Path,
+    config: ChunkingConfig,
+) -> Vec<Chunk> {
+    let max = config.size.max(MIN_CHUNK_SIZE);
+    let size_range = ChunkSizeRange { min: max / 8, max };
+    let chunks = chunk_text_with_size_range(text, language, path, size_range);
+    overlap_chunks(text, chunks, config.overlap)
+}
+
+/// Extends the start of each chunk back into the previous one by up to
+/// `overlap` bytes, starting at a line boundary when there's one in reach.
+fn overlap_chunks(text: &str, mut chunks: Vec<Chunk>, overlap: usize) -> Vec<Chunk> {
+    if overlap == 0 {
+        return chunks;
+    }
+
+    let mut previous_start = 0;
+    for chunk in &mut chunks {
+        let start = chunk.range.start;
+        let mut overlap_start = start.saturating_sub(overlap).max(previous_start);
+        while !text.is_char_boundary(overlap_start) {
+            overlap_start += 1;
+        }
+        if overlap_start > 0 && text.as_bytes()[overlap_start - 1] != b'\n' {
+            let overlap_text = &text[overlap_start..start];
+            if let Some(newline) = overlap_text
+                .strip_suffix('\n')
+                .unwrap_or(overlap_text)
+                .find('\n')
+            {
+                overlap_start += newline + 1;
+            }
+        }
+
+        previous_start = start;
+        if overlap_start < start {
+            chunk.range.start = overlap_start;
+            chunk.digest = Sha256::digest(&text[chunk.range.clone()]).into();
+        }
+    }
+    chunks
 }
 
 fn chunk_text_with_size_range(
@@ -378,10 +443,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_chunk_text_with_overlap() {
+        let text = "aaaa\nbbbb\ncccc\ndddd\n";
+        let chunks = chunk_text_with_size_range(
+            text,
+            None,
+            Path::new("lib.rs"),
+            ChunkSizeRange { min: 5, max: 10 },
+        );
+        assert_chunks(text, &chunks, &["aaaa", "cccc"]);
+
+        let overlapping = overlap_chunks(text, chunks.clone(), 5);
+        assert_eq!(&text[overlapping[1].range.clone()], "bbbb\ncccc\ndddd\n");
+        assert_ne!(overlapping[1].digest, chunks[1].digest);
+
+        let overlapping = overlap_chunks(text, chunks.clone(), 7);
+        assert_eq!(&text[overlapping[1].range.clone()], "bbbb\ncccc\ndddd\n");
+
+        let overlapping = overlap_chunks(text, chunks.clone(), 3);
+        assert_eq!(&text[overlapping[1].range.clone()], "bb\ncccc\ndddd\n");
+
+        let overlapping = overlap_chunks(text, chunks, 100);
+        assert_eq!(overlapping[0].range, 0..10);
+        assert_eq!(overlapping[1].range, 0..20);
+    }
+
     #[test]
     fn test_chunk_text() {
         let text = "a\n".repeat(1000);
-        let chunks = chunk_text(&text, None, Path::new("lib.rs"));
+        let chunks = chunk_text(&text, None, Path::new("lib.rs"), ChunkingConfig::default());
         assert_eq!(
             chunks.len(),
             ((2000_f64) / (CHUNK_SIZE_RANGE.max as f64)).ceil() as usize
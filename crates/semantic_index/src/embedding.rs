@@ -7,10 +7,14 @@ pub use ollama::*;
 pub use open_ai::*;
 use sha2::{Digest, Sha256};
 
-use anyhow::Result;
+use crate::semantic_index_settings::{EmbeddingModelSettings, EmbeddingProviderName};
+use anyhow::{Context as _, Result};
 use futures::{FutureExt, future::BoxFuture};
+use gpui::AsyncApp;
 use serde::{Deserialize, Serialize};
-use std::{fmt, future};
+use std::{fmt, future, sync::Arc};
+
+const OPENAI_API_KEY_VAR: &str = "OPENAI_API_KEY";
 
 /// Trait for embedding providers. Texts in, vectors out.
 pub trait EmbeddingProvider: Sync + Send {
@@ -18,6 +22,45 @@ pub trait EmbeddingProvider: Sync + Send {
     fn batch_size(&self) -> usize;
 }
 
+/// Creates the provider for the embedding model in the settings. The OpenAI
+/// API key is read from the environment or from the one saved for OpenAI's
+/// language models.
+pub async fn embedding_provider_for_settings(
+    settings: &EmbeddingModelSettings,
+    cx: &AsyncApp,
+) -> Result<Arc<dyn EmbeddingProvider>> {
+    let client = cx.update(|cx| cx.http_client())?;
+    match settings.provider {
+        EmbeddingProviderName::OpenAi => {
+            let model = serde_json::from_value(serde_json::Value::String(settings.model.clone()))
+                .with_context(|| {
+                format!("unknown OpenAI embedding model {:?}", settings.model)
+            })?;
+            let api_url = ::open_ai::OPEN_AI_API_URL.to_string();
+            let api_key = if let Ok(api_key) = std::env::var(OPENAI_API_KEY_VAR) {
+                api_key
+            } else {
+                let (_, api_key) = cx
+                    .update(|cx| cx.read_credentials(&api_url))?
+                    .await?
+                    .context("no OpenAI API key is set")?;
+                String::from_utf8(api_key).context("invalid OpenAI API key")?
+            };
+            Ok(Arc::new(OpenAiEmbeddingProvider::new(
+                client, model, api_url, api_key,
+            )))
+        }
+        EmbeddingProviderName::Ollama => Ok(Arc::new(OllamaEmbeddingProvider::new(
+            client,
+            settings.model.clone(),
+        ))),
+        EmbeddingProviderName::LmStudio => Ok(Arc::new(LmStudioEmbeddingProvider::new(
+            client,
+            settings.model.clone(),
+        ))),
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Embedding(Vec<f32>);
 
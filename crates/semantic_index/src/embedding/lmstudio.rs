@@ -6,13 +6,9 @@ use std::sync::Arc;
 
 use crate::{Embedding, EmbeddingProvider, TextToEmbed};
 
-pub enum LmStudioEmbeddingModel {
-    NomicEmbedText,
-}
-
 pub struct LmStudioEmbeddingProvider {
     client: Arc<dyn HttpClient>,
-    model: LmStudioEmbeddingModel,
+    model: String,
 }
 
 #[derive(Serialize)]
@@ -27,20 +23,16 @@ struct LmStudioEmbeddingResponse {
 }
 
 impl LmStudioEmbeddingProvider {
-    pub fn new(client: Arc<dyn HttpClient>, model: LmStudioEmbeddingModel) -> Self {
+    pub fn new(client: Arc<dyn HttpClient>, model: String) -> Self {
         Self { client, model }
     }
 }
 
 impl EmbeddingProvider for LmStudioEmbeddingProvider {
     fn embed<'a>(&'a self, texts: &'a [TextToEmbed<'a>]) -> BoxFuture<'a, Result<Vec<Embedding>>> {
-        let model = match self.model {
-            LmStudioEmbeddingModel::NomicEmbedText => "nomic-embed-text",
-        };
-
         futures::future::try_join_all(texts.iter().map(|to_embed| {
             let request = LmStudioEmbeddingRequest {
-                model: model.to_string(),
+                model: self.model.clone(),
                 prompt: to_embed.text.to_string(),
             };
 
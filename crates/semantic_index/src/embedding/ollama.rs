@@ -6,14 +6,9 @@ use std::sync::Arc;
 
 use crate::{Embedding, EmbeddingProvider, TextToEmbed};
 
-pub enum OllamaEmbeddingModel {
-    NomicEmbedText,
-    MxbaiEmbedLarge,
-}
-
 pub struct OllamaEmbeddingProvider {
     client: Arc<dyn HttpClient>,
-    model: OllamaEmbeddingModel,
+    model: String,
 }
 
 #[derive(Serialize)]
@@ -28,22 +23,16 @@ struct OllamaEmbeddingResponse {
 }
 
 impl OllamaEmbeddingProvider {
-    pub fn new(client: Arc<dyn HttpClient>, model: OllamaEmbeddingModel) -> Self {
+    pub fn new(client: Arc<dyn HttpClient>, model: String) -> Self {
         Self { client, model }
     }
 }
 
 impl EmbeddingProvider for OllamaEmbeddingProvider {
     fn embed<'a>(&'a self, texts: &'a [TextToEmbed<'a>]) -> BoxFuture<'a, Result<Vec<Embedding>>> {
-        //
-        let model = match self.model {
-            OllamaEmbeddingModel::NomicEmbedText => "nomic-embed-text",
-            OllamaEmbeddingModel::MxbaiEmbedLarge => "mxbai-embed-large",
-        };
-
         futures::future::try_join_all(texts.iter().map(|to_embed| {
             let request = OllamaEmbeddingRequest {
-                model: model.to_string(),
+                model: self.model.clone(),
                 prompt: to_embed.text.to_string(),
             };
 
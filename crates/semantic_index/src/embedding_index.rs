@@ -1,10 +1,12 @@
 use crate::{
-    chunking::{self, Chunk},
+    chunking::{self, Chunk, ChunkingConfig},
     embedding::{Embedding, EmbeddingProvider, TextToEmbed},
     indexing::{IndexingEntryHandle, IndexingEntrySet},
+    retrieval,
+    semantic_index_settings::SemanticIndexSettings,
 };
 use anyhow::{Context as _, Result};
-use collections::Bound;
+use collections::{Bound, HashMap};
 use feature_flags::FeatureFlagAppExt;
 use fs::Fs;
 use fs::MTime;
@@ -16,9 +18,10 @@ use language::LanguageRegistry;
 use log;
 use project::{Entry, UpdatedEntriesSet, Worktree};
 use serde::{Deserialize, Serialize};
+use settings::Settings as _;
 use smol::channel;
 use std::{cmp::Ordering, future::Future, iter, path::Path, pin::pin, sync::Arc, time::Duration};
-use util::ResultExt;
+use util::{ResultExt, paths::PathMatcher};
 use worktree::Snapshot;
 
 pub struct EmbeddingIndex {
@@ -29,6 +32,9 @@ pub struct EmbeddingIndex {
     language_registry: Arc<LanguageRegistry>,
     embedding_provider: Arc<dyn EmbeddingProvider>,
     entry_ids_being_indexed: Arc<IndexingEntrySet>,
+    chunking: ChunkingConfig,
+    /// The files to index. When it has no globs, every file is indexed.
+    include: PathMatcher,
 }
 
 impl EmbeddingIndex {
@@ -40,6 +46,8 @@ impl EmbeddingIndex {
         language_registry: Arc<LanguageRegistry>,
         embedding_provider: Arc<dyn EmbeddingProvider>,
         entry_ids_being_indexed: Arc<IndexingEntrySet>,
+        chunking: ChunkingConfig,
+        include: PathMatcher,
     ) -> Self {
         Self {
             worktree,
@@ -49,6 +57,8 @@ impl EmbeddingIndex {
             language_registry,
             embedding_provider,
             entry_ids_being_indexed,
+            chunking,
+            include,
         }
     }
 
@@ -60,7 +70,7 @@ impl EmbeddingIndex {
         &self,
         cx: &App,
     ) -> impl Future<Output = Result<()>> + use<> {
-        if !cx.is_staff() {
+        if !cx.is_staff() && !SemanticIndexSettings::get_global(cx).enabled {
             return async move { Ok(()) }.boxed();
         }
 
@@ -82,7 +92,7 @@ impl EmbeddingIndex {
         updated_entries: UpdatedEntriesSet,
         cx: &App,
     ) -> impl Future<Output = Result<()>> + use<> {
-        if !cx.is_staff() {
+        if !cx.is_staff() && !SemanticIndexSettings::get_global(cx).enabled {
             return async move { Ok(()) }.boxed();
         }
 
@@ -105,6 +115,7 @@ impl EmbeddingIndex {
         let db_connection = self.db_connection.clone();
         let db = self.db;
         let entries_being_indexed = self.entry_ids_being_indexed.clone();
+        let include = self.include.clone();
         let task = cx.background_spawn(async move {
             let txn = db_connection
                 .read_txn()
@@ -117,6 +128,9 @@ impl EmbeddingIndex {
 
             let mut deletion_range: Option<(Bound<&str>, Bound<&str>)> = None;
             for entry in worktree.files(false, 0) {
+                if !is_included(&include, &entry.path) {
+                    continue;
+                }
                 log::trace!("scanning for embedding index: {:?}", &entry.path);
 
                 let entry_db_key = db_key_for_path(&entry.path);
@@ -188,6 +202,7 @@ impl EmbeddingIndex {
         let (updated_entries_tx, updated_entries_rx) = channel::bounded(512);
         let (deleted_entry_ranges_tx, deleted_entry_ranges_rx) = channel::bounded(128);
         let entries_being_indexed = self.entry_ids_being_indexed.clone();
+        let include = self.include.clone();
         let task = cx.background_spawn(async move {
             for (path, entry_id, status) in updated_entries.iter() {
                 match status {
@@ -195,7 +210,7 @@ impl EmbeddingIndex {
                     | project::PathChange::Updated
                     | project::PathChange::AddedOrUpdated => {
                         if let Some(entry) = worktree.entry_for_id(*entry_id) {
-                            if entry.is_file() {
+                            if entry.is_file() && is_included(&include, &entry.path) {
                                 let handle = entries_being_indexed.insert(entry.id);
                                 updated_entries_tx.send((entry.clone(), handle)).await?;
                             }
@@ -231,6 +246,9 @@ impl EmbeddingIndex {
     ) -> ChunkFiles {
         let language_registry = self.language_registry.clone();
         let fs = self.fs.clone();
+        let db_connection = self.db_connection.clone();
+        let db = self.db;
+        let chunking_config = self.chunking;
         let (chunked_files_tx, chunked_files_rx) = channel::bounded(2048);
         let task = cx.spawn(async move |cx| {
             cx.background_executor()
@@ -244,16 +262,22 @@ impl EmbeddingIndex {
                                         .language_for_file_path(&entry.path)
                                         .await
                                         .ok();
+                                    let previous_embeddings =
+                                        previous_embeddings(&db_connection, db, &entry.path)
+                                            .log_err()
+                                            .unwrap_or_default();
                                     let chunked_file = ChunkedFile {
                                         chunks: chunking::chunk_text(
                                             &text,
                                             language.as_ref(),
                                             &entry.path,
+                                            chunking_config,
                                         ),
                                         handle,
                                         path: entry.path,
                                         mtime: entry.mtime,
                                         text,
+                                        previous_embeddings,
                                     };
 
                                     if chunked_files_tx.send(chunked_file).await.is_err() {
@@ -289,14 +313,18 @@ impl EmbeddingIndex {
                 // Flatten out to a vec of chunks that we can subdivide into batch sized pieces
                 // Once those are done, reassemble them back into the files in which they belong
                 // If any embeddings fail for a file, the entire file is discarded
+                // Chunks whose digest is unchanged since the file was last indexed reuse their embedding
 
                 let chunks: Vec<TextToEmbed> = chunked_files
                     .iter()
                     .flat_map(|file| {
-                        file.chunks.iter().map(|chunk| TextToEmbed {
-                            text: &file.text[chunk.range.clone()],
-                            digest: chunk.digest,
-                        })
+                        file.chunks
+                            .iter()
+                            .filter(|chunk| !file.previous_embeddings.contains_key(&chunk.digest))
+                            .map(|chunk| TextToEmbed {
+                                text: &file.text[chunk.range.clone()],
+                                digest: chunk.digest,
+                            })
                     })
                     .collect::<Vec<_>>();
 
@@ -327,13 +355,19 @@ impl EmbeddingIndex {
                     };
 
                     let mut embedded_all_chunks = true;
-                    for (chunk, embedding) in
-                        chunked_file.chunks.into_iter().zip(embeddings.by_ref())
-                    {
+                    for chunk in chunked_file.chunks {
+                        let embedding = match chunked_file.previous_embeddings.get(&chunk.digest) {
+                            Some(embedding) => Some(embedding.clone()),
+                            None => embeddings.next().flatten(),
+                        };
                         if let Some(embedding) = embedding {
-                            embedded_file
-                                .chunks
-                                .push(EmbeddedChunk { chunk, embedding });
+                            let keywords =
+                                retrieval::keywords(&chunked_file.text[chunk.range.clone()]);
+                            embedded_file.chunks.push(EmbeddedChunk {
+                                chunk,
+                                embedding,
+                                keywords,
+                            });
                         } else {
                             embedded_all_chunks = false;
                         }
@@ -446,6 +480,9 @@ pub struct ChunkedFile {
     pub handle: IndexingEntryHandle,
     pub text: String,
     pub chunks: Vec<Chunk>,
+    /// The embeddings of the chunks the file had when it was last indexed,
+    /// keyed by digest, so unchanged chunks aren't embedded again.
+    pub previous_embeddings: HashMap<[u8; 32], Embedding>,
 }
 
 pub struct EmbedFiles {
@@ -464,6 +501,32 @@ pub struct EmbeddedFile {
 pub struct EmbeddedChunk {
     pub chunk: Chunk,
     pub embedding: Embedding,
+    /// The chunk's distinct keywords, sorted, for keyword search.
+    pub keywords: Vec<String>,
+}
+
+fn is_included(include: &PathMatcher, path: &Path) -> bool {
+    include.sources().is_empty() || include.is_match(path)
+}
+
+fn previous_embeddings(
+    db_connection: &heed::Env,
+    db: heed::Database<Str, SerdeBincode<EmbeddedFile>>,
+    path: &Arc<Path>,
+) -> Result<HashMap<[u8; 32], Embedding>> {
+    let txn = db_connection
+        .read_txn()
+        .context("failed to create read transaction")?;
+    let embeddings = db
+        .get(&txn, &db_key_for_path(path))?
+        .map(|file| {
+            file.chunks
+                .into_iter()
+                .map(|chunk| (chunk.chunk.digest, chunk.embedding))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(embeddings)
 }
 
 fn db_key_for_path(path: &Arc<Path>) -> String {
@@ -37,6 +37,12 @@ impl IndexingEntrySet {
     pub fn len(&self) -> usize {
         self.entry_ids.lock().len()
     }
+
+    /// Signals that the indexing status changed without an entry being
+    /// added or removed.
+    pub fn notify(&self) {
+        self.tx.send_blocking(()).ok();
+    }
 }
 
 impl Drop for IndexingEntryHandle {
@@ -1,5 +1,8 @@
 use crate::{
+    SemanticDb,
     embedding::{EmbeddingProvider, TextToEmbed},
+    retrieval::{self, KeywordMatches, MAX_QUERY_KEYWORDS},
+    semantic_index_settings::SemanticIndexSettings,
     summary_index::FileSummary,
     worktree_index::{WorktreeIndex, WorktreeIndexHandle},
 };
@@ -11,9 +14,11 @@ use gpui::{
     App, AppContext as _, Context, Entity, EntityId, EventEmitter, Subscription, Task, WeakEntity,
 };
 use language::LanguageRegistry;
+use language_model::LanguageModelRegistry;
 use log;
 use project::{Project, Worktree, WorktreeId};
 use serde::{Deserialize, Serialize};
+use settings::Settings as _;
 use smol::channel;
 use std::{
     cmp::Ordering,
@@ -22,6 +27,7 @@ use std::{
     ops::{Range, RangeInclusive},
     path::{Path, PathBuf},
     sync::Arc,
+    time::SystemTime,
 };
 use util::ResultExt;
 
@@ -51,11 +57,32 @@ pub struct WorktreeSearchResult {
     pub score: f32,
 }
 
+/// How many candidates each search contributes to [`ProjectIndex::retrieve`]
+/// per result it returns, and how many more it considers when reranking.
+const RETRIEVAL_CANDIDATES_PER_RESULT: usize = 2;
+const RERANK_CANDIDATES_PER_RESULT: usize = 4;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Status {
     Idle,
     Loading,
-    Scanning { remaining_count: NonZeroUsize },
+    Scanning {
+        remaining_count: NonZeroUsize,
+    },
+    /// An index is being built with new settings, and the current one is
+    /// still used until it's done.
+    Reindexing {
+        indexed_count: usize,
+        total_count: usize,
+    },
+}
+
+/// An index being built with new settings, which replaces the current one
+/// once it has caught up with the project's files.
+struct NextIndex {
+    settings: SemanticIndexSettings,
+    embedding_provider: Arc<dyn EmbeddingProvider>,
+    worktree_indices: HashMap<EntityId, WorktreeIndexHandle>,
 }
 
 pub struct ProjectIndex {
@@ -65,8 +92,11 @@ pub struct ProjectIndex {
     language_registry: Arc<LanguageRegistry>,
     fs: Arc<dyn Fs>,
     last_status: Status,
+    last_updated: Option<SystemTime>,
     status_tx: channel::Sender<()>,
     embedding_provider: Arc<dyn EmbeddingProvider>,
+    settings: SemanticIndexSettings,
+    next_index: Option<NextIndex>,
     _maintain_status: Task<()>,
     _subscription: Subscription,
 }
@@ -76,6 +106,7 @@ impl ProjectIndex {
         project: Entity<Project>,
         db_connection: heed::Env,
         embedding_provider: Arc<dyn EmbeddingProvider>,
+        settings: SemanticIndexSettings,
         cx: &mut Context<Self>,
     ) -> Self {
         let language_registry = project.read(cx).languages().clone();
@@ -89,7 +120,10 @@ impl ProjectIndex {
             fs,
             status_tx,
             last_status: Status::Idle,
+            last_updated: None,
             embedding_provider,
+            settings,
+            next_index: None,
             _subscription: cx.subscribe(&project, Self::handle_project_event),
             _maintain_status: cx.spawn(async move |this, cx| {
                 while status_rx.recv().await.is_ok() {
@@ -107,6 +141,12 @@ impl ProjectIndex {
         self.last_status
     }
 
+    /// When the index last finished catching up with the changes to the
+    /// project's files.
+    pub fn last_updated(&self) -> Option<SystemTime> {
+        self.last_updated
+    }
+
     pub fn project(&self) -> WeakEntity<Project> {
         self.project.clone()
     }
@@ -129,6 +169,33 @@ impl ProjectIndex {
         }
     }
 
+    /// Builds an index with new settings in the background. The current
+    /// index keeps serving searches until the new one has caught up with the
+    /// project's files, and then replaces it.
+    pub fn reindex(
+        &mut self,
+        settings: SemanticIndexSettings,
+        embedding_provider: Arc<dyn EmbeddingProvider>,
+        cx: &mut Context<Self>,
+    ) {
+        if settings.index_id() == self.settings.index_id() {
+            self.next_index = None;
+        } else if self
+            .next_index
+            .as_ref()
+            .is_some_and(|next_index| next_index.settings.index_id() == settings.index_id())
+        {
+            return;
+        } else {
+            self.next_index = Some(NextIndex {
+                settings,
+                embedding_provider,
+                worktree_indices: HashMap::default(),
+            });
+        }
+        self.update_worktree_indices(cx);
+    }
+
     fn update_worktree_indices(&mut self, cx: &mut Context<Self>) {
         let Some(project) = self.project.upgrade() else {
             return;
@@ -148,54 +215,113 @@ impl ProjectIndex {
 
         self.worktree_indices
             .retain(|worktree_id, _| worktrees.contains_key(worktree_id));
-        for (worktree_id, worktree) in worktrees {
-            self.worktree_indices.entry(worktree_id).or_insert_with(|| {
-                let worktree_index = WorktreeIndex::load(
+        for (worktree_id, worktree) in &worktrees {
+            if !self.worktree_indices.contains_key(worktree_id) {
+                let index = self.load_worktree_index(
                     worktree.clone(),
-                    self.db_connection.clone(),
-                    self.language_registry.clone(),
-                    self.fs.clone(),
-                    self.status_tx.clone(),
+                    &self.settings,
                     self.embedding_provider.clone(),
                     cx,
                 );
+                self.worktree_indices.insert(*worktree_id, index);
+            }
+        }
+
+        if let Some(mut next_index) = self.next_index.take() {
+            next_index
+                .worktree_indices
+                .retain(|worktree_id, _| worktrees.contains_key(worktree_id));
+            for (worktree_id, worktree) in &worktrees {
+                if !next_index.worktree_indices.contains_key(worktree_id) {
+                    let index = self.load_worktree_index(
+                        worktree.clone(),
+                        &next_index.settings,
+                        next_index.embedding_provider.clone(),
+                        cx,
+                    );
+                    next_index.worktree_indices.insert(*worktree_id, index);
+                }
+            }
+            self.next_index = Some(next_index);
+        }
 
-                let load_worktree = cx.spawn(async move |this, cx| {
-                    let result = match worktree_index.await {
-                        Ok(worktree_index) => {
-                            this.update(cx, |this, _| {
-                                this.worktree_indices.insert(
-                                    worktree_id,
-                                    WorktreeIndexHandle::Loaded {
-                                        index: worktree_index.clone(),
-                                    },
-                                );
-                            })?;
-                            Ok(worktree_index)
+        self.update_status(cx);
+    }
+
+    fn load_worktree_index(
+        &self,
+        worktree: Entity<Worktree>,
+        settings: &SemanticIndexSettings,
+        embedding_provider: Arc<dyn EmbeddingProvider>,
+        cx: &mut Context<Self>,
+    ) -> WorktreeIndexHandle {
+        let worktree_id = worktree.entity_id();
+        let index_id = settings.index_id();
+        let worktree_index = WorktreeIndex::load(
+            worktree,
+            self.db_connection.clone(),
+            self.language_registry.clone(),
+            self.fs.clone(),
+            self.status_tx.clone(),
+            embedding_provider,
+            settings,
+            cx,
+        );
+
+        let load_worktree = cx.spawn(async move |this, cx| {
+            let result = match worktree_index.await {
+                Ok(worktree_index) => {
+                    this.update(cx, |this, _| {
+                        if let Some(worktree_indices) = this.worktree_indices_mut(&index_id) {
+                            worktree_indices.insert(
+                                worktree_id,
+                                WorktreeIndexHandle::Loaded {
+                                    index: worktree_index.clone(),
+                                },
+                            );
                         }
-                        Err(error) => {
-                            this.update(cx, |this, _cx| {
-                                this.worktree_indices.remove(&worktree_id)
-                            })?;
-                            Err(Arc::new(error))
+                    })?;
+                    Ok(worktree_index)
+                }
+                Err(error) => {
+                    this.update(cx, |this, _cx| {
+                        if let Some(worktree_indices) = this.worktree_indices_mut(&index_id) {
+                            worktree_indices.remove(&worktree_id);
                         }
-                    };
+                    })?;
+                    Err(Arc::new(error))
+                }
+            };
 
-                    this.update(cx, |this, cx| this.update_status(cx))?;
+            this.update(cx, |this, cx| this.update_status(cx))?;
 
-                    result
-                });
+            result
+        });
 
-                WorktreeIndexHandle::Loading {
-                    index: load_worktree.shared(),
-                }
-            });
+        WorktreeIndexHandle::Loading {
+            index: load_worktree.shared(),
         }
+    }
 
-        self.update_status(cx);
+    fn worktree_indices_mut(
+        &mut self,
+        index_id: &str,
+    ) -> Option<&mut HashMap<EntityId, WorktreeIndexHandle>> {
+        if self.settings.index_id() == index_id {
+            Some(&mut self.worktree_indices)
+        } else {
+            self.next_index
+                .as_mut()
+                .filter(|next_index| next_index.settings.index_id() == index_id)
+                .map(|next_index| &mut next_index.worktree_indices)
+        }
     }
 
     fn update_status(&mut self, cx: &mut Context<Self>) {
+        if self.next_index.is_some() && !self.update_next_index(cx) {
+            return;
+        }
+
         let mut indexing_count = 0;
         let mut any_loading = false;
 
@@ -218,8 +344,75 @@ impl ProjectIndex {
         } else {
             Status::Idle
         };
+        self.set_status(status, cx);
+    }
+
+    /// Reports the progress of the index being built with new settings, and
+    /// switches to it once it's done. Returns whether it switched.
+    fn update_next_index(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(next_index) = self.next_index.as_ref() else {
+            return false;
+        };
+
+        let mut complete = true;
+        let mut indexed_count = 0;
+        let mut total_count = 0;
+        for index in next_index.worktree_indices.values() {
+            match index {
+                WorktreeIndexHandle::Loading { .. } => complete = false,
+                WorktreeIndexHandle::Loaded { index } => {
+                    let index = index.read(cx);
+                    complete &=
+                        index.initial_scan_complete() && index.entry_ids_being_indexed().len() == 0;
+                    let file_count = index.worktree().read(cx).file_count();
+                    indexed_count +=
+                        (index.path_count().log_err().unwrap_or_default() as usize).min(file_count);
+                    total_count += file_count;
+                }
+            }
+        }
+
+        if !complete {
+            self.set_status(
+                Status::Reindexing {
+                    indexed_count,
+                    total_count,
+                },
+                cx,
+            );
+            return false;
+        }
+
+        let Some(next_index) = self.next_index.take() else {
+            return false;
+        };
+        log::info!("switching to the semantic index built with new settings");
+        self.settings = next_index.settings;
+        self.embedding_provider = next_index.embedding_provider;
+        let previous_indices =
+            std::mem::replace(&mut self.worktree_indices, next_index.worktree_indices);
+        for index in previous_indices.into_values() {
+            if let WorktreeIndexHandle::Loaded { index } = index {
+                let index = index.read(cx);
+                let db_connection = index.db_connection().clone();
+                let db = *index.embedding_index().db();
+                cx.background_spawn(async move {
+                    let mut txn = db_connection.write_txn()?;
+                    db.clear(&mut txn)?;
+                    txn.commit()?;
+                    anyhow::Ok(())
+                })
+                .detach_and_log_err(cx);
+            }
+        }
+        true
+    }
 
+    fn set_status(&mut self, status: Status, cx: &mut Context<Self>) {
         if status != self.last_status {
+            if status == Status::Idle {
+                self.last_updated = Some(SystemTime::now());
+            }
             self.last_status = status;
             cx.emit(status);
         }
@@ -367,7 +560,158 @@ impl ProjectIndex {
         })
     }
 
-    #[cfg(test)]
+    /// Finds the chunks that contain the most, and the rarest, of the
+    /// query's keywords.
+    pub fn keyword_search(
+        &self,
+        query: &str,
+        limit: usize,
+        cx: &App,
+    ) -> Task<Result<Vec<SearchResult>>> {
+        let query_keywords = Arc::new(
+            retrieval::keywords(query)
+                .into_iter()
+                .take(MAX_QUERY_KEYWORDS)
+                .collect::<Vec<_>>(),
+        );
+        let worktree_scan_tasks = self
+            .worktree_indices
+            .values()
+            .map(|worktree_index| {
+                let worktree_index = worktree_index.clone();
+                let query_keywords = query_keywords.clone();
+                cx.spawn(async move |cx| {
+                    let index = match worktree_index {
+                        WorktreeIndexHandle::Loading { index } => {
+                            index.clone().await.map_err(|error| anyhow!(error))?
+                        }
+                        WorktreeIndexHandle::Loaded { index } => index.clone(),
+                    };
+
+                    index
+                        .read_with(cx, |index, cx| {
+                            let worktree_id = index.worktree().read(cx).id();
+                            let db_connection = index.db_connection().clone();
+                            let db = *index.embedding_index().db();
+                            cx.background_spawn(async move {
+                                let txn = db_connection
+                                    .read_txn()
+                                    .context("failed to create read transaction")?;
+                                let mut matches = KeywordMatches::new(query_keywords.len());
+                                let db_entries =
+                                    db.iter(&txn).context("failed to iterate database")?;
+                                for db_entry in db_entries {
+                                    let (_key, db_embedded_file) = db_entry?;
+                                    for chunk in db_embedded_file.chunks {
+                                        matches.push(
+                                            retrieval::keyword_matches(
+                                                &chunk.keywords,
+                                                &query_keywords,
+                                            ),
+                                            || {
+                                                (
+                                                    worktree_id,
+                                                    db_embedded_file.path.clone(),
+                                                    chunk.chunk.range.clone(),
+                                                )
+                                            },
+                                        );
+                                    }
+                                }
+                                anyhow::Ok(matches)
+                            })
+                        })?
+                        .await
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let project = self.project.clone();
+        cx.spawn(async move |cx| {
+            let mut matches = KeywordMatches::new(query_keywords.len());
+            for worktree_matches in futures::future::join_all(worktree_scan_tasks).await {
+                if let Some(worktree_matches) = worktree_matches.log_err() {
+                    matches.extend(worktree_matches);
+                }
+            }
+
+            project.read_with(cx, |project, cx| {
+                matches
+                    .top(limit)
+                    .into_iter()
+                    .filter_map(|((worktree_id, path, range), score)| {
+                        Some(SearchResult {
+                            worktree: project.worktree_for_id(worktree_id, cx)?,
+                            path,
+                            range,
+                            score,
+                            query_index: 0,
+                        })
+                    })
+                    .collect()
+            })
+        })
+    }
+
+    /// Finds the excerpts most relevant to the query by combining keyword
+    /// and vector search, and reranks them with the default model when
+    /// that's enabled in the settings.
+    pub fn retrieve(
+        &self,
+        query: String,
+        limit: usize,
+        cx: &App,
+    ) -> Task<Result<Vec<LoadedSearchResult>>> {
+        let rerank_model = if SemanticIndexSettings::get_global(cx).rerank {
+            LanguageModelRegistry::read_global(cx)
+                .default_model()
+                .map(|configured_model| configured_model.model)
+        } else {
+            None
+        };
+        let candidate_count = if rerank_model.is_some() {
+            limit * RERANK_CANDIDATES_PER_RESULT
+        } else {
+            limit * RETRIEVAL_CANDIDATES_PER_RESULT
+        };
+        let vector_search = self.search(vec![query.clone()], candidate_count, cx);
+        let keyword_search = self.keyword_search(&query, candidate_count, cx);
+        let fs = self.fs.clone();
+
+        cx.spawn(async move |cx| {
+            let (vector_results, keyword_results) =
+                futures::future::join(vector_search, keyword_search).await;
+            let rankings = match (vector_results, keyword_results) {
+                (Ok(vector_results), Ok(keyword_results)) => vec![vector_results, keyword_results],
+                (Ok(results), Err(error)) | (Err(error), Ok(results)) => {
+                    log::error!("semantic index search failed: {error:?}");
+                    vec![results]
+                }
+                (Err(error), Err(_)) => return Err(error),
+            };
+            let mut results = fuse_search_results(rankings);
+            results.truncate(candidate_count);
+
+            let mut results = SemanticDb::load_results(results, &fs, cx).await?;
+            if let Some(model) = rerank_model {
+                if let Some(ranking) = retrieval::rerank(model, &query, &results, cx)
+                    .await
+                    .log_err()
+                {
+                    let mut unranked = results.into_iter().map(Some).collect::<Vec<_>>();
+                    results = ranking
+                        .into_iter()
+                        .filter_map(|ix| unranked.get_mut(ix)?.take())
+                        .collect();
+                }
+            }
+            results.truncate(limit);
+            Ok(results)
+        })
+    }
+
+    /// The number of files that have been embedded across the project's
+    /// worktrees.
     pub fn path_count(&self, cx: &App) -> Result<u64> {
         let mut result = 0;
         for worktree_index in self.worktree_indices.values() {
@@ -546,3 +890,38 @@ impl ProjectIndex {
 }
 
 impl EventEmitter<Status> for ProjectIndex {}
+
+/// Combines the rankings of the same query's results from different
+/// searches into one, scoring each result by its fused rank.
+fn fuse_search_results(rankings: Vec<Vec<SearchResult>>) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    let mut ixs_by_key = HashMap::default();
+    let rankings = rankings
+        .into_iter()
+        .map(|ranking| {
+            ranking
+                .into_iter()
+                .map(|result| {
+                    let key = (
+                        result.worktree.entity_id(),
+                        result.path.clone(),
+                        result.range.start,
+                    );
+                    *ixs_by_key.entry(key).or_insert_with(|| {
+                        results.push(Some(result));
+                        results.len() - 1
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    retrieval::fuse_rankings(&rankings)
+        .into_iter()
+        .filter_map(|(ix, score)| {
+            let mut result = results.get_mut(ix)?.take()?;
+            result.score = score;
+            Some(result)
+        })
+        .collect()
+}
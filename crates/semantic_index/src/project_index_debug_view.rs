@@ -1,4 +1,4 @@
-use crate::ProjectIndex;
+use crate::{ProjectIndex, Status};
 use gpui::{
     AnyElement, App, CursorStyle, Entity, EventEmitter, FocusHandle, Focusable, IntoElement,
     ListOffset, ListState, MouseMoveEvent, Render, UniformListScrollHandle, canvas, div, list,
@@ -6,14 +6,16 @@ use gpui::{
 };
 use project::WorktreeId;
 use settings::Settings;
-use std::{path::Path, sync::Arc};
+use std::{path::Path, sync::Arc, time::SystemTime};
 use theme::ThemeSettings;
 use ui::prelude::*;
+use util::ResultExt as _;
 use workspace::item::Item;
 
 pub struct ProjectIndexDebugView {
     index: Entity<ProjectIndex>,
     rows: Vec<Row>,
+    indexed_file_count: u64,
     selected_path: Option<PathState>,
     hovered_row_ix: Option<usize>,
     focus_handle: FocusHandle,
@@ -36,6 +38,7 @@ impl ProjectIndexDebugView {
     pub fn new(index: Entity<ProjectIndex>, window: &mut Window, cx: &mut Context<Self>) -> Self {
         let mut this = Self {
             rows: Vec::new(),
+            indexed_file_count: 0,
             list_scroll_handle: UniformListScrollHandle::new(),
             selected_path: None,
             hovered_row_ix: None,
@@ -50,6 +53,12 @@ impl ProjectIndexDebugView {
     }
 
     fn update_rows(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.indexed_file_count = self
+            .index
+            .read(cx)
+            .path_count(cx)
+            .log_err()
+            .unwrap_or_default();
         let worktree_indices = self.index.read(cx).worktree_indices(cx);
         cx.spawn_in(window, async move |this, cx| {
             let mut rows = Vec::new();
@@ -188,6 +197,43 @@ impl ProjectIndexDebugView {
             .into_any_element()
     }
 
+    fn render_status(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let index = self.index.read(cx);
+        let pending = match index.status() {
+            Status::Idle => "up to date".to_string(),
+            Status::Loading => "loading".to_string(),
+            Status::Scanning { remaining_count } => format!("{remaining_count} pending"),
+            Status::Reindexing {
+                indexed_count,
+                total_count,
+            } => format!("re-indexing: {indexed_count} of {total_count} files"),
+        };
+        let last_updated = match index
+            .last_updated()
+            .and_then(|last_updated| SystemTime::now().duration_since(last_updated).ok())
+        {
+            None => "never updated".to_string(),
+            Some(elapsed) if elapsed.as_secs() < 60 => "updated just now".to_string(),
+            Some(elapsed) if elapsed.as_secs() < 60 * 60 => {
+                format!("updated {} min ago", elapsed.as_secs() / 60)
+            }
+            Some(elapsed) => format!("updated {} h ago", elapsed.as_secs() / (60 * 60)),
+        };
+
+        h_flex()
+            .px_2()
+            .py_1()
+            .gap_2()
+            .border_b_1()
+            .border_color(cx.theme().colors().border)
+            .child(Label::new(format!(
+                "{} files indexed",
+                self.indexed_file_count
+            )))
+            .child(Label::new(pending).color(Color::Muted))
+            .child(Label::new(last_updated).color(Color::Muted))
+    }
+
     fn scroll_to_chunk(&mut self, ix: usize) {
         if let Some(state) = self.selected_path.as_mut() {
             state.list_state.scroll_to(ListOffset {
@@ -269,17 +315,23 @@ impl Render for ProjectIndexDebugView {
             .text_bg(cx.theme().colors().background)
             .into_any_element();
 
-            canvas(
-                move |bounds, window, cx| {
-                    list.prepaint_as_root(bounds.origin, bounds.size.into(), window, cx);
-                    list
-                },
-                |_, mut list, window, cx| {
-                    list.paint(window, cx);
-                },
-            )
-            .size_full()
-            .into_any_element()
+            v_flex()
+                .size_full()
+                .child(self.render_status(cx))
+                .child(
+                    canvas(
+                        move |bounds, window, cx| {
+                            list.prepaint_as_root(bounds.origin, bounds.size.into(), window, cx);
+                            list
+                        },
+                        |_, mut list, window, cx| {
+                            list.paint(window, cx);
+                        },
+                    )
+                    .flex_1()
+                    .w_full(),
+                )
+                .into_any_element()
         }
     }
 }
@@ -0,0 +1,353 @@
+//! Ranks the chunks of the semantic index by keyword matches, combines that
+//! ranking with the one from vector search, and optionally has a language
+//! model rerank the result.
+
+use crate::LoadedSearchResult;
+use anyhow::Result;
+use collections::HashMap;
+use futures::StreamExt as _;
+use gpui::AsyncApp;
+use language_model::{LanguageModel, LanguageModelRequest, LanguageModelRequestMessage, Role};
+use std::{cmp::Ordering, fmt::Write as _, hash::Hash, sync::Arc};
+
+/// Keywords shorter than this match too much to be useful.
+const MIN_KEYWORD_LEN: usize = 2;
+
+/// Query keywords at least this long also match the longer keywords they're
+/// a prefix of, so that "embed" finds "embedding".
+const MIN_PREFIX_MATCH_LEN: usize = 4;
+
+/// At most this many of a query's keywords are searched for, one bit each in
+/// a chunk's match mask.
+pub(crate) const MAX_QUERY_KEYWORDS: usize = 64;
+
+/// Damps the difference between the top ranks in reciprocal rank fusion, so
+/// that a chunk ranked well by both searches beats one ranked first by one.
+const RRF_K: f32 = 60.;
+
+/// How much of each excerpt is shown to the model when reranking.
+const MAX_RERANK_EXCERPT_LEN: usize = 2000;
+
+/// Splits the text into lowercase keywords: each identifier, and the words
+/// within snake_case and camelCase identifiers. They're returned sorted and
+/// without duplicates.
+pub(crate) fn keywords(text: &str) -> Vec<String> {
+    let mut keywords = Vec::new();
+    for identifier in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        if identifier.len() < MIN_KEYWORD_LEN {
+            continue;
+        }
+        keywords.push(identifier.to_lowercase());
+        let words = identifier_words(identifier);
+        if words.len() > 1 {
+            keywords.extend(
+                words
+                    .into_iter()
+                    .filter(|word| word.len() >= MIN_KEYWORD_LEN)
+                    .map(str::to_lowercase),
+            );
+        }
+    }
+    keywords.sort_unstable();
+    keywords.dedup();
+    keywords
+}
+
+fn identifier_words(identifier: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for part in identifier.split('_') {
+        let mut word_start = 0;
+        let mut previous = None;
+        for (ix, c) in part.char_indices() {
+            if previous
+                .is_some_and(|previous: char| previous.is_lowercase() || previous.is_ascii_digit())
+                && c.is_uppercase()
+            {
+                words.push(&part[word_start..ix]);
+                word_start = ix;
+            }
+            previous = Some(c);
+        }
+        words.push(&part[word_start..]);
+    }
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+/// Which of the query's keywords the chunk's sorted keywords match, as one
+/// bit per query keyword.
+pub(crate) fn keyword_matches(chunk_keywords: &[String], query_keywords: &[String]) -> u64 {
+    let mut matches = 0;
+    for (ix, query_keyword) in query_keywords.iter().take(MAX_QUERY_KEYWORDS).enumerate() {
+        let candidate_ix =
+            chunk_keywords.partition_point(|keyword| keyword.as_str() < query_keyword.as_str());
+        let is_match = chunk_keywords.get(candidate_ix).is_some_and(|keyword| {
+            keyword == query_keyword
+                || (query_keyword.len() >= MIN_PREFIX_MATCH_LEN
+                    && keyword.starts_with(query_keyword.as_str()))
+        });
+        if is_match {
+            matches |= 1 << ix;
+        }
+    }
+    matches
+}
+
+/// The chunks that match any of a query's keywords, along with how many
+/// chunks match each keyword.
+pub(crate) struct KeywordMatches<T> {
+    matches: Vec<(T, u64)>,
+    document_frequencies: Vec<usize>,
+    chunk_count: usize,
+}
+
+impl<T> KeywordMatches<T> {
+    pub fn new(keyword_count: usize) -> Self {
+        Self {
+            matches: Vec::new(),
+            document_frequencies: vec![0; keyword_count.min(MAX_QUERY_KEYWORDS)],
+            chunk_count: 0,
+        }
+    }
+
+    pub fn push(&mut self, matches: u64, item: impl FnOnce() -> T) {
+        self.chunk_count += 1;
+        if matches == 0 {
+            return;
+        }
+        for (ix, frequency) in self.document_frequencies.iter_mut().enumerate() {
+            if matches & (1 << ix) != 0 {
+                *frequency += 1;
+            }
+        }
+        self.matches.push((item(), matches));
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        self.matches.extend(other.matches);
+        for (frequency, other_frequency) in self
+            .document_frequencies
+            .iter_mut()
+            .zip(other.document_frequencies)
+        {
+            *frequency += other_frequency;
+        }
+        self.chunk_count += other.chunk_count;
+    }
+
+    /// The best matches, scored by the inverse document frequency of the
+    /// keywords they match, so that rare keywords count for more.
+    pub fn top(self, limit: usize) -> Vec<(T, f32)> {
+        let chunk_count = self.chunk_count as f32;
+        let weights = self
+            .document_frequencies
+            .iter()
+            .map(|&frequency| {
+                let frequency = frequency as f32;
+                (1. + (chunk_count - frequency + 0.5) / (frequency + 0.5)).ln()
+            })
+            .collect::<Vec<_>>();
+        let mut scored = self
+            .matches
+            .into_iter()
+            .map(|(item, matches)| {
+                let score = weights
+                    .iter()
+                    .enumerate()
+                    .filter(|(ix, _)| matches & (1 << ix) != 0)
+                    .map(|(_, weight)| weight)
+                    .sum::<f32>();
+                (item, score)
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        scored.truncate(limit);
+        scored
+    }
+}
+
+/// Ranks the documents by how many, and how rare, of the query's keywords
+/// they contain, the same way chunks are ranked for keyword search. Documents
+/// that contain none of them are left out.
+pub fn rank_by_keywords<'a, T>(
+    query: &str,
+    documents: impl IntoIterator<Item = (T, &'a str)>,
+    limit: usize,
+) -> Vec<(T, f32)> {
+    let query_keywords = keywords(query)
+        .into_iter()
+        .take(MAX_QUERY_KEYWORDS)
+        .collect::<Vec<_>>();
+    let mut matches = KeywordMatches::new(query_keywords.len());
+    for (document, text) in documents {
+        matches.push(keyword_matches(&keywords(text), &query_keywords), || {
+            document
+        });
+    }
+    matches.top(limit)
+}
+
+/// Combines rankings with reciprocal rank fusion, where each item scores the
+/// sum of `1 / (RRF_K + rank)` over the rankings it's in. Ties keep the order
+/// in which the items first appear.
+pub(crate) fn fuse_rankings<T: Clone + Eq + Hash>(rankings: &[Vec<T>]) -> Vec<(T, f32)> {
+    let mut fused = Vec::<(T, f32)>::new();
+    let mut ixs_by_item = HashMap::default();
+    for ranking in rankings {
+        for (rank, item) in ranking.iter().enumerate() {
+            let ix = *ixs_by_item.entry(item.clone()).or_insert_with(|| {
+                fused.push((item.clone(), 0.));
+                fused.len() - 1
+            });
+            fused[ix].1 += 1. / (RRF_K + rank as f32 + 1.);
+        }
+    }
+    fused.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+    fused
+}
+
+/// Asks the model to order the results by how relevant they are to the
+/// query. Returns the indices of the results in their new order, with the
+/// ones the model left out following the ones it ranked.
+pub(crate) async fn rerank(
+    model: Arc<dyn LanguageModel>,
+    query: &str,
+    results: &[LoadedSearchResult],
+    cx: &AsyncApp,
+) -> Result<Vec<usize>> {
+    let mut prompt = format!(
+        "Order the excerpts below by how relevant they are to this query: {query}\n\n\
+        Reply with the numbers of the relevant excerpts, most relevant first, \
+        separated by commas, and nothing else.\n\n"
+    );
+    for (ix, result) in results.iter().enumerate() {
+        let mut end = result.excerpt_content.len().min(MAX_RERANK_EXCERPT_LEN);
+        while !result.excerpt_content.is_char_boundary(end) {
+            end -= 1;
+        }
+        writeln!(
+            prompt,
+            "Excerpt {} ({}):\n```\n{}\n```\n",
+            ix + 1,
+            result.full_path.display(),
+            &result.excerpt_content[..end]
+        )
+        .ok();
+    }
+
+    let request = LanguageModelRequest {
+        messages: vec![LanguageModelRequestMessage {
+            role: Role::User,
+            content: vec![prompt.into()],
+            cache: false,
+        }],
+        ..Default::default()
+    };
+    let mut stream = model.stream_completion_text(request, cx).await?.stream;
+    let mut reply = String::new();
+    while let Some(chunk) = stream.next().await {
+        reply.push_str(&chunk?);
+    }
+
+    let mut ranking = parse_ranking(&reply, results.len());
+    for ix in 0..results.len() {
+        if !ranking.contains(&ix) {
+            ranking.push(ix);
+        }
+    }
+    Ok(ranking)
+}
+
+/// The indices of the excerpts the model's reply lists by number, in order,
+/// skipping duplicates and numbers without an excerpt.
+fn parse_ranking(reply: &str, count: usize) -> Vec<usize> {
+    let mut ranking = Vec::new();
+    for number in reply
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|number| number.parse::<usize>().ok())
+    {
+        if let Some(ix) = number
+            .checked_sub(1)
+            .filter(|ix| *ix < count && !ranking.contains(ix))
+        {
+            ranking.push(ix);
+        }
+    }
+    ranking
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keywords() {
+        assert_eq!(
+            keywords("fn load_worktree(cx: &mut App) -> HttpClient2Builder { a }"),
+            [
+                "app",
+                "builder",
+                "client2",
+                "cx",
+                "fn",
+                "http",
+                "httpclient2builder",
+                "load",
+                "load_worktree",
+                "mut",
+                "worktree",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyword_search() {
+        let query = keywords("embed worktree fs");
+        let chunks = [
+            keywords("let embedding = embed(worktree)"),
+            keywords("worktree.read(cx)"),
+            keywords("fs.load(path)"),
+            keywords("embe"),
+        ];
+
+        let mut matches = KeywordMatches::new(query.len());
+        for (ix, chunk) in chunks.iter().enumerate() {
+            matches.push(keyword_matches(chunk, &query), || ix);
+        }
+        let top = matches
+            .top(3)
+            .into_iter()
+            .map(|(ix, _)| ix)
+            .collect::<Vec<_>>();
+        assert_eq!(top, [0, 2, 1]);
+    }
+
+    #[test]
+    fn test_rank_by_keywords() {
+        let documents = [
+            "How do I configure the embedding model?",
+            "Why does the worktree scan skip hidden files?",
+            "Which embedding providers are supported?",
+        ];
+        let ranked = rank_by_keywords(
+            "configure embedding model",
+            documents.iter().enumerate().map(|(ix, text)| (ix, *text)),
+            5,
+        );
+        let order = ranked.iter().map(|(ix, _)| *ix).collect::<Vec<_>>();
+        assert_eq!(order, [0, 2]);
+    }
+
+    #[test]
+    fn test_fuse_rankings() {
+        let fused = fuse_rankings(&[vec!["a", "b", "c"], vec!["c", "d", "b"]]);
+        let order = fused.iter().map(|(item, _)| *item).collect::<Vec<_>>();
+        assert_eq!(order, ["c", "b", "a", "d"]);
+    }
+
+    #[test]
+    fn test_parse_ranking() {
+        assert_eq!(parse_ranking("3, 1, 3, 7, 0, 2", 3), [2, 0, 1]);
+        assert_eq!(parse_ranking("None are relevant.", 3), Vec::<usize>::new());
+    }
+}
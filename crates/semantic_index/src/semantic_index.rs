@@ -4,6 +4,8 @@ mod embedding_index;
 mod indexing;
 mod project_index;
 mod project_index_debug_view;
+mod retrieval;
+mod semantic_index_settings;
 mod summary_backlog;
 mod summary_index;
 mod worktree_index;
@@ -14,6 +16,7 @@ use fs::Fs;
 use gpui::{App, AppContext as _, AsyncApp, BorrowAppContext, Context, Entity, Global, WeakEntity};
 use language::LineEnding;
 use project::{Project, Worktree};
+use settings::{Settings as _, SettingsStore};
 use std::{
     cmp::Ordering,
     path::{Path, PathBuf},
@@ -25,12 +28,69 @@ use workspace::Workspace;
 pub use embedding::*;
 pub use project_index::{LoadedSearchResult, ProjectIndex, SearchResult, Status};
 pub use project_index_debug_view::ProjectIndexDebugView;
+pub use retrieval::rank_by_keywords;
+pub use semantic_index_settings::{
+    EmbeddingModelSettings, EmbeddingProviderName, SemanticIndexSettings,
+};
 pub use summary_index::FileSummary;
 
+/// Registers the semantic index settings, and creates the [`SemanticDb`]
+/// once the index is enabled in them.
+pub fn init(cx: &mut App) {
+    SemanticIndexSettings::register(cx);
+    load_semantic_db_if_enabled(cx);
+    cx.observe_global::<SettingsStore>(load_semantic_db_if_enabled)
+        .detach();
+}
+
+/// Set while the [`SemanticDb`] is being created, so that it's created once.
+struct LoadingSemanticDb;
+
+impl Global for LoadingSemanticDb {}
+
+fn load_semantic_db_if_enabled(cx: &mut App) {
+    if !SemanticIndexSettings::get_global(cx).enabled
+        || cx.has_global::<SemanticDb>()
+        || cx.has_global::<LoadingSemanticDb>()
+    {
+        return;
+    }
+
+    cx.set_global(LoadingSemanticDb);
+    let embedding_model = SemanticIndexSettings::get_global(cx)
+        .embedding_model
+        .clone();
+    cx.spawn(async move |cx| {
+        let semantic_db = load_semantic_db(&embedding_model, cx).await;
+        cx.update(|cx| {
+            cx.remove_global::<LoadingSemanticDb>();
+            if let Some(semantic_db) = semantic_db.log_err() {
+                cx.set_global(semantic_db);
+            }
+        })
+    })
+    .detach_and_log_err(cx);
+}
+
+async fn load_semantic_db(
+    embedding_model: &EmbeddingModelSettings,
+    cx: &mut AsyncApp,
+) -> Result<SemanticDb> {
+    let embedding_provider = embedding_provider_for_settings(embedding_model, cx).await?;
+    SemanticDb::new(
+        paths::embeddings_dir().join("semantic-index-db.0.mdb"),
+        embedding_provider,
+        cx,
+    )
+    .await
+}
+
 pub struct SemanticDb {
     embedding_provider: Arc<dyn EmbeddingProvider>,
     db_connection: Option<heed::Env>,
     project_indices: HashMap<WeakEntity<Project>, Entity<ProjectIndex>>,
+    /// The settings the project indices are being built with.
+    settings: SemanticIndexSettings,
 }
 
 impl Global for SemanticDb {}
@@ -54,7 +114,10 @@ impl SemanticDb {
             .await
             .context("opening database connection")?;
 
-        cx.update(|cx| {
+        let settings = cx.update(|cx| {
+            SemanticIndexSettings::register(cx);
+            cx.observe_global::<SettingsStore>(Self::handle_settings_changed)
+                .detach();
             cx.observe_new(
                 |workspace: &mut Workspace, _window, cx: &mut Context<Workspace>| {
                     let project = workspace.project().clone();
@@ -69,16 +132,55 @@ impl SemanticDb {
                 },
             )
             .detach();
-        })
-        .ok();
+            SemanticIndexSettings::get_global(cx).clone()
+        })?;
 
         Ok(SemanticDb {
             db_connection: Some(db_connection),
             embedding_provider,
             project_indices: HashMap::default(),
+            settings,
         })
     }
 
+    fn handle_settings_changed(cx: &mut App) {
+        if !cx.has_global::<SemanticDb>() {
+            return;
+        }
+        let settings = SemanticIndexSettings::get_global(cx).clone();
+        let this = cx.global::<SemanticDb>();
+        if settings == this.settings {
+            return;
+        }
+        if settings.index_id() == this.settings.index_id() {
+            cx.update_global::<SemanticDb, _>(|this, _| this.settings = settings);
+            return;
+        }
+
+        let embedding_model_changed = settings.embedding_model != this.settings.embedding_model;
+        let embedding_provider = this.embedding_provider.clone();
+        cx.spawn(async move |cx| {
+            let embedding_provider = if embedding_model_changed {
+                embedding_provider_for_settings(&settings.embedding_model, cx).await?
+            } else {
+                embedding_provider
+            };
+            cx.update_global::<SemanticDb, _>(|this, cx| {
+                if *SemanticIndexSettings::get_global(cx) != settings {
+                    return;
+                }
+                this.settings = settings.clone();
+                this.embedding_provider = embedding_provider.clone();
+                for project_index in this.project_indices.values() {
+                    project_index.update(cx, |project_index, cx| {
+                        project_index.reindex(settings.clone(), embedding_provider.clone(), cx)
+                    });
+                }
+            })
+        })
+        .detach_and_log_err(cx);
+    }
+
     pub async fn load_results(
         mut results: Vec<SearchResult>,
         fs: &Arc<dyn Fs>,
@@ -207,9 +309,16 @@ impl SemanticDb {
     pub fn project_index(
         &mut self,
         project: Entity<Project>,
-        _cx: &mut App,
-    ) -> Option<Entity<ProjectIndex>> {
-        self.project_indices.get(&project.downgrade()).cloned()
+        cx: &mut App,
+    ) -> Entity<ProjectIndex> {
+        match self.project_indices.get(&project.downgrade()) {
+            Some(project_index) => project_index.clone(),
+            None => self.create_project_index(project, cx),
+        }
+    }
+
+    pub fn embedding_provider(&self) -> Arc<dyn EmbeddingProvider> {
+        self.embedding_provider.clone()
     }
 
     pub fn remaining_summaries(
@@ -234,6 +343,7 @@ impl SemanticDb {
                 project.clone(),
                 self.db_connection.clone().unwrap(),
                 self.embedding_provider.clone(),
+                self.settings.clone(),
                 cx,
             )
         });
@@ -288,6 +398,7 @@ mod tests {
             language::init(cx);
             cx.update_flags(false, vec![]);
             Project::init_settings(cx);
+            crate::init(cx);
             SettingsStore::update(cx, |store, cx| {
                 store.update_user_settings::<AllLanguageSettings>(cx, |_| {});
             });
@@ -473,6 +584,7 @@ mod tests {
                         digest: Default::default(),
                     })
                     .collect(),
+                previous_embeddings: HashMap::default(),
             })
             .unwrap();
         chunked_files_tx
@@ -488,6 +600,7 @@ mod tests {
                         digest: Default::default(),
                     })
                     .collect(),
+                previous_embeddings: HashMap::default(),
             })
             .unwrap();
         chunked_files_tx.close();
@@ -518,6 +631,66 @@ mod tests {
         );
     }
 
+    #[gpui::test]
+    async fn test_embed_files_reuses_unchanged_chunks(cx: &mut TestAppContext) {
+        cx.executor().allow_parking();
+
+        let provider = Arc::new(TestEmbeddingProvider::new(3, |text| {
+            anyhow::ensure!(
+                !text.contains('g'),
+                "cannot embed text containing a 'g' character"
+            );
+            Ok(Embedding::new(
+                ('a'..='z')
+                    .map(|char| text.chars().filter(|c| *c == char).count() as f32)
+                    .collect(),
+            ))
+        }));
+
+        let (indexing_progress_tx, _) = channel::unbounded();
+        let indexing_entries = Arc::new(IndexingEntrySet::new(indexing_progress_tx));
+
+        let previous_embedding = (provider.compute_embedding)("previous").unwrap();
+        let (chunked_files_tx, chunked_files_rx) = channel::unbounded::<ChunkedFile>();
+        chunked_files_tx
+            .send_blocking(ChunkedFile {
+                path: Path::new("test.md").into(),
+                mtime: None,
+                handle: indexing_entries.insert(ProjectEntryId::from_proto(0)),
+                text: "abcdefgh".to_string(),
+                chunks: vec![
+                    Chunk {
+                        range: 0..4,
+                        digest: [1; 32],
+                    },
+                    Chunk {
+                        range: 4..8,
+                        digest: [2; 32],
+                    },
+                ],
+                previous_embeddings: HashMap::from_iter([([2; 32], previous_embedding.clone())]),
+            })
+            .unwrap();
+        chunked_files_tx.close();
+
+        let embed_files_task =
+            cx.update(|cx| EmbeddingIndex::embed_files(provider.clone(), chunked_files_rx, cx));
+        embed_files_task.task.await.unwrap();
+
+        let (embedded_file, _) = embed_files_task.files.recv().await.unwrap();
+        assert_eq!(
+            embedded_file
+                .chunks
+                .iter()
+                .map(|embedded_chunk| embedded_chunk.embedding.clone())
+                .collect::<Vec<Embedding>>(),
+            vec![
+                (provider.compute_embedding)("abcd").unwrap(),
+                previous_embedding,
+            ],
+        );
+    }
+
     #[gpui::test]
     async fn test_load_search_results(cx: &mut TestAppContext) {
         init_test(cx);
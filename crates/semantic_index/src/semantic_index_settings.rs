@@ -0,0 +1,101 @@
+use anyhow::Result;
+use gpui::App;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::{Settings, SettingsSources};
+
+use crate::chunking::ChunkingConfig;
+
+/// Changes whenever what's stored for each file changes, so that indices
+/// stored in an older format are rebuilt.
+const INDEX_FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct SemanticIndexSettings {
+    pub enabled: bool,
+    pub rerank: bool,
+    pub chunk_size: usize,
+    pub chunk_overlap: usize,
+    pub embedding_model: EmbeddingModelSettings,
+    pub include: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EmbeddingModelSettings {
+    pub provider: EmbeddingProviderName,
+    pub model: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingProviderName {
+    OpenAi,
+    Ollama,
+    LmStudio,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, JsonSchema, Debug)]
+pub struct SemanticIndexSettingsContent {
+    /// Whether to index the project's files and enable the `/search` command.
+    ///
+    /// Default: false
+    pub enabled: Option<bool>,
+    /// Whether to ask the default language model to rerank search results
+    /// by how relevant they are to the query.
+    ///
+    /// Default: false
+    pub rerank: Option<bool>,
+    /// The largest size of a chunk of a file that's embedded, in bytes.
+    ///
+    /// Default: 8192
+    pub chunk_size: Option<usize>,
+    /// How many bytes of the text before each chunk are included in it too,
+    /// so that text near a chunk boundary keeps some of its context.
+    ///
+    /// Default: 0
+    pub chunk_overlap: Option<usize>,
+    /// The model used to embed the chunks.
+    ///
+    /// Default: {"provider": "open_ai", "model": "text-embedding-3-small"}
+    pub embedding_model: Option<EmbeddingModelSettings>,
+    /// Globs for the files to index. When empty, every file is indexed.
+    ///
+    /// Default: []
+    pub include: Option<Vec<String>>,
+}
+
+impl SemanticIndexSettings {
+    pub fn chunking(&self) -> ChunkingConfig {
+        ChunkingConfig {
+            size: self.chunk_size,
+            overlap: self.chunk_overlap,
+        }
+    }
+
+    /// Identifies the index built with these settings. Indices built with
+    /// different chunking, embedding models or included files are stored
+    /// separately, so changing those re-indexes the project.
+    pub fn index_id(&self) -> String {
+        let key = format!(
+            "{INDEX_FORMAT_VERSION}:{}:{}:{:?}:{}:{}",
+            self.chunk_size,
+            self.chunk_overlap,
+            self.embedding_model.provider,
+            self.embedding_model.model,
+            self.include.join("\0"),
+        );
+        blake3::hash(key.as_bytes()).to_hex()[..16].to_string()
+    }
+}
+
+impl Settings for SemanticIndexSettings {
+    const KEY: Option<&'static str> = Some("semantic_index");
+
+    type FileContent = SemanticIndexSettingsContent;
+
+    fn load(sources: SettingsSources<Self::FileContent>, _: &mut App) -> Result<Self> {
+        sources.json_merge()
+    }
+
+    fn import_from_vscode(_vscode: &settings::VsCodeSettings, _current: &mut Self::FileContent) {}
+}
@@ -10,7 +10,7 @@ use heed::{
 };
 use language_model::{
     LanguageModelCompletionEvent, LanguageModelId, LanguageModelRegistry, LanguageModelRequest,
-    LanguageModelRequestMessage, Role,
+    LanguageModelRequestMessage, RequestPriority, Role,
 };
 use log;
 use parking_lot::Mutex;
@@ -570,6 +570,9 @@ impl SummaryIndex {
             tool_choice: None,
             stop: Vec::new(),
             temperature: None,
+            seed: None,
+            priority: RequestPriority::Background,
+            web_search: false,
         };
 
         let code_len = code.len();
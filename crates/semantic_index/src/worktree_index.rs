@@ -1,8 +1,9 @@
 use crate::embedding::EmbeddingProvider;
 use crate::embedding_index::EmbeddingIndex;
 use crate::indexing::IndexingEntrySet;
+use crate::semantic_index_settings::SemanticIndexSettings;
 use crate::summary_index::SummaryIndex;
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use fs::Fs;
 use futures::future::Shared;
 use gpui::{App, AppContext as _, AsyncApp, Context, Entity, Subscription, Task, WeakEntity};
@@ -11,7 +12,7 @@ use log;
 use project::{UpdatedEntriesSet, Worktree};
 use smol::channel;
 use std::sync::Arc;
-use util::ResultExt;
+use util::{ResultExt, paths::PathMatcher};
 
 #[derive(Clone)]
 pub enum WorktreeIndexHandle {
@@ -29,6 +30,7 @@ pub struct WorktreeIndex {
     embedding_index: EmbeddingIndex,
     summary_index: SummaryIndex,
     entry_ids_being_indexed: Arc<IndexingEntrySet>,
+    initial_scan_complete: bool,
     _index_entries: Task<Result<()>>,
     _subscription: Subscription,
 }
@@ -41,8 +43,12 @@ impl WorktreeIndex {
         fs: Arc<dyn Fs>,
         status_tx: channel::Sender<()>,
         embedding_provider: Arc<dyn EmbeddingProvider>,
+        settings: &SemanticIndexSettings,
         cx: &mut App,
     ) -> Task<Result<Entity<Self>>> {
+        let chunking = settings.chunking();
+        let include = PathMatcher::new(&settings.include);
+        let index_id = settings.index_id();
         let worktree_for_index = worktree.clone();
         let worktree_for_summary = worktree.clone();
         let worktree_abs_path = worktree.read(cx).abs_path();
@@ -55,9 +61,11 @@ impl WorktreeIndex {
                     let entries_being_indexed = Arc::clone(&entries_being_indexed);
                     let db_connection = db_connection.clone();
                     async move {
+                        let include = include.context("invalid semantic index include globs")?;
                         let mut txn = db_connection.write_txn()?;
                         let embedding_index = {
-                            let db_name = worktree_abs_path.to_string_lossy();
+                            let db_name =
+                                format!("{}-{index_id}", worktree_abs_path.to_string_lossy());
                             let db = db_connection.create_database(&mut txn, Some(&db_name))?;
 
                             EmbeddingIndex::new(
@@ -68,6 +76,8 @@ impl WorktreeIndex {
                                 language_registry,
                                 embedding_provider,
                                 Arc::clone(&entries_being_indexed),
+                                chunking,
+                                include,
                             )
                         };
                         let summary_index = {
@@ -137,6 +147,7 @@ impl WorktreeIndex {
             summary_index,
             worktree,
             entry_ids_being_indexed,
+            initial_scan_complete: false,
             _index_entries: cx.spawn(async move |this, cx| {
                 Self::index_entries(this, updated_entries_rx, cx).await
             }),
@@ -148,6 +159,12 @@ impl WorktreeIndex {
         self.entry_ids_being_indexed.as_ref()
     }
 
+    /// Whether the files that changed while the index wasn't loaded have
+    /// been indexed, after which the index only has to keep up with changes.
+    pub fn initial_scan_complete(&self) -> bool {
+        self.initial_scan_complete
+    }
+
     pub fn worktree(&self) -> &Entity<Worktree> {
         &self.worktree
     }
@@ -176,6 +193,10 @@ impl WorktreeIndex {
             )
         })?;
         index.await.log_err();
+        this.update(cx, |this, _| {
+            this.initial_scan_complete = true;
+            this.entry_ids_being_indexed.notify();
+        })?;
 
         while let Ok(updated_entries) = updated_entries.recv().await {
             let index = this.update(cx, |this, cx| {
@@ -192,10 +213,7 @@ impl WorktreeIndex {
         Ok(())
     }
 
-    #[cfg(test)]
     pub fn path_count(&self) -> Result<u64> {
-        use anyhow::Context as _;
-
         let txn = self
             .db_connection
             .read_txn()
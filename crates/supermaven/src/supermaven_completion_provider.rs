@@ -2,7 +2,9 @@ use crate::{Supermaven, SupermavenCompletionStateId};
 use anyhow::Result;
 use futures::StreamExt as _;
 use gpui::{App, Context, Entity, EntityId, Task};
-use inline_completion::{Direction, EditPredictionProvider, InlineCompletion};
+use inline_completion::{
+    Direction, EditPredictionProvider, InlineCompletion, trim_to_end_of_line_unless_leading_newline,
+};
 use language::{Anchor, Buffer, BufferSnapshot};
 use project::Project;
 use std::{
@@ -208,25 +210,3 @@ impl EditPredictionProvider for SupermavenCompletionProvider {
         }
     }
 }
-
-fn trim_to_end_of_line_unless_leading_newline(text: &str) -> &str {
-    if has_leading_newline(text) {
-        text
-    } else if let Some(i) = text.find('\n') {
-        &text[..i]
-    } else {
-        text
-    }
-}
-
-fn has_leading_newline(text: &str) -> bool {
-    for c in text.chars() {
-        if c == '\n' {
-            return true;
-        }
-        if !c.is_whitespace() {
-            return false;
-        }
-    }
-    false
-}
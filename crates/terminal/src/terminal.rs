@@ -1656,6 +1656,11 @@ impl Terminal {
         })
     }
 
+    /// Whether the terminal runs a shell on the remote host of an SSH project.
+    pub fn is_ssh_terminal(&self) -> bool {
+        self.is_ssh_terminal
+    }
+
     pub fn working_directory(&self) -> Option<PathBuf> {
         if self.is_ssh_terminal {
             // We can't yet reliably detect the working directory of a shell on the
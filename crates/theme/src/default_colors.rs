@@ -153,6 +153,13 @@ impl ThemeColors {
             version_control_conflict_ours_marker_background: green().light().step_10().alpha(0.7),
             version_control_conflict_theirs_marker_background: blue().light().step_10().alpha(0.7),
             version_control_conflict_divider_background: Hsla::default(),
+            message_header_user_role: neutral().light().step_12(),
+            message_header_assistant_role: blue().light().step_9(),
+            message_header_system_role: yellow().light().step_9(),
+            message_header_user_background: system.transparent,
+            message_header_assistant_background: system.transparent,
+            message_header_system_background: system.transparent,
+            message_header_divider: neutral().light().step_5(),
         }
     }
 
@@ -278,6 +285,13 @@ impl ThemeColors {
             version_control_conflict_ours_marker_background: green().dark().step_10().alpha(0.7),
             version_control_conflict_theirs_marker_background: blue().dark().step_10().alpha(0.7),
             version_control_conflict_divider_background: Hsla::default(),
+            message_header_user_role: neutral().dark().step_12(),
+            message_header_assistant_role: blue().dark().step_9(),
+            message_header_system_role: yellow().dark().step_9(),
+            message_header_user_background: system.transparent,
+            message_header_assistant_background: system.transparent,
+            message_header_system_background: system.transparent,
+            message_header_divider: neutral().dark().step_5(),
         }
     }
 }
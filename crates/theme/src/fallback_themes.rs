@@ -228,6 +228,13 @@ pub(crate) fn zed_default_dark() -> Theme {
                     .step_12()
                     .alpha(0.7),
                 version_control_conflict_divider_background: Hsla::default(),
+                message_header_user_role: hsla(221. / 360., 11. / 100., 86. / 100., 1.0),
+                message_header_assistant_role: blue,
+                message_header_system_role: yellow,
+                message_header_user_background: SystemColors::default().transparent,
+                message_header_assistant_background: SystemColors::default().transparent,
+                message_header_system_background: SystemColors::default().transparent,
+                message_header_divider: hsla(228. / 360., 8. / 100., 25. / 100., 1.),
             },
             status: StatusColors {
                 conflict: yellow,
@@ -638,6 +638,34 @@ pub struct ThemeColorsContent {
     /// Background color for row highlights of the "ours"/"theirs" divider in merge conflicts.
     #[serde(rename = "version_control.conflict.divider_background")]
     pub version_control_conflict_divider_background: Option<String>,
+
+    /// The color of the role label in the header of a user message in a text thread.
+    #[serde(rename = "message_header.user.role")]
+    pub message_header_user_role: Option<String>,
+
+    /// The color of the role label in the header of an assistant message in a text thread.
+    #[serde(rename = "message_header.assistant.role")]
+    pub message_header_assistant_role: Option<String>,
+
+    /// The color of the role label in the header of a system message in a text thread.
+    #[serde(rename = "message_header.system.role")]
+    pub message_header_system_role: Option<String>,
+
+    /// The background of the header of a user message in a text thread.
+    #[serde(rename = "message_header.user.background")]
+    pub message_header_user_background: Option<String>,
+
+    /// The background of the header of an assistant message in a text thread.
+    #[serde(rename = "message_header.assistant.background")]
+    pub message_header_assistant_background: Option<String>,
+
+    /// The background of the header of a system message in a text thread.
+    #[serde(rename = "message_header.system.background")]
+    pub message_header_system_background: Option<String>,
+
+    /// The line drawn above message headers in a text thread, when dividers are enabled.
+    #[serde(rename = "message_header.divider")]
+    pub message_header_divider: Option<String>,
 }
 
 impl ThemeColorsContent {
@@ -676,12 +704,17 @@ impl ThemeColorsContent {
             .scrollbar_thumb_border
             .as_ref()
             .and_then(|color| try_parse_color(color).ok());
+        let border_variant = self
+            .border_variant
+            .as_ref()
+            .and_then(|color| try_parse_color(color).ok());
+        let text = self
+            .text
+            .as_ref()
+            .and_then(|color| try_parse_color(color).ok());
         ThemeColorsRefinement {
             border,
-            border_variant: self
-                .border_variant
-                .as_ref()
-                .and_then(|color| try_parse_color(color).ok()),
+            border_variant,
             border_focused: self
                 .border_focused
                 .as_ref()
@@ -754,10 +787,7 @@ impl ThemeColorsContent {
                 .ghost_element_disabled
                 .as_ref()
                 .and_then(|color| try_parse_color(color).ok()),
-            text: self
-                .text
-                .as_ref()
-                .and_then(|color| try_parse_color(color).ok()),
+            text,
             text_muted: self
                 .text_muted
                 .as_ref()
@@ -1138,6 +1168,40 @@ impl ThemeColorsContent {
                 .version_control_conflict_divider_background
                 .as_ref()
                 .and_then(|color| try_parse_color(color).ok()),
+            message_header_user_role: self
+                .message_header_user_role
+                .as_ref()
+                .and_then(|color| try_parse_color(color).ok())
+                // Fall back to the colors message headers used before they
+                // could be themed.
+                .or(text),
+            message_header_assistant_role: self
+                .message_header_assistant_role
+                .as_ref()
+                .and_then(|color| try_parse_color(color).ok())
+                .or(status_colors.info),
+            message_header_system_role: self
+                .message_header_system_role
+                .as_ref()
+                .and_then(|color| try_parse_color(color).ok())
+                .or(status_colors.warning),
+            message_header_user_background: self
+                .message_header_user_background
+                .as_ref()
+                .and_then(|color| try_parse_color(color).ok()),
+            message_header_assistant_background: self
+                .message_header_assistant_background
+                .as_ref()
+                .and_then(|color| try_parse_color(color).ok()),
+            message_header_system_background: self
+                .message_header_system_background
+                .as_ref()
+                .and_then(|color| try_parse_color(color).ok()),
+            message_header_divider: self
+                .message_header_divider
+                .as_ref()
+                .and_then(|color| try_parse_color(color).ok())
+                .or(border_variant),
         }
     }
 }
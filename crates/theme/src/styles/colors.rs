@@ -279,6 +279,21 @@ pub struct ThemeColors {
     pub version_control_conflict_ours_marker_background: Hsla,
     pub version_control_conflict_theirs_marker_background: Hsla,
     pub version_control_conflict_divider_background: Hsla,
+
+    /// The color of the role label in the header of a user message in a text thread.
+    pub message_header_user_role: Hsla,
+    /// The color of the role label in the header of an assistant message in a text thread.
+    pub message_header_assistant_role: Hsla,
+    /// The color of the role label in the header of a system message in a text thread.
+    pub message_header_system_role: Hsla,
+    /// The background of the header of a user message in a text thread.
+    pub message_header_user_background: Hsla,
+    /// The background of the header of an assistant message in a text thread.
+    pub message_header_assistant_background: Hsla,
+    /// The background of the header of a system message in a text thread.
+    pub message_header_system_background: Hsla,
+    /// The line drawn above message headers in a text thread, when dividers are enabled.
+    pub message_header_divider: Hsla,
 }
 
 #[derive(EnumIter, Debug, Clone, Copy, AsRefStr)]
@@ -614,4 +629,33 @@ mod tests {
         assert_eq!(colors.background, Some(gpui::rgb(0xff00ff).into()));
         assert_eq!(colors.text, Some(gpui::rgb(0xff0000).into()));
     }
+
+    #[test]
+    fn message_header_colors_fall_back_to_the_colors_they_replaced() {
+        let style: crate::ThemeStyleContent = serde_json::from_value(json!({
+            "text": "#ff0000",
+            "border.variant": "#00ff00",
+            "info": "#0000ff",
+            "message_header.system.role": "#ff00ff"
+        }))
+        .unwrap();
+
+        let mut colors = ThemeColors::dark();
+        colors.refine(&style.theme_colors_refinement());
+
+        assert_eq!(colors.message_header_user_role, gpui::rgb(0xff0000).into());
+        assert_eq!(
+            colors.message_header_assistant_role,
+            gpui::rgb(0x0000ff).into()
+        );
+        assert_eq!(
+            colors.message_header_system_role,
+            gpui::rgb(0xff00ff).into()
+        );
+        assert_eq!(colors.message_header_divider, gpui::rgb(0x00ff00).into());
+        assert_eq!(
+            colors.message_header_user_background,
+            ThemeColors::dark().message_header_user_background
+        );
+    }
 }
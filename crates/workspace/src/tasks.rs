@@ -8,7 +8,7 @@ use remote::ConnectionState;
 use task::{DebugScenario, ResolvedTask, SpawnInTerminal, TaskContext, TaskTemplate};
 use ui::Window;
 
-use crate::Workspace;
+use crate::{Event, Workspace};
 
 impl Workspace {
     pub fn schedule_task(
@@ -72,19 +72,29 @@ impl Workspace {
         }
 
         if let Some(terminal_provider) = self.terminal_provider.as_ref() {
+            let label = spawn_in_terminal.full_label.clone();
+            let command_label = spawn_in_terminal.command_label.clone();
             let task_status = terminal_provider.spawn(spawn_in_terminal, window, cx);
-            cx.background_spawn(async move {
-                match task_status.await {
-                    Some(Ok(status)) => {
-                        if status.success() {
-                            log::debug!("Task spawn succeeded");
-                        } else {
-                            log::debug!("Task spawn failed, code: {:?}", status.code());
-                        }
+            cx.spawn(async move |workspace, cx| match task_status.await {
+                Some(Ok(status)) => {
+                    if status.success() {
+                        log::debug!("Task spawn succeeded");
+                    } else {
+                        log::debug!("Task spawn failed, code: {:?}", status.code());
                     }
-                    Some(Err(e)) => log::error!("Task spawn failed: {e}"),
-                    None => log::debug!("Task spawn got cancelled"),
+                    workspace
+                        .update(cx, |_, cx| {
+                            cx.emit(Event::TaskFinished {
+                                label,
+                                command_label,
+                                success: status.success(),
+                                exit_code: status.code(),
+                            });
+                        })
+                        .ok();
                 }
+                Some(Err(e)) => log::error!("Task spawn failed: {e}"),
+                None => log::debug!("Task spawn got cancelled"),
             })
             .detach();
         }
@@ -886,6 +886,12 @@ pub enum Event {
     ZoomChanged,
     ModalOpened,
     ClearActivityIndicator,
+    TaskFinished {
+        label: String,
+        command_label: String,
+        success: bool,
+        exit_code: Option<i32>,
+    },
 }
 
 #[derive(Debug)]